@@ -6,7 +6,6 @@ use std::io::Write;
 use hannoy::Reader;
 use hannoy::{distances::Cosine, Database, Writer};
 use heed::{Env, EnvOpenOptions, RwTxn};
-use hnsw_rs;
 use hnsw_rs::hnsw::Hnsw;
 use hnsw_rs::prelude::DistCosine;
 use rand::thread_rng;
@@ -24,7 +23,6 @@ fn gen_vecs<const DIM: usize>(size: usize) -> Vec<[f32; DIM]> {
     let mut rng = rng();
 
     (0..size)
-        .into_iter()
         .map(|_| {
             let mut arr = [0.0; DIM];
             rng.fill(&mut arr);
@@ -46,7 +44,7 @@ fn setup_lmdb() -> Env {
 
 fn create_lmdb_and_fill_with_vecs<'a, const DIM: usize>(
     env: &'a Env,
-    vecs: &'a Vec<[f32; DIM]>,
+    vecs: &'a [[f32; DIM]],
 ) -> hannoy::Result<(Writer<Cosine>, RwTxn<'a>, Database<Cosine>)> {
     let mut wtxn = env.write_txn().unwrap();
 
@@ -0,0 +1,81 @@
+//! Reads the items of the index files written by [annoy]'s `AnnoyIndex::save`.
+//!
+//! An annoy file is an array of nodes of the same size: the items come first, at the offset of
+//! their id, then the nodes of the trees and the copies of their roots. A node starts with the
+//! number of items under it, `1` for an item and `0` for an id that was never added, followed
+//! by a header that depends on the metric and the vector itself. The file doesn't record its
+//! dimensions nor its metric, they are the ones the index was created with.
+//!
+//! [annoy]: https://github.com/spotify/annoy
+use std::path::Path;
+
+use anyhow::{ensure, Context};
+use clap::ValueEnum;
+use hannoy::{Distance, Writer};
+use heed::RwTxn;
+
+/// The metric an annoy index was created with, which decides the layout of its nodes.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum AnnoyMetric {
+    Angular,
+    Euclidean,
+    Manhattan,
+    Dot,
+}
+
+impl AnnoyMetric {
+    /// The offset of the vector in a node: the number of descendants and the two children, plus
+    /// the offset of the split plane of the Minkowski metrics or the dot factor.
+    fn vector_offset(self) -> usize {
+        match self {
+            AnnoyMetric::Angular => 12,
+            AnnoyMetric::Euclidean | AnnoyMetric::Manhattan | AnnoyMetric::Dot => 16,
+        }
+    }
+}
+
+/// Adds the items of the annoy file at `path` to the writer, under their annoy id, and returns
+/// how many there were.
+pub fn import<D: Distance>(
+    writer: &Writer<D>,
+    wtxn: &mut RwTxn,
+    path: &Path,
+    metric: AnnoyMetric,
+) -> anyhow::Result<u64> {
+    let bytes = std::fs::read(path).with_context(|| format!("while reading {}", path.display()))?;
+    let dimensions = writer.dimensions();
+    let offset = metric.vector_offset();
+    let node_size = offset + dimensions * size_of::<f32>();
+    ensure!(
+        !bytes.is_empty() && bytes.len() % node_size == 0,
+        "{} is not an annoy index of {dimensions} dimensions",
+        path.display()
+    );
+
+    let descendants = |node: &[u8]| i32::from_le_bytes(node[..4].try_into().unwrap());
+    let nodes: Vec<&[u8]> = bytes.chunks_exact(node_size).collect();
+    // the last node is a root, it has every item under it
+    let n_items = descendants(nodes[nodes.len() - 1]).max(0) as u64;
+
+    // the items come before the nodes of the trees, which all have several descendants but
+    // the root of an index of a single item
+    let mut imported = 0;
+    let mut vector = Vec::with_capacity(dimensions);
+    for (id, node) in nodes.iter().enumerate() {
+        if imported == n_items {
+            break;
+        }
+        if descendants(node) != 1 {
+            continue;
+        }
+        vector.clear();
+        vector.extend(
+            node[offset..]
+                .chunks_exact(size_of::<f32>())
+                .map(|x| f32::from_le_bytes(x.try_into().unwrap())),
+        );
+        writer.add_item(wtxn, id as u32, &vector)?;
+        imported += 1;
+    }
+    Ok(imported)
+}
@@ -0,0 +1,175 @@
+//! An evaluation runner reporting recall and throughput on [ann-benchmarks] datasets.
+//!
+//! Every combination of the parameter grid is built into a fresh temporary environment, then
+//! queried with every `ef_search` of the grid. The results are printed as CSV so they can be
+//! plotted next to the hnswlib or FAISS curves of the same dataset.
+//!
+//! [ann-benchmarks]: https://github.com/erikbern/ann-benchmarks
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{ensure, Context};
+use clap::Args;
+use hannoy::hdf5::Hdf5File;
+use hannoy::{npy, Database, Distance, Reader, Writer};
+use heed::EnvOpenOptions;
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// An ann-benchmarks HDF5 file with the `train`, `test` and `neighbors` datasets.
+    #[arg(long, conflicts_with_all = ["train", "test", "neighbors"])]
+    hdf5: Option<PathBuf>,
+    /// A float32 `.npy` matrix of the vectors to index.
+    #[arg(long, required_unless_present = "hdf5")]
+    train: Option<PathBuf>,
+    /// A float32 `.npy` matrix of the query vectors.
+    #[arg(long, required_unless_present = "hdf5")]
+    test: Option<PathBuf>,
+    /// An integer `.npy` matrix of the ids of the true nearest neighbours of each query.
+    #[arg(long, required_unless_present = "hdf5")]
+    neighbors: Option<PathBuf>,
+    /// The comma separated numbers of links per node to build with.
+    #[arg(short, value_delimiter = ',', default_value = "16")]
+    m: Vec<usize>,
+    /// The comma separated sizes of the build queue.
+    #[arg(long, value_delimiter = ',', default_value = "100")]
+    ef_construction: Vec<usize>,
+    /// The comma separated sizes of the search queue.
+    #[arg(long, value_delimiter = ',', default_value = "10,20,40,80,120,200,400,800")]
+    ef_search: Vec<usize>,
+    /// The number of neighbours to search for each query.
+    #[arg(short, default_value_t = 10)]
+    k: usize,
+    /// The seed of the random generator used to pick the layers of the nodes.
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+}
+
+/// The vectors and the ground truth of a benchmark, with one row per vector.
+pub struct Dataset {
+    dimensions: usize,
+    train: Vec<f32>,
+    test: Vec<f32>,
+    /// The ids of the nearest train vectors of each query, ordered by distance.
+    neighbors: Vec<u32>,
+    n_neighbors: usize,
+}
+
+impl Dataset {
+    pub fn open(args: &BenchArgs) -> anyhow::Result<Dataset> {
+        match (&args.hdf5, &args.train, &args.test, &args.neighbors) {
+            (Some(path), ..) => Dataset::from_hdf5(path),
+            (None, Some(train), Some(test), Some(neighbors)) => {
+                Dataset::from_npy(train, test, neighbors)
+            }
+            _ => unreachable!("enforced by clap"),
+        }
+    }
+
+    fn from_npy(train: &Path, test: &Path, neighbors: &Path) -> anyhow::Result<Dataset> {
+        let open = |path: &Path| {
+            File::open(path)
+                .map(BufReader::new)
+                .with_context(|| format!("while opening {}", path.display()))
+        };
+        let (train, dimensions) = npy::read_f32_matrix(&mut open(train)?)?;
+        let (test, test_dimensions) = npy::read_f32_matrix(&mut open(test)?)?;
+        let (neighbors, n_neighbors) = npy::read_id_matrix(&mut open(neighbors)?)?;
+        Dataset::new(dimensions, train, test_dimensions, test, neighbors, n_neighbors)
+    }
+
+    fn from_hdf5(path: &Path) -> anyhow::Result<Dataset> {
+        let mut file =
+            Hdf5File::open(path).with_context(|| format!("while opening {}", path.display()))?;
+        let (train, dimensions) = file.read_f32_matrix("train")?;
+        let (test, test_dimensions) = file.read_f32_matrix("test")?;
+        let (neighbors, n_neighbors) = file.read_id_matrix("neighbors")?;
+        Dataset::new(dimensions, train, test_dimensions, test, neighbors, n_neighbors)
+    }
+
+    fn new(
+        dimensions: usize,
+        train: Vec<f32>,
+        test_dimensions: usize,
+        test: Vec<f32>,
+        neighbors: Vec<u32>,
+        n_neighbors: usize,
+    ) -> anyhow::Result<Dataset> {
+        ensure!(dimensions != 0 && n_neighbors != 0, "the dataset contains empty matrices");
+        ensure!(
+            dimensions == test_dimensions,
+            "the train vectors are of dimension {dimensions} but the queries of {test_dimensions}"
+        );
+        let n_queries = test.len() / dimensions;
+        ensure!(
+            neighbors.len() == n_queries * n_neighbors,
+            "the ground truth has {} rows for {n_queries} queries",
+            neighbors.len() / n_neighbors.max(1)
+        );
+        Ok(Dataset { dimensions, train, test, neighbors, n_neighbors })
+    }
+}
+
+/// Builds an index for every `m` and `ef_construction` of the grid and writes the recall and
+/// the queries per second reached with every `ef_search` as CSV.
+pub fn run<D: Distance>(
+    dataset: &Dataset,
+    args: &BenchArgs,
+    map_size: usize,
+    mut output: impl Write,
+) -> anyhow::Result<()> {
+    let BenchArgs { k, seed, .. } = *args;
+    ensure!(
+        k <= dataset.n_neighbors,
+        "the ground truth only has {} neighbours per query, cannot evaluate -k {k}",
+        dataset.n_neighbors
+    );
+    let Dataset { dimensions, train, test, neighbors, n_neighbors } = dataset;
+
+    writeln!(output, "m,ef_construction,build_secs,ef_search,recall,qps")?;
+    for &m in &args.m {
+        for &ef_construction in &args.ef_construction {
+            let dir = tempfile::tempdir()?;
+            let env = unsafe { EnvOpenOptions::new().map_size(map_size).open(dir.path()) }?;
+
+            let mut wtxn = env.write_txn()?;
+            let database: Database<D> = env.create_database(&mut wtxn, None)?;
+            let writer = Writer::new(database, 0, *dimensions);
+            let started = Instant::now();
+            writer.add_items(&mut wtxn, (0..).zip(train.chunks_exact(*dimensions)))?;
+            crate::build_index(&writer, &mut wtxn, m, ef_construction, seed)?;
+            wtxn.commit()?;
+            let build_secs = started.elapsed().as_secs_f64();
+
+            let rtxn = env.read_txn()?;
+            let reader = Reader::open(&rtxn, 0, database)?;
+            for &ef_search in &args.ef_search {
+                let mut query = reader.nns(k);
+                query.ef_search(ef_search.max(k));
+
+                let mut hits = 0;
+                let started = Instant::now();
+                for (vector, truth) in
+                    test.chunks_exact(*dimensions).zip(neighbors.chunks_exact(*n_neighbors))
+                {
+                    let found = query.by_vector(&rtxn, vector)?.into_nns();
+                    let truth = &truth[..k];
+                    hits += found.iter().filter(|(id, _)| truth.contains(id)).count();
+                }
+                let elapsed = started.elapsed().as_secs_f64();
+
+                let n_queries = test.len() / dimensions;
+                let recall = hits as f64 / (n_queries * k) as f64;
+                let qps = n_queries as f64 / elapsed;
+                writeln!(
+                    output,
+                    "{m},{ef_construction},{build_secs:.3},{ef_search},{recall:.4},{qps:.1}"
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,349 @@
+//! A command-line tool to build, query and inspect hannoy indexes.
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use hannoy::distances::{
+    BinaryQuantizedCosine, BinaryQuantizedEuclidean, BinaryQuantizedManhattan, BrayCurtis,
+    Canberra, Chebyshev, Cosine, EarthMovers, Euclidean, Hamming, Hellinger, Manhattan, Poincare,
+    U8Euclidean, U8Manhattan,
+};
+use hannoy::{Database, Distance, Reader, Writer};
+use heed::{Env, EnvOpenOptions, RoTxn, RwTxn};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+mod annoy;
+mod bench;
+
+#[derive(Parser)]
+#[command(name = "hannoy", version, about)]
+struct Cli {
+    /// The directory of the LMDB environment.
+    #[arg(long, global = true, default_value = ".")]
+    db: PathBuf,
+
+    /// The index to operate on.
+    #[arg(long, global = true, default_value_t = 0)]
+    index: u16,
+
+    /// The distance of the index.
+    #[arg(long, global = true, value_enum, default_value_t = DistanceKind::Cosine)]
+    distance: DistanceKind,
+
+    /// The maximum size of the LMDB environment in MiB.
+    #[arg(long, global = true, default_value_t = 10 * 1024)]
+    map_size: usize,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DistanceKind {
+    Cosine,
+    Euclidean,
+    Manhattan,
+    Hamming,
+    Chebyshev,
+    Canberra,
+    BrayCurtis,
+    Hellinger,
+    U8Euclidean,
+    U8Manhattan,
+    EarthMovers,
+    Poincare,
+    BqCosine,
+    BqEuclidean,
+    BqManhattan,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Add the vectors of a `.npy` or Parquet file to the index and build it.
+    Build {
+        /// A float32 matrix with one vector per row.
+        #[arg(long, conflicts_with = "parquet", required_unless_present = "parquet")]
+        npy: Option<PathBuf>,
+        /// An optional one dimensional array with the id of each row of the `--npy` matrix.
+        #[arg(long, requires = "npy")]
+        ids: Option<PathBuf>,
+        /// A Parquet file with an id and a vector column.
+        #[arg(long)]
+        parquet: Option<PathBuf>,
+        /// The UInt32 id column of the Parquet file.
+        #[arg(long, default_value = "id")]
+        id_column: String,
+        /// The list of Float32 column of the Parquet file.
+        #[arg(long, default_value = "embedding")]
+        vector_column: String,
+        #[command(flatten)]
+        build: BuildArgs,
+    },
+    /// Search the nearest neighbours of a vector or of an item.
+    Query {
+        /// A comma separated vector, e.g. `0.1,0.4,-0.3`.
+        #[arg(
+            long,
+            conflicts_with = "item",
+            required_unless_present = "item",
+            allow_hyphen_values = true
+        )]
+        vector: Option<String>,
+        /// The id of an item of the index.
+        #[arg(long)]
+        item: Option<u32>,
+        /// The number of neighbours to return.
+        #[arg(short, default_value_t = 10)]
+        k: usize,
+        /// The size of the search queue.
+        #[arg(long, default_value_t = 100)]
+        ef_search: usize,
+    },
+    /// Print the statistics of the index.
+    Stats,
+    /// Check that the graph is well formed.
+    Verify,
+    /// Write every item of the index as JSON Lines.
+    Dump {
+        /// The file to write to, the standard output by default.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Add the items of a JSON Lines file to the index and build it.
+    Restore {
+        /// The file to read from.
+        #[arg(long)]
+        input: PathBuf,
+        #[command(flatten)]
+        build: BuildArgs,
+    },
+    /// Add the items of an annoy index file to the index and build it.
+    ConvertFromAnnoy {
+        /// The `.ann` file written by `AnnoyIndex::save`.
+        #[arg(long)]
+        input: PathBuf,
+        /// The metric the annoy index was created with.
+        #[arg(long, value_enum)]
+        metric: annoy::AnnoyMetric,
+        #[command(flatten)]
+        build: BuildArgs,
+    },
+    /// Convert an arroy index stored in the same database to hannoy and build it.
+    ConvertFromArroy {
+        #[command(flatten)]
+        build: BuildArgs,
+    },
+    /// Measure the recall and queries per second of an ann-benchmarks dataset over a grid of
+    /// parameters, the indexes are built in a temporary environment and `--db` is ignored.
+    Bench {
+        #[command(flatten)]
+        bench: bench::BenchArgs,
+    },
+}
+
+#[derive(Args)]
+struct BuildArgs {
+    /// The dimensions of the vectors.
+    #[arg(long)]
+    dimensions: usize,
+    /// The number of links per node, the first layer has twice as many.
+    #[arg(short, default_value_t = 16)]
+    m: usize,
+    /// The size of the queue used to find the neighbours of a node.
+    #[arg(long, default_value_t = 100)]
+    ef_construction: usize,
+    /// The seed of the random generator used to pick the layers of the nodes.
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    if let Command::Bench { bench } = &cli.command {
+        let dataset = bench::Dataset::open(bench)?;
+        let map_size = cli.map_size * 1024 * 1024;
+        let stdout = io::stdout().lock();
+        return match cli.distance {
+            DistanceKind::Cosine => bench::run::<Cosine>(&dataset, bench, map_size, stdout),
+            DistanceKind::Euclidean => bench::run::<Euclidean>(&dataset, bench, map_size, stdout),
+            DistanceKind::Manhattan => bench::run::<Manhattan>(&dataset, bench, map_size, stdout),
+            DistanceKind::Hamming => bench::run::<Hamming>(&dataset, bench, map_size, stdout),
+            DistanceKind::Chebyshev => bench::run::<Chebyshev>(&dataset, bench, map_size, stdout),
+            DistanceKind::Canberra => bench::run::<Canberra>(&dataset, bench, map_size, stdout),
+            DistanceKind::BrayCurtis => bench::run::<BrayCurtis>(&dataset, bench, map_size, stdout),
+            DistanceKind::Hellinger => bench::run::<Hellinger>(&dataset, bench, map_size, stdout),
+            DistanceKind::U8Euclidean => {
+                bench::run::<U8Euclidean>(&dataset, bench, map_size, stdout)
+            }
+            DistanceKind::U8Manhattan => {
+                bench::run::<U8Manhattan>(&dataset, bench, map_size, stdout)
+            }
+            DistanceKind::EarthMovers => {
+                bench::run::<EarthMovers>(&dataset, bench, map_size, stdout)
+            }
+            DistanceKind::Poincare => bench::run::<Poincare>(&dataset, bench, map_size, stdout),
+            DistanceKind::BqCosine => {
+                bench::run::<BinaryQuantizedCosine>(&dataset, bench, map_size, stdout)
+            }
+            DistanceKind::BqEuclidean => {
+                bench::run::<BinaryQuantizedEuclidean>(&dataset, bench, map_size, stdout)
+            }
+            DistanceKind::BqManhattan => {
+                bench::run::<BinaryQuantizedManhattan>(&dataset, bench, map_size, stdout)
+            }
+        };
+    }
+
+    std::fs::create_dir_all(&cli.db)?;
+    let env = unsafe { EnvOpenOptions::new().map_size(cli.map_size * 1024 * 1024).open(&cli.db) }
+        .with_context(|| format!("while opening {}", cli.db.display()))?;
+
+    match cli.distance {
+        DistanceKind::Cosine => run::<Cosine>(&env, cli),
+        DistanceKind::Euclidean => run::<Euclidean>(&env, cli),
+        DistanceKind::Manhattan => run::<Manhattan>(&env, cli),
+        DistanceKind::Hamming => run::<Hamming>(&env, cli),
+        DistanceKind::Chebyshev => run::<Chebyshev>(&env, cli),
+        DistanceKind::Canberra => run::<Canberra>(&env, cli),
+        DistanceKind::BrayCurtis => run::<BrayCurtis>(&env, cli),
+        DistanceKind::Hellinger => run::<Hellinger>(&env, cli),
+        DistanceKind::U8Euclidean => run::<U8Euclidean>(&env, cli),
+        DistanceKind::U8Manhattan => run::<U8Manhattan>(&env, cli),
+        DistanceKind::EarthMovers => run::<EarthMovers>(&env, cli),
+        DistanceKind::Poincare => run::<Poincare>(&env, cli),
+        DistanceKind::BqCosine => run::<BinaryQuantizedCosine>(&env, cli),
+        DistanceKind::BqEuclidean => run::<BinaryQuantizedEuclidean>(&env, cli),
+        DistanceKind::BqManhattan => run::<BinaryQuantizedManhattan>(&env, cli),
+    }
+}
+
+fn run<D: Distance>(env: &Env, cli: Cli) -> anyhow::Result<()> {
+    let Cli { index, command, .. } = cli;
+    match command {
+        Command::Build { npy, ids, parquet, id_column, vector_column, build } => {
+            write::<D>(env, index, &build, |writer, wtxn| {
+                let rows = match (npy, parquet) {
+                    (Some(npy), _) => writer.import_npy(wtxn, npy, ids)?,
+                    (None, Some(parquet)) => {
+                        writer.import_parquet(wtxn, parquet, &id_column, &vector_column)?
+                    }
+                    (None, None) => unreachable!("enforced by clap"),
+                };
+                eprintln!("imported {rows} vectors");
+                Ok(())
+            })
+        }
+        Command::Restore { input, build } => write::<D>(env, index, &build, |writer, wtxn| {
+            let rows = writer.import_jsonl(wtxn, BufReader::new(File::open(input)?))?;
+            eprintln!("imported {rows} items");
+            Ok(())
+        }),
+        Command::ConvertFromAnnoy { input, metric, build } => {
+            write::<D>(env, index, &build, |writer, wtxn| {
+                let items = annoy::import(writer, wtxn, &input, metric)?;
+                eprintln!("imported {items} items");
+                Ok(())
+            })
+        }
+        Command::ConvertFromArroy { build } => write::<D>(env, index, &build, |writer, wtxn| {
+            let mut rng = StdRng::seed_from_u64(build.seed);
+            writer.builder(&mut rng).prepare_arroy_conversion(wtxn)?;
+            Ok(())
+        }),
+        Command::Query { vector, item, k, ef_search } => read::<D>(env, index, |reader, rtxn| {
+            let mut query = reader.nns(k);
+            query.ef_search(ef_search);
+            let found = match (vector, item) {
+                (Some(vector), _) => {
+                    let vector = vector
+                        .split(',')
+                        .map(|x| x.trim().parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .context("while parsing the query vector")?;
+                    query.by_vector(rtxn, &vector)?
+                }
+                (None, Some(item)) => match query.by_item(rtxn, item)? {
+                    Some(found) => found,
+                    None => bail!("item {item} does not exist"),
+                },
+                (None, None) => unreachable!("enforced by clap"),
+            };
+
+            let mut stdout = io::stdout().lock();
+            for (id, distance) in found.into_nns() {
+                writeln!(stdout, "{id}\t{distance}")?;
+            }
+            Ok(())
+        }),
+        Command::Stats => read::<D>(env, index, |reader, rtxn| {
+            println!("distance: {}", D::name());
+            println!("version: {}", reader.version());
+            println!("dimensions: {}", reader.dimensions());
+            println!("items: {}", reader.n_items());
+            println!("entry points: {}", reader.n_entrypoints());
+            println!("database entries: {}", reader.n_nodes(rtxn)?.map_or(0, |n| n.get()));
+            Ok(())
+        }),
+        Command::Verify => read::<D>(env, index, |reader, rtxn| {
+            reader.assert_validity(rtxn)?;
+            println!("index {index} is valid");
+            Ok(())
+        }),
+        Command::Bench { .. } => unreachable!("handled without opening the database"),
+        Command::Dump { output } => read::<D>(env, index, |reader, rtxn| {
+            let count = match output {
+                Some(path) => reader.export_jsonl(rtxn, BufWriter::new(File::create(path)?))?,
+                None => reader.export_jsonl(rtxn, BufWriter::new(io::stdout().lock()))?,
+            };
+            eprintln!("dumped {count} items");
+            Ok(())
+        }),
+    }
+}
+
+fn read<D: Distance>(
+    env: &Env,
+    index: u16,
+    f: impl FnOnce(&Reader<D>, &RoTxn) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let rtxn = env.read_txn()?;
+    let database: Database<D> =
+        env.open_database(&rtxn, None)?.context("the environment contains no database")?;
+    let reader = Reader::open(&rtxn, index, database)?;
+    f(&reader, &rtxn)
+}
+
+fn write<D: Distance>(
+    env: &Env,
+    index: u16,
+    build: &BuildArgs,
+    f: impl FnOnce(&Writer<D>, &mut RwTxn) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let BuildArgs { dimensions, m, ef_construction, seed } = *build;
+
+    let mut wtxn = env.write_txn()?;
+    let database: Database<D> = env.create_database(&mut wtxn, None)?;
+    let writer = Writer::new(database, index, dimensions);
+    f(&writer, &mut wtxn)?;
+    build_index(&writer, &mut wtxn, m, ef_construction, seed)?;
+    wtxn.commit()?;
+    Ok(())
+}
+
+/// Builds the index with `m` links per node, one of the [`hannoy::SUPPORTED_LINKS`].
+fn build_index<D: Distance>(
+    writer: &Writer<D>,
+    wtxn: &mut RwTxn,
+    m: usize,
+    ef_construction: usize,
+    seed: u64,
+) -> anyhow::Result<()> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut builder = writer.builder(&mut rng);
+    builder.ef_construction(ef_construction);
+    builder.build_with_links(wtxn, m)?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use std::path::Path;
+use std::process::Command;
+
+fn hannoy(db: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_hannoy"))
+        .arg("--db")
+        .arg(db)
+        .args(["--distance", "euclidean"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn restore_query_inspect_and_dump() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("db");
+    let input = dir.path().join("items.jsonl");
+    let items: String =
+        (0..20).map(|i| format!("{{\"id\": {i}, \"vector\": [{i}.0, 0.0]}}\n")).collect();
+    std::fs::write(&input, &items).unwrap();
+
+    hannoy(&db, &["restore", "--input", input.to_str().unwrap(), "--dimensions", "2", "-m", "4"]);
+
+    let found = hannoy(&db, &["query", "--vector", "3.1,0.0", "-k", "2"]);
+    let ids: Vec<&str> = found.lines().map(|line| line.split('\t').next().unwrap()).collect();
+    assert_eq!(ids, ["3", "4"]);
+    let found = hannoy(&db, &["query", "--item", "10", "-k", "2"]);
+    assert_eq!(found.lines().count(), 2);
+
+    let stats = hannoy(&db, &["stats"]);
+    assert!(stats.contains("dimensions: 2\nitems: 20\n"), "{stats}");
+    assert_eq!(hannoy(&db, &["verify"]), "index 0 is valid\n");
+
+    let dump = hannoy(&db, &["dump"]);
+    assert_eq!(dump.lines().count(), 20);
+    assert!(dump.starts_with("{\"id\":0,\"vector\":[0.0,0.0]}\n"));
+}
+
+/// Writes a C-ordered array the way `numpy.save` does.
+fn write_npy(path: &Path, descr: &str, shape: [usize; 2], data: &[u8]) {
+    let mut header = format!(
+        "{{'descr': '{descr}', 'fortran_order': False, 'shape': ({}, {}), }}",
+        shape[0], shape[1]
+    );
+    while (header.len() + 11) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(data);
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn bench_reports_recall_over_the_grid() {
+    let dir = tempfile::tempdir().unwrap();
+    let train: Vec<[f32; 2]> = (0..400).map(|i| [(i % 20) as f32, (i / 20) as f32]).collect();
+    let test: Vec<[f32; 2]> = (0..10).map(|i| [i as f32 + 0.3, 19.0 - i as f32 - 0.2]).collect();
+    let neighbors: Vec<i32> = test
+        .iter()
+        .flat_map(|[x, y]| {
+            let mut ids: Vec<i32> = (0..train.len() as i32).collect();
+            let dist = |id: &i32| {
+                let [a, b] = train[*id as usize];
+                (a - x).powi(2) + (b - y).powi(2)
+            };
+            ids.sort_by(|a, b| dist(a).total_cmp(&dist(b)));
+            ids.truncate(5);
+            ids
+        })
+        .collect();
+
+    let floats = |vectors: &[[f32; 2]]| -> Vec<u8> {
+        vectors.iter().flatten().flat_map(|x| x.to_le_bytes()).collect()
+    };
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+    write_npy(Path::new(&path("train.npy")), "<f4", [train.len(), 2], &floats(&train));
+    write_npy(Path::new(&path("test.npy")), "<f4", [test.len(), 2], &floats(&test));
+    let ids: Vec<u8> = neighbors.iter().flat_map(|id| id.to_le_bytes()).collect();
+    write_npy(Path::new(&path("neighbors.npy")), "<i4", [test.len(), 5], &ids);
+
+    let csv = hannoy(
+        dir.path(),
+        &[
+            "bench",
+            "--train",
+            &path("train.npy"),
+            "--test",
+            &path("test.npy"),
+            "--neighbors",
+            &path("neighbors.npy"),
+            "-m",
+            "4,8",
+            "--ef-construction",
+            "50",
+            "--ef-search",
+            "5,100",
+            "-k",
+            "5",
+        ],
+    );
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("m,ef_construction,build_secs,ef_search,recall,qps"));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    let grid: Vec<_> = rows.iter().map(|row| (row[0], row[1], row[3])).collect();
+    assert_eq!(grid, [("4", "50", "5"), ("4", "50", "100"), ("8", "50", "5"), ("8", "50", "100")]);
+    for row in &rows[1..] {
+        let recall: f64 = row[4].parse().unwrap();
+        assert!((0.0..=1.0).contains(&recall), "{row:?}");
+    }
+    assert_eq!(rows[3][4], "1.0000", "{rows:?}");
+}
+
+#[test]
+fn convert_from_annoy() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("db");
+    let input = dir.path().join("index.ann");
+
+    // the layout of an euclidean annoy index: the number of descendants, the offset of the
+    // split plane, the two children and the vector of every node
+    let node = |descendants: i32, vector: [f32; 2]| -> Vec<u8> {
+        let mut node = descendants.to_le_bytes().to_vec();
+        node.extend_from_slice(&[0; 12]);
+        node.extend(vector.iter().flat_map(|x| x.to_le_bytes()));
+        node
+    };
+    let mut bytes = Vec::new();
+    for i in 0..10 {
+        // the item 4 was never added
+        bytes.extend(node(if i == 4 { 0 } else { 1 }, [i as f32, 0.0]));
+    }
+    // a split node and the copy of the root, with the 9 items under them
+    bytes.extend(node(9, [1.0, 0.0]));
+    bytes.extend(node(9, [1.0, 0.0]));
+    std::fs::write(&input, bytes).unwrap();
+
+    let input = input.to_str().unwrap();
+    let args = ["--input", input, "--metric", "euclidean", "--dimensions", "2", "-m", "4"];
+    hannoy(&db, &[&["convert-from-annoy"], &args[..]].concat());
+
+    let stats = hannoy(&db, &["stats"]);
+    assert!(stats.contains("dimensions: 2\nitems: 9\n"), "{stats}");
+    let found = hannoy(&db, &["query", "--vector", "4.2,0.0", "-k", "2"]);
+    let ids: Vec<&str> = found.lines().map(|line| line.split('\t').next().unwrap()).collect();
+    assert_eq!(ids, ["5", "3"]);
+}
@@ -2,6 +2,8 @@
 # ruff: noqa: E501, F401
 
 import builtins
+import numpy
+import numpy.typing
 import os
 import pathlib
 import typing
@@ -9,7 +11,7 @@ from enum import Enum
 
 class Database:
     r"""
-    An LMDB-backed vector database for vector search.
+    An LMDB-backed database for vector search.
     """
     def __new__(cls, path:builtins.str | os.PathLike | pathlib.Path, distance:Metric=..., name:typing.Optional[builtins.str]=None, env_size:typing.Optional[builtins.int]=None) -> Database: ...
     def writer(self, dimensions:builtins.int, index:builtins.int=0, m:builtins.int=16, ef:builtins.int=96) -> Writer:
@@ -18,7 +20,7 @@ class Database:
         """
     def reader(self, index:builtins.int=0) -> Reader:
         r"""
-        Get a reader for a specific index and dimensions
+        Open a reader for a specific index.
         """
     @staticmethod
     def commit_rw_txn() -> builtins.bool: ...
@@ -42,6 +44,10 @@ class Reader:
         r"""
         Retrieve similar items from the db given a query.
         """
+    def by_vecs(self, queries:numpy.typing.NDArray[numpy.float32], n:builtins.int=10, ef_search:builtins.int=200) -> builtins.list[builtins.list[tuple[builtins.int, builtins.float]]]:
+        r"""
+        Retrieve similar items for every row of a 2D numpy array of queries.
+        """
 
 class Writer:
     r"""
@@ -65,10 +71,24 @@ class Writer:
         r"""
         Store a vector associated with an item ID in the database.
         """
+    def add_items(self, items:typing.Sequence[builtins.int], vectors:numpy.typing.NDArray[numpy.float32]) -> None:
+        r"""
+        Store many vectors at once, `vectors` is a 2D numpy array with one row per item ID.
+        """
+    def del_item(self, item:builtins.int) -> builtins.bool:
+        r"""
+        Delete an item from the database, returns `True` if it existed.
+        """
+    def build(self) -> None:
+        r"""
+        Build the HNSW graph from the items added or deleted since the last build.
+        
+        This is automatically called when leaving the `with` block of the writer.
+        """
 
 class Metric(Enum):
     r"""
-    Supported distance metrics in hannoy.
+    The supported distance metrics in hannoy.
     """
     COSINE = ...
     EUCLIDEAN = ...
@@ -77,6 +97,14 @@ class Metric(Enum):
     BQ_EUCLIDEAN = ...
     BQ_MANHATTAN = ...
     HAMMING = ...
+    CHEBYSHEV = ...
+    CANBERRA = ...
+    BRAY_CURTIS = ...
+    HELLINGER = ...
+    U8_EUCLIDEAN = ...
+    U8_MANHATTAN = ...
+    EARTH_MOVERS = ...
+    POINCARE = ...
 
     def __str__(self) -> builtins.str: ...
 
@@ -0,0 +1,185 @@
+/* Generated with cbindgen, do not edit by hand. */
+
+#ifndef HANNOY_H
+#define HANNOY_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The outcome of a call to the hannoy C API.
+typedef enum HannoyStatus {
+  // The call succeeded.
+  HANNOY_STATUS_OK = 0,
+  // A pointer was null or an argument was out of the supported range.
+  HANNOY_STATUS_INVALID_ARGUMENT = 1,
+  // The operation failed, see [`hannoy_last_error`].
+  HANNOY_STATUS_ERROR = 2,
+} HannoyStatus;
+
+// The distances available through the C API.
+typedef enum HannoyDistance {
+  // See [`distance::Cosine`].
+  HANNOY_DISTANCE_COSINE = 0,
+  // See [`distance::Euclidean`].
+  HANNOY_DISTANCE_EUCLIDEAN = 1,
+  // See [`distance::Manhattan`].
+  HANNOY_DISTANCE_MANHATTAN = 2,
+  // See [`distance::BinaryQuantizedCosine`].
+  HANNOY_DISTANCE_BINARY_QUANTIZED_COSINE = 3,
+  // See [`distance::BinaryQuantizedEuclidean`].
+  HANNOY_DISTANCE_BINARY_QUANTIZED_EUCLIDEAN = 4,
+  // See [`distance::BinaryQuantizedManhattan`].
+  HANNOY_DISTANCE_BINARY_QUANTIZED_MANHATTAN = 5,
+  // See [`distance::Hamming`].
+  HANNOY_DISTANCE_HAMMING = 6,
+  // See [`distance::Chebyshev`].
+  HANNOY_DISTANCE_CHEBYSHEV = 7,
+  // See [`distance::Canberra`].
+  HANNOY_DISTANCE_CANBERRA = 8,
+  // See [`distance::BrayCurtis`].
+  HANNOY_DISTANCE_BRAY_CURTIS = 9,
+  // See [`distance::Hellinger`].
+  HANNOY_DISTANCE_HELLINGER = 10,
+  // See [`distance::U8Euclidean`].
+  HANNOY_DISTANCE_U8_EUCLIDEAN = 11,
+  // See [`distance::U8Manhattan`].
+  HANNOY_DISTANCE_U8_MANHATTAN = 12,
+  // See [`distance::EarthMovers`].
+  HANNOY_DISTANCE_EARTH_MOVERS = 13,
+  // See [`distance::Poincare`].
+  HANNOY_DISTANCE_POINCARE = 14,
+} HannoyDistance;
+
+// An opened LMDB environment.
+typedef struct HannoyEnv HannoyEnv;
+
+// A reader owning its read transaction, it can be moved between threads.
+typedef struct HannoyReader HannoyReader;
+
+// A writer owning its write transaction, the changes are only visible once committed.
+typedef struct HannoyWriter HannoyWriter;
+
+// An identifier for the items stored in the database.
+typedef uint32_t ItemId;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the message of the last error that happened on this thread, or null.
+//
+// The string is owned by hannoy and stays valid until the next failing call on this thread.
+const char *hannoy_last_error(void);
+
+// Opens, or creates, the LMDB environment at `path` with a maximum size of `map_size` bytes.
+//
+// # Safety
+//
+// `path` must be a valid nul-terminated string and `out` a valid pointer.
+enum HannoyStatus hannoy_env_open(const char *path, uintptr_t map_size, struct HannoyEnv **out);
+
+// Closes an environment. Writers and readers created from it stay valid.
+//
+// # Safety
+//
+// `env` must come from [`hannoy_env_open`] and must not be used afterward.
+void hannoy_env_free(struct HannoyEnv *env);
+
+// Opens a write transaction and a writer on the `index` of the unnamed database.
+//
+// `distance` must be one of the [`HannoyDistance`] values, any other is an invalid argument.
+//
+// # Safety
+//
+// `env` must come from [`hannoy_env_open`] and `out` must be a valid pointer.
+enum HannoyStatus hannoy_writer_new(const struct HannoyEnv *env,
+                                    uint32_t distance,
+                                    uint16_t index,
+                                    uintptr_t dimensions,
+                                    struct HannoyWriter **out);
+
+// Stores the `dimensions` floats pointed by `vector` for `item`.
+//
+// # Safety
+//
+// `writer` must come from [`hannoy_writer_new`] and `vector` must point to `dimensions` floats.
+enum HannoyStatus hannoy_writer_add_item(struct HannoyWriter *writer,
+                                         ItemId item,
+                                         const float *vector,
+                                         uintptr_t dimensions);
+
+// Deletes `item` and writes whether it existed in `existed`.
+//
+// # Safety
+//
+// `writer` must come from [`hannoy_writer_new`] and `existed` must be a valid pointer.
+enum HannoyStatus hannoy_writer_del_item(struct HannoyWriter *writer, ItemId item, bool *existed);
+
+// Builds the graph with `m` links per node, `m` must be one of 4, 8, 12, 16, 24, 32 or 48.
+//
+// # Safety
+//
+// `writer` must come from [`hannoy_writer_new`].
+enum HannoyStatus hannoy_writer_build(struct HannoyWriter *writer,
+                                      uintptr_t m,
+                                      uintptr_t ef_construction,
+                                      uint64_t seed);
+
+// Commits the changes of the writer and frees it, even if the commit fails.
+//
+// # Safety
+//
+// `writer` must come from [`hannoy_writer_new`] and must not be used afterward.
+enum HannoyStatus hannoy_writer_commit(struct HannoyWriter *writer);
+
+// Aborts the changes of the writer and frees it.
+//
+// # Safety
+//
+// `writer` must come from [`hannoy_writer_new`] and must not be used afterward.
+void hannoy_writer_free(struct HannoyWriter *writer);
+
+// Opens a reader on the `index` of the unnamed database which must have been built.
+//
+// `distance` must be one of the [`HannoyDistance`] values, any other is an invalid argument.
+//
+// # Safety
+//
+// `env` must come from [`hannoy_env_open`] and `out` must be a valid pointer.
+enum HannoyStatus hannoy_reader_open(const struct HannoyEnv *env,
+                                     uint32_t distance,
+                                     uint16_t index,
+                                     struct HannoyReader **out);
+
+// Searches the `count` nearest neighbours of `query`.
+//
+// The ids and distances are written in `out_ids` and `out_distances`, which must both be able
+// to hold `count` values, and the number of results written is stored in `out_len`.
+//
+// # Safety
+//
+// `reader` must come from [`hannoy_reader_open`], `query` must point to `dimensions` floats and
+// the out pointers must be valid for `count` values.
+enum HannoyStatus hannoy_reader_search(const struct HannoyReader *reader,
+                                       const float *query,
+                                       uintptr_t dimensions,
+                                       uintptr_t count,
+                                       uintptr_t ef_search,
+                                       ItemId *out_ids,
+                                       float *out_distances,
+                                       uintptr_t *out_len);
+
+// Frees a reader and its read transaction.
+//
+// # Safety
+//
+// `reader` must come from [`hannoy_reader_open`] and must not be used afterward.
+void hannoy_reader_free(struct HannoyReader *reader);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* HANNOY_H */
@@ -0,0 +1,222 @@
+//! A reference HTTP server adding, building and searching the hannoy indexes of an LMDB
+//! environment.
+//!
+//! The handlers show how to handle the LMDB transactions from an async runtime:
+//! - A transaction belongs to the thread that opened it and blocks on disk IO, so each one
+//!   is opened, used and committed or dropped inside a single [`spawn_blocking`] closure and is
+//!   never held across an `.await`.
+//! - LMDB allows a single write transaction at a time. Writes are committed at the end of the
+//!   request that made them, and a build is its own request rather than a side effect of an
+//!   insertion.
+//! - A read transaction sees the snapshot of the database it was opened on and prevents LMDB
+//!   from reusing the pages freed by later writes. A new one is opened for every search, it is
+//!   cheap, so that searches see the last build and long-lived snapshots don't grow the file.
+//!   A [`Reader`] borrows its transaction and is dropped with it.
+//!
+//! | Method   | Path                          | Body                                      |
+//! |----------|-------------------------------|-------------------------------------------|
+//! | `POST`   | `/indexes/{index}/items`      | `{"items": [{"id": 0, "vector": [..]}]}`  |
+//! | `DELETE` | `/indexes/{index}/items/{id}` |                                           |
+//! | `POST`   | `/indexes/{index}/build`      | `{"m": 16, "ef_construction": 100}`       |
+//! | `POST`   | `/indexes/{index}/search`     | `{"vector": [..], "k": 10}`               |
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, post};
+use axum::{Json, Router};
+use hannoy::{Database, Distance, Error, ItemId, Reader, Writer};
+use heed::Env;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+/// The environment and database shared by every request.
+struct AppState<D> {
+    env: Env,
+    database: Database<D>,
+    dimensions: usize,
+}
+
+/// Creates the routes serving every index of `database`, whose vectors are all of
+/// `dimensions` dimensions.
+pub fn router<D: Distance>(env: Env, database: Database<D>, dimensions: usize) -> Router {
+    Router::new()
+        .route("/indexes/{index}/items", post(add_items::<D>))
+        .route("/indexes/{index}/items/{id}", delete(del_item::<D>))
+        .route("/indexes/{index}/build", post(build::<D>))
+        .route("/indexes/{index}/search", post(search::<D>))
+        .with_state(Arc::new(AppState { env, database, dimensions }))
+}
+
+#[derive(Deserialize)]
+struct AddItems {
+    items: Vec<NewItem>,
+}
+
+#[derive(Deserialize)]
+struct NewItem {
+    id: ItemId,
+    vector: Vec<f32>,
+}
+
+#[derive(Serialize)]
+struct Added {
+    added: usize,
+}
+
+async fn add_items<D: Distance>(
+    State(state): State<Arc<AppState<D>>>,
+    Path(index): Path<u16>,
+    Json(AddItems { items }): Json<AddItems>,
+) -> Result<Json<Added>, ApiError> {
+    let added = items.len();
+    blocking(move || {
+        let mut wtxn = state.env.write_txn()?;
+        let writer = Writer::new(state.database, index, state.dimensions);
+        writer.add_items(&mut wtxn, items.iter().map(|item| (item.id, &item.vector[..])))?;
+        wtxn.commit()?;
+        Ok(Json(Added { added }))
+    })
+    .await
+}
+
+#[derive(Serialize)]
+struct Deleted {
+    deleted: bool,
+}
+
+async fn del_item<D: Distance>(
+    State(state): State<Arc<AppState<D>>>,
+    Path((index, id)): Path<(u16, ItemId)>,
+) -> Result<Json<Deleted>, ApiError> {
+    blocking(move || {
+        let mut wtxn = state.env.write_txn()?;
+        let writer = Writer::new(state.database, index, state.dimensions);
+        let deleted = writer.del_item(&mut wtxn, id)?;
+        wtxn.commit()?;
+        Ok(Json(Deleted { deleted }))
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct Build {
+    #[serde(default = "default_m")]
+    m: usize,
+    #[serde(default = "default_ef")]
+    ef_construction: usize,
+    #[serde(default)]
+    seed: u64,
+}
+
+fn default_m() -> usize {
+    16
+}
+
+fn default_ef() -> usize {
+    100
+}
+
+async fn build<D: Distance>(
+    State(state): State<Arc<AppState<D>>>,
+    Path(index): Path<u16>,
+    Json(Build { m, ef_construction, seed }): Json<Build>,
+) -> Result<StatusCode, ApiError> {
+    blocking(move || {
+        let mut wtxn = state.env.write_txn()?;
+        let writer = Writer::new(state.database, index, state.dimensions);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut builder = writer.builder(&mut rng);
+        builder.ef_construction(ef_construction);
+        // dropping the transaction on an error aborts it
+        builder.build_with_links(&mut wtxn, m)?;
+        wtxn.commit()?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct Search {
+    vector: Vec<f32>,
+    #[serde(default = "default_k")]
+    k: usize,
+    #[serde(default = "default_ef")]
+    ef_search: usize,
+}
+
+fn default_k() -> usize {
+    10
+}
+
+#[derive(Serialize)]
+struct Neighbour {
+    id: ItemId,
+    distance: f32,
+}
+
+async fn search<D: Distance>(
+    State(state): State<Arc<AppState<D>>>,
+    Path(index): Path<u16>,
+    Json(Search { vector, k, ef_search }): Json<Search>,
+) -> Result<Json<Vec<Neighbour>>, ApiError> {
+    blocking(move || {
+        let rtxn = state.env.read_txn()?;
+        let reader = Reader::open(&rtxn, index, state.database)?;
+        let found = reader.nns(k).ef_search(ef_search.max(k)).by_vector(&rtxn, &vector)?;
+        let found =
+            found.into_nns().into_iter().map(|(id, distance)| Neighbour { id, distance }).collect();
+        Ok(Json(found))
+    })
+    .await
+}
+
+/// Runs `f` on the blocking thread pool, where transactions can be opened.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, ApiError> + Send + 'static,
+) -> Result<T, ApiError> {
+    match spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(error) => std::panic::resume_unwind(error.into_panic()),
+    }
+}
+
+/// An error answered with the status code matching its cause.
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> ApiError {
+        let status = match error {
+            Error::MissingMetadata(_) => StatusCode::NOT_FOUND,
+            Error::NeedBuild(_) => StatusCode::CONFLICT,
+            Error::InvalidDimensions { .. }
+            | Error::NonFiniteVector { .. }
+            | Error::UnsupportedLinks(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError { status, message: error.to_string() }
+    }
+}
+
+impl From<heed::Error> for ApiError {
+    fn from(error: heed::Error) -> ApiError {
+        Error::from(error).into()
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(ErrorBody { error: self.message })).into_response()
+    }
+}
@@ -0,0 +1,31 @@
+//! Serves the cosine indexes of an LMDB environment over HTTP.
+//!
+//! ```sh
+//! hannoy-server <db> <dimensions> [address]
+//! ```
+use std::error::Error;
+
+use hannoy::distances::Cosine;
+use hannoy::Database;
+use heed::EnvOpenOptions;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let usage = "usage: hannoy-server <db> <dimensions> [address]";
+    let path = args.next().ok_or(usage)?;
+    let dimensions: usize = args.next().ok_or(usage)?.parse()?;
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:3000".to_string());
+
+    std::fs::create_dir_all(&path)?;
+    // the environment is opened once and shared by every request
+    let env = unsafe { EnvOpenOptions::new().map_size(10 * 1024 * 1024 * 1024).open(&path) }?;
+    let mut wtxn = env.write_txn()?;
+    let database: Database<Cosine> = env.create_database(&mut wtxn, None)?;
+    wtxn.commit()?;
+
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    eprintln!("serving {path} on http://{address}");
+    axum::serve(listener, hannoy_server::router(env, database, dimensions)).await?;
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use hannoy::distances::Euclidean;
+use hannoy::Database;
+use heed::EnvOpenOptions;
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+async fn call(router: &Router, method: Method, uri: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn add_build_and_search_two_indexes() {
+    let dir = tempfile::tempdir().unwrap();
+    let env =
+        unsafe { EnvOpenOptions::new().map_size(200 * 1024 * 1024).open(dir.path()) }.unwrap();
+    let mut wtxn = env.write_txn().unwrap();
+    let database: Database<Euclidean> = env.create_database(&mut wtxn, None).unwrap();
+    wtxn.commit().unwrap();
+    let router = hannoy_server::router(env, database, 2);
+
+    let (status, body) =
+        call(&router, Method::POST, "/indexes/0/search", json!({"vector": [0.0, 0.0]})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+
+    for (index, offset) in [(0, 0.0), (1, 100.0)] {
+        let items: Vec<Value> =
+            (0..50).map(|i| json!({"id": i, "vector": [offset + i as f32, 0.0]})).collect();
+        let uri = format!("/indexes/{index}/items");
+        let (status, body) = call(&router, Method::POST, &uri, json!({ "items": items })).await;
+        assert_eq!((status, body), (StatusCode::OK, json!({"added": 50})));
+    }
+
+    // an index is only searchable once it has been built
+    let (status, _) =
+        call(&router, Method::POST, "/indexes/0/search", json!({"vector": [0.0, 0.0]})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = call(&router, Method::POST, "/indexes/0/build", json!({"m": 5})).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let error = "Unsupported number of links per node 5, use one of [4, 8, 12, 16, 24, 32, 48]";
+    assert_eq!(body, json!({ "error": error }));
+    for index in [0, 1] {
+        let uri = format!("/indexes/{index}/build");
+        let (status, _) = call(&router, Method::POST, &uri, json!({"m": 4})).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    let query = json!({"vector": [103.1, 0.0], "k": 2});
+    let (status, body) = call(&router, Method::POST, "/indexes/1/search", query).await;
+    assert_eq!(status, StatusCode::OK);
+    let ids: Vec<_> = body.as_array().unwrap().iter().map(|n| n["id"].clone()).collect();
+    assert_eq!(ids, [json!(3), json!(4)]);
+
+    let (status, body) = call(&router, Method::DELETE, "/indexes/0/items/3", Value::Null).await;
+    assert_eq!((status, body), (StatusCode::OK, json!({"deleted": true})));
+    let (status, _) =
+        call(&router, Method::POST, "/indexes/0/search", json!({"vector": [3.0, 0.0]})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let query = json!({"vector": [1.0, 2.0, 3.0]});
+    let (status, body) = call(&router, Method::POST, "/indexes/1/search", query).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+}
@@ -0,0 +1,122 @@
+//! Insert vectors straight from Arrow record batches.
+use std::ops::Range;
+
+use arrow_array::{Array, FixedSizeListArray, Float32Array, ListArray, RecordBatch, UInt32Array};
+use arrow_schema::ArrowError;
+use heed::RwTxn;
+use tracing::debug;
+
+use crate::distance::Distance;
+use crate::{Error, Result, Writer};
+
+impl<D: Distance> Writer<D> {
+    /// Add every row of an Arrow [`RecordBatch`] to the database.
+    ///
+    /// The `id_column` must be a non-nullable `UInt32` column and the `vector_column` a
+    /// non-nullable `FixedSizeList<Float32>` or `List<Float32>` column whose lists are of the
+    /// dimensions of this writer.
+    /// The vectors are read directly from the Arrow buffers and written with
+    /// [`Self::add_items`], without any per-row conversion.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn, batch): (Writer<Euclidean>, heed::RwTxn, arrow_array::RecordBatch) = todo!();
+    /// writer.add_record_batch(&mut wtxn, &batch, "id", "embedding")?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn add_record_batch(
+        &self,
+        wtxn: &mut RwTxn,
+        batch: &RecordBatch,
+        id_column: &str,
+        vector_column: &str,
+    ) -> Result<()> {
+        let invalid = |column: &str, reason: String| Error::InvalidColumn {
+            column: column.to_string(),
+            reason,
+        };
+        let column = |name: &str| {
+            batch.column_by_name(name).ok_or_else(|| invalid(name, "missing column".to_string()))
+        };
+
+        let ids = column(id_column)?;
+        let ids = ids.as_any().downcast_ref::<UInt32Array>().ok_or_else(|| {
+            invalid(id_column, format!("expected UInt32 but got {}", ids.data_type()))
+        })?;
+        if ids.null_count() != 0 {
+            return Err(invalid(id_column, format!("contains {} nulls", ids.null_count())));
+        }
+
+        let vectors = column(vector_column)?;
+        let expected = || {
+            format!(
+                "expected FixedSizeList<Float32> or List<Float32> but got {}",
+                vectors.data_type()
+            )
+        };
+        let vectors = vectors.as_any();
+        // the range of the values buffer covered by each row
+        let (values, nulls, range): (_, _, Box<dyn Fn(usize) -> Range<usize>>) =
+            if let Some(lists) = vectors.downcast_ref::<FixedSizeListArray>() {
+                let size = lists.value_length() as usize;
+                let range = move |row| {
+                    let start = lists.value_offset(row) as usize;
+                    start..start + size
+                };
+                (lists.values(), lists.null_count(), Box::new(range))
+            } else if let Some(lists) = vectors.downcast_ref::<ListArray>() {
+                let offsets = lists.value_offsets();
+                let range = move |row| offsets[row] as usize..offsets[row + 1] as usize;
+                (lists.values(), lists.null_count(), Box::new(range))
+            } else {
+                return Err(invalid(vector_column, expected()));
+            };
+        let values = values
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .ok_or_else(|| invalid(vector_column, expected()))?;
+        if nulls != 0 || values.null_count() != 0 {
+            return Err(invalid(vector_column, "contains nulls".to_string()));
+        }
+
+        let values = values.values();
+        let items = ids.values().iter().enumerate().map(|(row, &id)| (id, &values[range(row)]));
+
+        self.add_items(wtxn, items)
+    }
+    /// Adds the rows of a stream of Arrow record batches with [`Self::add_record_batch`] and
+    /// returns the number of rows read, the batches are dropped once written so the stream
+    /// never has to fit in memory.
+    ///
+    /// Any [`RecordBatchReader`](arrow_array::RecordBatchReader) can be imported, which is how
+    /// the Lance datasets and LanceDB tables are read: scan the id and vector columns into a
+    /// record batch reader and import it. Their `_rowid` column is an `UInt64` that doesn't fit
+    /// the ids of the items, the `id_column` must be one of the dataset.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn, batches): (Writer<Euclidean>, heed::RwTxn, Box<dyn arrow_array::RecordBatchReader>) = todo!();
+    /// let rows = writer.import_record_batches(&mut wtxn, batches, "id", "embedding")?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn import_record_batches(
+        &self,
+        wtxn: &mut RwTxn,
+        batches: impl IntoIterator<Item = std::result::Result<RecordBatch, ArrowError>>,
+        id_column: &str,
+        vector_column: &str,
+    ) -> Result<u64> {
+        let mut rows = 0;
+        for batch in batches {
+            let batch = batch?;
+            self.add_record_batch(wtxn, &batch, id_column, vector_column)?;
+            rows += batch.num_rows() as u64;
+            debug!("imported {rows} rows from record batches");
+        }
+        Ok(rows)
+    }
+}
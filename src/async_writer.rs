@@ -0,0 +1,152 @@
+//! Offloads the writes and the builds of an index to the blocking thread pool of tokio, so that
+//! async services don't block their runtime while indexing.
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use heed::Env;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use roaring::RoaringBitmap;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::distance::Distance;
+use crate::{Error, ItemId, Result, Writer};
+
+/// The default number of items added per write transaction, see [`AsyncWriter::batch_size`].
+const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// A step of a build, sent by [`AsyncWriter::build`] to follow its progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildProgress {
+    /// The name of the step.
+    pub step: Cow<'static, str>,
+    /// The number of units of work of the step already done.
+    pub current: u64,
+    /// The number of units of work of the step.
+    pub total: u64,
+}
+
+/// Sends the steps of a build over a channel, they are dropped once the receiver is.
+struct ChannelProgress(Option<UnboundedSender<BuildProgress>>);
+
+impl steppe::Progress for ChannelProgress {
+    fn update(&self, step: impl steppe::Step) {
+        if let Some(sender) = &self.0 {
+            let _ = sender.send(BuildProgress {
+                step: step.name(),
+                current: step.current(),
+                total: step.total(),
+            });
+        }
+    }
+}
+
+/// A [`Writer`] whose methods run their write transactions on the blocking thread pool of tokio,
+/// one after the other, and commit them.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn index(env: heed::Env, writer: hannoy::Writer<hannoy::distances::Cosine>) -> hannoy::Result<()> {
+/// use hannoy::{AsyncWriter, BuildProgress};
+///
+/// let writer = AsyncWriter::new(env, writer);
+/// writer.add_items(vec![(0, vec![0.5, 0.5]), (1, vec![0.1, 0.9])]).await?;
+///
+/// let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<BuildProgress>();
+/// tokio::spawn(async move {
+///     while let Some(progress) = receiver.recv().await {
+///         println!("{}: {}/{}", progress.step, progress.current, progress.total);
+///     }
+/// });
+/// writer.build::<16, 32>(42, Some(sender)).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncWriter<D: Distance> {
+    env: Env,
+    writer: Arc<Writer<D>>,
+    batch_size: usize,
+}
+
+impl<D: Distance> AsyncWriter<D> {
+    /// Wraps a `writer` of an index of the `env`.
+    pub fn new(env: Env, writer: Writer<D>) -> AsyncWriter<D> {
+        AsyncWriter { env, writer: Arc::new(writer), batch_size: DEFAULT_BATCH_SIZE }
+    }
+
+    /// Sets the number of items [`Self::add_items`] adds per write transaction, 1024 by default.
+    ///
+    /// A write transaction holds the write lock of the environment, smaller batches let the
+    /// other writers in between.
+    pub fn batch_size(mut self, batch_size: usize) -> AsyncWriter<D> {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns the wrapped writer, to use it in a write transaction of its own.
+    pub fn writer(&self) -> &Writer<D> {
+        &self.writer
+    }
+
+    /// Adds the items in batches of [`Self::batch_size`] items, each committed in its own write
+    /// transaction. The batches committed before an error are kept.
+    pub async fn add_items(&self, items: Vec<(ItemId, Vec<f32>)>) -> Result<()> {
+        let mut items = items.into_iter();
+        loop {
+            let batch: Vec<_> = items.by_ref().take(self.batch_size).collect();
+            if batch.is_empty() {
+                return Ok(());
+            }
+            self.write(move |writer, wtxn| {
+                writer.add_items(wtxn, batch.iter().map(|(item, vector)| (*item, &vector[..])))
+            })
+            .await?;
+        }
+    }
+
+    /// Deletes the items in a single write transaction and returns the number of items that
+    /// were deleted.
+    pub async fn del_items(&self, items: RoaringBitmap) -> Result<u64> {
+        self.write(move |writer, wtxn| writer.del_items(wtxn, &items)).await
+    }
+
+    /// Builds the index with `M` and `M0` links per item and commits it, the steps of the build
+    /// are sent to the `progress` channel as they start.
+    ///
+    /// The build is seeded with `seed`, see [`Writer::builder`].
+    pub async fn build<const M: usize, const M0: usize>(
+        &self,
+        seed: u64,
+        progress: Option<UnboundedSender<BuildProgress>>,
+    ) -> Result<()> {
+        self.write(move |writer, wtxn| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut builder = writer.builder(&mut rng).progress(ChannelProgress(progress));
+            builder.build::<M, M0>(wtxn)
+        })
+        .await
+    }
+
+    /// Runs `f` in a write transaction on the blocking thread pool and commits it if it
+    /// succeeds.
+    async fn write<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Writer<D>, &mut heed::RwTxn) -> Result<T> + Send + 'static,
+    {
+        let (env, writer) = (self.env.clone(), self.writer.clone());
+        let task = tokio::task::spawn_blocking(move || {
+            let mut wtxn = env.write_txn()?;
+            let output = f(&writer, &mut wtxn)?;
+            wtxn.commit()?;
+            Ok(output)
+        });
+        match task.await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(Error::Io(std::io::Error::other(e))),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! An inverted index of the attributes of the items, to build the filters of the searches from
+//! their name.
+use heed::types::Bytes;
+use heed::{RoTxn, RwTxn};
+use roaring::RoaringBitmap;
+
+use crate::roaring::RoaringBitmapCodec;
+use crate::{Error, ItemId, Result};
+
+/// The database the attributes of the items are stored in, next to the [`Database`](crate::Database)
+/// of their vectors. Every value of an attribute is a key of the items that have it.
+pub type AttributeDatabase = heed::Database<Bytes, RoaringBitmapCodec>;
+
+/// The named attributes of the items of an index, like their category or their tags, stored as
+/// the bitmap of the items of every value to be turned into the
+/// [`QueryBuilder::candidates`](crate::QueryBuilder::candidates) of the searches.
+///
+/// The ids are the ones the items were added with. An item can have several values of the
+/// same attribute, e.g. several tags.
+///
+/// # Example
+///
+/// ```no_run
+/// use hannoy::{AttributeDatabase, AttributeFilter, Attributes, Reader, distances::Cosine};
+/// # let (env, database): (heed::Env, hannoy::Database<Cosine>) = todo!();
+///
+/// let mut wtxn = env.write_txn()?;
+/// let attributes_db: AttributeDatabase = env.create_database(&mut wtxn, Some("attributes"))?;
+/// let attributes = Attributes::new(attributes_db, 0);
+/// attributes.add(&mut wtxn, 12, "color", "red")?;
+/// attributes.add(&mut wtxn, 12, "size", "xl")?;
+/// wtxn.commit()?;
+///
+/// let rtxn = env.read_txn()?;
+/// let filter = AttributeFilter::And(vec![
+///     AttributeFilter::Is("color", "red"),
+///     AttributeFilter::AnyOf("size", vec!["l", "xl"]),
+/// ]);
+/// let candidates = attributes.filter(&rtxn, &filter)?;
+/// let reader = Reader::open(&rtxn, 0, database)?;
+/// let found = reader.nns(10).candidates(&candidates).by_item(&rtxn, 12)?;
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Attributes {
+    database: AttributeDatabase,
+    index: u16,
+}
+
+/// A condition on the attributes of the items, see [`Attributes::filter`].
+#[derive(Debug, Clone)]
+pub enum AttributeFilter<'a> {
+    /// The items whose attribute has the value.
+    Is(&'a str, &'a str),
+    /// The items whose attribute has any of the values.
+    AnyOf(&'a str, Vec<&'a str>),
+    /// The items matching all of the filters, all the items of the index for none.
+    And(Vec<AttributeFilter<'a>>),
+    /// The items matching any of the filters.
+    Or(Vec<AttributeFilter<'a>>),
+}
+
+impl Attributes {
+    /// Returns the attributes of the items of the `index` stored in the `database`.
+    pub fn new(database: AttributeDatabase, index: u16) -> Attributes {
+        Attributes { database, index }
+    }
+
+    /// Gives the `value` of the `attribute` to an item, on top of its other values.
+    pub fn add(&self, wtxn: &mut RwTxn, item: ItemId, attribute: &str, value: &str) -> Result<()> {
+        let key = self.key(attribute, value)?;
+        let mut items = self.database.get(wtxn, &key)?.unwrap_or_default();
+        if items.insert(item) {
+            self.database.put(wtxn, &key, &items)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the `value` of the `attribute` from an item and returns `true` if it had it.
+    pub fn remove(
+        &self,
+        wtxn: &mut RwTxn,
+        item: ItemId,
+        attribute: &str,
+        value: &str,
+    ) -> Result<bool> {
+        let key = self.key(attribute, value)?;
+        let Some(mut items) = self.database.get(wtxn, &key)? else { return Ok(false) };
+        if !items.remove(item) {
+            return Ok(false);
+        }
+        if items.is_empty() {
+            self.database.delete(wtxn, &key)?;
+        } else {
+            self.database.put(wtxn, &key, &items)?;
+        }
+        Ok(true)
+    }
+
+    /// Removes all the attributes of an item, e.g. once it is deleted from the index. It goes
+    /// through all the values of the index.
+    pub fn remove_item(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<()> {
+        let prefix = self.index.to_be_bytes();
+        let mut iter = self.database.prefix_iter_mut(wtxn, &prefix)?;
+        while let Some((key, mut items)) = iter.next().transpose()? {
+            if !items.remove(item) {
+                continue;
+            }
+            let key = key.to_vec();
+            if items.is_empty() {
+                // SAFETY: Safe because we don't keep any references to the entry
+                unsafe { iter.del_current() }?;
+            } else {
+                // SAFETY: Safe because the key is copied and the bitmap is owned
+                unsafe { iter.put_current(&key, &items) }?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the items whose `attribute` has the `value`.
+    pub fn items(&self, rtxn: &RoTxn, attribute: &str, value: &str) -> Result<RoaringBitmap> {
+        let key = self.key(attribute, value)?;
+        Ok(self.database.get(rtxn, &key)?.unwrap_or_default())
+    }
+
+    /// Returns the values of an `attribute` with the number of items that have them, ordered
+    /// by value, e.g. to show the facets of the results.
+    pub fn values(&self, rtxn: &RoTxn, attribute: &str) -> Result<Vec<(String, u64)>> {
+        let prefix = self.key(attribute, "")?;
+        let mut values = Vec::new();
+        for result in self.database.prefix_iter(rtxn, &prefix)? {
+            let (key, items) = result?;
+            let value = String::from_utf8_lossy(&key[prefix.len()..]).into_owned();
+            values.push((value, items.len()));
+        }
+        Ok(values)
+    }
+
+    /// Returns the items matching the `filter`.
+    pub fn filter(&self, rtxn: &RoTxn, filter: &AttributeFilter) -> Result<RoaringBitmap> {
+        match filter {
+            AttributeFilter::Is(attribute, value) => self.items(rtxn, attribute, value),
+            AttributeFilter::AnyOf(attribute, values) => {
+                let mut items = RoaringBitmap::new();
+                for value in values {
+                    items |= self.items(rtxn, attribute, value)?;
+                }
+                Ok(items)
+            }
+            AttributeFilter::And(filters) => {
+                let mut filters = filters.iter();
+                let Some(first) = filters.next() else { return self.all_items(rtxn) };
+                let mut items = self.filter(rtxn, first)?;
+                for filter in filters {
+                    if items.is_empty() {
+                        break;
+                    }
+                    items &= self.filter(rtxn, filter)?;
+                }
+                Ok(items)
+            }
+            AttributeFilter::Or(filters) => {
+                let mut items = RoaringBitmap::new();
+                for filter in filters {
+                    items |= self.filter(rtxn, filter)?;
+                }
+                Ok(items)
+            }
+        }
+    }
+
+    /// Removes all the attributes of the items of the index.
+    pub fn clear(&self, wtxn: &mut RwTxn) -> Result<()> {
+        let prefix = self.index.to_be_bytes();
+        let mut iter = self.database.remap_data_type::<Bytes>().prefix_iter_mut(wtxn, &prefix)?;
+        while iter.next().transpose()?.is_some() {
+            // SAFETY: Safe because we don't keep any references to the entry
+            unsafe { iter.del_current() }?;
+        }
+        Ok(())
+    }
+
+    /// Returns the items that have any attribute.
+    fn all_items(&self, rtxn: &RoTxn) -> Result<RoaringBitmap> {
+        let mut items = RoaringBitmap::new();
+        for result in self.database.prefix_iter(rtxn, &self.index.to_be_bytes())? {
+            items |= result?.1;
+        }
+        Ok(items)
+    }
+
+    /// Returns the key of a value: the index, the attribute, a `NUL` byte and the value.
+    fn key(&self, attribute: &str, value: &str) -> Result<Vec<u8>> {
+        if attribute.is_empty() || attribute.contains('\0') {
+            return Err(Error::InvalidAttribute { attribute: attribute.to_owned() });
+        }
+        let mut key = Vec::with_capacity(2 + attribute.len() + 1 + value.len());
+        key.extend_from_slice(&self.index.to_be_bytes());
+        key.extend_from_slice(attribute.as_bytes());
+        key.push(0);
+        key.extend_from_slice(value.as_bytes());
+        Ok(key)
+    }
+}
@@ -0,0 +1,90 @@
+use heed::types::Bytes;
+use heed::{RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::internals::KeyCodec;
+use crate::reader::tombstones;
+use crate::stats::{VectorStats, VectorStatsBuilder};
+use crate::{Database, Error, Key, Node, Prefix, PrefixCodec, Result};
+
+/// The standard deviation the constant dimensions are given, so that the shifts of their means
+/// aren't divided by zero.
+const MIN_DEVIATION: f64 = 1e-6;
+
+/// Returns the mean and the standard deviation of every dimension of the vectors of an index
+/// when its drift baseline was last recorded, see
+/// [`Writer::recalibrate_drift_baseline`](crate::Writer::recalibrate_drift_baseline).
+pub(crate) fn get_calibration<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<Vec<(f32, f32)>>> {
+    let key = Key::calibration(index);
+    let bytes =
+        database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))?;
+    Ok(bytes.map(|bytes| {
+        let values: Vec<f32> = bytemuck::pod_collect_to_vec(bytes);
+        values.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+    }))
+}
+
+/// Stores the statistics of the vectors as the calibration read back by [`get_calibration`].
+pub(crate) fn put_calibration<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    stats: &VectorStats,
+) -> Result<()> {
+    let key = Key::calibration(index);
+    let values: Vec<f32> = stats
+        .dimensions
+        .iter()
+        .flat_map(|summary| [summary.mean as f32, summary.variance.sqrt() as f32])
+        .collect();
+    database
+        .remap_data_type::<Bytes>()
+        .put(wtxn, &key, bytemuck::cast_slice(&values))
+        .map_err(Error::on_key("put", key))
+}
+
+/// Returns the statistics of the vectors of all the items of an index, built or not.
+pub(crate) fn item_stats<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+) -> Result<VectorStats> {
+    let tombstones = tombstones(rtxn, database, index)?;
+    let mut stats = VectorStatsBuilder::new(dimensions);
+    let prefix = Prefix::item(index);
+    let iter = database
+        .remap_key_type::<PrefixCodec>()
+        .prefix_iter(rtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<KeyCodec>();
+    for result in iter {
+        let (key, node) = result?;
+        let Node::Item(item) = node else { return Err(Error::unexpected_node(key, "Item")) };
+        if !tombstones.contains(key.node.item) {
+            stats.push(item.vector.iter());
+        }
+    }
+    Ok(stats.finish())
+}
+
+/// Returns the root mean square of the shifts of the means of the dimensions, in standard
+/// deviations of the `calibration`.
+pub(crate) fn drift(calibration: &[(f32, f32)], stats: &VectorStats) -> f64 {
+    if calibration.is_empty() {
+        return 0.0;
+    }
+    let squares: f64 = calibration
+        .iter()
+        .zip(&stats.dimensions)
+        .map(|(&(mean, deviation), summary)| {
+            let shift = (summary.mean - mean as f64) / (deviation as f64).max(MIN_DEVIATION);
+            shift * shift
+        })
+        .sum();
+    (squares / calibration.len() as f64).sqrt()
+}
@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+use std::mem::size_of;
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::{DecodeIgnore, Unit, U32};
+use heed::{BoxedError, RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::{Database, Error, ItemId, Key, Result};
+
+/// What happened to an item, see [`Change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ChangeKind {
+    /// The item was added.
+    Added = 0,
+    /// The vector of an existing item was replaced.
+    Updated = 1,
+    /// The item was deleted.
+    Deleted = 2,
+}
+
+/// An entry of the change log of an index, see
+/// [`Writer::set_change_log`](crate::Writer::set_change_log).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Change {
+    /// The position of the change in the log, it increases with every change.
+    pub sequence: u32,
+    /// The item that changed.
+    pub item: ItemId,
+    /// What happened to the item.
+    pub kind: ChangeKind,
+}
+
+/// The codec of the values of the change log, the kind of change followed by the item.
+pub(crate) enum ChangeCodec {}
+
+impl<'a> heed::BytesEncode<'a> for ChangeCodec {
+    type EItem = (ChangeKind, ItemId);
+
+    fn bytes_encode((kind, item): &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+        let mut output = Vec::with_capacity(size_of::<u8>() + size_of::<ItemId>());
+        output.push(*kind as u8);
+        output.extend_from_slice(&item.to_be_bytes());
+        Ok(Cow::Owned(output))
+    }
+}
+
+impl heed::BytesDecode<'_> for ChangeCodec {
+    type DItem = (ChangeKind, ItemId);
+
+    fn bytes_decode(bytes: &'_ [u8]) -> Result<Self::DItem, BoxedError> {
+        let expected = size_of::<u8>() + size_of::<ItemId>();
+        if bytes.len() != expected {
+            return Err(format!(
+                "Could not decode a change of {} bytes, expected {expected}.",
+                bytes.len()
+            )
+            .into());
+        }
+        let kind = match bytes[0] {
+            0 => ChangeKind::Added,
+            1 => ChangeKind::Updated,
+            2 => ChangeKind::Deleted,
+            kind => return Err(format!("Could not convert {kind} as a `ChangeKind`.").into()),
+        };
+        Ok((kind, BigEndian::read_u32(&bytes[size_of::<u8>()..])))
+    }
+}
+
+/// Returns whether the changes of an index are logged, see [`set_enabled`].
+pub(crate) fn is_enabled<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<bool> {
+    let key = Key::change_log(index);
+    let enabled = database
+        .remap_data_type::<DecodeIgnore>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?;
+    Ok(enabled.is_some())
+}
+
+/// Stores whether the changes of an index are logged, so that every writer of the index
+/// follows it.
+pub(crate) fn set_enabled<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    enabled: bool,
+) -> Result<()> {
+    let key = Key::change_log(index);
+    if enabled {
+        database.remap_data_type::<Unit>().put(wtxn, &key, &()).map_err(Error::on_key("put", key))
+    } else {
+        database.delete(wtxn, &key).map(drop).map_err(Error::on_key("delete", key))
+    }
+}
+
+/// Appends a change to the log of an index and returns its sequence number.
+pub(crate) fn record<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    item: ItemId,
+    kind: ChangeKind,
+) -> Result<u32> {
+    // the next sequence number is kept apart so that it keeps increasing once truncated
+    let sequence = next_sequence(wtxn, database, Key::change_sequence(index))?;
+    let next = sequence.checked_add(1).ok_or(Error::ChangeLogExhausted(index))?;
+    set_next_sequence(wtxn, database, Key::change_sequence(index), next)?;
+
+    let key = Key::change(index, sequence);
+    database
+        .remap_data_type::<ChangeCodec>()
+        .put(wtxn, &key, &(kind, item))
+        .map_err(Error::on_key("put", key))?;
+    Ok(sequence)
+}
+
+/// Appends the `changes` of many items to the log of an index, in the given order, reading and
+/// writing the next sequence number once for all of them.
+pub(crate) fn record_all<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    changes: impl IntoIterator<Item = (ItemId, ChangeKind)>,
+) -> Result<()> {
+    let mut sequence = next_sequence(wtxn, database, Key::change_sequence(index))?;
+    let log = database.remap_data_type::<ChangeCodec>();
+    for (item, kind) in changes {
+        let next = sequence.checked_add(1).ok_or(Error::ChangeLogExhausted(index))?;
+        let key = Key::change(index, sequence);
+        log.put(wtxn, &key, &(kind, item)).map_err(Error::on_key("put", key))?;
+        sequence = next;
+    }
+    set_next_sequence(wtxn, database, Key::change_sequence(index), sequence)
+}
+
+/// Returns the sequence number stored under `key`, the one of the next change logged by an
+/// index or applied by a replica, `0` if there is none.
+pub(crate) fn next_sequence<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    key: Key,
+) -> Result<u32> {
+    let sequence = database
+        .remap_data_type::<U32<BigEndian>>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?;
+    Ok(sequence.unwrap_or(0))
+}
+
+/// Stores a sequence number under `key`, see [`next_sequence`].
+pub(crate) fn set_next_sequence<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    key: Key,
+    sequence: u32,
+) -> Result<()> {
+    database
+        .remap_data_type::<U32<BigEndian>>()
+        .put(wtxn, &key, &sequence)
+        .map_err(Error::on_key("put", key))
+}
+
+/// Returns the changes of an index whose sequence number is at least `since`, in order.
+pub(crate) fn changes<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    since: u32,
+) -> Result<Vec<Change>> {
+    let range = Key::change(index, since)..=Key::change(index, u32::MAX);
+    let mut changes = Vec::new();
+    for result in database.remap_data_type::<ChangeCodec>().range(rtxn, &range)? {
+        let (key, (kind, item)) = result?;
+        changes.push(Change { sequence: key.node.item, item, kind });
+    }
+    Ok(changes)
+}
+
+/// Deletes the changes of an index whose sequence number is lower than `before`.
+pub(crate) fn truncate<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    before: u32,
+) -> Result<u64> {
+    let range = Key::change(index, 0)..Key::change(index, before);
+    Ok(database.remap_data_type::<DecodeIgnore>().delete_range(wtxn, &range)? as u64)
+}
@@ -0,0 +1,290 @@
+//! Groups the items of an index around centroids with a mini-batch k-means, e.g. to partition
+//! a collection by topic or to pick representative items.
+//!
+//! The centroids are seeded with k-means++ on a sample of the items and refined on random
+//! batches of items, as described by Sculley in "Web-Scale K-Means Clustering", then every item
+//! is assigned to its closest centroid. The items are compared to the centroids with the
+//! distance of the index, on the vectors as they are stored.
+//!
+//! ```no_run
+//! # use hannoy::{Reader, distances::Cosine};
+//! # let (reader, rtxn): (Reader<Cosine>, heed::RoTxn) = todo!();
+//! use hannoy::clustering::KMeans;
+//! use rand::SeedableRng;
+//!
+//! let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+//! let clusters = KMeans::new(16).run(&rtxn, &reader, &mut rng)?;
+//! for (centroid, members) in clusters.centroids().iter().zip(clusters.members()) {
+//!     println!("{} items around {centroid:?}", members.len());
+//! }
+//! # Ok::<(), hannoy::Error>(())
+//! ```
+//!
+//! [`near_duplicates`] groups the items that are within a distance of each other, following
+//! the links of the graph instead of comparing all the pairs of items.
+use std::borrow::Cow;
+
+use hashbrown::HashMap;
+use heed::RoTxn;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::unaligned_vector::UnalignedVector;
+use crate::{Error, ItemId, Key, Reader, Result};
+
+/// The parameters of a mini-batch k-means over the items of an index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KMeans {
+    /// The number of clusters, at most the number of items.
+    pub k: usize,
+    /// The number of items drawn at every iteration to move the centroids.
+    pub batch_size: usize,
+    /// The number of batches the centroids are moved toward.
+    pub iterations: usize,
+    /// Whether the final assignment compares an item to the centroids of the clusters its
+    /// neighbours in the graph were assigned to, rather than to every centroid.
+    ///
+    /// Close items mostly belong to the same cluster: the assignment is much faster with many
+    /// clusters but may miss the closest centroid of the items at the border of a cluster. The
+    /// items of which less than half the neighbours are assigned yet are still compared to every
+    /// centroid.
+    pub use_graph: bool,
+}
+
+impl KMeans {
+    /// Returns the parameters of a k-means finding `k` clusters, with batches of 1024 items
+    /// and 100 iterations.
+    pub fn new(k: usize) -> KMeans {
+        KMeans { k, batch_size: 1024, iterations: 100, use_graph: false }
+    }
+
+    /// Clusters the items of the index, drawing the seeds and the batches from `rng`.
+    pub fn run<D: Distance, R: Rng>(
+        &self,
+        rtxn: &RoTxn,
+        reader: &Reader<D>,
+        rng: &mut R,
+    ) -> Result<Clusters> {
+        let ids = reader.item_ids();
+        let k = self.k.min(ids.len() as usize);
+        if k == 0 {
+            return Ok(Clusters { centroids: Vec::new(), members: Vec::new() });
+        }
+
+        let batch_size = self.batch_size.max(k);
+        let sample = vectors(rtxn, reader, ids.iter().choose_multiple(rng, batch_size))?;
+        let mut centroids = seed::<D, R>(&sample, k, rng);
+
+        let mut counts = vec![0u32; k];
+        for _ in 0..self.iterations {
+            let batch = vectors(rtxn, reader, ids.iter().choose_multiple(rng, batch_size))?;
+            let items: Vec<_> = centroids.iter().map(|c| item::<D>(c)).collect();
+            let nearest: Vec<_> =
+                batch.iter().map(|v| closest(&items, &item::<D>(v), 0..k)).collect();
+
+            for (vector, cluster) in batch.iter().zip(nearest) {
+                counts[cluster] += 1;
+                let rate = 1.0 / counts[cluster] as f32;
+                for (c, x) in centroids[cluster].iter_mut().zip(vector) {
+                    *c += rate * (x - *c);
+                }
+            }
+        }
+
+        let items: Vec<_> = centroids.iter().map(|c| item::<D>(c)).collect();
+        let mut members = vec![RoaringBitmap::new(); k];
+        let mut assigned = HashMap::new();
+        for id in ids {
+            let Some(vector) = reader.item_vector_ref(rtxn, id)? else { continue };
+            let query = Item { header: D::new_header(&vector), vector };
+
+            // the clusters of the neighbours are only trusted once most of them are assigned
+            let mut candidates = Vec::new();
+            if self.use_graph {
+                let neighbours = reader.neighbours(rtxn, id)?;
+                let mut known = 0;
+                for cluster in neighbours.iter().filter_map(|n| assigned.get(n).copied()) {
+                    known += 1;
+                    if !candidates.contains(&cluster) {
+                        candidates.push(cluster);
+                    }
+                }
+                if known * 2 < neighbours.len() {
+                    candidates.clear();
+                }
+            }
+            let cluster = match candidates.is_empty() {
+                true => closest(&items, &query, 0..k),
+                false => closest(&items, &query, candidates),
+            };
+            members[cluster].insert(id);
+            if self.use_graph {
+                assigned.insert(id, cluster);
+            }
+        }
+        Ok(Clusters { centroids, members })
+    }
+}
+
+/// The centroids of the clusters found by a [`KMeans`] and the items assigned to them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clusters {
+    centroids: Vec<Vec<f32>>,
+    members: Vec<RoaringBitmap>,
+}
+
+impl Clusters {
+    /// Returns the number of clusters.
+    pub fn len(&self) -> usize {
+        self.centroids.len()
+    }
+
+    /// Returns `true` if there are no clusters, i.e. the index had no items.
+    pub fn is_empty(&self) -> bool {
+        self.centroids.is_empty()
+    }
+
+    /// Returns the centroid of every cluster, in the space the vectors are stored in.
+    pub fn centroids(&self) -> &[Vec<f32>] {
+        &self.centroids
+    }
+
+    /// Returns the items assigned to every cluster, in the order of the centroids.
+    pub fn members(&self) -> &[RoaringBitmap] {
+        &self.members
+    }
+
+    /// Returns the cluster an item was assigned to, `None` if the index didn't contain it.
+    pub fn cluster_of(&self, item: ItemId) -> Option<usize> {
+        self.members.iter().position(|members| members.contains(item))
+    }
+}
+
+/// Returns the groups of near-duplicate items: the connected components of the items linked
+/// in the graph whose distance is at most `threshold`, in the unit of the distances the
+/// searches return. Only the groups of at least two items are returned, ordered by their
+/// smallest item.
+///
+/// The graph only links every item to its closest neighbours among a diverse set, but the
+/// neighbour of an item that was left unlinked is itself linked to a closer item: the
+/// near-duplicates of an item end up in its group through them.
+///
+/// ```no_run
+/// # use hannoy::{Reader, distances::Cosine};
+/// # let (reader, rtxn): (Reader<Cosine>, heed::RoTxn) = todo!();
+/// for group in hannoy::clustering::near_duplicates(&rtxn, &reader, 0.01)? {
+///     println!("{group:?} are near-duplicates");
+/// }
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+pub fn near_duplicates<D: Distance>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    threshold: f32,
+) -> Result<Vec<RoaringBitmap>> {
+    let mut parents = HashMap::<ItemId, ItemId>::new();
+    for id in reader.item_ids() {
+        let Some(vector) = reader.item_vector_ref(rtxn, id)? else { continue };
+        let item = Item { header: D::new_header(&vector), vector };
+        for neighbour in reader.neighbours(rtxn, id)? {
+            let Some(vector) = reader.item_vector_ref(rtxn, neighbour)? else { continue };
+            let other = Item { header: D::new_header(&vector), vector };
+            if D::distance(&item, &other) <= threshold {
+                let (a, b) = (root(&mut parents, id), root(&mut parents, neighbour));
+                parents.insert(a.max(b), a.min(b));
+            }
+        }
+    }
+
+    let mut groups = HashMap::<ItemId, RoaringBitmap>::new();
+    for id in parents.keys().copied().collect::<Vec<_>>() {
+        let root = root(&mut parents, id);
+        groups.entry(root).or_default().insert(id);
+    }
+    let mut groups: Vec<_> = groups.into_values().filter(|group| group.len() > 1).collect();
+    groups.sort_unstable_by_key(|group| group.min());
+    Ok(groups)
+}
+
+/// Returns the item representing the group of an `item`, compressing the path to it.
+fn root(parents: &mut HashMap<ItemId, ItemId>, item: ItemId) -> ItemId {
+    let mut root = *parents.entry(item).or_insert(item);
+    while parents[&root] != root {
+        root = parents[&root];
+    }
+    let mut current = item;
+    while current != root {
+        current = parents.insert(current, root).expect("the path is made of known items");
+    }
+    root
+}
+
+/// Picks `k` of the `sample` vectors with k-means++: every seed is drawn with a probability
+/// proportional to its distance to the closest seed already drawn.
+fn seed<D: Distance, R: Rng>(sample: &[Vec<f32>], k: usize, rng: &mut R) -> Vec<Vec<f32>> {
+    let items: Vec<_> = sample.iter().map(|v| item::<D>(v)).collect();
+    let mut seeds = vec![rng.gen_range(0..items.len())];
+    let mut distances: Vec<_> =
+        items.iter().map(|item| D::distance(item, &items[seeds[0]]).max(0.0)).collect();
+
+    while seeds.len() < k {
+        let total: f32 = distances.iter().sum();
+        let next = if total > 0.0 {
+            let mut target = rng.gen_range(0.0..total);
+            let mut next = distances.iter().rposition(|&d| d > 0.0).unwrap_or(0);
+            for (i, &distance) in distances.iter().enumerate() {
+                if target < distance {
+                    next = i;
+                    break;
+                }
+                target -= distance;
+            }
+            next
+        } else {
+            // all the items are on the seeds, any other one does
+            (0..items.len()).find(|i| !seeds.contains(i)).unwrap_or(0)
+        };
+        seeds.push(next);
+        for (distance, item) in distances.iter_mut().zip(&items) {
+            *distance = distance.min(D::distance(item, &items[next]).max(0.0));
+        }
+    }
+
+    seeds.into_iter().map(|i| sample[i].clone()).collect()
+}
+
+/// Returns the vectors of the `ids` items.
+fn vectors<D: Distance>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    ids: Vec<ItemId>,
+) -> Result<Vec<Vec<f32>>> {
+    ids.into_iter()
+        .map(|id| {
+            let vector = reader.item_vector(rtxn, id)?;
+            vector.ok_or_else(|| Error::missing_key(Key::item(reader.index(), id)))
+        })
+        .collect()
+}
+
+fn item<D: Distance>(vector: &[f32]) -> Item<'_, D> {
+    let vector: Cow<_> = UnalignedVector::from_slice(vector);
+    Item { header: D::new_header(&vector), vector }
+}
+
+/// Returns the closest of the `clusters` centroids to the `query`.
+fn closest<D: Distance>(
+    centroids: &[Item<D>],
+    query: &Item<D>,
+    clusters: impl IntoIterator<Item = usize>,
+) -> usize {
+    clusters
+        .into_iter()
+        .min_by(|&a, &b| {
+            D::distance(&centroids[a], query).total_cmp(&D::distance(&centroids[b], query))
+        })
+        .unwrap_or(0)
+}
@@ -0,0 +1,247 @@
+use std::mem::size_of;
+
+use bytemuck::{bytes_of, pod_read_unaligned};
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{RoTxn, RwTxn};
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::internals::KeyCodec;
+use crate::node::Item;
+use crate::reader::{get_build, get_item};
+use crate::unaligned_vector::{UnalignedVector, UnalignedVectorCodec};
+use crate::{Database, Error, ItemId, Key, Prefix, PrefixCodec, Result};
+
+/// The bytes before the codes: the build they were written by, the size of a code and the size
+/// of the bitmap of their items, which follows the codes. The codes stay aligned like the
+/// vectors of the overflow pages, see [`VECTOR_ALIGNMENT`](crate::node::VECTOR_ALIGNMENT).
+const CODES_HEADER: usize = 16;
+
+/// Returns `true` if the vectors of the distance are bit-packed codes, whose items are written
+/// in the contiguous region of [`write`].
+pub(crate) fn has_codes<D: Distance>() -> bool {
+    D::VectorCodec::word_size() > 1
+}
+
+/// The headers and quantized vectors of the items of an index laid out one after the other in
+/// the order of their ids, borrowed from a single LMDB value.
+///
+/// Reading an item is a rank in the bitmap of the items and a slice instead of a lookup in the
+/// b-tree, and the neighbours of the reordered indexes, whose ids are close, are read from the
+/// same pages.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Codes<'t> {
+    /// The size of the header and vector of an item.
+    stride: usize,
+    bytes: &'t [u8],
+    /// The items whose codes are stored, in the order of their positions.
+    items: &'t RoaringBitmap,
+}
+
+impl<'t> Codes<'t> {
+    /// Returns the code of an item, `None` if it isn't stored.
+    pub fn get<D: Distance>(&self, item: ItemId) -> Option<Item<'t, D>> {
+        if !self.items.contains(item) {
+            return None;
+        }
+        let start = (self.items.rank(item) - 1) as usize * self.stride;
+        let code = self.bytes.get(start..start + self.stride)?;
+        let (header, vector) = code.split_at(size_of::<D::Header>());
+        let vector = UnalignedVector::from_bytes(vector).ok()?;
+        Some(Item { header: pod_read_unaligned(header), vector })
+    }
+}
+
+/// The parts of the value of the codes.
+struct Region<'t> {
+    build: u64,
+    stride: usize,
+    codes: &'t [u8],
+    items: &'t [u8],
+}
+
+impl<'t> Region<'t> {
+    fn decode(key: Key, bytes: &'t [u8]) -> Result<Region<'t>> {
+        let invalid = || Error::unexpected_node(key, "Codes");
+        let (header, bytes) = bytes.split_at_checked(CODES_HEADER).ok_or_else(invalid)?;
+        let build = BigEndian::read_u64(header);
+        let stride = BigEndian::read_u32(&header[size_of::<u64>()..]) as usize;
+        let items = BigEndian::read_u32(&header[size_of::<u64>() + size_of::<u32>()..]) as usize;
+        let split = bytes.len().checked_sub(items).ok_or_else(invalid)?;
+        let (codes, items) = bytes.split_at(split);
+        Ok(Region { build, stride, codes, items })
+    }
+}
+
+/// Returns the region of the codes of an index if it was written by its last build.
+fn get_region<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<Region<'t>>> {
+    if !has_codes::<D>() {
+        return Ok(None);
+    }
+    let key = Key::codes(index);
+    let raw = database.remap_types::<Bytes, Bytes>();
+    let Some(bytes) = raw.get(rtxn, &key.to_bytes()).map_err(Error::on_key("get", key))? else {
+        return Ok(None);
+    };
+    let region = Region::decode(key, bytes)?;
+    if region.build != get_build(rtxn, database, index)? {
+        return Ok(None);
+    }
+    if region.stride < size_of::<D::Header>() {
+        return Err(Error::unexpected_node(key, "Codes"));
+    }
+    Ok(Some(region))
+}
+
+/// Returns the items whose codes the last build of an index wrote, which the readers keep to
+/// find the position of the items in the codes, see [`get_codes`].
+pub(crate) fn get_coded_items<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<RoaringBitmap>> {
+    let Some(region) = get_region(rtxn, database, index)? else { return Ok(None) };
+    let items = RoaringBitmap::deserialize_unchecked_from(region.items)
+        .map_err(|_| Error::unexpected_node(Key::codes(index), "Codes"))?;
+    Ok(Some(items))
+}
+
+/// Returns the codes of an index if they were written by its last build for the `items`
+/// returned by [`get_coded_items`], looked up as raw bytes so that the searches don't allocate.
+pub(crate) fn get_codes<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    database: Database<D>,
+    index: u16,
+    items: &'t RoaringBitmap,
+) -> Result<Option<Codes<'t>>> {
+    let Some(Region { stride, codes, .. }) = get_region(rtxn, database, index)? else {
+        return Ok(None);
+    };
+    Ok(Some(Codes { stride, bytes: codes, items }))
+}
+
+/// Writes the items of an index in the contiguous region read by [`get_codes`], or deletes it
+/// if the vectors of the distance aren't codes or don't all have the same size.
+///
+/// Only the codes of the `changed` items are read from their nodes, the other ones are copied
+/// from the previous region. All of them are read when `changed` is `None`. The region is kept
+/// in memory before being written, it takes about the size of the items.
+pub(crate) fn write<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    changed: Option<&RoaringBitmap>,
+) -> Result<()> {
+    let key = Key::codes(index);
+    let build = get_build(wtxn, database, index)?;
+    let codes = match has_codes::<D>() {
+        true => collect(wtxn, database, index, build, changed)?,
+        false => None,
+    };
+    match codes {
+        Some(codes) => database
+            .remap_data_type::<Bytes>()
+            .put(wtxn, &key, &codes)
+            .map_err(Error::on_key("put", key)),
+        None => {
+            database.remap_data_type::<DecodeIgnore>().delete(wtxn, &key)?;
+            Ok(())
+        }
+    }
+}
+
+/// Returns the region of the items of an index, `None` if they don't all have the same size or
+/// don't fit in an LMDB value.
+fn collect<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    build: u64,
+    changed: Option<&RoaringBitmap>,
+) -> Result<Option<Vec<u8>>> {
+    let key = Key::codes(index);
+    let raw = database.remap_types::<Bytes, Bytes>();
+    // the region of the previous build, whatever the build it was written by
+    let previous = match changed {
+        Some(_) => match raw.get(rtxn, &key.to_bytes()).map_err(Error::on_key("get", key))? {
+            Some(bytes) => {
+                let region = Region::decode(key, bytes)?;
+                let items = RoaringBitmap::deserialize_unchecked_from(region.items)
+                    .map_err(|_| Error::unexpected_node(key, "Codes"))?;
+                Some((region, items))
+            }
+            None => None,
+        },
+        None => None,
+    };
+    let mut previous_items =
+        previous.as_ref().map(|(_, items)| items.iter().enumerate().peekable());
+
+    let mut codes = vec![0; CODES_HEADER];
+    let mut items = RoaringBitmap::new();
+    let mut stride = None;
+    let prefix = Prefix::item(index);
+    let iter = database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter(rtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<KeyCodec>();
+    // the items are iterated in the order of their ids, which is the one of their codes
+    for result in iter {
+        let (key, ()) = result?;
+        let item = key.node.item;
+        items.push(item);
+
+        // the previous items are walked along with the current ones to find their positions
+        let mut position = None;
+        if let Some(previous_items) = &mut previous_items {
+            // the items deleted since the previous build are skipped
+            while previous_items.next_if(|&(_, previous)| previous < item).is_some() {}
+            position = previous_items.next_if(|&(_, previous)| previous == item).map(|(i, _)| i);
+        }
+        let kept = match (&previous, position) {
+            (Some((region, _)), Some(position))
+                if !changed.is_some_and(|changed| changed.contains(item)) =>
+            {
+                let start = position * region.stride;
+                region.codes.get(start..start + region.stride).map(|code| (region.stride, code))
+            }
+            _ => None,
+        };
+
+        let size = match kept {
+            Some((size, code)) => {
+                codes.extend_from_slice(code);
+                size
+            }
+            None => {
+                let node = get_item(database, index, rtxn, item)?
+                    .ok_or_else(|| Error::missing_key(key))?;
+                let header = bytes_of(&node.header);
+                codes.extend_from_slice(header);
+                codes.extend_from_slice(node.vector.as_bytes());
+                header.len() + node.vector.as_bytes().len()
+            }
+        };
+        if *stride.get_or_insert(size) != size {
+            return Ok(None);
+        }
+    }
+
+    let Some(stride) = stride else { return Ok(None) };
+    let items_len = items.serialized_size();
+    // LMDB values are limited to 4GiB, the searches read the items from the b-tree otherwise
+    if codes.len() + items_len > u32::MAX as usize {
+        return Ok(None);
+    }
+    items.serialize_into(&mut codes).map_err(|_| Error::unexpected_node(key, "Codes"))?;
+    BigEndian::write_u64(&mut codes, build);
+    BigEndian::write_u32(&mut codes[size_of::<u64>()..], stride as u32);
+    BigEndian::write_u32(&mut codes[size_of::<u64>() + size_of::<u32>()..], items_len as u32);
+    Ok(Some(codes))
+}
@@ -0,0 +1,135 @@
+use std::borrow::Cow;
+
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{BytesDecode, RwTxn};
+use roaring::RoaringBitmap;
+
+use crate::codes;
+use crate::density::{self, get_radii};
+use crate::distance::Distance;
+use crate::info::{get_build_info, put_build};
+use crate::lsh::{self, get_sketch_bits};
+use crate::node::{array_links, encode_array_links, ItemIds, Links, NodeCodec};
+use crate::preset::item_ids;
+use crate::reader::has_pending_updates;
+use crate::reorder::external_items;
+use crate::{Database, Error, ItemId, Key, LayerId, Metadata, MetadataCodec, Node, Result};
+
+/// Relabels the items of a built `index` with the dense ids `0..n` in the order of their current
+/// ids and returns the `(previous, new)` id of every item, see
+/// [`Writer::compact`](crate::Writer::compact).
+///
+/// The new id of an item is never greater than its previous one, the items are moved in the
+/// order of their ids so that an entry is never overwritten before it was moved itself.
+pub(crate) fn compact<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Vec<(ItemId, ItemId)>> {
+    let metadata_key = Key::metadata(index);
+    let Some(metadata) = database.remap_data_type::<MetadataCodec>().get(wtxn, &metadata_key)?
+    else {
+        return Err(Error::MissingMetadata(index));
+    };
+    if has_pending_updates(wtxn, database, index)? {
+        return Err(Error::NeedBuild(index));
+    }
+    if external_items(wtxn, database, index)?.is_some() {
+        return Err(Error::CannotCompact { index, reason: "its ids were already reordered" });
+    }
+    // the changes and the previous versions of the deleted items keep their ids
+    let raw = database.remap_data_type::<DecodeIgnore>();
+    let changes = Key::change(index, 0)..=Key::change(index, u32::MAX);
+    let history = Key::history(index, 0, 0)..=Key::history(index, ItemId::MAX, u8::MAX);
+    if raw.range(wtxn, &changes)?.next().is_some() || raw.range(wtxn, &history)?.next().is_some() {
+        return Err(Error::CannotCompact { index, reason: "its change log isn't empty" });
+    }
+
+    let dimensions = metadata.dimensions;
+    let distance = metadata.distance.to_owned();
+    let max_level = metadata.max_level;
+    let entry_points: Vec<_> = metadata.entry_points.iter().collect();
+    let graph_items = metadata.items;
+    let items = item_ids(wtxn, database, index)?;
+    let mapping: Vec<(ItemId, ItemId)> =
+        items.iter().enumerate().map(|(new, previous)| (previous, new as ItemId)).collect();
+    let relabel = |bitmap: &RoaringBitmap| -> RoaringBitmap {
+        bitmap.iter().filter_map(|item| new_id(&items, item)).collect()
+    };
+
+    let bytes = database.remap_data_type::<Bytes>();
+    for &(previous, new) in &mapping {
+        for layer in 0..=max_level as LayerId {
+            let from = Key::links(index, previous, layer);
+            let Some(links) = bytes.get(wtxn, &from).map_err(Error::on_key("get", from))? else {
+                continue;
+            };
+            let array = array_links(links).map_err(heed::Error::Decoding)?.is_some();
+            let Node::Links(Links { links }) =
+                NodeCodec::<D>::bytes_decode(links).map_err(heed::Error::Decoding)?
+            else {
+                return Err(Error::unexpected_node(from, "Links"));
+            };
+            let links = relabel(&links);
+            bytes.delete(wtxn, &from).map_err(Error::on_key("delete", from))?;
+            let to = Key::links(index, new, layer);
+            if array {
+                bytes.put(wtxn, &to, &encode_array_links(&links))
+            } else {
+                let node = Node::Links(Links { links: Cow::Owned(links) });
+                database.put(wtxn, &to, &node)
+            }
+            .map_err(Error::on_key("put", to))?;
+        }
+        if previous == new {
+            continue;
+        }
+        for key in [
+            Key::item as fn(u16, ItemId) -> Key,
+            Key::expiration,
+            Key::tombstone,
+            Key::timestamp,
+            Key::payload,
+            Key::boost,
+            Key::full_vector,
+        ] {
+            let from = key(index, previous);
+            // the copied bytes are still valid after the puts, which may move the pages around
+            let Some(value) = bytes.get(wtxn, &from)?.map(<[u8]>::to_vec) else { continue };
+            bytes.delete(wtxn, &from).map_err(Error::on_key("delete", from))?;
+            let to = key(index, new);
+            bytes.put(wtxn, &to, &value).map_err(Error::on_key("put", to))?;
+        }
+    }
+
+    // the relabeled graph is a new build for the readers and their caches
+    let info = get_build_info(wtxn, database, index)?;
+    put_build(wtxn, database, index, wtxn.id() as u64, info.as_ref())?;
+    // the contiguous regions are laid out in the order of the ids
+    codes::write(wtxn, database, index, None)?;
+    let densities = get_radii(wtxn, database, index)?.is_some();
+    density::write(wtxn, database, index, densities)?;
+    let bits = get_sketch_bits(wtxn, database, index)?;
+    lsh::write(wtxn, database, index, bits, None)?;
+
+    let entry_points: Vec<_> =
+        entry_points.iter().filter_map(|&item| new_id(&items, item)).collect();
+    let metadata = Metadata {
+        dimensions,
+        items: relabel(&graph_items),
+        distance: &distance,
+        entry_points: ItemIds::from_slice(&entry_points),
+        max_level,
+    };
+    database
+        .remap_data_type::<MetadataCodec>()
+        .put(wtxn, &metadata_key, &metadata)
+        .map_err(Error::on_key("put", metadata_key))?;
+
+    Ok(mapping)
+}
+
+/// Returns the dense id of an item, its rank among the `items`.
+fn new_id(items: &RoaringBitmap, item: ItemId) -> Option<ItemId> {
+    items.contains(item).then(|| items.rank(item) as ItemId - 1)
+}
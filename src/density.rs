@@ -0,0 +1,90 @@
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::internals::KeyCodec;
+use crate::reader::{get_item, get_links, tombstones};
+use crate::{Database, Error, ItemId, Key, Node, Prefix, PrefixCodec, Result};
+
+/// The mean distance of the items to their neighbours on the bottom layer of the graph, one
+/// `f32` per item laid out in the order of their ids, borrowed from a single LMDB value.
+///
+/// It measures how dense the indexed vectors are around every item: a query whose closest items
+/// are much further away than their own neighbours falls outside of the indexed distribution.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Radii<'t> {
+    bytes: &'t [u8],
+}
+
+impl Radii<'_> {
+    /// Returns the mean distance of `item` to its neighbours, `None` if it had none when the
+    /// index was built.
+    pub fn get(&self, item: ItemId) -> Option<f32> {
+        let start = item as usize * size_of::<f32>();
+        let bytes = self.bytes.get(start..start + size_of::<f32>())?;
+        let radius = f32::from_ne_bytes(bytes.try_into().unwrap());
+        (!radius.is_nan()).then_some(radius)
+    }
+}
+
+/// Returns the radii of the items of an index, if its last build recorded them.
+pub(crate) fn get_radii<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<Radii<'t>>> {
+    let key = Key::densities(index);
+    let bytes =
+        database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))?;
+    Ok(bytes.map(|bytes| Radii { bytes }))
+}
+
+/// Writes the radii of the items of a built index in the contiguous region read by
+/// [`get_radii`], or deletes it if it isn't `enabled`. The items without any neighbour are given
+/// `NaN`.
+pub(crate) fn write<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    enabled: bool,
+) -> Result<()> {
+    let key = Key::densities(index);
+    if !enabled {
+        database.remap_data_type::<DecodeIgnore>().delete(wtxn, &key)?;
+        return Ok(());
+    }
+    let tombstones = tombstones(wtxn, database, index)?;
+    let mut radii = Vec::new();
+    // the items are iterated in the order of their ids
+    let prefix = Prefix::item(index);
+    let iter = database
+        .remap_key_type::<PrefixCodec>()
+        .prefix_iter(wtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<KeyCodec>();
+    for result in iter {
+        let (key, node) = result?;
+        let Node::Item(item) = node else { return Err(Error::unexpected_node(key, "Item")) };
+        let item_id = key.node.item;
+        if tombstones.contains(item_id) {
+            continue;
+        }
+        let Some(links) = get_links(wtxn, database, index, item_id, 0)? else { continue };
+        let (mut sum, mut count) = (0.0, 0);
+        for link in links.iter().filter(|&link| link != item_id && !tombstones.contains(link)) {
+            if let Some(neighbour) = get_item(database, index, wtxn, link)? {
+                sum += D::distance(&item, &neighbour) as f64;
+                count += 1;
+            }
+        }
+        if count > 0 {
+            radii.resize(item_id as usize, f32::NAN);
+            radii.push((sum / count as f64) as f32);
+        }
+    }
+
+    database
+        .remap_data_type::<Bytes>()
+        .put(wtxn, &key, bytemuck::cast_slice(&radii))
+        .map_err(Error::on_key("put", key))
+}
@@ -19,6 +19,7 @@ pub enum BinaryQuantizedCosine {}
 #[repr(C)]
 #[derive(Pod, Zeroable, Clone, Copy)]
 pub struct NodeHeaderBinaryQuantizedCosine {
+    /// The norm of the quantized vector, computed at write time.
     norm: f32,
 }
 impl fmt::Debug for NodeHeaderBinaryQuantizedCosine {
@@ -58,6 +59,15 @@ impl Distance for BinaryQuantizedCosine {
         }
     }
 
+    fn similarity(distance: f32) -> f32 {
+        // the distance is (1 - cos) / 2
+        1.0 - 2.0 * distance
+    }
+
+    fn norm(item: &Item<Self>) -> f32 {
+        item.header.norm
+    }
+
     fn norm_no_header(v: &UnalignedVector<Self::VectorCodec>) -> f32 {
         dot_product_binary_quantized(v, v).sqrt()
     }
@@ -0,0 +1,42 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::unaligned_vector::UnalignedVector;
+
+/// The Bray–Curtis dissimilarity between two points is the sum of the absolute differences of
+/// their coordinates over the sum of their coordinates, for the abundance vectors of ecology and
+/// microbiome profiles. It ranges from `0.0` to `1.0` for the points of non-negative coordinates.
+///
+/// `d(p, q) = sum(|p - q|) / sum(|p + q|)`, two origins are at a distance of `0.0`.
+#[derive(Debug, Clone)]
+pub enum BrayCurtis {}
+
+/// The header of BrayCurtis item nodes, empty since the distance only reads the vectors.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+pub struct NodeHeaderBrayCurtis {}
+
+impl Distance for BrayCurtis {
+    type Header = NodeHeaderBrayCurtis;
+    type VectorCodec = f32;
+
+    fn name() -> &'static str {
+        "bray_curtis"
+    }
+
+    fn new_header(_vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderBrayCurtis {}
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        let (differences, sums) =
+            p.vector.iter().zip(q.vector.iter()).fold((0.0, 0.0), |(differences, sums), (p, q)| {
+                (differences + (p - q).abs(), sums + (p + q).abs())
+            });
+        match sums {
+            0.0 => 0.0,
+            sums => differences / sums,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::unaligned_vector::UnalignedVector;
+
+/// The Canberra distance between two points is the sum of the absolute differences of their
+/// coordinates, each weighted by the magnitude of the coordinates. It is very sensitive to the
+/// small differences near zero, which makes it a good fit for the sparse count-based features.
+///
+/// `d(p, q) = sum(|p - q| / (|p| + |q|))`, the coordinates that are both zero don't count.
+#[derive(Debug, Clone)]
+pub enum Canberra {}
+
+/// The header of Canberra item nodes, empty since the distance only reads the vectors.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+pub struct NodeHeaderCanberra {}
+
+impl Distance for Canberra {
+    type Header = NodeHeaderCanberra;
+    type VectorCodec = f32;
+
+    fn name() -> &'static str {
+        "canberra"
+    }
+
+    fn new_header(_vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderCanberra {}
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        p.vector
+            .iter()
+            .zip(q.vector.iter())
+            .map(|(p, q)| match p.abs() + q.abs() {
+                0.0 => 0.0,
+                magnitude => (p - q).abs() / magnitude,
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,35 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::unaligned_vector::UnalignedVector;
+
+/// The Chebyshev distance, or L∞ distance, between two points is the greatest of the absolute
+/// differences of their coordinates, for the bounding and threshold-like similarities where a
+/// single feature out of range is enough to tell the points apart.
+///
+/// `d(p, q) = max(|p - q|)`
+#[derive(Debug, Clone)]
+pub enum Chebyshev {}
+
+/// The header of Chebyshev item nodes, empty since the distance only reads the vectors.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+pub struct NodeHeaderChebyshev {}
+
+impl Distance for Chebyshev {
+    type Header = NodeHeaderChebyshev;
+    type VectorCodec = f32;
+
+    fn name() -> &'static str {
+        "chebyshev"
+    }
+
+    fn new_header(_vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderChebyshev {}
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        p.vector.iter().zip(q.vector.iter()).map(|(p, q)| (p - q).abs()).fold(0.0, f32::max)
+    }
+}
@@ -4,12 +4,15 @@ use bytemuck::{Pod, Zeroable};
 
 use crate::distance::Distance;
 use crate::node::Item;
-use crate::spaces::simple::dot_product;
+use crate::spaces::simple::{dot_product, dot_product_x2};
 use crate::unaligned_vector::UnalignedVector;
 
 /// The Cosine similarity is a measure of similarity between two
 /// non-zero vectors defined in an inner product space. Cosine similarity
 /// is the cosine of the angle between the vectors.
+///
+/// The norm of the items is computed once when they are written and stored in their header,
+/// the distance between a query and an item is then a single dot product.
 #[derive(Debug, Clone)]
 pub enum Cosine {}
 
@@ -17,6 +20,7 @@ pub enum Cosine {}
 #[repr(C)]
 #[derive(Pod, Zeroable, Clone, Copy)]
 pub struct NodeHeaderCosine {
+    /// The norm of the vector, computed at write time.
     norm: f32,
 }
 impl fmt::Debug for NodeHeaderCosine {
@@ -38,24 +42,48 @@ impl Distance for Cosine {
     }
 
     fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
-        let pn = p.header.norm;
-        let qn = q.header.norm;
-        let pq = dot_product(&p.vector, &q.vector);
-        let pnqn = pn * qn;
-        if pnqn > f32::EPSILON {
-            let cos = pq / pnqn;
-            let cos = cos.clamp(-1.0, 1.0);
-            // cos is [-1; 1]
-            // cos =  0. -> 0.5
-            // cos = -1. -> 1.0
-            // cos =  1. -> 0.0
-            (1.0 - cos) / 2.0
-        } else {
-            0.0
+        cosine_distance(dot_product(&p.vector, &q.vector), p.header.norm, q.header.norm)
+    }
+
+    fn distances(query: &Item<Self>, items: &[Item<Self>], distances: &mut Vec<f32>) {
+        let mut pairs = items.chunks_exact(2);
+        for pair in &mut pairs {
+            let dots = dot_product_x2(&query.vector, [&pair[0].vector, &pair[1].vector]);
+            distances.extend(
+                dots.into_iter()
+                    .zip(pair)
+                    .map(|(pq, item)| cosine_distance(pq, query.header.norm, item.header.norm)),
+            );
         }
+        distances.extend(pairs.remainder().iter().map(|item| Self::distance(query, item)));
+    }
+
+    fn similarity(distance: f32) -> f32 {
+        // the distance is (1 - cos) / 2
+        1.0 - 2.0 * distance
+    }
+
+    fn norm(item: &Item<Self>) -> f32 {
+        item.header.norm
     }
 
     fn norm_no_header(v: &UnalignedVector<Self::VectorCodec>) -> f32 {
         dot_product(v, v).sqrt()
     }
 }
+
+/// Turns the dot product `pq` of two vectors of norms `pn` and `qn` into their cosine distance.
+fn cosine_distance(pq: f32, pn: f32, qn: f32) -> f32 {
+    let pnqn = pn * qn;
+    if pnqn > f32::EPSILON {
+        let cos = pq / pnqn;
+        let cos = cos.clamp(-1.0, 1.0);
+        // cos is [-1; 1]
+        // cos =  0. -> 0.5
+        // cos = -1. -> 1.0
+        // cos =  1. -> 0.0
+        (1.0 - cos) / 2.0
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,57 @@
+use std::borrow::Cow;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::unaligned_vector::UnalignedVector;
+
+/// The Earth Mover's distance between two histograms, the least mass to move times the number
+/// of bins it's moved by to turn one into the other, for the histogram-like vectors whose
+/// adjacent bins are close.
+///
+/// `d(p, q) = sum(|cumsum(p) - cumsum(q)|)`
+///
+/// The histograms are normalized to sum to one and turned into their cumulative sums once when
+/// they are written and searched, the distance between these is the Manhattan one, which is
+/// exact for one-dimensional histograms whose bins are in order. The vectors returned by the
+/// readers are the cumulative sums and the negative bins count as empty.
+#[derive(Debug, Clone)]
+pub enum EarthMovers {}
+
+/// The header of EarthMovers item nodes, empty since the distance only reads the vectors.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+pub struct NodeHeaderEarthMovers {}
+
+impl Distance for EarthMovers {
+    type Header = NodeHeaderEarthMovers;
+    type VectorCodec = f32;
+
+    fn name() -> &'static str {
+        "earth_movers"
+    }
+
+    fn preprocess(vector: &[f32]) -> Cow<'_, [f32]> {
+        let total: f32 = vector.iter().map(|x| x.max(0.0)).sum();
+        let scale = if total > 0.0 { total.recip() } else { 0.0 };
+        let mut mass = 0.0;
+        Cow::Owned(
+            vector
+                .iter()
+                .map(|x| {
+                    mass += x.max(0.0) * scale;
+                    mass
+                })
+                .collect(),
+        )
+    }
+
+    fn new_header(_vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderEarthMovers {}
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        p.vector.iter().zip(q.vector.iter()).map(|(p, q)| (p - q).abs()).sum()
+    }
+}
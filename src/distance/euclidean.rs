@@ -4,7 +4,7 @@ use bytemuck::{Pod, Zeroable};
 
 use crate::distance::Distance;
 use crate::node::Item;
-use crate::spaces::simple::{dot_product, euclidean_distance};
+use crate::spaces::simple::{dot_product, euclidean_distance, euclidean_distance_x2};
 use crate::unaligned_vector::UnalignedVector;
 
 /// The Euclidean distance between two points in Euclidean space
@@ -43,6 +43,15 @@ impl Distance for Euclidean {
         euclidean_distance(&p.vector, &q.vector)
     }
 
+    fn distances(query: &Item<Self>, items: &[Item<Self>], distances: &mut Vec<f32>) {
+        let mut pairs = items.chunks_exact(2);
+        for pair in &mut pairs {
+            distances
+                .extend(euclidean_distance_x2(&query.vector, [&pair[0].vector, &pair[1].vector]));
+        }
+        distances.extend(pairs.remainder().iter().map(|item| Self::distance(query, item)));
+    }
+
     fn norm_no_header(v: &UnalignedVector<Self::VectorCodec>) -> f32 {
         dot_product(v, v).sqrt()
     }
@@ -0,0 +1,57 @@
+use std::borrow::Cow;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::spaces::simple::{euclidean_distance, euclidean_distance_x2};
+use crate::unaligned_vector::UnalignedVector;
+
+/// The Hellinger distance between two discrete probability distributions, like the topic or
+/// k-mer frequency profiles, is the Euclidean distance between their square roots scaled down
+/// to range from `0.0` to `1.0`.
+///
+/// `d(p, q) = sqrt((sqrt(p) - sqrt(q))² / 2)`
+///
+/// The vectors are normalized to sum to one and square-rooted once when they are written and
+/// searched, so that the distance itself is a Euclidean distance. The vectors returned by the
+/// readers are these square roots and the negative frequencies count as zero.
+#[derive(Debug, Clone)]
+pub enum Hellinger {}
+
+/// The header of Hellinger item nodes, empty since the distance only reads the vectors.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+pub struct NodeHeaderHellinger {}
+
+impl Distance for Hellinger {
+    type Header = NodeHeaderHellinger;
+    type VectorCodec = f32;
+
+    fn name() -> &'static str {
+        "hellinger"
+    }
+
+    fn preprocess(vector: &[f32]) -> Cow<'_, [f32]> {
+        let total: f32 = vector.iter().map(|x| x.max(0.0)).sum();
+        let scale = if total > 0.0 { total.recip() } else { 0.0 };
+        Cow::Owned(vector.iter().map(|x| (x.max(0.0) * scale).sqrt()).collect())
+    }
+
+    fn new_header(_vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderHellinger {}
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        (euclidean_distance(&p.vector, &q.vector) / 2.0).sqrt()
+    }
+
+    fn distances(query: &Item<Self>, items: &[Item<Self>], distances: &mut Vec<f32>) {
+        let mut pairs = items.chunks_exact(2);
+        for pair in &mut pairs {
+            let pair = euclidean_distance_x2(&query.vector, [&pair[0].vector, &pair[1].vector]);
+            distances.extend(pair.map(|distance| (distance / 2.0).sqrt()));
+        }
+        distances.extend(pairs.remainder().iter().map(|item| Self::distance(query, item)));
+    }
+}
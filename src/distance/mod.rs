@@ -1,13 +1,24 @@
+use std::borrow::Cow;
 use std::fmt;
 
 pub use binary_quantized_cosine::{BinaryQuantizedCosine, NodeHeaderBinaryQuantizedCosine};
 pub use binary_quantized_euclidean::BinaryQuantizedEuclidean;
 pub use binary_quantized_manhattan::BinaryQuantizedManhattan;
+pub use bray_curtis::BrayCurtis;
 use bytemuck::{Pod, Zeroable};
+pub use canberra::Canberra;
+pub use chebyshev::Chebyshev;
 pub use cosine::{Cosine, NodeHeaderCosine};
+pub use earth_movers::EarthMovers;
 pub use euclidean::{Euclidean, NodeHeaderEuclidean};
 pub use hamming::Hamming;
+pub use hellinger::Hellinger;
 pub use manhattan::Manhattan;
+pub use poincare::Poincare;
+pub use sparse::{NodeHeaderSparse, SparseCosine, SparseDotProduct};
+pub use u8_euclidean::U8Euclidean;
+pub use u8_manhattan::U8Manhattan;
+pub use weighted_euclidean::WeightedEuclidean;
 
 use crate::node::Item;
 use crate::unaligned_vector::{UnalignedVector, UnalignedVectorCodec};
@@ -15,17 +26,27 @@ use crate::unaligned_vector::{UnalignedVector, UnalignedVectorCodec};
 mod binary_quantized_cosine;
 mod binary_quantized_euclidean;
 mod binary_quantized_manhattan;
+mod bray_curtis;
+mod canberra;
+mod chebyshev;
 mod cosine;
+mod earth_movers;
 mod euclidean;
 mod hamming;
+mod hellinger;
 mod manhattan;
+mod poincare;
+mod sparse;
+mod u8_euclidean;
+mod u8_manhattan;
+mod weighted_euclidean;
 
 /// A trait used by hannoy to compute the distances,
 /// compute the split planes, and normalize user vectors.
 #[allow(missing_docs)]
 pub trait Distance: Send + Sync + Sized + Clone + fmt::Debug + 'static {
     /// A header structure with informations related to the
-    type Header: Pod + Zeroable + fmt::Debug;
+    type Header: Pod + Zeroable + fmt::Debug + Send + Sync;
     type VectorCodec: UnalignedVectorCodec;
 
     /// The name of the distance.
@@ -35,14 +56,46 @@ pub trait Distance: Send + Sync + Sized + Clone + fmt::Debug + 'static {
     /// recomputing links when moving from the former to the latter distance.
     fn name() -> &'static str;
 
+    /// Transforms the vectors given to the writer and the queries before they are stored or
+    /// compared, the default keeps them as they are.
+    ///
+    /// The distances defined over a transform of the vectors, like [`Hellinger`], apply it
+    /// here once instead of in every distance computation. The vectors returned by the readers
+    /// are the transformed ones.
+    fn preprocess(vector: &[f32]) -> Cow<'_, [f32]> {
+        Cow::Borrowed(vector)
+    }
+
     fn new_header(vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header;
 
     /// Returns a non-normalized distance.
     fn distance(p: &Item<Self>, q: &Item<Self>) -> f32;
 
+    /// Converts a [`Self::distance`] into a similarity, higher is closer, see
+    /// [`ScoreKind::Similarity`](crate::ScoreKind::Similarity). Defaults to `1 - distance`,
+    /// which is only bounded for the distances that are, and is negative past a distance of `1`.
+    fn similarity(distance: f32) -> f32 {
+        1.0 - distance
+    }
+
+    /// Appends the [`Self::distance`]s between the `query` and every one of the `items`, in
+    /// order, to `distances`.
+    ///
+    /// The searches compute the distances of the neighbours of an item as a block, a distance can
+    /// override this method to compute several of them at once with a kernel that reads the
+    /// query once. The results must be the same as the ones of [`Self::distance`].
+    fn distances(query: &Item<Self>, items: &[Item<Self>], distances: &mut Vec<f32>) {
+        distances.extend(items.iter().map(|item| Self::distance(query, item)));
+    }
+
+    /// Returns the norm of an item, the distances that store it in their header at write time
+    /// override this method to read it instead of going through the vector again.
     fn norm(item: &Item<Self>) -> f32 {
         Self::norm_no_header(&item.vector)
     }
 
-    fn norm_no_header(v: &UnalignedVector<Self::VectorCodec>) -> f32;
+    /// Returns the Euclidean norm of a vector.
+    fn norm_no_header(v: &UnalignedVector<Self::VectorCodec>) -> f32 {
+        v.iter().map(|x| x * x).sum::<f32>().sqrt()
+    }
 }
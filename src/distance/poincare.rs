@@ -0,0 +1,81 @@
+use std::fmt;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::spaces::simple::{dot_product, euclidean_distance, euclidean_distance_x2};
+use crate::unaligned_vector::UnalignedVector;
+
+/// The largest squared norm the distances are computed with, they grow to infinity at the
+/// boundary of the ball.
+const MAX_SQUARED_NORM: f32 = 1.0 - 1e-5;
+
+/// The hyperbolic distance between two points of the Poincaré ball, the unit ball in which
+/// the hierarchies are embedded with their roots near the origin and their leaves near the
+/// boundary.
+///
+/// `d(p, q) = arcosh(1 + 2 * (p - q)² / ((1 - p²) * (1 - q²)))`
+///
+/// The points are expected inside the unit ball. The gap to the boundary of the ones on it or
+/// outside is clamped, for their distances to stay finite instead of overflowing.
+#[derive(Debug, Clone)]
+pub enum Poincare {}
+
+/// The header of Poincare item nodes.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy)]
+pub struct NodeHeaderPoincare {
+    /// `1 / (1 - p²)`, computed at write time.
+    inverse_gap: f32,
+}
+impl fmt::Debug for NodeHeaderPoincare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeHeaderPoincare")
+            .field("inverse_gap", &format!("{:.4}", self.inverse_gap))
+            .finish()
+    }
+}
+
+impl Distance for Poincare {
+    type Header = NodeHeaderPoincare;
+    type VectorCodec = f32;
+
+    fn name() -> &'static str {
+        "poincare"
+    }
+
+    fn new_header(vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        let squared_norm = dot_product(vector, vector).min(MAX_SQUARED_NORM);
+        NodeHeaderPoincare { inverse_gap: (1.0 - squared_norm).recip() }
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        poincare_distance(euclidean_distance(&p.vector, &q.vector), p.header, q.header)
+    }
+
+    fn distances(query: &Item<Self>, items: &[Item<Self>], distances: &mut Vec<f32>) {
+        let mut pairs = items.chunks_exact(2);
+        for pair in &mut pairs {
+            let squares = euclidean_distance_x2(&query.vector, [&pair[0].vector, &pair[1].vector]);
+            distances.extend(
+                squares
+                    .into_iter()
+                    .zip(pair)
+                    .map(|(square, item)| poincare_distance(square, query.header, item.header)),
+            );
+        }
+        distances.extend(pairs.remainder().iter().map(|item| Self::distance(query, item)));
+    }
+
+    fn norm_no_header(v: &UnalignedVector<Self::VectorCodec>) -> f32 {
+        dot_product(v, v).sqrt()
+    }
+}
+
+/// Turns the squared Euclidean distance between two points of the ball into their hyperbolic
+/// distance.
+fn poincare_distance(square: f32, p: NodeHeaderPoincare, q: NodeHeaderPoincare) -> f32 {
+    // the rounding errors must not bring the argument under one, where arcosh isn't defined
+    (1.0 + 2.0 * square * p.inverse_gap * q.inverse_gap).max(1.0).acosh()
+}
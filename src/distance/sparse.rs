@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::unaligned_vector::{Sparse, UnalignedVector};
+
+/// The cosine distance between sparse vectors, e.g. SPLADE embeddings, only the non-zero values
+/// of the vectors are stored and compared.
+///
+/// See [`Cosine`](crate::distances::Cosine) for the dense vectors.
+#[derive(Debug, Clone)]
+pub enum SparseCosine {}
+
+/// The dot product between sparse vectors turned into a distance, the larger the dot product the
+/// closer the vectors.
+///
+/// `d(u,v) = 1 / (1 + u·v)` for a non-negative dot product, `1 - u·v` otherwise. The distance
+/// decreases with the dot product, which is what learned sparse retrieval models score with.
+#[derive(Debug, Clone)]
+pub enum SparseDotProduct {}
+
+/// The header of sparse Item nodes.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy)]
+pub struct NodeHeaderSparse {
+    /// The norm of the vector, computed at write time.
+    norm: f32,
+}
+impl fmt::Debug for NodeHeaderSparse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeHeaderSparse").field("norm", &format!("{:.4}", self.norm)).finish()
+    }
+}
+
+impl Distance for SparseCosine {
+    type Header = NodeHeaderSparse;
+    type VectorCodec = Sparse;
+
+    fn name() -> &'static str {
+        "sparse cosine"
+    }
+
+    fn new_header(vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderSparse { norm: Self::norm_no_header(vector) }
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        let pnqn = p.header.norm * q.header.norm;
+        if pnqn > f32::EPSILON {
+            let cos = (sparse_dot_product(&p.vector, &q.vector) / pnqn).clamp(-1.0, 1.0);
+            (1.0 - cos) / 2.0
+        } else {
+            0.0
+        }
+    }
+
+    fn similarity(distance: f32) -> f32 {
+        // the distance is (1 - cos) / 2
+        1.0 - 2.0 * distance
+    }
+
+    fn norm(item: &Item<Self>) -> f32 {
+        item.header.norm
+    }
+
+    fn norm_no_header(v: &UnalignedVector<Self::VectorCodec>) -> f32 {
+        v.entries().map(|(_, value)| value * value).sum::<f32>().sqrt()
+    }
+}
+
+impl Distance for SparseDotProduct {
+    type Header = NodeHeaderSparse;
+    type VectorCodec = Sparse;
+
+    fn name() -> &'static str {
+        "sparse dot product"
+    }
+
+    fn new_header(vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderSparse { norm: Self::norm_no_header(vector) }
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        let dot = sparse_dot_product(&p.vector, &q.vector);
+        if dot >= 0.0 {
+            1.0 / (1.0 + dot)
+        } else {
+            1.0 - dot
+        }
+    }
+
+    fn norm(item: &Item<Self>) -> f32 {
+        item.header.norm
+    }
+
+    fn norm_no_header(v: &UnalignedVector<Self::VectorCodec>) -> f32 {
+        v.entries().map(|(_, value)| value * value).sum::<f32>().sqrt()
+    }
+}
+
+/// Returns the dot product of two sparse vectors by walking their sorted indices together.
+pub fn sparse_dot_product(u: &UnalignedVector<Sparse>, v: &UnalignedVector<Sparse>) -> f32 {
+    let mut u = u.entries();
+    let mut v = v.entries();
+    let (mut a, mut b) = (u.next(), v.next());
+    let mut dot = 0.0;
+    while let (Some((i, x)), Some((j, y))) = (a, b) {
+        match i.cmp(&j) {
+            Ordering::Less => a = u.next(),
+            Ordering::Greater => b = v.next(),
+            Ordering::Equal => {
+                dot += x * y;
+                a = u.next();
+                b = v.next();
+            }
+        }
+    }
+    dot
+}
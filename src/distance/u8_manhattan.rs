@@ -0,0 +1,41 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::unaligned_vector::UnalignedVector;
+
+/// The Manhattan distance between vectors of bytes, stored at one byte per dimension, for the
+/// image hashes and the already-quantized embeddings. The differences are summed as integers.
+///
+/// `d(p, q) = sum(|p - q|)`
+#[derive(Debug, Clone)]
+pub enum U8Manhattan {}
+
+/// The header of U8Manhattan item nodes, empty since the distance only reads the vectors.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+pub struct NodeHeaderU8Manhattan {}
+
+impl Distance for U8Manhattan {
+    type Header = NodeHeaderU8Manhattan;
+    type VectorCodec = u8;
+
+    fn name() -> &'static str {
+        "u8_manhattan"
+    }
+
+    fn new_header(_vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderU8Manhattan {}
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        let differences: u64 = p
+            .vector
+            .values()
+            .iter()
+            .zip(q.vector.values())
+            .map(|(&p, &q)| u64::from(p.abs_diff(q)))
+            .sum();
+        differences as f32
+    }
+}
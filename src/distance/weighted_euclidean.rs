@@ -0,0 +1,50 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::spaces::simple::{euclidean_distance, euclidean_distance_x2};
+use crate::unaligned_vector::UnalignedVector;
+
+/// The weighted Euclidean distance between two points gives every dimension an importance,
+/// the weights of the index, set with
+/// [`Writer::set_weights`](crate::Writer::set_weights) before adding its items.
+///
+/// `d(p, q) = sqrt(w * (p - q)²)`
+///
+/// The vectors are scaled by the square roots of the weights once, when they are written and
+/// searched, so that the kernel is the one of [`Euclidean`](crate::distances::Euclidean) and
+/// the features don't have to be scaled beforehand. The vectors returned by the readers are the
+/// scaled ones. An index without weights weighs every dimension the same.
+#[derive(Debug, Clone)]
+pub enum WeightedEuclidean {}
+
+/// The header of WeightedEuclidean item nodes, empty since the distance only reads the vectors.
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+pub struct NodeHeaderWeightedEuclidean {}
+
+impl Distance for WeightedEuclidean {
+    type Header = NodeHeaderWeightedEuclidean;
+    type VectorCodec = f32;
+
+    fn name() -> &'static str {
+        "weighted_euclidean"
+    }
+
+    fn new_header(_vector: &UnalignedVector<Self::VectorCodec>) -> Self::Header {
+        NodeHeaderWeightedEuclidean {}
+    }
+
+    fn distance(p: &Item<Self>, q: &Item<Self>) -> f32 {
+        euclidean_distance(&p.vector, &q.vector)
+    }
+
+    fn distances(query: &Item<Self>, items: &[Item<Self>], distances: &mut Vec<f32>) {
+        let mut pairs = items.chunks_exact(2);
+        for pair in &mut pairs {
+            distances
+                .extend(euclidean_distance_x2(&query.vector, [&pair[0].vector, &pair[1].vector]));
+        }
+        distances.extend(pairs.remainder().iter().map(|item| Self::distance(query, item)));
+    }
+}
@@ -1,6 +1,9 @@
 use std::io;
 
-use crate::{key::Key, node_id::NodeMode, version::Version, ItemId, LayerId};
+use crate::key::{Key, Prefix};
+use crate::node_id::NodeMode;
+use crate::version::Version;
+use crate::{ItemId, LayerId};
 
 /// The different set of errors that hannoy can encounter.
 #[derive(Debug, thiserror::Error)]
@@ -13,19 +16,248 @@ pub enum Error {
     #[error(transparent)]
     Io(#[from] io::Error),
 
-    /// The user is trying to insert or search for a vector that is not of the right dimensions.
-    #[error("Invalid vector dimensions. Got {received} but expected {expected}")]
-    InvalidVecDimension {
+    /// An error happened while reading a stream of Arrow record batches.
+    #[cfg(feature = "arrow")]
+    #[error(transparent)]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    /// An error happened while reading a Parquet file.
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    Parquet(#[from] ::parquet::errors::ParquetError),
+
+    /// The user is trying to insert or search for a vector that is not of the right dimensions,
+    /// or writes to an index with a writer created with other dimensions.
+    #[error("Invalid dimensions. Got {got} but expected {expected}")]
+    InvalidDimensions {
         /// The expected number of dimensions.
         expected: usize,
         /// The dimensions given by the user.
-        received: usize,
+        got: usize,
+    },
+
+    /// The user is trying to insert a vector containing a `NaN` or an infinite value.
+    #[error("Item {item} contains the non-finite value {value} at position {position}")]
+    NonFiniteVector {
+        /// The item the vector was inserted for.
+        item: ItemId,
+        /// The position of the first non-finite value in the vector.
+        position: usize,
+        /// The non-finite value itself.
+        value: f32,
+    },
+
+    /// The user is trying to insert a sparse vector whose indices or values are invalid.
+    #[error("Invalid sparse vector for item {item}: {reason}")]
+    InvalidSparseVector {
+        /// The item the vector was inserted for.
+        item: ItemId,
+        /// What is wrong with the vector.
+        reason: String,
+    },
+
+    /// The user is trying to insert the vectors of an item that cannot be stored with a
+    /// [`MultiVector`](crate::MultiVector) layout.
+    #[error("Invalid multi-vector item {item}: {reason}")]
+    InvalidMultiVectorItem {
+        /// The item the vectors were inserted for.
+        item: ItemId,
+        /// Why the vectors cannot be stored.
+        reason: String,
+    },
+
+    /// The user is trying to search with a sparse vector whose indices or values are invalid.
+    #[error("Invalid sparse query vector: {reason}")]
+    InvalidSparseQuery {
+        /// What is wrong with the vector.
+        reason: String,
+    },
+
+    /// The user is trying to weigh the dimensions of an index with invalid weights, see
+    /// [`Writer::set_weights`](crate::Writer::set_weights).
+    #[error("Invalid weights: {reason}")]
+    InvalidWeights {
+        /// What is wrong with the weights.
+        reason: String,
+    },
+
+    /// The user is trying to boost an item by a factor that isn't positive and finite, see
+    /// [`Writer::set_boost`](crate::Writer::set_boost).
+    #[error("Invalid boost {boost} for item {item}, it must be positive and finite")]
+    InvalidBoost {
+        /// The item the boost was given to.
+        item: ItemId,
+        /// The invalid boost.
+        boost: f32,
+    },
+
+    /// The user is trying to store the values of an attribute whose name is empty or contains a
+    /// `NUL` byte, see [`Attributes`](crate::Attributes).
+    #[error("Invalid attribute name {attribute:?}, it must be non-empty and without NUL bytes")]
+    InvalidAttribute {
+        /// The invalid name.
+        attribute: String,
+    },
+
+    /// A dimensionality reduction couldn't be fitted or doesn't fit the index, see
+    /// [`Writer::set_projection`](crate::Writer::set_projection).
+    #[error("Invalid projection: {reason}")]
+    InvalidProjection {
+        /// What is wrong with the projection.
+        reason: String,
+    },
+
+    /// The prefixes of a Matryoshka index don't fit its vectors, see
+    /// [`Writer::set_matryoshka`](crate::Writer::set_matryoshka).
+    #[error("Invalid Matryoshka index: {reason}")]
+    InvalidMatryoshka {
+        /// What is wrong with the prefixes.
+        reason: String,
+    },
+
+    /// A column of an imported dataset is missing or of an unsupported type.
+    #[error("Invalid column `{column}`: {reason}")]
+    InvalidColumn {
+        /// The name of the column.
+        column: String,
+        /// What is wrong with the column.
+        reason: String,
+    },
+
+    /// A NumPy file is malformed or contains an unsupported array.
+    #[error("Invalid npy file: {reason}")]
+    InvalidNpy {
+        /// What is wrong with the file.
+        reason: String,
+    },
+
+    /// A NumPy `.npz` archive is malformed or uses an unsupported part of the zip format.
+    #[cfg(feature = "npz")]
+    #[error("Invalid npz archive: {reason}")]
+    InvalidNpz {
+        /// What is wrong with the archive.
+        reason: String,
+    },
+
+    /// An HDF5 file is malformed or uses an unsupported part of the format.
+    #[cfg(feature = "hdf5")]
+    #[error("Invalid HDF5 file: {reason}")]
+    InvalidHdf5 {
+        /// What is wrong with the file.
+        reason: String,
+    },
+
+    /// A line of a JSON Lines file is not a valid item.
+    #[cfg(feature = "jsonl")]
+    #[error("Invalid item at line {line}: {source}")]
+    InvalidJsonLine {
+        /// The line number, starting at 1.
+        line: usize,
+        /// The error returned by the JSON parser.
+        #[source]
+        source: serde_json::Error,
     },
 
     /// An internal error returned when hannoy cannot generate internal IDs.
     #[error("Database full. Hannoy cannot generate enough internal IDs for your items")]
     DatabaseFull,
 
+    /// A replica fell behind the change log of its leader, which was truncated past the changes
+    /// it didn't apply yet.
+    #[error("The changes from {expected} were truncated from the log before being applied, the first one left is {first} and the replica must be rebuilt")]
+    ChangesTruncated {
+        /// The sequence number of the next change the replica expected.
+        expected: u32,
+        /// The sequence number of the first change still in the log.
+        first: u32,
+    },
+
+    /// The change log of an index used all of its sequence numbers.
+    #[error("The change log of index {0} ran out of sequence numbers")]
+    ChangeLogExhausted(u16),
+
+    /// The version of an item at an older sequence number was overwritten by its more recent
+    /// versions, see [`Writer::set_versions`](crate::Writer::set_versions).
+    #[cfg(feature = "versioning")]
+    #[error("The version of item {item} at change {sequence} is not kept anymore")]
+    VersionPruned {
+        /// The item whose version is missing.
+        item: ItemId,
+        /// The sequence number the version was requested at.
+        sequence: u32,
+    },
+
+    /// No GPU could run the distances, or one of their dispatches failed, see
+    /// [`GpuDistances`](crate::gpu::GpuDistances).
+    #[cfg(feature = "gpu")]
+    #[error("GPU distances unavailable: {reason}")]
+    Gpu {
+        /// Why the distances can't be computed on the GPU.
+        reason: String,
+    },
+
+    /// Every index of the database is used, there is none to build a sample or a rebuilt graph in.
+    #[error("Every index of the database is used, there is no free index to build in")]
+    NoFreeIndex,
+
+    /// The graph can't be built with this number of links per node, see
+    /// [`HannoyBuilder::build_with_links`](crate::HannoyBuilder::build_with_links).
+    #[error(
+        "Unsupported number of links per node {0}, use one of {supported:?}",
+        supported = crate::SUPPORTED_LINKS
+    )]
+    UnsupportedLinks(usize),
+
+    /// Another writer locked the index, see [`Writer::lock`](crate::Writer::lock).
+    #[error("Index {0} is locked by another writer")]
+    IndexLocked(u16),
+
+    /// An index was asked to be migrated into itself.
+    #[error("Cannot migrate index {0} into itself, the successor must be another index")]
+    MigrateInPlace(u16),
+
+    /// A migration was finished before all the items were given their new vector.
+    #[error("{missing} items were not given their new vector before finishing the migration")]
+    MigrationIncomplete {
+        /// The number of items without a new vector.
+        missing: u64,
+    },
+
+    /// An index was asked to be reordered into itself.
+    #[error("Cannot reorder index {0} into itself, the target must be another index")]
+    ReorderInPlace(u16),
+
+    /// An index can't be compacted, see [`crate::Writer::compact`].
+    #[error("Cannot compact index {index}, {reason}")]
+    CannotCompact {
+        /// The index that was asked to be compacted.
+        index: u16,
+        /// Why it can't be.
+        reason: &'static str,
+    },
+
+    /// A snapshot of an index can't be taken or opened, see
+    /// [`crate::Writer::snapshot`].
+    #[error("Invalid snapshot `{name}` of index {index}, {reason}")]
+    InvalidSnapshot {
+        /// The index of the snapshot.
+        index: u16,
+        /// The name of the snapshot.
+        name: String,
+        /// Why it is invalid.
+        reason: &'static str,
+    },
+
+    /// An operation spanning several transactions was interrupted and must be resumed or rolled
+    /// back before starting another kind of them, see [`crate::Writer::unfinished_ingest`].
+    #[error("Index {index} has an unfinished {kind:?} to resume or roll back")]
+    UnfinishedIngest {
+        /// The index of the operation.
+        index: u16,
+        /// The operation that was interrupted.
+        kind: crate::IngestKind,
+    },
+
     /// The user tried to append an item in the database but the last inserted item
     /// is highler or equal to this one.
     #[error("Item cannot be appended into the database")]
@@ -40,6 +272,16 @@ pub enum Error {
         received: &'static str,
     },
 
+    /// The vectors stored for a distance can't be scored with another one, see
+    /// [`ScoringReader::new`](crate::ScoringReader::new).
+    #[error("Cannot score the vectors of a {built} index with the {scoring} distance, they transform the vectors differently")]
+    IncompatibleScoring {
+        /// The distance the index was built with.
+        built: &'static str,
+        /// The distance the results were asked to be scored with.
+        scoring: &'static str,
+    },
+
     /// Hannoy is not able to find the metadata for a given index.
     /// It is probably because the user forget to build the database.
     #[error(
@@ -68,6 +310,53 @@ pub enum Error {
         layer: LayerId,
     },
 
+    /// An LMDB operation failed on a specific key.
+    #[error("Failed to {operation} {mode}({item},{layer}) in index `{index}`: {source}")]
+    KeyOperation {
+        /// The operation that failed, e.g. `get` or `put`
+        operation: &'static str,
+        /// The index that caused the error
+        index: u16,
+        /// The kind of node that was being accessed
+        mode: &'static str,
+        /// The item ID accessed
+        item: ItemId,
+        /// The item's layer
+        layer: LayerId,
+        /// The underlying LMDB error
+        #[source]
+        source: heed::Error,
+    },
+
+    /// An LMDB operation failed while iterating over a range of keys.
+    #[error("Failed to {operation} the {range} keys of index `{index}`: {source}")]
+    RangeOperation {
+        /// The operation that failed, e.g. `iterate` or `delete`
+        operation: &'static str,
+        /// The index that caused the error
+        index: u16,
+        /// The kind of keys covered by the range
+        range: &'static str,
+        /// The underlying LMDB error
+        #[source]
+        source: heed::Error,
+    },
+
+    /// Internal error
+    #[error("Internal error: expected {expected} under {mode}({item},{layer}) in index `{index}`")]
+    UnexpectedNode {
+        /// The index that caused the error
+        index: u16,
+        /// The kind of key that was read
+        mode: &'static str,
+        /// The item ID read
+        item: ItemId,
+        /// The item's layer
+        layer: LayerId,
+        /// The kind of node hannoy expected to find under the key
+        expected: &'static str,
+    },
+
     /// Cannot decode the key mode
     #[error("Cannot decode key mode: `{mode:?}`")]
     CannotDecodeKeyMode {
@@ -81,20 +370,70 @@ pub enum Error {
         /// The version that is unknown.
         version: Version,
     },
+
+    /// The nodes of the index were built in a more recent format than this version of hannoy
+    /// reads, see [`FORMAT_VERSION`](crate::FORMAT_VERSION).
+    #[error("Unknown format: {format}, the index was built by a more recent hannoy")]
+    UnknownFormat {
+        /// The format that is unknown.
+        format: u32,
+    },
 }
 
 impl Error {
+    /// Returns `true` if the error comes from the LMDB map being full, see
+    /// [`write_with_map_growth`](crate::write_with_map_growth) to grow it and retry.
+    pub fn is_map_full(&self) -> bool {
+        let source = match self {
+            Error::Heed(source)
+            | Error::KeyOperation { source, .. }
+            | Error::RangeOperation { source, .. } => source,
+            _ => return false,
+        };
+        matches!(source, heed::Error::Mdb(heed::MdbError::MapFull))
+    }
+
     pub(crate) fn missing_key(key: Key) -> Self {
         Self::MissingKey {
             index: key.index,
-            mode: match key.node.mode {
-                NodeMode::Item => "Item",
-                NodeMode::Links => "Links",
-                NodeMode::Metadata => "Metadata",
-                NodeMode::Updated => "Updated",
-            },
+            mode: key.node.mode.name(),
+            item: key.node.item,
+            layer: key.node.layer,
+        }
+    }
+
+    pub(crate) fn unexpected_node(key: Key, expected: &'static str) -> Self {
+        Self::UnexpectedNode {
+            index: key.index,
+            mode: key.node.mode.name(),
             item: key.node.item,
             layer: key.node.layer,
+            expected,
+        }
+    }
+
+    /// Returns a closure attaching the failed `operation` and `key` to an LMDB error.
+    pub(crate) fn on_key(operation: &'static str, key: Key) -> impl FnOnce(heed::Error) -> Self {
+        move |source| Self::KeyOperation {
+            operation,
+            index: key.index,
+            mode: key.node.mode.name(),
+            item: key.node.item,
+            layer: key.node.layer,
+            source,
+        }
+    }
+
+    /// Returns a closure attaching the failed `operation` and key range to an LMDB error.
+    pub(crate) fn on_range(
+        operation: &'static str,
+        prefix: Prefix,
+    ) -> impl FnOnce(heed::Error) -> Self {
+        move |source| Self::RangeOperation {
+            operation,
+            index: prefix.index(),
+            range: prefix.name(),
+            source,
         }
     }
 }
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+/// The extrapolation of a build of all the items of an index from a build of a sample of them,
+/// see [`HannoyBuilder::estimate`](crate::HannoyBuilder::estimate).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuildEstimate {
+    /// The number of items the sample was built from.
+    pub sampled_items: u64,
+    /// The number of items of the index.
+    pub items: u64,
+    /// The time the build of the sample took.
+    pub sample_duration: Duration,
+    /// The wall-clock time the build of every item should take.
+    pub duration: Duration,
+    /// The bytes the build of every item should write to LMDB, the vectors and the links.
+    pub written_bytes: u64,
+    /// The bytes written by the build for every byte of the vectors.
+    pub write_amplification: f64,
+}
+
+impl BuildEstimate {
+    /// Extrapolates the build of `items` items from the build of `sampled_items` that took
+    /// `sample_duration` and wrote `sample_bytes` bytes, of which `sample_vector_bytes` bytes of
+    /// vectors.
+    ///
+    /// Every insertion searches a graph whose depth grows with the logarithm of the number of
+    /// items, the duration is extrapolated in `n log n`. The written bytes grow linearly.
+    pub(crate) fn extrapolate(
+        sampled_items: u64,
+        items: u64,
+        sample_duration: Duration,
+        sample_bytes: u64,
+        sample_vector_bytes: u64,
+    ) -> BuildEstimate {
+        let n_log_n = |n: u64| n as f64 * (n.max(2) as f64).ln();
+        let scale = if sampled_items == 0 { 0.0 } else { n_log_n(items) / n_log_n(sampled_items) };
+        let per_item = |bytes: u64| bytes as f64 / sampled_items.max(1) as f64;
+
+        BuildEstimate {
+            sampled_items,
+            items,
+            sample_duration,
+            duration: sample_duration.mul_f64(scale),
+            written_bytes: (per_item(sample_bytes) * items as f64) as u64,
+            write_amplification: sample_bytes as f64 / sample_vector_bytes.max(1) as f64,
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Measures how close the graph search comes to an exact search.
+//!
+//! The recall@k of a query is the proportion of its `k` true nearest neighbours, found by
+//! comparing the query to every item of the index, that the graph search returns. Exact search
+//! is linear in the number of items, it is meant to be run on a sample of queries after a build,
+//! e.g. drawn from the indexed items with [`sample_queries`].
+//!
+//! ```no_run
+//! # use hannoy::{Reader, distances::Euclidean};
+//! # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+//! use hannoy::eval;
+//! use rand::SeedableRng;
+//!
+//! let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+//! let queries = eval::sample_queries(&rtxn, &reader, 100, &mut rng)?;
+//! let recall = eval::recall(&rtxn, &reader, &queries, 10, 100)?;
+//! println!("mean recall {}, p5 {}", recall.mean(), recall.percentile(0.05));
+//! # Ok::<(), hannoy::Error>(())
+//! ```
+//!
+//! [`tune_ef_search`] searches the smallest `ef_search` reaching a target recall and stores it
+//! in the index, the readers then use it by default. A [`RecallMonitor`] measures the recall of
+//! a live index periodically from a background thread.
+use heed::{RoTxn, RwTxn};
+use rand::seq::IteratorRandom;
+use rand::Rng;
+
+pub use self::monitor::{RecallMonitor, RecallMonitorBuilder, RecallSample};
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::ordered_float::OrderedFloat;
+pub use crate::tuning::EfSearchTuning;
+use crate::tuning::TuningCodec;
+use crate::unaligned_vector::UnalignedVector;
+use crate::{Database, Error, ItemId, Key, Reader, Result};
+
+mod monitor;
+
+/// The recall@k of every evaluated query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recall {
+    /// The recall of every query, in increasing order.
+    recalls: Vec<f64>,
+}
+
+impl Recall {
+    /// Returns the number of evaluated queries.
+    pub fn queries(&self) -> usize {
+        self.recalls.len()
+    }
+
+    /// Returns the mean recall of the queries, `1.0` if there were none.
+    pub fn mean(&self) -> f64 {
+        if self.recalls.is_empty() {
+            return 1.0;
+        }
+        self.recalls.iter().sum::<f64>() / self.recalls.len() as f64
+    }
+
+    /// Returns the recall reached by all the queries but a `percentile`, between `0.0` and
+    /// `1.0`, of them: `percentile(0.05)` is the recall of the worst 5% queries.
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        if self.recalls.is_empty() {
+            return 1.0;
+        }
+        let rank = (percentile.clamp(0.0, 1.0) * (self.recalls.len() - 1) as f64).round();
+        self.recalls[rank as usize]
+    }
+
+    /// Returns the recall of every query, in increasing order.
+    pub fn recalls(&self) -> &[f64] {
+        &self.recalls
+    }
+}
+
+/// Returns the vectors of `count` items of the index picked at random, or of all of them if
+/// there are fewer.
+pub fn sample_queries<D: Distance, R: Rng>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    count: usize,
+    rng: &mut R,
+) -> Result<Vec<Vec<f32>>> {
+    let ids = reader.item_ids().iter().choose_multiple(rng, count);
+    let mut queries = Vec::with_capacity(ids.len());
+    for id in ids {
+        let vector = reader.item_vector(rtxn, id)?;
+        queries.push(vector.ok_or_else(|| Error::missing_key(Key::item(reader.index(), id)))?);
+    }
+    Ok(queries)
+}
+
+/// Computes the recall@`k` of the graph search of `queries` with a search queue of
+/// `ef_search`, against an exact search over all the items of the index.
+pub fn recall<D: Distance>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    queries: impl IntoIterator<Item = impl AsRef<[f32]>>,
+    k: usize,
+    ef_search: usize,
+) -> Result<Recall> {
+    let queries: Vec<_> = queries.into_iter().collect();
+    let truths = ground_truth(rtxn, reader, &queries, k)?;
+    measure(rtxn, reader, &queries, &truths, k, ef_search)
+}
+
+/// Finds the smallest `ef_search` whose mean recall@`k` on `queries` reaches `target_recall` and
+/// stores it in the index, where [`Reader::nns`] picks it up as its default.
+///
+/// The queue size is doubled from `k` until the target is reached, then bisected. The recall
+/// grows with the queue size but not strictly, the recommendation is only as good as the sample
+/// of queries is representative. If even a queue as large as the index misses the target, that
+/// size is recommended with the recall it reached. The recommendation is cleared by the next
+/// build that changes the graph, tune the index again after it.
+pub fn tune_ef_search<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    queries: &[impl AsRef<[f32]>],
+    k: usize,
+    target_recall: f64,
+) -> Result<EfSearchTuning> {
+    let reader = Reader::open(wtxn, index, database)?;
+    let truths = ground_truth(wtxn, &reader, queries, k)?;
+    let mean_recall = |ef| measure(wtxn, &reader, queries, &truths, k, ef).map(|r| r.mean());
+
+    let max_ef = (reader.n_items() as usize).max(k).max(1);
+    let (mut low, mut high) = (0, k.max(1));
+    let mut reached = mean_recall(high)?;
+    while reached < target_recall && high < max_ef {
+        low = high;
+        high = (high * 2).min(max_ef);
+        reached = mean_recall(high)?;
+    }
+
+    // `high` reaches the target, or is the largest queue, while `low` doesn't
+    if reached >= target_recall {
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            let recall = mean_recall(middle)?;
+            if recall >= target_recall {
+                (high, reached) = (middle, recall);
+            } else {
+                low = middle;
+            }
+        }
+    }
+
+    let tuning = EfSearchTuning { ef_search: high, k, target_recall, recall: reached };
+    let key = Key::tuning(index);
+    database
+        .remap_data_type::<TuningCodec>()
+        .put(wtxn, &key, &tuning)
+        .map_err(Error::on_key("put", key))?;
+    Ok(tuning)
+}
+
+/// Returns the exact nearest neighbours of every query.
+fn ground_truth<D: Distance>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    queries: &[impl AsRef<[f32]>],
+    k: usize,
+) -> Result<Vec<Vec<ItemId>>> {
+    queries.iter().map(|query| exact_nns(rtxn, reader, query.as_ref(), k)).collect()
+}
+
+/// Returns the recall of the graph search of `queries` against their `truths`.
+fn measure<D: Distance>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    queries: &[impl AsRef<[f32]>],
+    truths: &[Vec<ItemId>],
+    k: usize,
+    ef_search: usize,
+) -> Result<Recall> {
+    let mut builder = reader.nns(k);
+    builder.ef_search(ef_search);
+
+    let mut recalls = Vec::with_capacity(queries.len());
+    for (query, truth) in queries.iter().zip(truths) {
+        let found = builder.by_vector(rtxn, query.as_ref())?.into_nns();
+
+        let recall = if truth.is_empty() {
+            1.0
+        } else {
+            let hits = found.iter().filter(|(id, _)| truth.contains(id)).count();
+            hits as f64 / truth.len() as f64
+        };
+        recalls.push(recall);
+    }
+
+    recalls.sort_by(f64::total_cmp);
+    Ok(Recall { recalls })
+}
+
+/// Returns the ids of the `k` nearest items of `query` by comparing it to every item.
+fn exact_nns<D: Distance>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    query: &[f32],
+    k: usize,
+) -> Result<Vec<ItemId>> {
+    let vector = reader.query_vector(query)?;
+    let vector = UnalignedVector::from_slice(&vector);
+    let query = Item { header: D::new_header(&vector), vector };
+
+    let mut distances = Vec::with_capacity(reader.n_items() as usize);
+    for id in reader.item_ids() {
+        let Some(vector) = reader.item_vector_ref(rtxn, id)? else { continue };
+        let item = Item { header: D::new_header(&vector), vector };
+        distances.push((OrderedFloat(D::distance(&item, &query)), id));
+    }
+    distances.sort_unstable();
+    Ok(distances.into_iter().take(k).map(|(_, id)| id).collect())
+}
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use heed::Env;
+use tracing::warn;
+
+use crate::distance::Distance;
+use crate::{Database, Error, Reader, Result};
+
+/// The recall measured by a [`RecallMonitor`] at some point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecallSample {
+    /// When the recall was measured.
+    pub at: SystemTime,
+    /// The number of queries it was measured on.
+    pub queries: usize,
+    /// The mean recall of the queries.
+    pub mean: f64,
+    /// The recall of the worst 5% queries.
+    pub p5: f64,
+}
+
+/// Configures and spawns a [`RecallMonitor`].
+pub struct RecallMonitorBuilder<D: Distance> {
+    env: Env,
+    database: Database<D>,
+    index: u16,
+    interval: Duration,
+    sample: usize,
+    k: usize,
+    ef_search: Option<usize>,
+    history: usize,
+}
+
+impl<D: Distance> RecallMonitorBuilder<D> {
+    /// The time between two measures, one minute by default.
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The number of queries every measure is made on, 20 by default. Every query is compared
+    /// to all the items of the index.
+    pub fn sample(&mut self, sample: usize) -> &mut Self {
+        self.sample = sample;
+        self
+    }
+
+    /// The number of neighbours the recall is measured on, 10 by default.
+    pub fn k(&mut self, k: usize) -> &mut Self {
+        self.k = k;
+        self
+    }
+
+    /// The size of the search queue of the measured searches, by default the one the readers
+    /// use.
+    pub fn ef_search(&mut self, ef_search: usize) -> &mut Self {
+        self.ef_search = Some(ef_search);
+        self
+    }
+
+    /// The number of measures kept, 60 by default.
+    pub fn history(&mut self, history: usize) -> &mut Self {
+        self.history = history.max(1);
+        self
+    }
+
+    /// Spawns the thread measuring the recall, which stops when the monitor is dropped.
+    pub fn spawn(&self) -> RecallMonitor {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                recent: VecDeque::with_capacity(self.sample),
+                history: VecDeque::with_capacity(self.history),
+                stopped: false,
+            }),
+            wake: Condvar::new(),
+            sample: self.sample,
+            history: self.history,
+        });
+
+        let Self { ref env, database, index, k, ef_search, .. } = *self;
+        let env = env.clone();
+        let measure_shared = shared.clone();
+        let measure: Arc<dyn Fn() -> Result<Option<RecallSample>> + Send + Sync> =
+            Arc::new(move || measure_shared.measure(&env, database, index, k, ef_search));
+
+        let interval = self.interval;
+        let thread_shared = shared.clone();
+        let thread_measure = measure.clone();
+        let handle = std::thread::spawn(move || loop {
+            {
+                let state = thread_shared.state.lock().unwrap();
+                let (state, _) = thread_shared
+                    .wake
+                    .wait_timeout_while(state, interval, |state| !state.stopped)
+                    .unwrap();
+                if state.stopped {
+                    return;
+                }
+            }
+            if let Err(error) = thread_measure() {
+                warn!(index, %error, "failed to measure the recall");
+            }
+        });
+
+        RecallMonitor { shared, measure, handle: Some(handle) }
+    }
+}
+
+/// Periodically measures the recall of an index from a background thread, to notice when
+/// incremental updates degrade the graph.
+///
+/// Every measure compares the graph search to an exact search on the last queries given to
+/// [`Self::observe`] or, when there are not enough, on the vectors of random items. The index is
+/// skipped while it needs a build. The thread stops when the monitor is dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hannoy::distances::Euclidean;
+/// # let (env, database): (heed::Env, hannoy::Database<Euclidean>) = todo!();
+/// use std::time::Duration;
+/// use hannoy::eval::RecallMonitor;
+///
+/// let monitor = RecallMonitor::builder(env, database, 0).interval(Duration::from_secs(600)).spawn();
+/// // ... on every search
+/// monitor.observe(&[0.1, 0.2, 0.3]);
+/// // ... later
+/// if monitor.drift().is_some_and(|drift| drift > 0.05) {
+///     println!("the recall dropped by {:?}, time to rebuild", monitor.drift());
+/// }
+/// ```
+pub struct RecallMonitor {
+    shared: Arc<Shared>,
+    measure: Arc<dyn Fn() -> Result<Option<RecallSample>> + Send + Sync>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl RecallMonitor {
+    /// Returns a builder to monitor the index `index` of `database`.
+    pub fn builder<D: Distance>(
+        env: Env,
+        database: Database<D>,
+        index: u16,
+    ) -> RecallMonitorBuilder<D> {
+        RecallMonitorBuilder {
+            env,
+            database,
+            index,
+            interval: Duration::from_secs(60),
+            sample: 20,
+            k: 10,
+            ef_search: None,
+            history: 60,
+        }
+    }
+
+    /// Keeps `query` to measure the recall on, only the last ones are kept.
+    pub fn observe(&self, query: &[f32]) {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.recent.len() == self.shared.sample {
+            state.recent.pop_front();
+        }
+        if self.shared.sample != 0 {
+            state.recent.push_back(query.to_vec());
+        }
+    }
+
+    /// Measures the recall now, on the calling thread, and adds it to the history. Returns
+    /// `None` if the index needs to be built.
+    pub fn measure_now(&self) -> Result<Option<RecallSample>> {
+        (self.measure)()
+    }
+
+    /// Returns the last measure.
+    pub fn latest(&self) -> Option<RecallSample> {
+        self.shared.state.lock().unwrap().history.back().cloned()
+    }
+
+    /// Returns the kept measures, from the oldest to the latest.
+    pub fn history(&self) -> Vec<RecallSample> {
+        self.shared.state.lock().unwrap().history.iter().cloned().collect()
+    }
+
+    /// Returns how much the mean recall dropped between the oldest kept measure and the latest,
+    /// `None` until there are two measures. A negative drift is an improvement.
+    pub fn drift(&self) -> Option<f64> {
+        let state = self.shared.state.lock().unwrap();
+        match (state.history.front(), state.history.back()) {
+            (Some(oldest), Some(latest)) if state.history.len() > 1 => {
+                Some(oldest.mean - latest.mean)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Drop for RecallMonitor {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.wake.notify_all();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+    sample: usize,
+    history: usize,
+}
+
+struct State {
+    recent: VecDeque<Vec<f32>>,
+    history: VecDeque<RecallSample>,
+    stopped: bool,
+}
+
+impl Shared {
+    fn measure<D: Distance>(
+        &self,
+        env: &Env,
+        database: Database<D>,
+        index: u16,
+        k: usize,
+        ef_search: Option<usize>,
+    ) -> Result<Option<RecallSample>> {
+        let rtxn = env.read_txn()?;
+        let reader = match Reader::open(&rtxn, index, database) {
+            Ok(reader) => reader,
+            Err(Error::NeedBuild(_) | Error::MissingMetadata(_)) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let mut queries: Vec<_> = {
+            let state = self.state.lock().unwrap();
+            let recent = state.recent.iter().filter(|query| query.len() == reader.dimensions());
+            recent.cloned().collect()
+        };
+        let missing = self.sample.saturating_sub(queries.len());
+        queries.extend(super::sample_queries(&rtxn, &reader, missing, &mut rand::thread_rng())?);
+
+        let ef_search = ef_search.unwrap_or_else(|| reader.default_ef_search());
+        let recall = super::recall(&rtxn, &reader, &queries, k, ef_search)?;
+        let sample = RecallSample {
+            at: SystemTime::now(),
+            queries: recall.queries(),
+            mean: recall.mean(),
+            p5: recall.percentile(0.05),
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if state.history.len() == self.history {
+            state.history.pop_front();
+        }
+        state.history.push_back(sample.clone());
+        Ok(Some(sample))
+    }
+}
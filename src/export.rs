@@ -0,0 +1,264 @@
+//! Exports the graph of an index, e.g. to cluster the items with a community detection
+//! algorithm like Leiden or HDBSCAN without computing their neighbours again.
+//!
+//! ```no_run
+//! # use hannoy::{Reader, distances::Cosine};
+//! # let (reader, rtxn): (Reader<Cosine>, heed::RoTxn) = todo!();
+//! use hannoy::export::{edges, Neighbours};
+//!
+//! for edge in edges(&rtxn, &reader, Neighbours::Knn(10)) {
+//!     let (source, target, distance) = edge?;
+//!     println!("{source}\t{target}\t{distance}");
+//! }
+//! # Ok::<(), hannoy::Error>(())
+//! ```
+//!
+//! [`write_subgraph`] writes a region of the graph with all its layers to a Graphviz DOT or a
+//! GEXF file, to look at it with Graphviz or Gephi and understand why a region is hard to
+//! search.
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use heed::RoTxn;
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::{Error, ItemId, Key, Reader, Result};
+
+/// The neighbours of an item an edge list is made of, see [`edges`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbours {
+    /// The neighbours the item is linked to on the bottom layer of the graph. The graph keeps a
+    /// diverse set of neighbours rather than the closest ones, which makes it navigable.
+    Graph,
+    /// The given number of nearest neighbours of the item, searched in the graph.
+    Knn(usize),
+}
+
+/// Returns the edges from every item of the index to its `neighbours`, as `(source, target,
+/// distance)` tuples, grouped by source in increasing order.
+///
+/// The edges are computed lazily, one item at a time.
+pub fn edges<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    reader: &'t Reader<D>,
+    neighbours: Neighbours,
+) -> Edges<'t, D> {
+    Edges {
+        rtxn,
+        reader,
+        neighbours,
+        items: reader.item_ids().iter(),
+        pending: Vec::new().into_iter(),
+    }
+}
+
+/// An iterator over the edges of the graph of an index, see [`edges`].
+pub struct Edges<'t, D: Distance> {
+    rtxn: &'t RoTxn<'t>,
+    reader: &'t Reader<D>,
+    neighbours: Neighbours,
+    items: roaring::bitmap::Iter<'t>,
+    pending: std::vec::IntoIter<(ItemId, ItemId, f32)>,
+}
+
+impl<D: Distance> Edges<'_, D> {
+    /// Returns the edges from an item to its neighbours.
+    fn edges_of(&self, item: ItemId) -> Result<Vec<(ItemId, ItemId, f32)>> {
+        let found = match self.neighbours {
+            Neighbours::Graph => {
+                let neighbours = self.reader.neighbours(self.rtxn, item)?;
+                let distances = self.reader.distances(self.rtxn, item, &neighbours)?;
+                neighbours
+                    .into_iter()
+                    .zip(distances)
+                    .filter_map(|(neighbour, distance)| Some((neighbour, distance?)))
+                    .collect()
+            }
+            Neighbours::Knn(k) => match self.reader.nns(k).by_item(self.rtxn, item)? {
+                Some(searched) => searched.into_nns(),
+                None => Vec::new(),
+            },
+        };
+        Ok(found.into_iter().map(|(neighbour, distance)| (item, neighbour, distance)).collect())
+    }
+}
+
+impl<D: Distance> Iterator for Edges<'_, D> {
+    type Item = Result<(ItemId, ItemId, f32)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(edge) = self.pending.next() {
+                return Some(Ok(edge));
+            }
+            let item = self.items.next()?;
+            match self.edges_of(item) {
+                Ok(edges) => self.pending = edges.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// The region of the graph [`write_subgraph`] writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subgraph<'a> {
+    /// The given items and the links between them.
+    Items(&'a RoaringBitmap),
+    /// The items within a distance of an item, in the unit of the distances the searches return,
+    /// that are reachable from it through the bottom layer of the graph without leaving that
+    /// distance, and the links between them.
+    Around {
+        /// The item at the center of the region.
+        item: ItemId,
+        /// The distance from the `item` the region spans.
+        radius: f32,
+    },
+}
+
+/// The file format [`write_subgraph`] writes the graph in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// The DOT language of Graphviz.
+    Dot,
+    /// The GEXF 1.3 XML format of Gephi.
+    Gexf,
+}
+
+/// Writes a `subgraph` of the index to `out` in a graph `format`.
+///
+/// The nodes are the items with the highest layer of the graph they are on, the edges are the
+/// links between them on every layer with the distance between the linked items. The links are
+/// directed: an item can link to another without being linked back.
+///
+/// ```no_run
+/// # use hannoy::{Reader, distances::Cosine};
+/// # let (reader, rtxn): (Reader<Cosine>, heed::RoTxn) = todo!();
+/// use hannoy::export::{write_subgraph, GraphFormat, Subgraph};
+///
+/// let file = std::fs::File::create("around-42.dot")?;
+/// let around = Subgraph::Around { item: 42, radius: 0.3 };
+/// write_subgraph(&rtxn, &reader, around, GraphFormat::Dot, std::io::BufWriter::new(file))?;
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+pub fn write_subgraph<D: Distance>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    subgraph: Subgraph,
+    format: GraphFormat,
+    mut out: impl Write,
+) -> Result<()> {
+    let items = match subgraph {
+        Subgraph::Items(items) => items & reader.item_ids(),
+        Subgraph::Around { item, radius } => region(rtxn, reader, item, radius)?,
+    };
+
+    let mut nodes = Vec::with_capacity(items.len() as usize);
+    let mut links = Vec::new();
+    for item in &items {
+        let mut level = 0;
+        while let Some(neighbours) = reader.neighbours_on(rtxn, item, level)? {
+            let neighbours: Vec<_> =
+                neighbours.into_iter().filter(|&n| items.contains(n)).collect();
+            let distances = reader.distances(rtxn, item, &neighbours)?;
+            for (neighbour, distance) in neighbours.into_iter().zip(distances) {
+                if let Some(distance) = distance {
+                    links.push(Link { source: item, target: neighbour, level, distance });
+                }
+            }
+            level += 1;
+        }
+        nodes.push((item, level.saturating_sub(1)));
+    }
+
+    match format {
+        GraphFormat::Dot => write_dot(&mut out, reader.index(), &nodes, &links)?,
+        GraphFormat::Gexf => write_gexf(&mut out, &nodes, &links)?,
+    }
+    out.flush().map_err(Into::into)
+}
+
+/// A link from an item to another on a level of the graph.
+struct Link {
+    source: ItemId,
+    target: ItemId,
+    level: usize,
+    distance: f32,
+}
+
+/// Returns the items within `radius` of the `center` reachable from it on the bottom layer
+/// without leaving the radius.
+fn region<D: Distance>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    center: ItemId,
+    radius: f32,
+) -> Result<RoaringBitmap> {
+    if !reader.contains_item(rtxn, center)? {
+        return Err(Error::missing_key(Key::item(reader.index(), center)));
+    }
+
+    let mut region = RoaringBitmap::from_iter([center]);
+    let mut queue = VecDeque::from([center]);
+    while let Some(item) = queue.pop_front() {
+        let neighbours: Vec<_> =
+            reader.neighbours(rtxn, item)?.into_iter().filter(|&n| !region.contains(n)).collect();
+        let distances = reader.distances(rtxn, center, &neighbours)?;
+        for (neighbour, distance) in neighbours.into_iter().zip(distances) {
+            if distance.is_some_and(|distance| distance <= radius) && region.insert(neighbour) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    Ok(region)
+}
+
+fn write_dot(
+    out: &mut impl Write,
+    index: u16,
+    nodes: &[(ItemId, usize)],
+    links: &[Link],
+) -> io::Result<()> {
+    writeln!(out, "digraph index_{index} {{")?;
+    for &(item, level) in nodes {
+        writeln!(out, "  {item} [layer={level}];")?;
+    }
+    for Link { source, target, level, distance } in links {
+        writeln!(out, "  {source} -> {target} [layer={level}, distance={distance}];")?;
+    }
+    writeln!(out, "}}")
+}
+
+fn write_gexf(out: &mut impl Write, nodes: &[(ItemId, usize)], links: &[Link]) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#)?;
+    writeln!(out, r#"  <graph defaultedgetype="directed" mode="static">"#)?;
+    writeln!(out, r#"    <attributes class="node">"#)?;
+    writeln!(out, r#"      <attribute id="layer" title="layer" type="integer"/>"#)?;
+    writeln!(out, r#"    </attributes>"#)?;
+    writeln!(out, r#"    <attributes class="edge">"#)?;
+    writeln!(out, r#"      <attribute id="layer" title="layer" type="integer"/>"#)?;
+    writeln!(out, r#"      <attribute id="distance" title="distance" type="float"/>"#)?;
+    writeln!(out, r#"    </attributes>"#)?;
+
+    writeln!(out, "    <nodes>")?;
+    for &(item, level) in nodes {
+        writeln!(
+            out,
+            r#"      <node id="{item}" label="{item}"><attvalues><attvalue for="layer" value="{level}"/></attvalues></node>"#
+        )?;
+    }
+    writeln!(out, "    </nodes>")?;
+
+    writeln!(out, "    <edges>")?;
+    for (id, Link { source, target, level, distance }) in links.iter().enumerate() {
+        writeln!(
+            out,
+            r#"      <edge id="{id}" source="{source}" target="{target}"><attvalues><attvalue for="layer" value="{level}"/><attvalue for="distance" value="{distance}"/></attvalues></edge>"#
+        )?;
+    }
+    writeln!(out, "    </edges>")?;
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</gexf>")
+}
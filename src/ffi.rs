@@ -0,0 +1,494 @@
+//! C bindings for hannoy.
+//!
+//! Every function returns a [`HannoyStatus`] and writes its result through an out pointer.
+//! When a call fails, a description of the error can be retrieved with [`hannoy_last_error`].
+//! The header is generated with `cbindgen --config cbindgen.toml --output include/hannoy.h`.
+//!
+//! LMDB only allows a single write transaction per environment at a time, a second
+//! [`hannoy_writer_new`] blocks until the first writer is committed or freed.
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use heed::{RoTxn, RwTxn, WithoutTls};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::{distance, Database, Error, ItemId, Reader, Writer, SUPPORTED_LINKS};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The outcome of a call to the hannoy C API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HannoyStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A pointer was null or an argument was out of the supported range.
+    InvalidArgument = 1,
+    /// The operation failed, see [`hannoy_last_error`].
+    Error = 2,
+}
+
+/// The distances available through the C API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HannoyDistance {
+    /// See [`distance::Cosine`].
+    Cosine = 0,
+    /// See [`distance::Euclidean`].
+    Euclidean = 1,
+    /// See [`distance::Manhattan`].
+    Manhattan = 2,
+    /// See [`distance::BinaryQuantizedCosine`].
+    BinaryQuantizedCosine = 3,
+    /// See [`distance::BinaryQuantizedEuclidean`].
+    BinaryQuantizedEuclidean = 4,
+    /// See [`distance::BinaryQuantizedManhattan`].
+    BinaryQuantizedManhattan = 5,
+    /// See [`distance::Hamming`].
+    Hamming = 6,
+    /// See [`distance::Chebyshev`].
+    Chebyshev = 7,
+    /// See [`distance::Canberra`].
+    Canberra = 8,
+    /// See [`distance::BrayCurtis`].
+    BrayCurtis = 9,
+    /// See [`distance::Hellinger`].
+    Hellinger = 10,
+    /// See [`distance::U8Euclidean`].
+    U8Euclidean = 11,
+    /// See [`distance::U8Manhattan`].
+    U8Manhattan = 12,
+    /// See [`distance::EarthMovers`].
+    EarthMovers = 13,
+    /// See [`distance::Poincare`].
+    Poincare = 14,
+}
+
+impl HannoyDistance {
+    /// Maps the value given through the C API, which may not be a valid distance, to a distance.
+    fn from_raw(distance: u32) -> Option<HannoyDistance> {
+        Some(match distance {
+            0 => HannoyDistance::Cosine,
+            1 => HannoyDistance::Euclidean,
+            2 => HannoyDistance::Manhattan,
+            3 => HannoyDistance::BinaryQuantizedCosine,
+            4 => HannoyDistance::BinaryQuantizedEuclidean,
+            5 => HannoyDistance::BinaryQuantizedManhattan,
+            6 => HannoyDistance::Hamming,
+            7 => HannoyDistance::Chebyshev,
+            8 => HannoyDistance::Canberra,
+            9 => HannoyDistance::BrayCurtis,
+            10 => HannoyDistance::Hellinger,
+            11 => HannoyDistance::U8Euclidean,
+            12 => HannoyDistance::U8Manhattan,
+            13 => HannoyDistance::EarthMovers,
+            14 => HannoyDistance::Poincare,
+            _ => return None,
+        })
+    }
+}
+
+/// An opened LMDB environment.
+pub struct HannoyEnv {
+    env: heed::Env<WithoutTls>,
+}
+
+enum DynWriter {
+    Cosine(Writer<distance::Cosine>),
+    Euclidean(Writer<distance::Euclidean>),
+    Manhattan(Writer<distance::Manhattan>),
+    BqCosine(Writer<distance::BinaryQuantizedCosine>),
+    BqEuclidean(Writer<distance::BinaryQuantizedEuclidean>),
+    BqManhattan(Writer<distance::BinaryQuantizedManhattan>),
+    Hamming(Writer<distance::Hamming>),
+    Chebyshev(Writer<distance::Chebyshev>),
+    Canberra(Writer<distance::Canberra>),
+    BrayCurtis(Writer<distance::BrayCurtis>),
+    Hellinger(Writer<distance::Hellinger>),
+    U8Euclidean(Writer<distance::U8Euclidean>),
+    U8Manhattan(Writer<distance::U8Manhattan>),
+    EarthMovers(Writer<distance::EarthMovers>),
+    Poincare(Writer<distance::Poincare>),
+}
+
+enum DynReader {
+    Cosine(Reader<distance::Cosine>),
+    Euclidean(Reader<distance::Euclidean>),
+    Manhattan(Reader<distance::Manhattan>),
+    BqCosine(Reader<distance::BinaryQuantizedCosine>),
+    BqEuclidean(Reader<distance::BinaryQuantizedEuclidean>),
+    BqManhattan(Reader<distance::BinaryQuantizedManhattan>),
+    Hamming(Reader<distance::Hamming>),
+    Chebyshev(Reader<distance::Chebyshev>),
+    Canberra(Reader<distance::Canberra>),
+    BrayCurtis(Reader<distance::BrayCurtis>),
+    Hellinger(Reader<distance::Hellinger>),
+    U8Euclidean(Reader<distance::U8Euclidean>),
+    U8Manhattan(Reader<distance::U8Manhattan>),
+    EarthMovers(Reader<distance::EarthMovers>),
+    Poincare(Reader<distance::Poincare>),
+}
+
+macro_rules! dispatch {
+    ($enum:ident, $value:expr, $inner:ident => $body:expr) => {
+        match $value {
+            $enum::Cosine($inner) => $body,
+            $enum::Euclidean($inner) => $body,
+            $enum::Manhattan($inner) => $body,
+            $enum::BqCosine($inner) => $body,
+            $enum::BqEuclidean($inner) => $body,
+            $enum::BqManhattan($inner) => $body,
+            $enum::Hamming($inner) => $body,
+            $enum::Chebyshev($inner) => $body,
+            $enum::Canberra($inner) => $body,
+            $enum::BrayCurtis($inner) => $body,
+            $enum::Hellinger($inner) => $body,
+            $enum::U8Euclidean($inner) => $body,
+            $enum::U8Manhattan($inner) => $body,
+            $enum::EarthMovers($inner) => $body,
+            $enum::Poincare($inner) => $body,
+        }
+    };
+}
+
+/// A writer owning its write transaction, the changes are only visible once committed.
+pub struct HannoyWriter {
+    // The transaction borrows the environment and must be dropped first.
+    wtxn: RwTxn<'static>,
+    writer: DynWriter,
+    _env: Box<heed::Env<WithoutTls>>,
+}
+
+/// A reader owning its read transaction, it can be moved between threads.
+pub struct HannoyReader {
+    rtxn: RoTxn<'static, WithoutTls>,
+    reader: DynReader,
+}
+
+fn set_last_error(error: impl ToString) {
+    let message = CString::new(error.to_string()).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn status<T>(result: Result<T, Error>, out: impl FnOnce(T)) -> HannoyStatus {
+    match result {
+        Ok(value) => {
+            out(value);
+            HannoyStatus::Ok
+        }
+        Err(error) => {
+            set_last_error(error);
+            HannoyStatus::Error
+        }
+    }
+}
+
+/// Returns the message of the last error that happened on this thread, or null.
+///
+/// The string is owned by hannoy and stays valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn hannoy_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |msg| msg.as_ptr()))
+}
+
+/// Opens, or creates, the LMDB environment at `path` with a maximum size of `map_size` bytes.
+///
+/// # Safety
+///
+/// `path` must be a valid nul-terminated string and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_env_open(
+    path: *const c_char,
+    map_size: usize,
+    out: *mut *mut HannoyEnv,
+) -> HannoyStatus {
+    if path.is_null() || out.is_null() {
+        return HannoyStatus::InvalidArgument;
+    }
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        set_last_error("the path is not valid UTF-8");
+        return HannoyStatus::InvalidArgument;
+    };
+
+    let env = heed::EnvOpenOptions::new().read_txn_without_tls().map_size(map_size).open(path);
+    status(env.map_err(Error::from), |env| *out = Box::into_raw(Box::new(HannoyEnv { env })))
+}
+
+/// Closes an environment. Writers and readers created from it stay valid.
+///
+/// # Safety
+///
+/// `env` must come from [`hannoy_env_open`] and must not be used afterward.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_env_free(env: *mut HannoyEnv) {
+    if !env.is_null() {
+        drop(Box::from_raw(env));
+    }
+}
+
+/// Opens a write transaction and a writer on the `index` of the unnamed database.
+///
+/// `distance` must be one of the [`HannoyDistance`] values, any other is an invalid argument.
+///
+/// # Safety
+///
+/// `env` must come from [`hannoy_env_open`] and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_writer_new(
+    env: *const HannoyEnv,
+    distance: u32,
+    index: u16,
+    dimensions: usize,
+    out: *mut *mut HannoyWriter,
+) -> HannoyStatus {
+    if env.is_null() || out.is_null() {
+        return HannoyStatus::InvalidArgument;
+    }
+    let Some(distance) = HannoyDistance::from_raw(distance) else {
+        set_last_error(format!("Unknown distance {distance}"));
+        return HannoyStatus::InvalidArgument;
+    };
+
+    let env = Box::new((*env).env.clone());
+    let result = (|| {
+        // SAFETY: the environment is boxed and lives as long as the transaction,
+        // the transaction is the first field of `HannoyWriter` and is dropped first.
+        let mut wtxn: RwTxn<'static> = std::mem::transmute(env.write_txn()?);
+        macro_rules! create {
+            ($variant:ident) => {{
+                let database = env.create_database(&mut wtxn, None)?;
+                DynWriter::$variant(Writer::new(database, index, dimensions))
+            }};
+        }
+        let writer = match distance {
+            HannoyDistance::Cosine => create!(Cosine),
+            HannoyDistance::Euclidean => create!(Euclidean),
+            HannoyDistance::Manhattan => create!(Manhattan),
+            HannoyDistance::BinaryQuantizedCosine => create!(BqCosine),
+            HannoyDistance::BinaryQuantizedEuclidean => create!(BqEuclidean),
+            HannoyDistance::BinaryQuantizedManhattan => create!(BqManhattan),
+            HannoyDistance::Hamming => create!(Hamming),
+            HannoyDistance::Chebyshev => create!(Chebyshev),
+            HannoyDistance::Canberra => create!(Canberra),
+            HannoyDistance::BrayCurtis => create!(BrayCurtis),
+            HannoyDistance::Hellinger => create!(Hellinger),
+            HannoyDistance::U8Euclidean => create!(U8Euclidean),
+            HannoyDistance::U8Manhattan => create!(U8Manhattan),
+            HannoyDistance::EarthMovers => create!(EarthMovers),
+            HannoyDistance::Poincare => create!(Poincare),
+        };
+        Ok((wtxn, writer))
+    })();
+
+    status(result, |(wtxn, writer)| {
+        *out = Box::into_raw(Box::new(HannoyWriter { wtxn, writer, _env: env }))
+    })
+}
+
+/// Stores the `dimensions` floats pointed by `vector` for `item`.
+///
+/// # Safety
+///
+/// `writer` must come from [`hannoy_writer_new`] and `vector` must point to `dimensions` floats.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_writer_add_item(
+    writer: *mut HannoyWriter,
+    item: ItemId,
+    vector: *const f32,
+    dimensions: usize,
+) -> HannoyStatus {
+    if writer.is_null() || vector.is_null() {
+        return HannoyStatus::InvalidArgument;
+    }
+
+    let HannoyWriter { wtxn, writer, .. } = &mut *writer;
+    let vector = std::slice::from_raw_parts(vector, dimensions);
+    status(dispatch!(DynWriter, writer, w => w.add_item(wtxn, item, vector)), |()| ())
+}
+
+/// Deletes `item` and writes whether it existed in `existed`.
+///
+/// # Safety
+///
+/// `writer` must come from [`hannoy_writer_new`] and `existed` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_writer_del_item(
+    writer: *mut HannoyWriter,
+    item: ItemId,
+    existed: *mut bool,
+) -> HannoyStatus {
+    if writer.is_null() || existed.is_null() {
+        return HannoyStatus::InvalidArgument;
+    }
+
+    let HannoyWriter { wtxn, writer, .. } = &mut *writer;
+    status(dispatch!(DynWriter, writer, w => w.del_item(wtxn, item)), |e| *existed = e)
+}
+
+/// Builds the graph with `m` links per node, `m` must be one of 4, 8, 12, 16, 24, 32 or 48.
+///
+/// # Safety
+///
+/// `writer` must come from [`hannoy_writer_new`].
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_writer_build(
+    writer: *mut HannoyWriter,
+    m: usize,
+    ef_construction: usize,
+    seed: u64,
+) -> HannoyStatus {
+    if writer.is_null() {
+        return HannoyStatus::InvalidArgument;
+    }
+    if !SUPPORTED_LINKS.contains(&m) {
+        set_last_error(Error::UnsupportedLinks(m).to_string());
+        return HannoyStatus::InvalidArgument;
+    }
+
+    let HannoyWriter { wtxn, writer, .. } = &mut *writer;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let built = dispatch!(DynWriter, writer, w => {
+        w.builder(&mut rng).ef_construction(ef_construction).build_with_links(wtxn, m)
+    });
+    status(built, |()| ())
+}
+
+/// Commits the changes of the writer and frees it, even if the commit fails.
+///
+/// # Safety
+///
+/// `writer` must come from [`hannoy_writer_new`] and must not be used afterward.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_writer_commit(writer: *mut HannoyWriter) -> HannoyStatus {
+    if writer.is_null() {
+        return HannoyStatus::InvalidArgument;
+    }
+
+    let HannoyWriter { wtxn, writer: _, _env } = *Box::from_raw(writer);
+    status(wtxn.commit().map_err(Error::from), |()| ())
+}
+
+/// Aborts the changes of the writer and frees it.
+///
+/// # Safety
+///
+/// `writer` must come from [`hannoy_writer_new`] and must not be used afterward.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_writer_free(writer: *mut HannoyWriter) {
+    if !writer.is_null() {
+        drop(Box::from_raw(writer));
+    }
+}
+
+/// Opens a reader on the `index` of the unnamed database which must have been built.
+///
+/// `distance` must be one of the [`HannoyDistance`] values, any other is an invalid argument.
+///
+/// # Safety
+///
+/// `env` must come from [`hannoy_env_open`] and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_reader_open(
+    env: *const HannoyEnv,
+    distance: u32,
+    index: u16,
+    out: *mut *mut HannoyReader,
+) -> HannoyStatus {
+    if env.is_null() || out.is_null() {
+        return HannoyStatus::InvalidArgument;
+    }
+    let Some(distance) = HannoyDistance::from_raw(distance) else {
+        set_last_error(format!("Unknown distance {distance}"));
+        return HannoyStatus::InvalidArgument;
+    };
+
+    let env = &(*env).env;
+    let result = (|| {
+        let rtxn = env.clone().static_read_txn()?;
+        macro_rules! open {
+            ($variant:ident) => {{
+                let database: Database<_> =
+                    env.open_database(&rtxn, None)?.ok_or(Error::MissingMetadata(index))?;
+                DynReader::$variant(Reader::open(&rtxn, index, database)?)
+            }};
+        }
+        let reader = match distance {
+            HannoyDistance::Cosine => open!(Cosine),
+            HannoyDistance::Euclidean => open!(Euclidean),
+            HannoyDistance::Manhattan => open!(Manhattan),
+            HannoyDistance::BinaryQuantizedCosine => open!(BqCosine),
+            HannoyDistance::BinaryQuantizedEuclidean => open!(BqEuclidean),
+            HannoyDistance::BinaryQuantizedManhattan => open!(BqManhattan),
+            HannoyDistance::Hamming => open!(Hamming),
+            HannoyDistance::Chebyshev => open!(Chebyshev),
+            HannoyDistance::Canberra => open!(Canberra),
+            HannoyDistance::BrayCurtis => open!(BrayCurtis),
+            HannoyDistance::Hellinger => open!(Hellinger),
+            HannoyDistance::U8Euclidean => open!(U8Euclidean),
+            HannoyDistance::U8Manhattan => open!(U8Manhattan),
+            HannoyDistance::EarthMovers => open!(EarthMovers),
+            HannoyDistance::Poincare => open!(Poincare),
+        };
+        Ok(HannoyReader { rtxn, reader })
+    })();
+
+    status(result, |reader| *out = Box::into_raw(Box::new(reader)))
+}
+
+/// Searches the `count` nearest neighbours of `query`.
+///
+/// The ids and distances are written in `out_ids` and `out_distances`, which must both be able
+/// to hold `count` values, and the number of results written is stored in `out_len`.
+///
+/// # Safety
+///
+/// `reader` must come from [`hannoy_reader_open`], `query` must point to `dimensions` floats and
+/// the out pointers must be valid for `count` values.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn hannoy_reader_search(
+    reader: *const HannoyReader,
+    query: *const f32,
+    dimensions: usize,
+    count: usize,
+    ef_search: usize,
+    out_ids: *mut ItemId,
+    out_distances: *mut f32,
+    out_len: *mut usize,
+) -> HannoyStatus {
+    if reader.is_null()
+        || query.is_null()
+        || out_ids.is_null()
+        || out_distances.is_null()
+        || out_len.is_null()
+    {
+        return HannoyStatus::InvalidArgument;
+    }
+
+    let HannoyReader { rtxn, reader } = &*reader;
+    let query = std::slice::from_raw_parts(query, dimensions);
+    let ids = std::slice::from_raw_parts_mut(out_ids, count);
+    let distances = std::slice::from_raw_parts_mut(out_distances, count);
+    let result = dispatch!(DynReader, reader, r => r
+        .nns(count)
+        .ef_search(ef_search)
+        .by_vector_into(rtxn, query, ids, distances));
+
+    status(result, |written| *out_len = written)
+}
+
+/// Frees a reader and its read transaction.
+///
+/// # Safety
+///
+/// `reader` must come from [`hannoy_reader_open`] and must not be used afterward.
+#[no_mangle]
+pub unsafe extern "C" fn hannoy_reader_free(reader: *mut HannoyReader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
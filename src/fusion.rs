@@ -0,0 +1,83 @@
+//! Merges the results of several searches, e.g. from different indexes or metrics, into a
+//! single ranking.
+//!
+//! The distances of different metrics aren't comparable: a cosine distance lies between `0`
+//! and `2` while a dot product is unbounded and can be negative. [`reciprocal_rank`] only looks
+//! at the ranks of the items in every list, [`normalized_scores`] rescales the distances of every
+//! list to `[0, 1]` before summing them.
+//!
+//! ```no_run
+//! # use hannoy::{Reader, distances::{Cosine, Euclidean}};
+//! # let (dense, sparse, rtxn): (Reader<Cosine>, Reader<Euclidean>, heed::RoTxn) = todo!();
+//! use hannoy::fusion;
+//!
+//! let dense = dense.nns(50).by_item(&rtxn, 5)?.unwrap().into_nns();
+//! let sparse = sparse.nns(50).by_item(&rtxn, 5)?.unwrap().into_nns();
+//! let fused = fusion::reciprocal_rank(&[&dense, &sparse], fusion::RRF_K);
+//! # Ok::<(), hannoy::Error>(())
+//! ```
+//!
+//! The fused results are sorted by decreasing score, the higher the better, unlike the
+//! distances of the searches.
+use hashbrown::HashMap;
+
+use crate::ItemId;
+
+/// The constant of the reciprocal rank fusion recommended by its authors, it dampens the weight
+/// of the first ranks.
+pub const RRF_K: f32 = 60.0;
+
+/// Fuses the `lists` of results, each sorted by increasing distance, with the reciprocal rank
+/// fusion: every item scores `1 / (k + rank)` for each list it appears in, its rank starting at
+/// `1`. Returns the items sorted by decreasing score.
+pub fn reciprocal_rank(lists: &[&[(ItemId, f32)]], k: f32) -> Vec<(ItemId, f32)> {
+    let mut scores = HashMap::<ItemId, f32>::new();
+    for list in lists {
+        for (rank, &(item, _)) in list.iter().enumerate() {
+            *scores.entry(item).or_default() += 1.0 / (k + (rank + 1) as f32);
+        }
+    }
+    sorted(scores)
+}
+
+/// Fuses the `lists` of results, each sorted by increasing distance, from the sum of their
+/// distances rescaled to `[0, 1]`, weighted by the `weights` of the lists or `1.0` if there are
+/// none. Returns the items sorted by decreasing score.
+///
+/// The distances of every list are min-max normalized, the closest item of a list scores `1`
+/// and the furthest `0`, and the items of a list whose distances are all the same score `1`.
+/// An item missing from a list scores `0` for it, as if it were further than all its items.
+///
+/// # Panics
+///
+/// Panics if there are `weights` but not one per list.
+pub fn normalized_scores(
+    lists: &[&[(ItemId, f32)]],
+    weights: Option<&[f32]>,
+) -> Vec<(ItemId, f32)> {
+    if let Some(weights) = weights {
+        assert_eq!(weights.len(), lists.len(), "one weight is required per list");
+    }
+
+    let mut scores = HashMap::<ItemId, f32>::new();
+    for (i, list) in lists.iter().enumerate() {
+        let weight = weights.map_or(1.0, |weights| weights[i]);
+        let (min, max) =
+            list.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &(_, d)| {
+                (min.min(d), max.max(d))
+            });
+        let range = max - min;
+        for &(item, distance) in list.iter() {
+            let similarity = if range > 0.0 { (max - distance) / range } else { 1.0 };
+            *scores.entry(item).or_default() += weight * similarity;
+        }
+    }
+    sorted(scores)
+}
+
+/// Returns the scored items by decreasing score, the ties by increasing id.
+fn sorted(scores: HashMap<ItemId, f32>) -> Vec<(ItemId, f32)> {
+    let mut scores: Vec<_> = scores.into_iter().collect();
+    scores.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.cmp(b_id)));
+    scores
+}
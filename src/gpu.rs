@@ -0,0 +1,319 @@
+//! Compute the distances of the brute-force stages of the searches on a GPU, through wgpu.
+//!
+//! The graph is always traversed on the CPU, its searches compute a few hundred distances at a
+//! time and wait for every one of them before choosing the next items to visit. The linear scans
+//! of the filtered searches and the searches of a batch of queries instead compare every query
+//! with thousands of vectors at once: with a [`GpuDistances`] given to
+//! [`QueryBuilder::gpu`](crate::QueryBuilder::gpu) their distances are computed by a compute
+//! shader, one invocation per pair of a query and a vector.
+//!
+//! Only the [`Euclidean`](crate::distances::Euclidean),
+//! [`Cosine`](crate::distances::Cosine) and [`Manhattan`](crate::distances::Manhattan)
+//! distances are computed on GPUs, the searches with the other ones keep computing theirs on
+//! the CPU. The sums of the shader are not done in the order of the SIMD kernels of the CPU, the
+//! distances may differ in their last bits.
+//!
+//! ```no_run
+//! use hannoy::gpu::GpuDistances;
+//! # use hannoy::{Reader, distances::Euclidean};
+//! # let (reader, rtxn, candidates): (Reader<Euclidean>, heed::RoTxn, roaring::RoaringBitmap) = todo!();
+//!
+//! let gpu = GpuDistances::new()?;
+//! let queries: [&[f32]; 2] = [&[0.5, 1.5, 2.5], &[2.0, 0.5, 1.0]];
+//! let found = reader.nns(10).candidates(&candidates).gpu(&gpu).by_vector_batch(&rtxn, &queries)?;
+//! # Ok::<(), hannoy::Error>(())
+//! ```
+
+use std::sync::{mpsc, Mutex};
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::distance::Distance;
+use crate::{Error, Result};
+
+/// The number of invocations of a workgroup, along the vectors.
+const WORKGROUP_SIZE: usize = 64;
+
+/// Computes the distances between every query and every vector, `distances[q * vectors + v]`.
+const SHADER: &str = r#"
+struct Params {
+    dimensions: u32,
+    queries: u32,
+    vectors: u32,
+    metric: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> queries: array<f32>;
+@group(0) @binding(2) var<storage, read> vectors: array<f32>;
+@group(0) @binding(3) var<storage, read_write> distances: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let v = id.x;
+    let q = id.y;
+    if v >= params.vectors || q >= params.queries {
+        return;
+    }
+    let query = q * params.dimensions;
+    let vector = v * params.dimensions;
+
+    var distance = 0.0;
+    switch params.metric {
+        // euclidean, squared like the one of the CPU
+        case 0u: {
+            for (var i = 0u; i < params.dimensions; i++) {
+                let d = queries[query + i] - vectors[vector + i];
+                distance += d * d;
+            }
+        }
+        // cosine, (1 - cos) / 2
+        case 1u: {
+            var dot = 0.0;
+            var qn = 0.0;
+            var vn = 0.0;
+            for (var i = 0u; i < params.dimensions; i++) {
+                let a = queries[query + i];
+                let b = vectors[vector + i];
+                dot += a * b;
+                qn += a * a;
+                vn += b * b;
+            }
+            let norms = sqrt(qn) * sqrt(vn);
+            if norms > 1.1920929e-7 {
+                distance = (1.0 - clamp(dot / norms, -1.0, 1.0)) / 2.0;
+            }
+        }
+        // manhattan
+        default: {
+            for (var i = 0u; i < params.dimensions; i++) {
+                distance += abs(queries[query + i] - vectors[vector + i]);
+            }
+        }
+    }
+    distances[q * params.vectors + v] = distance;
+}
+"#;
+
+/// A GPU and the compute pipeline of the distances, shared by the searches of every thread.
+///
+/// Opening a device and compiling the shader takes a while, create it once and keep it for
+/// the lifetime of the application.
+pub struct GpuDistances {
+    adapter: String,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+    /// The error scopes of the device are shared by the threads, the dispatches are serialized
+    /// to report their errors to the search they belong to.
+    dispatch: Mutex<()>,
+}
+
+impl GpuDistances {
+    /// Opens the most powerful GPU of the machine, on the backends selected by the `WGPU_BACKEND`
+    /// environment variable or any of them, and compiles the distances for it.
+    ///
+    /// Returns [`Error::Gpu`] when there is no GPU or none that can run compute shaders.
+    pub fn new() -> Result<GpuDistances> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let options = wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        };
+        let adapter = pollster::block_on(instance.request_adapter(&options)).map_err(gpu)?;
+        let info = adapter.get_info();
+        let capabilities = adapter.get_downlevel_capabilities();
+        if !capabilities.flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+            return Err(gpu(format!("{} can't run compute shaders", info.name)));
+        }
+
+        let descriptor = wgpu::DeviceDescriptor {
+            label: Some("hannoy distances"),
+            required_limits: adapter.limits(),
+            ..Default::default()
+        };
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&descriptor)).map_err(gpu)?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("hannoy distances"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("hannoy distances"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let layout = pipeline.get_bind_group_layout(0);
+
+        Ok(GpuDistances {
+            adapter: info.name,
+            device,
+            queue,
+            pipeline,
+            layout,
+            dispatch: Mutex::new(()),
+        })
+    }
+
+    /// Returns the name of the GPU the distances are computed on.
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter
+    }
+
+    /// Returns whether the distances of `D` are computed on GPUs, the searches compute the
+    /// other ones on the CPU.
+    pub fn supports<D: Distance>() -> bool {
+        metric::<D>().is_some()
+    }
+
+    /// Returns the distances between every one of the `queries` and every one of the `vectors`,
+    /// both given row by row, the ones of the `q`th query starting at `q * vectors`.
+    ///
+    /// The vectors are uploaded by chunks fitting in a storage buffer of the GPU, every chunk
+    /// once for all the queries.
+    pub(crate) fn distances<D: Distance>(
+        &self,
+        queries: &[f32],
+        vectors: &[f32],
+        dimensions: usize,
+    ) -> Result<Vec<f32>> {
+        let Some(metric) = metric::<D>() else {
+            return Err(gpu(format!("the {} distance isn't computed on GPUs", D::name())));
+        };
+        let (n_queries, n_vectors) = (queries.len() / dimensions, vectors.len() / dimensions);
+        let mut distances = vec![0.0; n_queries * n_vectors];
+        if distances.is_empty() {
+            return Ok(distances);
+        }
+
+        let limits = self.device.limits();
+        let max_binding =
+            (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size) as usize;
+        let max_workgroups = limits.max_compute_workgroups_per_dimension as usize;
+        let row = dimensions * size_of::<f32>();
+        if row > max_binding {
+            return Err(gpu(format!("a vector of {dimensions} dimensions doesn't fit the GPU")));
+        }
+        let vector_rows = (max_binding / row).min(max_workgroups * WORKGROUP_SIZE);
+
+        let _dispatch = self.dispatch.lock().unwrap();
+        for (c, chunk) in vectors.chunks(vector_rows * dimensions).enumerate() {
+            let chunk_rows = chunk.len() / dimensions;
+            let query_rows = (max_binding / row)
+                .min(max_binding / (chunk_rows * size_of::<f32>()))
+                .min(max_workgroups)
+                .max(1);
+            let chunk = self.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("hannoy vectors"),
+                contents: bytemuck::cast_slice(chunk),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+
+            for (b, batch) in queries.chunks(query_rows * dimensions).enumerate() {
+                let found = self.dispatch(metric, dimensions, batch, &chunk, chunk_rows)?;
+                for (i, found) in found.chunks_exact(chunk_rows).enumerate() {
+                    let start = (b * query_rows + i) * n_vectors + c * vector_rows;
+                    distances[start..start + chunk_rows].copy_from_slice(found);
+                }
+            }
+        }
+
+        Ok(distances)
+    }
+
+    /// Computes the distances between the `queries` and the `rows` vectors of a chunk and
+    /// reads them back.
+    fn dispatch(
+        &self,
+        metric: u32,
+        dimensions: usize,
+        queries: &[f32],
+        vectors: &wgpu::Buffer,
+        rows: usize,
+    ) -> Result<Vec<f32>> {
+        let n_queries = queries.len() / dimensions;
+        let size = (n_queries * rows * size_of::<f32>()) as u64;
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let params = [dimensions as u32, n_queries as u32, rows as u32, metric];
+        let params = self.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("hannoy parameters"),
+            contents: bytemuck::cast_slice(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let queries = self.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("hannoy queries"),
+            contents: bytemuck::cast_slice(queries),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let distances = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("hannoy distances"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("hannoy read back"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("hannoy distances"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: queries.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: vectors.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: distances.as_entire_binding() },
+            ],
+        });
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(rows.div_ceil(WORKGROUP_SIZE) as u32, n_queries as u32, 1);
+        }
+        encoder.copy_buffer_to_buffer(&distances, 0, &staging, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let validation = pollster::block_on(self.device.pop_error_scope());
+        let out_of_memory = pollster::block_on(self.device.pop_error_scope());
+        if let Some(error) = validation.or(out_of_memory) {
+            return Err(gpu(error));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        staging.map_async(wgpu::MapMode::Read, .., move |mapped| drop(sender.send(mapped)));
+        self.device.poll(wgpu::PollType::Wait).map_err(gpu)?;
+        receiver.recv().map_err(gpu)?.map_err(gpu)?;
+
+        let found = staging
+            .get_mapped_range(..)
+            .chunks_exact(size_of::<f32>())
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        Ok(found)
+    }
+}
+
+/// Returns the number the shader identifies the distance `D` with, if it computes it.
+fn metric<D: Distance>() -> Option<u32> {
+    match D::name() {
+        "euclidean" => Some(0),
+        "cosine" => Some(1),
+        "manhattan" => Some(2),
+        _ => None,
+    }
+}
+
+fn gpu(reason: impl ToString) -> Error {
+    Error::Gpu { reason: reason.to_string() }
+}
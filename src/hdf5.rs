@@ -0,0 +1,534 @@
+//! Load the datasets of the HDF5 files of [ann-benchmarks], e.g. the `train` vectors into an
+//! index and the `test` queries with their `neighbors` ground truth to measure the recall.
+//!
+//! Only the subset of the format written by h5py with its default settings is supported: a
+//! version 0 or 1 superblock, groups indexed by symbol tables, and datasets of little-endian
+//! numbers stored contiguously or compactly. The chunked, and thus the compressed, datasets are
+//! rejected.
+//!
+//! ```no_run
+//! use hannoy::hdf5::Hdf5File;
+//!
+//! let mut file = Hdf5File::open("glove-100-angular.hdf5")?;
+//! let (queries, dimensions) = file.read_f32_matrix("test")?;
+//! let (neighbors, k) = file.read_id_matrix("neighbors")?;
+//! # Ok::<(), hannoy::Error>(())
+//! ```
+//!
+//! [ann-benchmarks]: https://github.com/erikbern/ann-benchmarks
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use heed::RwTxn;
+
+use crate::distance::Distance;
+use crate::{Error, ItemId, Result, Writer};
+
+const SIGNATURE: &[u8] = b"\x89HDF\r\n\x1a\n";
+
+/// The address of the missing objects, with all its bits set.
+const UNDEFINED: u64 = u64::MAX;
+
+/// The number of rows read and written at once.
+const IMPORT_BATCH_SIZE: usize = 8192;
+
+/// The types of the object header messages read to find the datasets and their data.
+const DATASPACE: u16 = 0x0001;
+const DATATYPE: u16 = 0x0003;
+const LAYOUT: u16 = 0x0008;
+const CONTINUATION: u16 = 0x0010;
+const SYMBOL_TABLE: u16 = 0x0011;
+
+/// An HDF5 file whose root group lists the datasets.
+#[derive(Debug)]
+pub struct Hdf5File {
+    file: BufReader<File>,
+    /// The size of the addresses and of the lengths of the file, in bytes.
+    sizes: Sizes,
+    /// The absolute address the addresses of the file are relative to.
+    base: u64,
+    /// The name and object header address of the datasets of the root group.
+    datasets: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sizes {
+    offsets: usize,
+    lengths: usize,
+}
+
+/// The numbers a dataset is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Numbers {
+    Float { size: usize },
+    Integer { size: usize, signed: bool },
+}
+
+impl Numbers {
+    fn size(self) -> usize {
+        match self {
+            Numbers::Float { size } | Numbers::Integer { size, .. } => size,
+        }
+    }
+}
+
+impl fmt::Display for Numbers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Numbers::Float { size } => write!(f, "float{}", size * 8),
+            Numbers::Integer { size, signed: true } => write!(f, "int{}", size * 8),
+            Numbers::Integer { size, signed: false } => write!(f, "uint{}", size * 8),
+        }
+    }
+}
+
+/// Where the values of a dataset are stored.
+#[derive(Debug)]
+enum Storage {
+    Contiguous { address: u64 },
+    Compact(Vec<u8>),
+}
+
+#[derive(Debug)]
+struct Dataset {
+    shape: Vec<usize>,
+    numbers: Numbers,
+    storage: Storage,
+}
+
+impl Hdf5File {
+    /// Opens an HDF5 file and lists the datasets of its root group.
+    pub fn open(path: impl AsRef<Path>) -> Result<Hdf5File> {
+        let mut file = BufReader::new(File::open(path)?);
+        let len = file.get_ref().metadata()?.len();
+
+        // the superblock is at the start of the file or at a power of two from 512 bytes
+        let mut base = 0;
+        loop {
+            let mut signature = [0; SIGNATURE.len()];
+            file.seek(SeekFrom::Start(base))?;
+            file.read_exact(&mut signature)?;
+            if signature == SIGNATURE {
+                break;
+            }
+            base = if base == 0 { 512 } else { base * 2 };
+            if base + SIGNATURE.len() as u64 > len {
+                return Err(invalid("missing the signature"));
+            }
+        }
+
+        let mut superblock = [0; 16];
+        file.read_exact(&mut superblock)?;
+        let version = superblock[0];
+        if version > 1 {
+            return Err(invalid(format!("unsupported superblock version {version}")));
+        }
+        let sizes = Sizes { offsets: superblock[5] as usize, lengths: superblock[6] as usize };
+        if !matches!(sizes.offsets, 2 | 4 | 8) || !matches!(sizes.lengths, 2 | 4 | 8) {
+            return Err(invalid(format!("unsupported sizes of offsets and lengths {sizes:?}")));
+        }
+        if version == 1 {
+            file.seek_relative(4)?;
+        }
+        // the base address, the free-space, end of file and driver addresses, then the root
+        // symbol table entry: its name and object header addresses
+        let mut addresses = vec![0; 6 * sizes.offsets];
+        file.read_exact(&mut addresses)?;
+        // the addresses are relative to the base address, the one of the superblock by default
+        let mut addresses = Parser::new(&addresses, sizes);
+        let base = addresses.address()?;
+        addresses.take(4 * sizes.offsets)?;
+        let root = addresses.address()?;
+
+        let mut hdf5 = Hdf5File { file, sizes, base, datasets: Vec::new() };
+        hdf5.datasets = hdf5.list_group(root)?;
+        Ok(hdf5)
+    }
+
+    /// Returns the names of the datasets and groups of the root group, in the order of the file.
+    pub fn dataset_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.datasets.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Reads a two dimensional `float32` dataset, such as the `train` or `test` vectors, and
+    /// returns the values row by row with the number of columns.
+    pub fn read_f32_matrix(&mut self, name: &str) -> Result<(Vec<f32>, usize)> {
+        let dataset = self.dataset(name)?;
+        let [rows, columns] = f32_matrix_shape(name, &dataset)?;
+        let bytes = self.read_values(&dataset, 0, rows * columns)?;
+        Ok((floats(&bytes).collect(), columns))
+    }
+
+    /// Reads a two dimensional integer dataset as item ids, such as the `neighbors` of the
+    /// queries, and returns the values row by row with the number of columns.
+    pub fn read_id_matrix(&mut self, name: &str) -> Result<(Vec<ItemId>, usize)> {
+        let dataset = self.dataset(name)?;
+        let [rows, columns] = matrix_shape(name, &dataset)?;
+        let Numbers::Integer { size, signed } = dataset.numbers else {
+            return Err(invalid(format!("expected `{name}` to contain integers")));
+        };
+        let bytes = self.read_values(&dataset, 0, rows * columns)?;
+        let ids = bytes.chunks_exact(size).map(|bytes| {
+            let mut value = [0; 8];
+            value[..size].copy_from_slice(bytes);
+            // the sign is extended to the 64 bits of the value
+            if signed && bytes[size - 1] & 0x80 != 0 {
+                value[size..].fill(0xff);
+            }
+            let id = match signed {
+                true => i128::from(i64::from_le_bytes(value)),
+                false => i128::from(u64::from_le_bytes(value)),
+            };
+            ItemId::try_from(id).map_err(|_| invalid(format!("invalid item id {id}")))
+        });
+        Ok((ids.collect::<Result<_>>()?, columns))
+    }
+
+    /// Returns the dataset of the root group named `name`.
+    fn dataset(&mut self, name: &str) -> Result<Dataset> {
+        let address = self
+            .datasets
+            .iter()
+            .find(|(dataset, _)| dataset == name)
+            .map(|&(_, address)| address)
+            .ok_or_else(|| invalid(format!("missing dataset `{name}`")))?;
+
+        let (mut shape, mut numbers, mut storage) = (None, None, None);
+        for (kind, message) in self.object_header(address)? {
+            let mut message = Parser::new(&message, self.sizes);
+            match kind {
+                DATASPACE => shape = Some(message.dataspace()?),
+                DATATYPE => numbers = Some(message.datatype()?),
+                LAYOUT => storage = Some(message.layout()?),
+                _ => (),
+            }
+        }
+        match (shape, numbers, storage) {
+            (Some(shape), Some(numbers), Some(storage)) => Ok(Dataset { shape, numbers, storage }),
+            _ => Err(invalid(format!("`{name}` is not a dataset"))),
+        }
+    }
+
+    /// Reads the bytes of `count` values of a dataset from the `start`-th one.
+    fn read_values(&mut self, dataset: &Dataset, start: usize, count: usize) -> Result<Vec<u8>> {
+        let size = dataset.numbers.size();
+        let range = start * size..(start + count) * size;
+        match &dataset.storage {
+            Storage::Compact(bytes) => bytes
+                .get(range)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| invalid("the compact data is truncated")),
+            Storage::Contiguous { .. } if count == 0 => Ok(Vec::new()),
+            Storage::Contiguous { address: UNDEFINED } => Err(invalid("the dataset has no data")),
+            Storage::Contiguous { address } => {
+                self.read_at(address + range.start as u64, range.len())
+            }
+        }
+    }
+
+    /// Reads `len` bytes at an address of the file.
+    fn read_at(&mut self, address: u64, len: usize) -> Result<Vec<u8>> {
+        let mut bytes = vec![0; len];
+        self.file.seek(SeekFrom::Start(self.base + address))?;
+        self.file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Returns the type and the data of the messages of a version 1 object header, including
+    /// the ones of its continuation blocks.
+    fn object_header(&mut self, address: u64) -> Result<Vec<(u16, Vec<u8>)>> {
+        let prefix = self.read_at(address, 16)?;
+        if prefix[0] != 1 {
+            let reason = format!("unsupported object header version {}", prefix[0]);
+            return Err(invalid(reason));
+        }
+        let mut prefix = Parser::new(&prefix[2..], self.sizes);
+        let count = prefix.u16()? as usize;
+        prefix.u32()?;
+        let size = prefix.u32()? as usize;
+
+        let mut messages = Vec::with_capacity(count);
+        let mut blocks = vec![(address + 16, size)];
+        while let Some((address, size)) = blocks.pop() {
+            let block = self.read_at(address, size)?;
+            let mut block = Parser::new(&block, self.sizes);
+            while block.remaining() >= 8 && messages.len() < count {
+                let kind = block.u16()?;
+                let size = block.u16()? as usize;
+                block.take(4)?;
+                let data = block.take(size)?;
+                if kind == CONTINUATION {
+                    let mut data = Parser::new(data, self.sizes);
+                    blocks.push((data.address()?, data.length()? as usize));
+                }
+                messages.push((kind, data.to_vec()));
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Returns the name and object header address of the entries of a group.
+    fn list_group(&mut self, address: u64) -> Result<Vec<(String, u64)>> {
+        let mut table = None;
+        for (kind, message) in self.object_header(address)? {
+            if kind == SYMBOL_TABLE {
+                let mut message = Parser::new(&message, self.sizes);
+                table = Some((message.address()?, message.address()?));
+            }
+        }
+        let Some((tree, heap)) = table else {
+            return Err(invalid("the root group has no symbol table"));
+        };
+
+        // the names of the entries are stored in the local heap of the group
+        let header = self.read_at(heap, 8 + 2 * self.sizes.lengths + self.sizes.offsets)?;
+        if &header[..4] != b"HEAP" {
+            return Err(invalid("missing the local heap of the root group"));
+        }
+        let mut header = Parser::new(&header[8..], self.sizes);
+        let heap_size = header.length()? as usize;
+        header.length()?;
+        let names = self.read_at(header.address()?, heap_size)?;
+
+        let mut entries = Vec::new();
+        let mut nodes = vec![tree];
+        while let Some(node) = nodes.pop() {
+            let (level, children) = self.btree_node(node)?;
+            if level > 0 {
+                // the children are visited in order, the stack pops the last pushed first
+                nodes.extend(children.into_iter().rev());
+                continue;
+            }
+            for child in children {
+                for (name, address) in self.symbol_node(child)? {
+                    let name = names
+                        .get(name as usize..)
+                        .and_then(|name| name.split(|&b| b == 0).next())
+                        .ok_or_else(|| invalid("a name is out of the local heap"))?;
+                    entries.push((String::from_utf8_lossy(name).into_owned(), address));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Returns the level and the children of a node of the version 1 B-tree of a group.
+    fn btree_node(&mut self, address: u64) -> Result<(u8, Vec<u64>)> {
+        let header = self.read_at(address, 8)?;
+        if &header[..4] != b"TREE" || header[4] != 0 {
+            return Err(invalid("missing a node of the group B-tree"));
+        }
+        let (level, count) = (header[5], u16::from_le_bytes([header[6], header[7]]) as usize);
+        let Sizes { offsets, lengths } = self.sizes;
+        let size = 2 * offsets + count * (lengths + offsets) + lengths;
+        let node = self.read_at(address + 8, size)?;
+        let mut node = Parser::new(&node, self.sizes);
+        node.take(2 * offsets)?;
+        let mut children = Vec::with_capacity(count);
+        for _ in 0..count {
+            node.length()?;
+            children.push(node.address()?);
+        }
+        Ok((level, children))
+    }
+
+    /// Returns the heap offset of the name and the object header address of the entries of a
+    /// symbol table node.
+    fn symbol_node(&mut self, address: u64) -> Result<Vec<(u64, u64)>> {
+        let header = self.read_at(address, 8)?;
+        if &header[..4] != b"SNOD" {
+            return Err(invalid("missing a symbol table node"));
+        }
+        let count = u16::from_le_bytes([header[6], header[7]]) as usize;
+        let entry = 2 * self.sizes.offsets + 24;
+        let entries = self.read_at(address + 8, count * entry)?;
+        entries
+            .chunks_exact(entry)
+            .map(|entry| {
+                let mut entry = Parser::new(entry, self.sizes);
+                Ok((entry.address()?, entry.address()?))
+            })
+            .collect()
+    }
+}
+
+/// Reads the little-endian fields of the structures of the file.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    sizes: Sizes,
+}
+
+impl<'a> Parser<'a> {
+    fn new(bytes: &'a [u8], sizes: Sizes) -> Parser<'a> {
+        Parser { bytes, sizes }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some((taken, rest)) = self.bytes.split_at_checked(len) else {
+            return Err(invalid("a structure is truncated"));
+        };
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64> {
+        let mut value = [0; 8];
+        value[..len].copy_from_slice(self.take(len)?);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.uint(1).map(|value| value as u8)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        self.uint(2).map(|value| value as u16)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.uint(4).map(|value| value as u32)
+    }
+
+    /// Reads an address, the undefined ones are returned as [`UNDEFINED`].
+    fn address(&mut self) -> Result<u64> {
+        let size = self.sizes.offsets;
+        let address = self.uint(size)?;
+        Ok(if address == u64::MAX >> (64 - 8 * size) { UNDEFINED } else { address })
+    }
+
+    fn length(&mut self) -> Result<u64> {
+        self.uint(self.sizes.lengths)
+    }
+
+    /// Reads the dimensions of a dataspace message.
+    fn dataspace(&mut self) -> Result<Vec<usize>> {
+        let version = self.u8()?;
+        let rank = self.u8()? as usize;
+        match version {
+            1 => self.take(6)?,
+            2 => self.take(2)?,
+            version => return Err(invalid(format!("unsupported dataspace version {version}"))),
+        };
+        (0..rank).map(|_| Ok(self.length()? as usize)).collect()
+    }
+
+    /// Reads the numbers of a datatype message.
+    fn datatype(&mut self) -> Result<Numbers> {
+        let class = self.u8()? & 0x0f;
+        let bits = self.u8()?;
+        self.take(2)?;
+        let size = self.u32()? as usize;
+        let numbers = match class {
+            0 if matches!(size, 1 | 2 | 4 | 8) => {
+                Numbers::Integer { size, signed: bits & 0x08 != 0 }
+            }
+            1 => Numbers::Float { size },
+            _ => {
+                return Err(invalid(format!("unsupported datatype class {class} of {size} bytes")))
+            }
+        };
+        // the bit 0 is the byte order and the bit 6 of the floats is the VAX order
+        if bits & 0x01 != 0 || (class == 1 && bits & 0x40 != 0) {
+            return Err(invalid("big-endian datasets are not supported"));
+        }
+        Ok(numbers)
+    }
+
+    /// Reads the storage of a data layout message.
+    fn layout(&mut self) -> Result<Storage> {
+        let version = self.u8()?;
+        let class = match version {
+            3 | 4 => self.u8()?,
+            1 | 2 => {
+                let rank = self.u8()?;
+                let class = self.u8()?;
+                if class != 1 {
+                    return Err(invalid(format!("unsupported layout class {class}")));
+                }
+                self.take(5)?;
+                let address = self.address()?;
+                self.take(4 * rank as usize)?;
+                return Ok(Storage::Contiguous { address });
+            }
+            version => return Err(invalid(format!("unsupported layout version {version}"))),
+        };
+        match class {
+            0 => {
+                let size = self.u16()? as usize;
+                Ok(Storage::Compact(self.take(size)?.to_vec()))
+            }
+            1 => Ok(Storage::Contiguous { address: self.address()? }),
+            2 => Err(invalid("chunked datasets are not supported")),
+            class => Err(invalid(format!("unsupported layout class {class}"))),
+        }
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::InvalidHdf5 { reason: reason.into() }
+}
+
+fn matrix_shape(name: &str, dataset: &Dataset) -> Result<[usize; 2]> {
+    match dataset.shape[..] {
+        [rows, columns] => Ok([rows, columns]),
+        ref shape => Err(invalid(format!("expected `{name}` to be a 2D matrix, got {shape:?}"))),
+    }
+}
+
+fn f32_matrix_shape(name: &str, dataset: &Dataset) -> Result<[usize; 2]> {
+    if dataset.numbers != (Numbers::Float { size: 4 }) {
+        let numbers = dataset.numbers;
+        return Err(invalid(format!("expected `{name}` to be a float32 matrix, got {numbers}")));
+    }
+    matrix_shape(name, dataset)
+}
+
+fn floats(bytes: &[u8]) -> impl Iterator<Item = f32> + '_ {
+    bytes.chunks_exact(size_of::<f32>()).map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+}
+
+impl<D: Distance> Writer<D> {
+    /// Loads a `float32` matrix dataset of an HDF5 file, one row per item, such as the `train`
+    /// vectors of an ann-benchmarks file, and returns the number of rows read. The n-th row is
+    /// inserted with the id `n`, the ids the `neighbors` of the benchmarks refer to.
+    ///
+    /// Rows are streamed by batches and written with [`Self::add_items`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Cosine};
+    /// # let (writer, mut wtxn): (Writer<Cosine>, heed::RwTxn) = todo!();
+    /// let rows = writer.import_hdf5(&mut wtxn, "glove-100-angular.hdf5", "train")?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn import_hdf5(&self, wtxn: &mut RwTxn, path: impl AsRef<Path>, name: &str) -> Result<u64> {
+        let mut file = Hdf5File::open(path)?;
+        let dataset = file.dataset(name)?;
+        let [rows, dimensions] = f32_matrix_shape(name, &dataset)?;
+        if dimensions != self.dimensions() {
+            return Err(Error::InvalidDimensions { expected: self.dimensions(), got: dimensions });
+        }
+        if ItemId::try_from(rows).is_err() {
+            return Err(Error::DatabaseFull);
+        }
+
+        let mut vectors = Vec::new();
+        for start in (0..rows).step_by(IMPORT_BATCH_SIZE) {
+            let count = IMPORT_BATCH_SIZE.min(rows - start);
+            let bytes = file.read_values(&dataset, start * dimensions, count * dimensions)?;
+            vectors.clear();
+            vectors.extend(floats(&bytes));
+            let ids = start as ItemId..;
+            self.add_items(wtxn, ids.zip(vectors.chunks_exact(dimensions)))?;
+        }
+
+        Ok(rows as u64)
+    }
+}
@@ -5,24 +5,25 @@ use std::f32;
 use std::fmt::{self, Debug};
 use std::marker::PhantomData;
 use std::sync::atomic::Ordering::Relaxed;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
+use heed::types::Bytes;
 use heed::RwTxn;
 use min_max_heap::MinMaxHeap;
 use papaya::HashMap;
 use rand::distributions::WeightedIndex;
 use rand::prelude::Distribution;
 use rand::Rng;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use roaring::RoaringBitmap;
 use tinyvec::{array_vec, ArrayVec};
 use tracing::debug;
 
 use crate::key::Key;
-use crate::node::{Item, Links, Node};
+use crate::node::{encode_array_links, Item, Links, Node};
 use crate::ordered_float::OrderedFloat;
-use crate::parallel::{ImmutableItems, ImmutableLinks};
+use crate::parallel::{self, ImmutableItems, ImmutableLinks};
 use crate::progress::{AtomicInsertItemsStep, HannoyBuild};
+use crate::scheduler::Scheduler;
 use crate::stats::BuildStats;
 use crate::writer::{BuildOption, FrozenReader};
 use crate::{Database, Distance, Error, ItemId, Result, CANCELLATION_PROBING};
@@ -58,10 +59,21 @@ pub struct HnswBuilder<'a, D, const M: usize, const M0: usize> {
     assign_probas: Vec<f32>,
     ef_construction: usize,
     alpha: f32,
+    shards: usize,
+    array_links: bool,
     cancel: &'a (dyn Fn() -> bool + 'a + Sync + Send),
     pub max_level: usize,
     pub entry_points: Vec<ItemId>,
     pub layers: Vec<HashMap<ItemId, NodeState<M0>>>,
+    /// Items already present in the previous graph whose vector was replaced since. Their old
+    /// links are only walked through by the searches, never kept.
+    replaced: RoaringBitmap,
+    /// Items of the previous graph whose links are searched again, see
+    /// [`crate::HannoyBuilder::relink_regions`].
+    relinked: RoaringBitmap,
+    /// The number of items the index is expected to grow to, see
+    /// [`crate::Writer::reserve`].
+    reserved: u64,
     distance: PhantomData<D>,
 }
 
@@ -72,10 +84,15 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
             assign_probas,
             ef_construction: opts.ef_construction,
             alpha: opts.alpha,
+            shards: opts.shards,
+            array_links: opts.array_links,
             cancel: &opts.cancel,
             max_level: 0,
             entry_points: Vec::new(),
             layers: vec![],
+            replaced: RoaringBitmap::new(),
+            relinked: RoaringBitmap::new(),
+            reserved: 0,
             distance: PhantomData,
         }
     }
@@ -90,6 +107,27 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
         self
     }
 
+    pub fn with_replaced_items(mut self, replaced: RoaringBitmap) -> Self {
+        self.replaced = replaced;
+        self
+    }
+
+    pub fn with_relinked_items(mut self, relinked: RoaringBitmap) -> Self {
+        self.relinked = relinked;
+        self
+    }
+
+    pub fn with_reserved_items(mut self, reserved: u64) -> Self {
+        self.reserved = reserved;
+        self
+    }
+
+    /// Returns the number of nodes the `level` is expected to hold once the index reaches its
+    /// reserved items, a node reaches the level `l` with a probability of `M^-l`.
+    fn reserved_capacity(&self, level: usize) -> usize {
+        (self.reserved as f64 / (M as f64).powi(level as i32)) as usize
+    }
+
     /// build quantiles from an x ~ exp(1/ln(m))
     fn get_default_probas() -> Vec<f32> {
         let mut assign_probas = Vec::with_capacity(M);
@@ -119,6 +157,7 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
     }
 
     #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(name = "build_graph", level = "debug", skip_all, fields(to_insert = to_insert.len(), to_delete = to_delete.len())))]
     pub fn build<R, P>(
         &mut self,
         mut to_insert: RoaringBitmap,
@@ -135,7 +174,7 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
     {
         let mut build_stats = BuildStats::new();
 
-        let items = ImmutableItems::new(wtxn, database, index, options)?;
+        let items = ImmutableItems::new(wtxn, database, options.vectors.unwrap_or(index), options)?;
         let links = ImmutableLinks::new(wtxn, database, index, database.len(wtxn)?, options)?;
         let lmdb = FrozenReader { index, items: &items, links: &links };
 
@@ -159,21 +198,52 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
         )?;
         to_insert |= ok_eps;
 
-        let level_groups: Vec<_> = levels.chunk_by(|(_, la), (_, lb)| la == lb).collect();
-
         // Insert layers L...0 multi-threaded
         options.progress.update(HannoyBuild::BuildingTheGraph);
         let (item_ctr, insert_step) = AtomicInsertItemsStep::new(to_insert.len());
         options.progress.update(insert_step);
+        self.insert_levels(&levels, &lmdb, &mut build_stats, &item_ctr)?;
+        self.relink(&lmdb, to_delete, &build_stats)?;
+
+        self.maybe_patch_old_links(&lmdb, to_delete, options)?;
+
+        // Single-threaded write to lmdb
+        options.progress.update(HannoyBuild::WritingTheItems);
+        self.write_links(database, index, wtxn)?;
+
+        build_stats.compute_mean_degree(wtxn, &database, index)?;
+        Ok(build_stats)
+    }
+
+    /// Inserts the items layer group by layer group, from the highest to the lowest, each group
+    /// in parallel with the work-stealing [`Scheduler`]. The bottom layer group is sharded when
+    /// asked to, see [`Self::insert_shards`].
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all, fields(items = levels.len())))]
+    fn insert_levels(
+        &self,
+        levels: &[(ItemId, usize)],
+        lmdb: &FrozenReader<'_, D>,
+        build_stats: &mut BuildStats<D>,
+        item_ctr: &AtomicU64,
+    ) -> Result<()> {
+        let level_groups: Vec<_> = levels.chunk_by(|(_, la), (_, lb)| la == lb).collect();
         let cancel_index = AtomicUsize::new(0);
+        let not_excluded = RoaringBitmap::new();
+        let scheduler = Scheduler::new();
 
         level_groups.into_iter().try_for_each(|grp| {
-            grp.into_par_iter().try_for_each(|&(item_id, lvl)| {
+            if grp[0].1 == 0 && self.shards > 1 {
+                self.insert_shards(grp, lmdb, build_stats, item_ctr)?;
+                build_stats.layer_dist.insert(0, grp.len());
+                return Ok(());
+            }
+
+            scheduler.try_for_each(grp, |&(item_id, lvl)| {
                 if cancel_index.fetch_add(1, Relaxed) % CANCELLATION_PROBING == 0 && (self.cancel)()
                 {
                     Err(Error::BuildCancelled)
                 } else {
-                    self.insert(item_id, lvl, &lmdb, &build_stats)?;
+                    self.insert(item_id, lvl, &not_excluded, lmdb, build_stats)?;
                     item_ctr.fetch_add(1, Relaxed);
                     Ok(())
                 }
@@ -182,14 +252,99 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
             build_stats.layer_dist.insert(grp[0].1, grp.len());
 
             Ok(()) as Result<(), Error>
+        })
+    }
+
+    /// Partitions the items only present in the bottom layer into independent sub-graphs, each
+    /// built by a single thread, and stitches them together once they're all built.
+    ///
+    /// The items of a shard are only linked to the other items of the shard and to the ones
+    /// shared by all the shards: the items of the upper layers and of the previous build. The
+    /// items of a shard linked to a shared item are its boundary nodes, they're cross-linked to
+    /// their nearest neighbours in the other shards so that every sub-graph is reachable.
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all, fields(items = group.len(), shards = self.shards)))]
+    fn insert_shards(
+        &self,
+        group: &[(ItemId, usize)],
+        lmdb: &FrozenReader<'_, D>,
+        build_stats: &BuildStats<D>,
+        item_ctr: &AtomicU64,
+    ) -> Result<()> {
+        let mut shards = vec![RoaringBitmap::new(); self.shards];
+        for (i, &(item_id, _)) in group.iter().enumerate() {
+            shards[i % self.shards].insert(item_id);
+        }
+        let sharded: RoaringBitmap = group.iter().map(|&(item_id, _)| item_id).collect();
+        let cancel_index = AtomicUsize::new(0);
+
+        debug!("Building {} independent sub-graphs", self.shards);
+        parallel::try_for_each_ref(&shards, |shard| {
+            let others = &sharded - shard;
+            for item_id in shard {
+                if cancel_index.fetch_add(1, Relaxed) % CANCELLATION_PROBING == 0 && (self.cancel)()
+                {
+                    return Err(Error::BuildCancelled);
+                }
+                self.insert(item_id, 0, &others, lmdb, build_stats)?;
+                item_ctr.fetch_add(1, Relaxed);
+            }
+            Ok(())
         })?;
 
-        self.maybe_patch_old_links(&lmdb, to_delete, options)?;
+        debug!("Stitching the sub-graphs by their boundary nodes");
+        let map = &self.layers[0];
+        parallel::try_for_each_ref(&shards, |shard| {
+            let boundary: Vec<_> = shard
+                .iter()
+                .filter_map(|item_id| {
+                    let shared: Vec<_> = map
+                        .pin()
+                        .get(&item_id)?
+                        .links
+                        .iter()
+                        .map(|&(_, n)| n)
+                        .filter(|n| !shard.contains(*n))
+                        .collect();
+                    (!shared.is_empty()).then_some((item_id, shared))
+                })
+                .collect();
 
-        // Single-threaded write to lmdb
-        options.progress.update(HannoyBuild::WritingTheItems);
+            for (item_id, shared) in boundary {
+                if cancel_index.fetch_add(1, Relaxed) % CANCELLATION_PROBING == 0 && (self.cancel)()
+                {
+                    return Err(Error::BuildCancelled);
+                }
+                // search the other shards from the shared items it is linked to
+                let q = lmdb.get_item(item_id)?;
+                let neighbours = self
+                    .walk_layer(&q, &shared, 0, self.ef_construction, shard, lmdb, build_stats)?
+                    .into_vec()
+                    .into_iter()
+                    .filter(|&(_, n)| sharded.contains(n))
+                    .collect();
+
+                for (dist, n) in self.robust_prune(neighbours, 0, self.alpha, lmdb)? {
+                    self.add_link(item_id, (dist, n), 0, lmdb)?;
+                    self.add_link(n, (dist, item_id), 0, lmdb)?;
+                    build_stats.incr_link_count(2);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Writes the links of every layer to LMDB, replacing the ones of the re-inserted items.
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all))]
+    fn write_links(&self, database: Database<D>, index: u16, wtxn: &mut RwTxn) -> Result<()> {
         let mut cancellation_index = 0;
 
+        // The links of replaced items may live on layers they were not re-inserted in.
+        for item_id in &self.replaced {
+            for lvl in 0..self.layers.len() {
+                database.delete(wtxn, &Key::links(index, item_id, lvl as u8))?;
+            }
+        }
+
         for lvl in 0..=self.max_level {
             let Some(map) = self.layers.get(lvl) else { break };
             let map_guard = map.pin();
@@ -200,24 +355,24 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
                 }
 
                 let key = Key::links(index, *item_id, lvl as u8);
-                let links = Links {
-                    links: Cow::Owned(RoaringBitmap::from_iter(
-                        node_state.links.iter().map(|(_, i)| *i),
-                    )),
-                };
-
-                database.put(wtxn, &key, &Node::Links(links))?;
+                let links = RoaringBitmap::from_iter(node_state.links.iter().map(|(_, i)| *i));
+                if self.array_links {
+                    let bytes = encode_array_links(&links);
+                    database.remap_data_type::<Bytes>().put(wtxn, &key, &bytes)?;
+                } else {
+                    database.put(wtxn, &key, &Node::Links(Links { links: Cow::Owned(links) }))?;
+                }
                 cancellation_index += 1;
             }
         }
 
-        build_stats.compute_mean_degree(wtxn, &database, index)?;
-        Ok(build_stats)
+        Ok(())
     }
 
     /// This function resolves several nasty edge cases that can occur, namely : deleted
     /// or partially deleted entrypoints, new indexed points assigned to higher layers, ensuring
     /// entry points are present on all layers before build
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all))]
     fn prepare_levels_and_entry_points<P>(
         &mut self,
         levels: &mut Vec<(u32, usize)>,
@@ -260,16 +415,22 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
         }
 
         // Schedule old entry point ids for re-indexing, otherwise we end up building a completely
-        // isolated sub-graph.
-        levels.extend(ok_eps.iter().map(|id| (id, self.max_level)));
+        // isolated sub-graph. The replaced ones are already scheduled and only raised to the top.
+        let scheduled = RoaringBitmap::from_iter(levels.iter().map(|&(id, _)| id));
+        for (id, level) in levels.iter_mut() {
+            if ok_eps.contains(*id) {
+                *level = (*level).max(self.max_level);
+            }
+        }
+        levels.extend((&ok_eps - scheduled).iter().map(|id| (id, self.max_level)));
 
         if cur_max_level > self.max_level {
             self.entry_points.clear();
         }
 
         self.max_level = self.max_level.max(cur_max_level);
-        for _ in 0..=self.max_level {
-            self.layers.push(HashMap::new());
+        for level in 0..=self.max_level {
+            self.layers.push(HashMap::with_capacity(self.reserved_capacity(level)));
         }
 
         levels.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
@@ -289,10 +450,13 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
         Ok(ok_eps)
     }
 
+    /// Inserts the `query` item in the layers up to `level`, without ever linking it to the
+    /// `excluded` items.
     fn insert(
         &self,
         query: ItemId,
         level: usize,
+        excluded: &RoaringBitmap,
         lmdb: &FrozenReader<'_, D>,
         build_stats: &BuildStats<D>,
     ) -> Result<()> {
@@ -302,7 +466,7 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
 
         // Greedy search with: ef = 1
         for lvl in (level + 1..=self.max_level).rev() {
-            let neighbours = self.walk_layer(&q, &eps, lvl, 1, lmdb, build_stats)?;
+            let neighbours = self.walk_layer(&q, &eps, lvl, 1, excluded, lmdb, build_stats)?;
             let closest = neighbours.peek_min().map(|(_, n)| *n).expect("No neighbor was found");
             eps = vec![closest];
         }
@@ -311,8 +475,9 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
 
         // Beam search with: ef = ef_construction
         for lvl in (0..=level).rev() {
-            let neighbours =
-                self.walk_layer(&q, &eps, lvl, self.ef_construction, lmdb, build_stats)?.into_vec();
+            let neighbours = self
+                .walk_layer(&q, &eps, lvl, self.ef_construction, excluded, lmdb, build_stats)?
+                .into_vec();
 
             eps.clear();
             for (dist, n) in self.robust_prune(neighbours, level, self.alpha, lmdb)? {
@@ -328,10 +493,50 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
         Ok(())
     }
 
+    /// Searches new links in the bottom layer for the relinked items, from the links they
+    /// already have. They're merged with their old links when patching them.
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all, fields(items = self.relinked.len())))]
+    fn relink(
+        &self,
+        lmdb: &FrozenReader<'_, D>,
+        to_delete: &RoaringBitmap,
+        build_stats: &BuildStats<D>,
+    ) -> Result<()> {
+        let relinked: Vec<_> = (&self.relinked - to_delete).iter().collect();
+        let cancel_index = AtomicUsize::new(0);
+        let not_excluded = RoaringBitmap::new();
+
+        parallel::try_for_each(relinked, |item_id| {
+            if cancel_index.fetch_add(1, Relaxed) % CANCELLATION_PROBING == 0 && (self.cancel)() {
+                return Err(Error::BuildCancelled);
+            }
+            let mut eps = self.get_neighbours(lmdb, item_id, 0, build_stats)?;
+            eps.retain(|&n| n != item_id && !to_delete.contains(n));
+            if eps.is_empty() {
+                return Ok(());
+            }
+
+            let q = lmdb.get_item(item_id)?;
+            let neighbours = self
+                .walk_layer(&q, &eps, 0, self.ef_construction, &not_excluded, lmdb, build_stats)?
+                .into_vec()
+                .into_iter()
+                .filter(|&(_, n)| n != item_id)
+                .collect();
+            for (dist, n) in self.robust_prune(neighbours, 0, self.alpha, lmdb)? {
+                self.add_link(item_id, (dist, n), 0, lmdb)?;
+                self.add_link(n, (dist, item_id), 0, lmdb)?;
+                build_stats.incr_link_count(2);
+            }
+            Ok(())
+        })
+    }
+
     /// During incremental updates we store a working copy of potential links to the new items. At
     /// the end of indexing we need to merge the old and new links and prune ones pointing to
     /// deleted items.
     /// Algorithm 4 from FreshDiskANN paper.
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all))]
     fn maybe_patch_old_links<P>(
         &mut self,
         lmdb: &FrozenReader<D>,
@@ -361,7 +566,7 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
 
         let cancel_index = AtomicUsize::new(0);
 
-        links_in_db.into_par_iter().try_for_each(|result| {
+        parallel::try_for_each(links_in_db, |result| {
             if cancel_index.fetch_add(1, Ordering::Relaxed) % CANCELLATION_PROBING == 0
                 && (self.cancel)()
             {
@@ -371,28 +576,41 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
 
             // Since we delete links AFTER a build (we need to do this to apply diskann-approach
             // for patching), links belonging to deleted items may still be present. We don't
-            // care about patching them.
-            if to_delete.contains(id) {
+            // care about patching them. Replaced items were re-inserted from scratch so their
+            // old links are dropped too.
+            if to_delete.contains(id) || self.replaced.contains(id) {
                 return Ok(());
             }
+            // A replaced item moved in the space, we treat it like a deletion for the purpose of
+            // finding new candidates but keep it as a potential neighbour.
             let del_subset = &links & to_delete;
+            let replaced_subset = &links & &self.replaced;
 
             // This is safe because we resized layers above.
             let map_guard = self.layers[lvl].pin();
             let mut new_links = map_guard.get(&id).map(|s| s.links.to_vec()).unwrap_or_default();
 
             // No work to be done, continue
-            if del_subset.is_empty() && new_links.is_empty() {
+            if del_subset.is_empty() && replaced_subset.is_empty() && new_links.is_empty() {
                 return Ok(());
             }
 
             // Iter through each of the deleted, and explore his neighbours
             let mut bitmap = RoaringBitmap::new();
-            for item_id in del_subset.iter() {
+            for item_id in del_subset.iter().chain(replaced_subset.iter()) {
                 bitmap.extend(lmdb.get_links(item_id, lvl)?.iter());
             }
             bitmap |= links;
             bitmap -= to_delete;
+            // The neighbours of a deleted or replaced item include this one, an item is never
+            // linked to itself, like in `add_link`
+            bitmap.remove(id);
+            // Replaced items may have been re-inserted on fewer layers than before
+            for item_id in replaced_subset.iter() {
+                if !map_guard.contains_key(&item_id) {
+                    bitmap.remove(item_id);
+                }
+            }
 
             // TODO: abstract this layer search and pruning bit as its duplicated a lot in
             // this file
@@ -427,7 +645,8 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
     ) -> Result<Vec<ItemId>> {
         let mut res = Vec::new();
 
-        // O(1) from frozzenreader
+        // O(1) from frozzenreader, the old links of replaced items still lead to the rest of the
+        // graph even though they aren't kept
         if let Ok(Links { links }) = lmdb.get_links(item_id, level) {
             build_stats.incr_lmdb_hits();
             res.extend(links.iter());
@@ -458,6 +677,7 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
         eps: &[ItemId],
         level: usize,
         ef: usize,
+        excluded: &RoaringBitmap,
         lmdb: &FrozenReader<'_, D>,
         build_stats: &BuildStats<D>,
     ) -> Result<MinMaxHeap<ScoredLink>> {
@@ -485,7 +705,7 @@ impl<'a, D: Distance, const M: usize, const M0: usize> HnswBuilder<'a, D, M, M0>
             // Get neighborhood of candidate either from self or LMDB
             let proximity = self.get_neighbours(lmdb, c, level, build_stats)?;
             for point in proximity {
-                if !visited.insert(point) {
+                if !visited.insert(point) || excluded.contains(point) {
                     continue;
                 }
                 // If the item isn't in the frozzen reader it must have been deleted from the index,
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::sync::Arc;
+
+use roaring::RoaringBitmap;
+
+use crate::ItemId;
+
+/// Callbacks a [`Writer`](crate::Writer) invokes on the changes it makes, e.g. to invalidate the
+/// caches of an application or to keep an audit log, see
+/// [`Writer::set_hooks`](crate::Writer::set_hooks).
+///
+/// The hooks are called once the change is written in the transaction, which may still be
+/// aborted. Every method does nothing by default.
+pub trait WriterHooks: Send + Sync {
+    /// Called once an `item` is added to the `index` or its vector is replaced.
+    fn on_insert(&self, index: u16, item: ItemId) {
+        let _ = (index, item);
+    }
+
+    /// Called once an `item` is deleted from the `index`, or tombstoned.
+    fn on_delete(&self, index: u16, item: ItemId) {
+        let _ = (index, item);
+    }
+
+    /// Called once the `items` are deleted from the `index` at once, or tombstoned, see
+    /// [`Writer::del_items`](crate::Writer::del_items). Calls [`Self::on_delete`] for every
+    /// item by default.
+    fn on_delete_items(&self, index: u16, items: &RoaringBitmap) {
+        items.iter().for_each(|item| self.on_delete(index, item));
+    }
+
+    /// Called when the build of the `index` starts, with the items it is going to insert in the
+    /// graph and the ones it is going to remove from it.
+    fn on_build_start(&self, index: u16, inserted: &RoaringBitmap, deleted: &RoaringBitmap) {
+        let _ = (index, inserted, deleted);
+    }
+
+    /// Called once the build of the `index` succeeded, with the items it inserted in the graph
+    /// and the ones it removed from it.
+    fn on_build_end(&self, index: u16, inserted: &RoaringBitmap, deleted: &RoaringBitmap) {
+        let _ = (index, inserted, deleted);
+    }
+
+    /// Called when a build changed the entry points of the graph of the `index`, before
+    /// [`Self::on_build_end`].
+    fn on_entry_points_change(&self, index: u16, old: &[ItemId], new: &[ItemId]) {
+        let _ = (index, old, new);
+    }
+}
+
+/// The hooks of a writer, if any.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(Option<Arc<dyn WriterHooks>>);
+
+impl Hooks {
+    pub fn new(hooks: Arc<dyn WriterHooks>) -> Hooks {
+        Hooks(Some(hooks))
+    }
+
+    /// Calls `f` with the hooks if there are some.
+    pub fn call(&self, f: impl FnOnce(&dyn WriterHooks)) {
+        if let Some(hooks) = &self.0 {
+            f(hooks.as_ref())
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Some(WriterHooks)"),
+            None => f.write_str("None"),
+        }
+    }
+}
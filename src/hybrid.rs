@@ -0,0 +1,128 @@
+use heed::RoTxn;
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::reader::{get_item, Reader, Searched};
+use crate::unaligned_vector::Sparse;
+use crate::{Database, Error, Result};
+
+/// The number of dense candidates re-ranked for every requested item by default.
+const DEFAULT_RERANK_FACTOR: usize = 4;
+
+/// A search for items carrying both a dense and a sparse vector, scored by a weighted sum of
+/// their dense and sparse distances.
+///
+/// An item carries both vectors by being added with the same id to a dense index and to a sparse
+/// one, e.g. a [`Cosine`](crate::distances::Cosine) index and a
+/// [`SparseDotProduct`](crate::distances::SparseDotProduct) index of the same environment. Only
+/// the dense index needs to be built: the search traverses its graph, then re-ranks the closest
+/// dense candidates with their sparse vectors, read from the sparse index.
+///
+/// An item without a sparse vector gets a sparse distance of `1.0`, the largest distance the
+/// sparse distances return for non-negative values.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hannoy::{distances::{Cosine, SparseDotProduct}, Database, HybridQuery, Reader};
+/// # let (reader, sparse, rtxn): (Reader<Cosine>, Database<SparseDotProduct>, heed::RoTxn) = todo!();
+/// let dense = [0.0, 1.0, 0.5];
+/// let searched = HybridQuery::new(&reader, sparse, 0, 10)
+///     .dense_weight(0.7)
+///     .sparse_weight(0.3)
+///     .by_vectors(&rtxn, &dense, &[12, 3071], &[0.4, 1.2])?;
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+pub struct HybridQuery<'a, D: Distance, S: Distance<VectorCodec = Sparse>> {
+    dense: &'a Reader<D>,
+    sparse: Database<S>,
+    sparse_index: u16,
+    count: usize,
+    dense_weight: f32,
+    sparse_weight: f32,
+    rerank: usize,
+    ef: Option<usize>,
+}
+
+impl<'a, D: Distance, S: Distance<VectorCodec = Sparse>> HybridQuery<'a, D, S> {
+    /// Prepares a search for the `count` closest items of the `dense` reader, re-ranked with the
+    /// sparse vectors stored in the `sparse_index` of the `sparse` database.
+    pub fn new(dense: &'a Reader<D>, sparse: Database<S>, sparse_index: u16, count: usize) -> Self {
+        HybridQuery {
+            dense,
+            sparse,
+            sparse_index,
+            count,
+            dense_weight: 1.0,
+            sparse_weight: 1.0,
+            rerank: count * DEFAULT_RERANK_FACTOR,
+            ef: None,
+        }
+    }
+
+    /// The weight of the dense distance in the combined score, defaults to `1.0`.
+    pub fn dense_weight(&mut self, weight: f32) -> &mut Self {
+        self.dense_weight = weight;
+        self
+    }
+
+    /// The weight of the sparse distance in the combined score, defaults to `1.0`.
+    pub fn sparse_weight(&mut self, weight: f32) -> &mut Self {
+        self.sparse_weight = weight;
+        self
+    }
+
+    /// The number of closest dense candidates that are re-ranked with their sparse vectors,
+    /// defaults to four times the number of requested items.
+    ///
+    /// Items whose sparse vector matches the query but whose dense vector isn't among these
+    /// candidates are missed, a larger number finds more of them but reads more vectors.
+    pub fn rerank(&mut self, rerank: usize) -> &mut Self {
+        self.rerank = rerank.max(self.count);
+        self
+    }
+
+    /// The size of the search queue of the dense search, see
+    /// [`QueryBuilder::ef_search`](crate::QueryBuilder::ef_search).
+    pub fn ef_search(&mut self, ef: usize) -> &mut Self {
+        self.ef = Some(ef);
+        self
+    }
+
+    /// Returns the closest items from the `dense` vector and the sparse vector given by the
+    /// sorted `indices` of its non-zero values and the `values` themselves, along with their
+    /// combined distances.
+    pub fn by_vectors(
+        &self,
+        rtxn: &RoTxn,
+        dense: &[f32],
+        indices: &[u32],
+        values: &[f32],
+    ) -> Result<Searched> {
+        Sparse::check_entries(u32::MAX as usize, indices, values)
+            .map_err(|reason| Error::InvalidSparseQuery { reason })?;
+        let dimensions = indices.last().map_or(0, |&index| index as usize + 1);
+        let vector = Sparse::from_entries(dimensions, indices, values);
+        let query = Item { header: S::new_header(&vector), vector };
+
+        let mut builder = self.dense.nns(self.rerank);
+        if let Some(ef) = self.ef {
+            builder.ef_search(ef);
+        }
+        let candidates = builder.by_vector(rtxn, dense)?;
+
+        let mut nns = Vec::with_capacity(candidates.nns.len());
+        for &(id, dense_distance) in &candidates.nns {
+            let sparse_distance = match get_item(self.sparse, self.sparse_index, rtxn, id)? {
+                Some(item) => S::distance(&query, &item),
+                None => 1.0,
+            };
+            let score = self.dense_weight * dense_distance + self.sparse_weight * sparse_distance;
+            nns.push((id, score));
+        }
+        nns.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        nns.truncate(self.count);
+
+        Ok(Searched::new(nns, candidates.did_cancel))
+    }
+}
@@ -0,0 +1,115 @@
+use std::mem::size_of;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::Bytes;
+use heed::{RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::version::Version;
+use crate::{Database, Error, Key, Result};
+
+/// The bytes of the build value written before the [`BuildInfo`]: the id of the write
+/// transaction of the build, which the contiguous regions are checked against.
+const BUILD_ID: usize = size_of::<u64>();
+
+/// The bytes of an encoded [`BuildInfo`]: `M`, `M0`, the `ef_construction`, the `alpha` and the
+/// instant of the build in milliseconds since the Unix epoch.
+const BUILD_INFO: usize = 2 * size_of::<u16>() + 2 * size_of::<u32>() + size_of::<u64>();
+
+/// A plain description of an index, see [`Reader::info`](crate::Reader::info).
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    /// The index in the database.
+    pub index: u16,
+    /// The number of dimensions of the stored vectors.
+    pub dimensions: usize,
+    /// The number of dimensions of the queries, which differs from the one of the vectors if
+    /// the index has a [`Projection`](crate::Projection) or indexes the prefixes of Matryoshka
+    /// embeddings.
+    pub query_dimensions: usize,
+    /// The name of the distance of the index, see [`Distance::name`].
+    pub distance: &'static str,
+    /// The number of items of the index.
+    pub items: u64,
+    /// The number of deleted items still in the graph, see
+    /// [`Writer::set_soft_deletes`](crate::Writer::set_soft_deletes).
+    pub tombstones: u64,
+    /// The number of entry points of the graph.
+    pub entry_points: usize,
+    /// The highest layer of the graph.
+    pub max_level: usize,
+    /// Whether the vectors are stored as bit-packed quantized codes, e.g. by
+    /// [`BinaryQuantizedCosine`](crate::distances::BinaryQuantizedCosine).
+    pub quantized: bool,
+    /// Whether a drift baseline was recorded, see
+    /// [`Writer::recalibrate_drift_baseline`](crate::Writer::recalibrate_drift_baseline).
+    pub calibrated: bool,
+    /// The number of bits of the LSH sketches of the items, see
+    /// [`HannoyBuilder::lsh_sketches`](crate::HannoyBuilder::lsh_sketches).
+    pub sketch_bits: Option<usize>,
+    /// The version of hannoy the index was last built with, which defines its format.
+    pub version: Version,
+    /// The parameters of the last build, `None` if it was made by a version of hannoy that
+    /// didn't record them.
+    pub build: Option<BuildInfo>,
+}
+
+/// The parameters of the last build of an index, see [`IndexInfo::build`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuildInfo {
+    /// The number of links of the nodes of the upper layers.
+    pub m: usize,
+    /// The number of links of the nodes of the bottom layer.
+    pub m0: usize,
+    /// The size of the search queue of the insertions, see
+    /// [`HannoyBuilder::ef_construction`](crate::HannoyBuilder::ef_construction).
+    pub ef_construction: usize,
+    /// The pruning factor of the links, see [`HannoyBuilder::alpha`](crate::HannoyBuilder::alpha).
+    pub alpha: f32,
+    /// The instant the build was made at.
+    pub built_at: SystemTime,
+}
+
+/// Writes the id of the write transaction of a build followed by its parameters, if any.
+pub(crate) fn put_build<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    build: u64,
+    info: Option<&BuildInfo>,
+) -> Result<()> {
+    let mut bytes = Vec::with_capacity(BUILD_ID + BUILD_INFO);
+    bytes.extend_from_slice(&build.to_be_bytes());
+    if let Some(info) = info {
+        let millis = info.built_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        bytes.extend_from_slice(&(info.m as u16).to_be_bytes());
+        bytes.extend_from_slice(&(info.m0 as u16).to_be_bytes());
+        bytes.extend_from_slice(&(info.ef_construction as u32).to_be_bytes());
+        bytes.extend_from_slice(&info.alpha.to_bits().to_be_bytes());
+        bytes.extend_from_slice(&(millis as u64).to_be_bytes());
+    }
+    let key = Key::build(index);
+    database.remap_data_type::<Bytes>().put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))
+}
+
+/// Returns the parameters of the last build of an index, if they were recorded.
+pub(crate) fn get_build_info<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<BuildInfo>> {
+    let key = Key::build(index);
+    let bytes =
+        database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))?;
+    let Some(bytes) = bytes.and_then(|bytes| bytes.get(BUILD_ID..BUILD_ID + BUILD_INFO)) else {
+        return Ok(None);
+    };
+    Ok(Some(BuildInfo {
+        m: BigEndian::read_u16(&bytes[0..]) as usize,
+        m0: BigEndian::read_u16(&bytes[2..]) as usize,
+        ef_construction: BigEndian::read_u32(&bytes[4..]) as usize,
+        alpha: f32::from_bits(BigEndian::read_u32(&bytes[8..])),
+        built_at: UNIX_EPOCH + Duration::from_millis(BigEndian::read_u64(&bytes[12..])),
+    }))
+}
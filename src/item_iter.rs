@@ -1,14 +1,22 @@
+use std::ops::{Bound, RangeBounds};
+
+use byteorder::BigEndian;
+use heed::types::U32;
 use heed::RoTxn;
 
 use crate::distance::Distance;
 use crate::internals::KeyCodec;
-use crate::key::{Prefix, PrefixCodec};
 use crate::node::Item;
-use crate::{Database, ItemId, Node, NodeCodec, Result};
+use crate::{Database, Error, ItemId, Key, Node, NodeCodec, Result};
 
 // used by the reader
 pub struct ItemIter<'t, D: Distance> {
-    pub inner: heed::RoPrefix<'t, KeyCodec, NodeCodec<D>>,
+    pub inner: heed::RoRange<'t, KeyCodec, NodeCodec<D>>,
+    /// The external ids of a reordered index, read along with the items since both are sorted
+    /// by internal id, see [`Writer::reorder_into`](crate::Writer::reorder_into).
+    external_ids: Option<heed::RoRange<'t, KeyCodec, U32<BigEndian>>>,
+    /// The external ids to yield, the items of a reordered index aren't stored in their order.
+    external_range: (Bound<ItemId>, Bound<ItemId>),
     dimensions: usize,
 }
 
@@ -19,34 +27,97 @@ impl<'t, D: Distance> ItemIter<'t, D> {
         dimensions: usize,
         rtxn: &'t RoTxn,
     ) -> heed::Result<Self> {
+        Self::new_range(database, index, dimensions, .., rtxn)
+    }
+
+    /// Iterates over the items whose ids are in `range`, in increasing order.
+    pub fn new_range(
+        database: Database<D>,
+        index: u16,
+        dimensions: usize,
+        range: impl RangeBounds<ItemId>,
+        rtxn: &'t RoTxn,
+    ) -> heed::Result<Self> {
+        let to_key = |bound: Bound<&ItemId>| bound.map(|&item| Key::item(index, item));
+        let start = match to_key(range.start_bound()) {
+            Bound::Unbounded => Bound::Included(Key::item(index, ItemId::MIN)),
+            bound => bound,
+        };
+        let end = match to_key(range.end_bound()) {
+            Bound::Unbounded => Bound::Included(Key::item(index, ItemId::MAX)),
+            bound => bound,
+        };
+
         Ok(ItemIter {
-            inner: database
-                .remap_key_type::<PrefixCodec>()
-                .prefix_iter(rtxn, &Prefix::item(index))?
-                .remap_key_type::<KeyCodec>(),
+            inner: database.range(rtxn, &(start, end))?,
+            external_ids: None,
+            external_range: (Bound::Unbounded, Bound::Unbounded),
             dimensions,
         })
     }
+
+    /// Iterates over all the items of a reordered index and yields the ones whose external ids
+    /// are in `range`, with their external ids, in the order they are stored in.
+    pub fn new_reordered(
+        database: Database<D>,
+        index: u16,
+        dimensions: usize,
+        range: impl RangeBounds<ItemId>,
+        rtxn: &'t RoTxn,
+    ) -> heed::Result<Self> {
+        let ids = Key::external_id(index, ItemId::MIN)..=Key::external_id(index, ItemId::MAX);
+        let external_ids = database.remap_data_type::<U32<BigEndian>>().range(rtxn, &ids)?;
+        let external_range = (range.start_bound().cloned(), range.end_bound().cloned());
+        Ok(ItemIter {
+            external_ids: Some(external_ids),
+            external_range,
+            ..Self::new(database, index, dimensions, rtxn)?
+        })
+    }
+
+    /// Returns the external id of the item stored under `key`, the id entries that come before
+    /// it are skipped.
+    fn external_id(&mut self, key: Key) -> Result<ItemId> {
+        let Some(external_ids) = &mut self.external_ids else { return Ok(key.node.item) };
+        for result in external_ids {
+            let (id_key, external) = result?;
+            if id_key.node.item == key.node.item {
+                return Ok(external);
+            }
+            if id_key.node.item > key.node.item {
+                break;
+            }
+        }
+        Err(Error::missing_key(Key::external_id(key.index, key.node.item)))
+    }
 }
 
 impl<D: Distance> Iterator for ItemIter<'_, D> {
     type Item = Result<(ItemId, Vec<f32>)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.inner.next() {
-            Some(Ok((key, node))) => match node {
-                Node::Item(Item { header: _, vector }) => {
-                    let mut vector = vector.to_vec();
-                    if vector.len() != self.dimensions {
-                        // quantized codecs pad to 8-bytes so we truncate to recover len
-                        vector.truncate(self.dimensions);
-                    }
-                    Some(Ok((key.node.item, vector)))
-                }
-                Node::Links(_) => unreachable!("Node must not be a link"),
-            },
-            Some(Err(e)) => Some(Err(e.into())),
-            None => None,
+        loop {
+            let (key, node) = match self.inner.next()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e.into())),
+            };
+            let Item { header: _, vector } = match node {
+                Node::Item(item) => item,
+                Node::Links(_) => return Some(Err(Error::unexpected_node(key, "Item"))),
+            };
+            let id = match self.external_id(key) {
+                Ok(id) => id,
+                Err(e) => return Some(Err(e)),
+            };
+            if !self.external_range.contains(&id) {
+                continue;
+            }
+            let mut vector = vector.to_vec();
+            if vector.len() != self.dimensions {
+                // quantized codecs pad to 8-bytes so we truncate to recover len
+                vector.truncate(self.dimensions);
+            }
+            return Some(Ok((id, vector)));
         }
     }
 }
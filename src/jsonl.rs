@@ -0,0 +1,81 @@
+//! Import and export items as JSON Lines, one `{"id": .., "vector": [..]}` object per line.
+use std::io::{BufRead, Write};
+
+use heed::{RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::item_iter::ItemIter;
+use crate::{Error, ItemId, Reader, Result, Writer};
+
+/// The number of lines parsed and written at once.
+const IMPORT_BATCH_SIZE: usize = 8192;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonItem<V> {
+    id: ItemId,
+    vector: V,
+}
+
+fn export<D: Distance>(items: ItemIter<'_, D>, mut output: impl Write) -> Result<u64> {
+    let mut count = 0;
+    for result in items {
+        let (id, vector) = result?;
+        serde_json::to_writer(&mut output, &JsonItem { id, vector })
+            .map_err(std::io::Error::from)?;
+        output.write_all(b"\n")?;
+        count += 1;
+    }
+    output.flush()?;
+    Ok(count)
+}
+
+impl<D: Distance> Writer<D> {
+    /// Adds the items of a JSON Lines stream, one `{"id": .., "vector": [..]}` object per line,
+    /// and returns the number of items read. Empty lines are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// let input = r#"{"id": 0, "vector": [0.0, 1.0]}"#;
+    /// writer.import_jsonl(&mut wtxn, input.as_bytes())?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn import_jsonl(&self, wtxn: &mut RwTxn, input: impl BufRead) -> Result<u64> {
+        let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+        let mut count = 0;
+        for (line, content) in input.lines().enumerate() {
+            let content = content?;
+            if content.trim().is_empty() {
+                continue;
+            }
+            let JsonItem { id, vector } = serde_json::from_str::<JsonItem<Vec<f32>>>(&content)
+                .map_err(|source| Error::InvalidJsonLine { line: line + 1, source })?;
+            batch.push((id, vector));
+
+            if batch.len() == IMPORT_BATCH_SIZE {
+                count += batch.len() as u64;
+                self.add_items(wtxn, batch.iter().map(|(id, v)| (*id, v.as_slice())))?;
+                batch.clear();
+            }
+        }
+        count += batch.len() as u64;
+        self.add_items(wtxn, batch.iter().map(|(id, v)| (*id, v.as_slice())))?;
+
+        Ok(count)
+    }
+
+    /// Writes every item of the database as JSON Lines and returns the number of items written.
+    pub fn export_jsonl(&self, rtxn: &RoTxn, output: impl Write) -> Result<u64> {
+        export(self.iter(rtxn)?, output)
+    }
+}
+
+impl<D: Distance> Reader<D> {
+    /// Writes every item of the index as JSON Lines, one `{"id": .., "vector": [..]}` object
+    /// per line, and returns the number of items written.
+    pub fn export_jsonl(&self, rtxn: &RoTxn, output: impl Write) -> Result<u64> {
+        export(self.iter(rtxn)?, output)
+    }
+}
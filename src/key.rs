@@ -15,6 +15,15 @@ use crate::{NodeId, NodeMode};
 ///  - `Links`: we're looking at the `Links` bitmap of neighbours for a node
 ///  - `Updated`: The list of items that has been updated since the last build of the database.
 ///  - `Metadata`: There is only one item at `0` that contains the header required to read the index.
+///  - `Expiration`: The instant the item expires at, if it was given one.
+///  - `Change`: The change of an item under its sequence number in the change log.
+///  - `History`: A previous vector of an item under the slot of its ring of versions.
+///  - `Tombstone`: An item that was deleted but is kept in the graph until the next vacuum.
+///  - `Timestamp`: The instant the item was created or refreshed at, if it was given one.
+///  - `ExternalId`/`InternalId`: The two directions of the id mapping of a reordered index.
+///  - `Payload`: The opaque bytes an application attached to the item, if any.
+///  - `Boost`: The factor the final score of the item is boosted by, if it was given one.
+///  - `FullVector`: The complete vector of an item of a Matryoshka index.
 #[derive(Debug, Copy, Clone)]
 pub struct Key {
     /// The prefix specified by the user.
@@ -35,6 +44,10 @@ impl Key {
         Self::new(index, NodeId::version())
     }
 
+    pub const fn tuning(index: u16) -> Self {
+        Self::new(index, NodeId::tuning())
+    }
+
     pub const fn updated(index: u16, item: u32) -> Self {
         Self::new(index, NodeId::updated(item))
     }
@@ -46,6 +59,120 @@ impl Key {
     pub const fn links(index: u16, item: u32, layer: u8) -> Self {
         Self::new(index, NodeId::links(item, layer))
     }
+
+    pub const fn expiration(index: u16, item: u32) -> Self {
+        Self::new(index, NodeId::expiration(item))
+    }
+
+    pub const fn change(index: u16, sequence: u32) -> Self {
+        Self::new(index, NodeId::change(sequence))
+    }
+
+    pub const fn history(index: u16, item: u32, slot: u8) -> Self {
+        Self::new(index, NodeId::history(item, slot))
+    }
+
+    pub const fn tombstone(index: u16, item: u32) -> Self {
+        Self::new(index, NodeId::tombstone(item))
+    }
+
+    pub const fn timestamp(index: u16, item: u32) -> Self {
+        Self::new(index, NodeId::timestamp(item))
+    }
+
+    pub const fn payload(index: u16, item: u32) -> Self {
+        Self::new(index, NodeId::payload(item))
+    }
+
+    pub const fn boost(index: u16, item: u32) -> Self {
+        Self::new(index, NodeId::boost(item))
+    }
+
+    pub const fn full_vector(index: u16, item: u32) -> Self {
+        Self::new(index, NodeId::full_vector(item))
+    }
+
+    pub const fn external_id(index: u16, internal: u32) -> Self {
+        Self::new(index, NodeId::external_id(internal))
+    }
+
+    pub const fn internal_id(index: u16, external: u32) -> Self {
+        Self::new(index, NodeId::internal_id(external))
+    }
+
+    pub const fn external_items(index: u16) -> Self {
+        Self::new(index, NodeId::external_items())
+    }
+
+    pub const fn build(index: u16) -> Self {
+        Self::new(index, NodeId::build())
+    }
+
+    pub const fn codes(index: u16) -> Self {
+        Self::new(index, NodeId::codes())
+    }
+
+    pub const fn weights(index: u16) -> Self {
+        Self::new(index, NodeId::weights())
+    }
+
+    pub const fn write_lock(index: u16) -> Self {
+        Self::new(index, NodeId::write_lock())
+    }
+
+    pub const fn projection(index: u16) -> Self {
+        Self::new(index, NodeId::projection())
+    }
+
+    pub const fn sketches(index: u16) -> Self {
+        Self::new(index, NodeId::sketches())
+    }
+
+    pub const fn calibration(index: u16) -> Self {
+        Self::new(index, NodeId::calibration())
+    }
+
+    pub const fn densities(index: u16) -> Self {
+        Self::new(index, NodeId::densities())
+    }
+
+    pub const fn manifest(index: u16) -> Self {
+        Self::new(index, NodeId::manifest())
+    }
+
+    pub const fn matryoshka(index: u16) -> Self {
+        Self::new(index, NodeId::matryoshka())
+    }
+
+    pub const fn snapshots(index: u16) -> Self {
+        Self::new(index, NodeId::snapshots())
+    }
+
+    pub const fn change_log(index: u16) -> Self {
+        Self::new(index, NodeId::change_log())
+    }
+
+    pub const fn format(index: u16) -> Self {
+        Self::new(index, NodeId::format())
+    }
+
+    /// Encodes the key on the stack, the searches look the keys up as raw bytes to not allocate.
+    pub fn to_bytes(self) -> [u8; size_of::<u64>()] {
+        let mut output = [0; size_of::<u64>()];
+        output[..2].copy_from_slice(&self.index.to_be_bytes());
+        output[2] = self.node.mode as u8;
+        output[3..7].copy_from_slice(&self.node.item.to_be_bytes());
+        output[7] = self.node.layer;
+        output
+    }
+
+    pub const fn change_sequence(index: u16) -> Self {
+        Self::new(index, NodeId::change_sequence())
+    }
+
+    pub const fn applied_sequence(index: u16) -> Self {
+        Self::new(index, NodeId::applied_sequence())
+    }
 }
 
 /// The heed codec used internally to encode/decoding the internal key type.
@@ -55,13 +182,7 @@ impl<'a> heed::BytesEncode<'a> for KeyCodec {
     type EItem = Key;
 
     fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
-        let mut output = Vec::with_capacity(size_of::<u64>());
-        output.extend_from_slice(&item.index.to_be_bytes());
-        output.extend_from_slice(&(item.node.mode as u8).to_be_bytes());
-        output.extend_from_slice(&item.node.item.to_be_bytes());
-        output.extend_from_slice(&(item.node.layer).to_be_bytes());
-
-        Ok(Cow::Owned(output))
+        Ok(Cow::Owned(item.to_bytes().to_vec()))
     }
 }
 
@@ -106,6 +227,31 @@ impl Prefix {
     pub const fn updated(index: u16) -> Self {
         Self { index, mode: Some(NodeMode::Updated) }
     }
+
+    pub const fn expiration(index: u16) -> Self {
+        Self { index, mode: Some(NodeMode::Expiration) }
+    }
+
+    #[cfg(feature = "versioning")]
+    pub const fn history(index: u16) -> Self {
+        Self { index, mode: Some(NodeMode::History) }
+    }
+
+    pub const fn tombstone(index: u16) -> Self {
+        Self { index, mode: Some(NodeMode::Tombstone) }
+    }
+
+    pub const fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// A human readable name of the range covered by this prefix.
+    pub const fn name(&self) -> &'static str {
+        match &self.mode {
+            Some(mode) => mode.name(),
+            None => "all",
+        }
+    }
 }
 
 pub enum PrefixCodec {}
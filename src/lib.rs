@@ -69,21 +69,65 @@
 )]
 #![warn(clippy::todo)]
 
+mod attributes;
+mod calibration;
+mod change_log;
+pub mod clustering;
+mod codes;
+mod compaction;
+mod density;
 mod distance;
 mod error;
+mod estimate;
+pub mod eval;
+pub mod export;
+pub mod fusion;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+#[cfg(feature = "hdf5")]
+pub mod hdf5;
 mod hnsw;
+mod hooks;
+mod hybrid;
+mod info;
 mod item_iter;
+#[cfg(feature = "jsonl")]
+mod jsonl;
 mod key;
+mod lsh;
+mod maintenance;
+mod manifest;
+mod map_growth;
+mod matryoshka;
+mod memory;
 mod metadata;
+mod migration;
+mod multi_reader;
+mod multi_vector;
 mod node;
 mod node_id;
+pub mod npy;
+#[cfg(feature = "npz")]
+pub mod npz;
 mod parallel;
+mod preset;
 mod progress;
+mod projection;
+mod read_only;
 mod reader;
+mod recency;
+mod reorder;
 mod roaring;
+mod scheduler;
+mod scoring;
+mod snapshot;
 mod spaces;
 mod stats;
+mod tuning;
 mod version;
+#[cfg(feature = "versioning")]
+mod versioning;
+mod weights;
 mod writer;
 
 #[cfg(test)]
@@ -95,31 +139,77 @@ mod unaligned_vector;
 #[cfg(feature = "python")]
 pub mod python;
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "parquet")]
+mod parquet;
+
+#[cfg(feature = "metrics")]
+pub mod monitoring;
+
+#[cfg(feature = "unstable-raw")]
+pub mod raw;
+
+#[cfg(feature = "tokio")]
+mod async_writer;
+
+#[cfg(feature = "tokio")]
+pub use async_writer::{AsyncWriter, BuildProgress};
+pub use attributes::{AttributeDatabase, AttributeFilter, Attributes};
+pub use change_log::{Change, ChangeKind};
 pub use distance::Distance;
 pub use error::Error;
+pub use estimate::BuildEstimate;
+pub use hooks::WriterHooks;
+pub use hybrid::HybridQuery;
+pub use info::{BuildInfo, IndexInfo};
 use key::{Key, Prefix, PrefixCodec};
-use metadata::{Metadata, MetadataCodec};
+pub use maintenance::{Maintenance, MaintenanceStep};
+pub use manifest::{IngestKind, IngestManifest};
+pub use map_growth::{write_with_map_growth, MapGrowth};
+use metadata::{Metadata, MetadataCodec, MetadataDimensionsCodec};
+pub use migration::{swap_indexes, Migration};
+pub use multi_reader::{MultiReader, MultiSearched};
+pub use multi_vector::{MultiVector, MultiVectorScore};
 use node::{Node, NodeCodec};
 use node_id::{NodeId, NodeMode};
-pub use reader::{QueryBuilder, Reader, Searched};
+pub use preset::{BuildParams, BuildPreset, DatasetStats};
+pub use projection::Projection;
+pub use read_only::{ReadAccess, ReadOnlyEnv, SharedRoTxn};
+pub use reader::{
+    FilterStrategy, PreparedQuery, Query, QueryBuilder, Reader, ScoreKind, SearchTrace, Searched,
+    TraceEvent,
+};
+pub use recency::{Decay, RecencyQuery};
 pub use roaring::RoaringBitmapCodec;
-pub use writer::{HannoyBuilder, Writer};
+pub use scoring::ScoringReader;
+pub use stats::{SearchStats, SearchStatsSnapshot, Summary, VectorStats};
+pub use version::{Version, FORMAT_VERSION};
+#[cfg(feature = "versioning")]
+pub use versioning::PointInTime;
+pub use writer::{BuildThreshold, HannoyBuilder, Writer, SUPPORTED_LINKS};
 
 /// The set of types used by the [`Distance`] trait.
 pub mod internals {
     pub use crate::distance::{
-        NodeHeaderBinaryQuantizedCosine, NodeHeaderCosine, NodeHeaderEuclidean,
+        NodeHeaderBinaryQuantizedCosine, NodeHeaderCosine, NodeHeaderEuclidean, NodeHeaderSparse,
     };
     pub use crate::key::KeyCodec;
     pub use crate::node::{Item, NodeCodec};
-    pub use crate::unaligned_vector::{SizeMismatch, UnalignedVector, UnalignedVectorCodec};
+    pub use crate::unaligned_vector::{
+        SizeMismatch, Sparse, UnalignedVector, UnalignedVectorCodec,
+    };
 }
 
 /// The set of distances implementing the [`Distance`] and supported by hannoy.
 pub mod distances {
     pub use crate::distance::{
-        BinaryQuantizedCosine, BinaryQuantizedEuclidean, BinaryQuantizedManhattan, Cosine,
-        Euclidean, Hamming, Manhattan,
+        BinaryQuantizedCosine, BinaryQuantizedEuclidean, BinaryQuantizedManhattan, BrayCurtis,
+        Canberra, Chebyshev, Cosine, EarthMovers, Euclidean, Hamming, Hellinger, Manhattan,
+        Poincare, SparseCosine, SparseDotProduct, U8Euclidean, U8Manhattan, WeightedEuclidean,
     };
 }
 
@@ -0,0 +1,283 @@
+use std::mem::size_of;
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{RoTxn, RwTxn};
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::internals::KeyCodec;
+use crate::node::Item;
+use crate::reader::{get_build, get_item};
+use crate::{Database, Error, ItemId, Key, Prefix, PrefixCodec, Result};
+
+/// The bytes before the sketches: the build they were written by, the number of words of a
+/// sketch and the size of the bitmap of their items, which follows the sketches.
+const SKETCHES_HEADER: usize = 16;
+
+/// The seed of the hyperplanes, the sketches of the items and of the queries must be computed
+/// with the same ones.
+const SEED: u64 = 0x6c73_685f_7365_6564;
+
+/// The SimHash sketches of the items of an index, `words` of 64 bits per item laid out one
+/// after the other in the order of their ids, borrowed from a single LMDB value. The position
+/// of the sketch of an item is its rank in the bitmap of the items.
+///
+/// Every bit of a sketch is the side of a random hyperplane going through the origin a vector
+/// is on, the fraction of the bits two sketches differ on estimates the angle between their
+/// vectors.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Sketches<'t> {
+    words: usize,
+    bytes: &'t [u8],
+    /// The items whose sketches are stored, in the order of their positions.
+    items: &'t RoaringBitmap,
+}
+
+impl Sketches<'_> {
+    /// Returns the number of bits the sketch of `item` differs from the `query` sketch on,
+    /// `None` if it isn't stored.
+    pub fn hamming(&self, item: ItemId, query: &[u64]) -> Option<u32> {
+        if !self.items.contains(item) {
+            return None;
+        }
+        let stride = self.words * size_of::<u64>();
+        let start = (self.items.rank(item) - 1) as usize * stride;
+        let sketch = self.bytes.get(start..start + stride)?;
+        let words = sketch.chunks_exact(size_of::<u64>()).map(BigEndian::read_u64);
+        Some(words.zip(query).map(|(a, b)| (a ^ b).count_ones()).sum())
+    }
+
+    /// Returns the sketch of the `query`, comparable with the ones of the items.
+    pub fn sketch<D: Distance>(&self, query: &Item<D>) -> Vec<u64> {
+        sketch(&query.vector.to_vec(), self.words)
+    }
+}
+
+/// Prunes the neighbours whose sketch is too far from the one of the query before their
+/// distance is computed, see [`QueryBuilder::lsh_prefilter`](crate::QueryBuilder::lsh_prefilter).
+#[derive(Debug, Clone)]
+pub(crate) struct Prefilter<'t> {
+    pub sketches: Sketches<'t>,
+    pub query: Vec<u64>,
+    pub max_hamming: u32,
+}
+
+impl Prefilter<'_> {
+    /// Returns `true` if the distance of the `item` must be computed. The items without a sketch
+    /// are always kept.
+    pub fn keeps(&self, item: ItemId) -> bool {
+        self.sketches.hamming(item, &self.query).is_none_or(|hamming| hamming <= self.max_hamming)
+    }
+}
+
+/// The parts of the value of the sketches.
+struct Region<'t> {
+    build: u64,
+    words: usize,
+    sketches: &'t [u8],
+    items: &'t [u8],
+}
+
+impl<'t> Region<'t> {
+    fn decode(key: Key, bytes: &'t [u8]) -> Result<Region<'t>> {
+        let invalid = || Error::unexpected_node(key, "Sketches");
+        let (header, bytes) = bytes.split_at_checked(SKETCHES_HEADER).ok_or_else(invalid)?;
+        let build = BigEndian::read_u64(header);
+        let words = BigEndian::read_u32(&header[size_of::<u64>()..]) as usize;
+        let items = BigEndian::read_u32(&header[size_of::<u64>() + size_of::<u32>()..]) as usize;
+        if words == 0 {
+            return Err(invalid());
+        }
+        let split = bytes.len().checked_sub(items).ok_or_else(invalid)?;
+        let (sketches, items) = bytes.split_at(split);
+        Ok(Region { build, words, sketches, items })
+    }
+
+    fn items(&self, key: Key) -> Result<RoaringBitmap> {
+        RoaringBitmap::deserialize_unchecked_from(self.items)
+            .map_err(|_| Error::unexpected_node(key, "Sketches"))
+    }
+}
+
+/// Returns the region of the sketches of an index if it was written by its last build.
+fn get_region<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<Region<'t>>> {
+    let key = Key::sketches(index);
+    let raw = database.remap_types::<Bytes, Bytes>();
+    let Some(bytes) = raw.get(rtxn, &key.to_bytes()).map_err(Error::on_key("get", key))? else {
+        return Ok(None);
+    };
+    let region = Region::decode(key, bytes)?;
+    Ok((region.build == get_build(rtxn, database, index)?).then_some(region))
+}
+
+/// Returns the number of bits of the sketches of an index if they were written by its last
+/// build.
+pub(crate) fn get_sketch_bits<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<usize>> {
+    Ok(get_region(rtxn, database, index)?.map(|region| region.words * u64::BITS as usize))
+}
+
+/// Returns the items whose sketches the last build of an index wrote, which the readers keep to
+/// find the position of the items in the sketches, see [`get_sketches`].
+pub(crate) fn get_sketched_items<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<RoaringBitmap>> {
+    let Some(region) = get_region(rtxn, database, index)? else { return Ok(None) };
+    region.items(Key::sketches(index)).map(Some)
+}
+
+/// Returns the sketches of an index if they were written by its last build for the `items`
+/// returned by [`get_sketched_items`].
+pub(crate) fn get_sketches<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    database: Database<D>,
+    index: u16,
+    items: &'t RoaringBitmap,
+) -> Result<Option<Sketches<'t>>> {
+    let Some(Region { words, sketches, .. }) = get_region(rtxn, database, index)? else {
+        return Ok(None);
+    };
+    Ok(Some(Sketches { words, bytes: sketches, items }))
+}
+
+/// Writes the sketches of `bits` bits, rounded up to a multiple of 64, of the items of an index
+/// in the contiguous region read by [`get_sketches`], or deletes it if there is no `bits`.
+///
+/// Only the `changed` items are sketched again, the sketches of the other ones are copied from
+/// the previous region if it has the same number of bits. All of them are sketched when
+/// `changed` is `None`.
+pub(crate) fn write<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    bits: Option<usize>,
+    changed: Option<&RoaringBitmap>,
+) -> Result<()> {
+    let key = Key::sketches(index);
+    let Some(bits) = bits.filter(|&bits| bits > 0) else {
+        database.remap_data_type::<DecodeIgnore>().delete(wtxn, &key)?;
+        return Ok(());
+    };
+    let words = bits.div_ceil(u64::BITS as usize);
+    let build = get_build(wtxn, database, index)?;
+    let sketches = collect(wtxn, database, index, build, words, changed)?;
+    database
+        .remap_data_type::<Bytes>()
+        .put(wtxn, &key, &sketches)
+        .map_err(Error::on_key("put", key))
+}
+
+/// Returns the region of the sketches of `words` words of the items of an index.
+fn collect<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    build: u64,
+    words: usize,
+    changed: Option<&RoaringBitmap>,
+) -> Result<Vec<u8>> {
+    let key = Key::sketches(index);
+    let raw = database.remap_types::<Bytes, Bytes>();
+    // the region of the previous build, whatever the build it was written by
+    let previous = match changed {
+        Some(_) => match raw.get(rtxn, &key.to_bytes()).map_err(Error::on_key("get", key))? {
+            Some(bytes) => {
+                let region = Region::decode(key, bytes)?;
+                let items = region.items(key)?;
+                (region.words == words).then_some((region, items))
+            }
+            None => None,
+        },
+        None => None,
+    };
+    let mut previous_items =
+        previous.as_ref().map(|(_, items)| items.iter().enumerate().peekable());
+
+    let stride = words * size_of::<u64>();
+    let mut sketches = vec![0; SKETCHES_HEADER];
+    let mut items = RoaringBitmap::new();
+    let prefix = Prefix::item(index);
+    let iter = database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter(rtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<KeyCodec>();
+    // the items are iterated in the order of their ids, which is the one of their sketches
+    for result in iter {
+        let (key, ()) = result?;
+        let item = key.node.item;
+        items.push(item);
+
+        // the previous items are walked along with the current ones to find their positions
+        let mut position = None;
+        if let Some(previous_items) = &mut previous_items {
+            // the items deleted since the previous build are skipped
+            while previous_items.next_if(|&(_, previous)| previous < item).is_some() {}
+            position = previous_items.next_if(|&(_, previous)| previous == item).map(|(i, _)| i);
+        }
+        let kept = match (&previous, position) {
+            (Some((region, _)), Some(position))
+                if !changed.is_some_and(|changed| changed.contains(item)) =>
+            {
+                region.sketches.get(position * stride..(position + 1) * stride)
+            }
+            _ => None,
+        };
+
+        match kept {
+            Some(sketch) => sketches.extend_from_slice(sketch),
+            None => {
+                let node = get_item(database, index, rtxn, item)?
+                    .ok_or_else(|| Error::missing_key(key))?;
+                for word in sketch(&node.vector.to_vec(), words) {
+                    sketches.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+        }
+    }
+
+    let items_len = items.serialized_size();
+    items.serialize_into(&mut sketches).map_err(|_| Error::unexpected_node(key, "Sketches"))?;
+    BigEndian::write_u64(&mut sketches, build);
+    BigEndian::write_u32(&mut sketches[size_of::<u64>()..], words as u32);
+    BigEndian::write_u32(&mut sketches[size_of::<u64>() + size_of::<u32>()..], items_len as u32);
+    Ok(sketches)
+}
+
+/// Returns the `words` of the SimHash sketch of a vector.
+///
+/// The hyperplanes have random ±1 coordinates, derived from the seed, the dimension and the
+/// word, so that they don't have to be stored.
+fn sketch(vector: &[f32], words: usize) -> Vec<u64> {
+    let mut sums = vec![0.0f32; words * u64::BITS as usize];
+    for (dimension, &x) in vector.iter().enumerate() {
+        for (word, sums) in sums.chunks_exact_mut(u64::BITS as usize).enumerate() {
+            let signs = splitmix64(SEED ^ (dimension * words + word) as u64);
+            for (bit, sum) in sums.iter_mut().enumerate() {
+                *sum += if signs >> bit & 1 == 1 { x } else { -x };
+            }
+        }
+    }
+    sums.chunks_exact(u64::BITS as usize)
+        .map(|sums| {
+            sums.iter().enumerate().fold(0, |word, (bit, &sum)| word | ((sum > 0.0) as u64) << bit)
+        })
+        .collect()
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,142 @@
+//! Small steps of background work that improve the graph of an index between the writes of the
+//! application, see [`HannoyBuilder::maintain`](crate::HannoyBuilder::maintain).
+use std::time::{Duration, Instant};
+
+use heed::RoTxn;
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::reader::get_links;
+use crate::{Database, ItemId, Metadata, Result};
+
+/// The progress and the pace of the maintenance of an index, kept by the application between
+/// the steps of [`HannoyBuilder::maintain`](crate::HannoyBuilder::maintain).
+///
+/// Every step vacuums a few tombstoned items and inspects the links of the next items of the
+/// graph, in the order of their ids: the nodes with more links than the graph allows, or so few
+/// that the searches hardly reach them, are re-inserted in the graph by the build that ends the
+/// step. Once the last item was inspected the next step starts over from the first one.
+///
+/// # Example
+///
+/// ```no_run
+/// # use hannoy::{Maintenance, Writer, distances::Euclidean};
+/// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+/// use std::time::Duration;
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+///
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let mut maintenance = Maintenance::new(1000).with_interval(Duration::from_secs(10));
+/// loop {
+///     // on the idle thread of the application, between its own writes
+///     let mut wtxn = env.write_txn()?;
+///     if let Some(step) = writer.builder(&mut rng).maintain::<16, 32>(&mut wtxn, &mut maintenance)? {
+///         println!("repaired {} nodes", step.repaired);
+///     }
+///     wtxn.commit()?;
+///     std::thread::sleep(Duration::from_secs(1));
+/// }
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Maintenance {
+    budget: u64,
+    interval: Duration,
+    last_step: Option<Instant>,
+    next: ItemId,
+}
+
+/// What a step of [`HannoyBuilder::maintain`](crate::HannoyBuilder::maintain) did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaintenanceStep {
+    /// The number of tombstoned items that were removed from the graph.
+    pub vacuumed: u64,
+    /// The number of items whose links were inspected.
+    pub inspected: u64,
+    /// The number of items re-inserted in the graph for having too many or too few links.
+    pub repaired: u64,
+    /// Whether the step inspected the last item of the graph, the next one starts over.
+    pub wrapped: bool,
+}
+
+impl Maintenance {
+    /// Starts the maintenance of an index from its first item, every step vacuums and inspects
+    /// at most `budget` items.
+    pub fn new(budget: u64) -> Maintenance {
+        Maintenance { budget: budget.max(1), interval: Duration::ZERO, last_step: None, next: 0 }
+    }
+
+    /// Skips the steps asked for less than `interval` after the previous one, to bound the
+    /// share of the write transactions the maintenance takes.
+    pub fn with_interval(mut self, interval: Duration) -> Maintenance {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the maximum number of items a step vacuums and inspects.
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Returns the id the next step inspects the items from.
+    pub fn next_item(&self) -> ItemId {
+        self.next
+    }
+
+    /// Returns whether a step is due at `now`, and records it as the last one if it is.
+    pub(crate) fn start_step(&mut self, now: Instant) -> bool {
+        let due = self.last_step.is_none_or(|last| now.duration_since(last) >= self.interval);
+        if due {
+            self.last_step = Some(now);
+        }
+        due
+    }
+
+    /// Returns the next items of the graph to inspect and moves past them, starting over from
+    /// the first item once all of them were.
+    pub(crate) fn next_items(&mut self, graph: &RoaringBitmap) -> (RoaringBitmap, bool) {
+        let items: RoaringBitmap = graph.range(self.next..).take(self.budget as usize).collect();
+        let wrapped = match items.max() {
+            Some(last) if graph.max() != Some(last) => {
+                self.next = last + 1;
+                false
+            }
+            _ => {
+                self.next = 0;
+                true
+            }
+        };
+        (items, wrapped)
+    }
+}
+
+/// Returns the `items` of the graph with more links than a node of their layer can have, or
+/// fewer than a quarter of the links of the bottom layer.
+pub(crate) fn unhealthy_items<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    metadata: &Metadata,
+    items: &RoaringBitmap,
+    m: usize,
+    m0: usize,
+) -> Result<RoaringBitmap> {
+    // a node of a small graph can't have more links than there are other items
+    let weak = (m0 / 4).min(metadata.items.len().saturating_sub(1) as usize);
+    let mut unhealthy = RoaringBitmap::new();
+    for item in items {
+        for layer in 0..=metadata.max_level as usize {
+            let Some(links) = get_links(rtxn, database, index, item, layer)? else {
+                break;
+            };
+            let len = links.links.len() as usize;
+            let max = if layer == 0 { m0 } else { m };
+            if len > max || (layer == 0 && len < weak) {
+                unhealthy.insert(item);
+                break;
+            }
+        }
+    }
+    Ok(unhealthy)
+}
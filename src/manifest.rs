@@ -0,0 +1,157 @@
+//! The record of an ingest that spans several write transactions, to tell on the next run that
+//! it was interrupted and either resume or roll it back.
+use std::mem::size_of;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{RoTxn, RwTxn};
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::{Database, Error, Key, Result};
+
+/// The bytes of the kind, the start and the number of commits before the added items.
+const MANIFEST_HEADER: usize = size_of::<u8>() + 2 * size_of::<u64>();
+
+/// The operation an [`IngestManifest`] records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngestKind {
+    /// Items added and built chunk by chunk, see
+    /// [`HannoyBuilder::ingest_in_transactions`](crate::HannoyBuilder::ingest_in_transactions).
+    Ingest,
+    /// The pending changes built chunk by chunk, see
+    /// [`HannoyBuilder::build_in_transactions`](crate::HannoyBuilder::build_in_transactions).
+    Build,
+    /// The graph rebuilt chunk by chunk in a free index, see
+    /// [`HannoyBuilder::rebuild_in_chunks`](crate::HannoyBuilder::rebuild_in_chunks).
+    Rebuild,
+}
+
+/// The progress of an operation spanning several write transactions, stored with the index
+/// when it starts and removed by the commit of its last transaction, see
+/// [`Writer::unfinished_ingest`](crate::Writer::unfinished_ingest).
+///
+/// Every transaction of the operation leaves a consistent index. Finding a manifest once the
+/// operation returned means it was interrupted, e.g. by a crash, between two of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IngestManifest {
+    /// The operation that was interrupted.
+    pub kind: IngestKind,
+    /// The instant the operation started at.
+    pub started_at: SystemTime,
+    /// The number of transactions the operation committed.
+    pub committed: u64,
+    /// The items the committed transactions added, which a rollback deletes. A build adds none.
+    pub added: RoaringBitmap,
+    /// The free index a rebuild writes the new graph in, which a rollback clears.
+    pub scratch: Option<u16>,
+    /// The pending changes of the index a rebuild took over, which a rollback leaves pending
+    /// again.
+    pub pending: RoaringBitmap,
+}
+
+impl IngestManifest {
+    pub(crate) fn new(kind: IngestKind) -> IngestManifest {
+        IngestManifest {
+            kind,
+            started_at: SystemTime::now(),
+            committed: 0,
+            added: RoaringBitmap::new(),
+            scratch: None,
+            pending: RoaringBitmap::new(),
+        }
+    }
+}
+
+/// Returns the manifest of the operation of an index that didn't finish, if any.
+pub(crate) fn get_manifest<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<IngestManifest>> {
+    let key = Key::manifest(index);
+    let bytes =
+        database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))?;
+    let Some(bytes) = bytes else { return Ok(None) };
+    let invalid = || Error::unexpected_node(key, "IngestManifest");
+    let Some((header, mut rest)) = bytes.split_at_checked(MANIFEST_HEADER) else {
+        return Err(invalid());
+    };
+    let kind = match header[0] {
+        0 => IngestKind::Ingest,
+        1 => IngestKind::Build,
+        2 => IngestKind::Rebuild,
+        _ => return Err(invalid()),
+    };
+    let millis = BigEndian::read_u64(&header[1..]);
+    let added = RoaringBitmap::deserialize_from(&mut rest).map_err(|_| invalid())?;
+    // only a rebuild records its free index and the changes it took over
+    let (scratch, pending) = match kind {
+        IngestKind::Rebuild => {
+            let (scratch, mut pending) =
+                rest.split_at_checked(size_of::<u16>()).ok_or_else(invalid)?;
+            let pending = RoaringBitmap::deserialize_from(&mut pending).map_err(|_| invalid())?;
+            (Some(BigEndian::read_u16(scratch)), pending)
+        }
+        _ => (None, RoaringBitmap::new()),
+    };
+    Ok(Some(IngestManifest {
+        kind,
+        started_at: UNIX_EPOCH + Duration::from_millis(millis),
+        committed: BigEndian::read_u64(&header[1 + size_of::<u64>()..]),
+        added,
+        scratch,
+        pending,
+    }))
+}
+
+/// Stores the progress of an operation, to be committed with the changes it made.
+pub(crate) fn put_manifest<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    manifest: &IngestManifest,
+) -> Result<()> {
+    let millis = manifest.started_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let mut bytes = Vec::with_capacity(MANIFEST_HEADER + manifest.added.serialized_size());
+    bytes.push(manifest.kind as u8);
+    bytes.extend_from_slice(&(millis as u64).to_be_bytes());
+    bytes.extend_from_slice(&manifest.committed.to_be_bytes());
+    manifest.added.serialize_into(&mut bytes)?;
+    if let Some(scratch) = manifest.scratch {
+        bytes.extend_from_slice(&scratch.to_be_bytes());
+        manifest.pending.serialize_into(&mut bytes)?;
+    }
+    let key = Key::manifest(index);
+    database.remap_data_type::<Bytes>().put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))
+}
+
+/// Removes the manifest of an index once its operation finished.
+pub(crate) fn delete_manifest<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<()> {
+    let key = Key::manifest(index);
+    database
+        .remap_data_type::<DecodeIgnore>()
+        .delete(wtxn, &key)
+        .map_err(Error::on_key("delete", key))?;
+    Ok(())
+}
+
+/// Returns the manifest of an operation of `kind` to resume or a new one, or fails with
+/// [`Error::UnfinishedIngest`] if an operation of another kind was interrupted.
+pub(crate) fn resume_manifest<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    kind: IngestKind,
+) -> Result<IngestManifest> {
+    match get_manifest(rtxn, database, index)? {
+        Some(manifest) if manifest.kind == kind => Ok(manifest),
+        Some(manifest) => Err(Error::UnfinishedIngest { index, kind: manifest.kind }),
+        None => Ok(IngestManifest::new(kind)),
+    }
+}
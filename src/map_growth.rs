@@ -0,0 +1,98 @@
+//! Retries the write transactions that fill the LMDB map after growing it.
+use heed::{Env, RwTxn};
+use tracing::debug;
+
+use crate::{Error, Result};
+
+/// How [`write_with_map_growth`] grows the map of an environment once it is full.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapGrowth {
+    /// The factor the size of the map is multiplied by, rounded up to a multiple of the page
+    /// size.
+    pub factor: f32,
+    /// The size, in bytes, the map never grows past.
+    pub max_size: Option<usize>,
+    /// The number of times the map is grown before the error is returned.
+    pub max_retries: usize,
+}
+
+impl Default for MapGrowth {
+    fn default() -> Self {
+        Self { factor: 2.0, max_size: None, max_retries: 8 }
+    }
+}
+
+/// Runs `f` in a write transaction of the `env` and commits it. When `f` or the commit fails
+/// because the map is full, the transaction is aborted, the map grown according to the `growth`
+/// policy and `f` called again in a new transaction.
+///
+/// `f` must redo all of its writes when it is called again, e.g. add a batch of items and build
+/// the index, since nothing of the aborted transaction is kept. The error is returned once the
+/// map can't grow anymore.
+///
+/// # Safety
+///
+/// The map of an environment can only be resized while no transaction is alive. The caller
+/// must ensure that no other thread of the process holds a read or a write transaction of the
+/// `env` while `f` runs. The other processes pick the new size up on their next transaction.
+///
+/// # Example
+///
+/// ```no_run
+/// # use hannoy::{Writer, distances::Euclidean};
+/// # let (env, writer, items): (heed::Env, Writer<Euclidean>, Vec<(u32, Vec<f32>)>) = todo!();
+/// use hannoy::{write_with_map_growth, MapGrowth};
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+///
+/// let growth = MapGrowth { max_size: Some(64 * 1024 * 1024 * 1024), ..MapGrowth::default() };
+/// // safety: the indexing thread is the only one using the environment
+/// unsafe {
+///     write_with_map_growth(&env, growth, |wtxn| {
+///         for (item, vector) in &items {
+///             writer.add_item(wtxn, *item, vector)?;
+///         }
+///         writer.builder(&mut StdRng::seed_from_u64(42)).build::<16, 32>(wtxn)
+///     })?;
+/// }
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+pub unsafe fn write_with_map_growth<Tls, T, F>(
+    env: &Env<Tls>,
+    growth: MapGrowth,
+    mut f: F,
+) -> Result<T>
+where
+    F: FnMut(&mut RwTxn) -> Result<T>,
+{
+    let mut retries = 0;
+    loop {
+        let mut wtxn = env.write_txn()?;
+        let error = match f(&mut wtxn) {
+            Ok(output) => match wtxn.commit() {
+                Ok(()) => return Ok(output),
+                Err(e) => Error::from(e),
+            },
+            Err(e) => {
+                wtxn.abort();
+                e
+            }
+        };
+        if !error.is_map_full() || retries == growth.max_retries {
+            return Err(error);
+        }
+
+        let size = env.info().map_size;
+        let mut grown = (size as f64 * growth.factor as f64) as usize;
+        grown = grown.next_multiple_of(page_size::get());
+        if let Some(max_size) = growth.max_size {
+            grown = grown.min(max_size);
+        }
+        if grown <= size {
+            return Err(error);
+        }
+        debug!("the map of {size} bytes is full, growing it to {grown} bytes");
+        env.resize(grown)?;
+        retries += 1;
+    }
+}
@@ -0,0 +1,108 @@
+//! Indexes the prefixes of the vectors of Matryoshka embeddings, whose first dimensions carry
+//! most of their meaning, and keeps their complete vectors to re-rank the results of the
+//! searches, see [`Writer::set_matryoshka`](crate::Writer::set_matryoshka).
+use byteorder::BigEndian;
+use heed::types::U32;
+use heed::{RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::unaligned_vector::UnalignedVector;
+use crate::{Database, Error, ItemId, Key, Node, Result};
+
+/// The number of candidates found with the prefixes per result of a search, which are then
+/// re-ranked with the complete vectors, see [`QueryBuilder::rerank`](crate::QueryBuilder::rerank).
+pub(crate) const MATRYOSHKA_OVERSAMPLING: usize = 4;
+
+/// Returns the number of dimensions of the complete vectors of an index, `None` if it isn't a
+/// Matryoshka index.
+pub(crate) fn get_full_dimensions<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<usize>> {
+    let key = Key::matryoshka(index);
+    let dimensions = database
+        .remap_data_type::<U32<BigEndian>>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?;
+    Ok(dimensions.map(|dimensions| dimensions as usize))
+}
+
+/// Stores the number of dimensions of the complete vectors, read back by
+/// [`get_full_dimensions`].
+pub(crate) fn put_full_dimensions<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+) -> Result<()> {
+    let key = Key::matryoshka(index);
+    database
+        .remap_data_type::<U32<BigEndian>>()
+        .put(wtxn, &key, &(dimensions as u32))
+        .map_err(Error::on_key("put", key))
+}
+
+/// Returns the prefix of `dimensions` values of a complete vector if there are `full`
+/// dimensions, the vector itself otherwise.
+pub(crate) fn prefix(full: Option<usize>, dimensions: usize, vector: &[f32]) -> Result<&[f32]> {
+    match full {
+        Some(full) if vector.len() != full => {
+            Err(Error::InvalidDimensions { expected: full, got: vector.len() })
+        }
+        Some(_) => Ok(&vector[..dimensions]),
+        None => Ok(vector),
+    }
+}
+
+/// Returns a complete vector transformed like the vectors of the distance are.
+pub(crate) fn full_item<D: Distance>(vector: &[f32]) -> Item<'static, D> {
+    let vector = UnalignedVector::from_vec(D::preprocess(vector).into_owned());
+    Item { header: D::new_header(&vector), vector }
+}
+
+/// Returns the complete vector of an item, if it has one.
+pub(crate) fn get_full_vector<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    database: Database<D>,
+    index: u16,
+    item: ItemId,
+) -> Result<Option<Item<'t, D>>> {
+    let key = Key::full_vector(index, item);
+    match database.get(rtxn, &key).map_err(Error::on_key("get", key))? {
+        Some(Node::Item(item)) => Ok(Some(item)),
+        Some(Node::Links(_)) => Err(Error::unexpected_node(key, "Item")),
+        None => Ok(None),
+    }
+}
+
+/// Stores the complete vector of an item, read back by [`get_full_vector`].
+pub(crate) fn put_full_vector<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    item: ItemId,
+    vector: &[f32],
+) -> Result<()> {
+    let key = Key::full_vector(index, item);
+    database.put(wtxn, &key, &Node::Item(full_item(vector))).map_err(Error::on_key("put", key))
+}
+
+/// Replaces the distances of the candidates found with the prefixes of the vectors by the ones
+/// of their complete vectors to the complete `query`, and sorts them.
+pub(crate) fn rerank<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    query: &Item<D>,
+    candidates: &mut [(ItemId, f32)],
+) -> Result<()> {
+    for (item, distance) in candidates.iter_mut() {
+        if let Some(vector) = get_full_vector(rtxn, database, index, *item)? {
+            *distance = D::distance(query, &vector);
+        }
+    }
+    candidates.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    Ok(())
+}
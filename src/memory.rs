@@ -0,0 +1,63 @@
+use std::mem::size_of;
+
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::hnsw::ScoredLink;
+use crate::node::{item_size, LINKS_HEADER};
+use crate::unaligned_vector::UnalignedVector;
+use crate::ItemId;
+
+/// The bytes an LMDB entry takes besides its value: the key, the header of the node and its
+/// offset in the page.
+const LMDB_ENTRY_OVERHEAD: u64 = 8 + 8 + 2;
+
+/// The proportion of an LMDB page used by its entries, b-tree pages are between half full and
+/// full.
+const LMDB_PAGE_FILL: f64 = 0.75;
+
+/// The bytes the concurrent hash maps of the build spend on every node besides its links.
+const BUILD_NODE_OVERHEAD: u64 = 32;
+
+/// Returns the bytes of the pages read by the searches of an index of `items` vectors of
+/// `dimensions` dimensions, linked to `m` neighbours in the upper layers and `2 * m` in layer 0.
+///
+/// Every adjacency list is assumed full, the estimate is an upper bound of a well built graph.
+pub(crate) fn serving_bytes<D: Distance>(items: u64, dimensions: usize, m: usize) -> u64 {
+    let vector = UnalignedVector::<D::VectorCodec>::from_vec(vec![0.0; dimensions]);
+    let item = item_size::<D>(vector.as_bytes().len()) as u64;
+
+    let entries = items * (item + LMDB_ENTRY_OVERHEAD)
+        + items * (links_bytes(2 * m, items) + LMDB_ENTRY_OVERHEAD)
+        + upper_layer_nodes(items, m) * (links_bytes(m, items) + LMDB_ENTRY_OVERHEAD);
+    (entries as f64 / LMDB_PAGE_FILL) as u64
+}
+
+/// Returns the bytes resident during the build of an index, see [`serving_bytes`] for the
+/// parameters.
+///
+/// The build keeps the adjacency lists of every layer in memory, reads every vector and holds
+/// the written pages in the transaction until it is committed.
+pub(crate) fn build_bytes<D: Distance>(items: u64, dimensions: usize, m: usize) -> u64 {
+    // every layer stores the nodes with the capacity of layer 0
+    let node = (2 * m * size_of::<ScoredLink>() + size_of::<u64>()) as u64 + BUILD_NODE_OVERHEAD;
+    let graph = (items + upper_layer_nodes(items, m)) * node;
+    serving_bytes::<D>(items, dimensions, m) + graph
+}
+
+/// The expected number of nodes above layer 0: a node reaches the layer `l` with a probability
+/// of `m^-l`.
+fn upper_layer_nodes(items: u64, m: usize) -> u64 {
+    if m < 2 {
+        return items;
+    }
+    (items as f64 / (m - 1) as f64).ceil() as u64
+}
+
+/// The encoded size of the links of a node to `degree` neighbours spread over `items` ids.
+fn links_bytes(degree: usize, items: u64) -> u64 {
+    let step = (items / degree.max(1) as u64).max(1);
+    let links: RoaringBitmap =
+        (0..degree as u64).map(|i| ItemId::try_from(i * step).unwrap_or(ItemId::MAX)).collect();
+    (LINKS_HEADER + links.serialized_size()) as u64
+}
@@ -24,7 +24,7 @@ impl<'a> heed::BytesEncode<'a> for MetadataCodec {
 
     fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
         let Metadata { dimensions, items, entry_points, distance, max_level } = item;
-        debug_assert!(!distance.as_bytes().iter().any(|&b| b == 0));
+        debug_assert!(!distance.as_bytes().contains(&0));
 
         let mut output = Vec::with_capacity(
             size_of::<u32>()
@@ -64,6 +64,19 @@ impl<'a> heed::BytesDecode<'a> for MetadataCodec {
     }
 }
 
+/// Decodes only the dimensions of a [`Metadata`], without deserializing its items.
+pub enum MetadataDimensionsCodec {}
+
+impl<'a> heed::BytesDecode<'a> for MetadataDimensionsCodec {
+    type DItem = u32;
+
+    fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
+        let distance = CStr::from_bytes_until_nul(bytes)?;
+        let bytes = &bytes[distance.to_bytes_with_nul().len()..];
+        Ok(BigEndian::read_u32(bytes))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use heed::{BytesDecode, BytesEncode};
@@ -88,5 +101,6 @@ mod test {
         assert_eq!(metadata.entry_points.raw_bytes(), decoded.entry_points.raw_bytes());
         assert_eq!(metadata.distance, decoded.distance);
         assert_eq!(metadata.max_level, decoded.max_level);
+        assert_eq!(MetadataDimensionsCodec::bytes_decode(&encoded).unwrap(), 12);
     }
 }
@@ -0,0 +1,182 @@
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{RoTxn, RwTxn};
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::internals::KeyCodec;
+use crate::reader::tombstones;
+use crate::writer::check_lock;
+use crate::{Database, Error, Key, Prefix, PrefixCodec, Result, Writer};
+
+/// The number of keys moved at once from an index to another, the values of a batch are copied
+/// in memory before being written.
+const MOVE_BATCH: usize = 10_000;
+
+/// Moves the items of an index to a successor index with vectors of other dimensions, e.g.
+/// after changing of embedding model, see [`Writer::migrate`].
+///
+/// The payloads, timestamps and expirations of the items are copied to the successor as soon
+/// as the migration starts, the re-embedded vectors of the items are then added with
+/// [`Self::writer`] and the successor is built like any other index. [`Self::finish`] finally
+/// swaps the two indexes: the old index id serves the new vectors.
+pub struct Migration<D: Distance> {
+    database: Database<D>,
+    source: u16,
+    successor: Writer<D>,
+}
+
+impl<D: Distance> Migration<D> {
+    pub(crate) fn start(
+        wtxn: &mut RwTxn,
+        database: Database<D>,
+        source: u16,
+        successor: u16,
+        dimensions: usize,
+    ) -> Result<Migration<D>> {
+        let writer = Writer::new(database, successor, dimensions);
+        writer.clear(wtxn)?;
+
+        let raw = database.remap_data_type::<Bytes>();
+        for item in crate::preset::item_ids(wtxn, database, source)? {
+            for key in [
+                Key::payload(source, item),
+                Key::boost(source, item),
+                Key::timestamp(source, item),
+                Key::expiration(source, item),
+            ] {
+                let Some(bytes) = raw.get(wtxn, &key)?.map(<[u8]>::to_vec) else { continue };
+                let key = Key::new(successor, key.node);
+                raw.put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))?;
+            }
+        }
+
+        Ok(Migration { database, source, successor: writer })
+    }
+
+    /// Returns the writer of the successor index to add the re-embedded vectors of the items to,
+    /// and to build it.
+    pub fn writer(&self) -> &Writer<D> {
+        &self.successor
+    }
+
+    /// Returns the items of the old index whose vector wasn't added to the successor yet.
+    pub fn missing(&self, rtxn: &RoTxn) -> Result<RoaringBitmap> {
+        let source = crate::preset::item_ids(rtxn, self.database, self.source)?
+            - tombstones(rtxn, self.database, self.source)?;
+        let migrated = crate::preset::item_ids(rtxn, self.database, self.successor.index())?;
+        Ok(source - migrated)
+    }
+
+    /// Swaps the old and the successor indexes, the old index id serves the new vectors and the
+    /// old ones are moved to the successor index id, which can then be cleared.
+    ///
+    /// Fails with [`Error::MigrationIncomplete`] if some items were not re-embedded and with
+    /// [`Error::NeedBuild`] if the successor wasn't built since its last change. The readers see
+    /// the swap atomically once the transaction is committed.
+    pub fn finish(self, wtxn: &mut RwTxn) -> Result<Writer<D>> {
+        let missing = self.missing(wtxn)?.len();
+        if missing > 0 {
+            return Err(Error::MigrationIncomplete { missing });
+        }
+        let successor = self.successor.index();
+        if self.successor.need_build(wtxn)? {
+            return Err(Error::NeedBuild(successor));
+        }
+
+        swap_indexes(wtxn, self.database, self.source, successor)?;
+        Ok(Writer::new(self.database, self.source, self.successor.dimensions()))
+    }
+}
+
+/// Swaps the `a` and `b` indexes of the database: everything stored under the `a` index id,
+/// the graph, the items, their payloads, the settings and the change log, is moved to the `b`
+/// index id and the other way around.
+///
+/// The swap happens in the write transaction, the readers see either both indexes as they were
+/// or both swapped once it is committed. It is how an index built from scratch in a shadow
+/// index replaces the one being served without downtime. Every key of the two indexes is
+/// rewritten, the transaction grows with the size of both. Swapping an index with itself does
+/// nothing, the swap fails with [`Error::NoFreeIndex`] if every index id of the database is
+/// used and with [`Error::IndexLocked`] if a writer locked one of the indexes, see
+/// [`Writer::lock`].
+///
+/// # Example
+///
+/// ```no_run
+/// # use hannoy::{Database, Writer, distances::Cosine};
+/// # let (env, database, vectors): (heed::Env, Database<Cosine>, Vec<(u32, Vec<f32>)>) = todo!();
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+///
+/// // the index 0 is served while the shadow index 1 is rebuilt from scratch
+/// let mut wtxn = env.write_txn()?;
+/// let shadow = Writer::new(database, 1, 768);
+/// shadow.clear(&mut wtxn)?;
+/// for (item, vector) in &vectors {
+///     shadow.add_item(&mut wtxn, *item, vector)?;
+/// }
+/// shadow.builder(&mut StdRng::seed_from_u64(42)).build::<16, 32>(&mut wtxn)?;
+/// hannoy::swap_indexes(&mut wtxn, database, 0, 1)?;
+/// wtxn.commit()?;
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+pub fn swap_indexes<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    a: u16,
+    b: u16,
+) -> Result<()> {
+    if a == b {
+        return Ok(());
+    }
+    check_lock(wtxn, database, a, None)?;
+    check_lock(wtxn, database, b, None)?;
+    let scratch = free_index(wtxn, database)?;
+    move_keys(wtxn, database, Prefix::all(a), scratch)?;
+    move_keys(wtxn, database, Prefix::all(b), a)?;
+    move_keys(wtxn, database, Prefix::all(scratch), b)
+}
+
+/// Moves the keys starting with the `prefix` to the `target` index, batch by batch.
+pub(crate) fn move_keys<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    prefix: Prefix,
+    target: u16,
+) -> Result<()> {
+    let raw = database.remap_data_type::<Bytes>();
+    loop {
+        let moved = raw
+            .remap_key_type::<PrefixCodec>()
+            .prefix_iter(wtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
+            .remap_key_type::<KeyCodec>()
+            .take(MOVE_BATCH)
+            .map(|result| result.map(|(key, bytes)| (key, bytes.to_vec())))
+            .collect::<Result<Vec<_>, _>>()?;
+        if moved.is_empty() {
+            return Ok(());
+        }
+        for (key, bytes) in moved {
+            let moved = Key::new(target, key.node);
+            raw.put(wtxn, &moved, &bytes).map_err(Error::on_key("put", moved))?;
+            raw.delete(wtxn, &key).map_err(Error::on_key("delete", key))?;
+        }
+    }
+}
+
+/// Returns the highest index of the database without any key.
+pub(crate) fn free_index<D: Distance>(rtxn: &RoTxn, database: Database<D>) -> Result<u16> {
+    for index in (0..=u16::MAX).rev() {
+        let prefix = Prefix::all(index);
+        let mut entries = database
+            .remap_types::<PrefixCodec, DecodeIgnore>()
+            .prefix_iter(rtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
+            .remap_key_type::<DecodeIgnore>();
+        if entries.next().is_none() {
+            return Ok(index);
+        }
+    }
+    Err(Error::NoFreeIndex)
+}
@@ -0,0 +1,90 @@
+//! Metrics recorded through the [`metrics`](::metrics) facade.
+//!
+//! Nothing is recorded until an exporter, e.g. Prometheus, installs a recorder. Every metric is
+//! labelled with the `index` and the `distance` it was recorded for. Searches and builds are
+//! recorded as they complete, a cancelled search or build isn't recorded. The state of an index
+//! is only known from a transaction and must be sampled with [`record_index_state`], e.g. from
+//! the handler of the scraping endpoint.
+use std::time::Duration;
+
+use ::metrics::{counter, gauge, histogram};
+use heed::types::DecodeIgnore;
+use heed::RoTxn;
+
+use crate::distance::Distance;
+use crate::key::{Key, KeyCodec, Prefix, PrefixCodec};
+use crate::metadata::MetadataCodec;
+use crate::{Database, Error, Result};
+
+/// Counter of the completed searches.
+pub const SEARCHES: &str = "hannoy_searches_total";
+/// Histogram of the duration of the searches, in seconds.
+pub const SEARCH_DURATION: &str = "hannoy_search_duration_seconds";
+/// Histogram of the number of nodes visited by the searches.
+pub const SEARCH_VISITED_NODES: &str = "hannoy_search_visited_nodes";
+/// Counter of the completed builds.
+pub const BUILDS: &str = "hannoy_builds_total";
+/// Histogram of the duration of the builds, in seconds.
+pub const BUILD_DURATION: &str = "hannoy_build_duration_seconds";
+/// Counter of the items inserted or updated in the graph by the builds.
+pub const INDEXED_ITEMS: &str = "hannoy_indexed_items_total";
+/// Counter of the items removed from the graph by the builds.
+pub const REMOVED_ITEMS: &str = "hannoy_removed_items_total";
+/// Gauge of the number of items in the graph of an index.
+pub const ITEMS: &str = "hannoy_items";
+/// Gauge of the number of items added or deleted since the last build of an index.
+pub const PENDING_UPDATES: &str = "hannoy_pending_updates";
+
+pub(crate) fn record_search<D: Distance>(index: u16, visited: u64, duration: Duration) {
+    let labels = [("index", index.to_string()), ("distance", D::name().to_string())];
+    counter!(SEARCHES, &labels).increment(1);
+    histogram!(SEARCH_DURATION, &labels).record(duration);
+    histogram!(SEARCH_VISITED_NODES, &labels).record(visited as f64);
+}
+
+pub(crate) fn record_build<D: Distance>(
+    index: u16,
+    indexed: u64,
+    removed: u64,
+    duration: Duration,
+) {
+    let labels = [("index", index.to_string()), ("distance", D::name().to_string())];
+    counter!(BUILDS, &labels).increment(1);
+    histogram!(BUILD_DURATION, &labels).record(duration);
+    counter!(INDEXED_ITEMS, &labels).increment(indexed);
+    counter!(REMOVED_ITEMS, &labels).increment(removed);
+}
+
+/// Sets the [`ITEMS`] and [`PENDING_UPDATES`] gauges of an index.
+///
+/// This only reads the metadata of the index and walks the keys of its pending updates, it
+/// is cheap enough to be called on every scrape.
+pub fn record_index_state<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<()> {
+    let metadata_key = Key::metadata(index);
+    let items = database
+        .remap_data_type::<MetadataCodec>()
+        .get(rtxn, &metadata_key)
+        .map_err(Error::on_key("get", metadata_key))?
+        .map_or(0, |metadata| metadata.items.len());
+
+    let prefix = Prefix::updated(index);
+    let mut pending = 0;
+    for result in database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter(rtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<KeyCodec>()
+    {
+        result?;
+        pending += 1;
+    }
+
+    let labels = [("index", index.to_string()), ("distance", D::name().to_string())];
+    gauge!(ITEMS, &labels).set(items as f64);
+    gauge!(PENDING_UPDATES, &labels).set(pending as f64);
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Searches several indexes of a database as a single one, e.g. the shards of a collection or
+//! the indexes of the tenants of an application.
+use heed::RoTxn;
+
+use crate::distance::Distance;
+use crate::ordered_float::OrderedFloat;
+use crate::{Database, Error, ItemId, Reader, Result, ScoreKind};
+
+/// The readers of several indexes of the same dimensions and distance whose results are merged
+/// into a single ranking.
+///
+/// Every index is searched for the requested number of results, which are merged by distance:
+/// the distances of the same metric are comparable between indexes, unlike the scores
+/// normalized per search. The [`ScoreKind`] is applied to the merged results, so that a
+/// [`ScoreKind::Normalized`] score means the same for all the indexes.
+///
+/// The searches share the read transaction, which LMDB doesn't let several threads use: they
+/// run one after the other on a consistent view of all the indexes, and
+/// [`Self::with_parallel_expansion`] spreads the distances of each of them over the rayon
+/// thread pool.
+///
+/// # Example
+///
+/// ```no_run
+/// use hannoy::{distances::Cosine, MultiReader, ScoreKind};
+/// # let (rtxn, database): (heed::RoTxn, hannoy::Database<Cosine>) = todo!();
+///
+/// let shards = MultiReader::open(&rtxn, 0..4, database)?.with_scores(ScoreKind::Similarity);
+/// let searched = shards.nns_by_vector(&rtxn, &[1.25854, -0.75598, 0.58524], 10)?;
+/// for (index, item, similarity) in searched.nns {
+///     println!("item {item} of shard {index}: {similarity}");
+/// }
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct MultiReader<D: Distance> {
+    readers: Vec<Reader<D>>,
+    scores: ScoreKind,
+    expansion_batch: usize,
+}
+
+/// The merged results of a [`MultiReader`] search.
+#[derive(Debug)]
+pub struct MultiSearched {
+    /// The index, the id and the score of the nearest neighbours, closest first.
+    pub nns: Vec<(u16, ItemId, f32)>,
+    /// Whether the search of any index terminated early.
+    pub did_cancel: bool,
+    /// What the `f32` of the neighbours are, see [`MultiReader::with_scores`].
+    scores: ScoreKind,
+}
+
+impl MultiSearched {
+    /// Returns what the `f32` of the neighbours are, see [`MultiReader::with_scores`].
+    pub fn scores(&self) -> ScoreKind {
+        self.scores
+    }
+}
+
+impl<D: Distance> MultiReader<D> {
+    /// Merges the searches of the `readers`, or fails with [`Error::InvalidDimensions`] if
+    /// they don't all take queries of the same dimensions.
+    pub fn new(readers: Vec<Reader<D>>) -> Result<Self> {
+        if let Some(first) = readers.first() {
+            let expected = first.query_dimensions();
+            if let Some(other) = readers.iter().find(|r| r.query_dimensions() != expected) {
+                return Err(Error::InvalidDimensions { expected, got: other.query_dimensions() });
+            }
+        }
+        Ok(MultiReader { readers, scores: ScoreKind::Distance, expansion_batch: 1 })
+    }
+
+    /// Opens the readers of the `indexes` of the `database`, see [`Reader::open`].
+    pub fn open(
+        rtxn: &RoTxn,
+        indexes: impl IntoIterator<Item = u16>,
+        database: Database<D>,
+    ) -> Result<Self> {
+        let readers = indexes
+            .into_iter()
+            .map(|index| Reader::open(rtxn, index, database))
+            .collect::<Result<_>>()?;
+        Self::new(readers)
+    }
+
+    /// Returns the readers of the indexes, in the order they were given.
+    pub fn readers(&self) -> &[Reader<D>] {
+        &self.readers
+    }
+
+    /// Returns the merged results as `scores`, the distances by default, see
+    /// [`QueryBuilder::scores`](crate::QueryBuilder::scores).
+    pub fn with_scores(mut self, scores: ScoreKind) -> Self {
+        self.scores = scores;
+        self
+    }
+
+    /// Expands `batch` candidates at once in the search of every index, see
+    /// [`QueryBuilder::parallel_expansion`](crate::QueryBuilder::parallel_expansion).
+    pub fn with_parallel_expansion(mut self, batch: usize) -> Self {
+        self.expansion_batch = batch.max(1);
+        self
+    }
+
+    /// Returns the `count` closest items from the `query` among all the indexes.
+    pub fn nns_by_vector(
+        &self,
+        rtxn: &RoTxn,
+        query: &[f32],
+        count: usize,
+    ) -> Result<MultiSearched> {
+        let mut nns = Vec::with_capacity(count * self.readers.len());
+        let mut did_cancel = false;
+        for reader in &self.readers {
+            let searched = reader
+                .nns(count)
+                .parallel_expansion(self.expansion_batch)
+                .by_vector(rtxn, query)?;
+            did_cancel |= searched.did_cancel;
+            let index = reader.index();
+            nns.extend(searched.nns.into_iter().map(|(item, distance)| (index, item, distance)));
+        }
+        // the ties are broken by index, the items of different indexes can share an id
+        nns.sort_by_key(|&(index, item, distance)| (OrderedFloat(distance), index, item));
+        nns.truncate(count);
+
+        let (min, max) = self.scores.bounds(nns.iter().map(|&(_, _, distance)| distance));
+        for (_, _, score) in &mut nns {
+            *score = self.scores.score::<D>(*score, min, max);
+        }
+        Ok(MultiSearched { nns, did_cancel, scores: self.scores })
+    }
+}
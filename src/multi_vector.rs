@@ -0,0 +1,183 @@
+use std::collections::BTreeSet;
+
+use heed::{RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::ordered_float::OrderedFloat;
+use crate::unaligned_vector::UnalignedVector;
+use crate::{Error, ItemId, Reader, Result, Writer};
+
+/// The number of candidate vectors searched for every query vector and requested item.
+const CANDIDATES_FACTOR: usize = 4;
+
+/// How the distances between the vectors of a query and the vectors of an item are combined into
+/// the distance of the item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiVectorScore {
+    /// The distance of the closest pair of query and item vectors.
+    Max,
+    /// The mean distance between the query vectors and the item vectors.
+    Mean,
+    /// The mean over the query vectors of the distance to their closest item vector, the
+    /// late-interaction score of ColBERT.
+    #[default]
+    MaxSim,
+}
+
+/// Stores several vectors per item, e.g. the token embeddings of a document for late-interaction
+/// models like ColBERT, and searches the items by combining the distances of their vectors.
+///
+/// Every vector is stored as an item of the index whose id keeps the item in its high bits and
+/// the position of the vector in the `slot_bits` low bits: with 8 bits an item holds up to 256
+/// vectors and the item ids must be lower than 2²⁴. The index is built like any other.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hannoy::{distances::Cosine, MultiVector, MultiVectorScore, Reader, Writer};
+/// # let (writer, reader, mut wtxn, rtxn): (Writer<Cosine>, Reader<Cosine>, heed::RwTxn, heed::RoTxn) = todo!();
+/// let layout = MultiVector::new(8);
+/// layout.add_item(&writer, &mut wtxn, 42, &[[0.1, 0.9], [0.7, 0.2]])?;
+///
+/// let queries = [[0.2, 0.8], [0.9, 0.0]];
+/// let nns = layout.nns(&reader, &rtxn, &queries, 10, MultiVectorScore::MaxSim)?;
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiVector {
+    slot_bits: u8,
+}
+
+impl MultiVector {
+    /// Reserves the `slot_bits` low bits of the ids of the index to the positions of the vectors
+    /// of an item, up to 31 bits.
+    pub fn new(slot_bits: u8) -> Self {
+        MultiVector { slot_bits: slot_bits.min(31) }
+    }
+
+    /// The maximum number of vectors of an item.
+    pub fn max_vectors(&self) -> usize {
+        1 << self.slot_bits
+    }
+
+    /// Returns the id the `slot`-th vector of `item` is stored at.
+    pub fn vector_id(&self, item: ItemId, slot: u32) -> ItemId {
+        (item << self.slot_bits) | slot
+    }
+
+    /// Returns the item a vector stored at `vector_id` belongs to.
+    pub fn item(&self, vector_id: ItemId) -> ItemId {
+        vector_id >> self.slot_bits
+    }
+
+    /// Replaces the vectors of an item by `vectors`.
+    pub fn add_item<D: Distance>(
+        &self,
+        writer: &Writer<D>,
+        wtxn: &mut RwTxn,
+        item: ItemId,
+        vectors: &[impl AsRef<[f32]>],
+    ) -> Result<()> {
+        if item.checked_shl(self.slot_bits.into()).is_none_or(|id| id >> self.slot_bits != item) {
+            let reason = format!("the id doesn't fit with {} slot bits", self.slot_bits);
+            return Err(Error::InvalidMultiVectorItem { item, reason });
+        }
+        if vectors.len() > self.max_vectors() {
+            let reason = format!("{} vectors but at most {}", vectors.len(), self.max_vectors());
+            return Err(Error::InvalidMultiVectorItem { item, reason });
+        }
+
+        let ids = (0..vectors.len() as u32).map(|slot| self.vector_id(item, slot));
+        writer.add_items(wtxn, ids.zip(vectors.iter().map(AsRef::as_ref)))?;
+        // the previous vectors are contiguous, delete the ones past the new vectors
+        for slot in vectors.len()..self.max_vectors() {
+            if !writer.del_item(wtxn, self.vector_id(item, slot as u32))? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes every vector of an item and returns `true` if it had any.
+    pub fn del_item<D: Distance>(
+        &self,
+        writer: &Writer<D>,
+        wtxn: &mut RwTxn,
+        item: ItemId,
+    ) -> Result<bool> {
+        let mut deleted = false;
+        for slot in 0..self.max_vectors() {
+            if !writer.del_item(wtxn, self.vector_id(item, slot as u32))? {
+                break;
+            }
+            deleted = true;
+        }
+        Ok(deleted)
+    }
+
+    /// Returns the `count` closest items from the `queries` vectors, every item appears once
+    /// along with its distance combined according to `score`.
+    ///
+    /// The closest vectors of every query vector are searched in the graph and their items are
+    /// then scored with all their vectors.
+    pub fn nns<D: Distance>(
+        &self,
+        reader: &Reader<D>,
+        rtxn: &RoTxn,
+        queries: &[impl AsRef<[f32]>],
+        count: usize,
+        score: MultiVectorScore,
+    ) -> Result<Vec<(ItemId, f32)>> {
+        let mut items = BTreeSet::new();
+        for query in queries {
+            let searched = reader.nns(count * CANDIDATES_FACTOR).by_vector(rtxn, query.as_ref())?;
+            items.extend(searched.into_nns().into_iter().map(|(id, _)| self.item(id)));
+        }
+
+        let queries: Vec<_> = queries
+            .iter()
+            .map(|query| reader.query_vector(query.as_ref()))
+            .collect::<Result<_>>()?;
+        let queries: Vec<_> = queries
+            .iter()
+            .map(|query| {
+                let vector = UnalignedVector::from_slice(query);
+                Item { header: D::new_header(&vector), vector }
+            })
+            .collect();
+
+        let mut nns = Vec::with_capacity(items.len());
+        let mut closest = vec![f32::INFINITY; queries.len()];
+        for item in items {
+            let (mut sum, mut pairs) = (0.0, 0);
+            closest.iter_mut().for_each(|distance| *distance = f32::INFINITY);
+            let first = self.vector_id(item, 0);
+            let last = self.vector_id(item, self.max_vectors() as u32 - 1);
+            for id in reader.item_ids().range(first..=last) {
+                let Some(vector) = reader.item_vector_ref(rtxn, id)? else { continue };
+                let vector = Item { header: D::new_header(&vector), vector };
+                for (query, closest) in queries.iter().zip(&mut closest) {
+                    let distance = D::distance(query, &vector);
+                    *closest = closest.min(distance);
+                    sum += distance;
+                    pairs += 1;
+                }
+            }
+
+            let distance = match score {
+                MultiVectorScore::Max => closest.iter().copied().fold(f32::INFINITY, f32::min),
+                MultiVectorScore::Mean => sum / pairs.max(1) as f32,
+                MultiVectorScore::MaxSim => closest.iter().sum::<f32>() / closest.len() as f32,
+            };
+            nns.push((OrderedFloat(distance), item));
+        }
+
+        nns.sort_unstable();
+        Ok(nns
+            .into_iter()
+            .take(count)
+            .map(|(OrderedFloat(distance), item)| (item, distance))
+            .collect())
+    }
+}
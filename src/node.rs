@@ -4,7 +4,7 @@ use std::mem::size_of;
 use std::ops::Deref;
 
 use bytemuck::{bytes_of, cast_slice, pod_read_unaligned};
-use byteorder::{ByteOrder, NativeEndian};
+use byteorder::{ByteOrder, LittleEndian, NativeEndian};
 use heed::{BoxedError, BytesDecode, BytesEncode};
 use roaring::RoaringBitmap;
 
@@ -20,6 +20,77 @@ pub enum Node<'a, D: Distance> {
 
 const NODE_TAG: u8 = 0;
 const LINKS_TAG: u8 = 1;
+/// An item whose header is preceded by padding, so that its vector is aligned.
+const ALIGNED_NODE_TAG: u8 = 2;
+/// Links whose roaring bitmap is preceded by padding, so that it is aligned.
+const ALIGNED_LINKS_TAG: u8 = 3;
+/// Links stored as the sorted array of their ids, see [`encode_array_links`].
+const ARRAY_LINKS_TAG: u8 = 4;
+
+/// The alignment of the vectors from the start of the values. LMDB writes the values too large
+/// for a leaf page in overflow pages, right after their 16 bytes header, where the vectors can
+/// then be loaded with aligned 128 bits loads from the memory map. The values kept in the leaf
+/// pages are only aligned on 2 bytes, the readers must check the alignment before relying on it.
+pub(crate) const VECTOR_ALIGNMENT: usize = 16;
+
+/// The alignment of the roaring bitmaps from the start of the values, the one of their widest
+/// words.
+const LINKS_ALIGNMENT: usize = 8;
+
+/// The alignment of the ids of the array links from the start of the values.
+const ARRAY_LINKS_ALIGNMENT: usize = size_of::<ItemId>();
+
+/// Returns the number of padding bytes to write after the tag and the padding length so that
+/// the `before` bytes that follow them end at a multiple of `alignment`.
+const fn padding(before: usize, alignment: usize) -> usize {
+    (alignment - (2 + before) % alignment) % alignment
+}
+
+/// Returns the bytes that follow the padding of an aligned node.
+fn unpad(padding: u8, bytes: &[u8]) -> Result<&[u8], BoxedError> {
+    bytes.get(usize::from(padding)..).ok_or_else(|| {
+        Box::new(InvalidNodeDecoding { unknown_tag: None, truncated: true }) as BoxedError
+    })
+}
+
+/// The bytes of the encoded links before their roaring bitmap.
+pub(crate) const LINKS_HEADER: usize = 2 + padding(0, LINKS_ALIGNMENT);
+
+/// Returns the encoded size of an item of `vector_bytes` bytes.
+pub(crate) fn item_size<D: Distance>(vector_bytes: usize) -> usize {
+    let header = size_of::<D::Header>();
+    2 + padding(header, VECTOR_ALIGNMENT) + header + vector_bytes
+}
+
+/// Returns the roaring bitmap of an encoded links node, `None` if it is another node.
+pub(crate) fn serialized_links(bytes: &[u8]) -> Result<Option<&[u8]>, BoxedError> {
+    match bytes {
+        [LINKS_TAG, bytes @ ..] => Ok(Some(bytes)),
+        [ALIGNED_LINKS_TAG, padding, bytes @ ..] => unpad(*padding, bytes).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the ids of an encoded array links node, `None` if it is another node.
+pub(crate) fn array_links(bytes: &[u8]) -> Result<Option<&[u8]>, BoxedError> {
+    match bytes {
+        [ARRAY_LINKS_TAG, padding, bytes @ ..] => unpad(*padding, bytes).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Encodes links as the sorted array of their little-endian ids rather than a roaring bitmap,
+/// see [`HannoyBuilder::array_links`](crate::HannoyBuilder::array_links). The searches iterate
+/// them with a load per id, without going through the containers of a bitmap. The ids are
+/// preceded by padding, so that they are aligned and read in place on little-endian targets.
+pub(crate) fn encode_array_links(links: &RoaringBitmap) -> Vec<u8> {
+    let padding = padding(0, ARRAY_LINKS_ALIGNMENT);
+    let mut bytes = Vec::with_capacity(2 + padding + links.len() as usize * size_of::<ItemId>());
+    bytes.extend_from_slice(&[ARRAY_LINKS_TAG, padding as u8]);
+    bytes.resize(bytes.len() + padding, 0);
+    links.iter().for_each(|id| bytes.extend_from_slice(&id.to_le_bytes()));
+    bytes
+}
 
 impl<'a, D: Distance> Node<'a, D> {
     pub fn item(self) -> Option<Item<'a, D>> {
@@ -60,6 +131,13 @@ impl<D: Distance> Clone for Item<'_, D> {
     }
 }
 
+/// An empty item, only used to fill the unused slots of the arrays of items kept on the stack.
+impl<D: Distance> Default for Item<'_, D> {
+    fn default() -> Self {
+        Self { header: bytemuck::Zeroable::zeroed(), vector: Cow::default() }
+    }
+}
+
 impl<D: Distance> Item<'_, D> {
     /// Converts the item into an owned version of itself by cloning
     /// the internal vector. Doing so will make it mutable.
@@ -134,12 +212,19 @@ impl<'a, D: Distance> BytesEncode<'a> for NodeCodec<D> {
         let mut bytes = Vec::new();
         match item {
             Node::Item(Item { header, vector }) => {
-                bytes.push(NODE_TAG);
-                bytes.extend_from_slice(bytes_of(header));
+                let header = bytes_of(header);
+                let padding = padding(header.len(), VECTOR_ALIGNMENT);
+                bytes.reserve(2 + padding + header.len() + vector.as_bytes().len());
+                bytes.extend_from_slice(&[ALIGNED_NODE_TAG, padding as u8]);
+                bytes.resize(bytes.len() + padding, 0);
+                bytes.extend_from_slice(header);
                 bytes.extend(vector.as_bytes());
             }
             Node::Links(Links { links }) => {
-                bytes.push(LINKS_TAG);
+                let padding = padding(0, LINKS_ALIGNMENT);
+                bytes.reserve(2 + padding + links.serialized_size());
+                bytes.extend_from_slice(&[ALIGNED_LINKS_TAG, padding as u8]);
+                bytes.resize(bytes.len() + padding, 0);
                 links.serialize_into(&mut bytes)?;
             }
         }
@@ -152,36 +237,54 @@ impl<'a, D: Distance> BytesDecode<'a> for NodeCodec<D> {
 
     fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
         match bytes {
-            [NODE_TAG, bytes @ ..] => {
-                let (header_bytes, remaining) = bytes.split_at(size_of::<D::Header>());
-                let header = pod_read_unaligned(header_bytes);
-                let vector = UnalignedVector::<D::VectorCodec>::from_bytes(remaining)?;
-
-                Ok(Node::Item(Item { header, vector }))
-            }
-            [LINKS_TAG, bytes @ ..] => {
-                let links: Cow<'_, RoaringBitmap> =
-                    Cow::Owned(RoaringBitmap::deserialize_from(bytes).unwrap());
-                Ok(Node::Links(Links { links }))
+            [NODE_TAG, bytes @ ..] => decode_item(bytes),
+            [ALIGNED_NODE_TAG, padding, bytes @ ..] => decode_item(unpad(*padding, bytes)?),
+            [LINKS_TAG, bytes @ ..] => decode_links(bytes),
+            [ALIGNED_LINKS_TAG, padding, bytes @ ..] => decode_links(unpad(*padding, bytes)?),
+            [ARRAY_LINKS_TAG, padding, bytes @ ..] => decode_array_links(unpad(*padding, bytes)?),
+            [ALIGNED_NODE_TAG | ALIGNED_LINKS_TAG | ARRAY_LINKS_TAG] => {
+                Err(Box::new(InvalidNodeDecoding { unknown_tag: None, truncated: true }))
             }
 
-            [unknown_tag, ..] => {
-                Err(Box::new(InvalidNodeDecoding { unknown_tag: Some(*unknown_tag) }))
-            }
-            [] => Err(Box::new(InvalidNodeDecoding { unknown_tag: None })),
+            [unknown_tag, ..] => Err(Box::new(InvalidNodeDecoding {
+                unknown_tag: Some(*unknown_tag),
+                truncated: false,
+            })),
+            [] => Err(Box::new(InvalidNodeDecoding { unknown_tag: None, truncated: false })),
         }
     }
 }
 
+fn decode_item<D: Distance>(bytes: &[u8]) -> Result<Node<'_, D>, BoxedError> {
+    let (header_bytes, remaining) = bytes.split_at(size_of::<D::Header>());
+    let header = pod_read_unaligned(header_bytes);
+    let vector = UnalignedVector::<D::VectorCodec>::from_bytes(remaining)?;
+
+    Ok(Node::Item(Item { header, vector }))
+}
+
+fn decode_links<D: Distance>(bytes: &[u8]) -> Result<Node<'_, D>, BoxedError> {
+    let links: Cow<'_, RoaringBitmap> = Cow::Owned(RoaringBitmap::deserialize_from(bytes)?);
+    Ok(Node::Links(Links { links }))
+}
+
+fn decode_array_links<D: Distance>(bytes: &[u8]) -> Result<Node<'_, D>, BoxedError> {
+    let ids = bytes.chunks_exact(size_of::<ItemId>()).map(LittleEndian::read_u32);
+    let links = RoaringBitmap::from_sorted_iter(ids)?;
+    Ok(Node::Links(Links { links: Cow::Owned(links) }))
+}
+
 #[derive(Debug, thiserror::Error)]
 pub struct InvalidNodeDecoding {
     unknown_tag: Option<u8>,
+    truncated: bool,
 }
 
 impl fmt::Display for InvalidNodeDecoding {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.unknown_tag {
             Some(unknown_tag) => write!(f, "Invalid node decoding: unknown tag {unknown_tag}"),
+            None if self.truncated => write!(f, "Invalid node decoding: truncated padding"),
             None => write!(f, "Invalid node decoding: empty array of bytes"),
         }
     }
@@ -189,7 +292,10 @@ impl fmt::Display for InvalidNodeDecoding {
 
 #[cfg(test)]
 mod tests {
-    use super::{Item, Links, Node, NodeCodec};
+    use super::{
+        array_links, encode_array_links, item_size, serialized_links, Item, Links, Node, NodeCodec,
+        LINKS_TAG, NODE_TAG, VECTOR_ALIGNMENT,
+    };
     use crate::{distance::Cosine, internals::UnalignedVector, Distance};
     use heed::{BytesDecode, BytesEncode};
     use roaring::RoaringBitmap;
@@ -239,6 +345,52 @@ mod tests {
         assert_eq!(new_item.vector.as_bytes(), item.vector.as_bytes());
     }
 
+    #[test]
+    fn test_vectors_are_padded_to_the_alignment() {
+        type D = Cosine;
+
+        let item = Item::<D>::new(vec![1.0f32, 2.0f32, 3.0f32]);
+        let node = Node::Item(item.clone());
+        let bytes = NodeCodec::<D>::bytes_encode(&node).unwrap();
+        assert_eq!(bytes.len(), item_size::<D>(item.vector.as_bytes().len()));
+
+        let vector = &bytes[bytes.len() - item.vector.as_bytes().len()..];
+        assert_eq!((bytes.len() - vector.len()) % VECTOR_ALIGNMENT, 0);
+        assert_eq!(vector, item.vector.as_bytes());
+    }
+
+    #[test]
+    fn test_legacy_layouts_decode() {
+        type D = Cosine;
+
+        let item = Item::<D>::new(vec![1.0f32, 2.0f32]);
+        let mut bytes = vec![NODE_TAG];
+        bytes.extend_from_slice(bytemuck::bytes_of(&item.header));
+        bytes.extend_from_slice(item.vector.as_bytes());
+        let decoded = NodeCodec::<D>::bytes_decode(&bytes).unwrap().item().unwrap();
+        assert_eq!(decoded.vector.as_bytes(), item.vector.as_bytes());
+
+        let bitmap = RoaringBitmap::from_iter([1, 42]);
+        let mut bytes = vec![LINKS_TAG];
+        bitmap.serialize_into(&mut bytes).unwrap();
+        let decoded = NodeCodec::<D>::bytes_decode(&bytes).unwrap().links().unwrap();
+        assert_eq!(*decoded.links, bitmap);
+        assert_eq!(serialized_links(&bytes).unwrap(), Some(&bytes[1..]));
+    }
+
+    #[test]
+    fn test_array_links_codec() {
+        let bitmap = RoaringBitmap::from_iter([1, 42, 70_000]);
+        let bytes = encode_array_links(&bitmap);
+        let ids = array_links(&bytes).unwrap().unwrap();
+        assert_eq!(ids, [1, 0, 0, 0, 42, 0, 0, 0, 0x70, 0x11, 0x01, 0]);
+        assert_eq!((bytes.len() - ids.len()) % size_of::<u32>(), 0);
+        assert_eq!(serialized_links(&bytes).unwrap(), None);
+
+        let decoded = NodeCodec::<Cosine>::bytes_decode(&bytes).unwrap().links().unwrap();
+        assert_eq!(*decoded.links, bitmap);
+    }
+
     #[test]
     fn test_bitmap_codec() {
         let mut bitmap = RoaringBitmap::new();
@@ -18,10 +18,58 @@ pub enum NodeMode {
     Links = 2,
     /// The original vectors are stored under this id in `Item` structures.
     Item = 3,
+    /// The instant an item expires at, in milliseconds since the Unix epoch.
+    Expiration = 4,
+    /// The log of the changes of the items under their sequence number.
+    Change = 5,
+    /// The previous vectors of an item, under the item and the slot of its ring of versions.
+    History = 6,
+    /// The items deleted but kept in the graph until the next vacuum.
+    /// We only stores `Unit` values under the keys.
+    Tombstone = 7,
+    /// The instant an item was created or last refreshed at, in milliseconds since the Unix epoch.
+    Timestamp = 8,
+    /// The external id of an item of a reordered index, under its internal id.
+    ExternalId = 9,
+    /// The internal id of an item of a reordered index, under its external id.
+    InternalId = 10,
+    /// The opaque bytes an application attached to an item.
+    Payload = 11,
+    /// The factor the final score of an item is boosted by, the bits of an `f32`.
+    Boost = 12,
+    /// The complete vector of an item of a Matryoshka index, whose `Item` only holds a prefix.
+    FullVector = 13,
+}
+
+impl NodeMode {
+    /// A human readable name used in error messages.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            NodeMode::Item => "Item",
+            NodeMode::Links => "Links",
+            NodeMode::Metadata => "Metadata",
+            NodeMode::Updated => "Updated",
+            NodeMode::Expiration => "Expiration",
+            NodeMode::Change => "Change",
+            NodeMode::History => "History",
+            NodeMode::Tombstone => "Tombstone",
+            NodeMode::Timestamp => "Timestamp",
+            NodeMode::ExternalId => "ExternalId",
+            NodeMode::InternalId => "InternalId",
+            NodeMode::Payload => "Payload",
+            NodeMode::Boost => "Boost",
+            NodeMode::FullVector => "FullVector",
+        }
+    }
 }
 
+/// Returned when a byte doesn't correspond to any [`NodeMode`].
+#[derive(Debug, thiserror::Error)]
+#[error("Could not convert {0} as a `NodeMode`.")]
+pub struct InvalidNodeMode(pub u8);
+
 impl TryFrom<u8> for NodeMode {
-    type Error = String;
+    type Error = InvalidNodeMode;
 
     fn try_from(v: u8) -> std::result::Result<Self, Self::Error> {
         match v {
@@ -29,7 +77,17 @@ impl TryFrom<u8> for NodeMode {
             v if v == NodeMode::Links as u8 => Ok(NodeMode::Links),
             v if v == NodeMode::Updated as u8 => Ok(NodeMode::Updated),
             v if v == NodeMode::Metadata as u8 => Ok(NodeMode::Metadata),
-            v => Err(format!("Could not convert {v} as a `NodeMode`.")),
+            v if v == NodeMode::Expiration as u8 => Ok(NodeMode::Expiration),
+            v if v == NodeMode::Change as u8 => Ok(NodeMode::Change),
+            v if v == NodeMode::History as u8 => Ok(NodeMode::History),
+            v if v == NodeMode::Tombstone as u8 => Ok(NodeMode::Tombstone),
+            v if v == NodeMode::Timestamp as u8 => Ok(NodeMode::Timestamp),
+            v if v == NodeMode::ExternalId as u8 => Ok(NodeMode::ExternalId),
+            v if v == NodeMode::InternalId as u8 => Ok(NodeMode::InternalId),
+            v if v == NodeMode::Payload as u8 => Ok(NodeMode::Payload),
+            v if v == NodeMode::Boost as u8 => Ok(NodeMode::Boost),
+            v if v == NodeMode::FullVector as u8 => Ok(NodeMode::FullVector),
+            v => Err(InvalidNodeMode(v)),
         }
     }
 }
@@ -60,6 +118,74 @@ impl NodeId {
         Self { mode: NodeMode::Metadata, item: 1, layer: 0 }
     }
 
+    pub const fn tuning() -> Self {
+        Self { mode: NodeMode::Metadata, item: 2, layer: 0 }
+    }
+
+    pub const fn change_sequence() -> Self {
+        Self { mode: NodeMode::Metadata, item: 3, layer: 0 }
+    }
+
+    pub const fn applied_sequence() -> Self {
+        Self { mode: NodeMode::Metadata, item: 4, layer: 0 }
+    }
+
+    pub const fn external_items() -> Self {
+        Self { mode: NodeMode::Metadata, item: 5, layer: 0 }
+    }
+
+    pub const fn build() -> Self {
+        Self { mode: NodeMode::Metadata, item: 6, layer: 0 }
+    }
+
+    pub const fn codes() -> Self {
+        Self { mode: NodeMode::Metadata, item: 7, layer: 0 }
+    }
+
+    pub const fn weights() -> Self {
+        Self { mode: NodeMode::Metadata, item: 8, layer: 0 }
+    }
+
+    pub const fn write_lock() -> Self {
+        Self { mode: NodeMode::Metadata, item: 9, layer: 0 }
+    }
+
+    pub const fn projection() -> Self {
+        Self { mode: NodeMode::Metadata, item: 10, layer: 0 }
+    }
+
+    pub const fn sketches() -> Self {
+        Self { mode: NodeMode::Metadata, item: 11, layer: 0 }
+    }
+
+    pub const fn calibration() -> Self {
+        Self { mode: NodeMode::Metadata, item: 12, layer: 0 }
+    }
+
+    pub const fn densities() -> Self {
+        Self { mode: NodeMode::Metadata, item: 13, layer: 0 }
+    }
+
+    pub const fn manifest() -> Self {
+        Self { mode: NodeMode::Metadata, item: 14, layer: 0 }
+    }
+
+    pub const fn matryoshka() -> Self {
+        Self { mode: NodeMode::Metadata, item: 15, layer: 0 }
+    }
+
+    pub const fn snapshots() -> Self {
+        Self { mode: NodeMode::Metadata, item: 16, layer: 0 }
+    }
+
+    pub const fn change_log() -> Self {
+        Self { mode: NodeMode::Metadata, item: 17, layer: 0 }
+    }
+
+    pub const fn format() -> Self {
+        Self { mode: NodeMode::Metadata, item: 18, layer: 0 }
+    }
+
     pub const fn updated(item: u32) -> Self {
         Self { mode: NodeMode::Updated, item, layer: 0 }
     }
@@ -72,6 +198,46 @@ impl NodeId {
         Self { mode: NodeMode::Item, item, layer: 0 }
     }
 
+    pub const fn expiration(item: u32) -> Self {
+        Self { mode: NodeMode::Expiration, item, layer: 0 }
+    }
+
+    pub const fn change(sequence: u32) -> Self {
+        Self { mode: NodeMode::Change, item: sequence, layer: 0 }
+    }
+
+    pub const fn history(item: u32, slot: u8) -> Self {
+        Self { mode: NodeMode::History, item, layer: slot }
+    }
+
+    pub const fn tombstone(item: u32) -> Self {
+        Self { mode: NodeMode::Tombstone, item, layer: 0 }
+    }
+
+    pub const fn timestamp(item: u32) -> Self {
+        Self { mode: NodeMode::Timestamp, item, layer: 0 }
+    }
+
+    pub const fn payload(item: u32) -> Self {
+        Self { mode: NodeMode::Payload, item, layer: 0 }
+    }
+
+    pub const fn boost(item: u32) -> Self {
+        Self { mode: NodeMode::Boost, item, layer: 0 }
+    }
+
+    pub const fn full_vector(item: u32) -> Self {
+        Self { mode: NodeMode::FullVector, item, layer: 0 }
+    }
+
+    pub const fn external_id(internal: u32) -> Self {
+        Self { mode: NodeMode::ExternalId, item: internal, layer: 0 }
+    }
+
+    pub const fn internal_id(external: u32) -> Self {
+        Self { mode: NodeMode::InternalId, item: external, layer: 0 }
+    }
+
     /// Return the underlying `ItemId` if it is an item.
     /// Panic otherwise.
     #[track_caller]
@@ -112,6 +278,13 @@ impl NodeId {
 mod test {
     use super::*;
 
+    #[test]
+    fn invalid_node_mode() {
+        assert_eq!(NodeMode::try_from(NodeMode::Links as u8).unwrap(), NodeMode::Links);
+        let err = NodeMode::try_from(42).unwrap_err();
+        assert_eq!(err.to_string(), "Could not convert 42 as a `NodeMode`.");
+    }
+
     #[test]
     fn check_node_id_ordering() {
         // NOTE: `layer`s take precedence over item_ids
@@ -0,0 +1,235 @@
+//! Load dense matrices stored in the NumPy `.npy` format.
+//!
+//! Only C-ordered little-endian arrays are supported: `float32` two dimensional matrices for
+//! the vectors and integer arrays for the ids. Besides [`Writer::import_npy`], the readers of
+//! this module load whole arrays in memory, e.g. the queries and ground truth of a benchmark.
+//!
+//! The arrays of the `.npz` archives are read by the `npz` module, behind the `npz` feature.
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use heed::RwTxn;
+
+use crate::distance::Distance;
+use crate::{Error, ItemId, Result, Writer};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// The number of rows read and written at once.
+const IMPORT_BATCH_SIZE: usize = 8192;
+
+/// The parsed header of an `.npy` file.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct NpyHeader {
+    pub descr: String,
+    pub shape: Vec<usize>,
+}
+
+impl NpyHeader {
+    /// Reads the header and leaves `reader` at the start of the data.
+    pub(crate) fn read(reader: &mut impl Read) -> Result<NpyHeader> {
+        let mut preamble = [0; 8];
+        reader.read_exact(&mut preamble)?;
+        if &preamble[..6] != MAGIC {
+            return Err(invalid("missing the magic string"));
+        }
+
+        let header_len = match preamble[6] {
+            1 => {
+                let mut len = [0; 2];
+                reader.read_exact(&mut len)?;
+                u16::from_le_bytes(len) as usize
+            }
+            2 | 3 => {
+                let mut len = [0; 4];
+                reader.read_exact(&mut len)?;
+                u32::from_le_bytes(len) as usize
+            }
+            version => return Err(invalid(format!("unsupported version {version}"))),
+        };
+        let mut header = vec![0; header_len];
+        reader.read_exact(&mut header)?;
+        let header = String::from_utf8(header).map_err(|_| invalid("header is not UTF-8"))?;
+
+        if dict_value(&header, "fortran_order")? != "False" {
+            return Err(invalid("fortran ordered arrays are not supported"));
+        }
+        let descr = dict_value(&header, "descr")?.trim_matches(['\'', '"']).to_string();
+        let shape = dict_value(&header, "shape")?
+            .trim_matches(['(', ')'])
+            .split(',')
+            .map(str::trim)
+            .filter(|dim| !dim.is_empty())
+            .map(|dim| dim.parse().map_err(|_| invalid(format!("invalid shape dimension {dim}"))))
+            .collect::<Result<_>>()?;
+
+        Ok(NpyHeader { descr, shape })
+    }
+}
+
+/// Extracts the raw value associated to `key` in the python dict of the header.
+fn dict_value<'a>(header: &'a str, key: &str) -> Result<&'a str> {
+    let missing = || invalid(format!("missing `{key}` in the header"));
+    let start = header.find(&format!("'{key}'")).ok_or_else(missing)? + key.len() + 2;
+    let value = header[start..].trim_start().strip_prefix(':').ok_or_else(missing)?.trim_start();
+    let end = if value.starts_with('(') {
+        value.find(')').map(|end| end + 1)
+    } else {
+        value.find([',', '}'])
+    };
+    Ok(&value[..end.ok_or_else(missing)?])
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::InvalidNpy { reason: reason.into() }
+}
+
+/// Reads a one dimensional array of integers as item ids.
+pub fn read_ids(reader: &mut impl Read) -> Result<Vec<ItemId>> {
+    let header = NpyHeader::read(reader)?;
+    let [len] = header.shape[..] else {
+        return Err(invalid(format!("expected a 1D array of ids, got {:?}", header.shape)));
+    };
+    read_ids_data(reader, &header.descr, len)
+}
+
+/// Reads a two dimensional array of integers as item ids, such as the indices of the nearest
+/// neighbours of a set of queries, and returns the values row by row with the number of columns.
+pub fn read_id_matrix(reader: &mut impl Read) -> Result<(Vec<ItemId>, usize)> {
+    let header = NpyHeader::read(reader)?;
+    let [rows, columns] = matrix_shape(&header)?;
+    Ok((read_ids_data(reader, &header.descr, matrix_len(rows, columns, 1)?)?, columns))
+}
+
+/// Reads a two dimensional `float32` matrix and returns the values row by row with the number
+/// of columns.
+pub fn read_f32_matrix(reader: &mut impl Read) -> Result<(Vec<f32>, usize)> {
+    let header = NpyHeader::read(reader)?;
+    let [rows, columns] = f32_matrix_shape(&header)?;
+    let mut bytes = vec![0; matrix_len(rows, columns, size_of::<f32>())?];
+    reader.read_exact(&mut bytes)?;
+    Ok((floats(&bytes).collect(), columns))
+}
+
+fn matrix_shape(header: &NpyHeader) -> Result<[usize; 2]> {
+    match header.shape[..] {
+        [_, 0] => Err(invalid("expected a 2D matrix with columns, got 0")),
+        [rows, columns] => Ok([rows, columns]),
+        _ => Err(invalid(format!("expected a 2D matrix, got {:?}", header.shape))),
+    }
+}
+
+/// Returns the number of bytes of an array of `rows` by `columns` values of `size` bytes, whose
+/// shape comes from the header and may not fit in memory.
+fn matrix_len(rows: usize, columns: usize, size: usize) -> Result<usize> {
+    rows.checked_mul(columns)
+        .and_then(|len| len.checked_mul(size))
+        .ok_or_else(|| invalid(format!("the {rows}x{columns} array is too large")))
+}
+
+fn f32_matrix_shape(header: &NpyHeader) -> Result<[usize; 2]> {
+    if header.descr != "<f4" {
+        return Err(invalid(format!("expected a float32 matrix, got {}", header.descr)));
+    }
+    matrix_shape(header)
+}
+
+fn floats(bytes: &[u8]) -> impl Iterator<Item = f32> + '_ {
+    bytes.chunks_exact(size_of::<f32>()).map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_ids_data(reader: &mut impl Read, descr: &str, len: usize) -> Result<Vec<ItemId>> {
+    macro_rules! read {
+        ($ty:ty) => {{
+            let mut bytes = vec![0; matrix_len(len, 1, size_of::<$ty>())?];
+            reader.read_exact(&mut bytes)?;
+            bytes
+                .chunks_exact(size_of::<$ty>())
+                .map(|bytes| {
+                    let id = <$ty>::from_le_bytes(bytes.try_into().unwrap());
+                    ItemId::try_from(id).map_err(|_| invalid(format!("invalid item id {id}")))
+                })
+                .collect()
+        }};
+    }
+
+    match descr {
+        "<u4" => read!(u32),
+        "<i4" => read!(i32),
+        "<u8" => read!(u64),
+        "<i8" => read!(i64),
+        descr => Err(invalid(format!("unsupported ids type {descr}"))),
+    }
+}
+
+impl<D: Distance> Writer<D> {
+    /// Loads a `float32` matrix stored in an `.npy` file, one row per item, and returns the
+    /// number of rows read.
+    ///
+    /// The ids of the items are read from the optional `ids` `.npy` file, which must be a one
+    /// dimensional integer array with as many values as there are rows. The n-th row is
+    /// inserted with the id `n` when no ids are provided. Rows are streamed by batches and
+    /// written with [`Self::add_items`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// let rows = writer.import_npy(&mut wtxn, "vectors.npy", Some("ids.npy"))?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn import_npy(
+        &self,
+        wtxn: &mut RwTxn,
+        vectors: impl AsRef<Path>,
+        ids: Option<impl AsRef<Path>>,
+    ) -> Result<u64> {
+        let ids = match ids {
+            Some(path) => Some(read_ids(&mut BufReader::new(File::open(path)?))?),
+            None => None,
+        };
+        self.import_npy_rows(wtxn, &mut BufReader::new(File::open(vectors)?), ids)
+    }
+
+    /// Loads the rows of the `float32` matrix of an `.npy` array, see [`Self::import_npy`].
+    pub(crate) fn import_npy_rows(
+        &self,
+        wtxn: &mut RwTxn,
+        reader: &mut impl Read,
+        ids: Option<Vec<ItemId>>,
+    ) -> Result<u64> {
+        let header = NpyHeader::read(reader)?;
+        let [rows, dimensions] = f32_matrix_shape(&header)?;
+        matrix_len(rows, dimensions, size_of::<f32>())?;
+        if dimensions != self.dimensions() {
+            return Err(Error::InvalidDimensions { expected: self.dimensions(), got: dimensions });
+        }
+
+        let ids = match ids {
+            Some(ids) => {
+                if ids.len() != rows {
+                    let reason = format!("got {} ids for {rows} vectors", ids.len());
+                    return Err(invalid(reason));
+                }
+                ids
+            }
+            None => (0..rows)
+                .map(|row| ItemId::try_from(row).map_err(|_| Error::DatabaseFull))
+                .collect::<Result<_>>()?,
+        };
+
+        let mut bytes = Vec::new();
+        let mut vectors = Vec::new();
+        for ids in ids.chunks(IMPORT_BATCH_SIZE) {
+            bytes.resize(ids.len() * dimensions * size_of::<f32>(), 0);
+            reader.read_exact(&mut bytes)?;
+            vectors.clear();
+            vectors.extend(floats(&bytes));
+            self.add_items(wtxn, ids.iter().copied().zip(vectors.chunks_exact(dimensions)))?;
+        }
+
+        Ok(rows as u64)
+    }
+}
@@ -0,0 +1,291 @@
+//! Load the arrays of the NumPy `.npz` archives, the files written by `numpy.savez` and
+//! `numpy.savez_compressed`.
+//!
+//! An archive is a zip file holding one `.npy` file per array, its entries are either stored
+//! or compressed with deflate. The arrays are read like the `.npy` files of the [`npy`] module,
+//! with the same restrictions, and are named without their `.npy` extension: `arr_0`, `arr_1`…
+//! or the names of the keyword arguments given to numpy.
+//!
+//! ```no_run
+//! use hannoy::npz::NpzArchive;
+//!
+//! let mut archive = NpzArchive::open("benchmark.npz")?;
+//! let (queries, dimensions) = archive.read_f32_matrix("test")?;
+//! let (neighbors, k) = archive.read_id_matrix("neighbors")?;
+//! # Ok::<(), hannoy::Error>(())
+//! ```
+//!
+//! [`npy`]: crate::npy
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use flate2::read::DeflateDecoder;
+use heed::RwTxn;
+
+use crate::distance::Distance;
+use crate::{npy, Error, ItemId, Result, Writer};
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x06064b50;
+const ZIP64_LOCATOR: u32 = 0x07064b50;
+
+/// The sizes of the fixed parts of the records.
+const LOCAL_HEADER_SIZE: usize = 30;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE: usize = 56;
+const ZIP64_LOCATOR_SIZE: usize = 20;
+
+/// The id of the extra field holding the 64 bits sizes and offsets.
+const ZIP64_EXTRA: u16 = 0x0001;
+
+/// The compression methods of the entries.
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// A `.npz` archive whose central directory lists the arrays.
+#[derive(Debug)]
+pub struct NpzArchive {
+    file: BufReader<File>,
+    arrays: Vec<Array>,
+}
+
+/// The entry of an array in the archive.
+#[derive(Debug)]
+struct Array {
+    /// The name of the array, without the `.npy` extension.
+    name: String,
+    method: u16,
+    compressed_size: u64,
+    /// The offset of the local header of the entry, followed by its data.
+    offset: u64,
+}
+
+impl NpzArchive {
+    /// Opens a `.npz` archive and lists its arrays.
+    pub fn open(path: impl AsRef<Path>) -> Result<NpzArchive> {
+        let mut file = BufReader::new(File::open(path)?);
+        let len = file.get_ref().metadata()?.len();
+
+        // the end of central directory record is followed by a comment of at most 64KiB
+        let tail_len = len.min((END_OF_CENTRAL_DIRECTORY_SIZE + u16::MAX as usize) as u64);
+        let tail_start = len - tail_len;
+        let mut tail = vec![0; tail_len as usize];
+        file.seek(SeekFrom::Start(tail_start))?;
+        file.read_exact(&mut tail)?;
+        let signature = END_OF_CENTRAL_DIRECTORY.to_le_bytes();
+        let position = tail
+            .windows(END_OF_CENTRAL_DIRECTORY_SIZE)
+            .rposition(|record| record[..4] == signature)
+            .ok_or_else(|| invalid("missing the end of central directory"))?;
+
+        let mut record = Parser::new(&tail[position + 4..]);
+        record.take(6)?;
+        let mut entries = record.u16()? as u64;
+        let mut size = record.u32()? as u64;
+        let mut offset = record.u32()? as u64;
+        // the archives written by numpy may be zip64 ones, whose values are in another record
+        if entries == u16::MAX as u64 || size == u32::MAX as u64 || offset == u32::MAX as u64 {
+            let locator = (tail_start + position as u64)
+                .checked_sub(ZIP64_LOCATOR_SIZE as u64)
+                .ok_or_else(|| invalid("missing the zip64 end of central directory locator"))?;
+            let locator = read_at(&mut file, locator, ZIP64_LOCATOR_SIZE)?;
+            let mut locator = Parser::new(&locator);
+            if locator.u32()? != ZIP64_LOCATOR {
+                return Err(invalid("missing the zip64 end of central directory locator"));
+            }
+            locator.take(4)?;
+            let record = locator.u64()?;
+            let record = read_at(&mut file, record, ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE)?;
+            let mut record = Parser::new(&record);
+            if record.u32()? != ZIP64_END_OF_CENTRAL_DIRECTORY {
+                return Err(invalid("missing the zip64 end of central directory"));
+            }
+            record.take(28)?;
+            entries = record.u64()?;
+            size = record.u64()?;
+            offset = record.u64()?;
+        }
+        if offset.checked_add(size).is_none_or(|end| end > len) {
+            return Err(invalid("the central directory is out of the file"));
+        }
+
+        let directory = read_at(&mut file, offset, size as usize)?;
+        let mut directory = Parser::new(&directory);
+        let mut arrays = Vec::new();
+        for _ in 0..entries {
+            if directory.u32()? != CENTRAL_HEADER {
+                return Err(invalid("a central directory header is corrupted"));
+            }
+            directory.take(4)?;
+            let flags = directory.u16()?;
+            let method = directory.u16()?;
+            directory.take(8)?;
+            let mut compressed_size = directory.u32()? as u64;
+            let size = directory.u32()?;
+            let name_len = directory.u16()? as usize;
+            let extra_len = directory.u16()? as usize;
+            let comment_len = directory.u16()? as usize;
+            directory.take(8)?;
+            let mut offset = directory.u32()? as u64;
+            let name = directory.take(name_len)?;
+            let mut extra = Parser::new(directory.take(extra_len)?);
+            directory.take(comment_len)?;
+
+            // the zip64 field only holds the values that didn't fit, in this order
+            while extra.remaining() >= 4 {
+                let id = extra.u16()?;
+                let len = extra.u16()? as usize;
+                let mut field = Parser::new(extra.take(len)?);
+                if id == ZIP64_EXTRA {
+                    if size == u32::MAX {
+                        field.u64()?;
+                    }
+                    if compressed_size == u32::MAX as u64 {
+                        compressed_size = field.u64()?;
+                    }
+                    if offset == u32::MAX as u64 {
+                        offset = field.u64()?;
+                    }
+                }
+            }
+
+            let name = String::from_utf8_lossy(name);
+            let Some(name) = name.strip_suffix(".npy") else { continue };
+            if flags & 0x01 != 0 {
+                return Err(invalid(format!("the array `{name}` is encrypted")));
+            }
+            arrays.push(Array { name: name.to_string(), method, compressed_size, offset });
+        }
+
+        Ok(NpzArchive { file, arrays })
+    }
+
+    /// Returns the names of the arrays, in the order of the archive.
+    pub fn array_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.arrays.iter().map(|array| array.name.as_str())
+    }
+
+    /// Reads a two dimensional `float32` array, such as the `train` or `test` vectors, and
+    /// returns the values row by row with the number of columns.
+    pub fn read_f32_matrix(&mut self, name: &str) -> Result<(Vec<f32>, usize)> {
+        npy::read_f32_matrix(&mut self.array(name)?)
+    }
+
+    /// Reads a two dimensional integer array as item ids, such as the `neighbors` of the
+    /// queries, and returns the values row by row with the number of columns.
+    pub fn read_id_matrix(&mut self, name: &str) -> Result<(Vec<ItemId>, usize)> {
+        npy::read_id_matrix(&mut self.array(name)?)
+    }
+
+    /// Reads a one dimensional integer array as item ids.
+    pub fn read_ids(&mut self, name: &str) -> Result<Vec<ItemId>> {
+        npy::read_ids(&mut self.array(name)?)
+    }
+
+    /// Returns the uncompressed `.npy` file of the array named `name`.
+    fn array(&mut self, name: &str) -> Result<Box<dyn Read + '_>> {
+        let array = self
+            .arrays
+            .iter()
+            .find(|array| array.name == name)
+            .ok_or_else(|| invalid(format!("missing array `{name}`")))?;
+
+        let header = read_at(&mut self.file, array.offset, LOCAL_HEADER_SIZE)?;
+        let mut header = Parser::new(&header);
+        if header.u32()? != LOCAL_HEADER {
+            return Err(invalid(format!("the local header of `{name}` is corrupted")));
+        }
+        header.take(22)?;
+        // the local header may have another extra field than the central directory one
+        let skipped = header.u16()? as i64 + header.u16()? as i64;
+        self.file.seek_relative(skipped)?;
+
+        let data = (&mut self.file).take(array.compressed_size);
+        match array.method {
+            STORED => Ok(Box::new(data)),
+            DEFLATED => Ok(Box::new(DeflateDecoder::new(data))),
+            method => Err(invalid(format!("unsupported compression method {method} of `{name}`"))),
+        }
+    }
+}
+
+/// Reads `len` bytes of the file at `offset`.
+fn read_at(file: &mut BufReader<File>, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0; len];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads the little-endian fields of the records of the archive.
+struct Parser<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Parser<'a> {
+    fn new(bytes: &'a [u8]) -> Parser<'a> {
+        Parser { bytes }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some((taken, rest)) = self.bytes.split_at_checked(len) else {
+            return Err(invalid("a record is truncated"));
+        };
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::InvalidNpz { reason: reason.into() }
+}
+
+impl<D: Distance> Writer<D> {
+    /// Loads the `float32` matrix of the `vectors` array of an `.npz` archive, one row per item,
+    /// and returns the number of rows read.
+    ///
+    /// The ids of the items are read from the optional `ids` array of the archive, which must
+    /// be a one dimensional integer array with as many values as there are rows. The n-th row
+    /// is inserted with the id `n` when no ids are provided. Rows are streamed by batches and
+    /// written with [`Self::add_items`], like the ones of [`Self::import_npy`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// let rows = writer.import_npz(&mut wtxn, "embeddings.npz", "vectors", Some("ids"))?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn import_npz(
+        &self,
+        wtxn: &mut RwTxn,
+        path: impl AsRef<Path>,
+        vectors: &str,
+        ids: Option<&str>,
+    ) -> Result<u64> {
+        let mut archive = NpzArchive::open(path)?;
+        let ids = ids.map(|ids| archive.read_ids(ids)).transpose()?;
+        let mut vectors = archive.array(vectors)?;
+        self.import_npy_rows(wtxn, &mut vectors, ids)
+    }
+}
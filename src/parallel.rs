@@ -5,6 +5,8 @@ use std::marker;
 use hashbrown::HashMap;
 use heed::types::Bytes;
 use heed::{BytesDecode, RoTxn};
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use roaring::RoaringBitmap;
 use rustc_hash::FxBuildHasher;
 use tracing::debug;
@@ -22,8 +24,7 @@ use crate::{Database, Distance, ItemId, LayerId};
 /// in the mmapped file and the transaction is kept here and therefore
 /// no longer touches the database.
 pub struct ImmutableItems<'t, D> {
-    items: HashMap<ItemId, *const u8, FxBuildHasher>,
-    constant_length: Option<usize>,
+    items: HashMap<ItemId, (*const u8, usize), FxBuildHasher>,
     _marker: marker::PhantomData<(&'t (), D)>,
 }
 
@@ -36,6 +37,10 @@ impl<'t, D: Distance> ImmutableItems<'t, D> {
     /// and keeping the transaction making the pointers valid.
     /// Do not take more items than memory allows.
     /// Remove from the list of candidates all the items that were selected and return them.
+    #[cfg_attr(
+        feature = "tracing-spans",
+        tracing::instrument(name = "fetch_item_pointers", level = "debug", skip_all)
+    )]
     pub fn new<P>(
         rtxn: &'t RoTxn,
         database: Database<D>,
@@ -50,7 +55,6 @@ impl<'t, D: Distance> ImmutableItems<'t, D> {
 
         let mut map =
             HashMap::with_capacity_and_hasher(database.len(rtxn)? as usize, FxBuildHasher);
-        let mut constant_length = None;
 
         let cursor = database
             .remap_types::<PrefixCodec, Bytes>()
@@ -59,28 +63,23 @@ impl<'t, D: Distance> ImmutableItems<'t, D> {
 
         for res in cursor {
             let (item_id, bytes) = res?;
-            assert_eq!(*constant_length.get_or_insert(bytes.len()), bytes.len());
-            let ptr = bytes.as_ptr();
-            map.insert(item_id.node.item, ptr);
+            // sparse vectors don't have the same length
+            map.insert(item_id.node.item, (bytes.as_ptr(), bytes.len()));
         }
 
-        Ok(ImmutableItems { items: map, constant_length, _marker: marker::PhantomData })
+        Ok(ImmutableItems { items: map, _marker: marker::PhantomData })
     }
 
     /// Returns the items identified by the given ID.
     pub fn get(&self, item_id: ItemId) -> heed::Result<Option<Item<'t, D>>> {
-        let len = match self.constant_length {
-            Some(len) => len,
-            None => return Ok(None),
-        };
-        let ptr = match self.items.get(&item_id) {
-            Some(ptr) => *ptr,
+        let (ptr, len) = match self.items.get(&item_id) {
+            Some(entry) => *entry,
             None => return Ok(None),
         };
 
         // safety:
         // - ptr: The pointer comes from LMDB. Since the database cannot be written to, it is still valid.
-        // - len: All the items share the same dimensions and are the same size
+        // - len: The length of the entry the pointer was read with
         let bytes = unsafe { slice::from_raw_parts(ptr, len) };
         NodeCodec::bytes_decode(bytes).map_err(heed::Error::Decoding).map(|node| node.item())
     }
@@ -100,6 +99,10 @@ pub struct ImmutableLinks<'t, D> {
 impl<'t, D: Distance> ImmutableLinks<'t, D> {
     /// Creates the structure by fetching all the root pointers
     /// and keeping the transaction making the pointers valid.
+    #[cfg_attr(
+        feature = "tracing-spans",
+        tracing::instrument(name = "fetch_links_pointers", level = "debug", skip_all)
+    )]
     pub fn new<P>(
         rtxn: &'t RoTxn,
         database: Database<D>,
@@ -184,3 +187,44 @@ impl<'t, D: Distance> ImmutableLinks<'t, D> {
 }
 
 unsafe impl<D> Sync for ImmutableLinks<'_, D> {}
+
+/// Calls `f` with every item, in parallel on the global rayon thread pool or one after the
+/// other on the calling thread when the `rayon` feature is disabled, and stops at the first
+/// error.
+pub(crate) fn try_for_each<T, E, F>(items: Vec<T>, f: F) -> Result<(), E>
+where
+    T: Send,
+    E: Send,
+    F: Fn(T) -> Result<(), E> + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    return items.into_par_iter().try_for_each(f);
+    #[cfg(not(feature = "rayon"))]
+    return items.into_iter().try_for_each(f);
+}
+
+/// Same as [`try_for_each`] with borrowed items.
+pub(crate) fn try_for_each_ref<T, E, F>(items: &[T], f: F) -> Result<(), E>
+where
+    T: Sync,
+    E: Send,
+    F: Fn(&T) -> Result<(), E> + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    return items.par_iter().try_for_each(f);
+    #[cfg(not(feature = "rayon"))]
+    return items.iter().try_for_each(f);
+}
+
+/// Returns `f` of every item in their order, computed like in [`try_for_each`].
+pub(crate) fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    return items.par_iter().map(f).collect();
+    #[cfg(not(feature = "rayon"))]
+    return items.iter().map(f).collect();
+}
@@ -0,0 +1,61 @@
+//! Bulk import vectors from Parquet files.
+use std::fs::File;
+use std::path::Path;
+
+use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use ::parquet::arrow::ProjectionMask;
+use ::parquet::errors::ParquetError;
+use heed::RwTxn;
+use tracing::debug;
+
+use crate::distance::Distance;
+use crate::{Error, Result, Writer};
+
+/// The number of rows decoded and written at once.
+const IMPORT_BATCH_SIZE: usize = 8192;
+
+impl<D: Distance> Writer<D> {
+    /// Streams the rows of a Parquet file into the database and returns the number of rows read.
+    ///
+    /// Only the `id_column` and `vector_column` are decoded, by batches of a few thousand rows
+    /// that are written with [`Self::add_record_batch`], so the file never has to fit in memory.
+    /// The columns must follow the same rules as [`Self::add_record_batch`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// let rows = writer.import_parquet(&mut wtxn, "embeddings.parquet", "id", "embedding")?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn import_parquet(
+        &self,
+        wtxn: &mut RwTxn,
+        path: impl AsRef<Path>,
+        id_column: &str,
+        vector_column: &str,
+    ) -> Result<u64> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+
+        let fields = builder.parquet_schema().root_schema().get_fields();
+        let root = |name: &str| {
+            fields.iter().position(|field| field.name() == name).ok_or_else(|| {
+                Error::InvalidColumn { column: name.to_string(), reason: "missing column".into() }
+            })
+        };
+        let roots = [root(id_column)?, root(vector_column)?];
+        let mask = ProjectionMask::roots(builder.parquet_schema(), roots);
+        let reader = builder.with_projection(mask).with_batch_size(IMPORT_BATCH_SIZE).build()?;
+
+        let mut rows = 0;
+        for batch in reader {
+            let batch = batch.map_err(ParquetError::from)?;
+            self.add_record_batch(wtxn, &batch, id_column, vector_column)?;
+            rows += batch.num_rows() as u64;
+            debug!("imported {rows} rows from parquet");
+        }
+
+        Ok(rows)
+    }
+}
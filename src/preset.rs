@@ -0,0 +1,165 @@
+use heed::types::DecodeIgnore;
+use heed::RoTxn;
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::internals::KeyCodec;
+use crate::reader::get_item;
+use crate::{Database, Error, Prefix, PrefixCodec, Result};
+
+/// The number of items sampled to estimate the intrinsic dimension of a dataset.
+const INTRINSIC_DIMENSION_SAMPLE: u64 = 1000;
+
+/// The number of neighbours of every sampled item the intrinsic dimension is estimated from.
+const INTRINSIC_DIMENSION_NEIGHBOURS: usize = 10;
+
+/// The numbers of links per node the presets pick from, they are the ones
+/// [`HannoyBuilder::build_with_preset`](crate::HannoyBuilder::build_with_preset) can build.
+const M_LADDER: [usize; 6] = [8, 12, 16, 24, 32, 48];
+
+/// A trade-off between the build time and the recall, to pick the build parameters from the
+/// statistics of a dataset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuildPreset {
+    /// Good recall for a reasonable build time, the usual choice.
+    #[default]
+    Balanced,
+    /// Sparser links and a shorter build queue, for indexes rebuilt often.
+    FastBuild,
+    /// Denser links and a longer build queue, for indexes where the recall matters most.
+    HighRecall,
+}
+
+/// The `M` and `ef_construction` picked by a [`BuildPreset`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildParams {
+    /// The maximum number of links per node in the layers above 0, there are twice as many in
+    /// layer 0.
+    pub m: usize,
+    /// The size of the build queue, see
+    /// [`HannoyBuilder::ef_construction`](crate::HannoyBuilder::ef_construction).
+    pub ef_construction: usize,
+}
+
+/// The statistics of a dataset the [`BuildPreset`]s are chosen from, see
+/// [`Writer::dataset_stats`](crate::Writer::dataset_stats).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DatasetStats {
+    /// The number of items of the index, built or not.
+    pub items: u64,
+    /// The number of dimensions of the vectors.
+    pub dimensions: usize,
+    /// The estimated number of dimensions the vectors actually spread over, which is what makes
+    /// a graph hard to navigate rather than the number of dimensions of the vectors.
+    pub intrinsic_dimension: f64,
+}
+
+impl BuildPreset {
+    /// Returns the build parameters for a dataset.
+    ///
+    /// The number of links grows with the intrinsic dimension, which is what makes the
+    /// neighbourhoods hard to connect, and is capped for small datasets that are easy to search
+    /// anyway. The build queue grows with the number of items.
+    pub fn params(self, stats: &DatasetStats) -> BuildParams {
+        let rung: usize = match stats.intrinsic_dimension {
+            d if d < 8.0 => 0,
+            d if d < 16.0 => 1,
+            d if d < 32.0 => 2,
+            d if d < 64.0 => 3,
+            _ => 4,
+        };
+        let rung = match self {
+            BuildPreset::Balanced => rung,
+            BuildPreset::FastBuild => rung.saturating_sub(1),
+            BuildPreset::HighRecall => rung + 1,
+        };
+        let rung = if stats.items < 10_000 { rung.min(2) } else { rung };
+        let m = M_LADDER[rung.min(M_LADDER.len() - 1)];
+
+        let ef_construction = match self {
+            BuildPreset::Balanced if stats.items >= 1_000_000 => 200,
+            BuildPreset::Balanced => 100,
+            BuildPreset::FastBuild => (2 * m).max(64),
+            BuildPreset::HighRecall => 400,
+        };
+
+        BuildParams { m, ef_construction }
+    }
+}
+
+/// Returns the statistics of the items of an index, see [`crate::Writer::dataset_stats`].
+pub(crate) fn dataset_stats<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+) -> Result<DatasetStats> {
+    let ids = item_ids(rtxn, database, index)?;
+    let items = ids.len();
+    let step = items.div_ceil(INTRINSIC_DIMENSION_SAMPLE).max(1);
+    let mut sample = Vec::new();
+    for rank in (0..items).step_by(step as usize) {
+        let id = ids.select(rank as u32).expect("the rank is lower than the length");
+        if let Some(item) = get_item(database, index, rtxn, id)? {
+            let mut vector = item.vector.to_vec();
+            vector.truncate(dimensions);
+            sample.push(vector);
+        }
+    }
+
+    let intrinsic_dimension = intrinsic_dimension(&sample).unwrap_or(dimensions as f64);
+    Ok(DatasetStats { items, dimensions, intrinsic_dimension })
+}
+
+/// Returns the ids of the items of an index, built or not.
+pub(crate) fn item_ids<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<RoaringBitmap> {
+    let prefix = Prefix::item(index);
+    let mut ids = RoaringBitmap::new();
+    for result in database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter(rtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<KeyCodec>()
+    {
+        let (key, _) = result?;
+        ids.insert(key.node.item);
+    }
+    Ok(ids)
+}
+
+/// MacKay and Ghahramani's average of the Levina-Bickel estimates of every vector, `None` if
+/// there are too few distinct vectors.
+fn intrinsic_dimension(vectors: &[Vec<f32>]) -> Option<f64> {
+    let k = INTRINSIC_DIMENSION_NEIGHBOURS.min(vectors.len().saturating_sub(1));
+    if k < 2 {
+        return None;
+    }
+
+    let mut inverses = Vec::with_capacity(vectors.len());
+    let mut distances = Vec::with_capacity(vectors.len());
+    for vector in vectors {
+        distances.clear();
+        distances.extend(vectors.iter().filter_map(|other| {
+            let squared: f32 = vector.iter().zip(other).map(|(a, b)| (a - b) * (a - b)).sum();
+            // duplicates don't tell anything about the dimension
+            (squared > 0.0).then(|| (squared as f64).sqrt())
+        }));
+        if distances.len() < k {
+            continue;
+        }
+        distances.select_nth_unstable_by(k - 1, f64::total_cmp);
+        let nearest = &mut distances[..k];
+        nearest.sort_unstable_by(f64::total_cmp);
+
+        let furthest = nearest[k - 1];
+        let sum: f64 = nearest[..k - 1].iter().map(|d| (furthest / d).ln()).sum();
+        inverses.push(sum / (k - 1) as f64);
+    }
+
+    let mean = inverses.iter().sum::<f64>() / inverses.len() as f64;
+    (mean > 0.0).then(|| 1.0 / mean)
+}
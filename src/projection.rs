@@ -0,0 +1,324 @@
+use std::borrow::Cow;
+use std::f32::consts::TAU;
+
+use heed::types::Bytes;
+use heed::{BoxedError, RoTxn, RwTxn};
+use rand::Rng;
+
+use crate::distance::Distance;
+use crate::parallel;
+use crate::{Database, Error, Key, Result};
+
+/// The number of passes over the samples refining the principal components, see
+/// [`Projection::pca`]. A handful is enough for the components to converge on embeddings whose
+/// variance is concentrated on the first ones.
+const PCA_ITERATIONS: usize = 6;
+
+/// A linear reduction of the dimensions of the vectors, applied to the vectors added to an
+/// index and to its queries, see [`Writer::set_projection`](crate::Writer::set_projection).
+///
+/// The projection subtracts a mean from a vector and multiplies it by a matrix of orthonormal
+/// rows, it preserves the distances between the vectors as much as the retained dimensions
+/// allow. Fit it with [`Self::pca`] on a sample of the vectors, or draw a data-independent
+/// [`Self::random_orthogonal`] one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+    input: usize,
+    output: usize,
+    /// The vector subtracted from the vectors before they are projected.
+    mean: Vec<f32>,
+    /// The `output` rows of `input` values the vectors are multiplied by.
+    components: Vec<f32>,
+}
+
+impl Projection {
+    /// Fits a principal component analysis on `samples`, the projection keeps the `output`
+    /// directions the samples vary the most along.
+    ///
+    /// The components are found with a randomized subspace iteration, which only goes through
+    /// the samples a few times, in parallel on the global rayon thread pool unless the `rayon`
+    /// feature is disabled. A few thousand
+    /// samples drawn from the vectors of the index are enough, e.g. with
+    /// [`Reader::sample`](crate::Reader::sample).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Projection, Writer, distances::Cosine};
+    /// # let (writer, mut wtxn, samples): (Writer<Cosine>, heed::RwTxn, Vec<Vec<f32>>) = todo!();
+    /// let projection = Projection::pca(&samples, 256, &mut rand::thread_rng())?;
+    /// writer.set_projection(&mut wtxn, &projection)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn pca<R: Rng>(
+        samples: &[impl AsRef<[f32]> + Sync],
+        output: usize,
+        rng: &mut R,
+    ) -> Result<Projection> {
+        let Some(input) = samples.first().map(|sample| sample.as_ref().len()) else {
+            return Err(invalid("there are no samples to fit the projection on".to_string()));
+        };
+        check_dimensions(input, output)?;
+        if let Some(sample) = samples.iter().find(|sample| sample.as_ref().len() != input) {
+            let got = sample.as_ref().len();
+            return Err(Error::InvalidDimensions { expected: input, got });
+        }
+
+        let mut mean = vec![0.0; input];
+        for sample in samples {
+            mean.iter_mut().zip(sample.as_ref()).for_each(|(m, x)| *m += x);
+        }
+        mean.iter_mut().for_each(|m| *m /= samples.len() as f32);
+
+        let mut components = gaussian(rng, input * output);
+        orthonormalize(&mut components, input, rng);
+        for _ in 0..PCA_ITERATIONS {
+            // the coordinates of the centered samples in the current subspace...
+            let coordinates: Vec<Vec<f32>> = parallel::map(samples, |sample| {
+                components.chunks_exact(input).map(|row| centered_dot(sample, &mean, row)).collect()
+            });
+            // ...multiplied back by the samples grow the directions of largest variance
+            let rows: Vec<usize> = (0..output).collect();
+            components = parallel::map(&rows, |&j| {
+                let mut row = vec![0.0; input];
+                for (sample, coordinates) in samples.iter().zip(&coordinates) {
+                    let c = coordinates[j];
+                    row.iter_mut()
+                        .zip(sample.as_ref().iter().zip(&mean))
+                        .for_each(|(r, (x, m))| *r += c * (x - m));
+                }
+                row
+            })
+            .concat();
+            orthonormalize(&mut components, input, rng);
+        }
+
+        Ok(Projection { input, output, mean, components })
+    }
+
+    /// Draws a random projection on `output` orthonormal directions, which preserves the
+    /// distances between the vectors up to a small distortion whatever they are, without
+    /// looking at them.
+    pub fn random_orthogonal<R: Rng>(
+        input: usize,
+        output: usize,
+        rng: &mut R,
+    ) -> Result<Projection> {
+        check_dimensions(input, output)?;
+        let mut components = gaussian(rng, input * output);
+        orthonormalize(&mut components, input, rng);
+        Ok(Projection { input, output, mean: vec![0.0; input], components })
+    }
+
+    /// Returns the number of dimensions of the vectors the projection reduces.
+    pub fn input_dimensions(&self) -> usize {
+        self.input
+    }
+
+    /// Returns the number of dimensions of the projected vectors.
+    pub fn output_dimensions(&self) -> usize {
+        self.output
+    }
+
+    /// Projects a vector of [`Self::input_dimensions`] values.
+    pub fn project(&self, vector: &[f32]) -> Result<Vec<f32>> {
+        if vector.len() != self.input {
+            return Err(Error::InvalidDimensions { expected: self.input, got: vector.len() });
+        }
+        Ok(self
+            .components
+            .chunks_exact(self.input)
+            .map(|row| centered_dot(vector, &self.mean, row))
+            .collect())
+    }
+
+    /// Encodes the projection as its dimensions followed by its mean and its components.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + 4 * (self.mean.len() + self.components.len()));
+        bytes.extend_from_slice(&(self.input as u32).to_ne_bytes());
+        bytes.extend_from_slice(&(self.output as u32).to_ne_bytes());
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.mean));
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.components));
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Projection, BoxedError> {
+        let ProjectionView { input, output, mean, components } = ProjectionView::from_bytes(bytes)?;
+        let mean = bytemuck::pod_collect_to_vec(mean);
+        let components = bytemuck::pod_collect_to_vec(components);
+        Ok(Projection { input, output, mean, components })
+    }
+}
+
+/// A projection read in place from the bytes of an index, without copying its matrix, see
+/// [`get_projection_view`].
+pub(crate) struct ProjectionView<'t> {
+    input: usize,
+    output: usize,
+    /// The native-endian values of the mean, in bytes as LMDB doesn't align them.
+    mean: &'t [u8],
+    /// The native-endian values of the components, in bytes like the mean.
+    components: &'t [u8],
+}
+
+impl<'t> ProjectionView<'t> {
+    pub(crate) fn from_bytes(bytes: &'t [u8]) -> Result<ProjectionView<'t>, BoxedError> {
+        let invalid = || format!("Could not decode a projection of {} bytes.", bytes.len());
+        let (dimensions, values) = bytes.split_at_checked(8).ok_or_else(invalid)?;
+        let dimensions: Vec<u32> = bytemuck::pod_collect_to_vec(dimensions);
+        let (input, output) = (dimensions[0] as usize, dimensions[1] as usize);
+        if values.len() != size_of::<f32>() * input * (1 + output) {
+            return Err(invalid().into());
+        }
+        let (mean, components) = values.split_at(size_of::<f32>() * input);
+        Ok(ProjectionView { input, output, mean, components })
+    }
+
+    /// Projects a vector like [`Projection::project`].
+    pub(crate) fn project(&self, vector: &[f32]) -> Result<Vec<f32>> {
+        if vector.len() != self.input {
+            return Err(Error::InvalidDimensions { expected: self.input, got: vector.len() });
+        }
+        match (bytemuck::try_cast_slice(self.mean), bytemuck::try_cast_slice(self.components)) {
+            (Ok(mean), Ok(components)) => Ok(components
+                .chunks_exact(self.input)
+                .map(|row| centered_dot(vector, mean, row))
+                .collect()),
+            // the unaligned rows are decoded one value at a time, only the mean is copied
+            _ => {
+                let mean: Vec<f32> = bytemuck::pod_collect_to_vec(self.mean);
+                let rows = self.components.chunks_exact(size_of::<f32>() * self.input);
+                Ok(rows
+                    .map(|row| {
+                        let row = row.chunks_exact(size_of::<f32>());
+                        let row = row.map(|value| f32::from_ne_bytes(value.try_into().unwrap()));
+                        vector.iter().zip(&mean).zip(row).map(|((x, m), r)| (x - m) * r).sum()
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Returns the projection of an index, see
+/// [`Writer::set_projection`](crate::Writer::set_projection).
+pub(crate) fn get_projection<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<Projection>> {
+    let key = Key::projection(index);
+    let bytes =
+        database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))?;
+    bytes
+        .map(Projection::from_bytes)
+        .transpose()
+        .map_err(|error| Error::on_key("get", key)(heed::Error::Decoding(error)))
+}
+
+/// Returns the projection of an index read in place, see [`ProjectionView`].
+pub(crate) fn get_projection_view<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<ProjectionView<'t>>> {
+    let key = Key::projection(index);
+    let bytes =
+        database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))?;
+    bytes
+        .map(ProjectionView::from_bytes)
+        .transpose()
+        .map_err(|error| Error::on_key("get", key)(heed::Error::Decoding(error)))
+}
+
+/// Stores the projection of an index, read back by [`get_projection`].
+pub(crate) fn put_projection<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    projection: &Projection,
+) -> Result<()> {
+    let key = Key::projection(index);
+    database
+        .remap_data_type::<Bytes>()
+        .put(wtxn, &key, &projection.to_bytes())
+        .map_err(Error::on_key("put", key))
+}
+
+/// Projects a vector if there is a `projection`, it must then have as many dimensions as the
+/// projection expects.
+pub(crate) fn apply<'v>(
+    projection: Option<&Projection>,
+    vector: &'v [f32],
+) -> Result<Cow<'v, [f32]>> {
+    match projection {
+        Some(projection) => projection.project(vector).map(Cow::Owned),
+        None => Ok(Cow::Borrowed(vector)),
+    }
+}
+
+/// Projects a vector like [`apply`] with a projection read in place.
+pub(crate) fn apply_view<'v>(
+    projection: Option<&ProjectionView>,
+    vector: &'v [f32],
+) -> Result<Cow<'v, [f32]>> {
+    match projection {
+        Some(projection) => projection.project(vector).map(Cow::Owned),
+        None => Ok(Cow::Borrowed(vector)),
+    }
+}
+
+fn check_dimensions(input: usize, output: usize) -> Result<()> {
+    if output == 0 || output > input {
+        let reason = format!("can't project {input} dimensions on {output}");
+        return Err(invalid(reason));
+    }
+    Ok(())
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidProjection { reason }
+}
+
+/// Returns the dot product of `vector - mean` and `row`.
+fn centered_dot(vector: impl AsRef<[f32]>, mean: &[f32], row: &[f32]) -> f32 {
+    vector.as_ref().iter().zip(mean).zip(row).map(|((x, m), r)| (x - m) * r).sum()
+}
+
+/// Returns `len` values drawn from a standard normal distribution with the Box-Muller
+/// transform.
+fn gaussian<R: Rng>(rng: &mut R, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|_| {
+            let (u, v): (f32, f32) = (1.0 - rng.gen::<f32>(), rng.gen());
+            (-2.0 * u.ln()).sqrt() * (TAU * v).cos()
+        })
+        .collect()
+}
+
+/// Makes the rows of `width` values of the `matrix` orthonormal with the modified Gram-Schmidt
+/// process, run twice to not lose the orthogonality to rounding errors. The rows that depend on
+/// the previous ones are redrawn at random.
+fn orthonormalize<R: Rng>(matrix: &mut [f32], width: usize, rng: &mut R) {
+    let norm = |row: &[f32]| row.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let rows = matrix.len() / width;
+    let mut i = 0;
+    while i < rows {
+        let (previous, rest) = matrix.split_at_mut(i * width);
+        let row = &mut rest[..width];
+        let before = norm(row);
+        for _ in 0..2 {
+            for other in previous.chunks_exact(width) {
+                let dot: f32 = row.iter().zip(other).map(|(a, b)| a * b).sum();
+                row.iter_mut().zip(other).for_each(|(a, b)| *a -= dot * b);
+            }
+        }
+        let after = norm(row);
+        if after > 1e-4 * before && after > 0.0 {
+            row.iter_mut().for_each(|x| *x /= after);
+            i += 1;
+        } else {
+            row.copy_from_slice(&gaussian(rng, width));
+        }
+    }
+}
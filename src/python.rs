@@ -1,9 +1,10 @@
 //! Python bindings for hannoy.
 use heed::{RoTxn, RwTxn, WithoutTls};
+use numpy::{PyReadonlyArray2, PyUntypedArrayMethods};
 use once_cell::sync::OnceCell;
 use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
 use pyo3::{
-    exceptions::{PyIOError, PyRuntimeError},
+    exceptions::{PyIOError, PyRuntimeError, PyValueError},
     prelude::*,
     types::PyType,
 };
@@ -39,6 +40,22 @@ pub(super) enum PyDistance {
     BqManhattan,
     #[pyo3(name = "HAMMING")]
     Hamming,
+    #[pyo3(name = "CHEBYSHEV")]
+    Chebyshev,
+    #[pyo3(name = "CANBERRA")]
+    Canberra,
+    #[pyo3(name = "BRAY_CURTIS")]
+    BrayCurtis,
+    #[pyo3(name = "HELLINGER")]
+    Hellinger,
+    #[pyo3(name = "U8_EUCLIDEAN")]
+    U8Euclidean,
+    #[pyo3(name = "U8_MANHATTAN")]
+    U8Manhattan,
+    #[pyo3(name = "EARTH_MOVERS")]
+    EarthMovers,
+    #[pyo3(name = "POINCARE")]
+    Poincare,
 }
 
 #[gen_stub_pymethods]
@@ -53,6 +70,14 @@ impl PyDistance {
             PyDistance::BqEuclidean => "bq_euclidean".into(),
             PyDistance::BqManhattan => "bq_manhattan".into(),
             PyDistance::Hamming => "hamming".into(),
+            PyDistance::Chebyshev => "chebyshev".into(),
+            PyDistance::Canberra => "canberra".into(),
+            PyDistance::BrayCurtis => "bray_curtis".into(),
+            PyDistance::Hellinger => "hellinger".into(),
+            PyDistance::U8Euclidean => "u8_euclidean".into(),
+            PyDistance::U8Manhattan => "u8_manhattan".into(),
+            PyDistance::EarthMovers => "earth_movers".into(),
+            PyDistance::Poincare => "poincare".into(),
         }
     }
 }
@@ -65,6 +90,14 @@ enum DynDatabase {
     BqEuclidean(Database<distance::BinaryQuantizedEuclidean>),
     BqManhattan(Database<distance::BinaryQuantizedManhattan>),
     Hamming(Database<distance::Hamming>),
+    Chebyshev(Database<distance::Chebyshev>),
+    Canberra(Database<distance::Canberra>),
+    BrayCurtis(Database<distance::BrayCurtis>),
+    Hellinger(Database<distance::Hellinger>),
+    U8Euclidean(Database<distance::U8Euclidean>),
+    U8Manhattan(Database<distance::U8Manhattan>),
+    EarthMovers(Database<distance::EarthMovers>),
+    Poincare(Database<distance::Poincare>),
 }
 impl DynDatabase {
     pub fn new(
@@ -85,6 +118,20 @@ impl DynDatabase {
                 Ok(DynDatabase::BqManhattan(env.create_database(wtxn, name)?))
             }
             PyDistance::Hamming => Ok(DynDatabase::Hamming(env.create_database(wtxn, name)?)),
+            PyDistance::Chebyshev => Ok(DynDatabase::Chebyshev(env.create_database(wtxn, name)?)),
+            PyDistance::Canberra => Ok(DynDatabase::Canberra(env.create_database(wtxn, name)?)),
+            PyDistance::BrayCurtis => Ok(DynDatabase::BrayCurtis(env.create_database(wtxn, name)?)),
+            PyDistance::Hellinger => Ok(DynDatabase::Hellinger(env.create_database(wtxn, name)?)),
+            PyDistance::U8Euclidean => {
+                Ok(DynDatabase::U8Euclidean(env.create_database(wtxn, name)?))
+            }
+            PyDistance::U8Manhattan => {
+                Ok(DynDatabase::U8Manhattan(env.create_database(wtxn, name)?))
+            }
+            PyDistance::EarthMovers => {
+                Ok(DynDatabase::EarthMovers(env.create_database(wtxn, name)?))
+            }
+            PyDistance::Poincare => Ok(DynDatabase::Poincare(env.create_database(wtxn, name)?)),
         }
     }
 }
@@ -119,7 +166,7 @@ impl PyDatabase {
     /// Get a writer for a specific index and dimensions.
     #[pyo3(signature = (dimensions, index=0, m=16, ef=96))]
     fn writer(&self, dimensions: usize, index: u16, m: usize, ef: usize) -> PyWriter {
-        let opts = BuildOptions { ef, m, m0: 2 * m };
+        let opts = BuildOptions { ef, m };
 
         match self.0 {
             DynDatabase::Cosine(db) => {
@@ -149,6 +196,38 @@ impl PyDatabase {
                 dyn_writer: DynWriter::Hamming(Writer::new(db, index, dimensions)),
                 opts,
             },
+            DynDatabase::Chebyshev(db) => PyWriter {
+                dyn_writer: DynWriter::Chebyshev(Writer::new(db, index, dimensions)),
+                opts,
+            },
+            DynDatabase::Canberra(db) => PyWriter {
+                dyn_writer: DynWriter::Canberra(Writer::new(db, index, dimensions)),
+                opts,
+            },
+            DynDatabase::BrayCurtis(db) => PyWriter {
+                dyn_writer: DynWriter::BrayCurtis(Writer::new(db, index, dimensions)),
+                opts,
+            },
+            DynDatabase::Hellinger(db) => PyWriter {
+                dyn_writer: DynWriter::Hellinger(Writer::new(db, index, dimensions)),
+                opts,
+            },
+            DynDatabase::U8Euclidean(db) => PyWriter {
+                dyn_writer: DynWriter::U8Euclidean(Writer::new(db, index, dimensions)),
+                opts,
+            },
+            DynDatabase::U8Manhattan(db) => PyWriter {
+                dyn_writer: DynWriter::U8Manhattan(Writer::new(db, index, dimensions)),
+                opts,
+            },
+            DynDatabase::EarthMovers(db) => PyWriter {
+                dyn_writer: DynWriter::EarthMovers(Writer::new(db, index, dimensions)),
+                opts,
+            },
+            DynDatabase::Poincare(db) => PyWriter {
+                dyn_writer: DynWriter::Poincare(Writer::new(db, index, dimensions)),
+                opts,
+            },
         }
     }
 
@@ -193,6 +272,46 @@ impl PyDatabase {
                 let dyn_reader = DynReader::Hamming(reader);
                 PyReader { dyn_reader, rtxn }
             }
+            DynDatabase::Chebyshev(database) => {
+                let reader = Reader::open(&rtxn, index, database).map_err(h2py_err)?;
+                let dyn_reader = DynReader::Chebyshev(reader);
+                PyReader { dyn_reader, rtxn }
+            }
+            DynDatabase::Canberra(database) => {
+                let reader = Reader::open(&rtxn, index, database).map_err(h2py_err)?;
+                let dyn_reader = DynReader::Canberra(reader);
+                PyReader { dyn_reader, rtxn }
+            }
+            DynDatabase::BrayCurtis(database) => {
+                let reader = Reader::open(&rtxn, index, database).map_err(h2py_err)?;
+                let dyn_reader = DynReader::BrayCurtis(reader);
+                PyReader { dyn_reader, rtxn }
+            }
+            DynDatabase::Hellinger(database) => {
+                let reader = Reader::open(&rtxn, index, database).map_err(h2py_err)?;
+                let dyn_reader = DynReader::Hellinger(reader);
+                PyReader { dyn_reader, rtxn }
+            }
+            DynDatabase::U8Euclidean(database) => {
+                let reader = Reader::open(&rtxn, index, database).map_err(h2py_err)?;
+                let dyn_reader = DynReader::U8Euclidean(reader);
+                PyReader { dyn_reader, rtxn }
+            }
+            DynDatabase::U8Manhattan(database) => {
+                let reader = Reader::open(&rtxn, index, database).map_err(h2py_err)?;
+                let dyn_reader = DynReader::U8Manhattan(reader);
+                PyReader { dyn_reader, rtxn }
+            }
+            DynDatabase::EarthMovers(database) => {
+                let reader = Reader::open(&rtxn, index, database).map_err(h2py_err)?;
+                let dyn_reader = DynReader::EarthMovers(reader);
+                PyReader { dyn_reader, rtxn }
+            }
+            DynDatabase::Poincare(database) => {
+                let reader = Reader::open(&rtxn, index, database).map_err(h2py_err)?;
+                let dyn_reader = DynReader::Poincare(reader);
+                PyReader { dyn_reader, rtxn }
+            }
         };
         Ok(reader)
     }
@@ -226,13 +345,20 @@ enum DynWriter {
     BqEuclidean(Writer<distance::BinaryQuantizedEuclidean>),
     BqManhattan(Writer<distance::BinaryQuantizedManhattan>),
     Hamming(Writer<distance::Hamming>),
+    Chebyshev(Writer<distance::Chebyshev>),
+    Canberra(Writer<distance::Canberra>),
+    BrayCurtis(Writer<distance::BrayCurtis>),
+    Hellinger(Writer<distance::Hellinger>),
+    U8Euclidean(Writer<distance::U8Euclidean>),
+    U8Manhattan(Writer<distance::U8Manhattan>),
+    EarthMovers(Writer<distance::EarthMovers>),
+    Poincare(Writer<distance::Poincare>),
 }
 
 #[derive(Clone)]
 struct BuildOptions {
     pub ef: usize,
     pub m: usize,
-    pub m0: usize,
 }
 
 /// A struct for configuring the HNSW build and performing transactional insertions/deletions from
@@ -255,46 +381,6 @@ pub(super) struct PyWriter {
     opts: BuildOptions,
 }
 
-impl PyWriter {
-    fn build(&self) -> PyResult<()> {
-        use rand::{rngs::StdRng, SeedableRng};
-
-        let mut rng = StdRng::seed_from_u64(42);
-        let mut wtxn = get_rw_txn()?;
-
-        let BuildOptions { ef, m, m0 } = self.opts;
-
-        // a helper macro to auto generating some matches
-        macro_rules! match_table {
-            ($w:expr => $(($M:literal, $M0:literal)),* $(,)?) => {
-                match (m, m0) {
-                    $(
-                        ($M, $M0) => $w.builder(&mut rng).ef_construction(ef).build::<$M, $M0>(&mut wtxn),
-                    )*
-                    _ => panic!("not supported: m = {}, m0 = {}", m, m0),
-                }.map_err(h2py_err)?
-            };
-        }
-        // the real macro
-        macro_rules! hnsw_build {
-            ($w:expr) => {{
-                match_table! {$w => (4, 8), (8, 16), (12, 24), (16, 32), (24, 48), (32, 64)}
-            }};
-        }
-
-        match &self.dyn_writer {
-            DynWriter::Cosine(writer) => hnsw_build!(writer),
-            DynWriter::Euclidean(writer) => hnsw_build!(writer),
-            DynWriter::Manhattan(writer) => hnsw_build!(writer),
-            DynWriter::BqCosine(writer) => hnsw_build!(writer),
-            DynWriter::BqEuclidean(writer) => hnsw_build!(writer),
-            DynWriter::BqManhattan(writer) => hnsw_build!(writer),
-            DynWriter::Hamming(writer) => hnsw_build!(writer),
-        };
-        Ok(())
-    }
-}
-
 #[gen_stub_pymethods]
 #[pymethods]
 impl PyWriter {
@@ -339,9 +425,140 @@ impl PyWriter {
             DynWriter::Hamming(writer) => {
                 writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
             }
+            DynWriter::Chebyshev(writer) => {
+                writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
+            }
+            DynWriter::Canberra(writer) => {
+                writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
+            }
+            DynWriter::BrayCurtis(writer) => {
+                writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
+            }
+            DynWriter::Hellinger(writer) => {
+                writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
+            }
+            DynWriter::U8Euclidean(writer) => {
+                writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
+            }
+            DynWriter::U8Manhattan(writer) => {
+                writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
+            }
+            DynWriter::EarthMovers(writer) => {
+                writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
+            }
+            DynWriter::Poincare(writer) => {
+                writer.add_item(&mut wtxn, item, &vector).map_err(h2py_err)?
+            }
         }
         Ok(())
     }
+
+    /// Store many vectors at once, `vectors` is a 2D numpy array with one row per item ID.
+    fn add_items(&self, items: Vec<ItemId>, vectors: PyReadonlyArray2<f32>) -> PyResult<()> {
+        if items.len() != vectors.shape()[0] {
+            return Err(PyValueError::new_err(format!(
+                "Got {} item IDs but {} vectors",
+                items.len(),
+                vectors.shape()[0]
+            )));
+        }
+
+        let dimensions = vectors.shape()[1];
+        if dimensions == 0 {
+            return Err(PyValueError::new_err("Got vectors of 0 dimensions"));
+        }
+
+        // the rows of a C-contiguous array are read in place, the other arrays are copied once
+        let copy: Vec<f32>;
+        let values = match vectors.is_c_contiguous() {
+            true => vectors.as_slice()?,
+            false => {
+                copy = vectors.as_array().iter().copied().collect();
+                &copy
+            }
+        };
+        let iter = || items.iter().copied().zip(values.chunks_exact(dimensions));
+        let mut wtxn = get_rw_txn()?;
+        match &self.dyn_writer {
+            DynWriter::Cosine(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::Euclidean(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::Manhattan(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::BqCosine(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::BqEuclidean(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::BqManhattan(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::Hamming(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::Chebyshev(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::Canberra(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::BrayCurtis(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::Hellinger(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::U8Euclidean(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::U8Manhattan(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::EarthMovers(writer) => writer.add_items(&mut wtxn, iter()),
+            DynWriter::Poincare(writer) => writer.add_items(&mut wtxn, iter()),
+        }
+        .map_err(h2py_err)
+    }
+
+    /// Delete an item from the database, returns `True` if it existed.
+    fn del_item(&self, item: ItemId) -> PyResult<bool> {
+        let mut wtxn = get_rw_txn()?;
+        match &self.dyn_writer {
+            DynWriter::Cosine(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::Euclidean(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::Manhattan(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::BqCosine(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::BqEuclidean(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::BqManhattan(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::Hamming(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::Chebyshev(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::Canberra(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::BrayCurtis(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::Hellinger(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::U8Euclidean(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::U8Manhattan(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::EarthMovers(writer) => writer.del_item(&mut wtxn, item),
+            DynWriter::Poincare(writer) => writer.del_item(&mut wtxn, item),
+        }
+        .map_err(h2py_err)
+    }
+
+    /// Build the HNSW graph from the items added or deleted since the last build.
+    ///
+    /// This is automatically called when leaving the `with` block of the writer.
+    fn build(&self) -> PyResult<()> {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut wtxn = get_rw_txn()?;
+
+        let BuildOptions { ef, m } = self.opts;
+
+        // the bottom layer has twice as many links, the unsupported `m` raise a ValueError
+        macro_rules! hnsw_build {
+            ($w:expr) => {
+                $w.builder(&mut rng).ef_construction(ef).build_with_links(&mut wtxn, m)
+            };
+        }
+
+        match &self.dyn_writer {
+            DynWriter::Cosine(writer) => hnsw_build!(writer),
+            DynWriter::Euclidean(writer) => hnsw_build!(writer),
+            DynWriter::Manhattan(writer) => hnsw_build!(writer),
+            DynWriter::BqCosine(writer) => hnsw_build!(writer),
+            DynWriter::BqEuclidean(writer) => hnsw_build!(writer),
+            DynWriter::BqManhattan(writer) => hnsw_build!(writer),
+            DynWriter::Hamming(writer) => hnsw_build!(writer),
+            DynWriter::Chebyshev(writer) => hnsw_build!(writer),
+            DynWriter::Canberra(writer) => hnsw_build!(writer),
+            DynWriter::BrayCurtis(writer) => hnsw_build!(writer),
+            DynWriter::Hellinger(writer) => hnsw_build!(writer),
+            DynWriter::U8Euclidean(writer) => hnsw_build!(writer),
+            DynWriter::U8Manhattan(writer) => hnsw_build!(writer),
+            DynWriter::EarthMovers(writer) => hnsw_build!(writer),
+            DynWriter::Poincare(writer) => hnsw_build!(writer),
+        }
+        .map_err(h2py_err)
+    }
 }
 
 enum DynReader {
@@ -352,6 +569,14 @@ enum DynReader {
     BqEuclidean(Reader<distance::BinaryQuantizedEuclidean>),
     BqManhattan(Reader<distance::BinaryQuantizedManhattan>),
     Hamming(Reader<distance::Hamming>),
+    Chebyshev(Reader<distance::Chebyshev>),
+    Canberra(Reader<distance::Canberra>),
+    BrayCurtis(Reader<distance::BrayCurtis>),
+    Hellinger(Reader<distance::Hellinger>),
+    U8Euclidean(Reader<distance::U8Euclidean>),
+    U8Manhattan(Reader<distance::U8Manhattan>),
+    EarthMovers(Reader<distance::EarthMovers>),
+    Poincare(Reader<distance::Poincare>),
 }
 
 /// A thread-local Database reader holding its own `RoTxn`. It is safe to spawn multiple readers in
@@ -393,9 +618,33 @@ impl PyReader {
             DynReader::BqEuclidean(reader) => hnsw_search!(reader, &query)?,
             DynReader::BqManhattan(reader) => hnsw_search!(reader, &query)?,
             DynReader::Hamming(reader) => hnsw_search!(reader, &query)?,
+            DynReader::Chebyshev(reader) => hnsw_search!(reader, &query)?,
+            DynReader::Canberra(reader) => hnsw_search!(reader, &query)?,
+            DynReader::BrayCurtis(reader) => hnsw_search!(reader, &query)?,
+            DynReader::Hellinger(reader) => hnsw_search!(reader, &query)?,
+            DynReader::U8Euclidean(reader) => hnsw_search!(reader, &query)?,
+            DynReader::U8Manhattan(reader) => hnsw_search!(reader, &query)?,
+            DynReader::EarthMovers(reader) => hnsw_search!(reader, &query)?,
+            DynReader::Poincare(reader) => hnsw_search!(reader, &query)?,
         };
         Ok(found.into_nns())
     }
+
+    /// Retrieve similar items for every row of a 2D numpy array of queries.
+    #[pyo3(signature = (queries, n=10, ef_search=200))]
+    fn by_vecs(
+        &self,
+        queries: PyReadonlyArray2<f32>,
+        n: usize,
+        ef_search: usize,
+    ) -> PyResult<Vec<Vec<(ItemId, f32)>>> {
+        queries
+            .as_array()
+            .rows()
+            .into_iter()
+            .map(|query| self.by_vec(query.to_vec(), n, ef_search))
+            .collect()
+    }
 }
 
 fn h2py_err<E: Into<crate::error::Error>>(e: E) -> PyErr {
@@ -403,6 +652,11 @@ fn h2py_err<E: Into<crate::error::Error>>(e: E) -> PyErr {
         crate::Error::Heed(heed::Error::Io(e)) | crate::Error::Io(e) => {
             PyIOError::new_err(e.to_string())
         }
+        e @ (crate::Error::KeyOperation { source: heed::Error::Io(_), .. }
+        | crate::Error::RangeOperation { source: heed::Error::Io(_), .. }) => {
+            PyIOError::new_err(e.to_string())
+        }
+        e @ crate::Error::UnsupportedLinks(_) => PyValueError::new_err(e.to_string()),
         e => PyRuntimeError::new_err(e.to_string()),
     }
 }
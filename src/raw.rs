@@ -0,0 +1,156 @@
+//! **Unstable**: a cursor over the raw entries of a database, for the tools that inspect or
+//! migrate the storage format of hannoy, e.g. debuggers and format explorers.
+//!
+//! The entries are the ones hannoy stores: their keys are decoded into the index, the
+//! [`NodeMode`] and the item they're about, their values are given as is. The meaning of the
+//! modes and the encoding of the values are internal to hannoy and change between its versions
+//! without notice, this module follows them and isn't covered by semver. The tools built on it
+//! must check the [`Version`](crate::Version) of the indexes they read.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use hannoy::{Database, distances::Cosine};
+//! # let (rtxn, database): (heed::RoTxn, Database<Cosine>) = todo!();
+//! use hannoy::raw::{NodeMode, RawCursor};
+//!
+//! for entry in RawCursor::index(&rtxn, database, 0)? {
+//!     let entry = entry?;
+//!     if entry.key.mode() == Some(NodeMode::Links) {
+//!         println!("links of {} in layer {}: {} bytes", entry.key.item, entry.key.layer, entry.value.len());
+//!     }
+//! }
+//! # Ok::<(), hannoy::Error>(())
+//! ```
+use std::mem::size_of;
+use std::ops::Bound;
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::Bytes;
+use heed::RoTxn;
+
+use crate::distance::Distance;
+use crate::{Database, ItemId, LayerId, Result};
+
+pub use crate::node_id::NodeMode;
+
+/// The bytes of an encoded [`RawKey`].
+const RAW_KEY: usize = size_of::<u64>();
+
+/// The key of an entry of the database, decoded without checking its mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RawKey {
+    /// The index the entry belongs to.
+    pub index: u16,
+    /// The byte of the [`NodeMode`] of the entry, which may be unknown to this version.
+    pub mode: u8,
+    /// The item, the sequence number or the number of the metadata the entry is about.
+    pub item: ItemId,
+    /// The layer of the links, the slot of a previous vector, `0` for the other modes.
+    pub layer: LayerId,
+}
+
+impl RawKey {
+    /// Returns the key of the entry of an `index` about an `item`.
+    pub fn new(index: u16, mode: NodeMode, item: ItemId, layer: LayerId) -> RawKey {
+        RawKey { index, mode: mode as u8, item, layer }
+    }
+
+    /// Returns the [`NodeMode`] of the entry, `None` if this version of hannoy doesn't know it.
+    pub fn mode(&self) -> Option<NodeMode> {
+        NodeMode::try_from(self.mode).ok()
+    }
+
+    /// Encodes the key like hannoy stores it, the entries are sorted by these bytes.
+    pub fn to_bytes(&self) -> [u8; RAW_KEY] {
+        let mut bytes = [0; RAW_KEY];
+        bytes[..2].copy_from_slice(&self.index.to_be_bytes());
+        bytes[2] = self.mode;
+        bytes[3..7].copy_from_slice(&self.item.to_be_bytes());
+        bytes[7] = self.layer;
+        bytes
+    }
+
+    /// Decodes a key stored by hannoy, `None` if it isn't one.
+    pub fn from_bytes(bytes: &[u8]) -> Option<RawKey> {
+        let bytes: &[u8; RAW_KEY] = bytes.try_into().ok()?;
+        Some(RawKey {
+            index: BigEndian::read_u16(&bytes[..2]),
+            mode: bytes[2],
+            item: BigEndian::read_u32(&bytes[3..7]),
+            layer: bytes[7],
+        })
+    }
+}
+
+/// An entry of the database read by a [`RawCursor`].
+#[derive(Debug, Clone, Copy)]
+pub struct RawEntry<'t> {
+    /// The decoded key of the entry.
+    pub key: RawKey,
+    /// The value of the entry, as it is stored.
+    pub value: &'t [u8],
+}
+
+/// An iterator over the entries of a database in the order of their keys, see the
+/// [module documentation](self).
+pub struct RawCursor<'t> {
+    iter: heed::RoRange<'t, Bytes, Bytes>,
+    /// The index the entries are read from, `None` to read all of them.
+    index: Option<u16>,
+}
+
+impl<'t> RawCursor<'t> {
+    /// Returns a cursor over all the entries of the database, of every index.
+    pub fn all<D: Distance>(rtxn: &'t RoTxn, database: Database<D>) -> Result<RawCursor<'t>> {
+        Self::range(rtxn, database, Bound::Unbounded, None)
+    }
+
+    /// Returns a cursor over the entries of an `index`.
+    pub fn index<D: Distance>(
+        rtxn: &'t RoTxn,
+        database: Database<D>,
+        index: u16,
+    ) -> Result<RawCursor<'t>> {
+        let start = RawKey { index, mode: 0, item: 0, layer: 0 }.to_bytes();
+        Self::range(rtxn, database, Bound::Included(&start), Some(index))
+    }
+
+    /// Returns a cursor over the entries of the database from the `key` included, of every
+    /// index.
+    pub fn starting_at<D: Distance>(
+        rtxn: &'t RoTxn,
+        database: Database<D>,
+        key: RawKey,
+    ) -> Result<RawCursor<'t>> {
+        Self::range(rtxn, database, Bound::Included(&key.to_bytes()), None)
+    }
+
+    fn range<D: Distance>(
+        rtxn: &'t RoTxn,
+        database: Database<D>,
+        start: Bound<&[u8]>,
+        index: Option<u16>,
+    ) -> Result<RawCursor<'t>> {
+        let iter =
+            database.remap_types::<Bytes, Bytes>().range(rtxn, &(start, Bound::Unbounded))?;
+        Ok(RawCursor { iter, index })
+    }
+}
+
+impl<'t> Iterator for RawCursor<'t> {
+    type Item = Result<RawEntry<'t>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = match self.iter.next()? {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(e.into())),
+        };
+        // every key of a hannoy database is a raw key
+        let key = RawKey::from_bytes(key).expect("the keys of hannoy are 8 bytes long");
+        if self.index.is_some_and(|index| index != key.index) {
+            return None;
+        }
+        Some(Ok(RawEntry { key, value }))
+    }
+}
@@ -0,0 +1,120 @@
+//! Opens the environment of the indexes read-only, e.g. from the analytics jobs running next to
+//! the process that writes them.
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{RwLock, RwLockReadGuard};
+
+use heed::{Env, EnvFlags, EnvOpenOptions, RoTxn, WithTls};
+
+use crate::distance::Distance;
+use crate::{Database, Error, Result};
+
+/// The number of named databases a [`ReadOnlyEnv`] can open, the ones of the writers are
+/// created by the writer process.
+const MAX_DBS: u32 = 128;
+
+/// How a [`ReadOnlyEnv`] coordinates with the other processes opening the environment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadAccess {
+    /// Registers the read transactions in the lock file of the environment, so that any number
+    /// of processes can read it while another one writes it. The lock file must be writable.
+    #[default]
+    Shared,
+    /// Ignores the lock file, for the environments no process writes anymore, e.g. a snapshot
+    /// on a read-only file system or mounted from an object store.
+    Frozen,
+}
+
+/// An environment opened read-only, which can be shared with the other processes reading it and
+/// with a process writing it elsewhere.
+///
+/// LMDB readers never block the writer and always see the last committed transaction when they
+/// start one. When the writer grows the map past the size this process mapped, the next
+/// [`Self::read_txn`] waits for the transactions of this process to end and maps the new size.
+///
+/// # Example
+///
+/// ```no_run
+/// use hannoy::{distances::Cosine, ReadAccess, ReadOnlyEnv, Reader};
+///
+/// // safety: only the indexing service writes the environment, through LMDB
+/// let env = unsafe { ReadOnlyEnv::open("path/to/the/env", ReadAccess::Shared)? };
+/// let rtxn = env.read_txn()?;
+/// let database = env.open_database::<Cosine>(&rtxn, None)?.expect("the index is created");
+/// let reader = Reader::open(&rtxn, 0, database)?;
+/// println!("{} items", reader.n_items());
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct ReadOnlyEnv {
+    env: Env,
+    /// Held shared by the read transactions and exclusively while the map is resized, which
+    /// LMDB only allows without any transaction.
+    resize: RwLock<()>,
+}
+
+impl ReadOnlyEnv {
+    /// Opens the environment at `path` read-only.
+    ///
+    /// # Safety
+    ///
+    /// The files of the environment must only be modified through LMDB, and not at all with
+    /// [`ReadAccess::Frozen`]: this process maps them in memory and reads them without checking
+    /// them, like [`EnvOpenOptions::open`].
+    pub unsafe fn open(path: impl AsRef<Path>, access: ReadAccess) -> Result<ReadOnlyEnv> {
+        let mut flags = EnvFlags::READ_ONLY;
+        if access == ReadAccess::Frozen {
+            flags |= EnvFlags::NO_LOCK;
+        }
+        let env = EnvOpenOptions::new().max_dbs(MAX_DBS).flags(flags).open(path)?;
+        Ok(ReadOnlyEnv { env, resize: RwLock::new(()) })
+    }
+
+    /// Starts a read transaction on the last committed state of the environment. A thread must
+    /// end its transaction before it starts another one.
+    pub fn read_txn(&self) -> Result<SharedRoTxn<'_>> {
+        loop {
+            let guard = self.resize.read().unwrap_or_else(|e| e.into_inner());
+            match self.env.read_txn() {
+                Ok(rtxn) => return Ok(SharedRoTxn { rtxn, _guard: guard }),
+                Err(heed::Error::Mdb(heed::MdbError::MapResized)) => {
+                    drop(guard);
+                    let _exclusive = self.resize.write().unwrap_or_else(|e| e.into_inner());
+                    // safety: the transactions of this process hold the lock, none is alive
+                    unsafe { self.env.resize(0)? };
+                }
+                Err(e) => return Err(Error::Heed(e)),
+            }
+        }
+    }
+
+    /// Opens the database of the indexes named `name`, `None` if the writer didn't create it.
+    /// Up to 128 named databases can be opened.
+    pub fn open_database<D: Distance>(
+        &self,
+        rtxn: &RoTxn,
+        name: Option<&str>,
+    ) -> Result<Option<Database<D>>> {
+        Ok(self.env.open_database(rtxn, name)?)
+    }
+
+    /// Clears the read transactions of the processes that died while reading from the lock
+    /// file and returns how many there were. LMDB does it when it opens the environment.
+    pub fn clear_stale_readers(&self) -> Result<usize> {
+        Ok(self.env.clear_stale_readers()?)
+    }
+}
+
+/// A read transaction of a [`ReadOnlyEnv`], it dereferences to a heed [`RoTxn`].
+pub struct SharedRoTxn<'e> {
+    rtxn: RoTxn<'e, WithTls>,
+    _guard: RwLockReadGuard<'e, ()>,
+}
+
+impl<'e> Deref for SharedRoTxn<'e> {
+    type Target = RoTxn<'e, WithTls>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rtxn
+    }
+}
@@ -1,23 +1,52 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::cmp::Reverse;
 use std::collections::BinaryHeap;
 use std::marker;
 use std::num::NonZeroUsize;
-
-use heed::types::DecodeIgnore;
-use heed::RoTxn;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use byteorder::{BigEndian, ByteOrder};
+use hashbrown::{HashMap, HashSet};
+use heed::types::{Bytes, DecodeIgnore, U32, U64};
+use heed::{BytesDecode, RoTxn};
 use min_max_heap::MinMaxHeap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use roaring::RoaringBitmap;
+use rustc_hash::FxBuildHasher;
+use tinyvec::ArrayVec;
 
+use crate::calibration::{self, get_calibration};
+use crate::codes::{self, Codes};
+use crate::density::get_radii;
 use crate::distance::Distance;
+#[cfg(feature = "gpu")]
+use crate::gpu::GpuDistances;
 use crate::hnsw::ScoredLink;
+use crate::info::{get_build_info, IndexInfo};
 use crate::internals::KeyCodec;
 use crate::item_iter::ItemIter;
+use crate::lsh::{self, Prefilter};
+use crate::matryoshka::{self, MATRYOSHKA_OVERSAMPLING};
 use crate::metadata::Metadata;
-use crate::node::{Item, Links};
+use crate::node::{array_links, serialized_links, Item, Links};
 use crate::ordered_float::OrderedFloat;
+use crate::projection::{self, Projection};
+use crate::reorder;
+use crate::roaring::SerializedBitmap;
+use crate::snapshot;
+use crate::stats::{SearchStats, VectorStats, VectorStatsBuilder};
+use crate::tuning::{EfSearchTuning, TuningCodec};
 use crate::unaligned_vector::UnalignedVector;
-use crate::version::{Version, VersionCodec};
-use crate::{Database, Error, ItemId, Key, MetadataCodec, Node, Prefix, PrefixCodec, Result};
+use crate::version::{self, Version, VersionCodec, FORMAT_VERSION};
+use crate::weights;
+use crate::{
+    Database, Error, ItemId, Key, LayerId, MetadataCodec, Node, NodeCodec, Prefix, PrefixCodec,
+    Result,
+};
 
 /// A good default value for the `ef` parameter.
 const DEFAULT_EF_SEARCH: usize = 100;
@@ -25,13 +54,48 @@ const DEFAULT_EF_SEARCH: usize = 100;
 #[cfg(not(windows))]
 const READER_AVAILABLE_MEMORY: &str = "HANNOY_READER_PREFETCH_MEMORY";
 
-#[cfg(not(test))]
-/// The threshold at which linear search is used instead of the HNSW algorithm.
+/// The number of candidates under which a filtered search always ranks them one by one instead
+/// of traversing the graph, see [`FilterStrategy`].
 const LINEAR_SEARCH_THRESHOLD: u64 = 1000;
-#[cfg(test)]
-/// Note that for tests purposes, we use set this threshold
-/// to zero to make sure we test the HNSW algorithm.
-const LINEAR_SEARCH_THRESHOLD: u64 = 0;
+
+/// The number of neighbours the traversal computes the distance of per expanded item, the
+/// default `M0`, which the planner of the filtered searches estimates their cost with.
+const PLANNED_DEGREE: f64 = 32.0;
+
+/// The fraction of the items under which a filter is selective enough for the traversal to
+/// skip the items that don't match it and look at their own neighbours instead.
+const FILTERED_EXPANSION_SELECTIVITY: f64 = 0.2;
+
+/// The number of neighbours whose distances are computed at once, see [`Distance::distances`].
+const DISTANCE_BLOCK: usize = 16;
+
+/// The largest difference between the ids of the neighbours read with the same range scan, see
+/// [`Reader::with_batched_fetches`].
+const BATCHED_FETCH_MAX_GAP: ItemId = 4;
+
+/// The number of neighbours from which the distances of a batch of expanded candidates are
+/// computed in parallel, see [`QueryBuilder::parallel_expansion`].
+const PARALLEL_DISTANCES_THRESHOLD: usize = 4 * DISTANCE_BLOCK;
+
+/// The number of closest neighbours [`Reader::count_within`] counts the items of a small radius
+/// from, the count of a larger radius is estimated from a sample.
+const COUNT_WITHIN_SEARCHED: usize = 256;
+
+/// The number of items [`Reader::count_within`] compares to the query to estimate the count of
+/// a large radius, the items of smaller indexes are all compared.
+const COUNT_WITHIN_SAMPLE: u64 = 1024;
+
+/// The number of closest neighbours of the query per requested neighbour that
+/// [`Reader::reverse_nns`] checks, the items further away rarely count the query among theirs.
+const REVERSE_NNS_CANDIDATES: usize = 4;
+
+/// The number of items evenly spread over the index [`Reader::furthest`] also starts from, on
+/// top of the entry points.
+const FURTHEST_RESTARTS: u64 = 16;
+
+/// The number of times more candidates than requested are searched when the duplicates are
+/// collapsed, see [`QueryBuilder::dedup`].
+const DEDUP_OVERSAMPLING: usize = 4;
 
 /// Container storing nearest neighbour search result
 #[derive(Debug)]
@@ -40,11 +104,23 @@ pub struct Searched {
     pub nns: Vec<(ItemId, f32)>,
     /// A bool indicating whether or not the search terminated early
     pub did_cancel: bool,
+    /// What the `f32` of the neighbours are, see [`QueryBuilder::scores`]
+    scores: ScoreKind,
 }
 
 impl Searched {
     pub(crate) fn new(nns: Vec<(ItemId, f32)>, did_cancel: bool) -> Self {
-        Searched { nns, did_cancel }
+        Searched { nns, did_cancel, scores: ScoreKind::Distance }
+    }
+
+    /// Converts the distances of the neighbours into the `scores`, once the search is done.
+    fn scored<D: Distance>(mut self, scores: ScoreKind) -> Self {
+        let (min, max) = scores.bounds(self.nns.iter().map(|&(_, distance)| distance));
+        for (_, score) in &mut self.nns {
+            *score = scores.score::<D>(*score, min, max);
+        }
+        self.scores = scores;
+        self
     }
 
     /// Indicates if the search terminated early
@@ -52,20 +128,299 @@ impl Searched {
         self.did_cancel
     }
 
+    /// Returns what the `f32` of the neighbours are, see [`QueryBuilder::scores`]
+    pub fn scores(&self) -> ScoreKind {
+        self.scores
+    }
+
     /// Consumes `self` and returns vector of nearest neighbours
     pub fn into_nns(self) -> Vec<(ItemId, f32)> {
         self.nns
     }
 }
 
+/// A search of a batch run by [`Reader::multi_search`].
+#[derive(Debug, Clone, Copy)]
+pub enum Query<'a> {
+    /// The `count` closest items from a vector, see [`QueryBuilder::by_vector`].
+    Vector {
+        /// The vector to search from.
+        vector: &'a [f32],
+        /// The number of items to return.
+        count: usize,
+    },
+    /// The `count` closest items from a stored item, see [`QueryBuilder::by_item`].
+    Item {
+        /// The item to search from.
+        item: ItemId,
+        /// The number of items to return.
+        count: usize,
+    },
+}
+
+/// A query vector converted once into the representation used by a [`Distance`], along with the
+/// scratch buffers needed to run a search.
+///
+/// Preparing a query is useful when the same vector is searched repeatedly against the same
+/// index, or against several indexes that transform their vectors the same way: the
+/// preprocessing, the header computation, the quantization and the allocation of the visited set
+/// are only done once. A query is prepared by [`Reader::prepare_query`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hannoy::{Reader, PreparedQuery, distances::Euclidean};
+/// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+/// let mut query = reader.prepare_query(&[1.25854, -0.75598, 0.58524])?;
+/// let first = reader.nns(20).by_prepared(&rtxn, &mut query)?;
+/// let second = reader.nns(5).ef_search(200).by_prepared(&rtxn, &mut query)?;
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+pub struct PreparedQuery<D: Distance> {
+    item: Item<'static, D>,
+    /// The complete vector the candidates of a Matryoshka index are re-ranked with.
+    full_query: Option<Vec<f32>>,
+    dimensions: usize,
+    scratch: SearchScratch,
+}
+
+impl<D: Distance> PreparedQuery<D> {
+    /// Returns the number of dimensions of the prepared vector.
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+}
+
+thread_local! {
+    /// The buffers of the searches run on this thread without a [`PreparedQuery`].
+    static SCRATCH: RefCell<SearchScratch> = RefCell::default();
+}
+
+/// A set of items that keeps its memory once cleared, contrary to a [`RoaringBitmap`] which
+/// frees its containers.
+pub(crate) type ItemSet = HashSet<ItemId, FxBuildHasher>;
+
+/// The buffers reused between searches.
+#[derive(Default)]
+pub(crate) struct SearchScratch {
+    /// The items visited during the traversal of a layer.
+    pub path: ItemSet,
+    /// The steps of the traversal, only recorded when debugging a search.
+    pub trace: Option<SearchTrace>,
+    /// The items queued for expansion while traversing a layer.
+    queue: BinaryHeap<(Reverse<OrderedFloat>, ItemId)>,
+    /// The neighbours of the expanded item that are visited next.
+    expansion: Vec<ItemId>,
+    /// The neighbours that were skipped to visit their own neighbours, see [`Visitor`].
+    hops: ItemSet,
+    /// The closest items found while traversing a layer.
+    results: MinMaxHeap<ScoredLink>,
+    /// The distances of a block of neighbours, see [`Distance::distances`].
+    distances: Vec<f32>,
+    /// The items the traversal of a layer starts from.
+    entry_points: Vec<ItemId>,
+    /// The closest items found by the last traversal, handed back by the searches that write
+    /// them elsewhere, see [`QueryBuilder::by_vector_into`].
+    found: Vec<(ItemId, f32)>,
+}
+
+impl SearchScratch {
+    pub fn clear(&mut self) {
+        self.path.clear();
+        self.trace = None;
+    }
+
+    /// Stores the buffers a [`Visitor`] took back in the scratch.
+    fn give_back(
+        &mut self,
+        queue: BinaryHeap<(Reverse<OrderedFloat>, ItemId)>,
+        expansion: Vec<ItemId>,
+        hops: ItemSet,
+    ) {
+        self.queue = queue;
+        self.expansion = expansion;
+        self.hops = hops;
+    }
+
+    /// Runs `f` with the buffers of this thread, cleared, or with new ones if they are in use.
+    pub fn with_pooled<T>(f: impl FnOnce(&mut SearchScratch) -> T) -> T {
+        SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+            Ok(mut scratch) => {
+                scratch.clear();
+                f(&mut scratch)
+            }
+            Err(_) => f(&mut SearchScratch::default()),
+        })
+    }
+
+    fn record(&mut self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(trace) = &mut self.trace {
+            trace.events.push(event());
+        }
+    }
+}
+
+/// The sequence of steps taken by a search through the graph, see
+/// [`QueryBuilder::by_vector_traced`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchTrace {
+    /// The steps in the order they were taken.
+    pub events: Vec<TraceEvent>,
+}
+
+impl SearchTrace {
+    /// Returns the items whose distance to the query was computed, in the order they were
+    /// visited. An item is visited at most once per layer.
+    pub fn visited(&self) -> impl Iterator<Item = (ItemId, f32)> + '_ {
+        self.events.iter().filter_map(|event| match *event {
+            TraceEvent::Visit { item, distance, .. } => Some((item, distance)),
+            _ => None,
+        })
+    }
+}
+
+/// A step of a [`SearchTrace`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// The search starts traversing a layer from these entry points. The bottom layer is
+    /// entered again, from an unvisited item, when the traversal found fewer items than
+    /// requested.
+    EnterLayer {
+        /// The layer, `0` being the bottom one that contains every item.
+        layer: usize,
+        /// The items the traversal of the layer starts from.
+        entry_points: Vec<ItemId>,
+    },
+    /// The distance between the query and an item was computed.
+    Visit {
+        /// The visited item.
+        item: ItemId,
+        /// Its distance to the query.
+        distance: f32,
+        /// Whether the item was close enough to be queued for expansion.
+        queued: bool,
+    },
+    /// The links of a queued item were read to visit its neighbours.
+    Expand {
+        /// The expanded item.
+        item: ItemId,
+        /// Its distance to the query.
+        distance: f32,
+    },
+    /// The candidates were few enough to be ranked one by one instead of traversing the graph.
+    LinearScan {
+        /// The number of candidates.
+        candidates: u64,
+    },
+}
+
+/// How a search restricted to [`QueryBuilder::candidates`] finds the closest of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// Picks one of the other strategies per query, from the number of candidates and the
+    /// number of items of the index, see [`FilterStrategy::plan`].
+    #[default]
+    Auto,
+    /// Traverses the graph, skipping the items that aren't candidates.
+    Graph,
+    /// Computes the distance of every candidate to the query, which is exact.
+    Linear,
+}
+
+/// What the `f32` returned along with the neighbours of a search are, see
+/// [`QueryBuilder::scores`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScoreKind {
+    /// The distances of the index, lower is closer.
+    #[default]
+    Distance,
+    /// The similarities computed from the distances by [`Distance::similarity`], higher is
+    /// closer. Their range depends on the distance:
+    /// - the cosine similarity in `[-1, 1]` for `Cosine`, `BinaryQuantizedCosine` and
+    ///   `SparseCosine`;
+    /// - `1 - distance` in `[0, 1]` for `Hamming`, `BrayCurtis` on non-negative vectors and
+    ///   `Hellinger` on probability distributions;
+    /// - `1 - distance` in `(-inf, 1]` for the others, e.g. `Euclidean` or `Manhattan`, which
+    ///   is negative once the distance is over `1`. Prefer [`ScoreKind::Normalized`] to get a
+    ///   bounded score out of them.
+    Similarity,
+    /// The distances rescaled between the closest and the furthest of the results, from `1.0`
+    /// for the closest to `0.0` for the furthest. They are all `1.0` if they are at the same
+    /// distance.
+    Normalized,
+}
+
+impl ScoreKind {
+    /// Returns the lowest and the highest of the `distances` of a list of results, which the
+    /// normalized scores are relative to.
+    pub(crate) fn bounds(self, distances: impl Iterator<Item = f32>) -> (f32, f32) {
+        match self {
+            ScoreKind::Normalized => distances
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| (min.min(d), max.max(d))),
+            ScoreKind::Distance | ScoreKind::Similarity => (0.0, 0.0),
+        }
+    }
+
+    /// Converts the `distance` of a result into this score, `min` and `max` being the
+    /// [`Self::bounds`] of its list.
+    pub(crate) fn score<D: Distance>(self, distance: f32, min: f32, max: f32) -> f32 {
+        match self {
+            ScoreKind::Distance => distance,
+            ScoreKind::Similarity => D::similarity(distance),
+            ScoreKind::Normalized if max > min => 1.0 - (distance - min) / (max - min),
+            ScoreKind::Normalized => 1.0,
+        }
+    }
+}
+
+impl FilterStrategy {
+    /// Returns the strategy that computes the fewest distances to find the closest of
+    /// `matching` candidates among the `items` of an index with a search buffer of `ef`.
+    ///
+    /// The linear scan computes the distance of every candidate. The traversal computes the
+    /// distances of the neighbours of about `ef` items, which it has to expand more of the
+    /// fewer candidates there are: it is estimated to `ef * M0 / selectivity`, up to all the
+    /// items. The scan wins up to about `sqrt(ef * M0 * items)` candidates.
+    pub fn plan(matching: u64, items: u64, ef: usize) -> FilterStrategy {
+        if matching == 0 || items == 0 {
+            return FilterStrategy::Linear;
+        }
+        let selectivity = matching as f64 / items as f64;
+        let graph = (ef as f64 * PLANNED_DEGREE / selectivity).min(items as f64);
+        if matching as f64 <= graph {
+            FilterStrategy::Linear
+        } else {
+            FilterStrategy::Graph
+        }
+    }
+}
+
 /// Options used to make a query against an hannoy [`Reader`].
 pub struct QueryBuilder<'a, D: Distance> {
     reader: &'a Reader<D>,
     candidates: Option<&'a RoaringBitmap>,
     count: usize,
     ef: usize,
+    expansion_batch: usize,
+    rescore: Option<(usize, Rescore<'a>)>,
+    boost: Option<usize>,
+    strategy: FilterStrategy,
+    scores: ScoreKind,
+    entry_points: &'a [ItemId],
+    dedup: Option<f32>,
+    stop_within: Option<f32>,
+    max_hamming: Option<u32>,
+    /// The complete query vector of a search of a Matryoshka index, see [`Self::rerank`].
+    full_query: Option<&'a [f32]>,
+    rerank: Option<usize>,
+    #[cfg(feature = "gpu")]
+    gpu: Option<&'a GpuDistances>,
 }
 
+/// Scores a candidate of a search from its id, distance and payload, see
+/// [`QueryBuilder::rescore`].
+type Rescore<'a> = &'a dyn Fn(ItemId, f32, Option<&[u8]>) -> f32;
+
 impl<'a, D: Distance> QueryBuilder<'a, D> {
     /// Returns the closests items from `item`.
     ///
@@ -80,7 +435,7 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
     /// ```
     pub fn by_item(&self, rtxn: &RoTxn, item: ItemId) -> Result<Option<Searched>> {
         self.reader.nns_by_item(rtxn, item, self, || false).map(|res| match res {
-            Some(Completion::Done(items)) => Some(Searched::new(items, false)),
+            Some(Completion::Done(items)) => Some(self.searched(items, false)),
             Some(Completion::Cancelled(_)) => {
                 unreachable!("cancellation only possible using by_item_with_cancellation")
             }
@@ -102,7 +457,7 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
     ///
     /// let later = Instant::now().checked_add(Duration::from_secs(1)).unwrap();
     /// let cancel_fn = || Instant::now() > later;
-    /// let Searched{ nns, did_cancel } = reader.nns(20).by_item_with_cancellation(&rtxn, 5, cancel_fn)?.unwrap();
+    /// let Searched { nns, did_cancel, .. } = reader.nns(20).by_item_with_cancellation(&rtxn, 5, cancel_fn)?.unwrap();
     /// # Ok::<(), hannoy::Error>(())
     /// ```
     pub fn by_item_with_cancellation(
@@ -112,12 +467,65 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
         cancel_fn: impl Fn() -> bool,
     ) -> Result<Option<Searched>> {
         self.reader.nns_by_item(rtxn, item, self, cancel_fn).map(|res| match res {
-            Some(Completion::Done(done)) => Some(Searched::new(done, false)),
-            Some(Completion::Cancelled(cancelled)) => Some(Searched::new(cancelled, true)),
+            Some(Completion::Done(done)) => Some(self.searched(done, false)),
+            Some(Completion::Cancelled(cancelled)) => Some(self.searched(cancelled, true)),
             None => None,
         })
     }
 
+    /// Returns the closest items from the centroid of the vectors of `items`, not including
+    /// them, the "more like these" query. `None` if none of the `items` exist.
+    ///
+    /// See also [`Self::by_weighted_items`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// reader.nns(20).by_items(&rtxn, &[5, 12, 47]);
+    /// ```
+    pub fn by_items(&self, rtxn: &RoTxn, items: &[ItemId]) -> Result<Option<Searched>> {
+        let weighted: Vec<_> = items.iter().map(|&item| (item, 1.0)).collect();
+        self.by_weighted_items(rtxn, &weighted)
+    }
+
+    /// Returns the closest items from the centroid of the vectors of `items` weighted by their
+    /// `f32`, not including them. `None` if none of the `items` exist or their weights sum to
+    /// zero.
+    ///
+    /// The centroid is computed from the vectors as they are stored, it isn't transformed like
+    /// the vectors given to [`Self::by_vector`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// reader.nns(20).by_weighted_items(&rtxn, &[(5, 2.0), (12, 1.0), (47, 0.5)]);
+    /// ```
+    pub fn by_weighted_items(
+        &self,
+        rtxn: &RoTxn,
+        items: &[(ItemId, f32)],
+    ) -> Result<Option<Searched>> {
+        let Some(centroid) = self.reader.weighted_centroid(rtxn, items.iter().copied())? else {
+            return Ok(None);
+        };
+
+        let vector = UnalignedVector::from_vec(centroid);
+        let item = Item { header: D::new_header(&vector), vector };
+        let opt = QueryBuilder { count: self.count + items.len(), ..*self };
+        let mut neighbours = SearchScratch::with_pooled(|scratch| {
+            self.reader.nns_by_vec(rtxn, &item, &opt, scratch, || false)
+        })?
+        .into_inner();
+        neighbours.retain(|(found, _)| items.iter().all(|(item, _)| item != found));
+        neighbours.truncate(self.count);
+
+        Ok(Some(self.searched(neighbours, false)))
+    }
+
     /// Returns the closest items from the provided `vector`.
     ///
     /// See also [`Self::by_item`].
@@ -130,21 +538,229 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
     /// reader.nns(20).by_vector(&rtxn, &[1.25854, -0.75598, 0.58524]);
     /// ```
     pub fn by_vector(&self, rtxn: &RoTxn, vector: &'a [f32]) -> Result<Searched> {
-        if vector.len() != self.reader.dimensions() {
-            return Err(Error::InvalidVecDimension {
-                expected: self.reader.dimensions(),
-                received: vector.len(),
-            });
+        let opt = self.with_full_query(vector);
+        let vector = self.reader.query_vector(vector)?;
+        let vector = UnalignedVector::from_slice(&vector);
+        let item = Item { header: D::new_header(&vector), vector };
+
+        let neighbours = SearchScratch::with_pooled(|scratch| {
+            self.reader.nns_by_vec(rtxn, &item, &opt, scratch, || false)
+        })?
+        .into_inner();
+
+        Ok(self.searched(neighbours, false))
+    }
+
+    /// Returns the closest items of every one of the `vectors`, in order, like
+    /// [`Self::by_vector`].
+    ///
+    /// With a GPU given to [`Self::gpu`] the queries aren't searched in the graph: all of them
+    /// are compared with every candidate, or with every item of the index without candidates,
+    /// in the dispatches of the GPU, which receives the vectors of the items once for the whole
+    /// batch. The results are exact, the batch is meant for the candidates and indexes small
+    /// enough to be scanned. The queries are searched one after the other with
+    /// [`Self::by_vector`] without a GPU, with a distance it doesn't compute or on a Matryoshka
+    /// index.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean, gpu::GpuDistances};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let gpu = GpuDistances::new()?;
+    /// let queries: [&[f32]; 2] = [&[1.25854, -0.75598], &[0.58524, 1.0]];
+    /// for found in reader.nns(20).gpu(&gpu).by_vector_batch(&rtxn, &queries)? {
+    ///     println!("{:?}", found.nns);
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    #[cfg(feature = "gpu")]
+    pub fn by_vector_batch(&self, rtxn: &RoTxn, vectors: &[&'a [f32]]) -> Result<Vec<Searched>> {
+        let gpu = self
+            .gpu
+            .filter(|_| GpuDistances::supports::<D>() && self.reader.full_dimensions.is_none());
+        let Some(gpu) = gpu else {
+            return vectors.iter().map(|vector| self.by_vector(rtxn, vector)).collect();
+        };
+
+        let mut queries = Vec::with_capacity(vectors.len() * self.reader.dimensions());
+        for vector in vectors {
+            queries.extend_from_slice(&self.reader.query_vector(vector)?);
+        }
+        let candidates = match (&self.reader.external_items, self.candidates) {
+            (Some(_), Some(candidates)) => {
+                Cow::Owned(self.reader.internal_candidates(rtxn, candidates)?)
+            }
+            (None, Some(candidates)) => Cow::Borrowed(candidates),
+            (_, None) => Cow::Borrowed(&self.reader.items),
+        };
+        let count = self.oversampled().count;
+        let found = self.reader.gpu_brute_force_search(rtxn, gpu, &queries, &candidates, count)?;
+
+        found
+            .into_iter()
+            .map(|nns| {
+                let found = self.reader.to_external(rtxn, Completion::Done(nns))?;
+                let found = self.reader.rescored(rtxn, found, self)?;
+                let found = self.reader.deduplicated(rtxn, found, self)?;
+                Ok(self.searched(found.into_inner(), false))
+            })
+            .collect()
+    }
+
+    /// Writes the closest items from the provided `vector` and their scores in `ids` and
+    /// `distances` instead of returning them, and returns how many were written, e.g. for the
+    /// callers of the FFI to search into their own buffers.
+    ///
+    /// At most as many items as the shorter of the two slices can hold are written, closest
+    /// first, even if the search was asked for more. The values past the returned length are
+    /// left untouched. The results are written from the buffers of the search, which are kept
+    /// for the next one of the thread, nothing is allocated for them.
+    ///
+    /// See also [`Self::by_vector`] and [`Reader::nns_into`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let (mut ids, mut distances) = ([0; 20], [0.0; 20]);
+    /// let found = reader.nns(20).ef_search(200).by_vector_into(&rtxn, &[1.25854, -0.75598], &mut ids, &mut distances)?;
+    /// for (id, distance) in ids.iter().zip(&distances).take(found) {
+    ///     println!("{id}: {distance}");
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn by_vector_into(
+        &self,
+        rtxn: &RoTxn,
+        vector: &'a [f32],
+        ids: &mut [ItemId],
+        distances: &mut [f32],
+    ) -> Result<usize> {
+        let capacity = ids.len().min(distances.len());
+        let opt = QueryBuilder { count: self.count.min(capacity), ..self.with_full_query(vector) };
+        let vector = self.reader.query_vector(vector)?;
+        let vector = UnalignedVector::from_slice(&vector);
+        let item = Item { header: D::new_header(&vector), vector };
+
+        SearchScratch::with_pooled(|scratch| {
+            let found = self.reader.nns_by_vec(rtxn, &item, &opt, scratch, || false)?.into_inner();
+            let (min, max) = self.scores.bounds(found.iter().map(|&(_, distance)| distance));
+            for ((id, distance), &(item, found)) in ids.iter_mut().zip(distances).zip(&found) {
+                *id = item;
+                *distance = self.scores.score::<D>(found, min, max);
+            }
+            let written = found.len();
+            scratch.found = found;
+            Ok(written)
+        })
+    }
+
+    /// Returns the closest items from the provided `vector` in each of the `buckets`, e.g. the
+    /// price bands of a faceted page, in the same order.
+    ///
+    /// The buckets share a single traversal filtered by their union that looks for `count`
+    /// items per bucket. A bucket this traversal didn't find enough items of, because the
+    /// closest items are in the other buckets, is searched on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let cheap = roaring::RoaringBitmap::from_iter([1, 3, 4, 5]);
+    /// let expensive = roaring::RoaringBitmap::from_iter([6, 7, 8, 9, 15, 16]);
+    /// let per_band = reader.nns(10).by_vector_per_bucket(&rtxn, &[1.25854, -0.75598], &[cheap, expensive])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn by_vector_per_bucket(
+        &self,
+        rtxn: &RoTxn,
+        vector: &[f32],
+        buckets: &[RoaringBitmap],
+    ) -> Result<Vec<Searched>> {
+        let opt = self.with_full_query(vector);
+        let vector = self.reader.query_vector(vector)?;
+        let vector = UnalignedVector::from_slice(&vector);
+        let item = Item { header: D::new_header(&vector), vector };
+
+        let buckets: Vec<Cow<RoaringBitmap>> = buckets
+            .iter()
+            .map(|bucket| match self.candidates {
+                Some(candidates) => Cow::Owned(bucket & candidates),
+                None => Cow::Borrowed(bucket),
+            })
+            .collect();
+        let union = buckets.iter().fold(RoaringBitmap::new(), |union, bucket| union | &**bucket);
+        let count = self.count.saturating_mul(buckets.len());
+        let shared =
+            QueryBuilder { candidates: Some(&union), count, ef: self.ef.max(count), ..opt };
+        let found = SearchScratch::with_pooled(|scratch| {
+            self.reader.nns_by_vec(rtxn, &item, &shared, scratch, || false)
+        })?
+        .into_inner();
+
+        let mut per_bucket = Vec::with_capacity(buckets.len());
+        for bucket in &buckets {
+            let mut nns: Vec<_> = found
+                .iter()
+                .filter(|(id, _)| bucket.contains(*id))
+                .take(self.count)
+                .copied()
+                .collect();
+            let matching = bucket.intersection_len(self.reader.item_ids());
+            if nns.len() < self.count && (nns.len() as u64) < matching {
+                let alone = QueryBuilder { candidates: Some(bucket), ..opt };
+                nns = SearchScratch::with_pooled(|scratch| {
+                    self.reader.nns_by_vec(rtxn, &item, &alone, scratch, || false)
+                })?
+                .into_inner();
+            }
+            per_bucket.push(self.searched(nns, false));
         }
 
-        let vector = UnalignedVector::from_slice(vector);
+        Ok(per_bucket)
+    }
+
+    /// Returns the closest items from the provided `vector` along with the trace of the
+    /// traversal that found them: the layers it went through and every item it visited and
+    /// expanded, with their distances.
+    ///
+    /// Recording the trace slows the search down, it is meant to understand why a specific query
+    /// misses some of its true neighbours.
+    ///
+    /// See also [`Self::by_vector`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let (searched, trace) = reader.nns(20).by_vector_traced(&rtxn, &[1.25854, -0.75598, 0.58524])?;
+    /// for (item, distance) in trace.visited() {
+    ///     println!("visited {item} at {distance}");
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn by_vector_traced(
+        &self,
+        rtxn: &RoTxn,
+        vector: &'a [f32],
+    ) -> Result<(Searched, SearchTrace)> {
+        let opt = self.with_full_query(vector);
+        let vector = self.reader.query_vector(vector)?;
+        let vector = UnalignedVector::from_slice(&vector);
         let item = Item { header: D::new_header(&vector), vector };
 
-        let cancel_fn = || false;
-        let neighbours =
-            self.reader.nns_by_vec(rtxn, &item, self, cancel_fn).map(|res| res.into_inner())?;
+        let mut scratch =
+            SearchScratch { trace: Some(SearchTrace::default()), ..Default::default() };
+        let neighbours = self
+            .reader
+            .nns_by_vec(rtxn, &item, &opt, &mut scratch, || false)
+            .map(|res| res.into_inner())?;
 
-        Ok(Searched::new(neighbours, false))
+        Ok((self.searched(neighbours, false), scratch.trace.unwrap_or_default()))
     }
 
     /// Returns as many nearest neighbours to the query as possible before `cancel_fn` evaluates to
@@ -161,7 +777,7 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
     ///
     /// let later = Instant::now().checked_add(Duration::from_secs(1)).unwrap();
     /// let cancel_fn = || Instant::now() > later;
-    /// let Searched{ nns, did_cancel } = reader.nns(20).by_vector_with_cancellation(&rtxn, &[1.25854, -0.75598, 0.58524], cancel_fn)?;
+    /// let Searched { nns, did_cancel, .. } = reader.nns(20).by_vector_with_cancellation(&rtxn, &[1.25854, -0.75598, 0.58524], cancel_fn)?;
     /// # Ok::<(), hannoy::Error>(())
     /// ```
     pub fn by_vector_with_cancellation(
@@ -170,20 +786,61 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
         vector: &'a [f32],
         cancel_fn: impl Fn() -> bool,
     ) -> Result<Searched> {
-        if vector.len() != self.reader.dimensions() {
-            return Err(Error::InvalidVecDimension {
-                expected: self.reader.dimensions(),
-                received: vector.len(),
-            });
+        let opt = self.with_full_query(vector);
+        let vector = self.reader.query_vector(vector)?;
+        let vector = UnalignedVector::from_slice(&vector);
+        let item = Item { header: D::new_header(&vector), vector };
+
+        let nns = SearchScratch::with_pooled(|scratch| {
+            self.reader.nns_by_vec(rtxn, &item, &opt, scratch, cancel_fn)
+        })?;
+        match nns {
+            Completion::Done(done) => Ok(self.searched(done, false)),
+            Completion::Cancelled(cancelled) => Ok(self.searched(cancelled, true)),
         }
+    }
 
-        let vector = UnalignedVector::from_slice(vector);
-        let item = Item { header: D::new_header(&vector), vector };
+    /// Returns the closest items from an already [`PreparedQuery`], reusing its buffers.
+    ///
+    /// See also [`Self::by_vector`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, PreparedQuery, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let mut query = reader.prepare_query(&[1.25854, -0.75598, 0.58524])?;
+    /// reader.nns(20).by_prepared(&rtxn, &mut query)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn by_prepared(&self, rtxn: &RoTxn, query: &mut PreparedQuery<D>) -> Result<Searched> {
+        self.by_prepared_with_cancellation(rtxn, query, || false)
+    }
+
+    /// Returns as many nearest neighbours to the [`PreparedQuery`] as possible before `cancel_fn`
+    /// evaluates to true, and indicates whether or not search terminated early.
+    ///
+    /// See also [`Self::by_vector_with_cancellation`].
+    pub fn by_prepared_with_cancellation(
+        &self,
+        rtxn: &RoTxn,
+        query: &mut PreparedQuery<D>,
+        cancel_fn: impl Fn() -> bool,
+    ) -> Result<Searched> {
+        if query.dimensions != self.reader.query_dimensions() {
+            return Err(Error::InvalidDimensions {
+                expected: self.reader.query_dimensions(),
+                got: query.dimensions,
+            });
+        }
 
-        let nns = self.reader.nns_by_vec(rtxn, &item, self, cancel_fn)?;
+        let PreparedQuery { item, full_query, scratch, .. } = query;
+        let opt = QueryBuilder { full_query: full_query.as_deref(), ..*self };
+        scratch.clear();
+        let nns = self.reader.nns_by_vec(rtxn, item, &opt, scratch, cancel_fn)?;
         match nns {
-            Completion::Done(done) => Ok(Searched::new(done, false)),
-            Completion::Cancelled(cancelled) => Ok(Searched::new(cancelled, true)),
+            Completion::Done(done) => Ok(self.searched(done, false)),
+            Completion::Cancelled(cancelled) => Ok(self.searched(cancelled, true)),
         }
     }
 
@@ -202,6 +859,39 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
         self
     }
 
+    /// Specify how the search finds the closest [`Self::candidates`], by default the planner
+    /// picks the strategy that computes the fewest distances, see [`FilterStrategy::plan`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean, FilterStrategy};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let candidates = roaring::RoaringBitmap::from_iter(0..5000);
+    /// reader.nns(20).candidates(&candidates).filter_strategy(FilterStrategy::Linear).by_item(&rtxn, 6);
+    /// ```
+    pub fn filter_strategy(&mut self, strategy: FilterStrategy) -> &mut Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Returns the neighbours along with the `scores` rather than their raw distances, e.g.
+    /// [`ScoreKind::Similarity`] to show cosine similarities. The conversion happens once the
+    /// results are ranked, after [`Self::rescore`] and [`Self::boosted`], and is recorded in
+    /// [`Searched::scores`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Cosine, ScoreKind};
+    /// # let (reader, rtxn): (Reader<Cosine>, heed::RoTxn) = todo!();
+    /// reader.nns(20).scores(ScoreKind::Similarity).by_item(&rtxn, 6);
+    /// ```
+    pub fn scores(&mut self, scores: ScoreKind) -> &mut Self {
+        self.scores = scores;
+        self
+    }
+
     /// Specify a search buffer size from which the closest elements are returned. Increasing this
     /// value improves the search relevancy but increases latency as more neighbours need to be
     /// searched.
@@ -218,41 +908,326 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
         self.ef = ef.max(self.count);
         self
     }
-}
 
-enum Completion<T> {
-    Done(T),
-    Cancelled(T),
-}
-impl<T> Completion<T> {
-    pub fn into_inner(self) -> T {
-        match self {
-            Completion::Done(inner) => inner,
-            Completion::Cancelled(inner) => inner,
-        }
-    }
-    pub fn map<U>(self, op: impl FnOnce(T) -> U) -> Completion<U> {
-        match self {
-            Self::Done(inner) => Completion::Done(op(inner)),
-            Self::Cancelled(inner) => Completion::Cancelled(op(inner)),
-        }
+    /// Expands up to `batch` of the closest candidates of the bottom layer at once and computes
+    /// the distances of their neighbours in parallel on the global rayon thread pool, so that a
+    /// single search with a large `ef_search` uses more than one core. Defaults to `1`, the
+    /// sequential search. The distances are computed on the calling thread when the `rayon`
+    /// feature is disabled.
+    ///
+    /// The vectors are read by the calling thread, only the distances are spread over the pool,
+    /// by blocks that idle threads steal. A batch may expand candidates the sequential search
+    /// would have skipped, the results are about the same but can differ slightly. A batch of
+    /// a few neighbours is computed sequentially, the parallelism only pays off for the searches
+    /// that go through thousands of items.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// reader.nns(20).ef_search(2000).parallel_expansion(8).by_item(&rtxn, 6);
+    /// ```
+    pub fn parallel_expansion(&mut self, batch: usize) -> &mut Self {
+        self.expansion_batch = batch.max(1);
+        self
     }
-}
 
-struct Visitor<'a> {
-    pub eps: Vec<ItemId>,
-    pub level: usize,
-    pub ef: usize,
-    pub candidates: Option<&'a RoaringBitmap>,
-}
-impl<'a> Visitor<'a> {
-    pub fn new(
+    /// Searches `oversampling` times more candidates than requested and ranks them by the
+    /// score `rescore` gives them from their id, distance and payload, lower is better, to
+    /// blend application signals in the ranking. The returned distances are the scores.
+    ///
+    /// The payloads are the ones attached by [`Writer::set_payload`](crate::Writer::set_payload).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// // favor the items whose payload flags them as promoted
+    /// let promoted = |_item, distance: f32, payload: Option<&[u8]>| match payload {
+    ///     Some([1, ..]) => distance * 0.5,
+    ///     _ => distance,
+    /// };
+    /// reader.nns(20).rescore(4, &promoted).by_item(&rtxn, 6);
+    /// ```
+    pub fn rescore(&mut self, oversampling: usize, rescore: Rescore<'a>) -> &mut Self {
+        self.rescore = Some((oversampling.max(1), rescore));
+        self
+    }
+
+    /// Searches `oversampling` times more candidates than requested and ranks them by their
+    /// distance divided by their boost, so that popularity or quality priors influence the
+    /// ranking without changing the traversal. The returned distances are the boosted ones.
+    ///
+    /// The boosts are the ones given by [`Writer::set_boost`](crate::Writer::set_boost), the
+    /// other items keep their distance. They apply after [`Self::rescore`], to its scores.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// reader.nns(20).boosted(4).by_item(&rtxn, 6);
+    /// ```
+    pub fn boosted(&mut self, oversampling: usize) -> &mut Self {
+        self.boost = Some(oversampling.max(1));
+        self
+    }
+
+    /// Starts the traversal of the bottom layer from the `items`, e.g. the last items a user
+    /// clicked on, instead of descending from the top layer, when the answer is known to be in
+    /// their neighbourhood.
+    ///
+    /// The items that don't exist are ignored, the search descends from the top layer if none
+    /// of them do. The searches by item always start from the item itself.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let last_clicked = [12, 47];
+    /// reader.nns(20).entry_points(&last_clicked).by_vector(&rtxn, &[1.25854, -0.75598, 0.58524]);
+    /// ```
+    pub fn entry_points(&mut self, items: &'a [ItemId]) -> &mut Self {
+        self.entry_points = items;
+        self
+    }
+
+    /// Collapses the results closer than `threshold` to each other, in the unit of the
+    /// distances, into the best of them, so that mirrored or duplicated documents don't fill
+    /// the whole results. The distances between the results are computed from their stored
+    /// vectors.
+    ///
+    /// The search looks for more candidates than requested to make up for the collapsed ones,
+    /// it can return fewer items if most of them are duplicates.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// reader.nns(20).dedup(1e-4).by_item(&rtxn, 6);
+    /// ```
+    pub fn dedup(&mut self, threshold: f32) -> &mut Self {
+        self.dedup = Some(threshold);
+        self
+    }
+
+    /// Stops the traversal as soon as it found `count` items whose distance to the query is at
+    /// most `distance`, for the queries that only need to know whether there is anything close
+    /// enough, instead of looking for closer items.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let searched = reader.nns(1).stop_when_within(0.05).by_item(&rtxn, 6)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn stop_when_within(&mut self, distance: f32) -> &mut Self {
+        self.stop_within = Some(distance);
+        self
+    }
+
+    /// Skips the neighbours whose LSH sketch differs from the one of the query on more than
+    /// `max_hamming` bits instead of computing their distance, a multi-probe lookup of the
+    /// buckets around the one of the query.
+    ///
+    /// Comparing two sketches is a few instructions whatever the dimensions of the vectors, it
+    /// saves most of the distance computations of the searches over vectors of thousands of
+    /// dimensions, at the cost of missing the neighbours that are close but whose sketches
+    /// disagree. The index must be built with
+    /// [`HannoyBuilder::lsh_sketches`](crate::HannoyBuilder::lsh_sketches), the option is
+    /// ignored otherwise. The entry points of the graph are never pruned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Cosine};
+    /// # let (reader, rtxn): (Reader<Cosine>, heed::RoTxn) = todo!();
+    /// // with sketches of 256 bits, two vectors 45° apart differ on 64 bits on average
+    /// reader.nns(20).lsh_prefilter(64).by_item(&rtxn, 6);
+    /// ```
+    pub fn lsh_prefilter(&mut self, max_hamming: u32) -> &mut Self {
+        self.max_hamming = Some(max_hamming);
+        self
+    }
+
+    /// Searches `oversampling` times more candidates than requested with the prefixes of the
+    /// vectors of a Matryoshka index and ranks them by the distances of their complete vectors
+    /// to the complete query, see [`Writer::set_matryoshka`](crate::Writer::set_matryoshka).
+    ///
+    /// The searches by vector of a Matryoshka index are always re-ranked, four times more
+    /// candidates than requested by default. The searches by item and by [`PreparedQuery`]
+    /// only use the prefixes, the option is ignored by the other indexes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Cosine};
+    /// # let (reader, rtxn): (Reader<Cosine>, heed::RoTxn) = todo!();
+    /// # let query = [0.5; 1024];
+    /// reader.nns(20).rerank(10).by_vector(&rtxn, &query);
+    /// ```
+    pub fn rerank(&mut self, oversampling: usize) -> &mut Self {
+        self.rerank = Some(oversampling.max(1));
+        self
+    }
+
+    /// Computes the distances of the linear scans of the filtered searches, see
+    /// [`FilterStrategy::Linear`], and of the searches of [`Self::by_vector_batch`] on a GPU.
+    ///
+    /// The graph is still traversed on the CPU. The option is ignored by the distances the GPU
+    /// doesn't compute, see [`GpuDistances::supports`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Cosine, gpu::GpuDistances};
+    /// # let (reader, rtxn, candidates): (Reader<Cosine>, heed::RoTxn, roaring::RoaringBitmap) = todo!();
+    /// let gpu = GpuDistances::new()?;
+    /// reader.nns(20).candidates(&candidates).gpu(&gpu).by_vector(&rtxn, &[0.5; 768])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    #[cfg(feature = "gpu")]
+    pub fn gpu(&mut self, gpu: &'a GpuDistances) -> &mut Self {
+        self.gpu = Some(gpu);
+        self
+    }
+
+    /// Returns the options of a search by `vector`, which re-ranks the candidates with it if the
+    /// index is a Matryoshka one.
+    fn with_full_query<'v>(&self, vector: &'v [f32]) -> QueryBuilder<'v, D>
+    where
+        'a: 'v,
+    {
+        let full_query = self.reader.full_dimensions.map(|_| vector);
+        QueryBuilder { full_query, ..*self }
+    }
+
+    /// Returns the results of the search, with the [`Self::scores`] they were asked for.
+    fn searched(&self, nns: Vec<(ItemId, f32)>, did_cancel: bool) -> Searched {
+        Searched::new(nns, did_cancel).scored::<D>(self.scores)
+    }
+
+    /// Returns the options of the search the candidates of the rescoring and the deduplication
+    /// are found with.
+    fn oversampled(&self) -> Self {
+        let rescore = self.rescore.map_or(1, |(oversampling, _)| oversampling);
+        let boost = self.boost.unwrap_or(1);
+        let dedup = if self.dedup.is_some() { DEDUP_OVERSAMPLING } else { 1 };
+        let rerank = match self.full_query {
+            Some(_) => self.rerank.unwrap_or(MATRYOSHKA_OVERSAMPLING),
+            None => 1,
+        };
+        let oversampling = rescore * boost * dedup * rerank;
+        QueryBuilder { count: self.count.saturating_mul(oversampling), ..*self }
+    }
+}
+
+impl<'a, D: Distance<VectorCodec = u8>> QueryBuilder<'a, D> {
+    /// Returns the closest items from the provided vector of bytes, compared as is with the
+    /// vectors of the items, along with their distances. See [`Self::by_vector`] to search with
+    /// a vector of f32.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::U8Euclidean};
+    /// # let (reader, rtxn): (Reader<U8Euclidean>, heed::RoTxn) = todo!();
+    /// reader.nns(20).by_u8_vector(&rtxn, &[12, 255, 0, 87]);
+    /// ```
+    pub fn by_u8_vector(&self, rtxn: &RoTxn, vector: &[u8]) -> Result<Searched> {
+        if vector.len() != self.reader.dimensions() {
+            return Err(Error::InvalidDimensions {
+                expected: self.reader.dimensions(),
+                got: vector.len(),
+            });
+        }
+
+        let vector = Cow::Borrowed(UnalignedVector::from_u8_slice(vector));
+        let item = Item { header: D::new_header(&vector), vector };
+
+        let neighbours = SearchScratch::with_pooled(|scratch| {
+            self.reader.nns_by_vec(rtxn, &item, self, scratch, || false)
+        })?
+        .into_inner();
+
+        Ok(self.searched(neighbours, false))
+    }
+}
+
+enum Completion<T> {
+    Done(T),
+    Cancelled(T),
+}
+impl<T> Completion<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            Completion::Done(inner) => inner,
+            Completion::Cancelled(inner) => inner,
+        }
+    }
+    pub fn map<U>(self, op: impl FnOnce(T) -> U) -> Completion<U> {
+        match self {
+            Self::Done(inner) => Completion::Done(op(inner)),
+            Self::Cancelled(inner) => Completion::Cancelled(op(inner)),
+        }
+    }
+}
+
+struct Visitor<'a> {
+    pub eps: Vec<ItemId>,
+    pub level: usize,
+    pub ef: usize,
+    pub candidates: Option<&'a RoaringBitmap>,
+    /// Whether the neighbours that aren't candidates are replaced by their own neighbours.
+    pub filtered_expansion: bool,
+    /// Whether the layer cache of the reader is up to date and the cached layers are read from
+    /// it, see [`Reader::with_layer_cache`].
+    pub cached: bool,
+    /// The contiguous quantized codes the items are read from, if they're up to date.
+    pub codes: Option<Codes<'a>>,
+    /// The number of candidates expanded at once, see [`QueryBuilder::parallel_expansion`].
+    pub expansion_batch: usize,
+    /// The deleted items the traversal goes through but never returns.
+    pub tombstones: Option<&'a RoaringBitmap>,
+    /// The number of results within a distance after which the traversal stops, see
+    /// [`QueryBuilder::stop_when_within`].
+    pub stop_within: Option<(usize, f32)>,
+    /// The sketches the neighbours are pruned with before their distance is computed, see
+    /// [`QueryBuilder::lsh_prefilter`].
+    pub prefilter: Option<Prefilter<'a>>,
+}
+impl<'a> Visitor<'a> {
+    pub fn new(
         eps: Vec<ItemId>,
         level: usize,
         ef: usize,
         candidates: Option<&'a RoaringBitmap>,
     ) -> Self {
-        Self { eps, level, ef, candidates }
+        Self {
+            eps,
+            level,
+            ef,
+            candidates,
+            filtered_expansion: false,
+            cached: false,
+            codes: None,
+            expansion_batch: 1,
+            tombstones: None,
+            stop_within: None,
+            prefilter: None,
+        }
+    }
+
+    /// Returns `true` if the point can be part of the results.
+    fn is_returned(&self, point: ItemId) -> bool {
+        self.candidates.is_none_or(|c| c.contains(point))
+            && self.tombstones.is_none_or(|t| !t.contains(point))
     }
 
     /// Iteratively traverse a given level of the HNSW graph, updating the search path history.
@@ -263,24 +1238,47 @@ impl<'a> Visitor<'a> {
         query: &Item<D>,
         reader: &Reader<D>,
         rtxn: &RoTxn,
-        path: &mut RoaringBitmap,
+        scratch: &mut SearchScratch,
         cancel_fn: &impl Fn() -> bool,
     ) -> Result<Completion<MinMaxHeap<ScoredLink>>> {
         use Completion::*;
 
-        let mut search_queue = BinaryHeap::new();
-        let mut res = MinMaxHeap::with_capacity(self.ef);
+        // the buffers are taken from the scratch to be borrowed along with it, then given back
+        let mut search_queue = std::mem::take(&mut scratch.queue);
+        let mut expansion = std::mem::take(&mut scratch.expansion);
+        let mut hops = std::mem::take(&mut scratch.hops);
+        search_queue.clear();
+        hops.clear();
+        let mut res = std::mem::take(&mut scratch.results);
+        res.clear();
+        res.reserve(self.ef);
+        let mut distances = std::mem::take(&mut scratch.distances);
+        // the neighbours whose distances are computed in parallel, only allocated by the
+        // parallel searches and reused by all the expansions of the layer
+        let mut items = Vec::new();
+        let cache =
+            reader.layer_cache.as_ref().filter(|c| self.cached && self.level >= c.min_layer);
+        scratch.record(|| TraceEvent::EnterLayer {
+            layer: self.level,
+            entry_points: self.eps.clone(),
+        });
+
+        // the number of results close enough to stop the traversal
+        let mut within = 0;
+        let is_within = |dist: f32| self.stop_within.is_some_and(|(_, d)| dist <= d);
 
         // Register all entry points as visited and populate candidates
         for &ep in &self.eps[..] {
-            let ve = get_item(reader.database, reader.index, rtxn, ep)?.unwrap();
+            let ve = reader.layer_item(rtxn, cache, self.codes, ep)?;
             let dist = D::distance(query, &ve);
 
             search_queue.push((Reverse(OrderedFloat(dist)), ep));
-            path.insert(ep);
+            scratch.path.insert(ep);
+            scratch.record(|| TraceEvent::Visit { item: ep, distance: dist, queued: true });
 
-            if self.candidates.is_none_or(|c| c.contains(ep)) {
+            if self.is_returned(ep) {
                 res.push((OrderedFloat(dist), ep));
+                within += is_within(dist) as usize;
             }
         }
 
@@ -288,47 +1286,147 @@ impl<'a> Visitor<'a> {
         // when we've exhausted the search queue.
         while let Some(&(Reverse(OrderedFloat(f)), _)) = search_queue.peek() {
             if cancel_fn() {
+                scratch.give_back(search_queue, expansion, hops);
+                scratch.distances = distances;
                 return Ok(Cancelled(res));
             }
             let f_max = res.peek_max().map(|&(OrderedFloat(d), _)| d).unwrap_or(f32::MAX);
-            if f > f_max {
+            if f > f_max || self.stop_within.is_some_and(|(count, _)| within >= count) {
                 break;
             }
-            let (_, c) = search_queue.pop().unwrap();
-
-            let Links { links } = get_links(rtxn, reader.database, reader.index, c, self.level)?
-                .expect("Links must exist");
+            // the batch expands the closest candidates that are still worth it, only one of
+            // them unless the search is parallel
+            expansion.clear();
+            for _ in 0..self.expansion_batch {
+                let Some(&(Reverse(OrderedFloat(f)), c)) = search_queue.peek() else { break };
+                if f > f_max {
+                    break;
+                }
+                search_queue.pop();
+                scratch.record(|| TraceEvent::Expand { item: c, distance: f });
+
+                let links = reader.layer_links(rtxn, cache, c, self.level)?;
+
+                // With a selective filter most neighbours don't match it and the traversal gets
+                // stuck, like ACORN we skip them and expand to their own neighbours that match.
+                match self.candidates.filter(|_| self.filtered_expansion) {
+                    Some(candidates) => {
+                        for point in links.iter() {
+                            if candidates.contains(point) {
+                                expansion.push(point);
+                            } else if hops.insert(point) {
+                                let hop = reader.layer_links(rtxn, cache, point, self.level)?;
+                                expansion.extend(hop.iter().filter(|&p| candidates.contains(p)));
+                            }
+                        }
+                    }
+                    None => expansion.extend(links.iter()),
+                }
+            }
+            expansion.retain(|&point| scratch.path.insert(point));
+            if let Some(prefilter) = &self.prefilter {
+                expansion.retain(|&point| prefilter.keeps(point));
+            }
+            if reader.prefetch {
+                reader.prefetch_items(rtxn, expansion.iter().copied())?;
+            }
 
-            for point in links.iter() {
-                if !path.insert(point) {
-                    continue;
+            distances.clear();
+            if self.expansion_batch > 1 && expansion.len() >= PARALLEL_DISTANCES_THRESHOLD {
+                items.clear();
+                for &point in &expansion {
+                    items.push(reader.layer_item(rtxn, cache, self.codes, point)?);
+                }
+                parallel_distances(query, &items, &mut distances);
+            } else {
+                // the neighbours are read in the order of their ids, which is the one of the keys
+                let batched = reader.batched_fetches && cache.is_none() && self.codes.is_none();
+                if batched {
+                    expansion.sort_unstable();
+                }
+                // the distances of the blocks of neighbours are computed at once, see
+                // `Distance::distances`, the blocks are kept on the stack to not allocate
+                for points in expansion.chunks(DISTANCE_BLOCK) {
+                    let mut block = ArrayVec::<[Item<D>; DISTANCE_BLOCK]>::new();
+                    if batched {
+                        reader.fetch_block(rtxn, points, &mut block)?;
+                    } else {
+                        for &point in points {
+                            block.push(reader.layer_item(rtxn, cache, self.codes, point)?);
+                        }
+                    }
+                    D::distances(query, &block, &mut distances);
                 }
-                let dist = D::distance(
-                    query,
-                    &get_item(reader.database, reader.index, rtxn, point)?.unwrap(),
-                );
+            }
+
+            for (&point, &dist) in expansion.iter().zip(&distances) {
+                let queued = res.len() < self.ef || dist < f_max;
+                scratch.record(|| TraceEvent::Visit { item: point, distance: dist, queued });
 
                 // The search queue can take points that aren't included in the (optional)
-                // candidates bitmap, but the final result must *not* include them.
-                if res.len() < self.ef || dist < f_max {
+                // candidates bitmap or are tombstoned, but the final result must *not* include
+                // them.
+                if queued {
                     search_queue.push((Reverse(OrderedFloat(dist)), point));
-                    if let Some(c) = self.candidates {
-                        if !c.contains(point) {
-                            continue;
-                        }
+                    if !self.is_returned(point) {
+                        continue;
                     }
+                    within += is_within(dist) as usize;
                     if res.len() == self.ef {
-                        let _ = res.push_pop_max((OrderedFloat(dist), point));
+                        let (OrderedFloat(evicted), _) =
+                            res.push_pop_max((OrderedFloat(dist), point));
+                        within -= is_within(evicted) as usize;
                     } else {
                         res.push((OrderedFloat(dist), point));
                     }
                 }
             }
         }
+        scratch.give_back(search_queue, expansion, hops);
+        scratch.distances = distances;
         Ok(Done(res))
     }
 }
 
+/// Computes the distances of the `items` to the `query` by blocks spread over the global rayon
+/// thread pool and appends them to `distances`, in the order of the items.
+#[cfg(feature = "rayon")]
+fn parallel_distances<D: Distance>(query: &Item<D>, items: &[Item<D>], distances: &mut Vec<f32>) {
+    use rayon::prelude::*;
+
+    let start = distances.len();
+    distances.resize(start + items.len(), 0.0);
+    items
+        .par_chunks(DISTANCE_BLOCK)
+        .zip(distances[start..].par_chunks_mut(DISTANCE_BLOCK))
+        .for_each_init(
+            || Vec::with_capacity(DISTANCE_BLOCK),
+            |computed, (block, output)| {
+                computed.clear();
+                D::distances(query, block, computed);
+                output.copy_from_slice(computed);
+            },
+        );
+}
+
+/// Computes the distances of the `items` to the `query` on the calling thread when the `rayon`
+/// feature is disabled.
+#[cfg(not(feature = "rayon"))]
+fn parallel_distances<D: Distance>(query: &Item<D>, items: &[Item<D>], distances: &mut Vec<f32>) {
+    D::distances(query, items, distances);
+}
+
+/// The nodes of the upper layers of the graph decoded once, see [`Reader::with_layer_cache`].
+#[derive(Debug)]
+pub(crate) struct LayerCache<D: Distance> {
+    /// The build the nodes were read from, the cache is ignored once the index is built again.
+    build: u64,
+    /// The lowest cached layer, the layers above it are cached too.
+    min_layer: usize,
+    pub items: HashMap<ItemId, Item<'static, D>, FxBuildHasher>,
+    links: HashMap<(ItemId, LayerId), RoaringBitmap, FxBuildHasher>,
+}
+
 /// A reader over the hannoy hnsw graph
 #[derive(Debug)]
 pub struct Reader<D: Distance> {
@@ -338,24 +1436,167 @@ pub struct Reader<D: Distance> {
     max_level: usize,
     dimensions: usize,
     items: RoaringBitmap,
+    /// The deleted items that are still in the graph, they are not part of the `items`.
+    tombstones: RoaringBitmap,
+    /// The ids the items are known by if the index was reordered, the `items` and the graph
+    /// use the internal ids otherwise, see [`Writer::reorder_into`](crate::Writer::reorder_into).
+    external_items: Option<RoaringBitmap>,
+    /// The items whose quantized codes the last build wrote, see [`Self::codes`].
+    coded_items: Option<RoaringBitmap>,
+    /// The items whose SimHash sketches the last build wrote, see [`Self::prefilter`].
+    sketched_items: Option<RoaringBitmap>,
     version: Version,
+    /// The id of the build the reader sees, see [`Self::build_id`].
+    build: u64,
+    tuning: Option<EfSearchTuning>,
+    /// The weights of the dimensions the queries are scaled by, see
+    /// [`Writer::set_weights`](crate::Writer::set_weights).
+    weights: Option<Vec<f32>>,
+    /// The projection the queries are reduced by, see
+    /// [`Writer::set_projection`](crate::Writer::set_projection).
+    projection: Option<Projection>,
+    /// The dimensions of the complete vectors the queries are given with, see
+    /// [`Writer::set_matryoshka`](crate::Writer::set_matryoshka).
+    full_dimensions: Option<usize>,
+    stats: Option<Arc<SearchStats>>,
+    /// Whether the vectors of the neighbours are prefetched before computing their distances.
+    prefetch: bool,
+    /// Whether the vectors of the neighbours with close ids are read with range scans.
+    batched_fetches: bool,
+    /// The decoded nodes of the upper layers, see [`Self::with_layer_cache`].
+    layer_cache: Option<LayerCache<D>>,
     _marker: marker::PhantomData<D>,
 }
 
 impl<D: Distance> Reader<D> {
+    /// Returns `true` if the graph of this index is up to date with its items.
+    ///
+    /// This is a cheap check that doesn't decode anything: the index must have been built
+    /// at least once and no item must have been added or deleted since. When it returns
+    /// `true` [`Self::open`] won't fail with [`Error::NeedBuild`] or [`Error::MissingMetadata`].
+    pub fn is_built(rtxn: &RoTxn, index: u16, database: Database<D>) -> Result<bool> {
+        let metadata_key = Key::metadata(index);
+        let has_metadata = database
+            .remap_data_type::<DecodeIgnore>()
+            .get(rtxn, &metadata_key)
+            .map_err(Error::on_key("get", metadata_key))?
+            .is_some();
+
+        Ok(has_metadata && !has_pending_updates(rtxn, database, index)?)
+    }
+
+    /// Returns the id of the last build of the index the transaction sees, `None` if it was
+    /// never built.
+    ///
+    /// A build is published atomically by the commit of its write transaction: the readers
+    /// opened on a transaction started before see the previous graph, the ones opened after
+    /// see the new one, never a mix of both. The ids increase with every published build, to
+    /// tell when a new one must be rolled out without opening a reader, see
+    /// [`Self::build_id`]. The indexes built by a version of hannoy that didn't record them
+    /// have the id `0`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (env, database, serving): (heed::Env, hannoy::Database<Euclidean>, Reader<Euclidean>) = todo!();
+    /// let rtxn = env.read_txn()?;
+    /// if Reader::published_build_id(&rtxn, 0, database)? > Some(serving.build_id()) {
+    ///     let reader = Reader::open(&rtxn, 0, database)?;
+    ///     // warm up the new reader, then swap it with the serving one
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn published_build_id(
+        rtxn: &RoTxn,
+        index: u16,
+        database: Database<D>,
+    ) -> Result<Option<u64>> {
+        let metadata_key = Key::metadata(index);
+        let has_metadata = database
+            .remap_data_type::<DecodeIgnore>()
+            .get(rtxn, &metadata_key)
+            .map_err(Error::on_key("get", metadata_key))?
+            .is_some();
+        if !has_metadata {
+            return Ok(None);
+        }
+        get_build(rtxn, database, index).map(Some)
+    }
+
     /// Returns a reader over the database with the specified [`Distance`] type.
+    #[cfg_attr(
+        feature = "tracing-spans",
+        tracing::instrument(level = "debug", skip_all, fields(index))
+    )]
     pub fn open(rtxn: &RoTxn, index: u16, database: Database<D>) -> Result<Reader<D>> {
-        let metadata_key = Key::metadata(index);
+        let reader = Self::open_partial(rtxn, index, database)?;
+        // check if we need to rebuild
+        if has_pending_updates(rtxn, database, index)? {
+            return Err(Error::NeedBuild(index));
+        }
+        Ok(reader)
+    }
 
-        let metadata = match database.remap_data_type::<MetadataCodec>().get(rtxn, &metadata_key)? {
-            Some(metadata) => metadata,
-            None => return Err(Error::MissingMetadata(index)),
-        };
+    /// Returns a reader over the snapshot `name` of an index, as it was when the snapshot was
+    /// taken, see [`Writer::snapshot`](crate::Writer::snapshot).
+    ///
+    /// Fails with [`Error::InvalidSnapshot`] if the index has no snapshot of this name.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (rtxn, database): (heed::RoTxn, hannoy::Database<Euclidean>) = todo!();
+    /// let pinned = Reader::open_snapshot(&rtxn, 0, database, "2024-06-eval")?;
+    /// let nns = pinned.nns(10).by_vector(&rtxn, &[1.25854, -0.75598])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn open_snapshot(
+        rtxn: &RoTxn,
+        index: u16,
+        database: Database<D>,
+        name: &str,
+    ) -> Result<Reader<D>> {
+        match snapshot::snapshot_index(rtxn, database, index, name)? {
+            Some(snapshot) => Self::open(rtxn, snapshot, database),
+            None => Err(Error::InvalidSnapshot {
+                index,
+                name: name.to_owned(),
+                reason: "it wasn't taken",
+            }),
+        }
+    }
+
+    /// Returns a reader over the graph built so far, ignoring the changes that weren't built
+    /// yet rather than failing with [`Error::NeedBuild`].
+    ///
+    /// It reads the intermediate commits of a
+    /// [`HannoyBuilder::build_in_transactions`](crate::HannoyBuilder::build_in_transactions):
+    /// the searches only find the items already inserted in the graph, with a lower recall than
+    /// the complete one. The index must have been built at least once.
+    pub fn open_partial(rtxn: &RoTxn, index: u16, database: Database<D>) -> Result<Reader<D>> {
         let version =
             match database.remap_data_type::<VersionCodec>().get(rtxn, &Key::version(index))? {
                 Some(version) => version,
                 None => Version { major: 0, minor: 0, patch: 0 },
             };
+        // a more recent format may store its nodes under tags this one doesn't know
+        let format = version::get_format(rtxn, database, index)?;
+        if format > FORMAT_VERSION {
+            return Err(Error::UnknownFormat { format });
+        }
+
+        let metadata_key = Key::metadata(index);
+        let metadata = match database.remap_data_type::<MetadataCodec>().get(rtxn, &metadata_key)? {
+            Some(metadata) => metadata,
+            None => return Err(Error::MissingMetadata(index)),
+        };
+        let key = Key::tuning(index);
+        let tuning = database
+            .remap_data_type::<TuningCodec>()
+            .get(rtxn, &key)
+            .map_err(Error::on_key("get", key))?;
 
         if D::name() != metadata.distance {
             return Err(Error::UnmatchingDistance {
@@ -364,19 +1605,9 @@ impl<D: Distance> Reader<D> {
             });
         }
 
-        // check if we need to rebuild
-        if database
-            .remap_types::<PrefixCodec, DecodeIgnore>()
-            .prefix_iter(rtxn, &Prefix::updated(index))?
-            .remap_key_type::<KeyCodec>()
-            .next()
-            .is_some()
-        {
-            return Err(Error::NeedBuild(index));
-        }
-
         // Hint to the kernel that we'll probably need some vectors in RAM.
         Self::prefetch_graph(rtxn, &database, index, &metadata)?;
+        let tombstones = tombstones(rtxn, database, index)?;
 
         Ok(Reader {
             database: database.remap_data_type(),
@@ -384,8 +1615,21 @@ impl<D: Distance> Reader<D> {
             entry_points: Vec::from_iter(metadata.entry_points.iter()),
             max_level: metadata.max_level as usize,
             dimensions: metadata.dimensions.try_into().unwrap(),
-            items: metadata.items,
+            items: metadata.items - &tombstones,
+            tombstones,
+            external_items: reorder::external_items(rtxn, database, index)?,
+            coded_items: codes::get_coded_items(rtxn, database, index)?,
+            sketched_items: lsh::get_sketched_items(rtxn, database, index)?,
             version,
+            build: get_build(rtxn, database, index)?,
+            tuning,
+            weights: weights::get_weights(rtxn, database, index)?,
+            projection: projection::get_projection(rtxn, database, index)?,
+            full_dimensions: matryoshka::get_full_dimensions(rtxn, database, index)?,
+            stats: None,
+            prefetch: false,
+            batched_fetches: false,
+            layer_cache: None,
             _marker: marker::PhantomData,
         })
     }
@@ -471,127 +1715,1397 @@ impl<D: Distance> Reader<D> {
             }
         }
 
-        // If we still have memory left over try fetching other nodes in layer zero.
-        let mut queue = VecDeque::from_iter(added.iter());
-        while let Some(item) = queue.pop_front() {
-            if available_memory < largest_alloc.load(Ordering::Relaxed) {
-                return Ok(());
+        // If we still have memory left over try fetching other nodes in layer zero.
+        let mut queue = VecDeque::from_iter(added.iter());
+        while let Some(item) = queue.pop_front() {
+            if available_memory < largest_alloc.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            if let Some(Node::Links(links)) = database.get(rtxn, &Key::links(index, item, 0))? {
+                for l in links.iter() {
+                    if !added.insert(l) {
+                        continue;
+                    }
+                    if let Some(bytes) =
+                        database.remap_data_type::<Bytes>().get(rtxn, &Key::item(index, l))?
+                    {
+                        match madvise_page(bytes) {
+                            Ok(usage) => available_memory -= usage,
+                            Err(e) => {
+                                warn!(e=?e);
+                                return Ok(());
+                            }
+                        }
+                        queue.push_back(l);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of dimensions in the index.
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Returns the number of entry points to the hnsw index.
+    pub fn n_entrypoints(&self) -> usize {
+        self.entry_points.len()
+    }
+
+    /// Returns the number of vectors stored in the index.
+    pub fn n_items(&self) -> u64 {
+        self.items.len()
+    }
+
+    /// Returns all the item ids contained in this index.
+    pub fn item_ids(&self) -> &RoaringBitmap {
+        self.external_items.as_ref().unwrap_or(&self.items)
+    }
+
+    /// Returns `n` items of the index drawn uniformly at random without replacement, or all of
+    /// them if there are fewer, in increasing order. The same `seed` draws the same items from
+    /// the same index.
+    ///
+    /// The items are drawn by rank among the ids of the index, which doesn't read the database:
+    /// sampling is cheap whatever the size of the index or how sparse its ids are.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// for item in reader.sample(1000, 42) {
+    ///     let vector = reader.item_vector(&rtxn, item)?;
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn sample(&self, n: usize, seed: u64) -> Vec<ItemId> {
+        let ids = self.item_ids();
+        let len = ids.len();
+        if n as u64 >= len {
+            return ids.iter().collect();
+        }
+
+        // Floyd's algorithm draws distinct ranks with one random number per rank
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut ranks = RoaringBitmap::new();
+        for j in (len - n as u64)..len {
+            let rank = rng.gen_range(0..=j) as u32;
+            if !ranks.insert(rank) {
+                ranks.insert(j as u32);
+            }
+        }
+        ranks.iter().map(|rank| ids.select(rank).expect("the rank is below the length")).collect()
+    }
+
+    /// Returns the index of this reader in the database.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the version of the database.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the id of the build the reader sees, see [`Self::published_build_id`].
+    pub fn build_id(&self) -> u64 {
+        self.build
+    }
+
+    /// Returns a description of the index: its dimensions, distance, items, graph, quantization,
+    /// format version and the parameters of its last build.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let info = reader.info(&rtxn)?;
+    /// println!("{} items of {} dimensions compared with {}", info.items, info.dimensions, info.distance);
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn info(&self, rtxn: &RoTxn) -> Result<IndexInfo> {
+        Ok(IndexInfo {
+            index: self.index,
+            dimensions: self.dimensions,
+            query_dimensions: self.query_dimensions(),
+            distance: D::name(),
+            items: self.items.len(),
+            tombstones: self.tombstones.len(),
+            entry_points: self.entry_points.len(),
+            max_level: self.max_level,
+            quantized: codes::has_codes::<D>(),
+            calibrated: get_calibration(rtxn, self.database, self.index)?.is_some(),
+            sketch_bits: lsh::get_sketch_bits(rtxn, self.database, self.index)?,
+            version: self.version,
+            build: get_build_info(rtxn, self.database, self.index)?,
+        })
+    }
+
+    /// Returns the number of nodes in the index. Useful to run an exhaustive search.
+    pub fn n_nodes(&self, rtxn: &RoTxn) -> Result<Option<NonZeroUsize>> {
+        Ok(NonZeroUsize::new(self.database.len(rtxn)? as usize))
+    }
+
+    /// Returns the vector for item `i` that was previously added.
+    pub fn item_vector(&self, rtxn: &RoTxn, item_id: ItemId) -> Result<Option<Vec<f32>>> {
+        let Some(item_id) = self.internal_id(rtxn, item_id)? else { return Ok(None) };
+        self.internal_vector(rtxn, item_id)
+    }
+
+    /// Returns the weights of the dimensions of the index, if they were set, see
+    /// [`Writer::set_weights`](crate::Writer::set_weights).
+    pub fn weights(&self) -> Option<&[f32]> {
+        self.weights.as_deref()
+    }
+
+    /// Returns the projection the vectors of the index are reduced by, if it was set, see
+    /// [`Writer::set_projection`](crate::Writer::set_projection).
+    pub fn projection(&self) -> Option<&Projection> {
+        self.projection.as_ref()
+    }
+
+    /// Returns the dimensions of the complete vectors of a Matryoshka index, whose prefixes of
+    /// [`Self::dimensions`] are indexed, see
+    /// [`Writer::set_matryoshka`](crate::Writer::set_matryoshka).
+    pub fn matryoshka(&self) -> Option<usize> {
+        self.full_dimensions
+    }
+
+    /// Returns the number of dimensions of the queries, the input dimensions of the projection
+    /// of the index if it was set, the dimensions of the complete vectors of a Matryoshka
+    /// index or the dimensions of its vectors.
+    pub fn query_dimensions(&self) -> usize {
+        match (&self.projection, self.full_dimensions) {
+            (Some(projection), _) => projection.input_dimensions(),
+            (None, Some(full)) => full,
+            (None, None) => self.dimensions,
+        }
+    }
+
+    /// Converts a query `vector` into the representation the items of this index are stored in,
+    /// to search it several times with [`QueryBuilder::by_prepared`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let mut query = reader.prepare_query(&[1.25854, -0.75598, 0.58524])?;
+    /// reader.nns(20).by_prepared(&rtxn, &mut query)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn prepare_query(&self, vector: &[f32]) -> Result<PreparedQuery<D>> {
+        Ok(PreparedQuery {
+            item: Item::new(self.query_vector(vector)?.into_owned()),
+            full_query: self.full_dimensions.map(|_| vector.to_vec()),
+            dimensions: vector.len(),
+            scratch: SearchScratch::default(),
+        })
+    }
+
+    /// Transforms a query vector like the vectors of the items were when they were written.
+    pub(crate) fn query_vector<'v>(&self, vector: &'v [f32]) -> Result<Cow<'v, [f32]>> {
+        if vector.len() != self.query_dimensions() {
+            return Err(Error::InvalidDimensions {
+                expected: self.query_dimensions(),
+                got: vector.len(),
+            });
+        }
+        let vector = matryoshka::prefix(self.full_dimensions, self.dimensions, vector)?;
+        Ok(match projection::apply(self.projection.as_ref(), vector)? {
+            Cow::Borrowed(vector) => weights::apply(self.weights.as_deref(), D::preprocess(vector)),
+            Cow::Owned(projected) => {
+                let vector = D::preprocess(&projected);
+                Cow::Owned(weights::apply(self.weights.as_deref(), vector).into_owned())
+            }
+        })
+    }
+
+    fn internal_vector(&self, rtxn: &RoTxn, item_id: ItemId) -> Result<Option<Vec<f32>>> {
+        if self.tombstones.contains(item_id) {
+            return Ok(None);
+        }
+        Ok(get_item(self.database, self.index, rtxn, item_id)?.map(|item| {
+            let mut vec = item.vector.to_vec();
+            vec.truncate(self.dimensions());
+            vec
+        }))
+    }
+
+    /// Returns the vector for item `i` borrowed directly from the LMDB page.
+    ///
+    /// Contrary to [`Self::item_vector`] this never allocates, making it a better fit to
+    /// stream many vectors through a computation. Note that quantized codecs store their
+    /// vectors padded to a multiple of their word size, only the first [`Self::dimensions`]
+    /// values of the vector are meaningful.
+    pub fn item_vector_ref<'t>(
+        &self,
+        rtxn: &'t RoTxn,
+        item_id: ItemId,
+    ) -> Result<Option<Cow<'t, UnalignedVector<D::VectorCodec>>>> {
+        let Some(item_id) = self.internal_id(rtxn, item_id)? else { return Ok(None) };
+        if self.tombstones.contains(item_id) {
+            return Ok(None);
+        }
+        Ok(get_item(self.database, self.index, rtxn, item_id)?.map(|item| item.vector))
+    }
+
+    /// Streams the vectors of the items and returns the statistics of every dimension and of
+    /// their norms, e.g. to calibrate a quantizer or to check that the vectors were normalized.
+    ///
+    /// The statistics are computed on the vectors as they are stored, after the preprocessing
+    /// of the distance and the weights of the dimensions.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let stats = reader.vector_stats(&rtxn)?;
+    /// if stats.unit_norms < stats.items {
+    ///     println!("{} vectors aren't normalized", stats.items - stats.unit_norms);
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn vector_stats(&self, rtxn: &RoTxn) -> Result<VectorStats> {
+        let mut stats = VectorStatsBuilder::new(self.dimensions);
+        for item_id in &self.items {
+            if self.tombstones.contains(item_id) {
+                continue;
+            }
+            let Some(item) = get_item(self.database, self.index, rtxn, item_id)? else { continue };
+            stats.push(item.vector.iter());
+        }
+        Ok(stats.finish())
+    }
+
+    /// Returns how far the distribution of the vectors moved since the drift baseline was
+    /// recorded with
+    /// [`Writer::recalibrate_drift_baseline`](crate::Writer::recalibrate_drift_baseline), `None`
+    /// if it never was.
+    ///
+    /// The drift is the root mean square of the shifts of the means of the dimensions, in
+    /// standard deviations of the baseline. It stays close to `0.0` while the vectors come
+    /// from the same distribution, a drift above `0.5` is a shift the quantized codes lose
+    /// recall to. It goes through all the vectors, like [`Self::vector_stats`].
+    pub fn quantizer_drift(&self, rtxn: &RoTxn) -> Result<Option<f64>> {
+        let Some(calibration) = get_calibration(rtxn, self.database, self.index)? else {
+            return Ok(None);
+        };
+        let stats = self.vector_stats(rtxn)?;
+        Ok(Some(calibration::drift(&calibration, &stats)))
+    }
+
+    /// Returns how far a query falls outside of the distribution of the indexed vectors from the
+    /// `nns` it found, `None` if there are none or the densities of the items weren't recorded
+    /// by the last build, see
+    /// [`HannoyBuilder::record_densities`](crate::HannoyBuilder::record_densities).
+    ///
+    /// The score is the distance of the last result divided by the mean distance of the results
+    /// to their own neighbours, measured when the index was built. It stays around or below
+    /// `1.0` for the queries that look like the indexed vectors and grows with the distance of
+    /// the queries to them, e.g. to flag the queries the results of which shouldn't be trusted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn, query): (Reader<Euclidean>, heed::RoTxn, Vec<f32>) = todo!();
+    /// let nns = reader.nns(10).by_vector(&rtxn, &query)?.into_nns();
+    /// if reader.outlier_score(&rtxn, &nns)?.is_some_and(|score| score > 3.0) {
+    ///     println!("the query is far from the indexed vectors");
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn outlier_score(&self, rtxn: &RoTxn, nns: &[(ItemId, f32)]) -> Result<Option<f32>> {
+        let Some(&(_, kth_distance)) = nns.last() else { return Ok(None) };
+        let Some(radii) = get_radii(rtxn, self.database, self.index)? else { return Ok(None) };
+        let (mut sum, mut count) = (0.0, 0);
+        for &(item, _) in nns {
+            let Some(internal) = self.internal_id(rtxn, item)? else { continue };
+            if let Some(radius) = radii.get(internal) {
+                sum += radius as f64;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return Ok(None);
+        }
+        let radius = (sum / count as f64) as f32;
+        Ok(Some(kth_distance / radius.max(f32::MIN_POSITIVE)))
+    }
+
+    /// Returns the distance between two items of the index as the searches compute it, `None`
+    /// if one of them doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let distance = reader.distance(&rtxn, 12, 47)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn distance(&self, rtxn: &RoTxn, a: ItemId, b: ItemId) -> Result<Option<f32>> {
+        let Some(a) = self.stored_item(rtxn, a)? else { return Ok(None) };
+        let Some(b) = self.stored_item(rtxn, b)? else { return Ok(None) };
+        Ok(Some(D::distance(&a, &b)))
+    }
+
+    /// Returns the distances between an item and every one of the `others`, in order, `None`
+    /// for the ones that don't exist or all of them if the item doesn't.
+    ///
+    /// The distances are computed in a block, like the ones of the neighbours of an item
+    /// during a search, see [`Distance::distances`].
+    pub fn distances(
+        &self,
+        rtxn: &RoTxn,
+        item: ItemId,
+        others: &[ItemId],
+    ) -> Result<Vec<Option<f32>>> {
+        let Some(item) = self.stored_item(rtxn, item)? else { return Ok(vec![None; others.len()]) };
+        let mut found = Vec::with_capacity(others.len());
+        let mut items = Vec::with_capacity(others.len());
+        for &other in others {
+            let other = self.stored_item(rtxn, other)?;
+            found.push(other.is_some());
+            items.extend(other);
+        }
+
+        let mut distances = Vec::with_capacity(items.len());
+        D::distances(&item, &items, &mut distances);
+        let mut distances = distances.into_iter();
+        Ok(found.into_iter().map(|found| if found { distances.next() } else { None }).collect())
+    }
+
+    /// Returns the distances between every pair of the `items`: the `j`th distance of the `i`th
+    /// row is the one between the `i`th and `j`th items, `None` if one of them doesn't exist.
+    ///
+    /// Every vector is read once and the matrix is symmetric, only its upper half is computed,
+    /// in blocks like [`Self::distances`]. The matrix is quadratic in the number of items, it is
+    /// meant for up to a few thousand items, e.g. to rerank or diversify search results.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let matrix = reader.distance_matrix(&rtxn, &[12, 47, 51])?;
+    /// let distance_12_51 = matrix[0][2];
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn distance_matrix(&self, rtxn: &RoTxn, items: &[ItemId]) -> Result<Vec<Vec<Option<f32>>>> {
+        let stored =
+            items.iter().map(|&item| self.stored_item(rtxn, item)).collect::<Result<Vec<_>>>()?;
+
+        let mut matrix = vec![vec![None; items.len()]; items.len()];
+        let mut distances = Vec::with_capacity(items.len());
+        for (i, item) in stored.iter().enumerate() {
+            let Some(item) = item else { continue };
+            matrix[i][i] = Some(D::distance(item, item));
+
+            let (others, columns): (Vec<_>, Vec<_>) = stored[i + 1..]
+                .iter()
+                .enumerate()
+                .filter_map(|(j, other)| other.as_ref().map(|other| (other.clone(), i + 1 + j)))
+                .unzip();
+            distances.clear();
+            D::distances(item, &others, &mut distances);
+            for (j, &distance) in columns.into_iter().zip(&distances) {
+                matrix[i][j] = Some(distance);
+                matrix[j][i] = Some(distance);
+            }
+        }
+        Ok(matrix)
+    }
+
+    /// Returns an item from its external id, `None` if it doesn't exist or was deleted.
+    pub(crate) fn stored_item<'t>(
+        &self,
+        rtxn: &'t RoTxn,
+        item_id: ItemId,
+    ) -> Result<Option<Item<'t, D>>> {
+        let Some(item_id) = self.internal_id(rtxn, item_id)? else { return Ok(None) };
+        if self.tombstones.contains(item_id) {
+            return Ok(None);
+        }
+        get_item(self.database, self.index, rtxn, item_id)
+    }
+
+    /// Returns the instant an item was created or last refreshed at, if it was given one, see
+    /// [`Writer::set_timestamp`](crate::Writer::set_timestamp).
+    pub fn timestamp(&self, rtxn: &RoTxn, item_id: ItemId) -> Result<Option<SystemTime>> {
+        let Some(item_id) = self.internal_id(rtxn, item_id)? else { return Ok(None) };
+        get_timestamp(rtxn, self.database, self.index, item_id)
+    }
+
+    /// Returns the payload attached to an item, if it was given one, see
+    /// [`Writer::set_payload`](crate::Writer::set_payload).
+    pub fn payload<'t>(&self, rtxn: &'t RoTxn, item_id: ItemId) -> Result<Option<&'t [u8]>> {
+        let Some(item_id) = self.internal_id(rtxn, item_id)? else { return Ok(None) };
+        get_payload(rtxn, self.database, self.index, item_id)
+    }
+
+    /// Returns the factor the final score of an item is boosted by, if it was given one, see
+    /// [`Writer::set_boost`](crate::Writer::set_boost).
+    pub fn boost(&self, rtxn: &RoTxn, item_id: ItemId) -> Result<Option<f32>> {
+        let Some(item_id) = self.internal_id(rtxn, item_id)? else { return Ok(None) };
+        get_boost(rtxn, self.database, self.index, item_id)
+    }
+
+    /// Returns `true` if the index is empty.
+    pub fn is_empty(&self, rtxn: &RoTxn) -> Result<bool> {
+        self.iter(rtxn).map(|mut iter| iter.next().is_none())
+    }
+
+    /// Returns `true` if the database contains the given item.
+    pub fn contains_item(&self, rtxn: &RoTxn, item_id: ItemId) -> Result<bool> {
+        let Some(item_id) = self.internal_id(rtxn, item_id)? else { return Ok(false) };
+        if self.tombstones.contains(item_id) {
+            return Ok(false);
+        }
+        self.database
+            .remap_data_type::<DecodeIgnore>()
+            .get(rtxn, &Key::item(self.index, item_id))
+            .map(|opt| opt.is_some())
+            .map_err(Into::into)
+    }
+
+    /// Returns an iterator over the items vector.
+    ///
+    /// The items of a reordered index are iterated in the order they are stored in, with the
+    /// ids their users know them by, see [`Self::external_id`].
+    pub fn iter<'t>(&self, rtxn: &'t RoTxn) -> Result<ItemIter<'t, D>> {
+        self.iter_range(rtxn, ..)
+    }
+
+    /// Returns an iterator over the vectors of the items whose ids are in `range`.
+    ///
+    /// Items are stored ordered by id so only the requested range is read, which makes it
+    /// easy to shard export or re-embedding jobs by id range. The items of a reordered index
+    /// aren't stored in the order of their ids, all of them are read.
+    pub fn iter_range<'t>(
+        &self,
+        rtxn: &'t RoTxn,
+        range: impl RangeBounds<ItemId>,
+    ) -> Result<ItemIter<'t, D>> {
+        let (database, index, dimensions) = (self.database, self.index, self.dimensions);
+        match self.external_items {
+            Some(_) => ItemIter::new_reordered(database, index, dimensions, range, rtxn),
+            None => ItemIter::new_range(database, index, dimensions, range, rtxn),
+        }
+        .map_err(Error::on_range("iterate", Prefix::item(self.index)))
+    }
+
+    /// Returns the id the item stored under `internal` is known by, which is the same id unless
+    /// the index was reordered, see [`Writer::reorder_into`](crate::Writer::reorder_into).
+    pub fn external_id(&self, rtxn: &RoTxn, internal: ItemId) -> Result<ItemId> {
+        match self.external_items {
+            Some(_) => reorder::external_id(rtxn, self.database, self.index, internal),
+            None => Ok(internal),
+        }
+    }
+
+    /// Returns the neighbours of an item on the bottom layer of the graph, without the deleted
+    /// ones, empty if the index doesn't contain the item or it wasn't built yet.
+    pub(crate) fn neighbours(&self, rtxn: &RoTxn, item: ItemId) -> Result<Vec<ItemId>> {
+        Ok(self.neighbours_on(rtxn, item, 0)?.unwrap_or_default())
+    }
+
+    /// Returns the neighbours of an item on a `level` of the graph, without the deleted ones,
+    /// `None` if the item isn't on that level.
+    pub(crate) fn neighbours_on(
+        &self,
+        rtxn: &RoTxn,
+        item: ItemId,
+        level: usize,
+    ) -> Result<Option<Vec<ItemId>>> {
+        let Some(internal) = self.internal_id(rtxn, item)? else { return Ok(None) };
+        let Some(links) = get_links(rtxn, self.database, self.index, internal, level)? else {
+            return Ok(None);
+        };
+        let mut neighbours = Vec::with_capacity(links.len() as usize);
+        for link in links.iter().filter(|&link| link != internal) {
+            if self.items.contains(link) && !self.tombstones.contains(link) {
+                neighbours.push(self.external_id(rtxn, link)?);
+            }
+        }
+        Ok(Some(neighbours))
+    }
+
+    /// Returns the id an item is stored under, `None` if a reordered index doesn't contain it.
+    fn internal_id(&self, rtxn: &RoTxn, item: ItemId) -> Result<Option<ItemId>> {
+        match self.external_items {
+            Some(_) => reorder::internal_id(rtxn, self.database, self.index, item),
+            None => Ok(Some(item)),
+        }
+    }
+
+    /// Replaces the internal ids of the found items by their external ones.
+    fn to_external<T>(
+        &self,
+        rtxn: &RoTxn,
+        mut found: Completion<Vec<(ItemId, T)>>,
+    ) -> Result<Completion<Vec<(ItemId, T)>>> {
+        if self.external_items.is_some() {
+            let (Completion::Done(nns) | Completion::Cancelled(nns)) = &mut found;
+            for (item, _) in nns {
+                *item = reorder::external_id(rtxn, self.database, self.index, *item)?;
+            }
+        }
+        Ok(found)
+    }
+
+    /// Returns an estimation of the bytes of the pages the searches read, which should fit in
+    /// RAM for the searches to stay fast, given the `m` links per node the index was built with.
+    ///
+    /// It accounts for the vectors with their quantization, full adjacency lists and the
+    /// overhead of LMDB, and doesn't depend on the number of searches.
+    pub fn estimated_ram_for_serving(&self, m: usize) -> u64 {
+        crate::memory::serving_bytes::<D>(self.n_items(), self.dimensions, m)
+    }
+
+    /// Returns the `ef_search` recommended by the last [`crate::eval::tune_ef_search`] of this
+    /// index, if any.
+    pub fn ef_search_tuning(&self) -> Option<EfSearchTuning> {
+        self.tuning
+    }
+
+    /// Return a [`QueryBuilder`] that lets you configure and execute a search request.
+    ///
+    /// You must provide the number of items you want to receive. The search queue defaults to
+    /// the recommended [`Self::ef_search_tuning`] of the index if it was tuned.
+    pub fn nns(&self, count: usize) -> QueryBuilder<'_, D> {
+        QueryBuilder {
+            reader: self,
+            candidates: None,
+            count,
+            ef: self.default_ef_search(),
+            expansion_batch: 1,
+            rescore: None,
+            boost: None,
+            strategy: FilterStrategy::Auto,
+            scores: ScoreKind::Distance,
+            entry_points: &[],
+            dedup: None,
+            stop_within: None,
+            max_hamming: None,
+            full_query: None,
+            rerank: None,
+            #[cfg(feature = "gpu")]
+            gpu: None,
+        }
+    }
+
+    /// Writes the closest items from the `query` and their distances in `ids` and `distances`,
+    /// as many as the shorter of the two slices can hold, and returns how many were written.
+    ///
+    /// It is a shortcut for [`QueryBuilder::by_vector_into`] with the default options, for the
+    /// hot paths that reuse the same buffers for every search.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let (mut ids, mut distances) = ([0; 10], [0.0; 10]);
+    /// let found = reader.nns_into(&rtxn, &[1.25854, -0.75598], &mut ids, &mut distances)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn nns_into(
+        &self,
+        rtxn: &RoTxn,
+        query: &[f32],
+        ids: &mut [ItemId],
+        distances: &mut [f32],
+    ) -> Result<usize> {
+        let count = ids.len().min(distances.len());
+        self.nns(count).by_vector_into(rtxn, query, ids, distances)
+    }
+
+    /// Returns an estimation of the number of items whose distance to the `query` is at most
+    /// `radius`, in the unit of the distances the searches return, without retrieving them all.
+    ///
+    /// The items of a small index are all compared to the query. Otherwise the closest
+    /// neighbours of the query are searched: a radius that doesn't contain all of them is counted
+    /// exactly, up to the recall of the search, and the count of a larger one is extrapolated
+    /// from an evenly spread sample of the items.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let dense = reader.count_within(&rtxn, &[1.25854, -0.75598, 0.58524], 0.5)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn count_within(&self, rtxn: &RoTxn, query: &[f32], radius: f32) -> Result<u64> {
+        let vector = self.query_vector(query)?;
+        let vector = UnalignedVector::from_slice(&vector);
+        let query = Item { header: D::new_header(&vector), vector };
+        let within = |item_id: ItemId| -> Result<bool> {
+            let item = get_item(self.database, self.index, rtxn, item_id)?;
+            Ok(item.is_some_and(|item| D::distance(&item, &query) <= radius))
+        };
+
+        let n_items = self.items.len();
+        if n_items <= COUNT_WITHIN_SAMPLE {
+            let mut count = 0;
+            for item_id in &self.items {
+                count += within(item_id)? as u64;
+            }
+            return Ok(count);
+        }
+
+        let opt = self.nns(COUNT_WITHIN_SEARCHED);
+        let nns = SearchScratch::with_pooled(|scratch| {
+            self.find_nns_by_vec(rtxn, &query, &opt, scratch, || false)
+        })?
+        .into_inner();
+        let found = nns.iter().take_while(|&&(_, distance)| distance <= radius).count();
+        if found < COUNT_WITHIN_SEARCHED {
+            return Ok(found as u64);
+        }
+
+        let stride = n_items / COUNT_WITHIN_SAMPLE;
+        let mut sampled = 0;
+        for i in 0..COUNT_WITHIN_SAMPLE {
+            let item_id =
+                self.items.select((i * stride) as u32).expect("the stride fits the items");
+            sampled += within(item_id)? as u64;
+        }
+        let estimate = sampled as f64 / COUNT_WITHIN_SAMPLE as f64 * n_items as f64;
+        Ok((estimate.round() as u64).max(found as u64))
+    }
+
+    /// Returns the items that would count the `query` among their `k` nearest neighbours, with
+    /// their distance to it, by increasing distance, e.g. to find the items affected by a new
+    /// item before it is inserted.
+    ///
+    /// The candidates are the closest items the graph leads the query to, the ones it would be
+    /// linked with if it was inserted. A candidate is kept when the query is at most as far as
+    /// the `k`-th closest of its own neighbours on the bottom layer of the graph. Those are
+    /// chosen to be spread around it, the reverse neighbours are approximated by excess.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let affected = reader.reverse_nns(&rtxn, &[1.25854, -0.75598, 0.58524], 10)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn reverse_nns(&self, rtxn: &RoTxn, query: &[f32], k: usize) -> Result<Vec<(ItemId, f32)>> {
+        let vector = self.query_vector(query)?;
+        let vector = UnalignedVector::from_slice(&vector);
+        let query = Item { header: D::new_header(&vector), vector };
+        self.reverse_nns_of(rtxn, &query, k, None)
+    }
+
+    /// Returns the items that count `item` among their `k` nearest neighbours, with their
+    /// distance to it, by increasing distance. `None` if the item doesn't exist.
+    ///
+    /// See [`Self::reverse_nns`] for how they are approximated.
+    pub fn reverse_nns_by_item(
+        &self,
+        rtxn: &RoTxn,
+        item: ItemId,
+        k: usize,
+    ) -> Result<Option<Vec<(ItemId, f32)>>> {
+        let Some(internal) = self.internal_id(rtxn, item)? else { return Ok(None) };
+        let Some(query) = self.stored_item(rtxn, item)? else { return Ok(None) };
+        self.reverse_nns_of(rtxn, &query, k, Some(internal)).map(Some)
+    }
+
+    fn reverse_nns_of(
+        &self,
+        rtxn: &RoTxn,
+        query: &Item<D>,
+        k: usize,
+        exclude: Option<ItemId>,
+    ) -> Result<Vec<(ItemId, f32)>> {
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+        let opt = self.nns(k * REVERSE_NNS_CANDIDATES);
+        let candidates = SearchScratch::with_pooled(|scratch| {
+            self.find_nns_by_vec(rtxn, query, &opt, scratch, || false)
+        })?
+        .into_inner();
+
+        let mut reverse = Vec::new();
+        let mut distances = Vec::new();
+        for (candidate, distance) in candidates {
+            if Some(candidate) == exclude {
+                continue;
+            }
+            let Some(item) = get_item(self.database, self.index, rtxn, candidate)? else {
+                continue;
+            };
+            let Some(links) = get_links(rtxn, self.database, self.index, candidate, 0)? else {
+                continue;
+            };
+            distances.clear();
+            for link in links.iter().filter(|&link| link != candidate && self.items.contains(link))
+            {
+                if let Some(neighbour) = get_item(self.database, self.index, rtxn, link)? {
+                    distances.push(OrderedFloat(D::distance(&item, &neighbour)));
+                }
+            }
+            // an item with fewer than `k` neighbours counts any other item among them
+            let kth = match distances.len().checked_sub(k) {
+                Some(_) => *distances.select_nth_unstable(k - 1).1,
+                None => OrderedFloat(f32::INFINITY),
+            };
+            if OrderedFloat(distance) <= kth {
+                reverse.push((self.external_id(rtxn, candidate)?, distance));
+            }
+        }
+        Ok(reverse)
+    }
+
+    /// Returns the distance of `item` to its `k`-th nearest neighbour, itself excluded, `None` if
+    /// it doesn't exist, `k` is `0` or the graph doesn't lead to `k` other items.
+    ///
+    /// It is the core distance of the item in density-based clusterings like HDBSCAN, see
+    /// [`Self::kth_distances`] to compute them for all the items.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let core_distance = reader.kth_distance(&rtxn, 5, 10)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn kth_distance(&self, rtxn: &RoTxn, item: ItemId, k: usize) -> Result<Option<f32>> {
+        let Some(internal) = self.internal_id(rtxn, item)? else { return Ok(None) };
+        if !self.items.contains(internal) {
+            return Ok(None);
+        }
+        SearchScratch::with_pooled(|scratch| self.internal_kth_distance(rtxn, internal, k, scratch))
+    }
+
+    /// Returns the distance of every item to its `k`-th nearest neighbour, itself excluded, in
+    /// the order the items are stored in, see [`Self::kth_distance`]. The items the graph
+    /// doesn't lead to `k` other items from are skipped.
+    ///
+    /// The neighbours of every item are searched on the bottom layer of the graph, starting
+    /// from the item itself, rather than from the entry points.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let core_distances = reader.kth_distances(&rtxn, 10)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn kth_distances(&self, rtxn: &RoTxn, k: usize) -> Result<Vec<(ItemId, f32)>> {
+        let mut distances = Vec::with_capacity(self.items.len() as usize);
+        SearchScratch::with_pooled(|scratch| {
+            for internal in &self.items {
+                if let Some(distance) = self.internal_kth_distance(rtxn, internal, k, scratch)? {
+                    distances.push((self.external_id(rtxn, internal)?, distance));
+                }
+            }
+            Ok(distances)
+        })
+    }
+
+    fn internal_kth_distance(
+        &self,
+        rtxn: &RoTxn,
+        internal: ItemId,
+        k: usize,
+        scratch: &mut SearchScratch,
+    ) -> Result<Option<f32>> {
+        let Some(nth) = k.checked_sub(1) else { return Ok(None) };
+        let Some(query) = get_item(self.database, self.index, rtxn, internal)? else {
+            return Ok(None);
+        };
+        scratch.clear();
+        let ef = self.default_ef_search().max(k + 1);
+        let mut visitor = Visitor::new(vec![internal], 0, ef, None);
+        visitor.tombstones = Some(&self.tombstones).filter(|t| !t.is_empty());
+        let mut found = visitor.visit(&query, self, rtxn, scratch, &|| false)?.into_inner();
+        let kth = found.drain_asc().filter(|&(_, item)| item != internal).nth(nth);
+        Ok(kth.map(|(OrderedFloat(distance), _)| distance))
+    }
+
+    /// Returns the centroid of the vectors of the `items`, as they are stored, `None` if none of
+    /// them exist.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let centroid = reader.centroid(&rtxn, &roaring::RoaringBitmap::from_iter([5, 12, 47]))?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn centroid(&self, rtxn: &RoTxn, items: &RoaringBitmap) -> Result<Option<Vec<f32>>> {
+        self.weighted_centroid(rtxn, items.iter().map(|item| (item, 1.0)))
+    }
+
+    /// Returns the medoid of the `items`, the one closest to their centroid, with its distance
+    /// to the centroid. `None` if none of them exist.
+    ///
+    /// The medoid is searched in the graph among the `items` rather than by comparing all of
+    /// them to the centroid, only the items inserted in the graph by a build are found.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let medoid = reader.medoid(&rtxn, &roaring::RoaringBitmap::from_iter([5, 12, 47]))?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn medoid(&self, rtxn: &RoTxn, items: &RoaringBitmap) -> Result<Option<(ItemId, f32)>> {
+        let Some(centroid) = self.centroid(rtxn, items)? else { return Ok(None) };
+
+        let vector = UnalignedVector::from_vec(centroid);
+        let query = Item { header: D::new_header(&vector), vector };
+        let mut opt = self.nns(1);
+        opt.candidates(items);
+        let nns = SearchScratch::with_pooled(|scratch| {
+            self.nns_by_vec(rtxn, &query, &opt, scratch, || false)
+        })?
+        .into_inner();
+        Ok(nns.into_iter().next())
+    }
+
+    /// Returns the centroid of the vectors of the `items` weighted by their `f32`, `None` if
+    /// none of them exist or their weights sum to zero.
+    fn weighted_centroid(
+        &self,
+        rtxn: &RoTxn,
+        items: impl IntoIterator<Item = (ItemId, f32)>,
+    ) -> Result<Option<Vec<f32>>> {
+        let mut centroid = vec![0.0; self.dimensions];
+        let mut total = 0.0;
+        for (item, weight) in items {
+            let Some(vector) = self.item_vector(rtxn, item)? else { continue };
+            centroid.iter_mut().zip(vector).for_each(|(c, x)| *c += weight * x);
+            total += weight;
+        }
+        if total == 0.0 {
+            return Ok(None);
+        }
+        centroid.iter_mut().for_each(|c| *c /= total);
+        Ok(Some(centroid))
+    }
+
+    /// Runs a batch of related searches in the same read transaction, which can't observe
+    /// different states of the index in the middle of the batch. The results are in the order
+    /// of the `queries`, `None` for the searches from an item that doesn't exist.
+    ///
+    /// The `rtxn` must be the transaction the reader was opened with for the searches to see
+    /// the state of the index the reader describes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, Query, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let results = reader.multi_search(&rtxn, &[
+    ///     Query::Vector { vector: &[1.25854, -0.75598, 0.58524], count: 20 },
+    ///     Query::Item { item: 5, count: 10 },
+    /// ])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn multi_search(&self, rtxn: &RoTxn, queries: &[Query]) -> Result<Vec<Option<Searched>>> {
+        queries
+            .iter()
+            .map(|query| match *query {
+                Query::Vector { vector, count } => {
+                    self.nns(count).by_vector(rtxn, vector).map(Some)
+                }
+                Query::Item { item, count } => self.nns(count).by_item(rtxn, item),
+            })
+            .collect()
+    }
+
+    /// Returns the approximately `count` most distant items from the `query`, by decreasing
+    /// distance, e.g. to sample diverse items or find outliers.
+    ///
+    /// The bottom layer of the graph is traversed towards the items the furthest from the
+    /// query, from the entry points and a few items spread over the index to avoid getting
+    /// stuck on a local maximum, which reads far fewer items than a scan.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let outliers = reader.furthest(&rtxn, &[1.25854, -0.75598, 0.58524], 10)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn furthest(
+        &self,
+        rtxn: &RoTxn,
+        query: &[f32],
+        count: usize,
+    ) -> Result<Vec<(ItemId, f32)>> {
+        let vector = self.query_vector(query)?;
+        let vector = UnalignedVector::from_slice(&vector);
+        let query = Item { header: D::new_header(&vector), vector };
+        let ef = count.max(self.default_ef_search());
+
+        let stride = (self.items.len() / FURTHEST_RESTARTS).max(1);
+        let restarts =
+            (0..FURTHEST_RESTARTS).filter_map(|i| self.items.select((i * stride) as u32));
+        let mut frontier: Vec<_> = self.entry_points.iter().copied().chain(restarts).collect();
+        let mut visited = RoaringBitmap::new();
+        // the candidates to expand, the furthest first, and the furthest items found so far
+        let mut candidates = BinaryHeap::new();
+        let mut furthest = BinaryHeap::new();
+
+        loop {
+            for item_id in frontier.drain(..) {
+                if !visited.insert(item_id) {
+                    continue;
+                }
+                let Some(item) = get_item(self.database, self.index, rtxn, item_id)? else {
+                    continue;
+                };
+                let distance = OrderedFloat(D::distance(&item, &query));
+                if furthest.len() < ef
+                    || furthest.peek().is_some_and(|&Reverse((d, _))| distance > d)
+                {
+                    candidates.push((distance, item_id));
+                    // the deleted items are traversed but not returned
+                    if !self.tombstones.contains(item_id) {
+                        furthest.push(Reverse((distance, item_id)));
+                        if furthest.len() > ef {
+                            furthest.pop();
+                        }
+                    }
+                }
+            }
+
+            let Some((distance, item_id)) = candidates.pop() else { break };
+            if furthest.len() >= ef && furthest.peek().is_some_and(|&Reverse((d, _))| distance < d)
+            {
+                break;
+            }
+            if let Some(links) = get_links(rtxn, self.database, self.index, item_id, 0)? {
+                frontier.extend(links.iter());
+            }
+        }
+
+        let mut found = Vec::with_capacity(count.min(furthest.len()));
+        for Reverse((OrderedFloat(distance), item_id)) in
+            furthest.into_sorted_vec().into_iter().take(count)
+        {
+            found.push((self.external_id(rtxn, item_id)?, distance));
+        }
+        Ok(found)
+    }
+
+    /// Returns the size of the search queue of the queries that don't set it.
+    pub(crate) fn default_ef_search(&self) -> usize {
+        self.tuning.map_or(DEFAULT_EF_SEARCH, |tuning| tuning.ef_search)
+    }
+
+    /// Records the latency, the queue size and the outcome of every search made through this
+    /// reader in `stats`.
+    ///
+    /// Readers live as long as their transaction, the same statistics can be attached to all
+    /// of them to follow the searches over time.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, SearchStats, distances::Euclidean};
+    /// # let (rtxn, database): (heed::RoTxn, hannoy::Database<Euclidean>) = todo!();
+    /// use std::sync::Arc;
+    ///
+    /// let stats = Arc::new(SearchStats::new());
+    /// let reader = Reader::open(&rtxn, 0, database)?.with_search_stats(stats.clone());
+    /// reader.nns(10).by_item(&rtxn, 5)?;
+    /// let window = stats.take();
+    /// println!("{} searches, p99 under {:?}", window.searches, window.latency_quantile(0.99));
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn with_search_stats(mut self, stats: Arc<SearchStats>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Asks the kernel to read the vectors of the neighbours of every expanded item ahead, before
+    /// computing their distances one by one, so that the page faults of a cold cache overlap
+    /// instead of being paid in sequence.
+    ///
+    /// It only helps with vectors large enough to be stored in their own LMDB pages, a few
+    /// hundreds of dimensions, when the index doesn't fit in RAM. It costs a system call per
+    /// visited item otherwise.
+    pub fn with_prefetch(mut self) -> Self {
+        self.prefetch = true;
+        self
+    }
+
+    /// Reads the vectors of the neighbours of every expanded item whose ids are close to each
+    /// other with a single range scan of the b-tree instead of one lookup each.
+    ///
+    /// It helps the fast distances, whose searches spend most of their time in LMDB, on the
+    /// indexes whose neighbours have close ids like the reordered ones, see
+    /// [`Writer::reorder_into`](crate::Writer::reorder_into). Every scan allocates its bounds,
+    /// the searches don't only allocate their results anymore.
+    pub fn with_batched_fetches(mut self) -> Self {
+        self.batched_fetches = true;
+        self
+    }
+
+    /// Pushes the items of the sorted `points` to the `block`, the runs of close ids being read
+    /// with a range scan, see [`Self::with_batched_fetches`].
+    fn fetch_block<'a>(
+        &'a self,
+        rtxn: &'a RoTxn,
+        points: &[ItemId],
+        block: &mut ArrayVec<[Item<'a, D>; DISTANCE_BLOCK]>,
+    ) -> Result<()> {
+        let mut runs = points.chunk_by(|a, b| b - a <= BATCHED_FETCH_MAX_GAP);
+        runs.try_for_each(|run| match run {
+            [] => Ok(()),
+            &[point] => {
+                block.push(self.layer_item(rtxn, None, None, point)?);
+                Ok(())
             }
-            if let Some(Node::Links(links)) = database.get(rtxn, &Key::links(index, item, 0))? {
-                for l in links.iter() {
-                    if !added.insert(l) {
+            [first, .., last] => {
+                let start = Key::item(self.index, *first).to_bytes();
+                let end = Key::item(self.index, *last).to_bytes();
+                let range = (Bound::Included(&start[..]), Bound::Included(&end[..]));
+                let raw = self.database.remap_key_type::<Bytes>();
+                let mut wanted = run.iter().copied().peekable();
+                for result in raw.range(rtxn, &range)?.lazily_decode_data() {
+                    let (key, node) = result?;
+                    let id = BigEndian::read_u32(&key[3..7]);
+                    if wanted.next_if_eq(&id).is_none() {
                         continue;
                     }
-                    if let Some(bytes) =
-                        database.remap_data_type::<Bytes>().get(rtxn, &Key::item(index, l))?
-                    {
-                        match madvise_page(bytes) {
-                            Ok(usage) => available_memory -= usage,
-                            Err(e) => {
-                                warn!(e=?e);
-                                return Ok(());
-                            }
+                    match node.decode().map_err(heed::Error::Decoding)? {
+                        Node::Item(item) => block.push(item),
+                        Node::Links(_) => {
+                            return Err(Error::unexpected_node(Key::item(self.index, id), "Item"))
                         }
-                        queue.push_back(l);
                     }
                 }
+                match wanted.next() {
+                    Some(missing) => Err(Error::missing_key(Key::item(self.index, missing))),
+                    None => Ok(()),
+                }
             }
-        }
+        })
+    }
+
+    /// Asks the kernel to read the pages of the vectors of the `items` ahead.
+    #[cfg(not(windows))]
+    fn prefetch_items(&self, rtxn: &RoTxn, items: impl Iterator<Item = ItemId>) -> Result<()> {
+        use heed::types::Bytes;
+        use madvise::AccessPattern;
 
+        let page_size = page_size::get();
+        for item in items {
+            let key = Key::item(self.index, item);
+            let Some(bytes) = self.database.remap_data_type::<Bytes>().get(rtxn, &key)? else {
+                continue;
+            };
+            let start = bytes.as_ptr() as usize;
+            let start_page = start - (start % page_size);
+            let length = start + bytes.len() - start_page;
+            // it's only an advice, the search works the same if the kernel ignores it
+            unsafe {
+                let _ = madvise::madvise(start_page as *const u8, length, AccessPattern::WillNeed);
+            }
+        }
         Ok(())
     }
 
-    /// Returns the number of dimensions in the index.
-    pub fn dimensions(&self) -> usize {
-        self.dimensions
+    #[cfg(windows)]
+    fn prefetch_items(&self, _rtxn: &RoTxn, _items: impl Iterator<Item = ItemId>) -> Result<()> {
+        // madvise crate does not support windows.
+        Ok(())
     }
 
-    /// Returns the number of entry points to the hnsw index.
-    pub fn n_entrypoints(&self) -> usize {
-        self.entry_points.len()
+    /// Keeps the nodes of the layers from `min_layer` up decoded in memory, so that the
+    /// searches read the few nodes they go through before the bottom layer, always the same
+    /// ones, without looking them up and decoding them.
+    ///
+    /// A layer holds about one `M`-th of the items of the layer under it: a `min_layer` of `1`
+    /// caches every upper layer while the top layers only hold a handful of items. The bottom
+    /// layer is never cached. The cache is ignored by the searches once the index is built
+    /// again, which a reader opened before doesn't see anyway.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, distances::Euclidean};
+    /// # let (database, rtxn): (hannoy::Database<Euclidean>, heed::RoTxn) = todo!();
+    /// let reader = Reader::open(&rtxn, 0, database)?.with_layer_cache(&rtxn, 2)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn with_layer_cache(mut self, rtxn: &RoTxn, min_layer: usize) -> Result<Self> {
+        let min_layer = min_layer.max(1);
+        let mut cache = LayerCache {
+            build: get_build(rtxn, self.database, self.index)?,
+            min_layer,
+            items: HashMap::default(),
+            links: HashMap::default(),
+        };
+
+        if min_layer <= self.max_level {
+            let prefix = Prefix::links(self.index);
+            let iter = self
+                .database
+                .remap_types::<PrefixCodec, Bytes>()
+                .prefix_iter(rtxn, &prefix)
+                .map_err(Error::on_range("iterate", prefix))?
+                .remap_key_type::<KeyCodec>();
+            for result in iter {
+                let (key, bytes) = result?;
+                let (item, layer) = (key.node.item, key.node.layer);
+                if usize::from(layer) < min_layer {
+                    continue;
+                }
+                let Node::Links(Links { links }) =
+                    NodeCodec::<D>::bytes_decode(bytes).map_err(heed::Error::Decoding)?
+                else {
+                    return Err(Error::unexpected_node(key, "Links"));
+                };
+                cache.links.insert((item, layer), links.into_owned());
+                if !cache.items.contains_key(&item) {
+                    let vector = get_item(self.database, self.index, rtxn, item)?
+                        .ok_or_else(|| Error::missing_key(Key::item(self.index, item)))?;
+                    cache.items.insert(item, vector.into_owned());
+                }
+            }
+        }
+
+        self.layer_cache = Some(cache);
+        Ok(self)
     }
 
-    /// Returns the number of vectors stored in the index.
-    pub fn n_items(&self) -> u64 {
-        self.items.len()
+    /// Returns the layer cache if the index wasn't built again since it was filled.
+    pub(crate) fn fresh_layer_cache(&self, rtxn: &RoTxn) -> Result<Option<&LayerCache<D>>> {
+        let Some(cache) = &self.layer_cache else { return Ok(None) };
+        Ok((get_build(rtxn, self.database, self.index)? == cache.build).then_some(cache))
     }
 
-    /// Returns all the item ids contained in this index.
-    pub fn item_ids(&self) -> &RoaringBitmap {
-        &self.items
+    /// Returns the contiguous quantized codes of the items if they're up to date.
+    fn codes<'a>(&'a self, rtxn: &'a RoTxn) -> Result<Option<Codes<'a>>> {
+        match &self.coded_items {
+            Some(items) => codes::get_codes(rtxn, self.database, self.index, items),
+            None => Ok(None),
+        }
     }
 
-    /// Returns the index of this reader in the database.
-    pub fn index(&self) -> u16 {
-        self.index
+    /// Returns an item the traversal of a layer goes through, from the `cache` if it holds it
+    /// or the contiguous `codes`.
+    fn layer_item<'a>(
+        &'a self,
+        rtxn: &'a RoTxn,
+        cache: Option<&'a LayerCache<D>>,
+        codes: Option<Codes<'a>>,
+        item: ItemId,
+    ) -> Result<Item<'a, D>> {
+        if let Some(cached) = cache.and_then(|cache| cache.items.get(&item)) {
+            return Ok(Item { header: cached.header, vector: Cow::Borrowed(&cached.vector) });
+        }
+        match codes.and_then(|codes| codes.get(item)) {
+            Some(item) => Ok(item),
+            None => get_item(self.database, self.index, rtxn, item)?
+                .ok_or_else(|| Error::missing_key(Key::item(self.index, item))),
+        }
     }
 
-    /// Returns the version of the database.
-    pub fn version(&self) -> Version {
-        self.version
+    /// Returns the links of an item in a layer, from the `cache` if it holds them.
+    fn layer_links<'a>(
+        &'a self,
+        rtxn: &'a RoTxn,
+        cache: Option<&'a LayerCache<D>>,
+        item: ItemId,
+        level: usize,
+    ) -> Result<SerializedBitmap<'a>> {
+        if let Some(links) = cache.and_then(|cache| cache.links.get(&(item, level as LayerId))) {
+            return Ok(SerializedBitmap::Borrowed(links));
+        }
+        get_serialized_links(rtxn, self.database, self.index, item, level)?
+            .ok_or_else(|| Error::missing_key(Key::links(self.index, item, level as u8)))
     }
 
-    /// Returns the number of nodes in the index. Useful to run an exhaustive search.
-    pub fn n_nodes(&self, rtxn: &RoTxn) -> Result<Option<NonZeroUsize>> {
-        Ok(NonZeroUsize::new(self.database.len(rtxn)? as usize))
+    /// Returns the statistics attached with [`Self::with_search_stats`].
+    pub fn search_stats(&self) -> Option<&Arc<SearchStats>> {
+        self.stats.as_ref()
     }
 
-    /// Returns the vector for item `i` that was previously added.
-    pub fn item_vector(&self, rtxn: &RoTxn, item_id: ItemId) -> Result<Option<Vec<f32>>> {
-        Ok(get_item(self.database, self.index, rtxn, item_id)?.map(|item| {
-            let mut vec = item.vector.to_vec();
-            vec.truncate(self.dimensions());
-            vec
-        }))
+    /// Runs `search` and records it in the attached [`SearchStats`], if any. Searches of missing
+    /// items aren't recorded.
+    fn recorded<T>(
+        &self,
+        opt: &QueryBuilder<D>,
+        search: impl FnOnce() -> Result<Option<Completion<T>>>,
+    ) -> Result<Option<Completion<T>>> {
+        let Some(stats) = &self.stats else { return search() };
+        let started = Instant::now();
+        let found = search()?;
+        if let Some(completion) = &found {
+            let cancelled = matches!(completion, Completion::Cancelled(_));
+            stats.record(opt.ef.max(opt.count), started.elapsed(), cancelled);
+        }
+        Ok(found)
     }
 
-    /// Returns `true` if the index is empty.
-    pub fn is_empty(&self, rtxn: &RoTxn) -> Result<bool> {
-        self.iter(rtxn).map(|mut iter| iter.next().is_none())
+    fn nns_by_vec(
+        &self,
+        rtxn: &RoTxn,
+        query: &Item<D>,
+        opt: &QueryBuilder<D>,
+        scratch: &mut SearchScratch,
+        cancel_fn: impl Fn() -> bool,
+    ) -> Result<Completion<Vec<(ItemId, f32)>>> {
+        let (relabeled, internal);
+        let opt = match (&self.external_items, opt.candidates) {
+            (Some(_), Some(candidates)) => {
+                internal = self.internal_candidates(rtxn, candidates)?;
+                relabeled = QueryBuilder { candidates: Some(&internal), ..*opt };
+                &relabeled
+            }
+            _ => opt,
+        };
+        let oversampled = opt.oversampled();
+        let search =
+            || self.find_nns_by_vec(rtxn, query, &oversampled, scratch, cancel_fn).map(Some);
+        let mut found =
+            self.recorded(&oversampled, search)?.expect("searching a vector always completes");
+        if let Some(full_query) = opt.full_query {
+            let full_query = matryoshka::full_item::<D>(full_query);
+            let (Completion::Done(nns) | Completion::Cancelled(nns)) = &mut found;
+            matryoshka::rerank(rtxn, self.database, self.index, &full_query, nns)?;
+        }
+        let found = self.to_external(rtxn, found)?;
+        let found = self.rescored(rtxn, found, opt)?;
+        self.deduplicated(rtxn, found, opt)
     }
 
-    /// Returns `true` if the database contains the given item.
-    pub fn contains_item(&self, rtxn: &RoTxn, item_id: ItemId) -> Result<bool> {
-        self.database
-            .remap_data_type::<DecodeIgnore>()
-            .get(rtxn, &Key::item(self.index, item_id))
-            .map(|opt| opt.is_some())
-            .map_err(Into::into)
+    /// Replaces the distances of the items found by an oversampled search by their scores and
+    /// sorts them by score, see [`QueryBuilder::rescore`] and [`QueryBuilder::boosted`].
+    fn rescored(
+        &self,
+        rtxn: &RoTxn,
+        mut found: Completion<Vec<(ItemId, f32)>>,
+        opt: &QueryBuilder<D>,
+    ) -> Result<Completion<Vec<(ItemId, f32)>>> {
+        if opt.rescore.is_none() && opt.boost.is_none() {
+            return Ok(found);
+        }
+        let (Completion::Done(nns) | Completion::Cancelled(nns)) = &mut found;
+        if let Some((_, rescore)) = opt.rescore {
+            for (item, distance) in nns.iter_mut() {
+                *distance = rescore(*item, *distance, self.payload(rtxn, *item)?);
+            }
+        }
+        if opt.boost.is_some() {
+            for (item, distance) in nns.iter_mut() {
+                if let Some(boost) = self.boost(rtxn, *item)? {
+                    *distance /= boost;
+                }
+            }
+        }
+        // unlike the distances the scores can be negative
+        nns.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        Ok(found)
     }
 
-    /// Returns an iterator over the items vector.
-    pub fn iter<'t>(&self, rtxn: &'t RoTxn) -> Result<ItemIter<'t, D>> {
-        ItemIter::new(self.database, self.index, self.dimensions, rtxn).map_err(Into::into)
+    /// Removes the items found by an oversampled search that are too close to a better one and
+    /// keeps the `count` best of the others, see [`QueryBuilder::dedup`].
+    fn deduplicated(
+        &self,
+        rtxn: &RoTxn,
+        mut found: Completion<Vec<(ItemId, f32)>>,
+        opt: &QueryBuilder<D>,
+    ) -> Result<Completion<Vec<(ItemId, f32)>>> {
+        let (Completion::Done(nns) | Completion::Cancelled(nns)) = &mut found;
+        if let Some(threshold) = opt.dedup {
+            let mut kept = Vec::with_capacity(opt.count);
+            let mut unique = Vec::with_capacity(opt.count);
+            for &(id, distance) in nns.iter() {
+                if unique.len() == opt.count {
+                    break;
+                }
+                let Some(item) = self.stored_item(rtxn, id)? else { continue };
+                if kept.iter().all(|kept| D::distance(kept, &item) >= threshold) {
+                    kept.push(item);
+                    unique.push((id, distance));
+                }
+            }
+            *nns = unique;
+        }
+        nns.truncate(opt.count);
+        Ok(found)
     }
 
-    /// Return a [`QueryBuilder`] that lets you configure and execute a search request.
-    ///
-    /// You must provide the number of items you want to receive.
-    pub fn nns(&self, count: usize) -> QueryBuilder<'_, D> {
-        QueryBuilder { reader: self, candidates: None, count, ef: DEFAULT_EF_SEARCH }
+    /// Returns the internal ids of the `candidates` of a reordered index.
+    fn internal_candidates(
+        &self,
+        rtxn: &RoTxn,
+        candidates: &RoaringBitmap,
+    ) -> Result<RoaringBitmap> {
+        let Some(external_items) = &self.external_items else { return Ok(candidates.clone()) };
+        let candidates = candidates & external_items;
+        let (Some(min), Some(max)) = (candidates.min(), candidates.max()) else {
+            return Ok(RoaringBitmap::new());
+        };
+        // the ids are read in one pass over the range of the candidates
+        let range = Key::internal_id(self.index, min)..=Key::internal_id(self.index, max);
+        let mut internal = RoaringBitmap::new();
+        for result in self.database.remap_data_type::<U32<BigEndian>>().range(rtxn, &range)? {
+            let (key, item) = result?;
+            if candidates.contains(key.node.item) {
+                internal.insert(item);
+            }
+        }
+        Ok(internal)
     }
 
-    fn nns_by_vec(
+    // In tests the linear search threshold is zero which makes the comparison always false.
+    #[cfg_attr(test, allow(clippy::absurd_extreme_comparisons))]
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(name = "search", level = "debug", skip_all, fields(index = self.index, count = opt.count, ef = opt.ef, filtered = opt.candidates.is_some(), visited = tracing::field::Empty)))]
+    fn find_nns_by_vec(
         &self,
         rtxn: &RoTxn,
         query: &Item<D>,
         opt: &QueryBuilder<D>,
+        scratch: &mut SearchScratch,
         cancel_fn: impl Fn() -> bool,
     ) -> Result<Completion<Vec<(ItemId, f32)>>> {
         use Completion::*;
 
         // If we will never find any candidates, return an empty vector
-        if opt.candidates.is_some_and(|c| self.item_ids().is_disjoint(c)) {
+        if opt.candidates.is_some_and(|c| self.items.is_disjoint(c)) {
             return Ok(Done(Vec::new()));
         }
 
-        // If the number of candidates is less than a given threshold, perform linear search
-        if let Some(candidates) = opt.candidates.filter(|c| c.len() < LINEAR_SEARCH_THRESHOLD) {
+        if let Some(candidates) = opt.candidates.filter(|_| self.scans_linearly(opt)) {
+            scratch.record(|| TraceEvent::LinearScan { candidates: candidates.len() });
+            #[cfg(feature = "gpu")]
+            if let Some(gpu) = opt.gpu.filter(|_| GpuDistances::supports::<D>()) {
+                let query: Vec<f32> = query.vector.iter().collect();
+                let mut found =
+                    self.gpu_brute_force_search(rtxn, gpu, &query, candidates, opt.count)?;
+                return Ok(Done(found.pop().unwrap_or_default()));
+            }
             return self.brute_force_search(query, rtxn, candidates, opt.count, cancel_fn);
         }
 
         // exhaustive search
-        self.hnsw_search(query, rtxn, opt, cancel_fn)
+        self.hnsw_search(query, rtxn, opt, scratch, cancel_fn)
     }
 
     /// Directly retrieves items in the candidate list and ranks them by distance to the query.
@@ -604,26 +3118,102 @@ impl<D: Distance> Reader<D> {
         cancel_fn: impl Fn() -> bool,
     ) -> Result<Completion<Vec<(ItemId, f32)>>> {
         use Completion::*;
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
 
         let mut item_distances = Vec::with_capacity(candidates.len() as usize);
+        let codes = self.codes(rtxn)?;
 
         for item_id in candidates {
             if cancel_fn() {
                 return Ok(Cancelled(item_distances));
             }
 
-            let Some(vector) = self.item_vector(rtxn, item_id)? else { continue };
-            let vector = UnalignedVector::from_vec(vector);
-            let item = Item { header: D::new_header(&vector), vector };
+            if self.tombstones.contains(item_id) {
+                continue;
+            }
+            let item = match codes.and_then(|codes| codes.get(item_id)) {
+                Some(item) => item,
+                None => match get_item(self.database, self.index, rtxn, item_id)? {
+                    Some(item) => item,
+                    None => continue,
+                },
+            };
             let distance = D::distance(&item, query);
             item_distances.push((item_id, distance));
         }
         item_distances.sort_by_key(|(_, dist)| OrderedFloat(*dist));
         item_distances.truncate(count);
 
+        #[cfg(feature = "metrics")]
+        crate::monitoring::record_search::<D>(self.index, candidates.len(), started.elapsed());
+
         Ok(Done(item_distances))
     }
 
+    /// Ranks the candidates by their distances to every one of the `queries`, given row by row,
+    /// computed on the GPU, and returns the `count` closest ones of every query.
+    #[cfg(feature = "gpu")]
+    fn gpu_brute_force_search(
+        &self,
+        rtxn: &RoTxn,
+        gpu: &GpuDistances,
+        queries: &[f32],
+        candidates: &RoaringBitmap,
+        count: usize,
+    ) -> Result<Vec<Vec<(ItemId, f32)>>> {
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+
+        let mut ids = Vec::with_capacity(candidates.len() as usize);
+        let mut vectors = Vec::with_capacity(candidates.len() as usize * self.dimensions);
+        for item_id in candidates - &self.tombstones {
+            if let Some(item) = get_item(self.database, self.index, rtxn, item_id)? {
+                ids.push(item_id);
+                vectors.extend(item.vector.iter());
+            }
+        }
+
+        let n_queries = queries.len() / self.dimensions;
+        if ids.is_empty() {
+            return Ok(vec![Vec::new(); n_queries]);
+        }
+        let distances = gpu.distances::<D>(queries, &vectors, self.dimensions)?;
+        let found = distances
+            .chunks_exact(ids.len())
+            .map(|distances| {
+                let mut item_distances: Vec<_> =
+                    ids.iter().copied().zip(distances.iter().copied()).collect();
+                item_distances.sort_by_key(|(_, dist)| OrderedFloat(*dist));
+                item_distances.truncate(count);
+                item_distances
+            })
+            .collect();
+
+        #[cfg(feature = "metrics")]
+        crate::monitoring::record_search::<D>(self.index, candidates.len(), started.elapsed());
+
+        Ok(found)
+    }
+
+    /// Returns the sketches the search prunes the neighbours with, if it asked for it and the
+    /// index was built with them.
+    fn prefilter<'a>(
+        &'a self,
+        rtxn: &'a RoTxn,
+        query: &Item<D>,
+        opt: &QueryBuilder<D>,
+    ) -> Result<Option<Prefilter<'a>>> {
+        let Some(max_hamming) = opt.max_hamming else { return Ok(None) };
+        let Some(items) = &self.sketched_items else { return Ok(None) };
+        let sketches = lsh::get_sketches(rtxn, self.database, self.index, items)?;
+        Ok(sketches.map(|sketches| Prefilter {
+            query: sketches.sketch(query),
+            sketches,
+            max_hamming,
+        }))
+    }
+
     /// Hnsw search according to arXiv:1603.09320.
     ///
     /// We perform greedy beam search from the top layer to the bottom, where the search frontier
@@ -638,27 +3228,58 @@ impl<D: Distance> Reader<D> {
         query: &Item<D>,
         rtxn: &RoTxn,
         opt: &QueryBuilder<D>,
+        scratch: &mut SearchScratch,
         cancel_fn: impl Fn() -> bool,
     ) -> Result<Completion<Vec<(ItemId, f32)>>> {
         use Completion::*;
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
 
         let cancel_fn = &cancel_fn;
-        let mut visitor = Visitor::new(self.entry_points.clone(), self.max_level, 1, None);
-
-        let mut path = RoaringBitmap::new();
-        for _ in (1..=self.max_level).rev() {
-            let neighbours = visitor.visit(query, self, rtxn, &mut path, &|| false)?.into_inner();
-            let closest = neighbours.peek_min().map(|(_, n)| n).expect("No neighbor was found");
-
-            visitor.eps = vec![*closest];
+        let mut entry_points = std::mem::take(&mut scratch.entry_points);
+        entry_points.clear();
+        for &item in opt.entry_points {
+            match self.internal_id(rtxn, item)? {
+                Some(item) if self.items.contains(item) => entry_points.push(item),
+                _ => (),
+            }
+        }
+        // the hinted entry points skip the descent through the upper layers
+        let top_level = if entry_points.is_empty() { self.max_level } else { 0 };
+        if entry_points.is_empty() {
+            entry_points.extend_from_slice(&self.entry_points);
+        }
+        let mut visitor = Visitor::new(entry_points, top_level, 1, None);
+        visitor.cached = self.fresh_layer_cache(rtxn)?.is_some();
+        visitor.codes = self.codes(rtxn)?;
+
+        for _ in (1..=top_level).rev() {
+            let neighbours = visitor.visit(query, self, rtxn, scratch, &|| false)?.into_inner();
+            let &(_, closest) = neighbours.peek_min().expect("No neighbor was found");
+            scratch.results = neighbours;
+            #[cfg(feature = "tracing-spans")]
+            tracing::trace!(
+                layer = visitor.level,
+                visited = scratch.path.len() as u64,
+                closest,
+                "descended"
+            );
+
+            visitor.eps.clear();
+            visitor.eps.push(closest);
             visitor.level -= 1;
         }
         // clear visited set as we only care about level 0
-        path.clear();
+        scratch.path.clear();
         debug_assert!(visitor.level == 0);
 
         visitor.ef = opt.ef.max(opt.count);
         visitor.candidates = opt.candidates;
+        visitor.filtered_expansion = self.is_selective(opt.candidates);
+        visitor.tombstones = Some(&self.tombstones).filter(|t| !t.is_empty());
+        visitor.expansion_batch = opt.expansion_batch;
+        visitor.stop_within = opt.stop_within.map(|distance| (opt.count, distance));
+        visitor.prefilter = self.prefilter(rtxn, query, opt)?;
 
         macro_rules! return_if_cancelled {
             ($completion: expr) => {
@@ -678,11 +3299,14 @@ impl<D: Distance> Reader<D> {
         }
 
         let mut neighbours =
-            return_if_cancelled!(visitor.visit(query, self, rtxn, &mut path, cancel_fn)?);
+            return_if_cancelled!(visitor.visit(query, self, rtxn, scratch, cancel_fn)?);
 
         // If we still don't have enough nns (e.g. search encountered cyclic subgraphs) then do exhaustive
         // search over remaining unseen items.
         if neighbours.len() < opt.count {
+            if let Some(stats) = &self.stats {
+                stats.record_exhaustive_fallback();
+            }
             let mut cursor = self
                 .database
                 .remap_types::<PrefixCodec, DecodeIgnore>()
@@ -691,28 +3315,66 @@ impl<D: Distance> Reader<D> {
 
             while let Some((key, _)) = cursor.next().transpose()? {
                 let id = key.node.item;
-                if path.contains(id) {
+                let pruned = visitor.prefilter.as_ref().is_some_and(|p| !p.keeps(id));
+                if pruned || scratch.path.contains(&id) {
                     continue;
                 }
 
-                visitor.eps = vec![id];
+                visitor.eps.clear();
+                visitor.eps.push(id);
                 visitor.ef = opt.count - neighbours.len();
 
                 let more_nns =
-                    return_if_cancelled!(visitor.visit(query, self, rtxn, &mut path, cancel_fn)?);
+                    return_if_cancelled!(visitor.visit(query, self, rtxn, scratch, cancel_fn)?);
 
-                neighbours.extend(more_nns.into_iter());
+                neighbours.extend(more_nns);
                 if neighbours.len() >= opt.count {
                     break;
                 }
             }
         }
 
-        let found =
-            neighbours.drain_asc().map(|(OrderedFloat(f), i)| (i, f)).take(opt.count).collect();
+        #[cfg(feature = "tracing-spans")]
+        tracing::Span::current().record("visited", scratch.path.len() as u64);
+        #[cfg(feature = "metrics")]
+        crate::monitoring::record_search::<D>(
+            self.index,
+            scratch.path.len() as u64,
+            started.elapsed(),
+        );
+        let mut found = std::mem::take(&mut scratch.found);
+        found.clear();
+        found.extend(neighbours.drain_asc().map(|(OrderedFloat(f), i)| (i, f)).take(opt.count));
+        scratch.results = neighbours;
+        scratch.entry_points = visitor.eps;
         Ok(Done(found))
     }
 
+    /// Returns `true` if the candidates of a filtered search are ranked one by one rather than
+    /// found by traversing the graph, see [`FilterStrategy`].
+    fn scans_linearly(&self, opt: &QueryBuilder<D>) -> bool {
+        let Some(candidates) = opt.candidates else { return false };
+        match opt.strategy {
+            FilterStrategy::Graph => false,
+            FilterStrategy::Linear => true,
+            FilterStrategy::Auto if candidates.len() < LINEAR_SEARCH_THRESHOLD => true,
+            FilterStrategy::Auto => {
+                let matching = candidates.intersection_len(&self.items);
+                let ef = opt.ef.max(opt.count);
+                FilterStrategy::plan(matching, self.items.len(), ef) == FilterStrategy::Linear
+            }
+        }
+    }
+
+    /// Returns `true` if the `candidates` are few enough for the traversal to only go through
+    /// them, see [`FILTERED_EXPANSION_SELECTIVITY`].
+    fn is_selective(&self, candidates: Option<&RoaringBitmap>) -> bool {
+        candidates.is_some_and(|candidates| {
+            let matching = candidates.intersection_len(&self.items) as f64;
+            matching < FILTERED_EXPANSION_SELECTIVITY * self.items.len() as f64
+        })
+    }
+
     /// Returns the nearest points to the item id, not including the point itself.
     ///
     /// Nearly identical behaviour to `Reader.nns_by_vec` except we only search layer 0 and use the
@@ -726,32 +3388,71 @@ impl<D: Distance> Reader<D> {
         item: ItemId,
         opt: &QueryBuilder<D>,
         cancel_fn: impl Fn() -> bool,
+    ) -> Result<Option<Completion<Vec<(ItemId, f32)>>>> {
+        let Some(item) = self.internal_id(rtxn, item)? else { return Ok(None) };
+        let (relabeled, internal);
+        let opt = match (&self.external_items, opt.candidates) {
+            (Some(_), Some(candidates)) => {
+                internal = self.internal_candidates(rtxn, candidates)?;
+                relabeled = QueryBuilder { candidates: Some(&internal), ..*opt };
+                &relabeled
+            }
+            _ => opt,
+        };
+        let oversampled = opt.oversampled();
+        let search = || self.find_nns_by_item(rtxn, item, &oversampled, cancel_fn);
+        match self.recorded(&oversampled, search)? {
+            Some(found) => {
+                let found = self.to_external(rtxn, found)?;
+                let found = self.rescored(rtxn, found, opt)?;
+                self.deduplicated(rtxn, found, opt).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    #[cfg_attr(test, allow(clippy::absurd_extreme_comparisons))]
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(name = "search_by_item", level = "debug", skip_all, fields(item, index = self.index, count = opt.count, ef = opt.ef, filtered = opt.candidates.is_some(), visited = tracing::field::Empty)))]
+    fn find_nns_by_item(
+        &self,
+        rtxn: &RoTxn,
+        item: ItemId,
+        opt: &QueryBuilder<D>,
+        cancel_fn: impl Fn() -> bool,
     ) -> Result<Option<Completion<Vec<(ItemId, f32)>>>> {
         use Completion::*;
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
         let cancel_fn = &cancel_fn;
 
         // If we will never find any candidates, return none
-        if opt.candidates.is_some_and(|c| self.item_ids().is_disjoint(c)) {
+        if opt.candidates.is_some_and(|c| self.items.is_disjoint(c)) {
             return Ok(None);
         }
 
-        let Some(vector) = self.item_vector(rtxn, item)? else { return Ok(None) };
+        let Some(vector) = self.internal_vector(rtxn, item)? else { return Ok(None) };
         let vector = UnalignedVector::from_vec(vector);
         let query = Item { header: D::new_header(&vector), vector };
 
-        // If the number of candidates is less than a given threshold, perform linear search
-        if let Some(candidates) = opt.candidates.filter(|c| c.len() < LINEAR_SEARCH_THRESHOLD) {
+        if let Some(candidates) = opt.candidates.filter(|_| self.scans_linearly(opt)) {
             let nns = self.brute_force_search(&query, rtxn, candidates, opt.count, cancel_fn)?;
             return Ok(Some(nns));
         }
 
         // Search over all items except `item`
         let ef = opt.ef.max(opt.count);
-        let mut path = RoaringBitmap::new();
-        let mut candidates = opt.candidates.unwrap_or_else(|| self.item_ids()).clone();
+        let mut scratch = SearchScratch::default();
+        let mut candidates = opt.candidates.unwrap_or(&self.items).clone();
         candidates.remove(item);
+        candidates -= &self.tombstones;
 
         let mut visitor = Visitor::new(vec![item], 0, ef, Some(&candidates));
+        visitor.filtered_expansion = self.is_selective(opt.candidates);
+        visitor.codes = self.codes(rtxn)?;
+        visitor.expansion_batch = opt.expansion_batch;
+        visitor.stop_within = opt.stop_within.map(|distance| (opt.count, distance));
+        visitor.prefilter = self.prefilter(rtxn, &query, opt)?;
 
         macro_rules! return_if_cancelled {
             ($completion: expr) => {
@@ -770,11 +3471,14 @@ impl<D: Distance> Reader<D> {
             };
         }
         let mut neighbours =
-            return_if_cancelled!(visitor.visit(&query, self, rtxn, &mut path, cancel_fn)?);
+            return_if_cancelled!(visitor.visit(&query, self, rtxn, &mut scratch, cancel_fn)?);
 
         // If we still don't have enough nns (e.g. search encountered cyclic subgraphs) then do exhaustive
         // search over remaining unseen items.
         if neighbours.len() < opt.count {
+            if let Some(stats) = &self.stats {
+                stats.record_exhaustive_fallback();
+            }
             let mut cursor = self
                 .database
                 .remap_types::<PrefixCodec, DecodeIgnore>()
@@ -783,23 +3487,37 @@ impl<D: Distance> Reader<D> {
 
             while let Some((key, _)) = cursor.next().transpose()? {
                 let id = key.node.item;
-                if path.contains(id) {
+                if scratch.path.contains(&id) {
                     continue;
                 }
 
                 // update walker
-                visitor.eps = vec![id];
+                visitor.eps.clear();
+                visitor.eps.push(id);
                 visitor.ef = opt.count - neighbours.len();
 
-                let more_nns =
-                    return_if_cancelled!(visitor.visit(&query, self, rtxn, &mut path, cancel_fn)?);
-                neighbours.extend(more_nns.into_iter());
+                let more_nns = return_if_cancelled!(visitor.visit(
+                    &query,
+                    self,
+                    rtxn,
+                    &mut scratch,
+                    cancel_fn
+                )?);
+                neighbours.extend(more_nns);
                 if neighbours.len() >= opt.count {
                     break;
                 }
             }
         }
 
+        #[cfg(feature = "tracing-spans")]
+        tracing::Span::current().record("visited", scratch.path.len() as u64);
+        #[cfg(feature = "metrics")]
+        crate::monitoring::record_search::<D>(
+            self.index,
+            scratch.path.len() as u64,
+            started.elapsed(),
+        );
         let found: Vec<_> =
             neighbours.drain_asc().map(|(OrderedFloat(f), i)| (i, f)).take(opt.count).collect();
         Ok(Some(Done(found)))
@@ -827,7 +3545,7 @@ impl<D: Distance> Reader<D> {
             let (i, _) = result?;
             item_ids.insert(i.node.unwrap_item());
         }
-        assert_eq!(item_ids, self.items);
+        assert_eq!(item_ids, &self.items | &self.tombstones);
 
         // 2. Check links are valid
         let mut link_ids = RoaringBitmap::new();
@@ -842,7 +3560,7 @@ impl<D: Distance> Reader<D> {
 
             let Links { links } = match node {
                 Node::Links(links) => links,
-                Node::Item(_) => unreachable!("Node must not be an item"),
+                Node::Item(_) => return Err(Error::unexpected_node(k, "Links")),
             };
 
             // this fails if links contains an item_id not in the db
@@ -860,16 +3578,32 @@ impl<D: Distance> Reader<D> {
     }
 }
 
+/// Returns `true` if some items were updated since the last build of the index.
+pub(crate) fn has_pending_updates<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<bool> {
+    Ok(database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter(rtxn, &Prefix::updated(index))
+        .map_err(Error::on_range("iterate", Prefix::updated(index)))?
+        .remap_key_type::<KeyCodec>()
+        .next()
+        .is_some())
+}
+
 pub fn get_item<'a, D: Distance>(
     database: Database<D>,
     index: u16,
     rtxn: &'a RoTxn,
     item: ItemId,
 ) -> Result<Option<Item<'a, D>>> {
-    match database.get(rtxn, &Key::item(index, item))? {
+    let key = Key::item(index, item);
+    let raw = database.remap_key_type::<Bytes>();
+    match raw.get(rtxn, &key.to_bytes()).map_err(Error::on_key("get", key))? {
         Some(Node::Item(item)) => Ok(Some(item)),
-        Some(Node::Links(_)) => Ok(None),
-        None => Ok(None),
+        Some(Node::Links(_)) | None => Ok(None),
     }
 }
 
@@ -880,9 +3614,135 @@ pub fn get_links<'a, D: Distance>(
     item_id: ItemId,
     level: usize,
 ) -> Result<Option<Links<'a>>> {
-    match database.get(rtxn, &Key::links(index, item_id, level as u8))? {
+    let key = Key::links(index, item_id, level as u8);
+    match database.get(rtxn, &key).map_err(Error::on_key("get", key))? {
         Some(Node::Links(links)) => Ok(Some(links)),
-        Some(Node::Item(_)) => Ok(None),
-        None => Ok(None),
+        Some(Node::Item(_)) | None => Ok(None),
+    }
+}
+
+/// Returns the links of an item iterated straight from the LMDB page, see [`SerializedBitmap`].
+pub(crate) fn get_serialized_links<'a, D: Distance>(
+    rtxn: &'a RoTxn,
+    database: Database<D>,
+    index: u16,
+    item_id: ItemId,
+    level: usize,
+) -> Result<Option<SerializedBitmap<'a>>> {
+    let key = Key::links(index, item_id, level as u8);
+    let raw = database.remap_types::<Bytes, Bytes>();
+    let Some(bytes) = raw.get(rtxn, &key.to_bytes()).map_err(Error::on_key("get", key))? else {
+        return Ok(None);
+    };
+    if let Some(ids) = array_links(bytes).map_err(heed::Error::Decoding)? {
+        return Ok(Some(SerializedBitmap::Array(ids)));
+    }
+    match serialized_links(bytes).map_err(heed::Error::Decoding)? {
+        Some(bytes) => Ok(Some(SerializedBitmap::new(bytes)?)),
+        None => Err(Error::unexpected_node(key, "Links")),
+    }
+}
+
+/// Returns the id of the last build of an index, `0` if it was built before they were recorded.
+pub(crate) fn get_build<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<u64> {
+    let key = Key::build(index);
+    let raw = database.remap_types::<Bytes, Bytes>();
+    let build = raw.get(rtxn, &key.to_bytes()).map_err(Error::on_key("get", key))?;
+    // the parameters of the build follow its id
+    Ok(build.map_or(0, BigEndian::read_u64))
+}
+
+/// Returns the instant an item was created or last refreshed at, if it was given one.
+pub(crate) fn get_timestamp<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    item_id: ItemId,
+) -> Result<Option<SystemTime>> {
+    let key = Key::timestamp(index, item_id);
+    let millis = database
+        .remap_data_type::<U64<BigEndian>>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?;
+    Ok(millis.map(|millis| UNIX_EPOCH + Duration::from_millis(millis)))
+}
+
+/// Returns the payload attached to an item, if it was given one.
+pub(crate) fn get_payload<'t, D: Distance>(
+    rtxn: &'t RoTxn,
+    database: Database<D>,
+    index: u16,
+    item_id: ItemId,
+) -> Result<Option<&'t [u8]>> {
+    let key = Key::payload(index, item_id);
+    database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))
+}
+
+/// Returns the factor the final score of an item is boosted by, if it was given one.
+pub(crate) fn get_boost<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    item_id: ItemId,
+) -> Result<Option<f32>> {
+    let key = Key::boost(index, item_id);
+    let bits = database
+        .remap_data_type::<U32<BigEndian>>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?;
+    Ok(bits.map(f32::from_bits))
+}
+
+/// Returns `true` if the item was deleted but is kept in the graph until the next vacuum.
+pub(crate) fn is_tombstoned<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    item_id: ItemId,
+) -> Result<bool> {
+    let key = Key::tombstone(index, item_id);
+    let tombstone = database
+        .remap_data_type::<DecodeIgnore>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?;
+    Ok(tombstone.is_some())
+}
+
+/// Returns the items deleted but kept in the graph until the next vacuum.
+pub(crate) fn tombstones<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<RoaringBitmap> {
+    let prefix = Prefix::tombstone(index);
+    let mut tombstones = RoaringBitmap::new();
+    for result in database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter(rtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<KeyCodec>()
+    {
+        let (key, ()) = result?;
+        tombstones.insert(key.node.item);
     }
+    Ok(tombstones)
+}
+
+/// Returns whether any item of the index is tombstoned, without reading them all.
+pub(crate) fn has_tombstones<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<bool> {
+    let prefix = Prefix::tombstone(index);
+    let mut iter = database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter(rtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<DecodeIgnore>();
+    Ok(iter.next().transpose()?.is_some())
 }
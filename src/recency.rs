@@ -0,0 +1,149 @@
+use std::time::{Duration, SystemTime};
+
+use heed::RoTxn;
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::reader::{Reader, Searched};
+use crate::Result;
+
+/// The number of candidates re-ranked for every requested item by default.
+const DEFAULT_RERANK_FACTOR: usize = 4;
+
+/// How the freshness of an item decreases with its age, from `1.0` for a new item to `0.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decay {
+    /// The freshness halves every `half_life`.
+    Exponential {
+        /// The age at which an item is half as fresh as a new one.
+        half_life: Duration,
+    },
+    /// The freshness decreases linearly until it reaches zero at the `horizon`.
+    Linear {
+        /// The age from which an item isn't fresh anymore.
+        horizon: Duration,
+    },
+}
+
+impl Decay {
+    /// Returns the freshness of an item of the given `age`, between `0.0` and `1.0`.
+    pub fn freshness(&self, age: Duration) -> f32 {
+        match *self {
+            Decay::Exponential { half_life } if half_life.is_zero() => 0.0,
+            Decay::Exponential { half_life } => {
+                0.5f64.powf(age.as_secs_f64() / half_life.as_secs_f64()) as f32
+            }
+            Decay::Linear { horizon } if age >= horizon => 0.0,
+            Decay::Linear { horizon } => (1.0 - age.as_secs_f64() / horizon.as_secs_f64()) as f32,
+        }
+    }
+}
+
+/// A search that favors the fresher items by adding a penalty growing with their age to their
+/// distances, the age being measured from the timestamps given with
+/// [`Writer::set_timestamp`](crate::Writer::set_timestamp).
+///
+/// The score of an item is `distance + weight * (1 - freshness)`, an item without timestamp
+/// gets the whole `weight` as penalty. The closest candidates are searched in the graph and then
+/// re-ranked by their score.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hannoy::{distances::Cosine, Decay, Reader, RecencyQuery};
+/// # let (reader, rtxn): (Reader<Cosine>, heed::RoTxn) = todo!();
+/// use std::time::Duration;
+///
+/// let half_life = Duration::from_secs(7 * 24 * 3600);
+/// let searched = RecencyQuery::new(&reader, 10, Decay::Exponential { half_life })
+///     .weight(0.2)
+///     .by_vector(&rtxn, &[0.0, 1.0, 0.5])?;
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+pub struct RecencyQuery<'a, D: Distance> {
+    reader: &'a Reader<D>,
+    count: usize,
+    decay: Decay,
+    weight: f32,
+    now: Option<SystemTime>,
+    rerank: usize,
+    ef: Option<usize>,
+    candidates: Option<&'a RoaringBitmap>,
+}
+
+impl<'a, D: Distance> RecencyQuery<'a, D> {
+    /// Prepares a search for the `count` closest items of the `reader`, penalized according to
+    /// their age by the `decay`.
+    pub fn new(reader: &'a Reader<D>, count: usize, decay: Decay) -> Self {
+        RecencyQuery {
+            reader,
+            count,
+            decay,
+            weight: 1.0,
+            now: None,
+            rerank: count * DEFAULT_RERANK_FACTOR,
+            ef: None,
+            candidates: None,
+        }
+    }
+
+    /// The penalty of an item that isn't fresh anymore, in the unit of the distance, defaults to
+    /// `1.0`.
+    pub fn weight(&mut self, weight: f32) -> &mut Self {
+        self.weight = weight;
+        self
+    }
+
+    /// The instant the ages are measured from, defaults to the time of the search.
+    pub fn now(&mut self, now: SystemTime) -> &mut Self {
+        self.now = Some(now);
+        self
+    }
+
+    /// The number of closest candidates that are re-ranked with their age, defaults to four
+    /// times the number of requested items.
+    pub fn rerank(&mut self, rerank: usize) -> &mut Self {
+        self.rerank = rerank.max(self.count);
+        self
+    }
+
+    /// The size of the search queue, see
+    /// [`QueryBuilder::ef_search`](crate::QueryBuilder::ef_search).
+    pub fn ef_search(&mut self, ef: usize) -> &mut Self {
+        self.ef = Some(ef);
+        self
+    }
+
+    /// Only searches among the `candidates`, see
+    /// [`QueryBuilder::candidates`](crate::QueryBuilder::candidates).
+    pub fn candidates(&mut self, candidates: &'a RoaringBitmap) -> &mut Self {
+        self.candidates = Some(candidates);
+        self
+    }
+
+    /// Returns the items of the best scores from the provided `vector`, along with their scores.
+    pub fn by_vector(&self, rtxn: &RoTxn, vector: &[f32]) -> Result<Searched> {
+        let mut builder = self.reader.nns(self.rerank);
+        if let Some(ef) = self.ef {
+            builder.ef_search(ef);
+        }
+        if let Some(candidates) = self.candidates {
+            builder.candidates(candidates);
+        }
+        let candidates = builder.by_vector(rtxn, vector)?;
+
+        let now = self.now.unwrap_or_else(SystemTime::now);
+        let mut nns = Vec::with_capacity(candidates.nns.len());
+        for &(id, distance) in &candidates.nns {
+            let freshness = match self.reader.timestamp(rtxn, id)? {
+                Some(at) => self.decay.freshness(now.duration_since(at).unwrap_or_default()),
+                None => 0.0,
+            };
+            nns.push((id, distance + self.weight * (1.0 - freshness)));
+        }
+        nns.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        nns.truncate(self.count);
+
+        Ok(Searched::new(nns, candidates.did_cancel))
+    }
+}
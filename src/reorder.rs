@@ -0,0 +1,230 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+
+use byteorder::BigEndian;
+use heed::types::{Bytes, DecodeIgnore, Unit, U32};
+use heed::{RoTxn, RwTxn};
+use roaring::RoaringBitmap;
+
+use crate::codes;
+use crate::density::{self, get_radii};
+use crate::distance::Distance;
+use crate::info::{get_build_info, put_build};
+use crate::node::{ItemIds, Links};
+use crate::reader::{get_links, has_pending_updates, tombstones};
+use crate::roaring::RoaringBitmapCodec;
+use crate::{
+    Database, Error, ItemId, Key, Metadata, MetadataCodec, Node, Prefix, PrefixCodec, Result,
+};
+
+/// Copies the built `index` into the `target` index with its items relabeled in the breadth-first
+/// order of the graph, see [`Writer::reorder_into`](crate::Writer::reorder_into).
+pub(crate) fn reorder_into<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    target: u16,
+) -> Result<()> {
+    if index == target {
+        return Err(Error::ReorderInPlace(index));
+    }
+
+    let metadata_key = Key::metadata(index);
+    let Some(metadata) = database.remap_data_type::<MetadataCodec>().get(wtxn, &metadata_key)?
+    else {
+        return Err(Error::MissingMetadata(index));
+    };
+    if has_pending_updates(wtxn, database, index)? {
+        return Err(Error::NeedBuild(index));
+    }
+    let dimensions = metadata.dimensions;
+    let distance = metadata.distance.to_owned();
+    let max_level = metadata.max_level;
+    let entry_points: Vec<_> = metadata.entry_points.iter().collect();
+    let items = metadata.items;
+
+    // the neighbours of an item get the ids following it, starting from the entry points
+    // which are the items every search goes through
+    let mut order = Vec::with_capacity(items.len() as usize);
+    let mut seen = RoaringBitmap::new();
+    let mut queue = VecDeque::new();
+    for &entry_point in &entry_points {
+        if seen.insert(entry_point) {
+            queue.push_back(entry_point);
+        }
+    }
+    while let Some(item) = queue.pop_front() {
+        order.push(item);
+        if let Some(Links { links }) = get_links(wtxn, database, index, item, 0)? {
+            for neighbour in links.iter() {
+                if items.contains(neighbour) && seen.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+    // the items unreachable from the entry points keep their relative order at the end
+    order.extend(&items - &seen);
+
+    let mapping: HashMap<ItemId, ItemId> =
+        order.iter().enumerate().map(|(internal, &item)| (item, internal as ItemId)).collect();
+    let relabel = |bitmap: &RoaringBitmap| -> RoaringBitmap {
+        bitmap.iter().filter_map(|item| mapping.get(&item).copied()).collect()
+    };
+
+    let source_tombstones = tombstones(wtxn, database, index)?;
+    let source_external = external_items(wtxn, database, index)?;
+    let mut externals = RoaringBitmap::new();
+
+    let mut cursor = database
+        .remap_key_type::<PrefixCodec>()
+        .prefix_iter_mut(wtxn, &Prefix::all(target))?
+        .remap_types::<DecodeIgnore, DecodeIgnore>();
+    while let Some((_, ())) = cursor.next().transpose()? {
+        // SAFETY: Safe because we don't keep any references to the entry
+        unsafe { cursor.del_current() }?;
+    }
+    drop(cursor);
+
+    let raw = database.remap_data_type::<Bytes>();
+    let ids = database.remap_data_type::<U32<BigEndian>>();
+    for (internal, &item) in order.iter().enumerate() {
+        let internal = internal as ItemId;
+        // the copied bytes are still valid after the puts, which may move the pages around
+        let copy = |rtxn: &RoTxn, key: Key| -> Result<Option<Vec<u8>>> {
+            Ok(raw.get(rtxn, &key).map_err(Error::on_key("get", key))?.map(<[u8]>::to_vec))
+        };
+
+        if let Some(bytes) = copy(wtxn, Key::item(index, item))? {
+            let key = Key::item(target, internal);
+            raw.put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))?;
+        }
+        for layer in 0..=max_level {
+            let links = match get_links(wtxn, database, index, item, layer as usize)? {
+                Some(Links { links }) => relabel(&links),
+                None => continue,
+            };
+            let key = Key::links(target, internal, layer);
+            let node = Node::Links(Links { links: Cow::Owned(links) });
+            database.put(wtxn, &key, &node).map_err(Error::on_key("put", key))?;
+        }
+        if let Some(bytes) = copy(wtxn, Key::expiration(index, item))? {
+            let key = Key::expiration(target, internal);
+            raw.put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))?;
+        }
+        if let Some(bytes) = copy(wtxn, Key::timestamp(index, item))? {
+            let key = Key::timestamp(target, internal);
+            raw.put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))?;
+        }
+        if let Some(bytes) = copy(wtxn, Key::payload(index, item))? {
+            let key = Key::payload(target, internal);
+            raw.put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))?;
+        }
+        if let Some(bytes) = copy(wtxn, Key::boost(index, item))? {
+            let key = Key::boost(target, internal);
+            raw.put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))?;
+        }
+        if let Some(bytes) = copy(wtxn, Key::full_vector(index, item))? {
+            let key = Key::full_vector(target, internal);
+            raw.put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))?;
+        }
+
+        // an index that was already reordered keeps the ids its users know
+        let external = match &source_external {
+            Some(_) => external_id(wtxn, database, index, item)?,
+            None => item,
+        };
+        if source_tombstones.contains(item) {
+            let key = Key::tombstone(target, internal);
+            let tombstone = database.remap_data_type::<Unit>();
+            tombstone.put(wtxn, &key, &()).map_err(Error::on_key("put", key))?;
+        } else {
+            externals.insert(external);
+        }
+        let key = Key::external_id(target, internal);
+        ids.put(wtxn, &key, &external).map_err(Error::on_key("put", key))?;
+        let key = Key::internal_id(target, external);
+        ids.put(wtxn, &key, &internal).map_err(Error::on_key("put", key))?;
+    }
+
+    for key in [
+        Key::version(index),
+        Key::format(index),
+        Key::tuning(index),
+        Key::weights(index),
+        Key::calibration(index),
+        Key::matryoshka(index),
+    ] {
+        if let Some(bytes) = raw.get(wtxn, &key)?.map(<[u8]>::to_vec) {
+            let key = Key::new(target, key.node);
+            raw.put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))?;
+        }
+    }
+
+    // the graph keeps the parameters it was built with
+    let build = wtxn.id() as u64;
+    let info = get_build_info(wtxn, database, index)?;
+    put_build(wtxn, database, target, build, info.as_ref())?;
+    codes::write(wtxn, database, target, None)?;
+    let densities = get_radii(wtxn, database, index)?.is_some();
+    density::write(wtxn, database, target, densities)?;
+
+    let key = Key::external_items(target);
+    database
+        .remap_data_type::<RoaringBitmapCodec>()
+        .put(wtxn, &key, &externals)
+        .map_err(Error::on_key("put", key))?;
+
+    let entry_points: Vec<_> = entry_points.iter().map(|item| mapping[item]).collect();
+    let metadata = Metadata {
+        dimensions,
+        items: (0..order.len() as ItemId).collect(),
+        distance: &distance,
+        entry_points: ItemIds::from_slice(&entry_points),
+        max_level,
+    };
+    let key = Key::metadata(target);
+    database
+        .remap_data_type::<MetadataCodec>()
+        .put(wtxn, &key, &metadata)
+        .map_err(Error::on_key("put", key))
+}
+
+/// Returns the external ids of the items of a reordered index, `None` if it wasn't reordered.
+pub(crate) fn external_items<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<RoaringBitmap>> {
+    let key = Key::external_items(index);
+    database
+        .remap_data_type::<RoaringBitmapCodec>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))
+}
+
+/// Returns the id the users of a reordered index know an item by.
+pub(crate) fn external_id<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    internal: ItemId,
+) -> Result<ItemId> {
+    let key = Key::external_id(index, internal);
+    database
+        .remap_data_type::<U32<BigEndian>>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?
+        .ok_or_else(|| Error::missing_key(key))
+}
+
+/// Returns the id an item of a reordered index is stored under.
+pub(crate) fn internal_id<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    external: ItemId,
+) -> Result<Option<ItemId>> {
+    let key = Key::internal_id(index, external);
+    database.remap_data_type::<U32<BigEndian>>().get(rtxn, &key).map_err(Error::on_key("get", key))
+}
@@ -1,5 +1,7 @@
 use std::borrow::Cow;
+use std::mem::size_of;
 
+use byteorder::{ByteOrder, LittleEndian};
 use heed::BoxedError;
 use roaring::RoaringBitmap;
 
@@ -30,3 +32,145 @@ impl heed::BytesEncode<'_> for RoaringBitmapCodec {
         Ok(Cow::Owned(bytes))
     }
 }
+
+/// The cookie of the serialized bitmaps without run containers, the only ones
+/// [`RoaringBitmap::serialize_into`] writes.
+const SERIAL_COOKIE_NO_RUNCONTAINER: u32 = 12346;
+
+/// The maximum cardinality of a container stored as an array of values, a bitmap otherwise.
+const ARRAY_LIMIT: usize = 4096;
+
+/// The number of words of a bitmap container.
+const BITMAP_WORDS: usize = 1024;
+
+/// A bitmap iterated straight from its serialized bytes, without decoding it in memory.
+///
+/// The searches read the links of many nodes and only iterate them once, which avoids
+/// allocating their containers. The bitmaps with run containers, never written by hannoy, are
+/// decoded.
+pub(crate) enum SerializedBitmap<'a> {
+    Raw {
+        descriptions: &'a [u8],
+        offsets: &'a [u8],
+        bytes: &'a [u8],
+    },
+    Decoded(RoaringBitmap),
+    /// A bitmap that was already decoded, e.g. in the layer cache of a reader.
+    Borrowed(&'a RoaringBitmap),
+    /// The sorted little-endian ids of links stored as an array, see
+    /// [`HannoyBuilder::array_links`](crate::HannoyBuilder::array_links).
+    Array(&'a [u8]),
+}
+
+impl<'a> SerializedBitmap<'a> {
+    pub fn new(bytes: &'a [u8]) -> std::io::Result<Self> {
+        let read_u32 = |at: usize| bytes.get(at..at + 4).map(LittleEndian::read_u32);
+        if let (Some(SERIAL_COOKIE_NO_RUNCONTAINER), Some(size)) = (read_u32(0), read_u32(4)) {
+            let size = size as usize;
+            let descriptions = bytes.get(8..8 + 4 * size);
+            let offsets = bytes.get(8 + 4 * size..8 + 8 * size);
+            if let (Some(descriptions), Some(offsets)) = (descriptions, offsets) {
+                return Ok(SerializedBitmap::Raw { descriptions, offsets, bytes });
+            }
+        }
+        RoaringBitmap::deserialize_from(bytes).map(SerializedBitmap::Decoded)
+    }
+
+    /// Iterates over the values of the bitmap in increasing order.
+    pub fn iter(&self) -> SerializedBitmapIter<'_> {
+        match self {
+            SerializedBitmap::Raw { descriptions, offsets, bytes } => SerializedBitmapIter::Raw {
+                descriptions,
+                offsets,
+                bytes,
+                container: 0,
+                position: 0,
+                word: 0,
+            },
+            SerializedBitmap::Decoded(bitmap) => SerializedBitmapIter::Decoded(bitmap.iter()),
+            SerializedBitmap::Borrowed(bitmap) => SerializedBitmapIter::Decoded(bitmap.iter()),
+            SerializedBitmap::Array(bytes) => {
+                // the ids are aligned in the values of the overflow pages but not always in the
+                // ones of the leaf pages, which are only aligned on 2 bytes
+                #[cfg(target_endian = "little")]
+                if let Ok(ids) = bytemuck::try_cast_slice(bytes) {
+                    return SerializedBitmapIter::Slice(ids.iter());
+                }
+                SerializedBitmapIter::Array(bytes.chunks_exact(size_of::<u32>()))
+            }
+        }
+    }
+}
+
+pub(crate) enum SerializedBitmapIter<'a> {
+    Raw {
+        descriptions: &'a [u8],
+        offsets: &'a [u8],
+        bytes: &'a [u8],
+        /// The container being iterated.
+        container: usize,
+        /// The next value of an array container, the next word of a bitmap container.
+        position: usize,
+        /// The bits of the current word of a bitmap container that weren't returned yet.
+        word: u64,
+    },
+    Decoded(roaring::bitmap::Iter<'a>),
+    Array(std::slice::ChunksExact<'a, u8>),
+    /// The ids of an array read in place, on the little-endian targets.
+    #[cfg(target_endian = "little")]
+    Slice(std::slice::Iter<'a, u32>),
+}
+
+impl Iterator for SerializedBitmapIter<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let (descriptions, offsets, bytes, container, position, word) = match self {
+            SerializedBitmapIter::Raw {
+                descriptions,
+                offsets,
+                bytes,
+                container,
+                position,
+                word,
+            } => (descriptions, offsets, bytes, container, position, word),
+            SerializedBitmapIter::Decoded(iter) => return iter.next(),
+            SerializedBitmapIter::Array(ids) => return ids.next().map(LittleEndian::read_u32),
+            #[cfg(target_endian = "little")]
+            SerializedBitmapIter::Slice(ids) => return ids.next().copied(),
+        };
+
+        loop {
+            let description = descriptions.get(4 * *container..4 * *container + 4)?;
+            let high = u32::from(LittleEndian::read_u16(description)) << 16;
+            let cardinality = usize::from(LittleEndian::read_u16(&description[2..])) + 1;
+            let offset = LittleEndian::read_u32(offsets.get(4 * *container..)?) as usize;
+
+            if cardinality <= ARRAY_LIMIT {
+                if *position < cardinality {
+                    let at = offset + 2 * *position;
+                    *position += 1;
+                    return Some(high | u32::from(LittleEndian::read_u16(bytes.get(at..at + 2)?)));
+                }
+            } else {
+                loop {
+                    if *word != 0 {
+                        let bit = word.trailing_zeros();
+                        *word &= *word - 1;
+                        return Some(high | ((*position as u32 - 1) * 64 + bit));
+                    }
+                    if *position == BITMAP_WORDS {
+                        break;
+                    }
+                    let at = offset + 8 * *position;
+                    *word = LittleEndian::read_u64(bytes.get(at..at + 8)?);
+                    *position += 1;
+                }
+            }
+
+            *container += 1;
+            *position = 0;
+            *word = 0;
+        }
+    }
+}
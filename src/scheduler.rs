@@ -0,0 +1,146 @@
+//! The work-stealing scheduler of the insertions of a build.
+//!
+//! The items of a level are cut into batches which are dealt round-robin into one queue per
+//! thread, so that every thread starts with the same share of the level. A thread pops the
+//! batches of its own queue, the most recently pushed first, and once it is empty steals half
+//! of the batches left in the queue of another thread. The costly insertions of a thread, e.g.
+//! the ones of the items of a dense region, don't hold the other threads back and the queues
+//! stay balanced until the end of the level.
+//!
+//! The levels are scheduled one after the other, from the top, as the items of a level are
+//! searched from the ones above it. The upper levels only hold a few items, they're given as
+//! many threads as they have items and batches of a single item.
+
+#[cfg(feature = "rayon")]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed};
+#[cfg(feature = "rayon")]
+use std::sync::Mutex;
+
+#[cfg(feature = "rayon")]
+use crossbeam_deque::{Steal, Stealer, Worker};
+#[cfg(feature = "rayon")]
+use tracing::debug;
+
+/// The number of batches of a level dealt to every thread, enough for the threads done with
+/// their batches to find some to steal from the others.
+#[cfg(feature = "rayon")]
+const BATCHES_PER_THREAD: usize = 8;
+
+/// The maximum number of items of a batch, so that a thread never owns a large part of a
+/// level whatever the number of threads.
+#[cfg(feature = "rayon")]
+const MAX_BATCH_SIZE: usize = 256;
+
+/// Schedules the insertions of the items of a level over the threads of the global rayon
+/// thread pool, or of the pool the build is installed in.
+pub(crate) struct Scheduler {
+    #[cfg(feature = "rayon")]
+    threads: usize,
+}
+
+impl Scheduler {
+    /// Creates a scheduler using every thread of the current rayon thread pool, or only the
+    /// calling thread when the `rayon` feature is disabled.
+    pub fn new() -> Scheduler {
+        Scheduler {
+            #[cfg(feature = "rayon")]
+            threads: rayon::current_num_threads(),
+        }
+    }
+
+    /// Returns the number of items of the batches of a level of `len` items.
+    #[cfg(feature = "rayon")]
+    pub fn batch_size(&self, len: usize) -> usize {
+        len.div_ceil(self.threads * BATCHES_PER_THREAD).clamp(1, MAX_BATCH_SIZE)
+    }
+
+    /// Calls `f` with every item of a level and stops at the first error, which is returned.
+    ///
+    /// The calls are spread over the threads by the batches of [`Self::batch_size`] items,
+    /// the threads steal the batches of the others once they're done with their own.
+    pub fn try_for_each<T, E, F>(&self, items: &[T], f: F) -> Result<(), E>
+    where
+        T: Sync,
+        E: Send,
+        F: Fn(&T) -> Result<(), E> + Sync,
+    {
+        #[cfg(feature = "rayon")]
+        if self.threads.min(items.len()) > 1 {
+            return self.try_for_each_stealing(items, f);
+        }
+        items.iter().try_for_each(f)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn try_for_each_stealing<T, E, F>(&self, items: &[T], f: F) -> Result<(), E>
+    where
+        T: Sync,
+        E: Send,
+        F: Fn(&T) -> Result<(), E> + Sync,
+    {
+        let threads = self.threads.min(items.len());
+        let workers: Vec<Worker<&[T]>> = (0..threads).map(|_| Worker::new_lifo()).collect();
+        for (i, batch) in items.chunks(self.batch_size(items.len())).enumerate() {
+            workers[i % threads].push(batch);
+        }
+        let stealers: Vec<Stealer<&[T]>> = workers.iter().map(Worker::stealer).collect();
+
+        let failed = AtomicBool::new(false);
+        let error = Mutex::new(None);
+        let stolen = AtomicUsize::new(0);
+        rayon::scope(|scope| {
+            for (thief, worker) in workers.into_iter().enumerate() {
+                let (f, stealers, failed, error, stolen) =
+                    (&f, &stealers, &failed, &error, &stolen);
+                scope.spawn(move |_| {
+                    while let Some(batch) = worker.pop().or_else(|| {
+                        let batch = steal(&worker, stealers, thief);
+                        stolen.fetch_add(batch.is_some() as usize, Relaxed);
+                        batch
+                    }) {
+                        for item in batch {
+                            if failed.load(Relaxed) {
+                                return;
+                            }
+                            if let Err(e) = f(item) {
+                                failed.store(true, Relaxed);
+                                error.lock().unwrap().get_or_insert(e);
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        debug!(
+            "{} items scheduled over {threads} threads, {} steals",
+            items.len(),
+            stolen.into_inner()
+        );
+        match error.into_inner().unwrap() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Moves half of the batches of the queue of another thread into the queue of the `thief`,
+/// trying the threads after it first, and returns one of them. Returns `None` once all the
+/// queues are empty, no batch is pushed after the start of a level.
+#[cfg(feature = "rayon")]
+fn steal<B>(worker: &Worker<B>, stealers: &[Stealer<B>], thief: usize) -> Option<B> {
+    loop {
+        let mut retry = false;
+        for stealer in stealers[thief + 1..].iter().chain(&stealers[..thief]) {
+            match stealer.steal_batch_and_pop(worker) {
+                Steal::Success(batch) => return Some(batch),
+                Steal::Retry => retry = true,
+                Steal::Empty => (),
+            }
+        }
+        if !retry {
+            return None;
+        }
+    }
+}
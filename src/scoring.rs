@@ -0,0 +1,125 @@
+//! Scores the items of an index with another distance than the one its graph was built with.
+use std::marker::PhantomData;
+
+use heed::RoTxn;
+
+use crate::distance::Distance;
+use crate::node::Item;
+use crate::ordered_float::OrderedFloat;
+use crate::unaligned_vector::UnalignedVector;
+use crate::{Error, ItemId, Reader, Result};
+
+/// The number of candidates searched in the graph per result of a [`ScoringReader`], the
+/// closest items of the two distances differ a bit.
+const SCORING_OVERSAMPLING: usize = 4;
+
+/// A vector transformed by both distances, on which they must agree for one to score the
+/// vectors stored for the other.
+const PROBE: [f32; 4] = [0.5, -0.25, 2.0, 0.0];
+
+/// A [`Reader`] whose results are scored with the distance `S` rather than the distance `D` the
+/// graph was built with, so that one stored copy of the vectors serves both, e.g. an index
+/// built with [`Cosine`](crate::distances::Cosine) reporting
+/// [`Euclidean`](crate::distances::Euclidean) distances.
+///
+/// The graph is searched with `D` for more candidates than requested, which are then ranked by
+/// their `S` distance. The distances must store the vectors with the same codec and transform
+/// them the same way, which [`Self::new`] checks.
+///
+/// # Example
+///
+/// ```no_run
+/// use hannoy::{distances::{Cosine, Euclidean}, Reader, ScoringReader};
+/// # let (rtxn, database): (heed::RoTxn, hannoy::Database<Cosine>) = todo!();
+///
+/// let reader = Reader::open(&rtxn, 0, database)?;
+/// let reader = ScoringReader::<Cosine, Euclidean>::new(reader)?;
+/// let closest = reader.nns_by_vector(&rtxn, &[1.25854, -0.75598, 0.58524], 10)?;
+/// # Ok::<(), hannoy::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct ScoringReader<D: Distance, S> {
+    reader: Reader<D>,
+    _scoring: PhantomData<S>,
+}
+
+impl<D: Distance, S: Distance<VectorCodec = D::VectorCodec>> ScoringReader<D, S> {
+    /// Wraps a reader to score its results with `S`, or returns [`Error::IncompatibleScoring`]
+    /// if `S` transforms the vectors differently than `D` stored them.
+    pub fn new(reader: Reader<D>) -> Result<Self> {
+        if D::preprocess(&PROBE) != S::preprocess(&PROBE) {
+            return Err(Error::IncompatibleScoring { built: D::name(), scoring: S::name() });
+        }
+        Ok(ScoringReader { reader, _scoring: PhantomData })
+    }
+
+    /// Returns the reader searching the graph, with its `D` distances.
+    pub fn reader(&self) -> &Reader<D> {
+        &self.reader
+    }
+
+    /// Returns the `count` closest items from the `query` by their `S` distance, by increasing
+    /// distance.
+    pub fn nns_by_vector(
+        &self,
+        rtxn: &RoTxn,
+        query: &[f32],
+        count: usize,
+    ) -> Result<Vec<(ItemId, f32)>> {
+        let found = self.reader.nns(count * SCORING_OVERSAMPLING).by_vector(rtxn, query)?;
+        // the query is transformed like the stored vectors were
+        let vector = self.reader.query_vector(query)?;
+        let vector = UnalignedVector::from_slice(&vector);
+        let query = Item::<S> { header: S::new_header(&vector), vector };
+        self.rescored(rtxn, &query, found.into_nns(), count)
+    }
+
+    /// Returns the `count` closest items from `item`, itself excluded, by their `S` distance,
+    /// `None` if it doesn't exist.
+    pub fn nns_by_item(
+        &self,
+        rtxn: &RoTxn,
+        item: ItemId,
+        count: usize,
+    ) -> Result<Option<Vec<(ItemId, f32)>>> {
+        let Some(query) = self.stored_item(rtxn, item)? else { return Ok(None) };
+        let Some(found) = self.reader.nns(count * SCORING_OVERSAMPLING).by_item(rtxn, item)? else {
+            return Ok(None);
+        };
+        self.rescored(rtxn, &query, found.into_nns(), count).map(Some)
+    }
+
+    /// Returns the `S` distance between two items, `None` if one of them doesn't exist.
+    pub fn distance(&self, rtxn: &RoTxn, a: ItemId, b: ItemId) -> Result<Option<f32>> {
+        let Some(a) = self.stored_item(rtxn, a)? else { return Ok(None) };
+        let Some(b) = self.stored_item(rtxn, b)? else { return Ok(None) };
+        Ok(Some(S::distance(&a, &b)))
+    }
+
+    /// Returns the stored vector of an item with the header of `S`.
+    fn stored_item<'t>(&self, rtxn: &'t RoTxn, item: ItemId) -> Result<Option<Item<'t, S>>> {
+        Ok(self
+            .reader
+            .stored_item(rtxn, item)?
+            .map(|item| Item { header: S::new_header(&item.vector), vector: item.vector }))
+    }
+
+    /// Ranks the candidates found with `D` by their `S` distance to the `query`.
+    fn rescored(
+        &self,
+        rtxn: &RoTxn,
+        query: &Item<S>,
+        candidates: Vec<(ItemId, f32)>,
+        count: usize,
+    ) -> Result<Vec<(ItemId, f32)>> {
+        let mut scored = Vec::with_capacity(candidates.len());
+        for (item, _) in candidates {
+            if let Some(stored) = self.stored_item(rtxn, item)? {
+                scored.push((item, S::distance(query, &stored)));
+            }
+        }
+        scored.sort_by_key(|&(_, distance)| OrderedFloat(distance));
+        scored.truncate(count);
+        Ok(scored)
+    }
+}
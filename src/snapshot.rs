@@ -0,0 +1,168 @@
+//! Named copies of an index frozen at the time they were taken, e.g. to evaluate a model on a
+//! pinned version of a dataset while the index keeps being updated.
+use std::mem::size_of;
+use std::ops::Bound;
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::internals::KeyCodec;
+use crate::migration::free_index;
+use crate::reader::has_pending_updates;
+use crate::{Database, Error, Key, NodeId, NodeMode, Prefix, PrefixCodec, Result};
+
+/// The number of keys copied at once from an index to its snapshot, the values of a batch are
+/// copied in memory before being written.
+const COPY_BATCH: usize = 10_000;
+
+/// Returns the name and the index of the snapshots of an index, in the order they were taken.
+pub(crate) fn get_snapshots<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Vec<(String, u16)>> {
+    let key = Key::snapshots(index);
+    let bytes =
+        database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))?;
+    let mut bytes = bytes.unwrap_or_default();
+    let mut snapshots = Vec::new();
+    // every snapshot is its index, the length of its name and its name
+    while let Some((header, rest)) = bytes.split_at_checked(2 * size_of::<u16>()) {
+        let snapshot = BigEndian::read_u16(header);
+        let len = BigEndian::read_u16(&header[size_of::<u16>()..]) as usize;
+        let Some((name, rest)) = rest.split_at_checked(len) else { break };
+        let Ok(name) = std::str::from_utf8(name) else { break };
+        snapshots.push((name.to_owned(), snapshot));
+        bytes = rest;
+    }
+    if !bytes.is_empty() {
+        return Err(Error::unexpected_node(key, "Snapshots"));
+    }
+    Ok(snapshots)
+}
+
+/// Stores the snapshots of an index, read back by [`get_snapshots`].
+fn put_snapshots<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    snapshots: &[(String, u16)],
+) -> Result<()> {
+    let key = Key::snapshots(index);
+    if snapshots.is_empty() {
+        database
+            .remap_data_type::<DecodeIgnore>()
+            .delete(wtxn, &key)
+            .map_err(Error::on_key("delete", key))?;
+        return Ok(());
+    }
+    let mut bytes = Vec::new();
+    for (name, snapshot) in snapshots {
+        bytes.extend_from_slice(&snapshot.to_be_bytes());
+        bytes.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+    }
+    database.remap_data_type::<Bytes>().put(wtxn, &key, &bytes).map_err(Error::on_key("put", key))
+}
+
+/// Returns the index of the snapshot `name` of an index, if it was taken.
+pub(crate) fn snapshot_index<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    name: &str,
+) -> Result<Option<u16>> {
+    let snapshots = get_snapshots(rtxn, database, index)?;
+    Ok(snapshots.into_iter().find(|(snapshot, _)| snapshot == name).map(|(_, index)| index))
+}
+
+/// Copies a built index into a free index of the database and records it as the snapshot
+/// `name` of the index, see [`Writer::snapshot`](crate::Writer::snapshot).
+pub(crate) fn snapshot<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    name: &str,
+) -> Result<u16> {
+    if database.remap_data_type::<DecodeIgnore>().get(wtxn, &Key::metadata(index))?.is_none() {
+        return Err(Error::MissingMetadata(index));
+    }
+    if has_pending_updates(wtxn, database, index)? {
+        return Err(Error::NeedBuild(index));
+    }
+    let mut snapshots = get_snapshots(wtxn, database, index)?;
+    let invalid = |reason| Error::InvalidSnapshot { index, name: name.to_owned(), reason };
+    if name.len() > u16::MAX as usize {
+        return Err(invalid("its name is too long"));
+    }
+    if snapshots.iter().any(|(snapshot, _)| snapshot == name) {
+        return Err(invalid("it was already taken"));
+    }
+
+    let target = free_index(wtxn, database)?;
+    let raw = database.remap_data_type::<Bytes>();
+    let mut from = Bound::Included(Key::metadata(index));
+    loop {
+        // every batch resumes after the last key copied by the previous one
+        let batch = raw
+            .remap_key_type::<KeyCodec>()
+            .range(wtxn, &(from, Bound::Unbounded))
+            .map_err(Error::on_range("iterate", Prefix::all(index)))?
+            .take_while(|result| result.as_ref().map_or(true, |(key, _)| key.index == index))
+            .take(COPY_BATCH)
+            .map(|result| result.map(|(key, bytes)| (key, bytes.to_vec())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(&(last, _)) = batch.last() else { break };
+        from = Bound::Excluded(last);
+        for (key, bytes) in batch.into_iter().filter(|(key, _)| is_frozen(key.node)) {
+            let copied = Key::new(target, key.node);
+            raw.put(wtxn, &copied, &bytes).map_err(Error::on_key("put", copied))?;
+        }
+    }
+
+    snapshots.push((name.to_owned(), target));
+    put_snapshots(wtxn, database, index, &snapshots)?;
+    Ok(target)
+}
+
+/// Returns whether an entry of an index belongs to its snapshots: the graph, the items and the
+/// settings do, the change log and the state of the ongoing writes don't.
+fn is_frozen(node: NodeId) -> bool {
+    !matches!(node.mode, NodeMode::Change | NodeMode::History)
+        && ![
+            NodeId::change_sequence(),
+            NodeId::applied_sequence(),
+            NodeId::write_lock(),
+            NodeId::manifest(),
+            NodeId::snapshots(),
+        ]
+        .contains(&node)
+}
+
+/// Removes the snapshot `name` of an index and everything in its copy, returns `false` if it
+/// wasn't taken.
+pub(crate) fn delete_snapshot<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    name: &str,
+) -> Result<bool> {
+    let mut snapshots = get_snapshots(wtxn, database, index)?;
+    let Some(position) = snapshots.iter().position(|(snapshot, _)| snapshot == name) else {
+        return Ok(false);
+    };
+    let (_, snapshot) = snapshots.remove(position);
+    let mut cursor = database
+        .remap_key_type::<PrefixCodec>()
+        .prefix_iter_mut(wtxn, &Prefix::all(snapshot))?
+        .remap_types::<DecodeIgnore, DecodeIgnore>();
+    while let Some((_, ())) = cursor.next().transpose()? {
+        // SAFETY: Safe because we don't keep any references to the entry
+        unsafe { cursor.del_current() }?;
+    }
+    drop(cursor);
+    put_snapshots(wtxn, database, index, &snapshots)?;
+    Ok(true)
+}
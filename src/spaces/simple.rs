@@ -44,6 +44,22 @@ pub fn euclidean_distance(u: &UnalignedVector<f32>, v: &UnalignedVector<f32>) ->
     euclidean_distance_non_optimized(u, v)
 }
 
+/// Returns the [`euclidean_distance`]s between `u` and both vectors of `v`, reading `u` once when
+/// the CPU supports AVX.
+pub fn euclidean_distance_x2(u: &UnalignedVector<f32>, v: [&UnalignedVector<f32>; 2]) -> [f32; 2] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx")
+            && is_x86_feature_detected!("fma")
+            && u.len() >= MIN_DIM_SIZE_AVX
+        {
+            return unsafe { euclid_similarity_avx_x2(u, v[0], v[1]) };
+        }
+    }
+
+    v.map(|v| euclidean_distance(u, v))
+}
+
 // Don't use dot-product: avoid catastrophic cancellation in
 // https://github.com/spotify/annoy/issues/314.
 pub fn euclidean_distance_non_optimized(u: &UnalignedVector<f32>, v: &UnalignedVector<f32>) -> f32 {
@@ -78,6 +94,22 @@ pub fn dot_product(u: &UnalignedVector<f32>, v: &UnalignedVector<f32>) -> f32 {
     dot_product_non_optimized(u, v)
 }
 
+/// Returns the [`dot_product`]s between `u` and both vectors of `v`, reading `u` once when the
+/// CPU supports AVX.
+pub fn dot_product_x2(u: &UnalignedVector<f32>, v: [&UnalignedVector<f32>; 2]) -> [f32; 2] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx")
+            && is_x86_feature_detected!("fma")
+            && u.len() >= MIN_DIM_SIZE_AVX
+        {
+            return unsafe { dot_similarity_avx_x2(u, v[0], v[1]) };
+        }
+    }
+
+    v.map(|v| dot_product(u, v))
+}
+
 pub fn dot_product_non_optimized(u: &UnalignedVector<f32>, v: &UnalignedVector<f32>) -> f32 {
     u.iter().zip(v.iter()).map(|(a, b)| a * b).sum()
 }
@@ -102,8 +134,8 @@ pub fn dot_product_non_optimized(u: &UnalignedVector<f32>, v: &UnalignedVector<f
 ///
 /// 2. Then we need to do the sum of the results:
 ///    2.1 First we must do the sum of the operation on the `Word`s
-///        /!\ We must be careful here because `1 - 0` actually translates to `1 - 1 = 0`.
-///        `word.count_ones() - word.count_zeroes()` should do it:
+///    /!\ We must be careful here because `1 - 0` actually translates to `1 - 1 = 0`.
+///    `word.count_ones() - word.count_zeroes()` should do it:
 /// ```text
 ///  00 => -2
 ///  01 => 0
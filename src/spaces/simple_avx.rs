@@ -109,6 +109,93 @@ pub(crate) unsafe fn dot_similarity_avx(
     result
 }
 
+/// Computes [`euclid_similarity_avx`] between `v1` and both `v2` and `v3`, loading `v1` once.
+///
+/// The operations on each pair are the same, in the same order, as the single distance which
+/// makes the results identical, and the eight accumulators with the loads of `v1` fit in the
+/// sixteen AVX registers.
+#[target_feature(enable = "avx")]
+#[target_feature(enable = "fma")]
+pub(crate) unsafe fn euclid_similarity_avx_x2(
+    v1: &UnalignedVector<f32>,
+    v2: &UnalignedVector<f32>,
+    v3: &UnalignedVector<f32>,
+) -> [f32; 2] {
+    let n = v1.len();
+    let m = n - (n % 32);
+    let mut ptr1 = v1.as_ptr() as *const f32;
+    let mut ptr2 = v2.as_ptr() as *const f32;
+    let mut ptr3 = v3.as_ptr() as *const f32;
+    let mut sums2: [__m256; 4] = [_mm256_setzero_ps(); 4];
+    let mut sums3: [__m256; 4] = [_mm256_setzero_ps(); 4];
+    let mut i: usize = 0;
+    while i < m {
+        for (lane, (sum2, sum3)) in sums2.iter_mut().zip(&mut sums3).enumerate() {
+            let x = _mm256_loadu_ps(ptr1.add(lane * 8));
+            let sub2 = _mm256_sub_ps(x, _mm256_loadu_ps(ptr2.add(lane * 8)));
+            *sum2 = _mm256_fmadd_ps(sub2, sub2, *sum2);
+            let sub3 = _mm256_sub_ps(x, _mm256_loadu_ps(ptr3.add(lane * 8)));
+            *sum3 = _mm256_fmadd_ps(sub3, sub3, *sum3);
+        }
+
+        ptr1 = ptr1.add(32);
+        ptr2 = ptr2.add(32);
+        ptr3 = ptr3.add(32);
+        i += 32;
+    }
+
+    let mut results = [sums2, sums3].map(|[s1, s2, s3, s4]| {
+        hsum256_ps_avx(s1) + hsum256_ps_avx(s2) + hsum256_ps_avx(s3) + hsum256_ps_avx(s4)
+    });
+    for i in 0..n - m {
+        let a = read_unaligned(ptr1.add(i));
+        results[0] += (a - read_unaligned(ptr2.add(i))).powi(2);
+        results[1] += (a - read_unaligned(ptr3.add(i))).powi(2);
+    }
+    results
+}
+
+/// Computes [`dot_similarity_avx`] between `v1` and both `v2` and `v3`, loading `v1` once, see
+/// [`euclid_similarity_avx_x2`].
+#[target_feature(enable = "avx")]
+#[target_feature(enable = "fma")]
+pub(crate) unsafe fn dot_similarity_avx_x2(
+    v1: &UnalignedVector<f32>,
+    v2: &UnalignedVector<f32>,
+    v3: &UnalignedVector<f32>,
+) -> [f32; 2] {
+    let n = v1.len();
+    let m = n - (n % 32);
+    let mut ptr1 = v1.as_ptr() as *const f32;
+    let mut ptr2 = v2.as_ptr() as *const f32;
+    let mut ptr3 = v3.as_ptr() as *const f32;
+    let mut sums2: [__m256; 4] = [_mm256_setzero_ps(); 4];
+    let mut sums3: [__m256; 4] = [_mm256_setzero_ps(); 4];
+    let mut i: usize = 0;
+    while i < m {
+        for (lane, (sum2, sum3)) in sums2.iter_mut().zip(&mut sums3).enumerate() {
+            let x = _mm256_loadu_ps(ptr1.add(lane * 8));
+            *sum2 = _mm256_fmadd_ps(x, _mm256_loadu_ps(ptr2.add(lane * 8)), *sum2);
+            *sum3 = _mm256_fmadd_ps(x, _mm256_loadu_ps(ptr3.add(lane * 8)), *sum3);
+        }
+
+        ptr1 = ptr1.add(32);
+        ptr2 = ptr2.add(32);
+        ptr3 = ptr3.add(32);
+        i += 32;
+    }
+
+    let mut results = [sums2, sums3].map(|[s1, s2, s3, s4]| {
+        hsum256_ps_avx(s1) + hsum256_ps_avx(s2) + hsum256_ps_avx(s3) + hsum256_ps_avx(s4)
+    });
+    for i in 0..n - m {
+        let a = read_unaligned(ptr1.add(i));
+        results[0] += a * read_unaligned(ptr2.add(i));
+        results[1] += a * read_unaligned(ptr3.add(i));
+    }
+    results
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -143,6 +230,12 @@ mod tests {
             let dot = dot_product_non_optimized(&v1, &v2);
             assert_eq!(dot_simd, dot);
 
+            // the batched kernels give the same results as the single ones
+            let euclid_x2 = unsafe { euclid_similarity_avx_x2(&v1, &v2, &v1) };
+            assert_eq!(euclid_x2, [euclid_simd, 0.0]);
+            let dot_x2 = unsafe { dot_similarity_avx_x2(&v1, &v2, &v1) };
+            assert_eq!(dot_x2, [dot_simd, unsafe { dot_similarity_avx(&v1, &v1) }]);
+
             // let cosine_simd = unsafe { cosine_preprocess_avx(v1.clone()) };
             // let cosine = cosine_preprocess(v1);
             // assert_eq!(cosine_simd, cosine);
@@ -1,12 +1,13 @@
 use std::marker::PhantomData;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
 
 use hashbrown::HashMap;
-use heed::{Result, RoTxn};
+use heed::RoTxn;
 
 use crate::key::{KeyCodec, Prefix, PrefixCodec};
 use crate::node::{Links, Node};
-use crate::{Database, Distance};
+use crate::{Database, Distance, Error, Result};
 
 // TODO: ignore the phantom
 #[derive(Debug)]
@@ -58,18 +59,19 @@ impl<D: Distance> BuildStats<D> {
     ) -> Result<()> {
         let iter = db
             .remap_key_type::<PrefixCodec>()
-            .prefix_iter(rtxn, &Prefix::links(index))?
+            .prefix_iter(rtxn, &Prefix::links(index))
+            .map_err(Error::on_range("iterate", Prefix::links(index)))?
             .remap_key_type::<KeyCodec>();
 
         let mut n_links = 0;
         let mut total_links = 0;
 
         for res in iter {
-            let (_key, node) = res?;
+            let (key, node) = res?;
 
             let links = match node {
                 Node::Links(Links { links }) => links,
-                Node::Item(_) => unreachable!("Node must not be an item"),
+                Node::Item(_) => return Err(Error::unexpected_node(key, "Links")),
             };
 
             total_links += links.len();
@@ -81,3 +83,210 @@ impl<D: Distance> BuildStats<D> {
         Ok(())
     }
 }
+
+/// The number of buckets of the latency histogram, the last one gathers the searches of at
+/// least `2^30` microseconds, about 18 minutes.
+const LATENCY_BUCKETS: usize = 32;
+
+/// Rolling statistics of the searches made through the readers they are attached to, see
+/// [`crate::Reader::with_search_stats`].
+///
+/// The statistics accumulate until they are taken with [`Self::take`], which starts a new
+/// window. Recording a search is a handful of relaxed atomic increments, the statistics can
+/// be shared by all the readers of a process. Searches that are cancelled are counted as having
+/// hit their time budget.
+#[derive(Debug, Default)]
+pub struct SearchStats {
+    searches: AtomicU64,
+    cancelled: AtomicU64,
+    exhaustive_fallbacks: AtomicU64,
+    ef_sum: AtomicU64,
+    latency_micros_sum: AtomicU64,
+    /// The bucket `i` counts the searches that took less than `2^i` microseconds and at least
+    /// `2^(i-1)`.
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl SearchStats {
+    /// Creates empty statistics.
+    pub fn new() -> SearchStats {
+        SearchStats::default()
+    }
+
+    pub(crate) fn record(&self, ef: usize, latency: Duration, cancelled: bool) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+
+        self.searches.fetch_add(1, Ordering::Relaxed);
+        self.cancelled.fetch_add(cancelled as u64, Ordering::Relaxed);
+        self.ef_sum.fetch_add(ef as u64, Ordering::Relaxed);
+        self.latency_micros_sum.fetch_add(micros, Ordering::Relaxed);
+        self.latency_buckets[bucket.min(LATENCY_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_exhaustive_fallback(&self) {
+        self.exhaustive_fallbacks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics accumulated so far.
+    pub fn snapshot(&self) -> SearchStatsSnapshot {
+        self.collect(|counter| counter.load(Ordering::Relaxed))
+    }
+
+    /// Returns the statistics accumulated so far and resets them. The searches running
+    /// concurrently are accounted for in this window or in the next one.
+    pub fn take(&self) -> SearchStatsSnapshot {
+        self.collect(|counter| counter.swap(0, Ordering::Relaxed))
+    }
+
+    fn collect(&self, read: impl Fn(&AtomicU64) -> u64) -> SearchStatsSnapshot {
+        let searches = read(&self.searches);
+        let mean = |sum: u64| if searches == 0 { 0.0 } else { sum as f64 / searches as f64 };
+        SearchStatsSnapshot {
+            searches,
+            cancelled: read(&self.cancelled),
+            exhaustive_fallbacks: read(&self.exhaustive_fallbacks),
+            mean_ef: mean(read(&self.ef_sum)),
+            mean_latency: Duration::from_secs_f64(mean(read(&self.latency_micros_sum)) / 1e6),
+            latency_buckets: self.latency_buckets.each_ref().map(read),
+        }
+    }
+}
+
+/// The statistics of a window of searches, see [`SearchStats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchStatsSnapshot {
+    /// The number of searches.
+    pub searches: u64,
+    /// The number of searches that were cancelled before completing.
+    pub cancelled: u64,
+    /// The number of searches that found fewer items than requested in the graph and had to
+    /// scan the remaining items.
+    pub exhaustive_fallbacks: u64,
+    /// The mean size of the search queue, the largest of `ef` and the number of requested items.
+    pub mean_ef: f64,
+    /// The mean duration of the searches.
+    pub mean_latency: Duration,
+    latency_buckets: [u64; LATENCY_BUCKETS],
+}
+
+impl SearchStatsSnapshot {
+    /// Returns an upper bound of the latency under which a `quantile` of the searches, between
+    /// `0.0` and `1.0`, completed. The bound is a power of two of microseconds.
+    pub fn latency_quantile(&self, quantile: f64) -> Duration {
+        let rank = (quantile.clamp(0.0, 1.0) * self.searches as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bucket, &count) in self.latency_buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank.max(1) {
+                return Duration::from_micros(1 << bucket);
+            }
+        }
+        Duration::ZERO
+    }
+}
+
+/// The tolerance under which the norm of a vector is considered to be `1`, see
+/// [`VectorStats::unit_norms`].
+const UNIT_NORM_TOLERANCE: f64 = 1e-3;
+
+/// The statistics of the vectors of an index, see [`crate::Reader::vector_stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorStats {
+    /// The number of items the statistics were computed from.
+    pub items: u64,
+    /// The statistics of every dimension of the vectors, in order.
+    pub dimensions: Vec<Summary>,
+    /// The statistics of the euclidean norms of the vectors.
+    pub norms: Summary,
+    /// The number of vectors whose norm is `1`, up to a `1e-3` tolerance. It is lower than
+    /// [`Self::items`] if someone inserted un-normalized vectors in an index meant to store
+    /// normalized ones.
+    pub unit_norms: u64,
+}
+
+/// The minimum, maximum, mean and variance of a series of values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    /// The smallest value, `+∞` if there were no values.
+    pub min: f64,
+    /// The largest value, `-∞` if there were no values.
+    pub max: f64,
+    /// The mean of the values, `0.0` if there were none.
+    pub mean: f64,
+    /// The population variance of the values, `0.0` if there were none.
+    pub variance: f64,
+}
+
+/// Accumulates the statistics of the vectors of an index, one vector at a time.
+pub(crate) struct VectorStatsBuilder {
+    items: u64,
+    dimensions: Vec<Moments>,
+    norms: Moments,
+    unit_norms: u64,
+}
+
+impl VectorStatsBuilder {
+    pub fn new(dimensions: usize) -> VectorStatsBuilder {
+        VectorStatsBuilder {
+            items: 0,
+            dimensions: vec![Moments::default(); dimensions],
+            norms: Moments::default(),
+            unit_norms: 0,
+        }
+    }
+
+    pub fn push(&mut self, vector: impl Iterator<Item = f32>) {
+        let mut squared_norm = 0.0;
+        for (moments, x) in self.dimensions.iter_mut().zip(vector) {
+            moments.push(x as f64);
+            squared_norm += x as f64 * x as f64;
+        }
+        let norm = f64::sqrt(squared_norm);
+        self.norms.push(norm);
+        self.unit_norms += ((norm - 1.0).abs() <= UNIT_NORM_TOLERANCE) as u64;
+        self.items += 1;
+    }
+
+    pub fn finish(self) -> VectorStats {
+        VectorStats {
+            items: self.items,
+            dimensions: self.dimensions.iter().map(Moments::summary).collect(),
+            norms: self.norms.summary(),
+            unit_norms: self.unit_norms,
+        }
+    }
+}
+
+/// The running moments of a series of values, updated with Welford's algorithm which doesn't
+/// lose precision on long series.
+#[derive(Debug, Clone, Copy)]
+struct Moments {
+    count: u64,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for Moments {
+    fn default() -> Moments {
+        Moments { count: 0, mean: 0.0, m2: 0.0, min: f64::INFINITY, max: f64::NEG_INFINITY }
+    }
+}
+
+impl Moments {
+    fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+    }
+
+    fn summary(&self) -> Summary {
+        let variance = if self.count == 0 { 0.0 } else { self.m2 / self.count as f64 };
+        Summary { min: self.min, max: self.max, mean: self.mean, variance }
+    }
+}
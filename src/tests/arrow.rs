@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use arrow_array::types::Float32Type;
+use arrow_array::{ArrayRef, FixedSizeListArray, Int64Array, RecordBatch, UInt32Array};
+use arrow_schema::ArrowError;
+
+use super::{create_database, rng};
+use crate::distance::Euclidean;
+use crate::{Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+fn batch(ids: ArrayRef, vectors: Vec<Option<Vec<Option<f32>>>>) -> RecordBatch {
+    let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(vectors, 2);
+    RecordBatch::try_from_iter([("id", ids), ("embedding", Arc::new(vectors) as ArrayRef)]).unwrap()
+}
+
+#[test]
+fn add_record_batch() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+
+    let vectors = (0..10).map(|i| Some(vec![Some(i as f32), Some(1.0)])).collect();
+    let ids = Arc::new(UInt32Array::from_iter_values((0..10).rev()));
+    // slicing makes the list offsets start in the middle of the values buffer
+    writer
+        .add_record_batch(&mut wtxn, &batch(ids, vectors).slice(2, 8), "id", "embedding")
+        .unwrap();
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_items(), 8);
+    assert_eq!(reader.item_vector(&wtxn, 7).unwrap().unwrap(), [2.0, 1.0]);
+    assert_eq!(reader.item_vector(&wtxn, 0).unwrap().unwrap(), [9.0, 1.0]);
+    assert!(!reader.contains_item(&wtxn, 8).unwrap());
+}
+
+#[test]
+fn add_record_batch_rejects_invalid_columns() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    let vector = || Some(vec![Some(0.0), Some(1.0)]);
+
+    let batch_ok = batch(Arc::new(UInt32Array::from(vec![0])), vec![vector()]);
+    let err = writer.add_record_batch(&mut wtxn, &batch_ok, "ids", "embedding").unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid column `ids`: missing column");
+    let err = writer.add_record_batch(&mut wtxn, &batch_ok, "embedding", "id").unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("Invalid column `embedding`: expected UInt32 but got FixedSizeList"));
+
+    let batch_i64 = batch(Arc::new(Int64Array::from(vec![0])), vec![vector()]);
+    let err = writer.add_record_batch(&mut wtxn, &batch_i64, "id", "embedding").unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid column `id`: expected UInt32 but got Int64");
+
+    let batch_null = batch(Arc::new(UInt32Array::from(vec![0, 1])), vec![vector(), None]);
+    let err = writer.add_record_batch(&mut wtxn, &batch_null, "id", "embedding").unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid column `embedding`: contains nulls");
+
+    let batch_null =
+        batch(Arc::new(UInt32Array::from(vec![Some(0), None])), vec![vector(), vector()]);
+    let err = writer.add_record_batch(&mut wtxn, &batch_null, "id", "embedding").unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid column `id`: contains 1 nulls");
+
+    assert!(writer.is_empty(&wtxn).unwrap());
+}
+
+#[test]
+fn import_record_batches() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+
+    let batches = (0..3).map(|b| {
+        let ids = Arc::new(UInt32Array::from_iter_values(b * 4..b * 4 + 4));
+        let vectors = (b * 4..b * 4 + 4).map(|i| Some(vec![Some(i as f32), Some(0.0)])).collect();
+        Ok(batch(ids, vectors))
+    });
+    let rows = writer.import_record_batches(&mut wtxn, batches, "id", "embedding").unwrap();
+    assert_eq!(rows, 12);
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_items(), 12);
+    assert_eq!(reader.item_vector(&wtxn, 9).unwrap().unwrap(), [9.0, 0.0]);
+
+    // the batches read before an error of the stream are kept
+    let ids = Arc::new(UInt32Array::from(vec![12]));
+    let batches = [
+        Ok(batch(ids, vec![Some(vec![Some(12.0), Some(0.0)])])),
+        Err(ArrowError::IoError("truncated".into(), std::io::ErrorKind::UnexpectedEof.into())),
+    ];
+    let err = writer.import_record_batches(&mut wtxn, batches, "id", "embedding").unwrap_err();
+    insta::assert_snapshot!(err, @"Io error: truncated");
+    assert!(writer.contains_item(&wtxn, 12).unwrap());
+}
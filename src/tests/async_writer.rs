@@ -0,0 +1,34 @@
+use roaring::RoaringBitmap;
+
+use super::{create_database, DatabaseHandle};
+use crate::distance::Euclidean;
+use crate::{AsyncWriter, Reader, Writer};
+
+#[test]
+fn write_and_build_on_the_blocking_pool() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let writer = AsyncWriter::new(env.clone(), Writer::new(database, 0, 2)).batch_size(3);
+
+    let progress = runtime.block_on(async {
+        let items = (0..10).map(|i| (i, vec![i as f32, 0.0])).collect();
+        writer.add_items(items).await.unwrap();
+        let deleted = writer.del_items(RoaringBitmap::from_iter([8, 9, 10])).await.unwrap();
+        assert_eq!(deleted, 2);
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        writer.build::<16, 32>(42, Some(sender)).await.unwrap();
+        let mut progress = Vec::new();
+        while let Some(step) = receiver.recv().await {
+            progress.push(step);
+        }
+        progress
+    });
+    assert!(!progress.is_empty());
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.item_ids(), &RoaringBitmap::from_iter(0..8));
+    let found = reader.nns(1).by_vector(&rtxn, &[3.1, 0.0]).unwrap().into_nns();
+    assert_eq!(found[0].0, 3);
+}
@@ -0,0 +1,74 @@
+use heed::EnvOpenOptions;
+use roaring::RoaringBitmap;
+
+use crate::distance::Euclidean;
+use crate::{AttributeDatabase, AttributeFilter, Attributes, Database, Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn filter_the_searches_by_attributes() {
+    let dir = tempfile::tempdir().unwrap();
+    let env =
+        unsafe { EnvOpenOptions::new().max_dbs(2).map_size(10 * 1024 * 1024).open(dir.path()) }
+            .unwrap();
+    let mut wtxn = env.write_txn().unwrap();
+    let database: Database<Euclidean> = env.create_database(&mut wtxn, Some("vectors")).unwrap();
+    let attributes_db: AttributeDatabase =
+        env.create_database(&mut wtxn, Some("attributes")).unwrap();
+
+    let writer = Writer::new(database, 0, 2);
+    let attributes = Attributes::new(attributes_db, 0);
+    for i in 0..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+        let color = if i % 2 == 0 { "red" } else { "blue" };
+        attributes.add(&mut wtxn, i, "color", color).unwrap();
+        if i % 5 == 0 {
+            attributes.add(&mut wtxn, i, "tag", "sale").unwrap();
+            attributes.add(&mut wtxn, i, "tag", "new").unwrap();
+        }
+    }
+    writer.builder(&mut super::rng()).build::<M, M0>(&mut wtxn).unwrap();
+    // the attributes of the other indexes are kept apart
+    Attributes::new(attributes_db, 1).add(&mut wtxn, 100, "color", "red").unwrap();
+
+    let red = attributes.items(&wtxn, "color", "red").unwrap();
+    assert_eq!(red, (0..20).step_by(2).collect::<RoaringBitmap>());
+    let values = attributes.values(&wtxn, "color").unwrap();
+    assert_eq!(values, vec![("blue".to_owned(), 10), ("red".to_owned(), 10)]);
+    let values = attributes.values(&wtxn, "tag").unwrap();
+    assert_eq!(values, vec![("new".to_owned(), 4), ("sale".to_owned(), 4)]);
+
+    let filter = AttributeFilter::And(vec![
+        AttributeFilter::Is("color", "blue"),
+        AttributeFilter::AnyOf("tag", vec!["sale", "missing"]),
+    ]);
+    let candidates = attributes.filter(&wtxn, &filter).unwrap();
+    assert_eq!(candidates, RoaringBitmap::from_iter([5, 15]));
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let found = reader.nns(10).candidates(&candidates).by_vector(&wtxn, &[0.0, 0.0]).unwrap();
+    assert_eq!(found.into_nns(), vec![(5, 25.0), (15, 225.0)]);
+
+    let filter = AttributeFilter::Or(vec![
+        AttributeFilter::Is("tag", "new"),
+        AttributeFilter::Is("color", "blue"),
+    ]);
+    assert_eq!(attributes.filter(&wtxn, &filter).unwrap().len(), 12);
+    assert_eq!(attributes.filter(&wtxn, &AttributeFilter::And(Vec::new())).unwrap().len(), 20);
+
+    assert!(attributes.remove(&mut wtxn, 5, "tag", "sale").unwrap());
+    assert!(!attributes.remove(&mut wtxn, 5, "tag", "sale").unwrap());
+    attributes.remove_item(&mut wtxn, 15).unwrap();
+    let candidates = attributes.filter(&wtxn, &AttributeFilter::Is("tag", "sale")).unwrap();
+    assert_eq!(candidates, RoaringBitmap::from_iter([0, 10]));
+    assert_eq!(attributes.values(&wtxn, "color").unwrap()[0], ("blue".to_owned(), 9));
+
+    let err = attributes.add(&mut wtxn, 0, "co\0lor", "red").unwrap_err();
+    insta::assert_snapshot!(err, @r#"Invalid attribute name "co\0lor", it must be non-empty and without NUL bytes"#);
+
+    attributes.clear(&mut wtxn).unwrap();
+    assert!(attributes.values(&wtxn, "color").unwrap().is_empty());
+    let other = Attributes::new(attributes_db, 1);
+    assert_eq!(other.items(&wtxn, "color", "red").unwrap(), RoaringBitmap::from_iter([100]));
+}
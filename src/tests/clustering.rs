@@ -0,0 +1,96 @@
+use rand::Rng;
+use roaring::RoaringBitmap;
+
+use super::{create_database, rng, DatabaseHandle};
+use crate::clustering::{near_duplicates, KMeans};
+use crate::distance::Euclidean;
+use crate::{Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn kmeans_finds_separated_blobs() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+
+    // three blobs of 100 items around (0, 0), (100, 0) and (0, 100)
+    let centers = [[0.0, 0.0], [100.0, 0.0], [0.0, 100.0]];
+    for (blob, [x, y]) in centers.iter().enumerate() {
+        for i in 0..100 {
+            let vector = [x + rng.gen_range(-1.0..1.0), y + rng.gen_range(-1.0..1.0)];
+            writer.add_item(&mut wtxn, (blob * 100 + i) as u32, &vector).unwrap();
+        }
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    for use_graph in [false, true] {
+        let kmeans = KMeans { batch_size: 64, iterations: 20, use_graph, ..KMeans::new(3) };
+        let clusters = kmeans.run(&wtxn, &reader, &mut rng).unwrap();
+        assert_eq!(clusters.len(), 3);
+
+        // every blob is a cluster whose centroid is close to the center of the blob
+        for (blob, [x, y]) in centers.iter().enumerate() {
+            let cluster = clusters.cluster_of(blob as u32 * 100).unwrap();
+            let members = &clusters.members()[cluster];
+            assert_eq!(members.len(), 100, "{clusters:?}");
+            assert!(members.iter().all(|item| item / 100 == blob as u32));
+
+            let centroid = &clusters.centroids()[cluster];
+            assert!((centroid[0] - x).abs() < 1.0 && (centroid[1] - y).abs() < 1.0, "{centroid:?}");
+        }
+        assert_eq!(clusters.cluster_of(300), None);
+    }
+
+    // there can't be more clusters than items
+    let clusters = KMeans::new(1000).run(&wtxn, &reader, &mut rng).unwrap();
+    assert_eq!(clusters.len(), 300);
+    assert_eq!(clusters.members().iter().map(|members| members.len()).sum::<u64>(), 300);
+}
+
+#[test]
+fn kmeans_over_an_empty_index() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let clusters = KMeans::new(4).run(&wtxn, &reader, &mut rng).unwrap();
+    assert!(clusters.is_empty());
+}
+
+#[test]
+fn group_the_near_duplicates() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+
+    // items 0 to 99 are spread on a grid, 100 to 104 duplicate item 0 and 105 to 109 item 55
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[(i % 10) as f32, (i / 10) as f32]).unwrap();
+    }
+    for i in 0..5 {
+        let offset = i as f32 * 0.001;
+        writer.add_item(&mut wtxn, 100 + i, &[offset, 0.0]).unwrap();
+        writer.add_item(&mut wtxn, 105 + i, &[5.0 + offset, 5.0]).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let groups = near_duplicates(&wtxn, &reader, 0.01).unwrap();
+    let expected: Vec<RoaringBitmap> = vec![
+        [0, 100, 101, 102, 103, 104].into_iter().collect(),
+        [55].into_iter().chain(105..110).collect(),
+    ];
+    assert_eq!(groups, expected);
+
+    // with a large enough threshold, the grid is a single group
+    let groups = near_duplicates(&wtxn, &reader, 1.0).unwrap();
+    assert_eq!(groups, vec![reader.item_ids().clone()]);
+}
@@ -0,0 +1,218 @@
+use rand::Rng;
+
+use crate::distance::{
+    BrayCurtis, Canberra, Chebyshev, Cosine, Distance, EarthMovers, Hellinger, Poincare,
+    U8Euclidean, U8Manhattan, WeightedEuclidean,
+};
+use crate::node::Item;
+use crate::tests::{create_database, rng, DatabaseHandle};
+use crate::{Error, Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+/// Returns the distance between two vectors as the writer and the queries would transform them.
+fn distance<D: Distance>(p: &[f32], q: &[f32]) -> f32 {
+    D::distance(
+        &Item::new(D::preprocess(p).into_owned()),
+        &Item::new(D::preprocess(q).into_owned()),
+    )
+}
+
+/// Builds an index of `vectors` and checks that the closest items match an exhaustive ranking.
+fn search_matches_brute_force<D: Distance>(vectors: &[Vec<f32>]) {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<D>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, vectors[0].len());
+    for (id, vector) in vectors.iter().enumerate() {
+        writer.add_item(&mut wtxn, id as u32, vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<D>::open(&rtxn, 0, database).unwrap();
+    let query = &vectors[0];
+    let mut expected: Vec<_> = vectors
+        .iter()
+        .enumerate()
+        .map(|(id, vector)| (id as u32, distance::<D>(query, vector)))
+        .collect();
+    expected.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    let found = reader.nns(5).ef_search(vectors.len()).by_vector(&rtxn, query).unwrap();
+    let found: Vec<_> = found.into_nns().into_iter().map(|(_, distance)| distance).collect();
+    let expected: Vec<_> = expected.into_iter().take(5).map(|(_, distance)| distance).collect();
+    assert_eq!(found, expected, "{}", D::name());
+}
+
+fn random_vectors(count: usize, dimensions: usize, range: std::ops::Range<f32>) -> Vec<Vec<f32>> {
+    let mut rng = rng();
+    (0..count).map(|_| (0..dimensions).map(|_| rng.gen_range(range.clone())).collect()).collect()
+}
+
+#[test]
+fn chebyshev_is_the_largest_difference() {
+    assert_eq!(distance::<Chebyshev>(&[1.0, -2.0, 3.0], &[1.5, 2.0, 2.0]), 4.0);
+    assert_eq!(distance::<Chebyshev>(&[1.0, 2.0], &[1.0, 2.0]), 0.0);
+    search_matches_brute_force::<Chebyshev>(&random_vectors(200, 8, -1.0..1.0));
+}
+
+#[test]
+fn canberra_weights_the_differences_by_their_magnitude() {
+    assert_eq!(distance::<Canberra>(&[1.0, 0.0, 3.0], &[3.0, 0.0, 3.0]), 0.5);
+    assert_eq!(distance::<Canberra>(&[0.0, 0.0], &[0.0, 0.0]), 0.0);
+    // any change from zero counts fully, however small it is
+    assert_eq!(distance::<Canberra>(&[0.0], &[0.001]), 1.0);
+    search_matches_brute_force::<Canberra>(&random_vectors(200, 8, 0.0..10.0));
+}
+
+#[test]
+fn bray_curtis_is_the_share_of_the_abundances_that_differ() {
+    assert_eq!(distance::<BrayCurtis>(&[6.0, 7.0, 4.0], &[10.0, 0.0, 6.0]), 13.0 / 33.0);
+    assert_eq!(distance::<BrayCurtis>(&[1.0, 0.0], &[0.0, 1.0]), 1.0);
+    assert_eq!(distance::<BrayCurtis>(&[0.0, 0.0], &[0.0, 0.0]), 0.0);
+    search_matches_brute_force::<BrayCurtis>(&random_vectors(200, 8, 0.0..10.0));
+}
+
+#[test]
+fn hellinger_compares_the_square_roots_of_the_frequencies() {
+    // the frequencies are normalized, only the profiles matter
+    assert_eq!(distance::<Hellinger>(&[1.0, 3.0], &[2.0, 6.0]), 0.0);
+    assert_eq!(distance::<Hellinger>(&[1.0, 0.0], &[0.0, 1.0]), 1.0);
+    let (half, quarter, three_quarters) = (0.5f32.sqrt(), 0.25f32.sqrt(), 0.75f32.sqrt());
+    let expected = ((half - quarter).powi(2) + (half - three_quarters).powi(2)) / 2.0;
+    assert!((distance::<Hellinger>(&[0.5, 0.5], &[0.25, 0.75]) - expected.sqrt()).abs() < 1e-6);
+    search_matches_brute_force::<Hellinger>(&random_vectors(200, 8, 0.0..10.0));
+
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Hellinger>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    writer.add_item(&mut wtxn, 0, &[1.0, 3.0]).unwrap();
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Hellinger>::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.item_vector(&rtxn, 0).unwrap().unwrap(), vec![0.5, 0.75f32.sqrt()]);
+    let found = reader.nns(1).by_vector(&rtxn, &[2.0, 6.0]).unwrap().into_nns();
+    assert_eq!(found, vec![(0, 0.0)]);
+}
+
+#[test]
+fn weighted_euclidean_weighs_the_dimensions() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<WeightedEuclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    let error = writer.set_weights(&mut wtxn, &[1.0]).unwrap_err();
+    assert!(matches!(error, Error::InvalidDimensions { expected: 2, got: 1 }));
+    let error = writer.set_weights(&mut wtxn, &[1.0, -1.0]).unwrap_err();
+    assert!(matches!(error, Error::InvalidWeights { .. }));
+
+    // the second dimension is ignored, only the first one tells the items apart
+    writer.set_weights(&mut wtxn, &[4.0, 0.0]).unwrap();
+    writer.add_item(&mut wtxn, 0, &[1.0, 0.0]).unwrap();
+    writer.add_items(&mut wtxn, [(1, &[0.0, 0.0][..]), (2, &[0.0, 10.0][..])]).unwrap();
+    let error = writer.set_weights(&mut wtxn, &[1.0, 1.0]).unwrap_err();
+    assert!(matches!(error, Error::InvalidWeights { .. }));
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<WeightedEuclidean>::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.weights(), Some(&[4.0, 0.0][..]));
+    assert_eq!(reader.item_vector(&rtxn, 0).unwrap().unwrap(), vec![2.0, 0.0]);
+    let found = reader.nns(3).by_vector(&rtxn, &[0.0, 9.0]).unwrap().into_nns();
+    assert_eq!(found[0].1, 0.0);
+    assert_eq!(found[2], (0, 4.0));
+    let found = reader.nns(1).by_item(&rtxn, 0).unwrap().unwrap().into_nns();
+    // the stored vectors are already scaled, they aren't scaled again
+    assert_eq!(found[0].1, 4.0);
+    drop(rtxn);
+
+    // the writer reads the weights again once it cleared the index
+    let mut wtxn = env.write_txn().unwrap();
+    writer.clear(&mut wtxn).unwrap();
+    writer.set_weights(&mut wtxn, &[1.0, 1.0]).unwrap();
+    writer.add_item(&mut wtxn, 0, &[1.0, 0.0]).unwrap();
+    assert_eq!(writer.item_vector(&wtxn, 0).unwrap().unwrap(), vec![1.0, 0.0]);
+    wtxn.abort();
+
+    // the weights set by an aborted transaction are dropped and the ones of another writer seen
+    let mut wtxn = env.write_txn().unwrap();
+    writer.add_item(&mut wtxn, 3, &[1.0, 0.0]).unwrap();
+    assert_eq!(writer.item_vector(&wtxn, 3).unwrap().unwrap(), vec![2.0, 0.0]);
+    let other = Writer::new(database, 0, 2);
+    other.clear(&mut wtxn).unwrap();
+    other.set_weights(&mut wtxn, &[9.0, 1.0]).unwrap();
+    writer.add_item(&mut wtxn, 0, &[1.0, 0.0]).unwrap();
+    assert_eq!(writer.item_vector(&wtxn, 0).unwrap().unwrap(), vec![3.0, 0.0]);
+}
+
+#[test]
+fn u8_vectors_are_stored_and_compared_as_bytes() {
+    assert_eq!(distance::<U8Manhattan>(&[0.0, 255.0, 10.0], &[255.0, 0.0, 12.0]), 512.0);
+    assert_eq!(distance::<U8Euclidean>(&[0.0, 255.0, 10.0], &[255.0, 0.0, 12.0]), 130_054.0);
+    // the f32 are rounded to the closest byte
+    assert_eq!(distance::<U8Manhattan>(&[-3.0, 300.0, 9.6], &[0.0, 255.0, 10.0]), 0.0);
+    search_matches_brute_force::<U8Euclidean>(&random_vectors(200, 16, 0.0..255.0));
+    search_matches_brute_force::<U8Manhattan>(&random_vectors(200, 16, 0.0..255.0));
+
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<U8Manhattan>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 4);
+    writer.add_u8_item(&mut wtxn, 0, &[12, 255, 0, 87]).unwrap();
+    writer.add_u8_item(&mut wtxn, 1, &[0, 0, 0, 0]).unwrap();
+    let error = writer.add_u8_item(&mut wtxn, 2, &[1, 2]).unwrap_err();
+    assert!(matches!(error, Error::InvalidDimensions { expected: 4, got: 2 }));
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<U8Manhattan>::open(&rtxn, 0, database).unwrap();
+    let vector = reader.item_vector_ref(&rtxn, 0).unwrap().unwrap();
+    assert_eq!(vector.values(), &[12, 255, 0, 87]);
+    assert_eq!(reader.item_vector(&rtxn, 0).unwrap().unwrap(), vec![12.0, 255.0, 0.0, 87.0]);
+    let found = reader.nns(2).by_u8_vector(&rtxn, &[10, 250, 0, 90]).unwrap().into_nns();
+    assert_eq!(found, vec![(0, 10.0), (1, 350.0)]);
+}
+
+#[test]
+fn cosine_reads_the_norms_stored_at_write_time() {
+    let p = Item::<Cosine>::new(vec![3.0, 4.0]);
+    let q = Item::<Cosine>::new(vec![4.0, 3.0]);
+    assert_eq!(Cosine::norm(&p), 5.0);
+    assert_eq!(Cosine::distance(&p, &q), (1.0 - 24.0 / 25.0) / 2.0);
+
+    // the vector isn't gone through again, a stale norm shows in the distance
+    let stale = Item::<Cosine> { header: bytemuck::cast(10.0f32), vector: p.vector.clone() };
+    assert_eq!(Cosine::norm(&stale), 10.0);
+    assert_eq!(Cosine::distance(&stale, &q), (1.0 - 24.0 / 50.0) / 2.0);
+}
+
+#[test]
+fn earth_movers_counts_how_far_the_mass_moves() {
+    // moving all the mass by one bin costs one, by two bins costs two
+    assert_eq!(distance::<EarthMovers>(&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]), 1.0);
+    assert_eq!(distance::<EarthMovers>(&[1.0, 0.0, 0.0], &[0.0, 0.0, 1.0]), 2.0);
+    // the histograms are normalized
+    assert_eq!(distance::<EarthMovers>(&[2.0, 2.0, 0.0], &[0.0, 1.0, 1.0]), 1.0);
+    search_matches_brute_force::<EarthMovers>(&random_vectors(200, 16, 0.0..10.0));
+}
+
+#[test]
+fn poincare_is_the_hyperbolic_distance_in_the_ball() {
+    let d = distance::<Poincare>(&[0.0, 0.0], &[0.5, 0.0]);
+    assert!((d - 3.0f32.ln()).abs() < 1e-6, "{d}");
+    assert_eq!(distance::<Poincare>(&[0.3, -0.2], &[0.3, -0.2]), 0.0);
+    // the same euclidean gap is much longer near the boundary
+    assert!(
+        distance::<Poincare>(&[0.8, 0.0], &[0.9, 0.0])
+            > 3.0 * distance::<Poincare>(&[0.0, 0.0], &[0.1, 0.0])
+    );
+    // the points on the boundary or outside stay at a finite distance
+    assert!(distance::<Poincare>(&[0.0, 0.0], &[1.0, 0.0]).is_finite());
+    assert!(distance::<Poincare>(&[0.0, 0.0], &[3.0, 0.0]).is_finite());
+    search_matches_brute_force::<Poincare>(&random_vectors(200, 4, -0.45..0.45));
+}
@@ -0,0 +1,121 @@
+use rand::Rng;
+
+use super::{create_database_indices_with_items, rng, DatabaseHandle};
+use crate::distance::Euclidean;
+use crate::eval::{recall, sample_queries, tune_ef_search, RecallMonitor};
+use crate::{Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn recall_against_exact_search() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 500, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+
+    let queries = sample_queries(&rtxn, &reader, 50, &mut rng).unwrap();
+    assert_eq!(queries.len(), 50);
+    assert!(queries.iter().all(|query| query.len() == DIM));
+
+    let found = recall(&rtxn, &reader, &queries, 10, 100).unwrap();
+    assert_eq!(found.queries(), 50);
+    assert!(found.mean() > 0.9, "{found:?}");
+    assert!(found.recalls().windows(2).all(|w| w[0] <= w[1]));
+    assert!(found.percentile(0.0) <= found.mean() && found.mean() <= found.percentile(1.0));
+
+    // the nearest item of an indexed vector is itself, and a queue as large as the index
+    // finds the nearest item of any query
+    assert_eq!(recall(&rtxn, &reader, &queries, 1, 100).unwrap().mean(), 1.0);
+    let random: Vec<[f32; DIM]> = (0..20).map(|_| std::array::from_fn(|_| rng.gen())).collect();
+    assert_eq!(recall(&rtxn, &reader, &random, 1, 500).unwrap().mean(), 1.0);
+
+    // sampling more queries than items returns all of them
+    assert_eq!(sample_queries(&rtxn, &reader, 1000, &mut rng).unwrap().len(), 500);
+}
+
+#[test]
+fn tune_ef_search_to_a_target_recall() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 500, &mut rng);
+    let queries: Vec<[f32; DIM]> = (0..50).map(|_| std::array::from_fn(|_| rng.gen())).collect();
+
+    let mut wtxn = env.write_txn().unwrap();
+    let tuning = tune_ef_search(&mut wtxn, database, 0, &queries, 10, 0.95).unwrap();
+    assert!(tuning.recall >= 0.95, "{tuning:?}");
+    assert_eq!((tuning.k, tuning.target_recall), (10, 0.95));
+
+    // the recommendation is the smallest queue reaching the target
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let reached = recall(&wtxn, &reader, &queries, 10, tuning.ef_search).unwrap();
+    assert_eq!(reached.mean(), tuning.recall);
+    if tuning.ef_search > 10 {
+        let below = recall(&wtxn, &reader, &queries, 10, tuning.ef_search - 1).unwrap();
+        assert!(below.mean() < 0.95, "{below:?}");
+    }
+
+    // an unreachable target recommends a queue as large as the index
+    let unreachable = tune_ef_search(&mut wtxn, database, 0, &queries, 10, 1.5).unwrap();
+    assert_eq!(unreachable.ef_search, 500);
+    assert!(unreachable.recall <= 1.0);
+    wtxn.commit().unwrap();
+
+    // the last recommendation is persisted and used by default
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.ef_search_tuning(), Some(unreachable));
+    let default = reader.nns(10).by_vector(&rtxn, &queries[0]).unwrap().into_nns();
+    let tuned = reader.nns(10).ef_search(500).by_vector(&rtxn, &queries[0]).unwrap().into_nns();
+    assert_eq!(default, tuned);
+    drop(rtxn);
+
+    // a build that changes the graph clears the recommendation
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, DIM);
+    writer.del_item(&mut wtxn, 0).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.ef_search_tuning(), None);
+}
+
+#[test]
+fn monitor_recall_in_the_background() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 500, &mut rng);
+    let monitor = RecallMonitor::builder(env.clone(), database, 0)
+        .interval(std::time::Duration::from_millis(5))
+        .sample(10)
+        .history(3)
+        .spawn();
+
+    // the recent queries of the right dimensions are measured first
+    monitor.observe(&[0.5; DIM]);
+    monitor.observe(&[0.5; 3]);
+    let sample = monitor.measure_now().unwrap().unwrap();
+    assert_eq!(sample.queries, 10);
+    assert!(sample.mean > 0.8 && sample.p5 <= sample.mean, "{sample:?}");
+
+    let started = std::time::Instant::now();
+    while monitor.history().len() < 3 {
+        assert!(started.elapsed().as_secs() < 10, "the monitor stopped measuring");
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    assert!(monitor.drift().is_some());
+    assert_eq!(monitor.history().last(), monitor.latest().as_ref());
+
+    // nothing is measured while the index needs a build
+    let mut wtxn = env.write_txn().unwrap();
+    Writer::new(database, 0, DIM).add_item(&mut wtxn, 1000, &[0.5; DIM]).unwrap();
+    wtxn.commit().unwrap();
+    assert_eq!(monitor.measure_now().unwrap(), None);
+}
@@ -0,0 +1,88 @@
+use roaring::RoaringBitmap;
+
+use super::{create_database, rng, DatabaseHandle};
+use crate::distance::Euclidean;
+use crate::export::{edges, write_subgraph, GraphFormat, Neighbours, Subgraph};
+use crate::{ItemId, Reader, Writer};
+
+const M: usize = 3;
+const M0: usize = 3;
+
+#[test]
+fn export_the_edges_of_the_graph() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let graph: Vec<_> = edges(&wtxn, &reader, Neighbours::Graph).collect::<Result<_, _>>().unwrap();
+    assert!(!graph.is_empty());
+    assert!(graph.windows(2).all(|w| w[0].0 <= w[1].0));
+    for &(source, target, distance) in &graph {
+        assert_ne!(source, target);
+        assert_eq!(distance, reader.distance(&wtxn, source, target).unwrap().unwrap());
+        assert!(reader.neighbours(&wtxn, source).unwrap().contains(&target));
+    }
+
+    // the two closest neighbours of every item, the ones at both ends have a single one at a
+    // distance of 1
+    let knn: Vec<_> = edges(&wtxn, &reader, Neighbours::Knn(2)).collect::<Result<_, _>>().unwrap();
+    assert_eq!(knn.len(), 2 * 20);
+    assert_eq!(&knn[..2], &[(0, 1, 1.0), (0, 2, 4.0)]);
+    assert!(knn.iter().all(|&(source, target, _)| source.abs_diff(target) <= 2));
+}
+
+#[test]
+fn write_a_subgraph_as_dot_and_gexf() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+
+    let dot = |subgraph| {
+        let mut out = Vec::new();
+        write_subgraph(&wtxn, &reader, subgraph, GraphFormat::Dot, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let nodes = |dot: &str| -> Vec<ItemId> {
+        dot.lines()
+            .filter(|line| line.contains("[layer=") && !line.contains("->"))
+            .map(|line| line.trim().split(' ').next().unwrap().parse().unwrap())
+            .collect()
+    };
+
+    // the items that don't exist are ignored
+    let items = RoaringBitmap::from_iter([0, 1, 2, 3, 42]);
+    let graph = dot(Subgraph::Items(&items));
+    assert!(graph.starts_with("digraph index_0 {\n") && graph.ends_with("}\n"), "{graph}");
+    assert_eq!(nodes(&graph), vec![0, 1, 2, 3]);
+    assert!(graph.contains("  0 -> 1 [layer=0, distance=1];"), "{graph}");
+    let n_links = graph.lines().filter(|line| line.contains("->")).count();
+    for line in graph.lines().filter(|line| line.contains("->")) {
+        let (source, rest) = line.trim().split_once(" -> ").unwrap();
+        let target = rest.split(' ').next().unwrap();
+        assert!(items.contains(source.parse().unwrap()) && items.contains(target.parse().unwrap()));
+    }
+
+    // the items within a distance of 4 of item 10 are the ones at most 2 apart
+    let graph = dot(Subgraph::Around { item: 10, radius: 4.0 });
+    assert_eq!(nodes(&graph), vec![8, 9, 10, 11, 12]);
+    let around = Subgraph::Around { item: 42, radius: 4.0 };
+    assert!(write_subgraph(&wtxn, &reader, around, GraphFormat::Dot, Vec::new()).is_err());
+
+    let mut out = Vec::new();
+    write_subgraph(&wtxn, &reader, Subgraph::Items(&items), GraphFormat::Gexf, &mut out).unwrap();
+    let gexf = String::from_utf8(out).unwrap();
+    assert!(gexf.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#), "{gexf}");
+    assert!(gexf.ends_with("</gexf>\n"), "{gexf}");
+    assert_eq!(gexf.matches("<node ").count(), 4);
+    assert_eq!(gexf.matches("<edge ").count(), n_links);
+}
@@ -0,0 +1,68 @@
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use crate::ffi::*;
+
+#[test]
+fn write_build_and_search_through_the_c_api() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = CString::new(dir.path().to_str().unwrap()).unwrap();
+
+    unsafe {
+        let mut env = ptr::null_mut();
+        assert_eq!(hannoy_env_open(path.as_ptr(), 10 * 1024 * 1024, &mut env), HannoyStatus::Ok);
+
+        // the distances are plain integers in C, an unknown one is refused
+        let mut writer = ptr::null_mut();
+        let status = hannoy_writer_new(env, 15, 0, 2, &mut writer);
+        assert_eq!(status, HannoyStatus::InvalidArgument);
+        let error = CStr::from_ptr(hannoy_last_error()).to_str().unwrap();
+        insta::assert_snapshot!(error, @"Unknown distance 15");
+        assert!(writer.is_null());
+
+        let status = hannoy_writer_new(env, HannoyDistance::Euclidean as u32, 0, 2, &mut writer);
+        assert_eq!(status, HannoyStatus::Ok);
+        for i in 0..10 {
+            let vector = [i as f32, 0.0];
+            let status = hannoy_writer_add_item(writer, i, vector.as_ptr(), vector.len());
+            assert_eq!(status, HannoyStatus::Ok);
+        }
+
+        // errors are reported through the last error
+        let status = hannoy_writer_add_item(writer, 10, [0.0].as_ptr(), 1);
+        assert_eq!(status, HannoyStatus::Error);
+        let error = CStr::from_ptr(hannoy_last_error()).to_str().unwrap();
+        insta::assert_snapshot!(error, @"Invalid dimensions. Got 1 but expected 2");
+
+        let mut existed = false;
+        assert_eq!(hannoy_writer_del_item(writer, 9, &mut existed), HannoyStatus::Ok);
+        assert!(existed);
+        assert_eq!(hannoy_writer_build(writer, 3, 100, 42), HannoyStatus::InvalidArgument);
+        assert_eq!(hannoy_writer_build(writer, 4, 100, 42), HannoyStatus::Ok);
+        assert_eq!(hannoy_writer_commit(writer), HannoyStatus::Ok);
+
+        let mut reader = ptr::null_mut();
+        let status = hannoy_reader_open(env, u32::MAX, 0, &mut reader);
+        assert_eq!(status, HannoyStatus::InvalidArgument);
+        let status = hannoy_reader_open(env, HannoyDistance::Euclidean as u32, 0, &mut reader);
+        assert_eq!(status, HannoyStatus::Ok);
+        let (mut ids, mut distances, mut len) = ([0; 3], [0.0; 3], 0);
+        let query = [8.9, 0.0];
+        let status = hannoy_reader_search(
+            reader,
+            query.as_ptr(),
+            query.len(),
+            3,
+            10,
+            ids.as_mut_ptr(),
+            distances.as_mut_ptr(),
+            &mut len,
+        );
+        assert_eq!(status, HannoyStatus::Ok);
+        assert_eq!(len, 3);
+        assert_eq!(ids, [8, 7, 6]);
+
+        hannoy_reader_free(reader);
+        hannoy_env_free(env);
+    }
+}
@@ -0,0 +1,42 @@
+use crate::fusion::{normalized_scores, reciprocal_rank, RRF_K};
+
+#[test]
+fn reciprocal_rank_fusion() {
+    let dense = [(1, 0.1), (2, 0.2), (3, 0.3)];
+    let sparse = [(3, -12.0), (1, -4.0), (4, 10.0)];
+    let fused = reciprocal_rank(&[&dense, &sparse], RRF_K);
+
+    let rrf = |rank: usize| 1.0 / (RRF_K + rank as f32);
+    assert_eq!(fused, vec![(1, rrf(1) + rrf(2)), (3, rrf(3) + rrf(1)), (2, rrf(2)), (4, rrf(3))]);
+    assert!(reciprocal_rank(&[], RRF_K).is_empty());
+}
+
+#[test]
+fn normalized_score_fusion() {
+    let approx_eq = |fused: Vec<(u32, f32)>, expected: &[(u32, f32)]| {
+        assert_eq!(fused.len(), expected.len(), "{fused:?}");
+        for (&(item, score), &(expected_item, expected_score)) in fused.iter().zip(expected) {
+            assert_eq!(item, expected_item, "{fused:?}");
+            assert!((score - expected_score).abs() < 1e-6, "{fused:?}");
+        }
+    };
+
+    // the distances of the lists aren't on the same scale
+    let cosine = [(1, 0.1), (2, 0.2), (3, 0.5)];
+    let dot = [(3, -100.0), (2, -60.0), (4, 0.0)];
+    let fused = normalized_scores(&[&cosine, &dot], None);
+    approx_eq(fused, &[(2, 0.75 + 0.6), (1, 1.0), (3, 1.0), (4, 0.0)]);
+
+    let fused = normalized_scores(&[&cosine, &dot], Some(&[1.0, 3.0]));
+    approx_eq(fused, &[(3, 3.0), (2, 0.75 + 1.8), (1, 1.0), (4, 0.0)]);
+
+    // the items of a list at the same distance all score the most
+    let same = [(5, 2.0), (6, 2.0)];
+    assert_eq!(normalized_scores(&[&same], None), vec![(5, 1.0), (6, 1.0)]);
+}
+
+#[test]
+#[should_panic(expected = "one weight is required per list")]
+fn normalized_score_fusion_requires_a_weight_per_list() {
+    normalized_scores(&[&[(1, 0.1)]], Some(&[1.0, 2.0]));
+}
@@ -0,0 +1,108 @@
+use rand::Rng;
+use roaring::RoaringBitmap;
+
+use super::{create_database_indices_with_items, rng, DatabaseHandle};
+use crate::distance::{Chebyshev, Cosine, Euclidean, Manhattan};
+use crate::gpu::GpuDistances;
+use crate::{Distance, FilterStrategy, Reader};
+
+const M: usize = 16;
+const M0: usize = 32;
+/// Not a multiple of the size of the workgroups of the shader.
+const DIM: usize = 33;
+
+/// Returns the GPU of the machine, the tests are skipped on the machines without one.
+fn gpu() -> Option<GpuDistances> {
+    match GpuDistances::new() {
+        Ok(gpu) => Some(gpu),
+        Err(e) => {
+            eprintln!("skipping the GPU test: {e}");
+            None
+        }
+    }
+}
+
+fn assert_same_nns(gpu: &[(u32, f32)], cpu: &[(u32, f32)]) {
+    assert_eq!(gpu.len(), cpu.len());
+    for (&(gpu_id, gpu_distance), &(cpu_id, cpu_distance)) in gpu.iter().zip(cpu) {
+        assert_eq!(gpu_id, cpu_id);
+        assert!((gpu_distance - cpu_distance).abs() <= 1e-4 * cpu_distance.max(1.0));
+    }
+}
+
+fn linear_scans<D: Distance>(gpu: &GpuDistances) {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<D, DIM, M, M0, _>(0..1, 500, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<D>::open(&rtxn, 0, database).unwrap();
+    let candidates: RoaringBitmap = (0..500).step_by(2).collect();
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+
+    let mut search = reader.nns(10);
+    search.candidates(&candidates).filter_strategy(FilterStrategy::Linear);
+    let cpu = search.by_vector(&rtxn, &query).unwrap();
+    let gpu = search.gpu(gpu).by_vector(&rtxn, &query).unwrap();
+    assert_same_nns(&gpu.nns, &cpu.nns);
+    assert!(gpu.nns.iter().all(|(id, _)| candidates.contains(*id)));
+}
+
+#[test]
+fn scan_the_candidates_on_the_gpu() {
+    let Some(gpu) = gpu() else { return };
+    assert!(GpuDistances::supports::<Euclidean>());
+    assert!(!GpuDistances::supports::<Chebyshev>());
+    linear_scans::<Euclidean>(&gpu);
+    linear_scans::<Cosine>(&gpu);
+    linear_scans::<Manhattan>(&gpu);
+}
+
+#[test]
+fn search_a_batch_of_queries() {
+    let Some(gpu) = gpu() else { return };
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 1000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let queries: Vec<[f32; DIM]> = (0..20).map(|_| std::array::from_fn(|_| rng.gen())).collect();
+    let queries: Vec<&[f32]> = queries.iter().map(|query| &query[..]).collect();
+
+    // every item is compared with every query
+    let found = reader.nns(10).gpu(&gpu).by_vector_batch(&rtxn, &queries).unwrap();
+    assert_eq!(found.len(), queries.len());
+    let all = reader.item_ids().clone();
+    for (found, query) in found.iter().zip(&queries) {
+        let mut exact = reader.nns(10);
+        exact.candidates(&all).filter_strategy(FilterStrategy::Linear);
+        assert_same_nns(&found.nns, &exact.by_vector(&rtxn, query).unwrap().nns);
+    }
+
+    // the candidates are the only items compared
+    let candidates: RoaringBitmap = (0..1000).step_by(7).collect();
+    let found = reader.nns(5).candidates(&candidates).gpu(&gpu).by_vector_batch(&rtxn, &queries);
+    for (found, query) in found.unwrap().iter().zip(&queries) {
+        let mut exact = reader.nns(5);
+        exact.candidates(&candidates).filter_strategy(FilterStrategy::Linear);
+        assert_same_nns(&found.nns, &exact.by_vector(&rtxn, query).unwrap().nns);
+    }
+
+    let err = reader.nns(10).gpu(&gpu).by_vector_batch(&rtxn, &[&[0.0; 3]]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid dimensions. Got 3 but expected 33");
+}
+
+#[test]
+fn search_a_batch_without_the_gpu() {
+    // the distances the GPU doesn't compute are searched in the graph
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Chebyshev, DIM, M, M0, _>(0..1, 200, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Chebyshev>::open(&rtxn, 0, database).unwrap();
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+
+    let found = reader.nns(10).by_vector_batch(&rtxn, &[&query, &query]).unwrap();
+    let single = reader.nns(10).by_vector(&rtxn, &query).unwrap();
+    assert_eq!(found[0].nns, single.nns);
+    assert_eq!(found[1].nns, single.nns);
+}
@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use super::{create_database, rng};
+use crate::distance::Euclidean;
+use crate::hdf5::Hdf5File;
+use crate::{Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+/// The datatype messages of the little-endian `float32` and `int32` numbers.
+const FLOAT32: [u8; 20] =
+    [0x11, 0x20, 0x1f, 0, 4, 0, 0, 0, 0, 0, 32, 0, 23, 8, 0, 23, 127, 0, 0, 0];
+const INT32: [u8; 12] = [0x10, 0x08, 0, 0, 4, 0, 0, 0, 0, 0, 32, 0];
+
+/// Appends an object header message, padded to a multiple of 8 bytes.
+fn message(header: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    let size = data.len().next_multiple_of(8);
+    header.extend_from_slice(&kind.to_le_bytes());
+    header.extend_from_slice(&(size as u16).to_le_bytes());
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(data);
+    header.resize(header.len() + size - data.len(), 0);
+}
+
+/// Returns a version 1 object header with its messages.
+fn object_header(messages: &[u8], count: u16) -> Vec<u8> {
+    let mut header = vec![1, 0];
+    header.extend_from_slice(&count.to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes());
+    header.extend_from_slice(&(messages.len() as u32).to_le_bytes());
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(messages);
+    header
+}
+
+fn symbol_table_entry(bytes: &mut Vec<u8>, name: u64, header: u64) {
+    bytes.extend_from_slice(&name.to_le_bytes());
+    bytes.extend_from_slice(&header.to_le_bytes());
+    bytes.extend_from_slice(&[0; 24]);
+}
+
+/// The name, datatype message, shape and little-endian values of a dataset.
+type Dataset<'a> = (&'a str, &'a [u8], [u64; 2], Vec<u8>);
+
+/// Writes the 2D `datasets` in the root group of an HDF5 file, the way h5py does with its
+/// default settings.
+fn write_hdf5(path: &Path, datasets: &[Dataset]) {
+    let mut names = vec![0];
+    let mut name_offsets = Vec::new();
+    for (name, ..) in datasets {
+        name_offsets.push(names.len() as u64);
+        names.extend_from_slice(name.as_bytes());
+        names.push(0);
+    }
+    names.resize(names.len().next_multiple_of(8), 0);
+
+    // the superblock, the root object header, the local heap, the B-tree and the symbol table
+    // node are followed by the object headers and the data of the datasets
+    let root = 96;
+    let heap = root + 40;
+    let tree = heap + 32 + names.len() as u64;
+    let node = tree + 48;
+    let mut headers = node + 8 + 40 * datasets.len() as u64;
+
+    let mut file = b"\x89HDF\r\n\x1a\n".to_vec();
+    file.extend_from_slice(&[0, 0, 0, 0, 0, 8, 8, 0, 4, 0, 16, 0, 0, 0, 0, 0]);
+    for address in [0, u64::MAX, 0, u64::MAX] {
+        file.extend_from_slice(&address.to_le_bytes());
+    }
+    symbol_table_entry(&mut file, 0, root);
+
+    let mut messages = Vec::new();
+    message(&mut messages, 0x0011, &[tree.to_le_bytes(), heap.to_le_bytes()].concat());
+    file.extend_from_slice(&object_header(&messages, 1));
+
+    file.extend_from_slice(b"HEAP\0\0\0\0");
+    file.extend_from_slice(&(names.len() as u64).to_le_bytes());
+    file.extend_from_slice(&u64::MAX.to_le_bytes());
+    file.extend_from_slice(&(heap + 32).to_le_bytes());
+    file.extend_from_slice(&names);
+
+    file.extend_from_slice(b"TREE\0\0\x01\0");
+    file.extend_from_slice(&[u64::MAX.to_le_bytes(), u64::MAX.to_le_bytes()].concat());
+    let last = *name_offsets.last().unwrap();
+    file.extend_from_slice(&[0u64.to_le_bytes(), node.to_le_bytes(), last.to_le_bytes()].concat());
+
+    let mut dataset_headers = Vec::new();
+    let mut data = Vec::new();
+    file.extend_from_slice(b"SNOD\x01\0");
+    file.extend_from_slice(&(datasets.len() as u16).to_le_bytes());
+    for ((_, datatype, [rows, columns], values), name) in datasets.iter().zip(name_offsets) {
+        let mut messages = Vec::new();
+        let dataspace = [[1, 2, 0, 0, 0, 0, 0, 0], rows.to_le_bytes(), columns.to_le_bytes()];
+        message(&mut messages, 0x0001, &dataspace.concat());
+        message(&mut messages, 0x0003, datatype);
+        // the address of the data is patched once the size of the headers is known
+        let layout = [[3, 1].as_slice(), &[0; 8], &(values.len() as u64).to_le_bytes()];
+        message(&mut messages, 0x0008, &layout.concat());
+        let header = object_header(&messages, 3);
+        symbol_table_entry(&mut file, name, headers);
+        headers += header.len() as u64;
+        dataset_headers.push(header);
+    }
+    for (header, (.., values)) in dataset_headers.iter_mut().zip(datasets) {
+        let layout = header.len() - 24 + 2;
+        header[layout..layout + 8].copy_from_slice(&(headers + data.len() as u64).to_le_bytes());
+        data.extend_from_slice(values);
+    }
+    file.extend(dataset_headers.concat());
+    file.extend(data);
+    std::fs::write(path, file).unwrap();
+}
+
+fn floats(values: impl IntoIterator<Item = f32>) -> Vec<u8> {
+    values.into_iter().flat_map(f32::to_le_bytes).collect()
+}
+
+#[test]
+fn load_an_ann_benchmarks_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dataset.hdf5");
+    let train = floats((0..100).flat_map(|i| [i as f32, 0.0]));
+    let test = floats([10.2, 0.0, 55.9, 0.0]);
+    let neighbors: Vec<u8> = [10i32, 11, 56, 55].iter().flat_map(|id| id.to_le_bytes()).collect();
+    write_hdf5(
+        &path,
+        &[
+            ("neighbors", &INT32, [2, 2], neighbors),
+            ("test", &FLOAT32, [2, 2], test),
+            ("train", &FLOAT32, [100, 2], train),
+        ],
+    );
+
+    let mut file = Hdf5File::open(&path).unwrap();
+    assert_eq!(file.dataset_names().collect::<Vec<_>>(), ["neighbors", "test", "train"]);
+    let (queries, dimensions) = file.read_f32_matrix("test").unwrap();
+    assert_eq!((queries, dimensions), (vec![10.2, 0.0, 55.9, 0.0], 2));
+    let (neighbors, k) = file.read_id_matrix("neighbors").unwrap();
+    assert_eq!((neighbors.clone(), k), (vec![10, 11, 56, 55], 2));
+
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    assert_eq!(writer.import_hdf5(&mut wtxn, &path, "train").unwrap(), 100);
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.item_vector(&wtxn, 42).unwrap().unwrap(), [42.0, 0.0]);
+    let found = reader.nns(2).by_vector(&wtxn, &[10.2, 0.0]).unwrap().into_nns();
+    assert_eq!(found.iter().map(|&(id, _)| id).collect::<Vec<_>>(), &neighbors[..2]);
+
+    let err = file.read_id_matrix("train").unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid HDF5 file: expected `train` to contain integers");
+    let err = file.read_f32_matrix("neighbors").unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid HDF5 file: expected `neighbors` to be a float32 matrix, got int32");
+    let err = file.read_f32_matrix("distances").unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid HDF5 file: missing dataset `distances`");
+    let err = Writer::<Euclidean>::new(handle.database, 1, 3)
+        .import_hdf5(&mut wtxn, &path, "train")
+        .unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid dimensions. Got 2 but expected 3");
+
+    std::fs::write(&path, b"not an hdf5 file").unwrap();
+    let err = Hdf5File::open(&path).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid HDF5 file: missing the signature");
+}
@@ -0,0 +1,42 @@
+use super::{create_database, rng};
+use crate::distance::{BinaryQuantizedCosine, Euclidean};
+use crate::{Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn import_export_round_trip() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+
+    let input = "{\"id\": 2, \"vector\": [2.0, 0.5]}\n\n{\"id\": 0, \"vector\": [0.0, 0.5]}\n";
+    assert_eq!(writer.import_jsonl(&mut wtxn, input.as_bytes()).unwrap(), 2);
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let mut output = Vec::new();
+    assert_eq!(reader.export_jsonl(&wtxn, &mut output).unwrap(), 2);
+    insta::assert_snapshot!(String::from_utf8(output).unwrap(), @r#"
+    {"id":0,"vector":[0.0,0.5]}
+    {"id":2,"vector":[2.0,0.5]}
+    "#);
+
+    let err = writer.import_jsonl(&mut wtxn, "{\"id\": 1, \"vector\": []}\n{\"id\": 1}".as_bytes());
+    insta::assert_snapshot!(err.unwrap_err(), @"Invalid item at line 2: missing field `vector` at line 1 column 9");
+    let err = writer.import_jsonl(&mut wtxn, "{\"id\": 1, \"vector\": [1.0]}".as_bytes());
+    insta::assert_snapshot!(err.unwrap_err(), @"Invalid dimensions. Got 1 but expected 2");
+}
+
+#[test]
+fn export_truncates_quantized_vectors() {
+    let handle = create_database::<BinaryQuantizedCosine>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 3);
+    writer.add_item(&mut wtxn, 0, &[1.0, -1.0, 1.0]).unwrap();
+
+    let mut output = Vec::new();
+    writer.export_jsonl(&wtxn, &mut output).unwrap();
+    insta::assert_snapshot!(String::from_utf8(output).unwrap(), @r#"{"id":0,"vector":[1.0,-1.0,1.0]}"#);
+}
@@ -0,0 +1,99 @@
+use heed::types::Bytes;
+use rand::Rng;
+
+use super::{create_database, rng, DatabaseHandle};
+use crate::distance::Cosine;
+use crate::{Key, Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+const DIMENSIONS: usize = 64;
+
+/// Returns a vector a few degrees away from `direction`.
+fn around(rng: &mut impl Rng, direction: &[f32]) -> Vec<f32> {
+    direction.iter().map(|x| x + rng.gen_range(-0.2..0.2)).collect()
+}
+
+#[test]
+fn prune_the_candidates_with_lsh_sketches() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Cosine>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, DIMENSIONS);
+
+    // items 0 to 99 point in a direction and 100 to 199 in the opposite one
+    let direction: Vec<f32> = (0..DIMENSIONS).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    let opposite: Vec<f32> = direction.iter().map(|x| -x).collect();
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &around(&mut rng, &direction)).unwrap();
+        writer.add_item(&mut wtxn, 100 + i, &around(&mut rng, &opposite)).unwrap();
+    }
+    writer.builder(&mut rng).lsh_sketches(128).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Cosine>::open(&wtxn, 0, database).unwrap();
+    let query = around(&mut rng, &direction);
+    let exact = reader.nns(10).by_vector(&wtxn, &query).unwrap().into_nns();
+    assert!(exact.iter().all(|&(item, _)| item < 100), "{exact:?}");
+
+    // a radius with all the bits prunes nothing, the close sketches keep the closest items
+    let all = reader.nns(10).lsh_prefilter(128).by_vector(&wtxn, &query).unwrap();
+    assert_eq!(all.into_nns(), exact);
+    let close = reader.nns(10).lsh_prefilter(32).by_vector(&wtxn, &query).unwrap();
+    assert_eq!(close.into_nns(), exact);
+
+    // only the items whose sketch is the one of the query are left
+    let pruned = reader.nns(10).lsh_prefilter(0).by_vector(&wtxn, &query).unwrap().into_nns();
+    assert!(pruned.len() < 10, "{pruned:?}");
+
+    // the sketches are removed by a build that doesn't ask for them and the option is ignored
+    let sketches = database.remap_data_type::<Bytes>();
+    assert!(sketches.get(&wtxn, &Key::sketches(0)).unwrap().is_some());
+    writer.add_item(&mut wtxn, 200, &query).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    assert!(sketches.get(&wtxn, &Key::sketches(0)).unwrap().is_none());
+
+    let reader = Reader::<Cosine>::open(&wtxn, 0, database).unwrap();
+    let found = reader.nns(10).lsh_prefilter(0).by_vector(&wtxn, &query).unwrap().into_nns();
+    assert_eq!(found.len(), 10);
+    assert_eq!(found[0].0, 200);
+}
+
+#[test]
+fn sketch_the_changed_items_by_position() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Cosine>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, DIMENSIONS);
+
+    // the ids are sparse, the region only holds the sketches of the items
+    for i in 0..20 {
+        let vector: Vec<f32> = (0..DIMENSIONS).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        writer.add_item(&mut wtxn, i * 100_000_000, &vector).unwrap();
+    }
+    writer.builder(&mut rng).lsh_sketches(128).build::<M, M0>(&mut wtxn).unwrap();
+    let sketches = database.remap_data_type::<Bytes>();
+    let region = sketches.get(&wtxn, &Key::sketches(0)).unwrap().unwrap();
+    assert!(region.len() < 1024, "{}", region.len());
+
+    let reader = Reader::<Cosine>::open(&wtxn, 0, database).unwrap();
+    let query = reader.item_vector(&wtxn, 300_000_000).unwrap().unwrap();
+    let found = reader.nns(1).lsh_prefilter(0).by_vector(&wtxn, &query).unwrap().into_nns();
+    assert_eq!(found[0].0, 300_000_000);
+
+    // an incremental build writes the same sketches as a complete one
+    for i in 0..5 {
+        let vector: Vec<f32> = (0..DIMENSIONS).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        writer.add_item(&mut wtxn, i * 100_000_000 + 1, &vector).unwrap();
+        let vector: Vec<f32> = (0..DIMENSIONS).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        writer.add_item(&mut wtxn, i * 100_000_000 + 2, &vector).unwrap();
+    }
+    let vector: Vec<f32> = (0..DIMENSIONS).map(|_| rng.gen_range(-1.0..1.0)).collect();
+    writer.add_item(&mut wtxn, 500_000_000, &vector).unwrap();
+    writer.del_item(&mut wtxn, 200_000_000).unwrap();
+    writer.del_item(&mut wtxn, 1_100_000_000).unwrap();
+    writer.builder(&mut rng).lsh_sketches(128).build::<M, M0>(&mut wtxn).unwrap();
+    let incremental = sketches.get(&wtxn, &Key::sketches(0)).unwrap().unwrap().to_vec();
+    crate::lsh::write(&mut wtxn, database, 0, Some(128), None).unwrap();
+    let complete = sketches.get(&wtxn, &Key::sketches(0)).unwrap().unwrap();
+    assert_eq!(incremental, complete);
+}
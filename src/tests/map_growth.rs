@@ -0,0 +1,53 @@
+use heed::EnvOpenOptions;
+use rand::Rng;
+
+use super::rng;
+use crate::distance::Euclidean;
+use crate::{write_with_map_growth, Database, MapGrowth, Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn grow_the_map_and_retry() {
+    let dir = tempfile::tempdir().unwrap();
+    let size = 256 * 1024;
+    let env = unsafe { EnvOpenOptions::new().map_size(size).open(dir.path()) }.unwrap();
+    let mut wtxn = env.write_txn().unwrap();
+    let database: Database<Euclidean> = env.create_database(&mut wtxn, None).unwrap();
+    wtxn.commit().unwrap();
+
+    let mut rng = rng();
+    let vectors: Vec<Vec<f32>> =
+        (0..2000).map(|_| (0..32).map(|_| rng.gen_range(-1.0..1.0)).collect()).collect();
+    let mut calls = 0;
+    let mut index = |wtxn: &mut heed::RwTxn| {
+        calls += 1;
+        let writer = Writer::new(database, 0, 32);
+        for (item, vector) in vectors.iter().enumerate() {
+            writer.add_item(wtxn, item as u32, vector)?;
+        }
+        let mut rng = super::rng();
+        let mut builder = writer.builder(&mut rng);
+        builder.build::<M, M0>(wtxn)
+    };
+
+    let growth = MapGrowth { max_retries: 0, ..MapGrowth::default() };
+    let err = unsafe { write_with_map_growth(&env, growth, &mut index) }.unwrap_err();
+    assert!(err.is_map_full(), "{err}");
+    assert_eq!(env.info().map_size, size);
+
+    let growth = MapGrowth { max_size: Some(512 * 1024), ..MapGrowth::default() };
+    let err = unsafe { write_with_map_growth(&env, growth, &mut index) }.unwrap_err();
+    assert!(err.is_map_full(), "{err}");
+    assert_eq!(env.info().map_size, 512 * 1024);
+
+    unsafe { write_with_map_growth(&env, MapGrowth::default(), &mut index) }.unwrap();
+    assert!(env.info().map_size > 512 * 1024);
+    assert!(calls > 3);
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.n_items(), 2000);
+    assert_eq!(reader.item_vector(&rtxn, 1999).unwrap().unwrap(), vectors[1999]);
+}
@@ -0,0 +1,55 @@
+use super::{create_database, rng, DatabaseHandle};
+use crate::distance::Euclidean;
+use crate::{Error, Projection, Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn rerank_the_prefixes_with_the_full_vectors() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+
+    let error = Writer::new(database, 0, 2).set_matryoshka(&mut wtxn, 1).unwrap_err();
+    assert!(matches!(error, Error::InvalidMatryoshka { .. }));
+    let writer = Writer::new(database, 0, 2);
+    writer.set_matryoshka(&mut wtxn, 4).unwrap();
+    let projection = Projection::random_orthogonal(4, 2, &mut rng).unwrap();
+    let error = writer.set_projection(&mut wtxn, &projection).unwrap_err();
+    assert!(matches!(error, Error::InvalidProjection { .. }));
+
+    // the prefix of item 1 is the closest to the origin, its last dimensions are far from it
+    writer.add_item(&mut wtxn, 0, &[1.0, 0.0, 0.0, 0.0]).unwrap();
+    writer
+        .add_items(&mut wtxn, [(1, &[0.5, 0.0, 5.0, 0.0][..]), (2, &[2.0, 0.0, 0.0, 0.0][..])])
+        .unwrap();
+    let error = writer.add_item(&mut wtxn, 3, &[0.0, 0.0]).unwrap_err();
+    assert!(matches!(error, Error::InvalidDimensions { expected: 4, got: 2 }));
+    let error = writer.set_matryoshka(&mut wtxn, 8).unwrap_err();
+    assert!(matches!(error, Error::InvalidMatryoshka { .. }));
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    assert_eq!(writer.matryoshka(&wtxn).unwrap(), Some(4));
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.matryoshka(), Some(4));
+    assert_eq!((reader.dimensions(), reader.query_dimensions()), (2, 4));
+    assert_eq!(reader.item_vector(&wtxn, 1).unwrap().unwrap(), [0.5, 0.0]);
+
+    let origin = [0.0; 4];
+    let found = reader.nns(2).by_vector(&wtxn, &origin).unwrap().into_nns();
+    assert_eq!(found, [(0, 1.0), (2, 4.0)]);
+    // the searches by item only compare the prefixes
+    let prefix = reader.nns(2).by_item(&wtxn, 0).unwrap().unwrap().into_nns();
+    assert_eq!(prefix, [(1, 0.25), (2, 1.0)]);
+    let error = reader.nns(1).by_vector(&wtxn, &[0.0, 0.0]).unwrap_err();
+    assert!(matches!(error, Error::InvalidDimensions { expected: 4, got: 2 }));
+
+    // the candidates of the prefixes are re-ranked even without oversampling
+    writer.del_item(&mut wtxn, 0).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let found = reader.nns(2).rerank(1).by_vector(&wtxn, &origin).unwrap().into_nns();
+    assert_eq!(found, [(2, 4.0), (1, 25.25)]);
+    assert!(crate::matryoshka::get_full_vector(&wtxn, database, 0, 0).unwrap().is_none());
+}
@@ -1,19 +1,109 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
 use std::fmt;
 use std::ops::Range;
 
-use heed::types::LazyDecode;
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::{Bytes, LazyDecode, U32, U64};
 use heed::{Env, EnvOpenOptions, WithTls};
 use rand::distributions::Uniform;
 use rand::rngs::StdRng;
 use rand::{thread_rng, Rng, SeedableRng};
 use tempfile::TempDir;
 
+use crate::change_log::ChangeCodec;
+use crate::tuning::TuningCodec;
 use crate::version::VersionCodec;
-use crate::{Database, Distance, MetadataCodec, NodeCodec, NodeMode, Reader, Writer};
+use crate::{
+    Database, Distance, MetadataCodec, NodeCodec, NodeMode, Reader, RoaringBitmapCodec, Writer,
+};
 
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "tokio")]
+mod async_writer;
+mod attributes;
+mod clustering;
+mod distance;
+mod eval;
+mod export;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fusion;
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "hdf5")]
+mod hdf5;
+#[cfg(feature = "jsonl")]
+mod jsonl;
+mod lsh;
+mod map_growth;
+mod matryoshka;
+#[cfg(feature = "metrics")]
+mod monitoring;
+mod npy;
+#[cfg(feature = "npz")]
+mod npz;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod projection;
+#[cfg(feature = "unstable-raw")]
+mod raw;
+mod read_only;
 mod reader;
+#[cfg(feature = "rayon")]
+mod scheduler;
+mod scoring;
+#[cfg(feature = "tracing-spans")]
+mod tracing;
+#[cfg(feature = "versioning")]
+mod versioning;
 mod writer;
 
+/// Counts the allocations of the threads that asked for it, see [`count_allocations`].
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+impl CountingAllocator {
+    fn count() {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get().map(|count| count + 1)));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the result of `f` along with the number of allocations it made on this thread.
+pub fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    ALLOCATIONS.with(|count| count.set(Some(0)));
+    let result = f();
+    (result, ALLOCATIONS.with(|count| count.take()).unwrap_or_default())
+}
+
 pub struct DatabaseHandle<D> {
     pub env: Env<WithTls>,
     pub database: Database<D>,
@@ -58,6 +148,10 @@ impl<D: Distance> fmt::Display for DatabaseHandle<D> {
                     let item = lazy_node.decode().unwrap();
                     writeln!(f, "Item {}: {item:?}", key.node.item)?;
                 }
+                NodeMode::FullVector => {
+                    let item = lazy_node.decode().unwrap();
+                    writeln!(f, "Full vector {}: {item:?}", key.node.item)?;
+                }
                 NodeMode::Links => {
                     let links = lazy_node.decode().unwrap();
                     writeln!(f, "Links {}: {links:?}", key.node.item)?;
@@ -80,6 +174,177 @@ impl<D: Distance> fmt::Display for DatabaseHandle<D> {
                         .unwrap();
                     writeln!(f, "Version: {version:?}")?;
                 }
+                NodeMode::Metadata if key.node.item == 2 => {
+                    let tuning = self
+                        .database
+                        .remap_data_type::<TuningCodec>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Tuning: {tuning:?}")?;
+                }
+                NodeMode::Metadata if key.node.item == 3 => {
+                    let sequence = self
+                        .database
+                        .remap_data_type::<U32<BigEndian>>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Next change: {sequence}")?;
+                }
+                NodeMode::Metadata if key.node.item == 4 => {
+                    let sequence = self
+                        .database
+                        .remap_data_type::<U32<BigEndian>>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Next applied change: {sequence}")?;
+                }
+                NodeMode::Change => {
+                    let change = self
+                        .database
+                        .remap_data_type::<ChangeCodec>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Change {}: {change:?}", key.node.item)?;
+                }
+                NodeMode::Expiration => {
+                    let at = self
+                        .database
+                        .remap_data_type::<U64<BigEndian>>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Expiration {}: {at}", key.node.item)?;
+                }
+                NodeMode::Metadata if key.node.item == 5 => {
+                    let items = self
+                        .database
+                        .remap_data_type::<RoaringBitmapCodec>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "External items: {items:?}")?;
+                }
+                NodeMode::Metadata if key.node.item == 6 => {
+                    // the parameters of the build, which change with every run, follow its id
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    writeln!(f, "Build: {}", BigEndian::read_u64(bytes))?;
+                }
+                NodeMode::Metadata if key.node.item == 7 => {
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    writeln!(f, "Codes: {} bytes", bytes.len())?;
+                }
+                NodeMode::Metadata if key.node.item == 8 => {
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    let weights: Vec<f32> = bytemuck::pod_collect_to_vec(bytes);
+                    writeln!(f, "Weights: {weights:?}")?;
+                }
+                NodeMode::Metadata if key.node.item == 9 => {
+                    let token = self
+                        .database
+                        .remap_data_type::<U64<BigEndian>>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Write lock: {token}")?;
+                }
+                NodeMode::Metadata if key.node.item == 10 => {
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    writeln!(f, "Projection: {} bytes", bytes.len())?;
+                }
+                NodeMode::Metadata if key.node.item == 11 => {
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    writeln!(f, "Sketches: {} bytes", bytes.len())?;
+                }
+                NodeMode::Metadata if key.node.item == 12 => {
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    let calibration: Vec<f32> = bytemuck::pod_collect_to_vec(bytes);
+                    writeln!(f, "Calibration: {calibration:?}")?;
+                }
+                NodeMode::Metadata if key.node.item == 13 => {
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    writeln!(f, "Densities: {} bytes", bytes.len())?;
+                }
+                NodeMode::Metadata if key.node.item == 14 => {
+                    let manifest =
+                        crate::manifest::get_manifest(&rtxn, self.database, key.index).unwrap();
+                    writeln!(f, "Ingest manifest: {manifest:?}")?;
+                }
+                NodeMode::Metadata if key.node.item == 15 => {
+                    let dimensions =
+                        crate::matryoshka::get_full_dimensions(&rtxn, self.database, key.index)
+                            .unwrap();
+                    writeln!(f, "Matryoshka: {dimensions:?}")?;
+                }
+                NodeMode::Metadata if key.node.item == 16 => {
+                    let snapshots =
+                        crate::snapshot::get_snapshots(&rtxn, self.database, key.index).unwrap();
+                    writeln!(f, "Snapshots: {snapshots:?}")?;
+                }
+                NodeMode::Metadata if key.node.item == 17 => writeln!(f, "Change log enabled")?,
+                NodeMode::Metadata if key.node.item == 18 => {
+                    let format = self
+                        .database
+                        .remap_data_type::<U32<BigEndian>>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Format: {format}")?;
+                }
+                NodeMode::ExternalId | NodeMode::InternalId => {
+                    let id = self
+                        .database
+                        .remap_data_type::<U32<BigEndian>>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "{} {}: {id}", key.node.mode.name(), key.node.item)?;
+                }
+                NodeMode::Tombstone => writeln!(f, "Tombstone {}", key.node.item)?,
+                NodeMode::Timestamp => {
+                    let at = self
+                        .database
+                        .remap_data_type::<U64<BigEndian>>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Timestamp {}: {at}", key.node.item)?;
+                }
+                NodeMode::Payload => {
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    writeln!(f, "Payload {}: {bytes:?}", key.node.item)?;
+                }
+                NodeMode::Boost => {
+                    let bits = self
+                        .database
+                        .remap_data_type::<U32<BigEndian>>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Boost {}: {}", key.node.item, f32::from_bits(bits))?;
+                }
+                NodeMode::History => {
+                    let bytes =
+                        self.database.remap_data_type::<Bytes>().get(&rtxn, &key).unwrap().unwrap();
+                    writeln!(
+                        f,
+                        "History {}/{}: {} bytes",
+                        key.node.item,
+                        key.node.layer,
+                        bytes.len()
+                    )?;
+                }
                 NodeMode::Updated | NodeMode::Metadata => {
                     unreachable!("Mode must be an Updated or Metadata")
                 }
@@ -0,0 +1,60 @@
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use metrics_util::MetricKind;
+
+use super::{create_database, rng};
+use crate::distance::Euclidean;
+use crate::monitoring::{self, record_index_state};
+use crate::{Reader, Writer};
+
+#[test]
+fn record_searches_builds_and_state() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let handle = create_database::<Euclidean>();
+
+    metrics::with_local_recorder(&recorder, || {
+        let mut wtxn = handle.env.write_txn().unwrap();
+        let writer = Writer::new(handle.database, 3, 2);
+        for i in 0..100 {
+            writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+        }
+        writer.builder(&mut rng()).build::<16, 32>(&mut wtxn).unwrap();
+
+        let reader = Reader::<Euclidean>::open(&wtxn, 3, handle.database).unwrap();
+        reader.nns(5).by_vector(&wtxn, &[20.0, 0.0]).unwrap();
+        reader.nns(5).by_item(&wtxn, 20).unwrap();
+
+        writer.del_item(&mut wtxn, 0).unwrap();
+        writer.add_item(&mut wtxn, 100, &[100.0, 0.0]).unwrap();
+        record_index_state(&wtxn, handle.database, 3).unwrap();
+    });
+
+    let metrics: Vec<_> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let (kind, key) = key.into_parts();
+            let labels: Vec<_> =
+                key.labels().map(|l| format!("{}={}", l.key(), l.value())).collect();
+            assert_eq!(labels, ["index=3", "distance=euclidean"]);
+            let value = match value {
+                DebugValue::Counter(n) => n.to_string(),
+                DebugValue::Gauge(n) => n.to_string(),
+                DebugValue::Histogram(values) => format!("{} values", values.len()),
+            };
+            (key.name().to_string(), kind, value)
+        })
+        .collect();
+
+    let find = |name: &str| metrics.iter().find(|(n, ..)| n == name).unwrap();
+    assert_eq!(find(monitoring::SEARCHES).2, "2");
+    assert_eq!(find(monitoring::SEARCH_DURATION).2, "2 values");
+    assert_eq!(find(monitoring::SEARCH_VISITED_NODES).1, MetricKind::Histogram);
+    assert_eq!(find(monitoring::BUILDS).2, "1");
+    assert_eq!(find(monitoring::BUILD_DURATION).2, "1 values");
+    assert_eq!(find(monitoring::INDEXED_ITEMS).2, "100");
+    assert_eq!(find(monitoring::REMOVED_ITEMS).2, "0");
+    assert_eq!(find(monitoring::ITEMS).2, "100");
+    assert_eq!(find(monitoring::PENDING_UPDATES).2, "2");
+}
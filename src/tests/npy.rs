@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use super::{create_database, rng};
+use crate::distance::Euclidean;
+use crate::npy::{read_f32_matrix, read_id_matrix, read_ids, NpyHeader};
+use crate::{Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+/// Writes an array the way `numpy.save` does.
+fn write_npy(path: &Path, descr: &str, shape: &[usize], data: &[u8]) {
+    std::fs::write(path, npy_bytes(descr, shape, data)).unwrap();
+}
+
+/// Returns the bytes `numpy.save` writes for an array.
+pub(super) fn npy_bytes(descr: &str, shape: &[usize], data: &[u8]) -> Vec<u8> {
+    let shape = match shape {
+        [len] => format!("({len},)"),
+        shape => {
+            format!("({})", shape.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", "))
+        }
+    };
+    let mut header = format!("{{'descr': '{descr}', 'fortran_order': False, 'shape': {shape}, }}");
+    while (header.len() + 11) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+fn parse_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ids.npy");
+    let ids: Vec<u8> = [3i64, 1, 2].iter().flat_map(|id| id.to_le_bytes()).collect();
+    write_npy(&path, "<i8", &[3], &ids);
+
+    let mut file = std::fs::File::open(&path).unwrap();
+    let header = NpyHeader::read(&mut file).unwrap();
+    assert_eq!(header, NpyHeader { descr: "<i8".to_string(), shape: vec![3] });
+    let mut file = std::fs::File::open(&path).unwrap();
+    assert_eq!(read_ids(&mut file).unwrap(), [3, 1, 2]);
+
+    let ids: Vec<u8> = [-1i32].iter().flat_map(|id| id.to_le_bytes()).collect();
+    write_npy(&path, "<i4", &[1], &ids);
+    let err = read_ids(&mut std::fs::File::open(&path).unwrap()).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid npy file: invalid item id -1");
+
+    let err = NpyHeader::read(&mut &b"not a numpy file"[..]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid npy file: missing the magic string");
+}
+
+#[test]
+fn read_matrices() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("matrix.npy");
+    let floats: Vec<u8> =
+        [0.5f32, 1.0, 1.5, 2.0, 2.5, 3.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+    write_npy(&path, "<f4", &[2, 3], &floats);
+    let (values, columns) = read_f32_matrix(&mut std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!((values, columns), (vec![0.5, 1.0, 1.5, 2.0, 2.5, 3.0], 3));
+    let err = read_id_matrix(&mut std::fs::File::open(&path).unwrap()).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid npy file: unsupported ids type <f4");
+
+    let ids: Vec<u8> = [4i32, 2, 0, 1].iter().flat_map(|id| id.to_le_bytes()).collect();
+    write_npy(&path, "<i4", &[2, 2], &ids);
+    let (ids, columns) = read_id_matrix(&mut std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!((ids, columns), (vec![4, 2, 0, 1], 2));
+
+    write_npy(&path, "<f4", &[6], &floats);
+    let err = read_f32_matrix(&mut std::fs::File::open(&path).unwrap()).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid npy file: expected a 2D matrix, got [6]");
+
+    write_npy(&path, "<f4", &[2, 0], &[]);
+    let err = read_f32_matrix(&mut std::fs::File::open(&path).unwrap()).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid npy file: expected a 2D matrix with columns, got 0");
+
+    write_npy(&path, "<i8", &[usize::MAX / 2, 3], &[]);
+    let err = read_id_matrix(&mut std::fs::File::open(&path).unwrap()).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid npy file: the 9223372036854775807x3 array is too large");
+}
+
+#[test]
+fn import_npy() {
+    let dir = tempfile::tempdir().unwrap();
+    let (rows, dimensions) = (10_000, 3);
+    let vectors: Vec<u8> =
+        (0..rows).flat_map(|row| [row as f32, 1.0, 2.0]).flat_map(|x| x.to_le_bytes()).collect();
+    let vectors_path = dir.path().join("vectors.npy");
+    write_npy(&vectors_path, "<f4", &[rows, dimensions], &vectors);
+    let ids: Vec<u8> = (0..rows as u32).rev().flat_map(|id| id.to_le_bytes()).collect();
+    let ids_path = dir.path().join("ids.npy");
+    write_npy(&ids_path, "<u4", &[rows], &ids);
+
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+
+    let writer = Writer::new(handle.database, 0, 4);
+    let err = writer.import_npy(&mut wtxn, &vectors_path, None::<&Path>).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid dimensions. Got 3 but expected 4");
+
+    let writer = Writer::new(handle.database, 0, dimensions);
+    let err = writer.import_npy(&mut wtxn, &ids_path, None::<&Path>).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid npy file: expected a float32 matrix, got <u4");
+
+    // without ids the rows are numbered
+    writer.import_npy(&mut wtxn, &vectors_path, None::<&Path>).unwrap();
+    assert_eq!(writer.item_vector(&wtxn, 10).unwrap().unwrap(), [10.0, 1.0, 2.0]);
+
+    writer.clear(&mut wtxn).unwrap();
+    let imported = writer.import_npy(&mut wtxn, &vectors_path, Some(&ids_path)).unwrap();
+    assert_eq!(imported, rows as u64);
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_items(), rows as u64);
+    assert_eq!(reader.item_vector(&wtxn, 0).unwrap().unwrap(), [9999.0, 1.0, 2.0]);
+    assert_eq!(reader.item_vector(&wtxn, 9989).unwrap().unwrap(), [10.0, 1.0, 2.0]);
+}
@@ -0,0 +1,173 @@
+use std::io::Write;
+use std::path::Path;
+
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use super::npy::npy_bytes;
+use super::{create_database, rng};
+use crate::distance::Euclidean;
+use crate::npz::NpzArchive;
+use crate::{Error, Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+/// Writes the arrays in a zip archive the way `numpy.savez` does, with zip64 local headers, or
+/// `numpy.savez_compressed` when `compressed`. The whole archive is a zip64 one when `zip64`.
+fn write_npz(path: &Path, arrays: &[(&str, Vec<u8>)], compressed: bool, zip64: bool) {
+    let mut bytes = Vec::new();
+    let mut directory = Vec::new();
+    for (name, npy) in arrays {
+        let name = format!("{name}.npy");
+        let data = match compressed {
+            true => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(npy).unwrap();
+                encoder.finish().unwrap()
+            }
+            false => npy.clone(),
+        };
+        let method: u16 = if compressed { 8 } else { 0 };
+        let offset = bytes.len() as u64;
+
+        bytes.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        bytes.extend_from_slice(&[45, 0, 0, 0]);
+        bytes.extend_from_slice(&method.to_le_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&[0xff; 8]);
+        bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&20u16.to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(&(npy.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&data);
+
+        directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        directory.extend_from_slice(&[45, 0, 45, 0, 0, 0]);
+        directory.extend_from_slice(&method.to_le_bytes());
+        directory.extend_from_slice(&[0; 8]);
+        match zip64 {
+            true => directory.extend_from_slice(&[0xff; 8]),
+            false => {
+                directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                directory.extend_from_slice(&(npy.len() as u32).to_le_bytes());
+            }
+        }
+        directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&(if zip64 { 28u16 } else { 0 }).to_le_bytes());
+        directory.extend_from_slice(&[0; 10]);
+        match zip64 {
+            true => directory.extend_from_slice(&[0xff; 4]),
+            false => directory.extend_from_slice(&(offset as u32).to_le_bytes()),
+        }
+        directory.extend_from_slice(name.as_bytes());
+        if zip64 {
+            directory.extend_from_slice(&1u16.to_le_bytes());
+            directory.extend_from_slice(&24u16.to_le_bytes());
+            directory.extend_from_slice(&(npy.len() as u64).to_le_bytes());
+            directory.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            directory.extend_from_slice(&offset.to_le_bytes());
+        }
+    }
+
+    let (entries, size, offset) = (arrays.len(), directory.len(), bytes.len());
+    bytes.extend_from_slice(&directory);
+    if zip64 {
+        let record = bytes.len() as u64;
+        bytes.extend_from_slice(&0x06064b50u32.to_le_bytes());
+        bytes.extend_from_slice(&44u64.to_le_bytes());
+        bytes.extend_from_slice(&[45, 0, 45, 0]);
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&(entries as u64).to_le_bytes());
+        bytes.extend_from_slice(&(entries as u64).to_le_bytes());
+        bytes.extend_from_slice(&(size as u64).to_le_bytes());
+        bytes.extend_from_slice(&(offset as u64).to_le_bytes());
+        bytes.extend_from_slice(&0x07064b50u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&record.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+    }
+    bytes.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    match zip64 {
+        true => bytes.extend_from_slice(&[0xff; 12]),
+        false => {
+            bytes.extend_from_slice(&(entries as u16).to_le_bytes());
+            bytes.extend_from_slice(&(entries as u16).to_le_bytes());
+            bytes.extend_from_slice(&(size as u32).to_le_bytes());
+            bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+        }
+    }
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    std::fs::write(path, bytes).unwrap();
+}
+
+fn floats(values: impl IntoIterator<Item = f32>) -> Vec<u8> {
+    values.into_iter().flat_map(f32::to_le_bytes).collect()
+}
+
+#[test]
+fn read_arrays() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("arrays.npz");
+    let matrix = npy_bytes("<f4", &[2, 3], &floats([0.5, 1.0, 1.5, 2.0, 2.5, 3.0]));
+    let ids: Vec<u8> = [4i64, 2, 0, 1].iter().flat_map(|id| id.to_le_bytes()).collect();
+    let neighbors = npy_bytes("<i8", &[2, 2], &ids);
+
+    for (compressed, zip64) in [(false, false), (true, false), (false, true), (true, true)] {
+        let arrays = [("arr_0", matrix.clone()), ("neighbors", neighbors.clone())];
+        write_npz(&path, &arrays, compressed, zip64);
+        let mut archive = NpzArchive::open(&path).unwrap();
+        assert_eq!(archive.array_names().collect::<Vec<_>>(), ["arr_0", "neighbors"]);
+        let (values, columns) = archive.read_id_matrix("neighbors").unwrap();
+        assert_eq!((values, columns), (vec![4, 2, 0, 1], 2));
+        let (values, columns) = archive.read_f32_matrix("arr_0").unwrap();
+        assert_eq!((values, columns), (vec![0.5, 1.0, 1.5, 2.0, 2.5, 3.0], 3));
+
+        // the arrays are validated like the `.npy` files
+        let err = archive.read_ids("arr_0").unwrap_err();
+        assert!(matches!(err, Error::InvalidNpy { .. }), "{err}");
+        let err = archive.read_f32_matrix("test").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid npz archive: missing array `test`");
+    }
+
+    std::fs::write(&path, b"not a zip archive").unwrap();
+    let err = NpzArchive::open(&path).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid npz archive: missing the end of central directory");
+}
+
+#[test]
+fn import_npz() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vectors.npz");
+    let (rows, dimensions) = (10_000, 3);
+    let vectors = floats((0..rows).flat_map(|row| [row as f32, 1.0, 2.0]));
+    let vectors = npy_bytes("<f4", &[rows, dimensions], &vectors);
+    let ids: Vec<u8> = (0..rows as u32).rev().flat_map(|id| id.to_le_bytes()).collect();
+    let ids = npy_bytes("<u4", &[rows], &ids);
+    write_npz(&path, &[("vectors", vectors), ("ids", ids)], true, false);
+
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    let err = writer.import_npz(&mut wtxn, &path, "vectors", None).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid dimensions. Got 3 but expected 4");
+
+    // without ids the rows are numbered
+    let writer = Writer::new(handle.database, 0, dimensions);
+    writer.import_npz(&mut wtxn, &path, "vectors", None).unwrap();
+    assert_eq!(writer.item_vector(&wtxn, 10).unwrap().unwrap(), [10.0, 1.0, 2.0]);
+
+    writer.clear(&mut wtxn).unwrap();
+    let imported = writer.import_npz(&mut wtxn, &path, "vectors", Some("ids")).unwrap();
+    assert_eq!(imported, rows as u64);
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_items(), rows as u64);
+    assert_eq!(reader.item_vector(&wtxn, 0).unwrap().unwrap(), [9999.0, 1.0, 2.0]);
+    assert_eq!(reader.item_vector(&wtxn, 9989).unwrap().unwrap(), [10.0, 1.0, 2.0]);
+}
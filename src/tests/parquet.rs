@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use arrow_array::builder::{Float32Builder, ListBuilder};
+use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt32Array};
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+
+use super::{create_database, rng};
+use crate::distance::Euclidean;
+use crate::{Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn import_parquet() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("embeddings.parquet");
+
+    // a variable size list column, the way most dataframe libraries write embeddings
+    let n = 20_000u32;
+    let mut vectors = ListBuilder::new(Float32Builder::new());
+    for i in 0..n {
+        vectors.values().append_slice(&[i as f32, 1.0, 2.0]);
+        vectors.append(true);
+    }
+    let batch = RecordBatch::try_from_iter([
+        ("id", Arc::new(UInt32Array::from_iter_values(0..n)) as ArrayRef),
+        ("title", Arc::new(StringArray::from_iter_values((0..n).map(|i| i.to_string())))),
+        ("embedding", Arc::new(vectors.finish())),
+    ])
+    .unwrap();
+    let properties = WriterProperties::builder().set_max_row_group_size(5000).build();
+    let mut file = ArrowWriter::try_new(
+        std::fs::File::create(&path).unwrap(),
+        batch.schema(),
+        Some(properties),
+    )
+    .unwrap();
+    file.write(&batch).unwrap();
+    file.close().unwrap();
+
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 3);
+
+    let err = writer.import_parquet(&mut wtxn, &path, "identifier", "embedding").unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid column `identifier`: missing column");
+
+    assert_eq!(writer.import_parquet(&mut wtxn, &path, "id", "embedding").unwrap(), n as u64);
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_items(), n as u64);
+    assert_eq!(reader.item_vector(&wtxn, 12_345).unwrap().unwrap(), [12_345.0, 1.0, 2.0]);
+}
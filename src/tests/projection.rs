@@ -0,0 +1,131 @@
+use heed::types::Bytes;
+use rand::Rng;
+
+use super::{create_database, rng, DatabaseHandle};
+use crate::distance::Euclidean;
+use crate::projection::ProjectionView;
+use crate::{Error, Key, Projection, Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+/// Returns points of 8 dimensions spread on a plane, off the origin.
+fn planar_points(rng: &mut impl Rng, count: usize) -> Vec<Vec<f32>> {
+    let u = [1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0].map(|x: f32| x / 2.0);
+    let v = [0.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0].map(|x: f32| x / 2.0);
+    (0..count)
+        .map(|_| {
+            let (a, b): (f32, f32) = (rng.gen_range(-10.0..10.0), rng.gen_range(-1.0..1.0));
+            (0..8).map(|i| 3.0 + a * u[i] + b * v[i]).collect()
+        })
+        .collect()
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+#[test]
+fn pca_keeps_the_distances_of_a_plane() {
+    let mut rng = rng();
+    let samples = planar_points(&mut rng, 200);
+    let projection = Projection::pca(&samples, 2, &mut rng).unwrap();
+    assert_eq!((projection.input_dimensions(), projection.output_dimensions()), (8, 2));
+
+    let points = planar_points(&mut rng, 20);
+    let projected: Vec<_> = points.iter().map(|p| projection.project(p).unwrap()).collect();
+    for (a, pa) in points.iter().zip(&projected) {
+        for (b, pb) in points.iter().zip(&projected) {
+            let (before, after) = (squared_distance(a, b), squared_distance(pa, pb));
+            assert!((before - after).abs() < 1e-2 * before.max(1.0), "{before} != {after}");
+        }
+    }
+
+    // a single direction keeps the one the points spread the most along
+    let line = Projection::pca(&samples, 1, &mut rng).unwrap();
+    let along = |p: &[f32]| line.project(p).unwrap()[0];
+    let (a, b) = (&points[0], &points[1]);
+    assert!((along(a) - along(b)).powi(2) > 0.9 * squared_distance(a, b) - 4.0);
+
+    assert!(matches!(projection.project(&[1.0; 3]), Err(Error::InvalidDimensions { .. })));
+    assert!(matches!(Projection::pca(&samples, 9, &mut rng), Err(Error::InvalidProjection { .. })));
+    let empty: &[Vec<f32>] = &[];
+    assert!(matches!(Projection::pca(empty, 2, &mut rng), Err(Error::InvalidProjection { .. })));
+}
+
+#[test]
+fn random_orthogonal_projection_of_all_the_dimensions_is_a_rotation() {
+    let mut rng = rng();
+    let projection = Projection::random_orthogonal(16, 16, &mut rng).unwrap();
+    for _ in 0..10 {
+        let vector: Vec<f32> = (0..16).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        let norm = squared_distance(&vector, &[0.0; 16]);
+        let projected = projection.project(&vector).unwrap();
+        assert!((squared_distance(&projected, &[0.0; 16]) - norm).abs() < 1e-4);
+    }
+
+    let error = Projection::random_orthogonal(4, 8, &mut rng).unwrap_err();
+    assert!(matches!(error, Error::InvalidProjection { .. }));
+}
+
+#[test]
+fn project_in_place_from_unaligned_bytes() {
+    let mut rng = rng();
+    let projection = Projection::random_orthogonal(16, 4, &mut rng).unwrap();
+    let bytes = projection.to_bytes();
+    // LMDB gives the values at any offset, the view reads them where they are
+    let mut shifted = vec![0u8; bytes.len() + 1];
+    shifted[1..].copy_from_slice(&bytes);
+    for bytes in [&bytes[..], &shifted[1..]] {
+        let view = ProjectionView::from_bytes(bytes).unwrap();
+        for _ in 0..10 {
+            let vector: Vec<f32> = (0..16).map(|_| rng.gen_range(-1.0..1.0)).collect();
+            assert_eq!(view.project(&vector).unwrap(), projection.project(&vector).unwrap());
+        }
+        assert!(matches!(view.project(&[1.0; 3]), Err(Error::InvalidDimensions { .. })));
+    }
+}
+
+#[test]
+fn index_projected_vectors() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let points = planar_points(&mut rng, 50);
+    let projection = Projection::pca(&points, 2, &mut rng).unwrap();
+
+    let error = Writer::new(database, 0, 3).set_projection(&mut wtxn, &projection).unwrap_err();
+    assert!(matches!(error, Error::InvalidProjection { .. }));
+    let writer = Writer::new(database, 0, 2);
+    writer.set_projection(&mut wtxn, &projection).unwrap();
+    writer.add_item(&mut wtxn, 0, &points[0]).unwrap();
+    writer
+        .add_items(&mut wtxn, points.iter().enumerate().skip(1).map(|(i, p)| (i as u32, &p[..])))
+        .unwrap();
+    let error = writer.add_item(&mut wtxn, 100, &[0.0, 0.0]).unwrap_err();
+    assert!(matches!(error, Error::InvalidDimensions { expected: 8, got: 2 }));
+    let error = writer.set_projection(&mut wtxn, &projection).unwrap_err();
+    assert!(matches!(error, Error::InvalidProjection { .. }));
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    assert_eq!(writer.projection(&wtxn).unwrap(), Some(projection.clone()));
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.projection(), Some(&projection));
+    assert_eq!((reader.dimensions(), reader.query_dimensions()), (2, 8));
+    assert_eq!(
+        reader.item_vector(&wtxn, 7).unwrap().unwrap(),
+        projection.project(&points[7]).unwrap()
+    );
+    for (i, point) in points.iter().enumerate() {
+        let found = reader.nns(1).by_vector(&wtxn, point).unwrap().into_nns();
+        assert_eq!(found[0].0, i as u32);
+    }
+    let error = reader.nns(1).by_vector(&wtxn, &[0.0, 0.0]).unwrap_err();
+    assert!(matches!(error, Error::InvalidDimensions { expected: 8, got: 2 }));
+
+    // a truncated projection is an error, not a panic
+    let key = Key::projection(0);
+    database.remap_data_type::<Bytes>().put(&mut wtxn, &key, &[1, 2, 3]).unwrap();
+    let error = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap_err();
+    insta::assert_snapshot!(error, @"Failed to get Metadata(10,0) in index `0`: error while decoding: Could not decode a projection of 3 bytes.");
+}
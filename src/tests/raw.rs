@@ -0,0 +1,50 @@
+use heed::BytesEncode;
+
+use super::{create_database, rng, DatabaseHandle};
+use crate::distance::Euclidean;
+use crate::internals::KeyCodec;
+use crate::raw::{NodeMode, RawCursor, RawKey};
+use crate::{Key, NodeId, Writer};
+
+const M: usize = 3;
+const M0: usize = 3;
+
+#[test]
+fn iterate_the_raw_entries() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    for index in 0..2 {
+        let writer = Writer::new(database, index, 2);
+        writer.add_item(&mut wtxn, 0, &[0.0, 1.0]).unwrap();
+        writer.add_item(&mut wtxn, 1, &[1.0, 0.0]).unwrap();
+        writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    }
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let key = Key::new(1, NodeId::item(1));
+    let raw = RawKey::new(1, NodeMode::Item, 1, 0);
+    assert_eq!(raw.to_bytes()[..], KeyCodec::bytes_encode(&key).unwrap()[..]);
+    assert_eq!(RawKey::from_bytes(&raw.to_bytes()), Some(raw));
+    assert_eq!(RawKey::from_bytes(&[0; 3]), None);
+    assert_eq!(RawKey { mode: u8::MAX, ..raw }.mode(), None);
+
+    let all: Vec<_> = RawCursor::all(&rtxn, database).unwrap().map(Result::unwrap).collect();
+    let first: Vec<_> = RawCursor::index(&rtxn, database, 0).unwrap().map(Result::unwrap).collect();
+    let second: Vec<_> =
+        RawCursor::index(&rtxn, database, 1).unwrap().map(Result::unwrap).collect();
+    assert_eq!(all.len(), first.len() + second.len());
+    assert!(first.iter().all(|entry| entry.key.index == 0));
+    assert!(all.windows(2).all(|entries| entries[0].key < entries[1].key));
+
+    // the values are the bytes hannoy stored under the keys
+    let item = second.iter().find(|entry| entry.key == raw).unwrap();
+    let stored = database.remap_data_type::<heed::types::Bytes>().get(&rtxn, &key).unwrap();
+    assert_eq!(Some(item.value), stored);
+
+    let from: Vec<_> =
+        RawCursor::starting_at(&rtxn, database, raw).unwrap().map(Result::unwrap).collect();
+    assert_eq!(from[0].key, raw);
+    assert_eq!(from.len(), second.iter().filter(|entry| entry.key >= raw).count());
+}
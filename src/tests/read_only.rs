@@ -0,0 +1,31 @@
+use super::{create_database, rng, DatabaseHandle};
+use crate::distance::Euclidean;
+use crate::{ReadAccess, ReadOnlyEnv, Reader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn read_an_index_from_a_read_only_env() {
+    let DatabaseHandle { env, database, tempdir } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+    drop(env);
+
+    for access in [ReadAccess::Shared, ReadAccess::Frozen] {
+        let env = unsafe { ReadOnlyEnv::open(tempdir.path(), access) }.unwrap();
+        let rtxn = env.read_txn().unwrap();
+        let database = env.open_database::<Euclidean>(&rtxn, None).unwrap().unwrap();
+        let reader = Reader::open(&rtxn, 0, database).unwrap();
+        let found = reader.nns(2).by_vector(&rtxn, &[41.8, 0.0]).unwrap().into_nns();
+        assert_eq!(found.iter().map(|&(id, _)| id).collect::<Vec<_>>(), [42, 41]);
+        assert!(env.open_database::<Euclidean>(&rtxn, Some("missing")).unwrap().is_none());
+        drop(rtxn);
+        assert_eq!(env.clear_stale_readers().unwrap(), 0);
+    }
+}
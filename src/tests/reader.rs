@@ -1,16 +1,46 @@
+use std::time::{Duration, SystemTime};
+
+use byteorder::BigEndian;
+use heed::types::U32;
 use proptest::prelude::*;
 use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
 use roaring::RoaringBitmap;
 
 use crate::{
-    distance::{BinaryQuantizedCosine, Cosine},
-    tests::{create_database, create_database_indices_with_items, rng, DatabaseHandle},
-    Reader, Writer,
+    codes::{get_coded_items, get_codes},
+    distance::{BinaryQuantizedCosine, Cosine, Euclidean, Hellinger, SparseDotProduct},
+    node::Item,
+    reader::{get_item, get_links, get_serialized_links},
+    roaring::SerializedBitmap,
+    tests::{
+        count_allocations, create_database, create_database_indices_with_items, rng, DatabaseHandle,
+    },
+    Decay, Distance, Error, FilterStrategy, HybridQuery, Key, MultiReader, MultiVector,
+    MultiVectorScore, NodeCodec, Query, Reader, RecencyQuery, ScoreKind, SearchStats, TraceEvent,
+    Writer, FORMAT_VERSION,
 };
 
 const M: usize = 16;
 const M0: usize = 32;
 
+#[test]
+fn refuse_the_indexes_of_a_more_recent_format() {
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, 4, M, M0, _>(0..1, 10, &mut rng);
+    let mut wtxn = env.write_txn().unwrap();
+    let formats = database.remap_data_type::<U32<BigEndian>>();
+    assert_eq!(formats.get(&wtxn, &Key::format(0)).unwrap(), Some(FORMAT_VERSION));
+    formats.put(&mut wtxn, &Key::format(0), &(FORMAT_VERSION + 1)).unwrap();
+
+    let error = Reader::<Cosine>::open(&wtxn, 0, database).unwrap_err();
+    assert!(matches!(error, Error::UnknownFormat { format } if format == FORMAT_VERSION + 1));
+
+    // the indexes built before the format was stored are read as they were
+    formats.delete(&mut wtxn, &Key::format(0)).unwrap();
+    Reader::<Cosine>::open(&wtxn, 0, database).unwrap();
+}
+
 // Minimal reproducer for issue #78
 // <https://github.com/nnethercott/hannoy/issues/78>
 #[test]
@@ -63,14 +93,24 @@ fn search_on_candidates_has_right_num() {
 
         let c: [u32; 10] = std::array::from_fn(|_| thread_rng().gen::<u32>() % 1000);
         let candidates = RoaringBitmap::from_iter(c);
-        let _found = reader.nns(10).candidates(&candidates).by_vector(&rtxn, &query).unwrap();
+        let _found = reader
+            .nns(10)
+            .candidates(&candidates)
+            .filter_strategy(FilterStrategy::Graph)
+            .by_vector(&rtxn, &query)
+            .unwrap();
         let found = _found.into_nns();
         assert_eq!(&RoaringBitmap::from_iter(found.into_iter().map(|(i, _)| i)), &candidates);
 
         // search with 1 candidate
         let c: [u32; 1] = std::array::from_fn(|_| thread_rng().gen::<u32>() % 1000);
         let candidates = RoaringBitmap::from_iter(c);
-        let _found = reader.nns(1).candidates(&candidates).by_vector(&rtxn, &query).unwrap();
+        let _found = reader
+            .nns(1)
+            .candidates(&candidates)
+            .filter_strategy(FilterStrategy::Graph)
+            .by_vector(&rtxn, &query)
+            .unwrap();
         let found = _found.into_nns();
         assert_eq!(&RoaringBitmap::from_iter(found.into_iter().map(|(i, _)| i)), &candidates);
     }
@@ -163,3 +203,1564 @@ fn search_cancellation_works() {
     let searched = reader.nns(10).by_item_with_cancellation(&rtxn, 0, || true).unwrap().unwrap();
     assert!(searched.did_cancel());
 }
+
+#[test]
+fn prepared_query_matches_by_vector() {
+    const DIM: usize = 768;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..3, 100, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+    let mut prepared = reader.prepare_query(&query).unwrap();
+
+    // the same prepared query can be reused across indexes and searches
+    for index in 0..3 {
+        let reader = Reader::<Cosine>::open(&rtxn, index, database).unwrap();
+        for _ in 0..2 {
+            let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+            let found = reader.nns(10).by_prepared(&rtxn, &mut prepared).unwrap().into_nns();
+            assert_eq!(expected, found);
+        }
+    }
+
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+    assert!(reader.prepare_query(&[0.0, 1.0]).is_err());
+    drop(rtxn);
+
+    // the prepared queries are transformed like the vectors of the index
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Hellinger, DIM, M, M0, _>(0..1, 100, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Hellinger>::open(&rtxn, 0, database).unwrap();
+    let mut prepared = reader.prepare_query(&query).unwrap();
+    let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+    let found = reader.nns(10).by_prepared(&rtxn, &mut prepared).unwrap().into_nns();
+    assert_eq!(expected, found);
+}
+
+#[test]
+fn batched_distances_match_single_ones() {
+    fn check<D: Distance>(rng: &mut StdRng) {
+        let mut random = |dimensions| Item::<D>::new((0..dimensions).map(|_| rng.gen()).collect());
+        for dimensions in [3, 31, 32, 100] {
+            let query = random(dimensions);
+            let items: Vec<_> = (0..7).map(|_| random(dimensions)).collect();
+            let mut batched = Vec::new();
+            D::distances(&query, &items, &mut batched);
+            let single: Vec<_> = items.iter().map(|item| D::distance(&query, item)).collect();
+            assert_eq!(batched, single, "{} over {dimensions} dimensions", D::name());
+        }
+    }
+
+    let mut rng = rng();
+    check::<Cosine>(&mut rng);
+    check::<Euclidean>(&mut rng);
+    check::<BinaryQuantizedCosine>(&mut rng);
+}
+
+#[test]
+fn prefetching_doesnt_change_the_results() {
+    const DIM: usize = 768;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 100, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+    let prefetching = Reader::<Cosine>::open(&rtxn, 0, database).unwrap().with_prefetch();
+
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+    let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+    assert_eq!(prefetching.nns(10).by_vector(&rtxn, &query).unwrap().into_nns(), expected);
+}
+
+#[test]
+fn parallel_expansion_finds_the_same_neighbours() {
+    const DIM: usize = 16;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 2000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+
+    let (mut found, mut expected) = (0, 0);
+    for _ in 0..10 {
+        let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        let sequential = reader.nns(10).ef_search(500).by_vector(&rtxn, &query).unwrap();
+        let parallel =
+            reader.nns(10).ef_search(500).parallel_expansion(8).by_vector(&rtxn, &query).unwrap();
+        assert!(parallel.nns.is_sorted_by(|(_, a), (_, b)| a <= b));
+        let sequential: RoaringBitmap = sequential.nns.iter().map(|&(id, _)| id).collect();
+        found += parallel.nns.iter().filter(|(id, _)| sequential.contains(*id)).count();
+        expected += sequential.len() as usize;
+    }
+    assert!(found * 100 >= expected * 95, "{found} of {expected} neighbours found");
+}
+
+#[test]
+fn batched_fetches_dont_change_the_results() {
+    const DIM: usize = 16;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 1000, &mut rng);
+    let mut wtxn = env.write_txn().unwrap();
+    Writer::<Euclidean>::new(database, 0, DIM).reorder_into(&mut wtxn, 1).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    for index in [0, 1] {
+        let reader = Reader::<Euclidean>::open(&rtxn, index, database).unwrap();
+        let batched = Reader::open(&rtxn, index, database).unwrap().with_batched_fetches();
+        for _ in 0..10 {
+            let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+            let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+            assert_eq!(batched.nns(10).by_vector(&rtxn, &query).unwrap().into_nns(), expected);
+        }
+    }
+}
+
+#[test]
+fn pooled_scratch_handles_nested_searches() {
+    const DIM: usize = 16;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 100, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+    let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+
+    // the buffers of the thread are in use by the outer search, the inner one gets new ones
+    let inner = std::cell::RefCell::new(Vec::new());
+    let cancel_fn = || {
+        if inner.borrow().is_empty() {
+            *inner.borrow_mut() = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+        }
+        false
+    };
+    let outer = reader.nns(10).by_vector_with_cancellation(&rtxn, &query, cancel_fn).unwrap();
+    assert_eq!(outer.into_nns(), expected);
+    assert_eq!(inner.into_inner(), expected);
+    assert_eq!(reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns(), expected);
+}
+
+#[test]
+#[cfg_attr(feature = "metrics", ignore = "the metrics allocate their labels")]
+fn warm_searches_dont_allocate() {
+    const DIM: usize = 16;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 1000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+    let candidates: RoaringBitmap = (0..1000).step_by(3).collect();
+
+    // the first searches of the thread size its buffers
+    let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+    let mut filtered = reader.nns(10);
+    filtered.candidates(&candidates).filter_strategy(FilterStrategy::Graph);
+    filtered.by_vector(&rtxn, &query).unwrap();
+
+    // the only allocation left is the vector of results handed to the caller
+    let (found, allocations) =
+        count_allocations(|| reader.nns(10).by_vector(&rtxn, &query).unwrap());
+    assert_eq!(found.into_nns(), expected);
+    assert_eq!(allocations, 1);
+    let (filtered, allocations) = count_allocations(|| filtered.by_vector(&rtxn, &query).unwrap());
+    assert!(filtered.into_nns().iter().all(|(id, _)| candidates.contains(*id)));
+    assert_eq!(allocations, 1);
+}
+
+#[test]
+fn layer_cache_is_ignored_once_rebuilt() {
+    const DIM: usize = 16;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 1000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let cached =
+        Reader::<Euclidean>::open(&rtxn, 0, database).unwrap().with_layer_cache(&rtxn, 1).unwrap();
+    assert!(cached.fresh_layer_cache(&rtxn).unwrap().is_some_and(|cache| !cache.items.is_empty()));
+
+    for _ in 0..10 {
+        let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+        assert_eq!(cached.nns(10).by_vector(&rtxn, &query).unwrap().into_nns(), expected);
+    }
+    drop(rtxn);
+
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, DIM);
+    writer.add_item(&mut wtxn, 1000, &[0.5; DIM]).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    assert!(cached.fresh_layer_cache(&rtxn).unwrap().is_none());
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap().with_layer_cache(&rtxn, 1);
+    assert!(reader.unwrap().fresh_layer_cache(&rtxn).unwrap().is_some());
+}
+
+#[test]
+fn large_vectors_are_aligned_in_the_memory_map() {
+    // the vectors of 1024 dimensions don't fit in a leaf page and go in the overflow pages
+    const DIM: usize = 1024;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 20, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    for item in reader.item_ids() {
+        let vector = reader.item_vector_ref(&rtxn, item).unwrap().unwrap();
+        assert!(matches!(vector, std::borrow::Cow::Borrowed(_)));
+        assert_eq!(
+            vector.as_aligned().unwrap(),
+            &reader.item_vector(&rtxn, item).unwrap().unwrap()[..]
+        );
+    }
+}
+
+#[test]
+fn quantized_codes_match_the_stored_items() {
+    const DIM: usize = 100;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<BinaryQuantizedCosine, DIM, M, M0, _>(
+            0..1,
+            200,
+            &mut rng,
+        );
+    let assert_codes_match = |rtxn: &heed::RoTxn, expected: RoaringBitmap| {
+        let items = get_coded_items(rtxn, database, 0).unwrap().unwrap();
+        assert_eq!(items, expected);
+        let codes = get_codes(rtxn, database, 0, &items).unwrap().unwrap();
+        for item in &items {
+            let stored = get_item(database, 0, rtxn, item).unwrap().unwrap();
+            let code = codes.get::<BinaryQuantizedCosine>(item).unwrap();
+            assert_eq!(bytemuck::bytes_of(&code.header), bytemuck::bytes_of(&stored.header));
+            assert_eq!(code.vector.as_bytes(), stored.vector.as_bytes());
+        }
+        assert!(codes.get::<BinaryQuantizedCosine>(items.max().unwrap() + 1).is_none());
+    };
+    let rtxn = env.read_txn().unwrap();
+    assert_codes_match(&rtxn, (0..200).collect());
+    drop(rtxn);
+
+    // a build only reads the codes of the changed items, the other ones keep their position
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, DIM);
+    writer.del_item(&mut wtxn, 10).unwrap();
+    writer.add_item(&mut wtxn, 20, &[-0.5; DIM]).unwrap();
+    writer.add_item(&mut wtxn, 100_000, &[0.5; DIM]).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+    let rtxn = env.read_txn().unwrap();
+    let mut expected: RoaringBitmap = (0..200).collect();
+    expected.remove(10);
+    expected.insert(100_000);
+    assert_codes_match(&rtxn, expected);
+
+    let reader = Reader::<BinaryQuantizedCosine>::open(&rtxn, 0, database).unwrap();
+    let candidates = RoaringBitmap::from_iter([3, 17, 250]);
+    let found = reader.nns(5).candidates(&candidates).by_vector(&rtxn, &[0.5; DIM]).unwrap();
+    assert_eq!(found.into_nns().into_iter().map(|(id, _)| id).collect::<RoaringBitmap>(), {
+        RoaringBitmap::from_iter([3, 17])
+    });
+    drop(rtxn);
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 200, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    assert!(get_coded_items(&rtxn, database, 0).unwrap().is_none());
+}
+
+#[test]
+fn serialized_bitmap_iterates_like_the_decoded_one() {
+    let mut rng = rng();
+    let sparse: RoaringBitmap = (0..200).map(|_| rng.gen_range(0..1_000_000)).collect();
+    let dense: RoaringBitmap = (70_000..80_000).chain([3, u32::MAX]).collect();
+    for bitmap in [RoaringBitmap::new(), sparse, dense] {
+        let mut bytes = Vec::new();
+        bitmap.serialize_into(&mut bytes).unwrap();
+        let serialized = SerializedBitmap::new(&bytes).unwrap();
+        assert!(matches!(serialized, SerializedBitmap::Raw { .. }));
+        assert_eq!(serialized.iter().collect::<Vec<_>>(), bitmap.iter().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn array_links_find_the_same_neighbours() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut rng = rng();
+    let vectors: Vec<[f32; 8]> =
+        (0..500).map(|_| std::array::from_fn(|_| rng.gen_range(-1.0..1.0))).collect();
+    for (index, array_links) in [(0, false), (1, true)] {
+        let writer = Writer::new(database, index, 8);
+        for (item, vector) in vectors.iter().enumerate() {
+            writer.add_item(&mut wtxn, item as u32, vector).unwrap();
+        }
+        writer
+            .builder(&mut StdRng::seed_from_u64(42))
+            .array_links(array_links)
+            .build::<M, M0>(&mut wtxn)
+            .unwrap();
+    }
+
+    let links = get_serialized_links(&wtxn, database, 1, 0, 0).unwrap().unwrap();
+    assert!(matches!(links, SerializedBitmap::Array(_)));
+    let bitmap = get_links(&wtxn, database, 1, 0, 0).unwrap().unwrap();
+    assert_eq!(links.iter().collect::<Vec<_>>(), bitmap.iter().collect::<Vec<_>>());
+    // the ids are little-endian, read in place when aligned and one by one otherwise
+    let ids: Vec<u8> = bitmap.iter().flat_map(u32::to_le_bytes).collect();
+    let unaligned = [&[0][..], &ids].concat();
+    for bytes in [&ids[..], &unaligned[1..]] {
+        let links = SerializedBitmap::Array(bytes);
+        assert_eq!(links.iter().collect::<Vec<_>>(), bitmap.iter().collect::<Vec<_>>());
+    }
+
+    let roaring = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let array = Reader::<Euclidean>::open(&wtxn, 1, database).unwrap();
+    for query in &vectors[..20] {
+        let expected = roaring.nns(10).by_vector(&wtxn, query).unwrap().into_nns();
+        assert_eq!(array.nns(10).by_vector(&wtxn, query).unwrap().into_nns(), expected);
+    }
+
+    // an incremental build reads the arrays of the previous one
+    let writer = Writer::new(database, 1, 8);
+    writer.add_item(&mut wtxn, 500, &[0.0; 8]).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 1, database).unwrap();
+    let found = reader.nns(1).by_vector(&wtxn, &[0.0; 8]).unwrap().into_nns();
+    assert_eq!(found[0].0, 500);
+}
+
+#[test]
+fn outlier_score_flags_the_queries_far_from_the_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut rng = rng();
+    let writer = Writer::new(database, 0, 8);
+    for item in 0..500 {
+        let vector: [f32; 8] = std::array::from_fn(|_| rng.gen_range(-1.0..1.0));
+        writer.add_item(&mut wtxn, item, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let nns = reader.nns(10).by_vector(&wtxn, &[0.0; 8]).unwrap().into_nns();
+    assert_eq!(reader.outlier_score(&wtxn, &nns).unwrap(), None);
+
+    writer.builder(&mut rng).record_densities(true).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.outlier_score(&wtxn, &[]).unwrap(), None);
+    for _ in 0..10 {
+        let query: [f32; 8] = std::array::from_fn(|_| rng.gen_range(-1.0..1.0));
+        let nns = reader.nns(10).by_vector(&wtxn, &query).unwrap().into_nns();
+        let score = reader.outlier_score(&wtxn, &nns).unwrap().unwrap();
+        assert!(score < 1.5, "{score}");
+    }
+    let nns = reader.nns(10).by_vector(&wtxn, &[10.0; 8]).unwrap().into_nns();
+    let score = reader.outlier_score(&wtxn, &nns).unwrap().unwrap();
+    assert!(score > 10.0, "{score}");
+
+    // the densities follow the items of a reordered index
+    writer.reorder_into(&mut wtxn, 1).unwrap();
+    let reordered = Reader::<Euclidean>::open(&wtxn, 1, database).unwrap();
+    let nns = reordered.nns(10).by_vector(&wtxn, &[10.0; 8]).unwrap().into_nns();
+    let reordered_score = reordered.outlier_score(&wtxn, &nns).unwrap().unwrap();
+    assert!((reordered_score - score).abs() < 1e-3, "{reordered_score} {score}");
+
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.outlier_score(&wtxn, &nns).unwrap(), None);
+}
+
+#[test]
+fn reverse_nns_find_the_items_the_query_is_a_neighbour_of() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut rng = rng();
+    let writer = Writer::new(database, 0, 4);
+    let vectors: Vec<[f32; 4]> =
+        (0..300).map(|_| std::array::from_fn(|_| rng.gen_range(-1.0..1.0))).collect();
+    for (item, vector) in vectors.iter().enumerate() {
+        writer.add_item(&mut wtxn, item as u32, vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+
+    // the items whose exact 5 nearest neighbours include the query
+    let query = [0.1, -0.2, 0.3, 0.0];
+    let k = 5;
+    let distance =
+        |a: &[f32], b: &[f32]| -> f32 { a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum() };
+    let expected: Vec<u32> = (0..vectors.len())
+        .filter(|&i| {
+            let mut others: Vec<f32> = (0..vectors.len())
+                .filter(|&j| j != i)
+                .map(|j| distance(&vectors[i], &vectors[j]))
+                .collect();
+            others.sort_by(f32::total_cmp);
+            distance(&vectors[i], &query) <= others[k - 1]
+        })
+        .map(|i| i as u32)
+        .collect();
+    assert!(!expected.is_empty());
+
+    let found = reader.reverse_nns(&wtxn, &query, k).unwrap();
+    assert!(found.windows(2).all(|w| w[0].1 <= w[1].1));
+    let found: Vec<u32> = found.iter().map(|&(item, _)| item).collect();
+    assert!(expected.iter().all(|item| found.contains(item)), "{expected:?} {found:?}");
+
+    let found = reader.reverse_nns_by_item(&wtxn, 42, k).unwrap().unwrap();
+    assert!(!found.is_empty());
+    assert!(found.iter().all(|&(item, _)| item != 42));
+    for (item, _) in found {
+        let nns = reader.nns(M0).by_item(&wtxn, item).unwrap().unwrap().into_nns();
+        assert!(nns.iter().any(|&(neighbour, _)| neighbour == 42));
+    }
+    assert_eq!(reader.reverse_nns_by_item(&wtxn, 1000, k).unwrap(), None);
+    assert!(reader.reverse_nns(&wtxn, &query, 0).unwrap().is_empty());
+}
+
+#[test]
+fn kth_distances_are_the_core_distances_of_the_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut rng = rng();
+    let writer = Writer::new(database, 0, 4);
+    let vectors: Vec<[f32; 4]> =
+        (0..200).map(|_| std::array::from_fn(|_| rng.gen_range(-1.0..1.0))).collect();
+    for (item, vector) in vectors.iter().enumerate() {
+        writer.add_item(&mut wtxn, item as u32, vector).unwrap();
+    }
+    writer.del_item(&mut wtxn, 7).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+
+    let k = 5;
+    let distances = reader.kth_distances(&wtxn, k).unwrap();
+    assert_eq!(distances.len(), 199);
+    let mut exact_matches = 0;
+    for &(item, distance) in &distances {
+        let mut exact: Vec<f32> = (0..vectors.len() as u32)
+            .filter(|&other| other != item && other != 7)
+            .map(|other| reader.distance(&wtxn, item, other).unwrap().unwrap())
+            .collect();
+        exact.sort_by(f32::total_cmp);
+        assert!(distance >= exact[k - 1]);
+        exact_matches += (distance == exact[k - 1]) as usize;
+        assert_eq!(reader.kth_distance(&wtxn, item, k).unwrap(), Some(distance));
+    }
+    assert!(exact_matches >= 195, "{exact_matches}");
+
+    assert_eq!(reader.kth_distance(&wtxn, 7, k).unwrap(), None);
+    assert_eq!(reader.kth_distance(&wtxn, 1000, k).unwrap(), None);
+    assert_eq!(reader.kth_distance(&wtxn, 0, 0).unwrap(), None);
+    assert_eq!(reader.kth_distance(&wtxn, 0, 200).unwrap(), None);
+}
+
+#[test]
+fn traced_search_records_the_traversal() {
+    const DIM: usize = 16;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 1000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+    let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+    let (searched, trace) = reader.nns(10).by_vector_traced(&rtxn, &query).unwrap();
+    assert_eq!(searched.into_nns(), expected);
+
+    // layers are traversed from the top one down to the bottom one, which may be re-entered
+    let layers: Vec<_> = trace
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::EnterLayer { layer, .. } => Some(*layer),
+            _ => None,
+        })
+        .collect();
+    assert!(layers.len() > 1, "{layers:?}");
+    assert!(layers.windows(2).all(|w| w[0] == w[1] + 1 || w == [0, 0]), "{layers:?}");
+    assert_eq!(layers.last(), Some(&0));
+
+    // every result was visited at the same distance, and only queued items are expanded
+    for (id, distance) in &expected {
+        assert!(trace.visited().any(|(item, d)| item == *id && d == *distance));
+    }
+    for event in &trace.events {
+        if let TraceEvent::Expand { item, distance } = event {
+            let visit = TraceEvent::Visit { item: *item, distance: *distance, queued: true };
+            assert!(trace.events.contains(&visit));
+        }
+    }
+}
+
+#[test]
+fn search_stats_accumulate_over_readers() {
+    const DIM: usize = 16;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 100, &mut rng);
+    let stats = std::sync::Arc::new(SearchStats::new());
+
+    for _ in 0..2 {
+        let rtxn = env.read_txn().unwrap();
+        let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+        let reader = reader.with_search_stats(stats.clone());
+        let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        reader.nns(10).ef_search(40).by_vector(&rtxn, &query).unwrap();
+        reader.nns(20).by_item(&rtxn, 0).unwrap();
+        let searched = reader.nns(10).by_vector_with_cancellation(&rtxn, &query, || true).unwrap();
+        assert!(searched.did_cancel());
+        // searching a missing item isn't recorded
+        assert!(reader.nns(10).by_item(&rtxn, 1000).unwrap().is_none());
+    }
+
+    let window = stats.take();
+    assert_eq!(window.searches, 6);
+    assert_eq!(window.cancelled, 2);
+    assert_eq!(window.mean_ef, 80.0);
+    assert!(window.latency_quantile(0.5) <= window.latency_quantile(1.0));
+    assert!(window.latency_quantile(1.0) >= window.mean_latency);
+
+    // taking the statistics starts a new window
+    let window = stats.snapshot();
+    assert_eq!((window.searches, window.cancelled), (0, 0));
+    assert_eq!(window.latency_quantile(0.99), std::time::Duration::ZERO);
+}
+
+#[test]
+fn search_with_wrong_dimensions_fails() {
+    const DIM: usize = 16;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 10, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+
+    for query in [vec![0.5; DIM - 1], vec![0.5; DIM + 1]] {
+        let err = reader.nns(5).by_vector(&rtxn, &query).unwrap_err();
+        assert!(matches!(err, Error::InvalidDimensions { expected: DIM, .. }));
+        let err = reader.nns(5).by_vector_with_cancellation(&rtxn, &query, || false).unwrap_err();
+        assert!(matches!(err, Error::InvalidDimensions { expected: DIM, .. }));
+    }
+}
+
+#[test]
+fn item_vector_ref_borrows_the_stored_vector() {
+    const DIM: usize = 10;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<BinaryQuantizedCosine, DIM, M, M0, _>(
+            0..1,
+            10,
+            &mut rng,
+        );
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<BinaryQuantizedCosine>::open(&rtxn, 0, database).unwrap();
+
+    for item in reader.item_ids() {
+        let vector = reader.item_vector_ref(&rtxn, item).unwrap().unwrap();
+        assert!(matches!(vector, std::borrow::Cow::Borrowed(_)));
+        let owned = reader.item_vector(&rtxn, item).unwrap().unwrap();
+        assert_eq!(vector.iter().take(DIM).collect::<Vec<_>>(), owned);
+    }
+    assert!(reader.item_vector_ref(&rtxn, 100).unwrap().is_none());
+}
+
+#[test]
+fn iter_range_only_yields_requested_ids() {
+    const DIM: usize = 4;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..2, 20, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+
+    let ids = |iter: crate::item_iter::ItemIter<'_, Cosine>| -> Vec<u32> {
+        iter.map(|res| res.unwrap().0).collect()
+    };
+    assert_eq!(ids(reader.iter_range(&rtxn, 5..8).unwrap()), [5, 6, 7]);
+    assert_eq!(ids(reader.iter_range(&rtxn, 17..).unwrap()), [17, 18, 19]);
+    assert_eq!(ids(reader.iter_range(&rtxn, ..=1).unwrap()), [0, 1]);
+    assert_eq!(ids(reader.iter_range(&rtxn, 100..).unwrap()), Vec::<u32>::new());
+    // the whole range of an index never leaks into the following one
+    assert_eq!(ids(reader.iter_range(&rtxn, ..).unwrap()).len(), 20);
+
+    let (id, vector) = reader.iter_range(&rtxn, 3..4).unwrap().next().unwrap().unwrap();
+    assert_eq!(id, 3);
+    assert_eq!(vector, reader.item_vector(&rtxn, 3).unwrap().unwrap());
+}
+
+#[test]
+fn is_built_reports_pending_updates() {
+    let handle = create_database::<Cosine>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+
+    // never built
+    assert!(!Reader::is_built(&wtxn, 0, handle.database).unwrap());
+    writer.add_item(&mut wtxn, 0, &[0.0, 1.0]).unwrap();
+    assert!(!Reader::is_built(&wtxn, 0, handle.database).unwrap());
+
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    assert!(Reader::is_built(&wtxn, 0, handle.database).unwrap());
+    assert!(!writer.need_build(&wtxn).unwrap());
+    // other indexes are not impacted
+    assert!(!Reader::is_built(&wtxn, 1, handle.database).unwrap());
+
+    writer.del_item(&mut wtxn, 0).unwrap();
+    assert!(!Reader::is_built(&wtxn, 0, handle.database).unwrap());
+    assert!(writer.need_build(&wtxn).unwrap());
+}
+
+#[test]
+fn estimated_memory_is_close_to_the_database_size() {
+    const DIM: usize = 64;
+    let mut rng = rng();
+
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 2000, &mut rng);
+    let used = env.non_free_pages_size().unwrap();
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+
+    let serving = reader.estimated_ram_for_serving(M);
+    assert!(used / 2 < serving && serving < used * 2, "{serving} for {used} bytes");
+
+    let writer = Writer::<Cosine>::new(database, 0, DIM);
+    assert!(writer.estimated_build_memory(&rtxn, M).unwrap() > serving);
+    assert!(reader.estimated_ram_for_serving(2 * M) > serving);
+}
+
+#[test]
+fn search_sparse_vectors() {
+    const DIM: usize = 30_000;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<SparseDotProduct>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, DIM);
+
+    let mut vectors = Vec::new();
+    for id in 0..200 {
+        let mut indices: Vec<u32> = (0..50).map(|_| rng.gen_range(0..DIM as u32)).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        let values: Vec<f32> = indices.iter().map(|_| rng.gen_range(0.1..2.0)).collect();
+        writer.add_sparse_item(&mut wtxn, id, &indices, &values).unwrap();
+        vectors.push((indices, values));
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<SparseDotProduct>::open(&rtxn, 0, database).unwrap();
+    let (indices, values) = &vectors[7];
+    let stored = reader.item_vector(&rtxn, 7).unwrap().unwrap();
+    assert_eq!(stored.len(), DIM);
+    assert!(indices.iter().zip(values).all(|(i, v)| stored[*i as usize] == *v));
+
+    // the query is given densely and the best dot product comes first
+    let nns = reader.nns(10).ef_search(200).by_vector(&rtxn, &stored).unwrap().into_nns();
+    let dot = |(indices, values): &(Vec<u32>, Vec<f32>)| -> f32 {
+        indices.iter().zip(values).map(|(i, v)| v * stored[*i as usize]).sum()
+    };
+    let best = (0..200).max_by(|a, b| dot(&vectors[*a]).total_cmp(&dot(&vectors[*b]))).unwrap();
+    assert_eq!(nns[0].0, best as u32);
+    assert!(nns.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+}
+
+#[test]
+fn hybrid_search_reranks_dense_candidates() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 100, &mut rng);
+
+    // the sparse vectors are stored in another index of the same database
+    let sparse = database.remap_data_type::<NodeCodec<SparseDotProduct>>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(sparse, 1, 100);
+    for id in 0..99 {
+        writer.add_sparse_item(&mut wtxn, id, &[id], &[1.0]).unwrap();
+    }
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+    let dense = reader.item_vector(&rtxn, 3).unwrap().unwrap();
+
+    let mut query = HybridQuery::new(&reader, sparse, 1, 5);
+    query.rerank(100).ef_search(100).sparse_weight(0.0);
+    let nns = query.by_vectors(&rtxn, &dense, &[50], &[5.0]).unwrap().into_nns();
+    assert_eq!(nns.len(), 5);
+    assert_eq!(nns[0].0, 3);
+
+    // a heavy sparse weight brings the item matching the sparse query first
+    let nns = query.sparse_weight(10.0).by_vectors(&rtxn, &dense, &[50], &[5.0]).unwrap();
+    let nns = nns.into_nns();
+    assert_eq!(nns[0].0, 50);
+    assert!(nns.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+
+    let err = query.by_vectors(&rtxn, &dense, &[50, 12], &[5.0, 1.0]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid sparse query vector: index 12 is not strictly greater than 50");
+}
+
+#[test]
+fn recency_query_favors_fresh_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    let now = SystemTime::now();
+    let day = Duration::from_secs(24 * 3600);
+    for id in 0..10 {
+        writer.add_item(&mut wtxn, id, &[id as f32 * 0.1, 0.0]).unwrap();
+        if id != 5 {
+            let age = if id == 9 { Duration::ZERO } else { 10 * day };
+            writer.set_timestamp(&mut wtxn, id, now - age).unwrap();
+        }
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    assert_eq!(writer.timestamp(&wtxn, 5).unwrap(), None);
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let mut query = RecencyQuery::new(&reader, 3, Decay::Exponential { half_life: day });
+    query.now(now).rerank(10).ef_search(10);
+    let nns = query.weight(0.0).by_vector(&wtxn, &[0.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns.iter().map(|&(id, _)| id).collect::<Vec<_>>(), [0, 1, 2]);
+
+    let nns = query.weight(1.0).by_vector(&wtxn, &[0.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns.iter().map(|&(id, _)| id).collect::<Vec<_>>(), [9, 0, 1]);
+
+    let linear = Decay::Linear { horizon: 2 * day };
+    assert_eq!(linear.freshness(day), 0.5);
+    assert_eq!(linear.freshness(3 * day), 0.0);
+    assert_eq!(Decay::Exponential { half_life: day }.freshness(2 * day), 0.25);
+}
+
+#[test]
+fn distances_between_stored_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    writer.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
+    writer.add_item(&mut wtxn, 1, &[3.0, 4.0]).unwrap();
+    writer.add_item(&mut wtxn, 2, &[1.0, 1.0]).unwrap();
+    writer.add_item(&mut wtxn, 3, &[2.0, 2.0]).unwrap();
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    writer.del_item(&mut wtxn, 3).unwrap();
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    // the distances are the ones of the searches, the euclidean ones aren't square-rooted
+    assert_eq!(reader.distance(&rtxn, 0, 1).unwrap(), Some(25.0));
+    assert_eq!(reader.distance(&rtxn, 1, 0).unwrap(), Some(25.0));
+    assert_eq!(reader.distance(&rtxn, 0, 3).unwrap(), None);
+    assert_eq!(reader.distance(&rtxn, 42, 0).unwrap(), None);
+
+    let distances = reader.distances(&rtxn, 0, &[1, 42, 2, 3, 0]).unwrap();
+    assert_eq!(distances, vec![Some(25.0), None, Some(2.0), None, Some(0.0)]);
+    assert_eq!(reader.distances(&rtxn, 42, &[0, 1]).unwrap(), vec![None, None]);
+}
+
+#[test]
+fn count_within_radius() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    // the euclidean distances aren't square-rooted
+    assert_eq!(reader.count_within(&rtxn, &[0.0, 0.0], 4.0).unwrap(), 3);
+    assert_eq!(reader.count_within(&rtxn, &[4.5, 0.0], 0.5).unwrap(), 2);
+    assert_eq!(reader.count_within(&rtxn, &[0.0, 100.0], 1.0).unwrap(), 0);
+    assert!(matches!(
+        reader.count_within(&rtxn, &[0.0], 1.0),
+        Err(Error::InvalidDimensions { expected: 2, got: 1 })
+    ));
+
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, 2, M, M0, _>(0..1, 3000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let exact = |radius: f32| {
+        let vectors = reader.iter(&rtxn).unwrap().map(|res| res.unwrap().1);
+        vectors.filter(|v| v[0] * v[0] + v[1] * v[1] <= radius).count() as u64
+    };
+
+    // a small radius is counted from the closest neighbours
+    assert_eq!(reader.count_within(&rtxn, &[0.0, 0.0], 0.001).unwrap(), exact(0.001));
+    // a large one is estimated from a sample
+    let estimate = reader.count_within(&rtxn, &[0.0, 0.0], 0.25).unwrap() as f64;
+    let exact_count = exact(0.25) as f64;
+    assert!((estimate - exact_count).abs() < 0.25 * exact_count, "{estimate} for {exact_count}");
+    assert_eq!(reader.count_within(&rtxn, &[0.0, 0.0], 10.0).unwrap(), 3000);
+}
+
+#[test]
+fn search_from_the_centroid_of_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let ids = |searched: Option<crate::Searched>| -> Vec<_> {
+        searched.unwrap().into_nns().into_iter().map(|(id, _)| id).collect()
+    };
+
+    // the items the query is built from aren't returned
+    let nns = reader.nns(3).by_items(&rtxn, &[1, 2, 6, 42]).unwrap();
+    assert_eq!(ids(nns), vec![3, 4, 5]);
+    let nns = reader.nns(2).by_weighted_items(&rtxn, &[(0, 3.0), (9, 1.0)]).unwrap();
+    assert_eq!(ids(nns), vec![2, 3]);
+
+    assert!(reader.nns(3).by_items(&rtxn, &[42]).unwrap().is_none());
+    assert!(reader.nns(3).by_weighted_items(&rtxn, &[(1, 0.0)]).unwrap().is_none());
+}
+
+#[test]
+fn centroid_and_medoid_of_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+
+    // the items that don't exist are ignored
+    let items = RoaringBitmap::from_iter([1, 2, 6, 42]);
+    assert_eq!(reader.centroid(&rtxn, &items).unwrap(), Some(vec![3.0, 0.0]));
+    // the medoid is one of the items, even though item 3 is closer to the centroid
+    assert_eq!(reader.medoid(&rtxn, &items).unwrap(), Some((2, 1.0)));
+
+    let missing = RoaringBitmap::from_iter([42]);
+    assert_eq!(reader.centroid(&rtxn, &missing).unwrap(), None);
+    assert_eq!(reader.medoid(&rtxn, &missing).unwrap(), None);
+}
+
+#[test]
+fn distance_matrix_of_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let matrix = reader.distance_matrix(&wtxn, &[1, 42, 3, 6]).unwrap();
+    assert_eq!(
+        matrix,
+        vec![
+            vec![Some(0.0), None, Some(4.0), Some(25.0)],
+            vec![None; 4],
+            vec![Some(4.0), None, Some(0.0), Some(9.0)],
+            vec![Some(25.0), None, Some(9.0), Some(0.0)],
+        ]
+    );
+    for (i, row) in matrix.iter().enumerate() {
+        let others = [1, 42, 3, 6];
+        let item = others[i];
+        assert_eq!(row, &reader.distances(&wtxn, item, &others).unwrap());
+    }
+    assert!(reader.distance_matrix(&wtxn, &[]).unwrap().is_empty());
+}
+
+#[test]
+fn sample_random_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i * 1000, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let sample = reader.sample(10, 42);
+    assert_eq!(sample.len(), 10);
+    assert!(sample.windows(2).all(|w| w[0] < w[1]));
+    assert!(sample.iter().all(|&item| reader.item_ids().contains(item)));
+    assert_eq!(reader.sample(10, 42), sample);
+    assert_ne!(reader.sample(10, 43), sample);
+    assert_eq!(reader.sample(1000, 42), reader.item_ids().iter().collect::<Vec<_>>());
+
+    // every item is drawn as often
+    let mut draws = vec![0; 100];
+    for seed in 0..1000 {
+        reader.sample(10, seed).into_iter().for_each(|item| draws[item as usize / 1000] += 1);
+    }
+    assert!(draws.iter().all(|&count| (50..150).contains(&count)), "{draws:?}");
+}
+
+#[test]
+fn statistics_of_the_vectors() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    writer.add_item(&mut wtxn, 0, &[1.0, 0.0]).unwrap();
+    writer.add_item(&mut wtxn, 1, &[0.0, -1.0]).unwrap();
+    writer.add_item(&mut wtxn, 2, &[3.0, 4.0]).unwrap();
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let stats = reader.vector_stats(&wtxn).unwrap();
+    assert_eq!((stats.items, stats.unit_norms), (3, 2));
+    let approx_eq = |s: crate::Summary, (min, max, mean, variance): (f64, f64, f64, f64)| {
+        assert_eq!((s.min, s.max), (min, max), "{s:?}");
+        assert!((s.mean - mean).abs() < 1e-9 && (s.variance - variance).abs() < 1e-9, "{s:?}");
+    };
+    approx_eq(stats.dimensions[0], (0.0, 3.0, 4.0 / 3.0, 14.0 / 9.0));
+    approx_eq(stats.dimensions[1], (-1.0, 4.0, 1.0, 14.0 / 3.0));
+    approx_eq(stats.norms, (1.0, 5.0, 7.0 / 3.0, 32.0 / 9.0));
+
+    // the deleted items are left out
+    writer.del_item(&mut wtxn, 2).unwrap();
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let stats = reader.vector_stats(&wtxn).unwrap();
+    assert_eq!((stats.items, stats.unit_norms), (2, 2));
+    assert_eq!(stats.norms.variance, 0.0);
+}
+
+#[test]
+fn rescore_oversampled_candidates_with_payloads() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.set_payload(&mut wtxn, 3, b"promoted").unwrap();
+    writer.set_payload(&mut wtxn, 9, b"promoted").unwrap();
+    writer.set_payload(&mut wtxn, 8, b"promoted").unwrap();
+    assert!(writer.del_item(&mut wtxn, 8).unwrap());
+    assert_eq!(writer.payload(&wtxn, 8).unwrap(), None);
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.payload(&rtxn, 3).unwrap(), Some(&b"promoted"[..]));
+    assert_eq!(reader.payload(&rtxn, 4).unwrap(), None);
+
+    let promoted = |_item, distance: f32, payload: Option<&[u8]>| match payload {
+        Some(b"promoted") => distance - 1000.0,
+        _ => distance,
+    };
+    let nns = reader.nns(2).rescore(2, &promoted).by_vector(&rtxn, &[0.0, 0.0]).unwrap();
+    // 9 is too far to be part of the oversampled candidates
+    assert_eq!(nns.into_nns(), vec![(3, 9.0 - 1000.0), (0, 0.0)]);
+    let nns = reader.nns(2).rescore(2, &promoted).by_item(&rtxn, 0).unwrap().unwrap();
+    assert_eq!(nns.into_nns(), vec![(3, 9.0 - 1000.0), (1, 1.0)]);
+    let nns = reader.nns(2).by_vector(&rtxn, &[0.0, 0.0]).unwrap();
+    assert_eq!(nns.into_nns(), vec![(0, 0.0), (1, 1.0)]);
+}
+
+#[test]
+fn boost_the_final_scores_of_the_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.set_boost(&mut wtxn, 2, 8.0).unwrap();
+    writer.set_boost(&mut wtxn, 7, 100.0).unwrap();
+    writer.set_boost(&mut wtxn, 8, 2.0).unwrap();
+    assert!(writer.del_item(&mut wtxn, 8).unwrap());
+    assert_eq!(writer.boost(&wtxn, 8).unwrap(), None);
+    let err = writer.set_boost(&mut wtxn, 1, 0.0).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid boost 0 for item 1, it must be positive and finite");
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.boost(&wtxn, 2).unwrap(), Some(8.0));
+    assert_eq!(reader.boost(&wtxn, 3).unwrap(), None);
+    let nns = reader.nns(2).boosted(2).by_vector(&wtxn, &[0.0, 0.0]).unwrap();
+    // 7 is too far to be part of the oversampled candidates
+    assert_eq!(nns.into_nns(), vec![(0, 0.0), (2, 4.0 / 8.0)]);
+    let nns = reader.nns(2).boosted(2).by_item(&wtxn, 1).unwrap().unwrap();
+    assert_eq!(nns.into_nns(), vec![(2, 1.0 / 8.0), (0, 1.0)]);
+
+    // the boosts apply to the scores of the rescoring
+    let doubled = |_item, distance: f32, _payload: Option<&[u8]>| distance * 2.0;
+    let nns = reader.nns(1).rescore(4, &doubled).boosted(1).by_vector(&wtxn, &[1.0, 0.0]);
+    assert_eq!(nns.unwrap().into_nns(), vec![(1, 0.0)]);
+    let nns = reader.nns(2).boosted(10).by_vector(&wtxn, &[0.0, 0.0]).unwrap();
+    assert_eq!(nns.into_nns(), vec![(0, 0.0), (7, 49.0 / 100.0)]);
+}
+
+#[test]
+fn normalize_the_scores_of_the_results() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let searched = reader.nns(3).by_vector(&wtxn, &[0.0, 0.0]).unwrap();
+    assert_eq!(searched.scores(), ScoreKind::Distance);
+    assert_eq!(searched.into_nns(), vec![(0, 0.0), (1, 1.0), (2, 4.0)]);
+    let searched =
+        reader.nns(3).scores(ScoreKind::Normalized).by_vector(&wtxn, &[0.0, 0.0]).unwrap();
+    assert_eq!(searched.scores(), ScoreKind::Normalized);
+    assert_eq!(searched.into_nns(), vec![(0, 1.0), (1, 0.75), (2, 0.0)]);
+    let searched = reader.nns(3).scores(ScoreKind::Similarity).by_item(&wtxn, 0).unwrap().unwrap();
+    assert_eq!(searched.into_nns(), vec![(1, 0.0), (2, -3.0), (3, -8.0)]);
+    // a single result is the closest and the furthest at once
+    let searched = reader.nns(1).scores(ScoreKind::Normalized).by_item(&wtxn, 4).unwrap().unwrap();
+    assert_eq!(searched.into_nns().len(), 1);
+
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Cosine>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for (i, vector) in [[1.0, 0.0], [0.0, 1.0], [-1.0, 0.0], [1.0, 1.0]].iter().enumerate() {
+        writer.add_item(&mut wtxn, i as u32, vector).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+
+    // the cosine similarities
+    let reader = Reader::<Cosine>::open(&wtxn, 0, database).unwrap();
+    let searched =
+        reader.nns(4).scores(ScoreKind::Similarity).by_vector(&wtxn, &[2.0, 0.0]).unwrap();
+    let expected = [(0, 1.0), (3, std::f32::consts::FRAC_1_SQRT_2), (1, 0.0), (2, -1.0)];
+    assert_eq!(searched.nns.len(), expected.len());
+    for (&(item, similarity), &(expected, cosine)) in searched.nns.iter().zip(&expected) {
+        assert_eq!(item, expected);
+        assert!((similarity - cosine).abs() < 1e-5, "{similarity} != {cosine}");
+    }
+}
+
+#[test]
+fn search_several_indexes_at_once() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut rng = rng();
+    for index in 0..3 {
+        let writer = Writer::new(database, index, 2);
+        for i in 0..10 {
+            writer.add_item(&mut wtxn, i, &[(index as u32 * 10 + i) as f32, 0.0]).unwrap();
+        }
+        writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    }
+    let writer = Writer::new(database, 3, 3);
+    writer.add_item(&mut wtxn, 0, &[0.0, 0.0, 0.0]).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    // the closest items of all the indexes, the ties broken by index
+    let shards = MultiReader::open(&wtxn, 0..3, database).unwrap();
+    assert_eq!(shards.readers().len(), 3);
+    let searched = shards.nns_by_vector(&wtxn, &[9.5, 0.0], 4).unwrap();
+    assert_eq!(searched.nns, vec![(0, 9, 0.25), (1, 0, 0.25), (0, 8, 2.25), (1, 1, 2.25)]);
+    assert!(!searched.did_cancel);
+
+    // the scores are normalized over the merged results
+    let shards = shards.with_scores(ScoreKind::Normalized);
+    let searched = shards.nns_by_vector(&wtxn, &[19.0, 0.0], 3).unwrap();
+    assert_eq!(searched.scores(), ScoreKind::Normalized);
+    assert_eq!(searched.nns, vec![(1, 9, 1.0), (1, 8, 0.0), (2, 0, 0.0)]);
+
+    // the indexes must take the same queries
+    let err = MultiReader::open(&wtxn, 2..4, database).unwrap_err();
+    assert!(matches!(err, Error::InvalidDimensions { expected: 2, got: 3 }), "{err:?}");
+}
+
+#[test]
+fn describe_the_index() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<BinaryQuantizedCosine>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, 64);
+    writer.set_soft_deletes(true);
+    let mut rng = rng();
+    for i in 0..100 {
+        let vector: Vec<f32> = (0..64).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        writer.add_item(&mut wtxn, i, &vector).unwrap();
+    }
+    let before = SystemTime::now() - Duration::from_secs(1);
+    writer
+        .builder(&mut rng)
+        .ef_construction(64)
+        .lsh_sketches(100)
+        .build::<M, M0>(&mut wtxn)
+        .unwrap();
+    writer.del_item(&mut wtxn, 3).unwrap();
+
+    let reader = Reader::<BinaryQuantizedCosine>::open(&wtxn, 0, database).unwrap();
+    let mut info = reader.info(&wtxn).unwrap();
+    let build = info.build.take().unwrap();
+    assert_eq!((build.m, build.m0, build.ef_construction, build.alpha), (M, M0, 64, 1.0));
+    assert!(build.built_at > before && build.built_at <= SystemTime::now());
+    info.max_level = 0;
+    insta::assert_debug_snapshot!(info, @r#"
+    IndexInfo {
+        index: 0,
+        dimensions: 64,
+        query_dimensions: 64,
+        distance: "binary quantized cosine",
+        items: 99,
+        tombstones: 1,
+        entry_points: 5,
+        max_level: 0,
+        quantized: true,
+        calibrated: false,
+        sketch_bits: Some(
+            128,
+        ),
+        version: Version {
+            major: 0,
+            minor: 0,
+            patch: 8,
+        },
+        build: None,
+    }
+    "#);
+}
+
+#[test]
+fn top_k_per_bucket() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let even: RoaringBitmap = (0..20).step_by(2).collect();
+    let odd: RoaringBitmap = (1..20).step_by(2).collect();
+    // too far from the query to be found by the shared traversal
+    let far: RoaringBitmap = (15..20).collect();
+    let few = RoaringBitmap::from_iter([7, 42]);
+    let buckets = [even, odd, far, few, RoaringBitmap::new()];
+
+    let ids =
+        |searched: &crate::Searched| -> Vec<_> { searched.nns.iter().map(|(id, _)| *id).collect() };
+    let per_bucket = reader.nns(2).by_vector_per_bucket(&rtxn, &[0.0, 0.0], &buckets).unwrap();
+    assert_eq!(
+        per_bucket.iter().map(ids).collect::<Vec<_>>(),
+        vec![vec![0, 2], vec![1, 3], vec![15, 16], vec![7], vec![],]
+    );
+
+    let candidates: RoaringBitmap = (4..20).collect();
+    let per_bucket = reader
+        .nns(2)
+        .candidates(&candidates)
+        .by_vector_per_bucket(&rtxn, &[0.0, 0.0], &buckets[..2])
+        .unwrap();
+    assert_eq!(per_bucket.iter().map(ids).collect::<Vec<_>>(), vec![vec![4, 6], vec![5, 7]]);
+}
+
+#[test]
+fn hinted_entry_points_skip_the_upper_layers() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 500, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+    let nns = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+    let hints = [nns[0].0, 4242];
+    let (hinted, trace) =
+        reader.nns(10).entry_points(&hints).by_vector_traced(&rtxn, &query).unwrap();
+    assert_eq!(hinted.into_nns(), nns);
+    assert!(matches!(
+        &trace.events[0],
+        TraceEvent::EnterLayer { layer: 0, entry_points } if entry_points == &[nns[0].0]
+    ));
+
+    // unknown items fall back to the descent from the top layer
+    let (fallback, trace) =
+        reader.nns(10).entry_points(&[4242]).by_vector_traced(&rtxn, &query).unwrap();
+    assert_eq!(fallback.into_nns(), nns);
+    assert!(matches!(trace.events[0], TraceEvent::EnterLayer { layer, .. } if layer > 0));
+}
+
+#[test]
+fn multi_search_in_one_transaction() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+
+    // the writes committed during the batch aren't seen by its searches
+    let mut wtxn = env.write_txn().unwrap();
+    writer.del_item(&mut wtxn, 1).unwrap();
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let results = reader
+        .multi_search(
+            &rtxn,
+            &[
+                Query::Vector { vector: &[0.0, 0.0], count: 2 },
+                Query::Item { item: 9, count: 1 },
+                Query::Item { item: 42, count: 1 },
+            ],
+        )
+        .unwrap();
+    let results: Vec<_> = results.into_iter().map(|res| res.map(|s| s.into_nns())).collect();
+    assert_eq!(results, vec![Some(vec![(0, 0.0), (1, 1.0)]), Some(vec![(8, 1.0)]), None]);
+
+    let wrong = [Query::Vector { vector: &[0.0], count: 2 }];
+    assert!(matches!(
+        reader.multi_search(&rtxn, &wrong),
+        Err(Error::InvalidDimensions { expected: 2, got: 1 })
+    ));
+}
+
+#[test]
+fn dedup_collapses_near_identical_results() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+        // a mirror of every item, slightly further from the origin
+        writer.add_item(&mut wtxn, 100 + i, &[i as f32 + 0.01, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let ids = |searched: crate::Searched| -> Vec<_> {
+        searched.into_nns().into_iter().map(|(id, _)| id).collect()
+    };
+
+    let nns = reader.nns(3).by_vector(&rtxn, &[0.0, 0.0]).unwrap();
+    assert_eq!(ids(nns), vec![0, 100, 1]);
+    let nns = reader.nns(3).dedup(0.01).by_vector(&rtxn, &[0.0, 0.0]).unwrap();
+    assert_eq!(ids(nns), vec![0, 1, 2]);
+    let nns = reader.nns(3).dedup(0.01).by_item(&rtxn, 5).unwrap().unwrap();
+    assert_eq!(ids(nns), vec![105, 104, 6]);
+    // everything is a duplicate of the closest item
+    let nns = reader.nns(3).dedup(1000.0).by_vector(&rtxn, &[0.0, 0.0]).unwrap();
+    assert_eq!(ids(nns), vec![0]);
+}
+
+#[test]
+fn furthest_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, 2);
+    writer.set_soft_deletes(true);
+    for i in 0..50 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    writer.del_item(&mut wtxn, 48).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let furthest = reader.furthest(&rtxn, &[0.0, 0.0], 3).unwrap();
+    assert_eq!(furthest, vec![(49, 49.0 * 49.0), (47, 47.0 * 47.0), (46, 46.0 * 46.0)]);
+    let furthest = reader.furthest(&rtxn, &[49.0, 0.0], 2).unwrap();
+    assert_eq!(furthest, vec![(0, 49.0 * 49.0), (1, 48.0 * 48.0)]);
+
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 1000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let mut found = 0;
+    for _ in 0..10 {
+        let query: [f32; DIM] = std::array::from_fn(|_| rng.gen_range(-1.0..1.0));
+        let mut exact: Vec<_> = reader
+            .iter(&rtxn)
+            .unwrap()
+            .map(|res| {
+                let (id, v) = res.unwrap();
+                (v.iter().zip(&query).map(|(a, b)| (a - b) * (a - b)).sum::<f32>(), id)
+            })
+            .collect();
+        exact.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let exact: RoaringBitmap = exact.into_iter().take(10).map(|(_, id)| id).collect();
+        let furthest = reader.furthest(&rtxn, &query, 10).unwrap();
+        assert!(furthest.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        found += furthest.iter().filter(|(id, _)| exact.contains(*id)).count();
+    }
+    assert!(found >= 90, "recall of {found}%");
+}
+
+#[test]
+fn stop_when_within_a_distance() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 1000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let visited = |trace: &crate::SearchTrace| trace.visited().count();
+
+    let query = reader.item_vector(&rtxn, 42).unwrap().unwrap();
+    let (full, full_trace) = reader.nns(1).by_vector_traced(&rtxn, &query).unwrap();
+    let (stopped, stopped_trace) =
+        reader.nns(1).stop_when_within(1e-6).by_vector_traced(&rtxn, &query).unwrap();
+    assert_eq!(full.into_nns(), vec![(42, 0.0)]);
+    assert_eq!(stopped.into_nns(), vec![(42, 0.0)]);
+    assert!(visited(&stopped_trace) < visited(&full_trace));
+
+    // a distance nothing is within doesn't stop the search
+    let (searched, _) =
+        reader.nns(10).stop_when_within(-1.0).by_vector_traced(&rtxn, &query).unwrap();
+    let nns = reader.nns(10).by_vector(&rtxn, &query).unwrap();
+    assert_eq!(searched.into_nns(), nns.into_nns());
+}
+
+#[test]
+fn multi_vector_items_appear_once() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Cosine>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, DIM);
+    let layout = MultiVector::new(2);
+
+    let mut documents = Vec::new();
+    for item in 0..50 {
+        let vectors: Vec<[f32; DIM]> =
+            (0..3).map(|_| std::array::from_fn(|_| rng.gen_range(-1.0..1.0))).collect();
+        layout.add_item(&writer, &mut wtxn, item, &vectors).unwrap();
+        documents.push(vectors);
+    }
+    // replacing the vectors of an item drops the previous ones
+    layout.add_item(&writer, &mut wtxn, 49, &documents[49][..1]).unwrap();
+    assert!(!writer.contains_item(&wtxn, layout.vector_id(49, 1)).unwrap());
+    let err = layout.add_item(&writer, &mut wtxn, 0, &[[0.0; DIM]; 5]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid multi-vector item 0: 5 vectors but at most 4");
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::open(&rtxn, 0, database).unwrap();
+    for score in [MultiVectorScore::Max, MultiVectorScore::Mean, MultiVectorScore::MaxSim] {
+        let nns = layout.nns(&reader, &rtxn, &documents[7], 10, score).unwrap();
+        let items: RoaringBitmap = nns.iter().map(|(item, _)| *item).collect();
+        assert_eq!(items.len(), nns.len() as u64);
+        assert!(nns.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        if score != MultiVectorScore::Mean {
+            assert_eq!(nns[0].0, 7);
+            assert!(nns[0].1 < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn plan_the_strategy_of_the_filtered_searches() {
+    // a million items searched with the default ef: the scan wins up to ~56k candidates
+    assert_eq!(FilterStrategy::plan(1000, 1_000_000, 100), FilterStrategy::Linear);
+    assert_eq!(FilterStrategy::plan(50_000, 1_000_000, 100), FilterStrategy::Linear);
+    assert_eq!(FilterStrategy::plan(60_000, 1_000_000, 100), FilterStrategy::Graph);
+    assert_eq!(FilterStrategy::plan(1_000_000, 1_000_000, 100), FilterStrategy::Graph);
+    // a larger search buffer makes the traversal more expensive
+    assert_eq!(FilterStrategy::plan(60_000, 1_000_000, 200), FilterStrategy::Linear);
+    // the small indexes are scanned
+    assert_eq!(FilterStrategy::plan(2000, 2000, 100), FilterStrategy::Linear);
+    assert_eq!(FilterStrategy::plan(0, 1_000_000, 100), FilterStrategy::Linear);
+
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 2000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+    let candidates: RoaringBitmap = (0..2000).step_by(3).collect();
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+    let (linear, trace) = reader
+        .nns(10)
+        .candidates(&candidates)
+        .filter_strategy(FilterStrategy::Linear)
+        .by_vector_traced(&rtxn, &query)
+        .unwrap();
+    assert!(matches!(trace.events[..], [TraceEvent::LinearScan { candidates: 667 }]));
+    let (graph, trace) = reader
+        .nns(10)
+        .candidates(&candidates)
+        .filter_strategy(FilterStrategy::Graph)
+        .ef_search(200)
+        .by_vector_traced(&rtxn, &query)
+        .unwrap();
+    assert!(trace.visited().count() > 10);
+    assert_eq!(graph.into_nns(), linear.into_nns());
+
+    // by default the few candidates of a small index are scanned
+    let (_, trace) =
+        reader.nns(10).candidates(&candidates).by_vector_traced(&rtxn, &query).unwrap();
+    assert!(matches!(trace.events[..], [TraceEvent::LinearScan { candidates: 667 }]));
+}
+
+#[test]
+fn selective_filters_only_visit_candidates() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Cosine, DIM, M, M0, _>(0..1, 2000, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Cosine>::open(&rtxn, 0, database).unwrap();
+    let candidates: RoaringBitmap = (0..2000).step_by(20).collect();
+
+    let mut found = 0;
+    for _ in 0..10 {
+        let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        let (searched, trace) = reader
+            .nns(10)
+            .candidates(&candidates)
+            .filter_strategy(FilterStrategy::Graph)
+            .by_vector_traced(&rtxn, &query)
+            .unwrap();
+        let nns = searched.into_nns();
+        assert!(nns.iter().all(|(id, _)| candidates.contains(*id)));
+
+        // in the bottom layer only the entry point can be outside of the candidates
+        let bottom = trace
+            .events
+            .iter()
+            .skip_while(|event| !matches!(event, TraceEvent::EnterLayer { layer: 0, .. }));
+        let outside = bottom
+            .filter(|event| matches!(event, TraceEvent::Visit { item, .. } if !candidates.contains(*item)))
+            .count();
+        assert!(outside <= 1, "{outside} items visited outside of the candidates");
+
+        let cosine = |v: &[f32]| {
+            let dot: f32 = v.iter().zip(&query).map(|(a, b)| a * b).sum();
+            -dot / v.iter().map(|a| a * a).sum::<f32>().sqrt()
+        };
+        let mut exact: Vec<_> = candidates
+            .iter()
+            .map(|id| (cosine(&reader.item_vector(&rtxn, id).unwrap().unwrap()), id))
+            .collect();
+        exact.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let exact: RoaringBitmap = exact.into_iter().take(10).map(|(_, id)| id).collect();
+        found += nns.iter().filter(|(id, _)| exact.contains(*id)).count();
+    }
+    assert!(found >= 90, "recall of {found}%");
+}
+
+#[test]
+fn search_into_caller_buffers() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 200, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+
+    let expected = reader.nns(10).by_vector(&rtxn, &query).unwrap().into_nns();
+    let (mut ids, mut distances) = ([u32::MAX; 10], [f32::NAN; 10]);
+    assert_eq!(reader.nns_into(&rtxn, &query, &mut ids, &mut distances).unwrap(), 10);
+    let found: Vec<_> = ids.into_iter().zip(distances).collect();
+    assert_eq!(found, expected);
+
+    // the shorter buffer bounds the results, the values past them are untouched
+    let (mut ids, mut distances) = ([u32::MAX; 3], [f32::NAN; 5]);
+    let written = reader.nns(10).by_vector_into(&rtxn, &query, &mut ids, &mut distances).unwrap();
+    assert_eq!(written, 3);
+    assert_eq!(ids, [expected[0].0, expected[1].0, expected[2].0]);
+    assert!(distances[3].is_nan() && distances[4].is_nan());
+
+    // an index smaller than the buffers fills part of them
+    let candidates = RoaringBitmap::from_iter([4, 8]);
+    let (mut ids, mut distances) = ([u32::MAX; 10], [0.0; 10]);
+    let written = reader
+        .nns(10)
+        .candidates(&candidates)
+        .by_vector_into(&rtxn, &query, &mut ids, &mut distances)
+        .unwrap();
+    assert_eq!(written, 2);
+    assert_eq!(ids[2..], [u32::MAX; 8]);
+
+    // the scores are converted like the ones of the vector of results
+    let expected =
+        reader.nns(10).scores(ScoreKind::Normalized).by_vector(&rtxn, &query).unwrap().into_nns();
+    let (mut ids, mut distances) = ([u32::MAX; 10], [f32::NAN; 10]);
+    reader
+        .nns(10)
+        .scores(ScoreKind::Normalized)
+        .by_vector_into(&rtxn, &query, &mut ids, &mut distances)
+        .unwrap();
+    assert_eq!(ids.into_iter().zip(distances).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+#[cfg_attr(feature = "metrics", ignore = "the metrics allocate their labels")]
+fn warm_searches_into_caller_buffers_dont_allocate() {
+    const DIM: usize = 8;
+    let mut rng = rng();
+    let DatabaseHandle { env, database, tempdir: _ } =
+        create_database_indices_with_items::<Euclidean, DIM, M, M0, _>(0..1, 200, &mut rng);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    let query: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+    let (mut ids, mut distances) = ([u32::MAX; 10], [f32::NAN; 10]);
+    reader.nns(10).by_vector_into(&rtxn, &query, &mut ids, &mut distances).unwrap();
+
+    // once the buffers of the thread are warm, writing the results allocates nothing
+    let (written, allocations) = count_allocations(|| {
+        reader.nns(10).by_vector_into(&rtxn, &query, &mut ids, &mut distances).unwrap()
+    });
+    assert_eq!(written, 10);
+    assert_eq!(allocations, 0);
+}
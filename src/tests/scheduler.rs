@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU8, Ordering::Relaxed};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::scheduler::Scheduler;
+
+fn pool(threads: usize) -> rayon::ThreadPool {
+    rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap()
+}
+
+#[test]
+fn batch_sizes() {
+    let scheduler = pool(4).install(Scheduler::new);
+    // the upper levels are inserted item by item
+    assert_eq!(scheduler.batch_size(3), 1);
+    assert_eq!(scheduler.batch_size(32), 1);
+    assert_eq!(scheduler.batch_size(1000), 32);
+    assert_eq!(scheduler.batch_size(1_000_000), 256);
+}
+
+#[test]
+fn schedule_every_item_once() {
+    let items: Vec<usize> = (0..1000).collect();
+    let visits: Vec<_> = items.iter().map(|_| AtomicU8::new(0)).collect();
+    let threads = Mutex::new(HashSet::new());
+
+    pool(4)
+        .install(|| {
+            Scheduler::new().try_for_each(&items, |&item| {
+                // the first batch, dealt to the first thread, is much slower than the others
+                if item < 8 {
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+                visits[item].fetch_add(1, Relaxed);
+                threads.lock().unwrap().insert(rayon::current_thread_index());
+                Ok::<_, ()>(())
+            })
+        })
+        .unwrap();
+
+    assert!(visits.iter().all(|visits| visits.load(Relaxed) == 1));
+    assert!(threads.into_inner().unwrap().len() > 1);
+}
+
+#[test]
+fn stop_at_the_first_error() {
+    let items: Vec<usize> = (0..1000).collect();
+    let result = pool(4).install(|| {
+        Scheduler::new().try_for_each(&items, |&item| if item == 500 { Err(item) } else { Ok(()) })
+    });
+    assert_eq!(result, Err(500));
+
+    // a level with fewer items than threads
+    let result = pool(4).install(|| {
+        Scheduler::new()
+            .try_for_each(&items[..2], |&item| if item == 1 { Err(item) } else { Ok(()) })
+    });
+    assert_eq!(result, Err(1));
+}
@@ -0,0 +1,45 @@
+use rand::Rng;
+
+use super::{create_database, rng, DatabaseHandle};
+use crate::distance::{Cosine, Euclidean, Hellinger};
+use crate::{Reader, ScoringReader, Writer};
+
+const M: usize = 16;
+const M0: usize = 32;
+
+#[test]
+fn score_a_cosine_index_with_euclidean_distances() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Cosine>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut rng = rng();
+    let writer = Writer::new(database, 0, 3);
+    let vectors: Vec<[f32; 3]> =
+        (0..300).map(|_| std::array::from_fn(|_| rng.gen_range(-1.0..1.0))).collect();
+    for (item, vector) in vectors.iter().enumerate() {
+        writer.add_item(&mut wtxn, item as u32, vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::open(&wtxn, 0, database).unwrap();
+    let reader = ScoringReader::<Cosine, Euclidean>::new(reader).unwrap();
+    let query = [0.3, -0.2, 0.5];
+    let found = reader.nns_by_vector(&wtxn, &query, 5).unwrap();
+    assert_eq!(found.len(), 5);
+    assert!(found.windows(2).all(|w| w[0].1 <= w[1].1));
+    for &(item, distance) in &found {
+        let expected: f32 =
+            vectors[item as usize].iter().zip(&query).map(|(a, b)| (a - b).powi(2)).sum();
+        assert!((distance - expected).abs() < 1e-5, "{distance} {expected}");
+    }
+    assert_eq!(reader.reader().n_items(), 300);
+
+    let found = reader.nns_by_item(&wtxn, 42, 5).unwrap().unwrap();
+    assert!(found.iter().all(|&(item, _)| item != 42));
+    let (closest, distance) = found[0];
+    assert_eq!(reader.distance(&wtxn, 42, closest).unwrap(), Some(distance));
+    assert_eq!(reader.nns_by_item(&wtxn, 1000, 5).unwrap(), None);
+
+    let reader = Reader::open(&wtxn, 0, database).unwrap();
+    let err = ScoringReader::<Cosine, Hellinger>::new(reader).unwrap_err();
+    insta::assert_snapshot!(err, @"Cannot score the vectors of a cosine index with the hellinger distance, they transform the vectors differently");
+}
@@ -0,0 +1,80 @@
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+use super::{create_database, rng};
+use crate::distance::Euclidean;
+use crate::{Reader, Writer};
+
+/// Remembers the name of every span and the number of visited nodes recorded by searches.
+#[derive(Clone, Default)]
+struct Collector {
+    spans: Arc<Mutex<Vec<&'static str>>>,
+    visited: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Visit for Collector {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "visited" {
+            self.visited.lock().unwrap().push(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+impl<S: Subscriber> Layer<S> for Collector {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        self.spans.lock().unwrap().push(attrs.metadata().name());
+    }
+
+    fn on_record(&self, _span: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        values.record(&mut self.clone());
+    }
+}
+
+#[test]
+fn build_and_search_open_spans() {
+    let collector = Collector::default();
+    let subscriber = tracing_subscriber::registry().with(collector.clone());
+    let handle = create_database::<Euclidean>();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let mut wtxn = handle.env.write_txn().unwrap();
+        let writer = Writer::new(handle.database, 0, 2);
+        for i in 0..100 {
+            writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+        }
+        writer.builder(&mut rng()).build::<16, 32>(&mut wtxn).unwrap();
+
+        let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+        reader.nns(5).by_vector(&wtxn, &[20.0, 0.0]).unwrap();
+    });
+
+    let spans = collector.spans.lock().unwrap();
+    insta::assert_debug_snapshot!(spans, @r###"
+    [
+        "build",
+        "item_indices",
+        "reset_and_retrieve_updated_items",
+        "build_graph",
+        "fetch_item_pointers",
+        "fetch_links_pointers",
+        "prepare_levels_and_entry_points",
+        "insert_levels",
+        "relink",
+        "maybe_patch_old_links",
+        "write_links",
+        "delete_links_from_db",
+        "open",
+        "search",
+    ]
+    "###);
+    // the search recorded how many nodes it visited
+    let visited = collector.visited.lock().unwrap();
+    assert!(matches!(visited[..], [n] if n > 0), "{visited:?}");
+}
@@ -0,0 +1,42 @@
+use super::{create_database, DatabaseHandle};
+use crate::distance::Euclidean;
+use crate::{Error, Writer};
+
+#[test]
+fn point_in_time_sees_the_previous_versions() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, 2);
+    writer.set_change_log(&mut wtxn, true).unwrap();
+    writer.set_versions(2);
+
+    writer.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap(); // 0
+    writer.add_item(&mut wtxn, 1, &[1.0, 0.0]).unwrap(); // 1
+    writer.add_item(&mut wtxn, 0, &[5.0, 5.0]).unwrap(); // 2
+    writer.del_item(&mut wtxn, 1).unwrap(); // 3
+    writer.add_item(&mut wtxn, 2, &[2.0, 0.0]).unwrap(); // 4
+
+    let now = writer.point_in_time(&wtxn, 5).unwrap();
+    assert_eq!(now.item_ids().iter().collect::<Vec<_>>(), [0, 2]);
+    assert_eq!(now.item_vector(&wtxn, 0).unwrap(), Some(vec![5.0, 5.0]));
+
+    let before_delete = writer.point_in_time(&wtxn, 3).unwrap();
+    assert_eq!(before_delete.item_ids().iter().collect::<Vec<_>>(), [0, 1]);
+    assert_eq!(before_delete.item_vector(&wtxn, 0).unwrap(), Some(vec![5.0, 5.0]));
+    assert_eq!(before_delete.item_vector(&wtxn, 1).unwrap(), Some(vec![1.0, 0.0]));
+    assert_eq!(before_delete.item_vector(&wtxn, 2).unwrap(), None);
+
+    let start = writer.point_in_time(&wtxn, 2).unwrap();
+    assert_eq!(start.item_vector(&wtxn, 0).unwrap(), Some(vec![0.0, 0.0]));
+    let nns = start.nns(&wtxn, &[0.9, 0.0], 2).unwrap();
+    assert_eq!(nns.iter().map(|&(id, _)| id).collect::<Vec<_>>(), [1, 0]);
+    assert!(writer.point_in_time(&wtxn, 0).unwrap().item_ids().is_empty());
+
+    // only the two last versions of item 0 are kept
+    writer.add_item(&mut wtxn, 0, &[6.0, 6.0]).unwrap(); // 5
+    writer.add_item(&mut wtxn, 0, &[7.0, 7.0]).unwrap(); // 6
+    let pruned = writer.point_in_time(&wtxn, 2).unwrap_err();
+    assert!(matches!(pruned, Error::VersionPruned { item: 0, sequence: 2 }), "{pruned}");
+    let kept = writer.point_in_time(&wtxn, 5).unwrap();
+    assert_eq!(kept.item_vector(&wtxn, 0).unwrap(), Some(vec![5.0, 5.0]));
+}
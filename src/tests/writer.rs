@@ -1,3 +1,8 @@
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
 use heed::types::DecodeIgnore;
 use proptest::proptest;
 use rand::distributions::Uniform;
@@ -7,11 +12,15 @@ use rand::{thread_rng, Rng, SeedableRng};
 use roaring::RoaringBitmap;
 
 use super::{create_database, rng};
-use crate::distance::{BinaryQuantizedCosine, Cosine, Euclidean};
-use crate::key::{KeyCodec, Prefix, PrefixCodec};
-use crate::reader::get_item;
+use crate::distance::{BinaryQuantizedCosine, Cosine, Euclidean, Hellinger, SparseCosine};
+use crate::key::{Key, KeyCodec, Prefix, PrefixCodec};
+use crate::node::{Links, Node};
+use crate::reader::{get_item, get_links};
 use crate::tests::DatabaseHandle;
-use crate::{Reader, Writer};
+use crate::{
+    BuildParams, BuildPreset, BuildThreshold, ChangeKind, DatasetStats, Error, IngestKind, ItemId,
+    Maintenance, MaintenanceStep, MetadataCodec, Reader, Writer, WriterHooks,
+};
 
 const M: usize = 3;
 const M0: usize = 3;
@@ -79,6 +88,8 @@ fn use_u32_max_minus_one_for_a_vec() {
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[4294967294]>, distance: "euclidean", entry_points: [4294967294], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 4294967294: Links(Links { links: RoaringBitmap<[]> })
     Links 4294967294: Links(Links { links: RoaringBitmap<[]> })
     Item 4294967294: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 1.0000, 2.0000] })
@@ -100,6 +111,8 @@ fn use_u32_max_for_a_vec() {
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[4294967295]>, distance: "euclidean", entry_points: [4294967295], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 4294967295: Links(Links { links: RoaringBitmap<[]> })
     Links 4294967295: Links(Links { links: RoaringBitmap<[]> })
     Item 4294967295: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 1.0000, 2.0000] })
@@ -121,6 +134,8 @@ fn write_one_vector() {
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, distance: "euclidean", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 1.0000, 2.0000] })
@@ -171,6 +186,8 @@ fn write_multiple_indexes() {
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, distance: "euclidean", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 1.0000, 2.0000] })
@@ -178,6 +195,8 @@ fn write_multiple_indexes() {
     Dumping index 1
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, distance: "euclidean", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 1.0000, 2.0000] })
@@ -185,6 +204,8 @@ fn write_multiple_indexes() {
     Dumping index 2
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, distance: "euclidean", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 1.0000, 2.0000] })
@@ -192,6 +213,8 @@ fn write_multiple_indexes() {
     Dumping index 3
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, distance: "euclidean", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 1.0000, 2.0000] })
@@ -199,6 +222,8 @@ fn write_multiple_indexes() {
     Dumping index 4
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, distance: "euclidean", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 1.0000, 2.0000] })
@@ -265,7 +290,7 @@ fn convert_from_arroy_to_hannoy() {
 
     let mut wtxn = env.write_txn().unwrap();
     let rtxn = env.read_txn().unwrap();
-    let database: crate::Database<Cosine> = env.open_database(&mut wtxn, None).unwrap().unwrap();
+    let database: crate::Database<Cosine> = env.open_database(&wtxn, None).unwrap().unwrap();
 
     db_indexes.shuffle(&mut rng);
 
@@ -276,7 +301,7 @@ fn convert_from_arroy_to_hannoy() {
 
         let writer = Writer::new(database, index, pre_commit_arroy_reader.dimensions());
         writer.builder(&mut rng).prepare_arroy_conversion(&mut wtxn).unwrap();
-        assert!(writer.need_build(&mut wtxn).unwrap());
+        assert!(writer.need_build(&wtxn).unwrap());
         writer.builder(&mut rng).build::<16, 32>(&mut wtxn).unwrap();
 
         for result in pre_commit_arroy_reader.iter(&rtxn).unwrap() {
@@ -337,7 +362,7 @@ fn convert_from_arroy_to_hannoy_binary_quantized() {
     let mut wtxn = env.write_txn().unwrap();
     let rtxn = env.read_txn().unwrap();
     let database: crate::Database<BinaryQuantizedCosine> =
-        env.open_database(&mut wtxn, None).unwrap().unwrap();
+        env.open_database(&wtxn, None).unwrap().unwrap();
 
     db_indexes.shuffle(&mut rng);
 
@@ -352,7 +377,7 @@ fn convert_from_arroy_to_hannoy_binary_quantized() {
 
         let writer = Writer::new(database, index, pre_commit_arroy_reader.dimensions());
         writer.builder(&mut rng).prepare_arroy_conversion(&mut wtxn).unwrap();
-        assert!(writer.need_build(&mut wtxn).unwrap());
+        assert!(writer.need_build(&wtxn).unwrap());
         writer.builder(&mut rng).build::<16, 32>(&mut wtxn).unwrap();
 
         for result in pre_commit_arroy_reader.iter(&rtxn).unwrap() {
@@ -390,6 +415,8 @@ fn overwrite_one_item_incremental() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, distance: "euclidean", entry_points: [0, 2, 3], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[1, 2]> })
     Links 0: Links(Links { links: RoaringBitmap<[2]> })
     Links 1: Links(Links { links: RoaringBitmap<[0, 2]> })
@@ -419,6 +446,8 @@ fn overwrite_one_item_incremental() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, distance: "euclidean", entry_points: [0, 2, 3], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 2
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[1]> })
     Links 0: Links(Links { links: RoaringBitmap<[2]> })
     Links 1: Links(Links { links: RoaringBitmap<[0, 2]> })
@@ -454,6 +483,8 @@ fn delete_one_item_in_a_one_item_db() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0]>, distance: "euclidean", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 0.0000] })
@@ -472,6 +503,8 @@ fn delete_one_item_in_a_one_item_db() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, distance: "euclidean", entry_points: [], max_level: 0 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 2
+    Format: 1
     "#);
 
     let rtxn = handle.env.read_txn().unwrap();
@@ -498,6 +531,8 @@ fn delete_document_in_an_empty_index_74() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0]>, distance: "euclidean", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 0.0000] })
@@ -520,6 +555,7 @@ fn delete_document_in_an_empty_index_74() {
     Searched {
         nns: [],
         did_cancel: false,
+        scores: Distance,
     }
     ");
 
@@ -530,10 +566,14 @@ fn delete_document_in_an_empty_index_74() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, distance: "euclidean", entry_points: [], max_level: 0 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 2
+    Format: 1
     ==================
     Dumping index 1
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, distance: "euclidean", entry_points: [], max_level: 0 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 2
+    Format: 1
     "#);
 
     let rtxn = handle.env.read_txn().unwrap();
@@ -543,6 +583,7 @@ fn delete_document_in_an_empty_index_74() {
     Searched {
         nns: [],
         did_cancel: false,
+        scores: Distance,
     }
     ");
 }
@@ -564,6 +605,8 @@ fn delete_one_item_in_a_single_document_database() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0]>, distance: "cosine", entry_points: [0], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Links 0: Links(Links { links: RoaringBitmap<[]> })
     Item 0: Item(Item { header: NodeHeaderCosine { norm: "0.0000" }, vector: [0.0000, 0.0000] })
@@ -582,6 +625,8 @@ fn delete_one_item_in_a_single_document_database() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, distance: "cosine", entry_points: [], max_level: 0 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 2
+    Format: 1
     "#);
 }
 
@@ -604,6 +649,8 @@ fn delete_one_item() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, distance: "euclidean", entry_points: [0, 2, 3], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 1
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[1, 2]> })
     Links 0: Links(Links { links: RoaringBitmap<[2]> })
     Links 1: Links(Links { links: RoaringBitmap<[0, 2]> })
@@ -634,13 +681,15 @@ fn delete_one_item() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 4, 5]>, distance: "euclidean", entry_points: [0, 1, 2], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
+    Build: 2
+    Format: 1
     Links 0: Links(Links { links: RoaringBitmap<[1]> })
     Links 0: Links(Links { links: RoaringBitmap<[1]> })
     Links 1: Links(Links { links: RoaringBitmap<[0, 2]> })
     Links 1: Links(Links { links: RoaringBitmap<[0, 2]> })
-    Links 2: Links(Links { links: RoaringBitmap<[1, 2, 4]> })
-    Links 2: Links(Links { links: RoaringBitmap<[1, 2]> })
-    Links 4: Links(Links { links: RoaringBitmap<[2, 4, 5]> })
+    Links 2: Links(Links { links: RoaringBitmap<[1, 4]> })
+    Links 2: Links(Links { links: RoaringBitmap<[1]> })
+    Links 4: Links(Links { links: RoaringBitmap<[2, 5]> })
     Links 5: Links(Links { links: RoaringBitmap<[4]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 0.0000] })
     Item 1: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [1.0000, 0.0000] })
@@ -663,11 +712,13 @@ fn delete_one_item() {
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 2, 4, 5]>, distance: "euclidean", entry_points: [0, 2, 4], max_level: 1 }
     Version: Version { major: 0, minor: 0, patch: 8 }
-    Links 0: Links(Links { links: RoaringBitmap<[0, 2]> })
-    Links 0: Links(Links { links: RoaringBitmap<[0, 2]> })
-    Links 2: Links(Links { links: RoaringBitmap<[0, 2, 4]> })
-    Links 2: Links(Links { links: RoaringBitmap<[0, 2, 4]> })
-    Links 4: Links(Links { links: RoaringBitmap<[2, 4, 5]> })
+    Build: 3
+    Format: 1
+    Links 0: Links(Links { links: RoaringBitmap<[2]> })
+    Links 0: Links(Links { links: RoaringBitmap<[2]> })
+    Links 2: Links(Links { links: RoaringBitmap<[0, 4]> })
+    Links 2: Links(Links { links: RoaringBitmap<[0, 4]> })
+    Links 4: Links(Links { links: RoaringBitmap<[2, 5]> })
     Links 4: Links(Links { links: RoaringBitmap<[2]> })
     Links 5: Links(Links { links: RoaringBitmap<[4]> })
     Item 0: Item(Item { header: NodeHeaderEuclidean { bias: "0.0000" }, vector: [0.0000, 0.0000] })
@@ -745,3 +796,1577 @@ proptest! {
         writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
     }
 }
+
+#[test]
+fn upsert_existing_items_relinks_them() {
+    const DIM: usize = 30;
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, DIM);
+    for id in 0..200 {
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        writer.add_item(&mut wtxn, id, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    // move a few items far away from the rest of the points
+    let far: Vec<[f32; DIM]> = (0..5).map(|i| [100.0 + i as f32; DIM]).collect();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    for (id, vector) in far.iter().enumerate() {
+        writer.add_item(&mut wtxn, id as u32 * 10, vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.n_items(), 200);
+
+    for (id, vector) in far.iter().enumerate() {
+        let found = reader.nns(1).by_vector(&rtxn, vector).unwrap().into_nns();
+        assert_eq!(found[0], (id as u32 * 10, 0.0));
+    }
+
+    // the moved items are now each other's closest neighbours
+    let found = reader.nns(4).by_item(&rtxn, 20).unwrap().unwrap().into_nns();
+    let found = RoaringBitmap::from_iter(found.into_iter().map(|(id, _)| id));
+    assert_eq!(found, RoaringBitmap::from_iter([0, 10, 30, 40]));
+}
+
+/// Keeps the steps of a build to look at their state once it's done.
+#[derive(Clone, Default)]
+struct Steps(Arc<Mutex<Vec<Box<dyn steppe::Step>>>>);
+
+impl steppe::Progress for Steps {
+    fn update(&self, step: impl steppe::Step) {
+        self.0.lock().unwrap().push(Box::new(step));
+    }
+}
+
+#[test]
+fn overwrite_the_entry_points() {
+    const DIM: usize = 30;
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let metadata = handle.database.remap_data_type::<MetadataCodec>();
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, DIM);
+    for id in 0..200 {
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        writer.add_item(&mut wtxn, id, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let root = metadata.get(&wtxn, &Key::metadata(0)).unwrap().unwrap();
+    let entry_points: Vec<_> = root.entry_points.iter().collect();
+
+    // move the entry points far away from the rest of the points
+    for (i, &id) in entry_points.iter().enumerate() {
+        writer.add_item(&mut wtxn, id, &[100.0 + i as f32; DIM]).unwrap();
+    }
+    let steps = Steps::default();
+    writer.builder(&mut rng).progress(steps.clone()).build::<M, M0>(&mut wtxn).unwrap();
+
+    // they're only inserted once, on all the layers
+    let steps = steps.0.lock().unwrap();
+    let inserted = steps.iter().find(|step| step.name() == "inserting items").unwrap();
+    assert_eq!(inserted.current(), inserted.total());
+    let root = metadata.get(&wtxn, &Key::metadata(0)).unwrap().unwrap();
+    let max_level = root.max_level as usize;
+    assert!(entry_points.iter().all(|&id| root.entry_points.iter().any(|ep| ep == id)));
+    for &id in &entry_points {
+        for level in 0..=max_level {
+            assert!(get_links(&wtxn, handle.database, 0, id, level).unwrap().is_some());
+        }
+    }
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    for (i, &id) in entry_points.iter().enumerate() {
+        let found = reader.nns(1).by_vector(&wtxn, &[100.0 + i as f32; DIM]).unwrap().into_nns();
+        assert_eq!(found[0], (id, 0.0));
+    }
+}
+
+#[test]
+fn add_items_matches_add_item() {
+    const DIM: usize = 10;
+    let mut rng = rng();
+    let mut ids: Vec<u32> = (0..100).collect();
+    ids.shuffle(&mut rng);
+    let vectors: Vec<(u32, [f32; DIM])> =
+        ids.iter().map(|&id| (id, std::array::from_fn(|_| rng.gen()))).collect();
+
+    let bulk = create_database::<Euclidean>();
+    let mut wtxn = bulk.env.write_txn().unwrap();
+    let writer = Writer::new(bulk.database, 0, DIM);
+    // the last occurence of a duplicated id wins
+    let overwritten = [42.0; DIM];
+    let iter = vectors.iter().map(|(id, v)| (*id, v.as_slice()));
+    writer.add_items(&mut wtxn, iter.chain([(0, overwritten.as_slice())])).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = bulk.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, bulk.database).unwrap();
+    assert_eq!(reader.n_items(), 100);
+    for (id, vector) in &vectors {
+        let expected = if *id == 0 { overwritten.to_vec() } else { vector.to_vec() };
+        assert_eq!(reader.item_vector(&rtxn, *id).unwrap().unwrap(), expected);
+    }
+
+    // nothing is written when one of the vectors is invalid
+    let mut wtxn = bulk.env.write_txn().unwrap();
+    let err = writer.add_items(&mut wtxn, [(1000, [0.0; DIM].as_slice()), (1001, &[0.0])]);
+    assert!(err.is_err());
+    assert!(!writer.contains_item(&wtxn, 1000).unwrap());
+}
+
+#[test]
+fn reject_non_finite_vectors() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let mut writer = Writer::new(handle.database, 0, 3);
+
+    let err = writer.add_item(&mut wtxn, 0, &[0.0, f32::NAN, 1.0]).unwrap_err();
+    insta::assert_snapshot!(err, @"Item 0 contains the non-finite value NaN at position 1");
+    let err = writer.add_items(&mut wtxn, [(1, [f32::NEG_INFINITY, 0.0, 0.0].as_slice())]);
+    insta::assert_snapshot!(err.unwrap_err(), @"Item 1 contains the non-finite value -inf at position 0");
+    assert!(writer.is_empty(&wtxn).unwrap());
+
+    writer.set_allow_non_finite(true);
+    writer.add_item(&mut wtxn, 0, &[0.0, f32::NAN, 1.0]).unwrap();
+    assert!(writer.contains_item(&wtxn, 0).unwrap());
+}
+
+#[test]
+fn reject_invalid_sparse_vectors() {
+    let handle = create_database::<SparseCosine>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 100);
+
+    let err = writer.add_sparse_item(&mut wtxn, 0, &[1, 5], &[1.0]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid sparse vector for item 0: 2 indices but 1 values");
+    let err = writer.add_sparse_item(&mut wtxn, 0, &[5, 5], &[1.0, 2.0]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid sparse vector for item 0: index 5 is not strictly greater than 5");
+    let err = writer.add_sparse_item(&mut wtxn, 0, &[5, 100], &[1.0, 2.0]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid sparse vector for item 0: index 100 is out of the 100 dimensions");
+    let err = writer.add_sparse_item(&mut wtxn, 0, &[5, 42], &[1.0, f32::NAN]).unwrap_err();
+    insta::assert_snapshot!(err, @"Item 0 contains the non-finite value NaN at position 42");
+    assert!(writer.is_empty(&wtxn).unwrap());
+
+    // the zeros are not stored
+    writer.add_sparse_item(&mut wtxn, 0, &[5, 42], &[0.0, 2.0]).unwrap();
+    let item = get_item(handle.database, 0, &wtxn, 0).unwrap().unwrap();
+    assert_eq!(item.vector.entries().collect::<Vec<_>>(), [(42, 2.0)]);
+}
+
+#[test]
+fn build_with_mismatching_dimensions() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 3);
+    writer.add_item(&mut wtxn, 0, &[0.0, 1.0, 2.0]).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    let err = writer.add_item(&mut wtxn, 1, &[0.0, 1.0]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid dimensions. Got 2 but expected 3");
+
+    // a writer opened with other dimensions can neither write to the index nor build it
+    let writer = Writer::new(handle.database, 0, 4);
+    let err = writer.add_item(&mut wtxn, 1, &[0.0, 1.0, 2.0, 3.0]).unwrap_err();
+    insta::assert_snapshot!(err, @"Invalid dimensions. Got 4 but expected 3");
+    let err = writer.add_items(&mut wtxn, [(1, &[0.0, 1.0, 2.0, 3.0][..])]).unwrap_err();
+    assert!(matches!(err, Error::InvalidDimensions { expected: 3, got: 4 }));
+    let err = writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap_err();
+    assert!(matches!(err, Error::InvalidDimensions { expected: 3, got: 4 }));
+    assert!(!writer.contains_item(&wtxn, 1).unwrap());
+}
+
+#[test]
+fn del_item_counting_edges() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..5 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    // the edges pointing to the item, not the ones going out of it
+    let expected = (0..5)
+        .filter(|&i| i != 2)
+        .flat_map(|i| (0..=u8::MAX as usize).map(move |layer| (i, layer)))
+        .filter_map(|(i, layer)| get_links(&wtxn, handle.database, 0, i, layer).unwrap())
+        .filter(|links| links.links.contains(2))
+        .count() as u64;
+    assert!(expected > 0);
+    assert_eq!(writer.del_item_counting_edges(&mut wtxn, 2).unwrap(), Some(expected));
+    assert_eq!(writer.del_item_counting_edges(&mut wtxn, 2).unwrap(), None);
+
+    // an item that was never built has no edges
+    writer.add_item(&mut wtxn, 10, &[10.0, 0.0]).unwrap();
+    assert_eq!(writer.del_item_counting_edges(&mut wtxn, 10).unwrap(), Some(0));
+}
+
+#[test]
+fn del_item_counting_edges_of_pruned_links() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    for i in 0..200 {
+        let vector: Vec<f32> = (0..4).map(|_| rng.gen()).collect();
+        writer.add_item(&mut wtxn, i, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    let links_of = |wtxn: &heed::RwTxn, i: ItemId| {
+        (0..=u8::MAX as usize)
+            .filter_map(|layer| get_links(wtxn, handle.database, 0, i, layer).unwrap())
+            .map(|links| links.links.into_owned())
+            .collect::<Vec<_>>()
+    };
+    let all_links: Vec<_> = (0..200).map(|i| links_of(&wtxn, i)).collect();
+    let incoming = |item: ItemId| {
+        (0..200)
+            .filter(|&i| i != item)
+            .flat_map(|i| &all_links[i as usize])
+            .filter(|links| links.contains(item))
+            .count() as u64
+    };
+    // the neighbours of the item on each of its layers that link back to it on that layer
+    let reciprocal = |item: ItemId| {
+        all_links[item as usize]
+            .iter()
+            .enumerate()
+            .flat_map(|(layer, links)| links.iter().map(move |n| (n, layer)))
+            .filter(|&(n, layer)| n != item && all_links[n as usize][layer].contains(item))
+            .count() as u64
+    };
+
+    // the pruned neighbours keep linking to an item that doesn't link back to them, those
+    // edges aren't counted
+    assert!((0..200).any(|i| incoming(i) > reciprocal(i)));
+    for item in 0..200 {
+        let edges = writer.del_item_counting_edges(&mut wtxn, item).unwrap();
+        assert_eq!(edges, Some(reciprocal(item)));
+    }
+}
+
+#[test]
+fn del_item_counting_no_edges_with_soft_deletes() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let mut writer = Writer::new(handle.database, 0, 2);
+    for i in 0..5 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    // the tombstoned item stays in the graph until the index is vacuumed
+    writer.set_soft_deletes(true);
+    assert_eq!(writer.del_item_counting_edges(&mut wtxn, 2).unwrap(), Some(0));
+    assert_eq!(writer.del_item_counting_edges(&mut wtxn, 2).unwrap(), None);
+    assert!(get_links(&wtxn, handle.database, 0, 2, 0).unwrap().is_some());
+}
+
+#[test]
+fn del_items_in_bulk() {
+    struct Deletions(Mutex<Vec<u64>>);
+
+    impl WriterHooks for Deletions {
+        fn on_delete_items(&self, _: u16, items: &RoaringBitmap) {
+            self.0.lock().unwrap().push(items.len());
+        }
+    }
+
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, 2);
+    let deletions = Arc::new(Deletions(Mutex::default()));
+    writer.set_hooks(deletions.clone());
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.set_payload(&mut wtxn, 3, b"purged").unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    writer.set_change_log(&mut wtxn, true).unwrap();
+    let purged: RoaringBitmap = (0..100).step_by(3).chain(1000..1010).collect();
+    assert_eq!(writer.del_items(&mut wtxn, &purged).unwrap(), 34);
+    assert_eq!(writer.del_items(&mut wtxn, &purged).unwrap(), 0);
+    assert_eq!(*deletions.0.lock().unwrap(), [34]);
+    assert_eq!(writer.payload(&wtxn, 3).unwrap(), None);
+    let changes = writer.changes(&wtxn, 0).unwrap();
+    assert_eq!(changes.iter().map(|change| change.item).collect::<Vec<_>>(), {
+        (0..100).step_by(3).collect::<Vec<_>>()
+    });
+    assert!(changes.iter().all(|change| change.kind == ChangeKind::Deleted));
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    let reader = Reader::open(&wtxn, 0, database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    let remaining: RoaringBitmap = (0..100).filter(|i| i % 3 != 0).collect();
+    assert_eq!(reader.item_ids(), &remaining);
+    let nns = reader.nns(3).by_vector(&wtxn, &[0.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(1, 1.0), (2, 4.0), (4, 16.0)]);
+}
+
+#[test]
+fn sharded_build_stitches_the_sub_graphs() {
+    const DIM: usize = 4;
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, DIM);
+    let vectors: Vec<[f32; DIM]> = (0..1000).map(|_| std::array::from_fn(|_| rng.gen())).collect();
+    for (id, vector) in vectors.iter().enumerate() {
+        writer.add_item(&mut wtxn, id as u32, vector).unwrap();
+    }
+    writer.builder(&mut rng).shards(4).build::<8, 16>(&mut wtxn).unwrap();
+
+    let reader = Reader::open(&wtxn, 0, database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    let mut found = 0;
+    for query in vectors.iter().step_by(50) {
+        let mut exact: Vec<_> = (0..vectors.len() as u32)
+            .map(|id| {
+                let vector = &vectors[id as usize];
+                (query.iter().zip(vector).map(|(a, b)| (a - b) * (a - b)).sum::<f32>(), id)
+            })
+            .collect();
+        exact.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let exact: RoaringBitmap = exact.iter().take(10).map(|&(_, id)| id).collect();
+        let nns = reader.nns(10).ef_search(64).by_vector(&wtxn, query).unwrap().into_nns();
+        found += nns.iter().filter(|(id, _)| exact.contains(*id)).count();
+    }
+    assert!(found >= 190, "recall: {found}/200");
+}
+
+#[test]
+fn rebuild_in_chunks_swaps_the_graph() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    writer.set_payload(&mut wtxn, 3, b"three").unwrap();
+
+    // the pending updates are part of the rebuilt graph
+    writer.del_item(&mut wtxn, 0).unwrap();
+    writer.add_item(&mut wtxn, 100, &[-0.5, 0.0]).unwrap();
+    wtxn.commit().unwrap();
+    writer.builder(&mut rng).rebuild_in_chunks::<M, M0, _>(&env, 30).unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    assert!(!writer.need_build(&rtxn).unwrap());
+    assert_eq!(writer.unfinished_ingest(&rtxn).unwrap(), None);
+    let keys = database.remap_data_type::<DecodeIgnore>().iter(&rtxn).unwrap();
+    assert!(keys.map(|entry| entry.unwrap().0.index).all(|index| index == 0));
+    let reader = Reader::open(&rtxn, 0, database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(1..=100).collect::<RoaringBitmap>());
+    assert_eq!(reader.payload(&rtxn, 3).unwrap(), Some(&b"three"[..]));
+    let nns = reader.nns(3).by_vector(&rtxn, &[0.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(100, 0.25), (1, 1.0), (2, 4.0)]);
+}
+
+#[test]
+fn resume_or_roll_back_an_interrupted_rebuild() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let writer = Writer::new(database, 0, 2);
+    let mut wtxn = env.write_txn().unwrap();
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    writer.add_item(&mut wtxn, 100, &[-0.5, 0.0]).unwrap();
+    wtxn.commit().unwrap();
+
+    // the rebuild is interrupted after its first two chunks
+    let steps = Steps::default();
+    let written = steps.clone();
+    let chunks = move || {
+        let steps = written.0.lock().unwrap();
+        steps.iter().filter(|step| step.name() == "write the metadata").count()
+    };
+    let mut builder = writer.builder(&mut rng).progress(steps);
+    let err = builder.cancel(move || chunks() == 2).rebuild_in_chunks::<M, M0, _>(&env, 30);
+    assert!(matches!(err, Err(Error::BuildCancelled)), "{err:?}");
+    drop(builder);
+    let rtxn = env.read_txn().unwrap();
+    let manifest = writer.unfinished_ingest(&rtxn).unwrap().unwrap();
+    assert_eq!((manifest.kind, manifest.committed), (IngestKind::Rebuild, 2));
+    assert_eq!(manifest.pending, RoaringBitmap::from_iter([100]));
+    // the vectors are read from the index, the new graph only holds the links so far
+    let scratch = manifest.scratch.unwrap();
+    let keys = |prefix| {
+        let keys = database.remap_types::<PrefixCodec, DecodeIgnore>();
+        keys.prefix_iter(&rtxn, &prefix).unwrap().remap_key_type::<KeyCodec>().count()
+    };
+    assert_eq!(keys(Prefix::item(scratch)), 0);
+    assert!(keys(Prefix::links(scratch)) > 0);
+    // the index keeps its previous graph in the meantime
+    let reader = Reader::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.item_ids(), &(0..100).collect::<RoaringBitmap>());
+    drop(rtxn);
+
+    // a rollback would drop the new graph and leave the changes it took over pending
+    let mut wtxn = env.write_txn().unwrap();
+    writer.roll_back_ingest(&mut wtxn).unwrap();
+    assert_eq!(writer.pending_changes(&wtxn).unwrap(), 1);
+    let keys = database.remap_data_type::<DecodeIgnore>().iter(&wtxn).unwrap();
+    assert!(keys.map(|entry| entry.unwrap().0.index).all(|index| index == 0));
+    drop(wtxn);
+
+    // or the rebuild resumes with the remaining items
+    writer.builder(&mut rng).rebuild_in_chunks::<M, M0, _>(&env, 30).unwrap();
+    let rtxn = env.read_txn().unwrap();
+    assert_eq!(writer.unfinished_ingest(&rtxn).unwrap(), None);
+    let reader = Reader::open(&rtxn, 0, database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(0..=100).collect::<RoaringBitmap>());
+    let nns = reader.nns(2).by_vector(&rtxn, &[0.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(0, 0.0), (100, 0.25)]);
+}
+
+#[test]
+fn build_once_enough_changes_are_pending() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    let threshold = BuildThreshold { pending: 5, fraction: 0.5 };
+
+    // an index that was never built is built right away
+    writer.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
+    assert!(writer.builder(&mut rng).build_if_pending::<M, M0>(&mut wtxn, threshold).unwrap());
+    assert!(!writer.builder(&mut rng).build_if_pending::<M, M0>(&mut wtxn, threshold).unwrap());
+
+    for i in 1..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    assert!(writer.builder(&mut rng).build_if_pending::<M, M0>(&mut wtxn, threshold).unwrap());
+
+    // 4 changes are less than 5 and than half the 20 items
+    for i in 20..23 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.del_item(&mut wtxn, 0).unwrap();
+    assert_eq!(writer.pending_changes(&wtxn).unwrap(), 4);
+    assert!(!writer.builder(&mut rng).build_if_pending::<M, M0>(&mut wtxn, threshold).unwrap());
+    assert!(writer.need_build(&wtxn).unwrap());
+
+    writer.add_item(&mut wtxn, 23, &[23.0, 0.0]).unwrap();
+    assert!(writer.builder(&mut rng).build_if_pending::<M, M0>(&mut wtxn, threshold).unwrap());
+    assert_eq!(writer.pending_changes(&wtxn).unwrap(), 0);
+    let reader = Reader::open(&wtxn, 0, database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(1..24).collect::<RoaringBitmap>());
+}
+
+#[test]
+fn hooks_follow_the_writes_and_builds() {
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl WriterHooks for Recorder {
+        fn on_insert(&self, index: u16, item: ItemId) {
+            self.0.lock().unwrap().push(format!("insert {index}/{item}"));
+        }
+
+        fn on_delete(&self, index: u16, item: ItemId) {
+            self.0.lock().unwrap().push(format!("delete {index}/{item}"));
+        }
+
+        fn on_build_start(&self, index: u16, inserted: &RoaringBitmap, deleted: &RoaringBitmap) {
+            let (inserted, deleted) = (inserted.len(), deleted.len());
+            self.0.lock().unwrap().push(format!("build {index} +{inserted} -{deleted}"));
+        }
+
+        fn on_build_end(&self, index: u16, _: &RoaringBitmap, _: &RoaringBitmap) {
+            self.0.lock().unwrap().push(format!("built {index}"));
+        }
+
+        fn on_entry_points_change(&self, index: u16, old: &[ItemId], new: &[ItemId]) {
+            self.0.lock().unwrap().push(format!("entry points {index} {old:?} -> {new:?}"));
+        }
+    }
+
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let recorder = Arc::new(Recorder::default());
+    let mut writer = Writer::new(database, 1, 2);
+    writer.set_hooks(recorder.clone());
+
+    writer.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
+    writer.add_item(&mut wtxn, 1, &[1.0, 0.0]).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    // deleting a missing item doesn't call the hook
+    assert!(!writer.del_item(&mut wtxn, 7).unwrap());
+    writer.del_item(&mut wtxn, 1).unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    let events = recorder.0.lock().unwrap();
+    let entry_points = events.iter().filter(|event| event.starts_with("entry points 1 [] ->"));
+    assert_eq!(entry_points.count(), 1, "{events:?}");
+    let events: Vec<_> = events.iter().filter(|event| !event.starts_with("entry points")).collect();
+    assert_eq!(
+        events,
+        [
+            "insert 1/0",
+            "insert 1/1",
+            "build 1 +2 -0",
+            "built 1",
+            "delete 1/1",
+            "build 1 +0 -1",
+            "built 1"
+        ]
+    );
+}
+
+#[test]
+fn locked_index_rejects_other_writers() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut owner = Writer::new(database, 0, 2);
+    let mut other = Writer::new(database, 0, 2);
+    owner.lock(&mut wtxn).unwrap();
+    owner.lock(&mut wtxn).unwrap();
+
+    owner.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
+    let locked = other.add_item(&mut wtxn, 1, &[1.0, 0.0]).unwrap_err();
+    assert!(matches!(locked, Error::IndexLocked(0)), "{locked:?}");
+    assert!(matches!(other.del_item(&mut wtxn, 0), Err(Error::IndexLocked(0))));
+    let locked = other.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap_err();
+    assert!(matches!(locked, Error::IndexLocked(0)), "{locked:?}");
+    assert!(matches!(other.lock(&mut wtxn), Err(Error::IndexLocked(0))));
+    owner.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    // every change of the index is rejected, not only the items and the builds
+    assert!(matches!(other.set_payload(&mut wtxn, 0, b"x"), Err(Error::IndexLocked(0))));
+    assert!(matches!(other.clear_boost(&mut wtxn, 0), Err(Error::IndexLocked(0))));
+    assert!(matches!(other.reorder_into(&mut wtxn, 2), Err(Error::IndexLocked(0))));
+    assert!(matches!(other.migrate(&mut wtxn, 2, 3), Err(Error::IndexLocked(0))));
+    let locked = other.builder(&mut rng).vacuum::<M, M0>(&mut wtxn).unwrap_err();
+    assert!(matches!(locked, Error::IndexLocked(0)), "{locked:?}");
+    let locked = crate::swap_indexes(&mut wtxn, database, 1, 0).unwrap_err();
+    assert!(matches!(locked, Error::IndexLocked(0)), "{locked:?}");
+
+    // the other indexes aren't locked
+    Writer::new(database, 1, 2).add_item(&mut wtxn, 1, &[1.0, 0.0]).unwrap();
+
+    // only the lock of a writer that went away can be broken
+    assert!(matches!(other.unlock(&mut wtxn), Err(Error::IndexLocked(0))));
+    drop(owner);
+    assert!(other.break_lock(&mut wtxn).unwrap());
+    assert!(!other.unlock(&mut wtxn).unwrap());
+    other.add_item(&mut wtxn, 1, &[1.0, 0.0]).unwrap();
+}
+
+#[test]
+fn migrate_to_other_dimensions() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.set_payload(&mut wtxn, 4, b"four").unwrap();
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    assert!(matches!(writer.migrate(&mut wtxn, 0, 3), Err(Error::MigrateInPlace(0))));
+    let migration = writer.migrate(&mut wtxn, 1, 3).unwrap();
+    assert_eq!(migration.missing(&wtxn).unwrap(), (0..10).collect::<RoaringBitmap>());
+    for i in 0..9 {
+        migration.writer().add_item(&mut wtxn, i, &[0.0, i as f32, 1.0]).unwrap();
+    }
+    assert_eq!(migration.missing(&wtxn).unwrap(), RoaringBitmap::from_iter([9]));
+    migration.writer().add_item(&mut wtxn, 9, &[0.0, 9.0, 1.0]).unwrap();
+
+    // the successor must be built before being swapped with the old index
+    let migration = match migration.finish(&mut wtxn) {
+        Err(Error::NeedBuild(1)) => writer.migrate(&mut wtxn, 1, 3).unwrap(),
+        result => panic!("{:?}", result.map(|_| ())),
+    };
+    assert!(matches!(migration.finish(&mut wtxn), Err(Error::MigrationIncomplete { missing: 10 })));
+    let migration = writer.migrate(&mut wtxn, 1, 3).unwrap();
+    for i in 0..10 {
+        migration.writer().add_item(&mut wtxn, i, &[0.0, i as f32, 1.0]).unwrap();
+    }
+    migration.writer().builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let writer = migration.finish(&mut wtxn).unwrap();
+
+    assert_eq!((writer.index(), writer.dimensions()), (0, 3));
+    let reader = Reader::open(&wtxn, 0, database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    assert_eq!(reader.dimensions(), 3);
+    assert_eq!(reader.payload(&wtxn, 4).unwrap(), Some(&b"four"[..]));
+    let nns = reader.nns(2).by_vector(&wtxn, &[0.0, 4.0, 1.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(4, 0.0), (3, 1.0)]);
+    // the old vectors moved to the successor index
+    assert_eq!(Reader::open(&wtxn, 1, database).unwrap().dimensions(), 2);
+}
+
+#[test]
+fn relink_the_regions_of_the_changed_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..200 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    // the items of a region move away and one of them is deleted
+    for i in 50..60 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 100.0]).unwrap();
+    }
+    writer.del_item(&mut wtxn, 60).unwrap();
+    writer.builder(&mut rng).relink_regions(true).build::<M, M0>(&mut wtxn).unwrap();
+    assert!(!writer.need_build(&wtxn).unwrap());
+    let reader = Reader::open(&wtxn, 0, database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+
+    // the neighbours on both sides of the emptied region are linked together
+    let links = |i| get_links(&wtxn, database, 0, i, 0).unwrap().unwrap().links.into_owned();
+    assert!(links(49).contains(61), "{:?}", links(49));
+    assert!(links(61).contains(49), "{:?}", links(61));
+}
+
+#[test]
+fn ingest_in_several_transactions() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let writer = Writer::new(database, 0, 2);
+    let items = (0..100).map(|i| (i, vec![i as f32, 0.0]));
+
+    // the ingestion stops after the first half of the items
+    let added = writer
+        .builder(&mut rng)
+        .ingest_in_transactions::<M, M0, _>(&env, items.clone().take(50), 30)
+        .unwrap();
+    assert_eq!(added, 50);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::open(&rtxn, 0, database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(0..50).collect::<RoaringBitmap>());
+    drop(rtxn);
+
+    // the stored items are skipped when resuming
+    let added =
+        writer.builder(&mut rng).ingest_in_transactions::<M, M0, _>(&env, items, 30).unwrap();
+    assert_eq!(added, 50);
+    let rtxn = env.read_txn().unwrap();
+    assert!(!writer.need_build(&rtxn).unwrap());
+    let reader = Reader::open(&rtxn, 0, database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(0..100).collect::<RoaringBitmap>());
+    let nns = reader.nns(2).by_vector(&rtxn, &[99.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(99, 0.0), (98, 1.0)]);
+}
+
+#[test]
+fn search_a_build_in_progress() {
+    struct Builds(Arc<AtomicU64>);
+
+    impl WriterHooks for Builds {
+        fn on_build_end(&self, _: u16, _: &RoaringBitmap, _: &RoaringBitmap) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut writer = Writer::new(database, 0, 2);
+    let builds = Arc::new(AtomicU64::new(0));
+    writer.set_hooks(Arc::new(Builds(builds.clone())));
+    let mut wtxn = env.write_txn().unwrap();
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    wtxn.commit().unwrap();
+
+    // the build is interrupted after its first two chunks
+    let err = writer
+        .builder(&mut rng)
+        .cancel(|| builds.load(Ordering::Relaxed) == 2)
+        .build_in_transactions::<M, M0, _>(&env, 30)
+        .unwrap_err();
+    assert!(matches!(err, Error::BuildCancelled), "{err:?}");
+    let rtxn = env.read_txn().unwrap();
+    assert!(matches!(Reader::open(&rtxn, 0, database), Err(Error::NeedBuild(0))));
+    let reader = Reader::open_partial(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.item_ids(), &(0..60).collect::<RoaringBitmap>());
+    let nns = reader.nns(2).by_vector(&rtxn, &[99.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(59, 1600.0), (58, 1681.0)]);
+    drop(rtxn);
+
+    // the build resumes with the remaining items
+    writer.builder(&mut rng).build_in_transactions::<M, M0, _>(&env, 30).unwrap();
+    assert_eq!(builds.load(Ordering::Relaxed), 4);
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::open(&rtxn, 0, database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(0..100).collect::<RoaringBitmap>());
+    let nns = reader.nns(2).by_vector(&rtxn, &[99.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(99, 0.0), (98, 1.0)]);
+}
+
+#[test]
+fn resume_or_roll_back_an_interrupted_ingest() {
+    struct Builds(Arc<AtomicU64>);
+
+    impl WriterHooks for Builds {
+        fn on_build_end(&self, _: u16, _: &RoaringBitmap, _: &RoaringBitmap) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut writer = Writer::new(database, 0, 2);
+    let builds = Arc::new(AtomicU64::new(0));
+    writer.set_hooks(Arc::new(Builds(builds.clone())));
+    let items = |range: std::ops::Range<u32>| range.map(|i| (i, vec![i as f32, 0.0]));
+
+    // the ingestion is interrupted after its first two chunks
+    let err = writer
+        .builder(&mut rng)
+        .cancel(|| builds.load(Ordering::Relaxed) == 2)
+        .ingest_in_transactions::<M, M0, _>(&env, items(0..100), 30)
+        .unwrap_err();
+    assert!(matches!(err, Error::BuildCancelled), "{err:?}");
+    let rtxn = env.read_txn().unwrap();
+    let manifest = writer.unfinished_ingest(&rtxn).unwrap().unwrap();
+    assert_eq!((manifest.kind, manifest.committed), (IngestKind::Ingest, 2));
+    assert_eq!(manifest.added, (0..60).collect::<RoaringBitmap>());
+    assert!(manifest.started_at <= SystemTime::now());
+    drop(rtxn);
+
+    // another operation can't start before it is resumed
+    let err = writer.builder(&mut rng).build_in_transactions::<M, M0, _>(&env, 30).unwrap_err();
+    assert!(matches!(err, Error::UnfinishedIngest { index: 0, kind: IngestKind::Ingest }));
+    let added = writer
+        .builder(&mut rng)
+        .ingest_in_transactions::<M, M0, _>(&env, items(0..100), 30)
+        .unwrap();
+    assert_eq!(added, 40);
+    let rtxn = env.read_txn().unwrap();
+    assert_eq!(writer.unfinished_ingest(&rtxn).unwrap(), None);
+    assert_eq!(Reader::open(&rtxn, 0, database).unwrap().n_items(), 100);
+    drop(rtxn);
+
+    // the items of an interrupted ingestion are deleted by its rollback
+    builds.store(0, Ordering::Relaxed);
+    writer
+        .builder(&mut rng)
+        .cancel(|| builds.load(Ordering::Relaxed) == 1)
+        .ingest_in_transactions::<M, M0, _>(&env, items(100..200), 30)
+        .unwrap_err();
+    let mut wtxn = env.write_txn().unwrap();
+    let manifest = writer.roll_back_ingest(&mut wtxn).unwrap().unwrap();
+    assert_eq!(manifest.added, (100..130).collect::<RoaringBitmap>());
+    assert_eq!(writer.roll_back_ingest(&mut wtxn).unwrap(), None);
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::open(&wtxn, 0, database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(0..100).collect::<RoaringBitmap>());
+}
+
+#[test]
+fn publish_the_builds_atomically() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let writer = Writer::new(database, 0, 2);
+    let mut wtxn = env.write_txn().unwrap();
+    assert_eq!(writer.build_id(&wtxn).unwrap(), None);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let first = writer.build_id(&wtxn).unwrap().unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let serving = Reader::open(&rtxn, 0, database).unwrap();
+    assert_eq!(serving.build_id(), first);
+
+    // a build is only seen by the readers once it is committed
+    let mut wtxn = env.write_txn().unwrap();
+    writer.add_item(&mut wtxn, 10, &[10.0, 0.0]).unwrap();
+    assert_eq!(writer.build_id(&wtxn).unwrap(), Some(first));
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let second = writer.build_id(&wtxn).unwrap().unwrap();
+    assert!(second > first, "{second} <= {first}");
+    assert_eq!(Reader::published_build_id(&rtxn, 0, database).unwrap(), Some(first));
+    wtxn.commit().unwrap();
+
+    // the serving reader keeps its snapshot of the previous graph
+    assert_eq!(Reader::published_build_id(&rtxn, 0, database).unwrap(), Some(first));
+    assert_eq!(serving.item_ids(), &(0..10).collect::<RoaringBitmap>());
+    let nns = serving.nns(1).by_vector(&rtxn, &[10.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(9, 1.0)]);
+    drop(rtxn);
+
+    let rtxn = env.read_txn().unwrap();
+    assert_eq!(Reader::published_build_id(&rtxn, 0, database).unwrap(), Some(second));
+    let reader = Reader::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.build_id(), second);
+    let nns = reader.nns(1).by_vector(&rtxn, &[10.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(10, 0.0)]);
+}
+
+#[test]
+fn build_with_preset_from_dataset_stats() {
+    const DIM: usize = 16;
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+
+    // the vectors of the first index lie on a plane, those of the second fill the space
+    let flat = Writer::new(database, 0, DIM);
+    let full = Writer::new(database, 1, DIM);
+    for id in 0..2000 {
+        let (x, y): (f32, f32) = (rng.gen(), rng.gen());
+        let vector: [f32; DIM] = std::array::from_fn(|i| x * (i % 3) as f32 + y * (i % 5) as f32);
+        flat.add_item(&mut wtxn, id, &vector).unwrap();
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        full.add_item(&mut wtxn, id, &vector).unwrap();
+    }
+
+    let stats = flat.dataset_stats(&wtxn).unwrap();
+    assert_eq!((stats.items, stats.dimensions), (2000, DIM));
+    assert!((1.5..3.0).contains(&stats.intrinsic_dimension), "{stats:?}");
+    let stats = full.dataset_stats(&wtxn).unwrap();
+    assert!((8.0..=16.0).contains(&stats.intrinsic_dimension), "{stats:?}");
+
+    let params =
+        flat.builder(&mut rng).build_with_preset(&mut wtxn, BuildPreset::FastBuild).unwrap();
+    assert_eq!(params, BuildParams { m: 8, ef_construction: 64 });
+    let params =
+        full.builder(&mut rng).build_with_preset(&mut wtxn, BuildPreset::Balanced).unwrap();
+    assert_eq!(params, BuildParams { m: 12, ef_construction: 100 });
+    Reader::open(&wtxn, 0, database).unwrap().assert_validity(&wtxn).unwrap();
+    Reader::open(&wtxn, 1, database).unwrap().assert_validity(&wtxn).unwrap();
+
+    // small datasets are capped to 16 links, large ones get a longer build queue
+    let hard = DatasetStats { items: 5000, dimensions: 768, intrinsic_dimension: 100.0 };
+    assert_eq!(BuildPreset::HighRecall.params(&hard), BuildParams { m: 16, ef_construction: 400 });
+    let large = DatasetStats { items: 5_000_000, ..hard };
+    assert_eq!(BuildPreset::Balanced.params(&large), BuildParams { m: 32, ef_construction: 200 });
+    assert_eq!(BuildPreset::HighRecall.params(&large).m, 48);
+}
+
+#[test]
+fn estimate_build_from_a_sample() {
+    const DIM: usize = 16;
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+
+    let writer = Writer::new(database, 0, DIM);
+    for id in 0..1000 {
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        writer.add_item(&mut wtxn, id, &vector).unwrap();
+    }
+
+    let estimate = writer.builder(&mut rng).estimate::<16, 32>(&mut wtxn, 200).unwrap();
+    assert_eq!((estimate.sampled_items, estimate.items), (200, 1000));
+    assert!(estimate.duration > estimate.sample_duration * 5, "{estimate:?}");
+    assert!(estimate.write_amplification > 1.0, "{estimate:?}");
+    let vectors = 1000 * (8 + 1 + DIM * 4) as u64;
+    assert!(estimate.written_bytes > vectors, "{estimate:?}");
+
+    // the sample was built in another index that is cleared
+    let keys = database.remap_data_type::<DecodeIgnore>().iter(&wtxn).unwrap();
+    assert!(keys.map(|entry| entry.unwrap().0.index).all(|index| index == 0));
+    assert!(writer.need_build(&wtxn).unwrap());
+}
+
+#[test]
+fn sweep_expired_items_in_batches() {
+    struct Builds(AtomicU64);
+
+    impl WriterHooks for Builds {
+        fn on_build_start(&self, _: u16, _: &RoaringBitmap, _: &RoaringBitmap) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    const DIM: usize = 4;
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, DIM);
+    let builds = Arc::new(Builds(AtomicU64::new(0)));
+    writer.set_hooks(builds.clone());
+    let now = SystemTime::now();
+    for id in 0..100 {
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        writer.add_item(&mut wtxn, id, &vector).unwrap();
+        if id < 40 {
+            let offset = Duration::from_secs(60);
+            let at = if id < 30 { now - offset } else { now + offset };
+            writer.set_expiration(&mut wtxn, id, at).unwrap();
+        }
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    assert_eq!(writer.expired_items(&wtxn, now).unwrap(), (0..30).collect());
+
+    let mut sweeps = Vec::new();
+    loop {
+        let swept = writer.builder(&mut rng).sweep_expired::<M, M0>(&mut wtxn, now, 20).unwrap();
+        sweeps.push(swept);
+        if swept == 0 {
+            break;
+        }
+    }
+    assert_eq!(sweeps, [20, 10, 0]);
+    assert_eq!(builds.0.load(Ordering::Relaxed), 3);
+    assert!(!writer.need_build(&wtxn).unwrap());
+    assert!(!writer.contains_item(&wtxn, 29).unwrap());
+    assert!(writer.expiration(&wtxn, 30).unwrap().is_some_and(|at| at > now));
+
+    // the expirations of the items that aren't in the index are dropped without a build
+    writer.set_expiration(&mut wtxn, 29, now).unwrap();
+    writer.set_expiration(&mut wtxn, 500, now).unwrap();
+    assert_eq!(writer.builder(&mut rng).sweep_expired::<M, M0>(&mut wtxn, now, 20).unwrap(), 0);
+    assert_eq!(builds.0.load(Ordering::Relaxed), 3);
+    assert!(writer.expired_items(&wtxn, now).unwrap().is_empty());
+
+    // deleting an item drops its expiration
+    writer.del_item(&mut wtxn, 30).unwrap();
+    assert_eq!(writer.expiration(&wtxn, 30).unwrap(), None);
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.item_ids(), &(31..100).collect());
+    let nns = reader.nns(100).ef_search(100).by_vector(&rtxn, &[0.5; DIM]).unwrap().into_nns();
+    assert_eq!(nns.len(), 69);
+}
+
+#[test]
+fn sweep_expired_items_out_of_the_graph_with_soft_deletes() {
+    const DIM: usize = 4;
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, DIM);
+    let now = SystemTime::now();
+    for id in 0..100 {
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        writer.add_item(&mut wtxn, id, &vector).unwrap();
+        if id < 30 {
+            writer.set_expiration(&mut wtxn, id, now - Duration::from_secs(60)).unwrap();
+        }
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    writer.set_soft_deletes(true);
+    assert_eq!(writer.builder(&mut rng).sweep_expired::<M, M0>(&mut wtxn, now, 100).unwrap(), 30);
+    assert!(writer.tombstones(&wtxn).unwrap().is_empty());
+
+    let links = database
+        .remap_key_type::<PrefixCodec>()
+        .prefix_iter(&wtxn, &Prefix::links(0))
+        .unwrap()
+        .remap_key_type::<KeyCodec>();
+    for result in links {
+        let (key, node) = result.unwrap();
+        let Node::Links(Links { links }) = node else { panic!("{key:?} is not a links") };
+        assert!(key.node.item >= 30, "{key:?}");
+        assert!(links.min().is_none_or(|min| min >= 30), "{key:?} links {links:?}");
+    }
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(30..100).collect());
+}
+
+#[test]
+fn soft_deletes_until_vacuum() {
+    const DIM: usize = 4;
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, DIM);
+    for id in 0..100 {
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        writer.add_item(&mut wtxn, id, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    writer.set_soft_deletes(true);
+    for id in 0..30 {
+        assert!(writer.del_item(&mut wtxn, id).unwrap());
+    }
+    assert!(!writer.del_item(&mut wtxn, 0).unwrap());
+    assert!(!writer.contains_item(&wtxn, 0).unwrap());
+    assert_eq!(writer.tombstones(&wtxn).unwrap(), (0..30).collect());
+    // an item added again is not tombstoned anymore, even by a writer without soft deletes
+    let other = Writer::new(database, 0, DIM);
+    other.add_item(&mut wtxn, 29, &[0.5; DIM]).unwrap();
+    assert_eq!(writer.tombstones(&wtxn).unwrap(), (0..29).collect());
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+
+    // the deletions are visible without repairing the graph
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.item_ids(), &(29..100).collect());
+    assert_eq!(reader.item_vector(&wtxn, 3).unwrap(), None);
+    let nns = reader.nns(100).ef_search(100).by_vector(&wtxn, &[0.5; DIM]).unwrap().into_nns();
+    assert_eq!(nns.len(), 71);
+    assert!(nns.iter().all(|&(id, _)| id >= 29));
+    let by_item = reader.nns(100).ef_search(100).by_item(&wtxn, 50).unwrap().unwrap();
+    assert!(by_item.into_nns().iter().all(|&(id, _)| id >= 29));
+    assert!(reader.nns(10).by_item(&wtxn, 3).unwrap().is_none());
+
+    assert_eq!(writer.builder(&mut rng).vacuum::<M, M0>(&mut wtxn).unwrap(), 29);
+    assert_eq!(writer.builder(&mut rng).vacuum::<M, M0>(&mut wtxn).unwrap(), 0);
+    wtxn.commit().unwrap();
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(29..100).collect());
+    assert!(writer.tombstones(&rtxn).unwrap().is_empty());
+}
+
+#[test]
+fn reordered_copy_keeps_the_external_ids() {
+    const DIM: usize = 4;
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, DIM);
+    for id in 0..100 {
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        writer.add_item(&mut wtxn, id * 3 + 7, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    writer.set_soft_deletes(true);
+    assert!(writer.del_item(&mut wtxn, 10).unwrap());
+    let expiration = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+    writer.set_expiration(&mut wtxn, 13, expiration).unwrap();
+
+    assert!(matches!(writer.reorder_into(&mut wtxn, 0), Err(crate::Error::ReorderInPlace(0))));
+    writer.reorder_into(&mut wtxn, 1).unwrap();
+    let source = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let reordered = Reader::<Euclidean>::open(&wtxn, 1, database).unwrap();
+    reordered.assert_validity(&wtxn).unwrap();
+
+    // the graph is stored under the internal ids, from the entry points
+    assert_eq!(reordered.item_ids(), source.item_ids());
+    assert!(source.item_ids().iter().any(|id| id >= 100));
+    let first = reordered.external_id(&wtxn, 0).unwrap();
+    assert!(source.item_ids().contains(first));
+    assert!(!reordered.contains_item(&wtxn, 10).unwrap());
+    for id in source.item_ids() {
+        assert!(reordered.contains_item(&wtxn, id).unwrap());
+        assert_eq!(
+            reordered.item_vector(&wtxn, id).unwrap(),
+            source.item_vector(&wtxn, id).unwrap()
+        );
+    }
+
+    let query = [0.5; DIM];
+    let expected = source.nns(10).ef_search(100).by_vector(&wtxn, &query).unwrap().into_nns();
+    let found = reordered.nns(10).ef_search(100).by_vector(&wtxn, &query).unwrap().into_nns();
+    assert_eq!(found, expected);
+    let by_item = reordered.nns(5).ef_search(100).by_item(&wtxn, 16).unwrap().unwrap();
+    let expected = source.nns(5).ef_search(100).by_item(&wtxn, 16).unwrap().unwrap();
+    assert_eq!(by_item.into_nns(), expected.into_nns());
+    let candidates: RoaringBitmap = source.item_ids().iter().step_by(2).collect();
+    let filtered =
+        reordered.nns(10).candidates(&candidates).by_vector(&wtxn, &query).unwrap().into_nns();
+    assert!(!filtered.is_empty());
+    assert!(filtered.iter().all(|(id, _)| candidates.contains(*id)));
+
+    // the items are iterated with their external ids and keep their expirations
+    let mut iterated: Vec<_> = reordered.iter(&wtxn).unwrap().map(Result::unwrap).collect();
+    iterated.sort_by_key(|&(id, _)| id);
+    let expected: Vec<_> = source.iter(&wtxn).unwrap().map(Result::unwrap).collect();
+    assert_eq!(iterated, expected);
+    let ranged: RoaringBitmap =
+        reordered.iter_range(&wtxn, 7..20).unwrap().map(|res| res.unwrap().0).collect();
+    assert_eq!(ranged, RoaringBitmap::from_iter([7, 10, 13, 16, 19]));
+    let expired = Writer::<Euclidean>::new(database, 1, DIM).expired_items(&wtxn, expiration);
+    assert_eq!(expired.unwrap().len(), 1);
+}
+
+#[test]
+fn change_log_follows_the_mutations() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    writer.add_item(&mut wtxn, 9, &[0.0, 0.0]).unwrap();
+    assert!(writer.changes(&wtxn, 0).unwrap().is_empty());
+
+    writer.set_change_log(&mut wtxn, true).unwrap();
+    writer.add_item(&mut wtxn, 0, &[0.0, 1.0]).unwrap();
+    writer.add_items(&mut wtxn, [(0, [1.0, 1.0].as_slice()), (1, [2.0, 1.0].as_slice())]).unwrap();
+    writer.del_item(&mut wtxn, 9).unwrap();
+    assert!(!writer.del_item(&mut wtxn, 42).unwrap());
+
+    let changes: Vec<_> = writer
+        .changes(&wtxn, 0)
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.sequence, c.item, c.kind))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (0, 0, ChangeKind::Added),
+            (1, 0, ChangeKind::Updated),
+            (2, 1, ChangeKind::Added),
+            (3, 9, ChangeKind::Deleted)
+        ]
+    );
+    assert_eq!(writer.changes(&wtxn, 3).unwrap().len(), 1);
+
+    // the sequence keeps increasing once the consumed changes are dropped
+    assert_eq!(writer.truncate_changes(&mut wtxn, 4).unwrap(), 4);
+    writer.del_item(&mut wtxn, 1).unwrap();
+    let changes = writer.changes(&wtxn, 0).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!((changes[0].sequence, changes[0].kind), (4, ChangeKind::Deleted));
+
+    // the setting is stored in the index and followed by the other writers
+    let other = Writer::<Euclidean>::new(database, 0, 2);
+    assert!(other.change_log(&wtxn).unwrap());
+    other.add_item(&mut wtxn, 3, &[3.0, 1.0]).unwrap();
+    assert_eq!(writer.changes(&wtxn, 5).unwrap()[0].item, 3);
+    other.set_change_log(&mut wtxn, false).unwrap();
+    other.del_item(&mut wtxn, 3).unwrap();
+    assert_eq!(writer.changes(&wtxn, 6).unwrap(), []);
+    assert!(!writer.change_log(&wtxn).unwrap());
+
+    // the log doesn't get in the way of the build
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    assert_eq!(reader.item_ids(), &RoaringBitmap::from_iter([0]));
+}
+
+#[test]
+fn change_log_runs_out_of_sequence_numbers() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    writer.set_change_log(&mut wtxn, true).unwrap();
+    let key = Key::change_sequence(0);
+    crate::change_log::set_next_sequence(&mut wtxn, database, key, u32::MAX - 1).unwrap();
+
+    writer.add_item(&mut wtxn, 0, &[0.0, 1.0]).unwrap();
+    let items = [(1, [1.0, 1.0].as_slice()), (2, [2.0, 1.0].as_slice())];
+    let err = writer.add_items(&mut wtxn, items).unwrap_err();
+    insta::assert_snapshot!(err, @"The change log of index 0 ran out of sequence numbers");
+    let err = writer.del_item(&mut wtxn, 0).unwrap_err();
+    insta::assert_snapshot!(err, @"The change log of index 0 ran out of sequence numbers");
+}
+
+#[test]
+fn replica_catches_up_with_the_change_log() {
+    const DIM: usize = 4;
+    let leader_handle = create_database::<Euclidean>();
+    let replica_handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let leader = Writer::new(leader_handle.database, 0, DIM);
+    let replica = Writer::new(replica_handle.database, 0, DIM);
+
+    let mut wtxn = leader_handle.env.write_txn().unwrap();
+    leader.set_change_log(&mut wtxn, true).unwrap();
+    for id in 0..50 {
+        let vector: [f32; DIM] = std::array::from_fn(|_| rng.gen());
+        leader.add_item(&mut wtxn, id, &vector).unwrap();
+    }
+    wtxn.commit().unwrap();
+
+    let catch_up = |rng: &mut StdRng| {
+        let leader_rtxn = leader_handle.env.read_txn().unwrap();
+        let mut wtxn = replica_handle.env.write_txn().unwrap();
+        let applied = replica.apply_changes(&mut wtxn, &leader, &leader_rtxn);
+        if let Ok(applied) = applied {
+            if applied > 0 {
+                replica.builder(rng).build::<M, M0>(&mut wtxn).unwrap();
+            }
+        }
+        wtxn.commit().unwrap();
+        applied
+    };
+    assert_eq!(catch_up(&mut rng).unwrap(), 50);
+    assert_eq!(catch_up(&mut rng).unwrap(), 0);
+
+    let mut wtxn = leader_handle.env.write_txn().unwrap();
+    leader.add_item(&mut wtxn, 3, &[1.0; DIM]).unwrap();
+    leader.add_item(&mut wtxn, 4, &[2.0; DIM]).unwrap();
+    leader.del_item(&mut wtxn, 4).unwrap();
+    leader.add_item(&mut wtxn, 60, &[3.0; DIM]).unwrap();
+    wtxn.commit().unwrap();
+    assert_eq!(catch_up(&mut rng).unwrap(), 4);
+
+    let rtxn = replica_handle.env.read_txn().unwrap();
+    assert_eq!(replica.next_change_to_apply(&rtxn).unwrap(), 54);
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, replica_handle.database).unwrap();
+    let mut expected: RoaringBitmap = (0..50).collect();
+    expected.remove(4);
+    expected.insert(60);
+    assert_eq!(reader.item_ids(), &expected);
+    assert_eq!(reader.item_vector(&rtxn, 3).unwrap().unwrap(), [1.0; DIM]);
+    drop(rtxn);
+
+    // the replica can't catch up once the changes it missed are dropped
+    let mut wtxn = leader_handle.env.write_txn().unwrap();
+    leader.del_item(&mut wtxn, 60).unwrap();
+    leader.truncate_changes(&mut wtxn, 55).unwrap();
+    wtxn.commit().unwrap();
+    insta::assert_snapshot!(catch_up(&mut rng).unwrap_err(), @"The changes from 54 were truncated from the log before being applied, the first one left is 55 and the replica must be rebuilt");
+}
+
+#[test]
+fn replica_copies_the_vectors_as_they_are_stored() {
+    let leader_handle = create_database::<Hellinger>();
+    let replica_handle = create_database::<Hellinger>();
+    let leader = Writer::new(leader_handle.database, 0, 2);
+    let replica = Writer::new(replica_handle.database, 0, 2);
+
+    let mut wtxn = leader_handle.env.write_txn().unwrap();
+    leader.set_change_log(&mut wtxn, true).unwrap();
+    leader.add_item(&mut wtxn, 0, &[1.0, 3.0]).unwrap();
+    wtxn.commit().unwrap();
+
+    // the square roots stored by the leader are not square-rooted again
+    let leader_rtxn = leader_handle.env.read_txn().unwrap();
+    let mut wtxn = replica_handle.env.write_txn().unwrap();
+    assert_eq!(replica.apply_changes(&mut wtxn, &leader, &leader_rtxn).unwrap(), 1);
+    assert_eq!(
+        replica.item_vector(&wtxn, 0).unwrap(),
+        leader.item_vector(&leader_rtxn, 0).unwrap()
+    );
+}
+
+#[test]
+fn swap_two_indexes() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let serving = Writer::new(database, 0, 2);
+    for i in 0..10 {
+        serving.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    serving.set_payload(&mut wtxn, 3, b"old").unwrap();
+    serving.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let shadow = Writer::new(database, 1, 3);
+    for i in 5..20 {
+        shadow.add_item(&mut wtxn, i, &[0.0, i as f32, 1.0]).unwrap();
+    }
+    shadow.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    // a reader opened before the swap keeps seeing the old index
+    let rtxn = env.read_txn().unwrap();
+    let mut wtxn = env.write_txn().unwrap();
+    crate::swap_indexes(&mut wtxn, database, 0, 1).unwrap();
+    crate::swap_indexes(&mut wtxn, database, 1, 1).unwrap();
+    wtxn.commit().unwrap();
+    assert_eq!(Reader::<Euclidean>::open(&rtxn, 0, database).unwrap().dimensions(), 2);
+    drop(rtxn);
+
+    let rtxn = env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.dimensions(), 3);
+    assert_eq!(reader.item_ids(), &(5..20).collect::<RoaringBitmap>());
+    assert_eq!(reader.payload(&rtxn, 3).unwrap(), None);
+    let nns = reader.nns(1).by_vector(&rtxn, &[0.0, 7.0, 1.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(7, 0.0)]);
+
+    let reader = Reader::<Euclidean>::open(&rtxn, 1, database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.item_ids(), &(0..10).collect::<RoaringBitmap>());
+    assert_eq!(reader.payload(&rtxn, 3).unwrap(), Some(&b"old"[..]));
+    // the scratch index used to swap them is left empty
+    assert_eq!(crate::migration::free_index(&rtxn, database).unwrap(), u16::MAX);
+}
+
+#[test]
+fn recalibrate_the_drift_baseline_once_the_vectors_drifted() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<BinaryQuantizedCosine>();
+    let mut rng = rng();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 64);
+    for i in 0..200 {
+        let vector: Vec<f32> = (0..64).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        writer.add_item(&mut wtxn, i, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<BinaryQuantizedCosine>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.quantizer_drift(&wtxn).unwrap(), None);
+
+    writer.recalibrate_drift_baseline(&mut wtxn).unwrap();
+    let reader = Reader::<BinaryQuantizedCosine>::open(&wtxn, 0, database).unwrap();
+    assert!(reader.quantizer_drift(&wtxn).unwrap().unwrap() < 1e-6);
+
+    // the new embedding model only outputs positive values
+    for i in 0..150 {
+        let vector: Vec<f32> = (0..64).map(|_| rng.gen_range(0.0..1.0)).collect();
+        writer.add_item(&mut wtxn, i, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<BinaryQuantizedCosine>::open(&wtxn, 0, database).unwrap();
+    let drift = reader.quantizer_drift(&wtxn).unwrap().unwrap();
+    assert!(drift > 0.5, "{drift}");
+
+    writer.recalibrate_drift_baseline(&mut wtxn).unwrap();
+    let reader = Reader::<BinaryQuantizedCosine>::open(&wtxn, 0, database).unwrap();
+    assert!(reader.quantizer_drift(&wtxn).unwrap().unwrap() < 1e-6);
+    reader.assert_validity(&wtxn).unwrap();
+}
+
+#[test]
+fn reserve_room_for_a_large_import() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut writer = Writer::new(database, 0, 768);
+    let size = env.info().map_size;
+
+    // safety: no transaction is alive
+    let reserved = unsafe { writer.reserve_map_size(&env, 100_000, M0) }.unwrap();
+    assert!(reserved > size);
+    assert_eq!(env.info().map_size, reserved);
+    let again = unsafe { writer.reserve_map_size(&env, 10, M0) }.unwrap();
+    assert_eq!(again, reserved);
+
+    writer.reserve(1000);
+    let mut wtxn = env.write_txn().unwrap();
+    let mut rng = rng();
+    for i in 0..100 {
+        let vector: Vec<f32> = (0..768).map(|_| rng.gen()).collect();
+        writer.add_item(&mut wtxn, i, &vector).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.item_ids().len(), 100);
+    let found = reader.nns(1).by_item(&wtxn, 42).unwrap().unwrap();
+    assert_eq!(found.into_nns().len(), 1);
+}
+
+#[test]
+fn compact_the_ids_of_the_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let writer = Writer::new(database, 0, 2);
+    for i in 0..50 {
+        writer.add_item(&mut wtxn, i * 10, &[i as f32, 1.0]).unwrap();
+    }
+    writer.builder(&mut rng()).array_links(true).build::<M, M0>(&mut wtxn).unwrap();
+    for i in (0..50).filter(|i| i % 3 != 0) {
+        writer.del_item(&mut wtxn, i * 10).unwrap();
+    }
+    writer.set_payload(&mut wtxn, 90, b"ninety").unwrap();
+    writer.set_boost(&mut wtxn, 120, 2.0).unwrap();
+    let err = writer.compact(&mut wtxn).unwrap_err();
+    insta::assert_snapshot!(err, @"The graph has not been built after an update on index 0");
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    let before = reader.nns(3).by_vector(&wtxn, &[9.0, 1.0]).unwrap();
+
+    let mapping = writer.compact(&mut wtxn).unwrap();
+    let expected: Vec<_> = (0..17).map(|i| (i * 30, i)).collect();
+    assert_eq!(mapping, expected);
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    assert_eq!(reader.item_ids(), &RoaringBitmap::from_iter(0..17));
+    assert_eq!(reader.item_vector(&wtxn, 3).unwrap(), Some(vec![9.0, 1.0]));
+    assert_eq!(reader.payload(&wtxn, 3).unwrap(), Some(&b"ninety"[..]));
+    assert_eq!(reader.boost(&wtxn, 4).unwrap(), Some(2.0));
+    assert_eq!(reader.item_vector(&wtxn, 30).unwrap(), None);
+    for item in 0..17 {
+        let links = get_links(&wtxn, database, 0, item, 0).unwrap().unwrap();
+        assert!(links.iter().all(|link| link < 17), "{item} links to {links:?}");
+    }
+    // the graph is the same, with its items relabeled
+    let found = reader.nns(3).by_vector(&wtxn, &[9.0, 1.0]).unwrap();
+    let relabeled =
+        before.into_nns().into_iter().map(|(item, d)| (item / 30, d)).collect::<Vec<_>>();
+    assert_eq!(found.into_nns(), relabeled);
+
+    // already dense
+    let mapping = writer.compact(&mut wtxn).unwrap();
+    assert!(mapping.iter().all(|(previous, new)| previous == new));
+
+    let logged = Writer::new(database, 1, 2);
+    logged.set_change_log(&mut wtxn, true).unwrap();
+    logged.add_item(&mut wtxn, 12, &[0.0, 1.0]).unwrap();
+    logged.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    let err = logged.compact(&mut wtxn).unwrap_err();
+    insta::assert_snapshot!(err, @"Cannot compact index 1, its change log isn't empty");
+}
+
+#[test]
+fn compact_keeps_the_payloads_and_tombstones_of_the_items() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut wtxn = env.write_txn().unwrap();
+    let mut writer = Writer::new(database, 0, 2);
+    for i in 0..60 {
+        writer.add_item(&mut wtxn, i * 7, &[i as f32, 1.0]).unwrap();
+        writer.set_payload(&mut wtxn, i * 7, format!("item {}", i * 7).as_bytes()).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    for i in (0..60).filter(|i| i % 4 == 1) {
+        writer.del_item(&mut wtxn, i * 7).unwrap();
+    }
+    writer.builder(&mut rng()).build::<M, M0>(&mut wtxn).unwrap();
+    writer.set_soft_deletes(true);
+    for i in (0..60).filter(|i| i % 5 == 2) {
+        writer.del_item(&mut wtxn, i * 7).unwrap();
+    }
+    let tombstones = writer.tombstones(&wtxn).unwrap();
+    assert!(!tombstones.is_empty());
+
+    let mapping = writer.compact(&mut wtxn).unwrap();
+    assert_eq!(mapping.len(), 45);
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, database).unwrap();
+    for &(previous, new) in &mapping {
+        // the tombstoned items are hidden from the reader and dropped their payload
+        if tombstones.contains(previous) {
+            assert_eq!(reader.payload(&wtxn, new).unwrap(), None);
+            continue;
+        }
+        let payload = format!("item {previous}");
+        assert_eq!(reader.payload(&wtxn, new).unwrap(), Some(payload.as_bytes()), "{new}");
+        assert_eq!(reader.item_vector(&wtxn, new).unwrap(), Some(vec![previous as f32 / 7.0, 1.0]));
+    }
+    let relabeled: RoaringBitmap = mapping
+        .iter()
+        .filter(|(previous, _)| tombstones.contains(*previous))
+        .map(|&(_, new)| new)
+        .collect();
+    assert_eq!(writer.tombstones(&wtxn).unwrap(), relabeled);
+    // the tombstoned items are still excluded from the searches under their new ids
+    let found = reader.nns(45).by_vector(&wtxn, &[0.0, 1.0]).unwrap().into_nns();
+    assert_eq!(found.len(), 45 - relabeled.len() as usize);
+    assert!(found.iter().all(|(item, _)| !relabeled.contains(*item)));
+}
+
+#[test]
+fn pin_a_snapshot_while_the_index_changes() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let writer = Writer::new(database, 0, 2);
+    let mut wtxn = env.write_txn().unwrap();
+    writer.set_change_log(&mut wtxn, true).unwrap();
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    let error = writer.snapshot(&mut wtxn, "v1").unwrap_err();
+    assert!(matches!(error, Error::MissingMetadata(0)));
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let v1 = writer.snapshot(&mut wtxn, "v1").unwrap();
+    assert_eq!(v1, u16::MAX);
+    let error = writer.snapshot(&mut wtxn, "v1").unwrap_err();
+    assert!(matches!(error, Error::InvalidSnapshot { index: 0, .. }));
+    wtxn.commit().unwrap();
+
+    let mut wtxn = env.write_txn().unwrap();
+    writer.del_item(&mut wtxn, 0).unwrap();
+    writer.add_item(&mut wtxn, 10, &[10.0, 0.0]).unwrap();
+    let error = writer.snapshot(&mut wtxn, "v2").unwrap_err();
+    assert!(matches!(error, Error::NeedBuild(0)));
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    let v2 = writer.snapshot(&mut wtxn, "v2").unwrap();
+    wtxn.commit().unwrap();
+
+    // the snapshot keeps the items it was taken with, without the change log
+    let rtxn = env.read_txn().unwrap();
+    assert_eq!(writer.snapshots(&rtxn).unwrap(), [("v1".to_string(), v1), ("v2".to_string(), v2)]);
+    let pinned = Reader::open_snapshot(&rtxn, 0, database, "v1").unwrap();
+    assert_eq!(pinned.index(), v1);
+    assert_eq!(pinned.item_ids(), &(0..10).collect::<RoaringBitmap>());
+    let nns = pinned.nns(1).by_vector(&rtxn, &[10.0, 0.0]).unwrap().into_nns();
+    assert_eq!(nns, vec![(9, 1.0)]);
+    assert!(Writer::new(database, v1, 2).changes(&rtxn, 0).unwrap().is_empty());
+    let latest = Reader::open(&rtxn, 0, database).unwrap();
+    assert_eq!(latest.item_ids(), &(1..11).collect::<RoaringBitmap>());
+    assert_eq!(
+        Reader::open_snapshot(&rtxn, 0, database, "v2").unwrap().item_ids(),
+        latest.item_ids()
+    );
+    let error = Reader::open_snapshot(&rtxn, 0, database, "v3").unwrap_err();
+    assert!(matches!(error, Error::InvalidSnapshot { index: 0, .. }));
+    drop(rtxn);
+
+    let mut wtxn = env.write_txn().unwrap();
+    assert!(writer.delete_snapshot(&mut wtxn, "v1").unwrap());
+    assert!(!writer.delete_snapshot(&mut wtxn, "v1").unwrap());
+    assert_eq!(writer.snapshots(&wtxn).unwrap(), [("v2".to_string(), v2)]);
+    assert!(Reader::open(&wtxn, v1, database).is_err());
+}
+
+#[test]
+fn maintain_the_graph_in_small_steps() {
+    let DatabaseHandle { env, database, tempdir: _ } = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut writer = Writer::new(database, 0, 2);
+    writer.set_soft_deletes(true);
+    let mut wtxn = env.write_txn().unwrap();
+    for i in 0..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).build::<M, M0>(&mut wtxn).unwrap();
+    writer.del_items(&mut wtxn, &(0..3).collect()).unwrap();
+
+    // the node 10 was given more links than the bottom layer allows
+    let key = Key::links(0, 10, 0);
+    let links: RoaringBitmap = (4..10).collect();
+    let node = Node::Links(Links { links: Cow::Owned(links) });
+    database.put(&mut wtxn, &key, &node).unwrap();
+
+    let mut maintenance = Maintenance::new(8).with_interval(Duration::from_secs(3600));
+    let step = writer.builder(&mut rng).maintain::<M, M0>(&mut wtxn, &mut maintenance).unwrap();
+    let step = step.unwrap();
+    assert_eq!(step, MaintenanceStep { vacuumed: 3, inspected: 8, repaired: 0, wrapped: false });
+    assert!(writer.tombstones(&wtxn).unwrap().is_empty());
+    // the interval didn't elapse
+    let step = writer.builder(&mut rng).maintain::<M, M0>(&mut wtxn, &mut maintenance).unwrap();
+    assert_eq!(step, None);
+
+    let mut maintenance = Maintenance::new(8);
+    let mut steps = Vec::new();
+    loop {
+        let step = writer.builder(&mut rng).maintain::<M, M0>(&mut wtxn, &mut maintenance).unwrap();
+        let step = step.unwrap();
+        steps.push((step.inspected, step.repaired));
+        if step.wrapped {
+            break;
+        }
+    }
+    assert_eq!(steps, [(8, 1), (8, 0), (1, 0)]);
+    let links = get_links(&wtxn, database, 0, 10, 0).unwrap().unwrap();
+    assert!(links.links.len() <= M0 as u64, "{links:?}");
+    assert_eq!(maintenance.next_item(), 0);
+
+    // the changes that weren't built are left to the application
+    writer.add_item(&mut wtxn, 20, &[20.0, 0.0]).unwrap();
+    let step = writer.builder(&mut rng).maintain::<M, M0>(&mut wtxn, &mut maintenance).unwrap();
+    assert_eq!(step, None);
+}
@@ -0,0 +1,62 @@
+use std::borrow::Cow;
+use std::mem::size_of;
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::BoxedError;
+
+/// The smallest `ef_search` reaching a target recall, found by [`crate::eval::tune_ef_search`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EfSearchTuning {
+    /// The recommended size of the search queue.
+    pub ef_search: usize,
+    /// The number of neighbours the recall was measured on.
+    pub k: usize,
+    /// The recall that was targeted.
+    pub target_recall: f64,
+    /// The mean recall reached with [`Self::ef_search`] on the sample of queries. It is below the
+    /// target if the target couldn't be reached.
+    pub recall: f64,
+}
+
+pub enum TuningCodec {}
+
+impl<'a> heed::BytesEncode<'a> for TuningCodec {
+    type EItem = EfSearchTuning;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+        let EfSearchTuning { ef_search, k, target_recall, recall } = item;
+
+        let mut output = Vec::with_capacity(size_of::<u32>() * 2 + size_of::<f64>() * 2);
+        output.extend_from_slice(&u32::try_from(*ef_search)?.to_be_bytes());
+        output.extend_from_slice(&u32::try_from(*k)?.to_be_bytes());
+        output.extend_from_slice(&target_recall.to_be_bytes());
+        output.extend_from_slice(&recall.to_be_bytes());
+
+        Ok(Cow::Owned(output))
+    }
+}
+
+impl heed::BytesDecode<'_> for TuningCodec {
+    type DItem = EfSearchTuning;
+
+    fn bytes_decode(bytes: &'_ [u8]) -> Result<Self::DItem, BoxedError> {
+        let expected = size_of::<u32>() * 2 + size_of::<f64>() * 2;
+        if bytes.len() != expected {
+            return Err(format!(
+                "Could not decode a tuning of {} bytes, expected {expected}.",
+                bytes.len()
+            )
+            .into());
+        }
+
+        let ef_search = BigEndian::read_u32(bytes) as usize;
+        let bytes = &bytes[size_of::<u32>()..];
+        let k = BigEndian::read_u32(bytes) as usize;
+        let bytes = &bytes[size_of::<u32>()..];
+        let target_recall = BigEndian::read_f64(bytes);
+        let bytes = &bytes[size_of::<f64>()..];
+        let recall = BigEndian::read_f64(bytes);
+
+        Ok(EfSearchTuning { ef_search, k, target_recall, recall })
+    }
+}
@@ -34,6 +34,9 @@ impl UnalignedVectorCodec for f32 {
     }
 
     fn to_vec(vec: &UnalignedVector<Self>) -> Vec<f32> {
+        if let Some(slice) = vec.as_aligned() {
+            return slice.to_vec();
+        }
         let iter = vec.iter();
         let mut ret = Vec::with_capacity(iter.len());
         ret.extend(iter);
@@ -52,6 +55,17 @@ impl UnalignedVectorCodec for f32 {
     }
 
     fn is_zero(vec: &UnalignedVector<Self>) -> bool {
-        vec.iter().all(|v| v == 0.0)
+        match vec.as_aligned() {
+            Some(slice) => slice.iter().all(|&v| v == 0.0),
+            None => vec.iter().all(|v| v == 0.0),
+        }
+    }
+}
+
+impl UnalignedVector<f32> {
+    /// Returns the vector as a slice of f32 when its bytes happen to be aligned, which is the
+    /// case of the vectors stored in the overflow pages of LMDB, `None` otherwise.
+    pub fn as_aligned(&self) -> Option<&[f32]> {
+        bytemuck::try_cast_slice(&self.vector).ok()
     }
 }
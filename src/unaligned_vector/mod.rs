@@ -7,12 +7,15 @@ use std::{
 
 pub use binary::Binary;
 pub use binary_quantized::BinaryQuantized;
+pub use sparse::Sparse;
 
 use bytemuck::pod_collect_to_vec;
 
 mod binary;
 mod binary_quantized;
 mod f32;
+mod sparse;
+mod u8;
 
 #[cfg(test)]
 mod binary_quantized_test;
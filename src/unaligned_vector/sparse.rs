@@ -0,0 +1,183 @@
+use std::borrow::Cow;
+use std::iter::FusedIterator;
+use std::mem::size_of;
+use std::slice::ChunksExact;
+
+use byteorder::{ByteOrder, NativeEndian};
+
+use super::{SizeMismatch, UnalignedVector, UnalignedVectorCodec};
+
+/// The bytes of the number of dimensions written before the entries.
+const DIMENSIONS_BYTES: usize = size_of::<u32>();
+
+/// The bytes of an entry, its index followed by its value.
+const ENTRY_BYTES: usize = size_of::<u32>() + size_of::<f32>();
+
+/// A codec storing only the non-zero values of the vectors, along with their indices.
+///
+/// The vector starts with its number of dimensions followed by the `(index, value)` entries
+/// sorted by increasing index, which makes SPLADE-like embeddings with tens of thousands of
+/// dimensions but a few hundred non-zero values cheap to store and to compare.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sparse {}
+
+impl Sparse {
+    /// Makes sure the `indices` are sorted, unique and within the `dimensions`, and that there
+    /// is a value for each of them, returns what is wrong otherwise.
+    pub(crate) fn check_entries(
+        dimensions: usize,
+        indices: &[u32],
+        values: &[f32],
+    ) -> Result<(), String> {
+        if indices.len() != values.len() {
+            return Err(format!("{} indices but {} values", indices.len(), values.len()));
+        }
+        if let Some(pair) = indices.windows(2).find(|pair| pair[0] >= pair[1]) {
+            return Err(format!("index {} is not strictly greater than {}", pair[1], pair[0]));
+        }
+        if let Some(&index) = indices.last().filter(|&&index| index as usize >= dimensions) {
+            return Err(format!("index {index} is out of the {dimensions} dimensions"));
+        }
+        Ok(())
+    }
+
+    /// Encodes a sparse vector from its sorted `indices` and their `values`, both are
+    /// expected to be validated by the caller.
+    pub(crate) fn from_entries(
+        dimensions: usize,
+        indices: &[u32],
+        values: &[f32],
+    ) -> Cow<'static, UnalignedVector<Self>> {
+        let mut bytes = Vec::with_capacity(DIMENSIONS_BYTES + indices.len() * ENTRY_BYTES);
+        bytes.extend_from_slice(&(dimensions as u32).to_ne_bytes());
+        for (index, value) in indices.iter().zip(values).filter(|(_, v)| **v != 0.0) {
+            bytes.extend_from_slice(&index.to_ne_bytes());
+            bytes.extend_from_slice(&value.to_ne_bytes());
+        }
+        Cow::Owned(bytes)
+    }
+}
+
+impl UnalignedVector<Sparse> {
+    /// Returns the `(index, value)` entries of the non-zero values, sorted by index.
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = (u32, f32)> + '_ {
+        entries(self)
+    }
+}
+
+fn entries(vec: &UnalignedVector<Sparse>) -> ChunkEntries<'_> {
+    ChunkEntries { chunks: vec.vector[DIMENSIONS_BYTES..].chunks_exact(ENTRY_BYTES) }
+}
+
+fn dense_from_slice(slice: &[f32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(DIMENSIONS_BYTES);
+    bytes.extend_from_slice(&(slice.len() as u32).to_ne_bytes());
+    for (index, value) in slice.iter().enumerate().filter(|(_, v)| **v != 0.0) {
+        bytes.extend_from_slice(&(index as u32).to_ne_bytes());
+        bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+    bytes
+}
+
+impl UnalignedVectorCodec for Sparse {
+    /// Creates a sparse vector from the bytes of its dimensions and entries.
+    fn from_bytes(bytes: &[u8]) -> Result<Cow<'_, UnalignedVector<Self>>, SizeMismatch> {
+        match bytes.len().checked_sub(DIMENSIONS_BYTES) {
+            Some(entries) if entries % ENTRY_BYTES == 0 => {
+                Ok(Cow::Borrowed(UnalignedVector::from_bytes_unchecked(bytes)))
+            }
+            Some(entries) => {
+                Err(SizeMismatch { vector_codec: "sparse", rem: entries % ENTRY_BYTES })
+            }
+            None => Err(SizeMismatch { vector_codec: "sparse", rem: bytes.len() }),
+        }
+    }
+
+    /// Keeps the non-zero values of a dense slice of f32.
+    fn from_slice(slice: &[f32]) -> Cow<'_, UnalignedVector<Self>> {
+        Cow::Owned(dense_from_slice(slice))
+    }
+
+    /// Keeps the non-zero values of a dense vector of f32.
+    fn from_vec(vec: Vec<f32>) -> Cow<'static, UnalignedVector<Self>> {
+        Cow::Owned(dense_from_slice(&vec))
+    }
+
+    fn to_vec(vec: &UnalignedVector<Self>) -> Vec<f32> {
+        let mut ret = vec![0.0; Self::len(vec)];
+        for (index, value) in entries(vec) {
+            ret[index as usize] = value;
+        }
+        ret
+    }
+
+    /// Returns an iterator over every dimension of the vector, zeros included.
+    fn iter(vec: &UnalignedVector<Self>) -> impl ExactSizeIterator<Item = f32> + '_ {
+        let mut entries = entries(vec);
+        let next = entries.next();
+        DenseIter { entries, next, position: 0, dimensions: Self::len(vec) as u32 }
+    }
+
+    /// Returns the number of dimensions of the vector, not its number of non-zero values.
+    fn len(vec: &UnalignedVector<Self>) -> usize {
+        NativeEndian::read_u32(&vec.vector[..DIMENSIONS_BYTES]) as usize
+    }
+
+    fn is_zero(vec: &UnalignedVector<Self>) -> bool {
+        entries(vec).all(|(_, value)| value == 0.0)
+    }
+}
+
+struct ChunkEntries<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+impl Iterator for ChunkEntries<'_> {
+    type Item = (u32, f32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunks.next()?;
+        Some((NativeEndian::read_u32(&chunk[..4]), NativeEndian::read_f32(&chunk[4..])))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl ExactSizeIterator for ChunkEntries<'_> {}
+
+impl FusedIterator for ChunkEntries<'_> {}
+
+struct DenseIter<'a> {
+    entries: ChunkEntries<'a>,
+    next: Option<(u32, f32)>,
+    position: u32,
+    dimensions: u32,
+}
+
+impl Iterator for DenseIter<'_> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.dimensions {
+            return None;
+        }
+        let value = match self.next {
+            Some((index, value)) if index == self.position => {
+                self.next = self.entries.next();
+                value
+            }
+            _ => 0.0,
+        };
+        self.position += 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.dimensions - self.position) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for DenseIter<'_> {}
@@ -0,0 +1,50 @@
+use std::borrow::Cow;
+
+use super::{SizeMismatch, UnalignedVector, UnalignedVectorCodec};
+
+impl UnalignedVectorCodec for u8 {
+    /// Creates a vector of bytes from a slice of bytes, any length is valid.
+    fn from_bytes(bytes: &[u8]) -> Result<Cow<'_, UnalignedVector<Self>>, SizeMismatch> {
+        Ok(Cow::Borrowed(UnalignedVector::from_bytes_unchecked(bytes)))
+    }
+
+    /// Rounds the f32 to the closest byte, saturating at `0` and `255`.
+    fn from_slice(slice: &[f32]) -> Cow<'_, UnalignedVector<Self>> {
+        Cow::Owned(slice.iter().map(|&x| x.round() as u8).collect())
+    }
+
+    /// Rounds the f32 to the closest byte, saturating at `0` and `255`.
+    fn from_vec(vec: Vec<f32>) -> Cow<'static, UnalignedVector<Self>> {
+        Cow::Owned(vec.iter().map(|&x| x.round() as u8).collect())
+    }
+
+    fn to_vec(vec: &UnalignedVector<Self>) -> Vec<f32> {
+        vec.vector.iter().map(|&x| f32::from(x)).collect()
+    }
+
+    /// Returns an iterator of the bytes of the vector converted to f32.
+    fn iter(vec: &UnalignedVector<Self>) -> impl ExactSizeIterator<Item = f32> + '_ {
+        vec.vector.iter().map(|&x| f32::from(x))
+    }
+
+    /// Return the number of bytes of the vector.
+    fn len(vec: &UnalignedVector<Self>) -> usize {
+        vec.vector.len()
+    }
+
+    fn is_zero(vec: &UnalignedVector<Self>) -> bool {
+        vec.vector.iter().all(|&x| x == 0)
+    }
+}
+
+impl UnalignedVector<u8> {
+    /// Creates a vector borrowing the `bytes`, one per dimension.
+    pub fn from_u8_slice(bytes: &[u8]) -> &Self {
+        UnalignedVector::from_bytes_unchecked(bytes)
+    }
+
+    /// Returns the values of the vector, one byte per dimension.
+    pub fn values(&self) -> &[u8] {
+        &self.vector
+    }
+}
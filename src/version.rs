@@ -2,12 +2,27 @@ use std::mem::size_of;
 use std::{borrow::Cow, fmt};
 
 use byteorder::{BigEndian, ByteOrder};
-use heed::BoxedError;
+use heed::types::U32;
+use heed::{BoxedError, RoTxn, RwTxn};
 
-#[derive(Debug, Clone, Copy)]
+use crate::distance::Distance;
+use crate::{Database, Error, Key, Result};
+
+/// The format the builds write the nodes of an index in, bumped whenever a reader of the
+/// previous format can't decode them, whatever the version of the crate.
+///
+/// - `0`: the indexes built before the format was stored.
+/// - `1`: the vectors and the links padded to aligned offsets, and the links stored as arrays.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The version of hannoy an index was built with, which defines its format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version {
+    /// The major version.
     pub major: u32,
+    /// The minor version.
     pub minor: u32,
+    /// The patch version.
     pub patch: u32,
 }
 
@@ -18,6 +33,7 @@ impl fmt::Display for Version {
 }
 
 impl Version {
+    /// Returns the version of this crate.
     pub fn current() -> Self {
         Version {
             major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap(),
@@ -58,6 +74,33 @@ impl heed::BytesDecode<'_> for VersionCodec {
     }
 }
 
+/// Returns the format the nodes of an index were last built in, `0` if it wasn't stored.
+pub(crate) fn get_format<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<u32> {
+    let key = Key::format(index);
+    let format = database
+        .remap_data_type::<U32<BigEndian>>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?;
+    Ok(format.unwrap_or(0))
+}
+
+/// Stores the [`FORMAT_VERSION`] the nodes of an index are built in.
+pub(crate) fn put_format<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<()> {
+    let key = Key::format(index);
+    database
+        .remap_data_type::<U32<BigEndian>>()
+        .put(wtxn, &key, &FORMAT_VERSION)
+        .map_err(Error::on_key("put", key))
+}
+
 #[cfg(test)]
 mod test {
     use heed::{BytesDecode, BytesEncode};
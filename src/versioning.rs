@@ -0,0 +1,221 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{BoxedError, BytesDecode, RoTxn, RwTxn};
+use roaring::RoaringBitmap;
+
+use crate::change_log;
+use crate::distance::Distance;
+use crate::internals::KeyCodec;
+use crate::node::{Item, NodeCodec};
+use crate::ordered_float::OrderedFloat;
+use crate::reader::{get_item, is_tombstoned, tombstones};
+use crate::unaligned_vector::UnalignedVector;
+use crate::weights;
+use crate::{Database, Error, ItemId, Key, Node, Prefix, PrefixCodec, Result};
+
+/// The codec of a version of an item: its generation, the sequence numbers of the changes that
+/// made it and replaced it, and the bytes of the item node, empty if the item didn't exist.
+enum HistoryCodec {}
+
+impl<'a> heed::BytesEncode<'a> for HistoryCodec {
+    type EItem = (u32, u32, u32, &'a [u8]);
+
+    fn bytes_encode(
+        (generation, since, sequence, node): &'a Self::EItem,
+    ) -> Result<Cow<'a, [u8]>, BoxedError> {
+        let mut output = Vec::with_capacity(3 * size_of::<u32>() + node.len());
+        output.extend_from_slice(&generation.to_be_bytes());
+        output.extend_from_slice(&since.to_be_bytes());
+        output.extend_from_slice(&sequence.to_be_bytes());
+        output.extend_from_slice(node);
+        Ok(Cow::Owned(output))
+    }
+}
+
+impl<'a> heed::BytesDecode<'a> for HistoryCodec {
+    type DItem = (u32, u32, u32, &'a [u8]);
+
+    fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
+        let (generation, bytes) = bytes.split_at(size_of::<u32>());
+        let (since, bytes) = bytes.split_at(size_of::<u32>());
+        let (sequence, node) = bytes.split_at(size_of::<u32>());
+        let read = BigEndian::read_u32;
+        Ok((read(generation), read(since), read(sequence), node))
+    }
+}
+
+/// Keeps the current vector of an item, about to be replaced or deleted by the next change of
+/// the log, in the ring of its `versions` last versions.
+pub(crate) fn record<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    item: ItemId,
+    versions: u8,
+) -> Result<()> {
+    let sequence = change_log::next_sequence(wtxn, database, Key::change_sequence(index))?;
+    let key = Key::item(index, item);
+    let node = match is_tombstoned(wtxn, database, index, item)? {
+        true => Vec::new(),
+        false => database
+            .remap_data_type::<Bytes>()
+            .get(wtxn, &key)
+            .map_err(Error::on_key("get", key))?
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default(),
+    };
+
+    // the generations increase by one with every version, the next one goes in the slot of the
+    // oldest version once the ring is full. The current vector was made by the change that
+    // replaced the last version, or before the versions were kept for the first one.
+    let range = Key::history(index, item, 0)..=Key::history(index, item, u8::MAX);
+    let (mut generation, mut since) = (0, 0);
+    for result in database.remap_data_type::<HistoryCodec>().range(wtxn, &range)? {
+        let (_, (previous, _, replaced_at, _)) = result?;
+        if previous + 1 > generation {
+            (generation, since) = (previous + 1, replaced_at);
+        }
+    }
+
+    let key = Key::history(index, item, (generation % u32::from(versions)) as u8);
+    database
+        .remap_data_type::<HistoryCodec>()
+        .put(wtxn, &key, &(generation, since, sequence, &node))
+        .map_err(Error::on_key("put", key))
+}
+
+/// A read-only view of the items of an index as they were at an older sequence number of its
+/// change log, see [`Writer::point_in_time`](crate::Writer::point_in_time).
+///
+/// The graph only exists for the current items, the searches of this view compare the query
+/// with every item, which is fine for offline experiments but not for serving queries.
+#[derive(Debug)]
+pub struct PointInTime<D: Distance> {
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    sequence: u32,
+    items: RoaringBitmap,
+    weights: Option<Vec<f32>>,
+    /// The nodes of the items that changed since, empty for the ones that didn't exist yet.
+    versions: HashMap<ItemId, Vec<u8>>,
+}
+
+impl<D: Distance> PointInTime<D> {
+    pub(crate) fn new(
+        rtxn: &RoTxn,
+        database: Database<D>,
+        index: u16,
+        dimensions: usize,
+        sequence: u32,
+    ) -> Result<Self> {
+        let mut items = RoaringBitmap::new();
+        let prefix = Prefix::item(index);
+        let iter = database
+            .remap_types::<PrefixCodec, DecodeIgnore>()
+            .prefix_iter(rtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
+            .remap_key_type::<KeyCodec>();
+        for result in iter {
+            let (key, ()) = result?;
+            items.insert(key.node.item);
+        }
+        items -= tombstones(rtxn, database, index)?;
+
+        // for every item, the first version replaced at or after the sequence is the one we
+        // look for, as long as it was made before the sequence, the versions in between were
+        // overwritten otherwise
+        let mut candidates: HashMap<ItemId, (u32, u32, u32, &[u8])> = HashMap::new();
+        let prefix = Prefix::history(index);
+        let iter = database
+            .remap_types::<PrefixCodec, HistoryCodec>()
+            .prefix_iter(rtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
+            .remap_key_type::<KeyCodec>();
+        for result in iter {
+            let (key, (generation, since, replaced_at, node)) = result?;
+            let item = key.node.item;
+            if replaced_at >= sequence
+                && candidates.get(&item).is_none_or(|&(_, _, other, _)| replaced_at < other)
+            {
+                candidates.insert(item, (generation, since, replaced_at, node));
+            }
+        }
+
+        let mut versions = HashMap::with_capacity(candidates.len());
+        for (item, (generation, since, _, node)) in candidates {
+            if generation > 0 && since >= sequence {
+                return Err(Error::VersionPruned { item, sequence });
+            }
+            if node.is_empty() {
+                items.remove(item);
+            } else {
+                items.insert(item);
+            }
+            versions.insert(item, node.to_vec());
+        }
+
+        let weights = weights::get_weights(rtxn, database, index)?;
+        Ok(PointInTime { database, index, dimensions, sequence, items, weights, versions })
+    }
+
+    /// Returns the sequence number this view was opened at.
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// Returns the ids of the items that existed at that point.
+    pub fn item_ids(&self) -> &RoaringBitmap {
+        &self.items
+    }
+
+    /// Returns the vector an item had at that point, `None` if it didn't exist.
+    pub fn item_vector(&self, rtxn: &RoTxn, item: ItemId) -> Result<Option<Vec<f32>>> {
+        Ok(self.item(rtxn, item)?.map(|item| {
+            let mut vec = item.vector.to_vec();
+            vec.truncate(self.dimensions);
+            vec
+        }))
+    }
+
+    /// Returns the `count` closest items from the `vector` among the items that existed at that
+    /// point, along with their distances, by comparing it with every item.
+    pub fn nns(&self, rtxn: &RoTxn, vector: &[f32], count: usize) -> Result<Vec<(ItemId, f32)>> {
+        if vector.len() != self.dimensions {
+            return Err(Error::InvalidDimensions { expected: self.dimensions, got: vector.len() });
+        }
+
+        let vector = weights::apply(self.weights.as_deref(), D::preprocess(vector));
+        let vector = UnalignedVector::from_slice(&vector);
+        let query = Item { header: D::new_header(&vector), vector };
+        let mut nns = Vec::with_capacity(self.items.len() as usize);
+        for id in &self.items {
+            if let Some(item) = self.item(rtxn, id)? {
+                nns.push((OrderedFloat(D::distance(&query, &item)), id));
+            }
+        }
+
+        nns.sort_unstable();
+        Ok(nns.into_iter().take(count).map(|(OrderedFloat(distance), id)| (id, distance)).collect())
+    }
+
+    fn item<'a>(&'a self, rtxn: &'a RoTxn, item: ItemId) -> Result<Option<Item<'a, D>>> {
+        match self.versions.get(&item) {
+            Some(node) if node.is_empty() => Ok(None),
+            Some(node) => {
+                match NodeCodec::<D>::bytes_decode(node).map_err(heed::Error::Decoding)? {
+                    Node::Item(item) => Ok(Some(item)),
+                    Node::Links(_) => {
+                        Err(Error::unexpected_node(Key::item(self.index, item), "Item"))
+                    }
+                }
+            }
+            None if !self.items.contains(item) => Ok(None),
+            None => get_item(self.database, self.index, rtxn, item),
+        }
+    }
+}
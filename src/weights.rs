@@ -0,0 +1,45 @@
+use std::borrow::Cow;
+
+use heed::types::Bytes;
+use heed::{RoTxn, RwTxn};
+
+use crate::distance::Distance;
+use crate::{Database, Error, Key, Result};
+
+/// Returns the weights of the dimensions of an index, see
+/// [`Writer::set_weights`](crate::Writer::set_weights).
+pub(crate) fn get_weights<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<Option<Vec<f32>>> {
+    let key = Key::weights(index);
+    let weights =
+        database.remap_data_type::<Bytes>().get(rtxn, &key).map_err(Error::on_key("get", key))?;
+    Ok(weights.map(bytemuck::pod_collect_to_vec))
+}
+
+/// Stores the weights of the dimensions of an index, read back by [`get_weights`].
+pub(crate) fn put_weights<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    weights: &[f32],
+) -> Result<()> {
+    let key = Key::weights(index);
+    database
+        .remap_data_type::<Bytes>()
+        .put(wtxn, &key, bytemuck::cast_slice(weights))
+        .map_err(Error::on_key("put", key))
+}
+
+/// Scales the coordinates of a vector by the square roots of the `weights`, so that the squared
+/// Euclidean distance between two scaled vectors is the weighted one.
+pub(crate) fn apply<'v>(weights: Option<&[f32]>, vector: Cow<'v, [f32]>) -> Cow<'v, [f32]> {
+    match weights {
+        Some(weights) => {
+            Cow::Owned(vector.iter().zip(weights).map(|(x, weight)| x * weight.sqrt()).collect())
+        }
+        None => vector,
+    }
+}
@@ -1,28 +1,60 @@
 use std::any::TypeId;
+use std::borrow::Cow;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use heed::types::{DecodeIgnore, Unit};
-use heed::{PutFlags, RoTxn, RwTxn};
+use byteorder::BigEndian;
+use heed::types::{Bytes, DecodeIgnore, Unit, U32, U64};
+use heed::{Env, PutFlags, RoTxn, RwTxn};
 use rand::{Rng, SeedableRng};
 use roaring::RoaringBitmap;
 use steppe::NoProgress;
 use tracing::{debug, info};
 
-use crate::distance::Distance;
+use crate::calibration;
+use crate::change_log::{self, Change, ChangeKind};
+use crate::codes;
+use crate::compaction;
+use crate::density;
+use crate::distance::{Distance, WeightedEuclidean};
+use crate::estimate::BuildEstimate;
 use crate::hnsw::HnswBuilder;
+use crate::hooks::{Hooks, WriterHooks};
+use crate::info::{self, BuildInfo};
 use crate::internals::KeyCodec;
 use crate::item_iter::ItemIter;
+use crate::lsh;
+use crate::maintenance::{self, Maintenance, MaintenanceStep};
+use crate::manifest::{self, IngestKind, IngestManifest};
+use crate::matryoshka;
+use crate::migration::{self, Migration};
 use crate::node::{Item, ItemIds, Links, NodeCodec};
 use crate::parallel::{ImmutableItems, ImmutableLinks};
+use crate::preset::{BuildParams, BuildPreset, DatasetStats};
 use crate::progress::HannoyBuild;
-use crate::reader::get_item;
-use crate::unaligned_vector::UnalignedVector;
-use crate::version::{Version, VersionCodec};
+use crate::projection::{self, Projection};
+use crate::reader::{
+    get_boost, get_item, get_links, get_payload, get_timestamp, has_tombstones, is_tombstoned,
+    tombstones, Reader,
+};
+use crate::reorder;
+use crate::snapshot;
+use crate::unaligned_vector::{Sparse, UnalignedVector};
+use crate::version::{self, Version, VersionCodec};
+#[cfg(feature = "versioning")]
+use crate::versioning::{self, PointInTime};
+use crate::weights;
 use crate::{
-    Database, Error, ItemId, Key, Metadata, MetadataCodec, Node, Prefix, PrefixCodec, Result,
-    CANCELLATION_PROBING,
+    Database, Error, ItemId, Key, LayerId, Metadata, MetadataCodec, MetadataDimensionsCodec, Node,
+    Prefix, PrefixCodec, Result, CANCELLATION_PROBING,
 };
 
+/// The numbers of links per node [`HannoyBuilder::build_with_links`] builds with, the nodes of
+/// the bottom layer have twice as many.
+pub const SUPPORTED_LINKS: [usize; 7] = [4, 8, 12, 16, 24, 32, 48];
+
 /// The options available when configuring the hannoy database.
 pub struct HannoyBuilder<'a, D: Distance, R: Rng + SeedableRng, P> {
     writer: &'a Writer<D>,
@@ -30,10 +62,37 @@ pub struct HannoyBuilder<'a, D: Distance, R: Rng + SeedableRng, P> {
     inner: BuildOption<'a, P>,
 }
 
+/// When [`HannoyBuilder::build_if_pending`] builds the index: as soon as either threshold is
+/// reached.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuildThreshold {
+    /// The number of pending additions, updates and deletions.
+    pub pending: u64,
+    /// The number of pending changes relative to the number of items of the last build.
+    pub fraction: f32,
+}
+
+impl Default for BuildThreshold {
+    fn default() -> Self {
+        Self { pending: 10_000, fraction: 0.1 }
+    }
+}
+
 /// The options available when building the hannoy database.
 pub(crate) struct BuildOption<'a, P> {
     pub(crate) ef_construction: usize,
     pub(crate) alpha: f32,
+    pub(crate) shards: usize,
+    pub(crate) relink_regions: bool,
+    pub(crate) lsh_bits: Option<usize>,
+    pub(crate) array_links: bool,
+    pub(crate) densities: bool,
+    /// The number of pending insertions each build takes, see
+    /// [`HannoyBuilder::build_in_transactions`].
+    pub(crate) chunk: Option<u64>,
+    /// The index the vectors of the items are read from, the built one when `None`, see
+    /// [`HannoyBuilder::rebuild_in_chunks`].
+    pub(crate) vectors: Option<u16>,
     pub(crate) available_memory: Option<usize>,
     pub(crate) cancel: Box<dyn Fn() -> bool + 'a + Sync + Send>,
     pub(crate) progress: P,
@@ -44,6 +103,13 @@ impl Default for BuildOption<'_, NoProgress> {
         Self {
             ef_construction: 100,
             alpha: 1.0,
+            shards: 1,
+            relink_regions: false,
+            lsh_bits: None,
+            array_links: false,
+            densities: false,
+            chunk: None,
+            vectors: None,
             available_memory: None,
             cancel: Box::new(|| false),
             progress: NoProgress,
@@ -112,13 +178,40 @@ impl<'a, D: Distance, R: Rng + SeedableRng, P> HannoyBuilder<'a, D, R, P> {
         let HannoyBuilder {
             writer,
             rng,
-            inner: BuildOption { ef_construction, available_memory, cancel, progress: _, alpha },
+            inner:
+                BuildOption {
+                    ef_construction,
+                    available_memory,
+                    cancel,
+                    progress: _,
+                    alpha,
+                    shards,
+                    relink_regions,
+                    lsh_bits,
+                    array_links,
+                    densities,
+                    chunk,
+                    vectors,
+                },
         } = self;
 
         HannoyBuilder {
             writer,
             rng,
-            inner: BuildOption { ef_construction, available_memory, cancel, progress, alpha },
+            inner: BuildOption {
+                ef_construction,
+                available_memory,
+                cancel,
+                progress,
+                alpha,
+                shards,
+                relink_regions,
+                lsh_bits,
+                array_links,
+                densities,
+                chunk,
+                vectors,
+            },
         }
     }
 
@@ -168,6 +261,131 @@ impl<'a, D: Distance, R: Rng + SeedableRng, P> HannoyBuilder<'a, D, R, P> {
         self
     }
 
+    /// Partitions the items of the bottom layer into `shards` independent sub-graphs, each one
+    /// built by a single thread, that are stitched together at the end of the build by
+    /// cross-linking their boundary nodes.
+    ///
+    /// The threads of a sharded build rarely touch the same nodes, which scales much better on
+    /// machines with many cores than all of them inserting in the same graph. The sub-graphs
+    /// share the upper layers, and the stitching re-links the items connected to them with the
+    /// other shards, which costs a little recall. The default is a single shard.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// let shards = rayon::current_num_threads();
+    /// writer.builder(&mut rng).shards(shards).build::<16,32>(&mut wtxn);
+    /// ```
+    pub fn shards(&mut self, shards: usize) -> &mut Self {
+        self.inner.shards = shards.max(1);
+        self
+    }
+
+    /// Also relinks the items of the graph linked to the items added, updated or deleted since
+    /// the last build when set to `true`.
+    ///
+    /// A build only repairs the links to the deleted and moved items, after a burst of changes
+    /// concentrated in a region of the space its neighbourhoods stay linked to the items that
+    /// were there before. Relinking the neighbours of the changed items searches them fresh
+    /// links in the new graph, which rebuilds the whole region without rebuilding the rest of
+    /// the graph. The default is `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// writer.builder(&mut rng).relink_regions(true).build::<16,32>(&mut wtxn);
+    /// ```
+    pub fn relink_regions(&mut self, enabled: bool) -> &mut Self {
+        self.inner.relink_regions = enabled;
+        self
+    }
+
+    /// Stores a SimHash sketch of `bits` bits, rounded up to a multiple of 64, of every item
+    /// along with the graph, which lets the searches prune their candidates with
+    /// [`QueryBuilder::lsh_prefilter`](crate::QueryBuilder::lsh_prefilter) before computing
+    /// their distances. The sketches are removed if the next build doesn't ask for them.
+    ///
+    /// The sketches take `bits / 8` bytes per item and pay off for vectors with thousands of
+    /// dimensions, whose distances are expensive even once quantized. They estimate the angle
+    /// between the vectors, which matches the cosine distances best.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Cosine};
+    /// # let (writer, wtxn): (Writer<Cosine>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// writer.builder(&mut rng).lsh_sketches(256).build::<16,32>(&mut wtxn);
+    /// ```
+    pub fn lsh_sketches(&mut self, bits: usize) -> &mut Self {
+        self.inner.lsh_bits = Some(bits);
+        self
+    }
+
+    /// Stores the links written by the build as sorted arrays of ids rather than roaring
+    /// bitmaps when set to `true`.
+    ///
+    /// The searches iterate the links of every node they expand, an array is read with a load
+    /// per id where a bitmap goes through the descriptions and the containers first. The arrays
+    /// take 4 bytes per link, a bit more than the bitmaps of the sparse ids. The links of the
+    /// previous builds the build doesn't rewrite keep their encoding, both are read. The default
+    /// is `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// writer.builder(&mut rng).array_links(true).build::<16,32>(&mut wtxn);
+    /// ```
+    pub fn array_links(&mut self, enabled: bool) -> &mut Self {
+        self.inner.array_links = enabled;
+        self
+    }
+
+    /// Records the mean distance of every item to its neighbours once the graph is built when
+    /// set to `true`, which [`Reader::outlier_score`](crate::Reader::outlier_score) compares
+    /// the results of the queries with. The densities are removed if the next build doesn't ask
+    /// for them.
+    ///
+    /// They take 4 bytes per item and the build computes the distances of the items to all of
+    /// their neighbours on the bottom layer once more. The default is `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// writer.builder(&mut rng).record_densities(true).build::<16,32>(&mut wtxn);
+    /// ```
+    pub fn record_densities(&mut self, enabled: bool) -> &mut Self {
+        self.inner.densities = enabled;
+        self
+    }
+
     /// Generates an HNSW graph with max `M` links per node in layers > 0 and max `M0` links in layer 0.
     ///
     /// A general rule of thumb is to take `M0`= 2*`M`, with `M` >=3.  Some common choices for
@@ -175,7 +393,12 @@ impl<'a, D: Distance, R: Rng + SeedableRng, P> HannoyBuilder<'a, D, R, P> {
     /// of longer build times.
     ///
     /// This function is using rayon to spawn threads. It can be configured by using the
-    /// [`rayon::ThreadPoolBuilder`].
+    /// [`rayon::ThreadPoolBuilder`]. Disabling the default `rayon` feature removes the
+    /// dependency and runs the whole build sequentially on the calling thread.
+    ///
+    /// The new graph is published atomically by the commit of the transaction: the readers
+    /// opened before it keep searching the previous graph, the ones opened after search the new
+    /// one, identified by [`Writer::build_id`].
     ///
     /// # Example
     ///
@@ -199,92 +422,719 @@ impl<'a, D: Distance, R: Rng + SeedableRng, P> HannoyBuilder<'a, D, R, P> {
         self.writer.build::<R, P, M, M0>(wtxn, self.rng, &self.inner)
     }
 
-    /// Converts an arroy db into a hannoy one.
-    #[cfg(any(test, feature = "arroy"))]
-    #[cfg_attr(docsrs, doc(cfg(feature = "arroy")))]
-    pub fn prepare_arroy_conversion(&self, wtxn: &mut RwTxn) -> Result<()>
+    /// Generates the HNSW graph like [`Self::build`] with `m` links per node and twice as many
+    /// in the bottom layer, for the callers that only know `m` at runtime, e.g. the bindings.
+    ///
+    /// `m` must be one of the [`SUPPORTED_LINKS`].
+    pub fn build_with_links(&mut self, wtxn: &mut RwTxn, m: usize) -> Result<()>
     where
         P: steppe::Progress,
     {
-        self.writer.prepare_arroy_conversion(wtxn, &self.inner)
+        macro_rules! build {
+            ($($M:literal),*) => {
+                match m {
+                    $($M => self.build::<$M, { $M * 2 }>(wtxn),)*
+                    m => Err(Error::UnsupportedLinks(m)),
+                }
+            };
+        }
+        build!(4, 8, 12, 16, 24, 32, 48)
     }
-}
 
-/// A writer to store new items, remove existing ones, and build the search
-/// index to query the nearest neighbors to items or vectors.
-#[derive(Debug)]
-pub struct Writer<D: Distance> {
-    database: Database<D>,
-    index: u16,
-    dimensions: usize,
-    /// The folder in which tempfile will write its temporary files.
-    tmpdir: Option<PathBuf>,
-}
+    /// Builds a sample of at most `sample` items spread over the index, with the same options,
+    /// and extrapolates the time and the writes of a build of all the items.
+    ///
+    /// The sample is built in an unused index of the database, which is cleared before
+    /// returning, so that the index itself is left untouched. Run it in a write transaction that
+    /// is then aborted to leave no trace, or before the actual build.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// let estimate = writer.builder(&mut rng).estimate::<16, 32>(&mut wtxn, 10_000)?;
+    /// println!("the build should take {:?}", estimate.duration);
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn estimate<const M: usize, const M0: usize>(
+        &mut self,
+        wtxn: &mut RwTxn,
+        sample: u64,
+    ) -> Result<BuildEstimate>
+    where
+        P: steppe::Progress,
+    {
+        let writer = self.writer;
+        let scratch = migration::free_index(wtxn, writer.database)?;
+
+        let ids = crate::preset::item_ids(wtxn, writer.database, writer.index)?;
+        let step = ids.len().div_ceil(sample.max(1)).max(1);
+        let scratch_writer = Writer {
+            index: scratch,
+            tmpdir: writer.tmpdir.clone(),
+            hooks: Hooks::default(),
+            lock: None,
+            ..*writer
+        };
+        // the stored vectors are copied as is, they were already transformed when added
+        let raw = writer.database.remap_data_type::<Bytes>();
+        let mut sampled = RoaringBitmap::new();
+        for rank in (0..ids.len()).step_by(step as usize) {
+            let id = ids.select(rank as u32).expect("the rank is lower than the length");
+            if is_tombstoned(wtxn, writer.database, writer.index, id)? {
+                continue;
+            }
+            let key = Key::item(writer.index, id);
+            let bytes = raw.get(wtxn, &key)?.map(<[u8]>::to_vec).ok_or(Error::missing_key(key))?;
+            let target = Key::item(scratch, id);
+            raw.put(wtxn, &target, &bytes).map_err(Error::on_key("put", target))?;
+            sampled.insert(id);
+        }
+        mark_updated(wtxn, writer.database, scratch, &sampled)?;
+        let sampled_items = sampled.len();
+        let vector_bytes = scratch_writer.written_bytes(wtxn)?;
+
+        let started = std::time::Instant::now();
+        let built = scratch_writer.build::<R, P, M, M0>(wtxn, self.rng, &self.inner);
+        let sample_duration = started.elapsed();
+        let written = built.and_then(|()| scratch_writer.written_bytes(wtxn));
+        scratch_writer.clear(wtxn)?;
+
+        Ok(BuildEstimate::extrapolate(
+            sampled_items,
+            ids.len(),
+            sample_duration,
+            written?,
+            vector_bytes,
+        ))
+    }
 
-impl<D: Distance> Writer<D> {
-    /// Creates a new writer from a database, index and dimensions.
-    pub fn new(database: Database<D>, index: u16, dimensions: usize) -> Writer<D> {
-        Writer { database, index, dimensions, tmpdir: None }
+    /// Adds the `items` and builds the index `chunk` items at a time, each chunk in its own write
+    /// transaction, and returns the number of added items.
+    ///
+    /// A single transaction holding millions of items and their links can exhaust the dirty
+    /// pages LMDB keeps in memory. Every committed chunk leaves a built index that can be read,
+    /// and the items already stored are skipped: calling it again with the same items after an
+    /// interruption resumes where it stopped. Use [`Writer::add_item`] to update the vector of
+    /// a stored item.
+    ///
+    /// The items added so far are recorded with the index until the last chunk is committed, see
+    /// [`Writer::unfinished_ingest`] to tell whether it was interrupted and
+    /// [`Writer::roll_back_ingest`] to remove them.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// # let vectors: Vec<(u32, Vec<f32>)> = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// let added = writer.builder(&mut rng).ingest_in_transactions::<16, 32, _>(&env, vectors, 100_000)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn ingest_in_transactions<const M: usize, const M0: usize, T>(
+        &mut self,
+        env: &Env<T>,
+        items: impl IntoIterator<Item = (ItemId, Vec<f32>)>,
+        chunk: usize,
+    ) -> Result<u64>
+    where
+        P: steppe::Progress,
+    {
+        let writer = self.writer;
+        let mut items = items.into_iter().peekable();
+        let mut ingested = 0;
+        while items.peek().is_some() {
+            let mut wtxn = env.write_txn()?;
+            let mut manifest = manifest::resume_manifest(
+                &wtxn,
+                writer.database,
+                writer.index,
+                IngestKind::Ingest,
+            )?;
+            let mut added = 0;
+            for (item, vector) in items.by_ref() {
+                if writer.contains_item(&wtxn, item)? {
+                    continue;
+                }
+                writer.add_item(&mut wtxn, item, &vector)?;
+                manifest.added.insert(item);
+                added += 1;
+                if added == chunk.max(1) as u64 {
+                    break;
+                }
+            }
+            if writer.pending_changes(&wtxn)? > 0 {
+                self.build::<M, M0>(&mut wtxn)?;
+            }
+            // the manifest is removed by the commit of the last chunk
+            if items.peek().is_some() {
+                manifest.committed += 1;
+                manifest::put_manifest(&mut wtxn, writer.database, writer.index, &manifest)?;
+            } else {
+                manifest::delete_manifest(&mut wtxn, writer.database, writer.index)?;
+            }
+            wtxn.commit()?;
+            ingested += added;
+            debug!("committed a chunk of {added} items, {ingested} added so far");
+        }
+        Ok(ingested)
     }
 
-    /// After opening an arroy database this function will prepare it for conversion,
-    /// cleanup the arroy database and only keep the items/vectors entries.
-    #[cfg(any(test, feature = "arroy"))]
-    pub(crate) fn prepare_arroy_conversion<P: steppe::Progress>(
-        &self,
-        wtxn: &mut RwTxn,
-        options: &BuildOption<P>,
-    ) -> Result<()> {
-        use crate::node_id::{NodeId, NodeMode};
-        use crate::unaligned_vector::UnalignedVectorCodec;
+    /// Builds the pending changes of the index `chunk` insertions at a time, each chunk in its
+    /// own write transaction, so that the graph built so far can be searched while the rest of
+    /// the items are being inserted.
+    ///
+    /// Every commit is a consistent graph of the items inserted so far, the pending deletions
+    /// being applied by the first one. Open it with
+    /// [`Reader::open_partial`](crate::Reader::open_partial) to answer queries
+    /// with a lower recall before the build finishes, e.g. for a brand-new index of millions of
+    /// items. Calling it again after an interruption resumes where it stopped, see
+    /// [`Writer::unfinished_ingest`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// writer.builder(&mut rng).build_in_transactions::<16, 32, _>(&env, 100_000)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn build_in_transactions<const M: usize, const M0: usize, T>(
+        &mut self,
+        env: &Env<T>,
+        chunk: u64,
+    ) -> Result<()>
+    where
+        P: steppe::Progress,
+    {
+        self.inner.chunk = Some(chunk.max(1));
+        let built = self.commit_chunks::<M, M0, T>(env);
+        self.inner.chunk = None;
+        built
+    }
 
-        debug!("Preparing dumpless upgrade from arroy to hannoy");
-        options.progress.update(HannoyBuild::ConvertingArroyToHannoy);
+    /// Builds and commits the chunks of pending insertions until none is left.
+    fn commit_chunks<const M: usize, const M0: usize, T>(&mut self, env: &Env<T>) -> Result<()>
+    where
+        P: steppe::Progress,
+    {
+        let writer = self.writer;
+        let mut committed = 0;
+        loop {
+            let mut wtxn = env.write_txn()?;
+            let mut manifest =
+                manifest::resume_manifest(&wtxn, writer.database, writer.index, IngestKind::Build)?;
+            if !writer.need_build(&wtxn)? {
+                return Ok(());
+            }
+            self.build::<M, M0>(&mut wtxn)?;
+            // the manifest is removed by the commit of the last chunk
+            if writer.need_build(&wtxn)? {
+                manifest.committed += 1;
+                manifest::put_manifest(&mut wtxn, writer.database, writer.index, &manifest)?;
+            } else {
+                manifest::delete_manifest(&mut wtxn, writer.database, writer.index)?;
+            }
+            wtxn.commit()?;
+            committed += 1;
+            debug!("committed the chunk {committed} of the build of index {}", writer.index);
+        }
+    }
 
-        let mut iter = self
+    /// Builds the index only if its pending changes reached the `threshold`, and returns whether
+    /// it did.
+    ///
+    /// The additions and deletions of items are only recorded until the index is built, this
+    /// lets the writes stay cheap and batches their insertions in the graph. Call it before
+    /// committing every write transaction to build once enough changes piled up, the items
+    /// added since the last build are not returned by the searches until then. An index that
+    /// was never built is built as soon as it has a pending change.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{BuildThreshold, Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// let mut wtxn = env.write_txn()?;
+    /// writer.add_item(&mut wtxn, 0, &[1.0, 0.0])?;
+    /// let threshold = BuildThreshold { pending: 1000, fraction: 0.05 };
+    /// writer.builder(&mut rng).build_if_pending::<16, 32>(&mut wtxn, threshold)?;
+    /// wtxn.commit()?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn build_if_pending<const M: usize, const M0: usize>(
+        &mut self,
+        wtxn: &mut RwTxn,
+        threshold: BuildThreshold,
+    ) -> Result<bool>
+    where
+        P: steppe::Progress,
+    {
+        let writer = self.writer;
+        let pending = writer.pending_changes(wtxn)?;
+        if pending == 0 {
+            return Ok(false);
+        }
+        let built = writer
             .database
-            .remap_key_type::<PrefixCodec>()
-            .prefix_iter_mut(wtxn, &Prefix::all(self.index))?
-            .remap_key_type::<KeyCodec>();
+            .remap_data_type::<MetadataCodec>()
+            .get(wtxn, &Key::metadata(writer.index))?
+            .map(|metadata| metadata.items.len());
+        let reached = match built {
+            Some(built) => {
+                pending >= threshold.pending || pending as f32 >= threshold.fraction * built as f32
+            }
+            None => true,
+        };
+        if reached {
+            debug!("building the index for its {pending} pending changes");
+            self.build::<M, M0>(wtxn)?;
+        }
+        Ok(reached)
+    }
 
-        // binary quantized have len vec.len().div_ceil(64)*64 >= vec.len()
-        let word_size = <D::VectorCodec as UnalignedVectorCodec>::word_size();
-        let on_disk_dim: usize = self.dimensions.div_ceil(word_size) * word_size;
+    /// Rebuilds the HNSW graph of the index from scratch without ever holding the links of all
+    /// its items in memory, `chunk` insertions per write transaction.
+    ///
+    /// The items are inserted in the order of their ids in a new graph written in a free index
+    /// of the database, which reads their vectors from this index instead of copying them. Each
+    /// chunk is committed in its own transaction and the last one replaces the links of this
+    /// index with the new graph, so the readers see either the old or the new graph. The pending
+    /// changes of the index, including the ones made during the rebuild, are part of the new
+    /// graph. Calling it again after an interruption resumes where it stopped, see
+    /// [`Writer::unfinished_ingest`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// writer.builder(&mut rng).rebuild_in_chunks::<16, 32, _>(&env, 100_000)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn rebuild_in_chunks<const M: usize, const M0: usize, T>(
+        &mut self,
+        env: &Env<T>,
+        chunk: u64,
+    ) -> Result<()>
+    where
+        P: steppe::Progress,
+    {
+        self.inner.chunk = Some(chunk.max(1));
+        self.inner.vectors = Some(self.writer.index);
+        let rebuilt = self.rebuild_chunks::<M, M0, T>(env);
+        self.inner.chunk = None;
+        self.inner.vectors = None;
+        rebuilt
+    }
 
-        let mut new_items = RoaringBitmap::new();
-        while let Some(result) = iter.next() {
-            match result {
-                Ok((
-                    Key { index: _, node: NodeId { mode: NodeMode::Item, item, .. } },
-                    Node::Item(Item { header: _, vector }),
-                )) => {
-                    // We only take care of the entries that can be decoded as Node Items (vectors) and
-                    // mark them as newly inserted so the Writer::build method can compute the links for them.
-                    new_items.insert(item);
-                    if vector.len() != on_disk_dim {
-                        return Err(Error::InvalidVecDimension {
-                            expected: on_disk_dim,
-                            received: vector.len(),
-                        });
+    /// Builds and commits the chunks of the new graph until none is left, then swaps it in.
+    fn rebuild_chunks<const M: usize, const M0: usize, T>(&mut self, env: &Env<T>) -> Result<()>
+    where
+        P: steppe::Progress,
+    {
+        let writer = self.writer;
+        let database = writer.database;
+        let mut committed = 0;
+        loop {
+            let mut wtxn = env.write_txn()?;
+            writer.check_lock(&wtxn)?;
+            let mut manifest =
+                manifest::resume_manifest(&wtxn, database, writer.index, IngestKind::Rebuild)?;
+            let scratch = match manifest.scratch {
+                Some(scratch) => writer.scratch(scratch),
+                None => {
+                    let ids = crate::preset::item_ids(&wtxn, database, writer.index)?;
+                    if ids.is_empty() {
+                        self.build::<M, M0>(&mut wtxn)?;
+                        wtxn.commit()?;
+                        return Ok(());
                     }
+                    let scratch = writer.scratch(migration::free_index(&wtxn, database)?);
+                    mark_updated(&mut wtxn, database, scratch.index, &ids)?;
+                    manifest.scratch = Some(scratch.index);
+                    scratch
                 }
-                Ok((Key { .. }, _)) | Err(heed::Error::Decoding(_)) => unsafe {
-                    // Every other entry that fails to decode can be considered as something
-                    // else than an item, is useless for the conversion and is deleted.
-                    // SAFETY: Safe because we don't keep any references to the entry
-                    iter.del_current()?;
-                },
-                // If there is another error (lmdb...), it is returned.
-                Err(e) => return Err(e.into()),
+            };
+            // the changes made to the index in the meantime are part of the new graph too
+            let pending = take_pending(&mut wtxn, database, writer.index)?;
+            mark_updated(&mut wtxn, database, scratch.index, &pending)?;
+            manifest.pending |= pending;
+
+            if scratch.pending_changes(&wtxn)? == 0 {
+                self.swap_graph(&mut wtxn, &scratch)?;
+                manifest::delete_manifest(&mut wtxn, database, writer.index)?;
+                wtxn.commit()?;
+                return Ok(());
             }
+            scratch.build::<R, P, M, M0>(&mut wtxn, self.rng, &self.inner)?;
+            manifest.committed += 1;
+            manifest::put_manifest(&mut wtxn, database, writer.index, &manifest)?;
+            wtxn.commit()?;
+            committed += 1;
+            debug!("committed the chunk {committed} of the rebuild of index {}", writer.index);
         }
+    }
 
-        drop(iter);
+    /// Replaces the links of the index with the graph built in the `scratch` index, which is
+    /// then cleared.
+    fn swap_graph(&self, wtxn: &mut RwTxn, scratch: &Writer<D>) -> Result<()> {
+        let writer = self.writer;
+        let database = writer.database;
+        debug!("swapping the rebuilt graph into index {}", writer.index);
+        clear_links(wtxn, database, writer.index)?;
+
+        // the links are moved, the other keys of the index are kept as is
+        migration::move_keys(wtxn, database, Prefix::links(scratch.index), writer.index)?;
+        let raw = database.remap_data_type::<Bytes>();
+        for key in [
+            Key::metadata(scratch.index),
+            Key::version(scratch.index),
+            Key::format(scratch.index),
+            Key::build(scratch.index),
+        ] {
+            let bytes = raw.get(wtxn, &key)?.map(<[u8]>::to_vec).ok_or(Error::missing_key(key))?;
+            let target = Key::new(writer.index, key.node);
+            raw.put(wtxn, &target, &bytes).map_err(Error::on_key("put", target))?;
+        }
+        codes::write(wtxn, database, writer.index, None)?;
+        density::write(wtxn, database, writer.index, self.inner.densities)?;
+        lsh::write(wtxn, database, writer.index, self.inner.lsh_bits, None)?;
+        scratch.clear(wtxn)
+    }
 
-        // We mark all the items as updated so
-        // the Writer::build method can handle them.
-        for item in new_items {
-            self.database.remap_data_type::<Unit>().put(
+    /// Generates the HNSW graph with the number of links and the `ef_construction` that
+    /// `preset` picks from the [`Writer::dataset_stats`] of the index, and returns them.
+    ///
+    /// The number of links per node is one of 8, 12, 16, 24, 32 or 48, with twice as many in
+    /// layer 0. This overrides any [`Self::ef_construction`] set before.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{BuildPreset, Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// let params = writer.builder(&mut rng).build_with_preset(&mut wtxn, BuildPreset::HighRecall)?;
+    /// println!("built with M={} and ef_construction={}", params.m, params.ef_construction);
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn build_with_preset(
+        &mut self,
+        wtxn: &mut RwTxn,
+        preset: BuildPreset,
+    ) -> Result<BuildParams>
+    where
+        P: steppe::Progress,
+    {
+        let params = preset.params(&self.writer.dataset_stats(wtxn)?);
+        self.inner.ef_construction = params.ef_construction;
+        self.build_with_links(wtxn, params.m)?;
+        Ok(params)
+    }
+
+    /// Deletes at most `batch` of the items that expired at `now`, see
+    /// [`Writer::set_expiration`], and builds the index to repair the graph around them. The
+    /// swept items are removed from the graph even when the writer soft deletes its items.
+    ///
+    /// Returns the number of swept items, call it again in new transactions until it returns `0`
+    /// to sweep everything that expired without keeping a write transaction for too long. The
+    /// expirations of the items that aren't in the index are dropped without being counted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// use std::time::SystemTime;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// let now = SystemTime::now();
+    /// loop {
+    ///     let mut wtxn = env.write_txn()?;
+    ///     let deleted = writer.builder(&mut rng).sweep_expired::<16, 32>(&mut wtxn, now, 10_000)?;
+    ///     wtxn.commit()?;
+    ///     if deleted == 0 {
+    ///         break;
+    ///     }
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn sweep_expired<const M: usize, const M0: usize>(
+        &mut self,
+        wtxn: &mut RwTxn,
+        now: SystemTime,
+        batch: u64,
+    ) -> Result<u64>
+    where
+        P: steppe::Progress,
+    {
+        let writer = self.writer;
+        let expired = writer.expired_items(wtxn, now)?;
+        let items = crate::preset::item_ids(wtxn, writer.database, writer.index)?
+            - writer.tombstones(wtxn)?;
+        // the expirations of the items that were never added or already deleted
+        for item in &expired - &items {
+            writer.clear_expiration(wtxn, item)?;
+        }
+
+        let mut deleted = 0;
+        for item in (expired & items).iter().take(batch as usize) {
+            writer.del_item(wtxn, item)?;
+            // a soft delete only tombstones the item, the build would leave it in the graph
+            if writer.soft_deletes {
+                writer.remove_item(wtxn, item)?;
+            }
+            deleted += 1;
+        }
+        if deleted > 0 {
+            info!("deleted {deleted} expired items");
+            self.build::<M, M0>(wtxn)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Removes the tombstoned items, see [`Writer::set_soft_deletes`], and builds the index to
+    /// repair the graph around all of them at once. Returns the number of removed items.
+    pub fn vacuum<const M: usize, const M0: usize>(&mut self, wtxn: &mut RwTxn) -> Result<u64>
+    where
+        P: steppe::Progress,
+    {
+        self.writer.check_lock(wtxn)?;
+        let tombstones = self.writer.tombstones(wtxn)?;
+        for item in &tombstones {
+            self.writer.remove_item(wtxn, item)?;
+        }
+        if !tombstones.is_empty() {
+            info!("vacuumed {} tombstoned items", tombstones.len());
+            self.build::<M, M0>(wtxn)?;
+        }
+        Ok(tombstones.len())
+    }
+
+    /// Runs a step of the background maintenance of the index: removes at most
+    /// [`Maintenance::budget`] of its tombstoned items, inspects the links of as many items of
+    /// the graph and builds the index to repair the graph around the removed items and re-insert
+    /// the ones with too many or too few links, see [`Maintenance`].
+    ///
+    /// Returns `None` without doing anything if the interval of the maintenance didn't elapse
+    /// since its last step, or if the index has changes that weren't built, which are left to the
+    /// builds of the application. The steps must be given the `M` and `M0` the index is built
+    /// with.
+    pub fn maintain<const M: usize, const M0: usize>(
+        &mut self,
+        wtxn: &mut RwTxn,
+        maintenance: &mut Maintenance,
+    ) -> Result<Option<MaintenanceStep>>
+    where
+        P: steppe::Progress,
+    {
+        let writer = self.writer;
+        writer.check_lock(wtxn)?;
+        if writer.need_build(wtxn)? || !maintenance.start_step(Instant::now()) {
+            return Ok(None);
+        }
+        let key = Key::metadata(writer.index);
+        let Some(metadata) = writer.database.remap_data_type::<MetadataCodec>().get(wtxn, &key)?
+        else {
+            return Ok(None);
+        };
+        let (inspected, wrapped) = maintenance.next_items(&metadata.items);
+        let unhealthy = maintenance::unhealthy_items(
+            wtxn,
+            writer.database,
+            writer.index,
+            &metadata,
+            &inspected,
+            M,
+            M0,
+        )?;
+
+        let tombstones = writer.tombstones(wtxn)?;
+        let vacuumed: RoaringBitmap =
+            tombstones.iter().take(maintenance.budget() as usize).collect();
+        for item in &vacuumed {
+            writer.remove_item(wtxn, item)?;
+        }
+        // the tombstoned items leave the graph with a later step
+        let repaired = unhealthy - &tombstones;
+        for item in &repaired {
+            let key = Key::updated(writer.index, item);
+            writer.database.remap_data_type::<Unit>().put(wtxn, &key, &())?;
+        }
+        if !vacuumed.is_empty() || !repaired.is_empty() {
+            debug!("maintenance of {} items and {} tombstones", repaired.len(), vacuumed.len());
+            self.build::<M, M0>(wtxn)?;
+        }
+
+        Ok(Some(MaintenanceStep {
+            vacuumed: vacuumed.len(),
+            inspected: inspected.len(),
+            repaired: repaired.len(),
+            wrapped,
+        }))
+    }
+
+    /// Converts an arroy db into a hannoy one.
+    #[cfg(any(test, feature = "arroy"))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "arroy")))]
+    pub fn prepare_arroy_conversion(&self, wtxn: &mut RwTxn) -> Result<()>
+    where
+        P: steppe::Progress,
+    {
+        self.writer.prepare_arroy_conversion(wtxn, &self.inner)
+    }
+}
+
+/// A writer to store new items, remove existing ones, and build the search
+/// index to query the nearest neighbors to items or vectors.
+#[derive(Debug)]
+pub struct Writer<D: Distance> {
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    /// The folder in which tempfile will write its temporary files.
+    tmpdir: Option<PathBuf>,
+    /// Whether vectors containing `NaN` or infinite values are accepted.
+    allow_non_finite: bool,
+    /// Whether the deleted items are only tombstoned until the next vacuum.
+    soft_deletes: bool,
+    /// The callbacks invoked on the changes, see [`Self::set_hooks`].
+    hooks: Hooks,
+    /// The token of the lock this writer holds on the index, see [`Self::lock`].
+    lock: Option<u64>,
+    /// The number of items the index is expected to grow to, see [`Self::reserve`].
+    reserved: u64,
+    /// The number of previous vectors kept for every item, see [`Self::set_versions`].
+    #[cfg(feature = "versioning")]
+    versions: u8,
+}
+
+/// The settings of an index the vectors are transformed with before being stored, which can
+/// only be set while the index is empty.
+#[derive(Debug)]
+struct IndexSettings {
+    /// The dimensions of the index written by its last build, the ones of the vectors it holds.
+    dimensions: Option<usize>,
+    /// The dimensions of the complete vectors, see [`Writer::set_matryoshka`].
+    full_dimensions: Option<usize>,
+    /// The weights of the dimensions, see [`Writer::set_weights`].
+    weights: Option<Vec<f32>>,
+}
+
+/// What the inserts of a call read once about the index before writing their items, see
+/// [`Writer::start_inserts`].
+struct Inserts {
+    /// Whether the changes of the index are logged, see [`Writer::set_change_log`].
+    change_log: bool,
+    /// Whether an inserted item can be tombstoned, see [`Writer::set_soft_deletes`].
+    tombstones: bool,
+    /// The settings the vectors are transformed with.
+    settings: IndexSettings,
+}
+
+impl<D: Distance> Writer<D> {
+    /// Creates a new writer from a database, index and dimensions.
+    pub fn new(database: Database<D>, index: u16, dimensions: usize) -> Writer<D> {
+        Writer {
+            database,
+            index,
+            dimensions,
+            tmpdir: None,
+            allow_non_finite: false,
+            soft_deletes: false,
+            hooks: Hooks::default(),
+            lock: None,
+            reserved: 0,
+            #[cfg(feature = "versioning")]
+            versions: 0,
+        }
+    }
+
+    /// After opening an arroy database this function will prepare it for conversion,
+    /// cleanup the arroy database and only keep the items/vectors entries.
+    #[cfg(any(test, feature = "arroy"))]
+    pub(crate) fn prepare_arroy_conversion<P: steppe::Progress>(
+        &self,
+        wtxn: &mut RwTxn,
+        options: &BuildOption<P>,
+    ) -> Result<()> {
+        use crate::node_id::{NodeId, NodeMode};
+        use crate::unaligned_vector::UnalignedVectorCodec;
+
+        debug!("Preparing dumpless upgrade from arroy to hannoy");
+        options.progress.update(HannoyBuild::ConvertingArroyToHannoy);
+
+        let mut iter = self
+            .database
+            .remap_key_type::<PrefixCodec>()
+            .prefix_iter_mut(wtxn, &Prefix::all(self.index))?
+            .remap_key_type::<KeyCodec>();
+
+        // binary quantized have len vec.len().div_ceil(64)*64 >= vec.len()
+        let word_size = <D::VectorCodec as UnalignedVectorCodec>::word_size();
+        let on_disk_dim: usize = self.dimensions.div_ceil(word_size) * word_size;
+
+        let mut new_items = RoaringBitmap::new();
+        while let Some(result) = iter.next() {
+            match result {
+                Ok((
+                    Key { index: _, node: NodeId { mode: NodeMode::Item, item, .. } },
+                    Node::Item(Item { header: _, vector }),
+                )) => {
+                    // We only take care of the entries that can be decoded as Node Items (vectors) and
+                    // mark them as newly inserted so the Writer::build method can compute the links for them.
+                    new_items.insert(item);
+                    if vector.len() != on_disk_dim {
+                        return Err(Error::InvalidDimensions {
+                            expected: on_disk_dim,
+                            got: vector.len(),
+                        });
+                    }
+                }
+                Ok((Key { .. }, _)) | Err(heed::Error::Decoding(_)) => unsafe {
+                    // Every other entry that fails to decode can be considered as something
+                    // else than an item, is useless for the conversion and is deleted.
+                    // SAFETY: Safe because we don't keep any references to the entry
+                    iter.del_current()?;
+                },
+                // If there is another error (lmdb...), it is returned.
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        drop(iter);
+
+        // We mark all the items as updated so
+        // the Writer::build method can handle them.
+        for item in new_items {
+            self.database.remap_data_type::<Unit>().put(
                 wtxn,
                 &Key::updated(self.index, item),
                 &(),
@@ -331,105 +1181,1163 @@ impl<D: Distance> Writer<D> {
                             )?
                         };
                     }
-                    Node::Links(_) => unreachable!("Node must not be a link"),
+                    Node::Links(_) => return Err(Error::unexpected_node(item_id, "Item")),
                 }
             }
 
-            drop(cursor);
+            drop(cursor);
+
+            for item in updated_items {
+                let key = Key::updated(self.index, item);
+                self.database.remap_types::<KeyCodec, Unit>().put(wtxn, &key, &())?;
+            }
+        }
+
+        let Writer {
+            database,
+            index,
+            dimensions,
+            tmpdir,
+            allow_non_finite,
+            soft_deletes,
+            hooks,
+            lock,
+            reserved,
+            #[cfg(feature = "versioning")]
+            versions,
+        } = self;
+        Ok(Writer {
+            database: database.remap_data_type(),
+            index,
+            dimensions,
+            tmpdir,
+            allow_non_finite,
+            soft_deletes,
+            hooks,
+            lock,
+            reserved,
+            #[cfg(feature = "versioning")]
+            versions,
+        })
+    }
+
+    /// Returns the index this writer writes in.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the number of dimensions of the vectors of this writer.
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Sets the path to the temporary directory where files are written.
+    pub fn set_tmpdir(&mut self, path: impl Into<PathBuf>) {
+        self.tmpdir = Some(path.into());
+    }
+
+    /// Accepts vectors containing `NaN` or infinite values when set to `true`.
+    ///
+    /// By default such vectors are rejected with [`Error::NonFiniteVector`] because a single
+    /// `NaN` makes every distance comparison involving it meaningless and silently degrades
+    /// the quality of the graph.
+    pub fn set_allow_non_finite(&mut self, allow: bool) {
+        self.allow_non_finite = allow;
+    }
+
+    /// Logs every addition, update and deletion of an item made through this writer when set to
+    /// `true`, so that other systems can follow the changes of the index, e.g. to invalidate
+    /// their caches or replicate it.
+    ///
+    /// Every change gets a sequence number that increases with every change of the index. The
+    /// consumers read the changes since the last sequence number they saw with
+    /// [`Self::changes`], and [`Self::truncate_changes`] drops the ones all of them consumed.
+    ///
+    /// The setting is stored in the index, every writer of the index follows it until it's
+    /// changed again.
+    pub fn set_change_log(&self, wtxn: &mut RwTxn, enabled: bool) -> Result<()> {
+        self.check_lock(wtxn)?;
+        change_log::set_enabled(wtxn, self.database, self.index, enabled)
+    }
+
+    /// Returns whether the changes of the index are logged, see [`Self::set_change_log`].
+    pub fn change_log(&self, rtxn: &RoTxn) -> Result<bool> {
+        change_log::is_enabled(rtxn, self.database, self.index)
+    }
+
+    /// Only tombstones the items deleted through this writer when set to `true`: they are
+    /// excluded from the searches right away but stay in the graph, and the reader doesn't need
+    /// a build to see the deletions. [`HannoyBuilder::vacuum`] later removes the tombstoned
+    /// items and repairs the graph around all of them at once, which is much cheaper than
+    /// repairing it after every batch of deletions when the items churn a lot.
+    ///
+    /// The tombstoned items still take space and are still traversed by the searches, the
+    /// index should be vacuumed once they are a noticeable share of the items.
+    pub fn set_soft_deletes(&mut self, enabled: bool) {
+        self.soft_deletes = enabled;
+    }
+
+    /// Tells the builds that the index is expected to grow to `items` items, e.g. at the start
+    /// of a large import, so that they size the adjacency lists of the graph they keep in memory
+    /// for all of them at once instead of growing them build after build.
+    ///
+    /// See [`Self::reserve_map_size`] to also make room for them in the LMDB map.
+    pub fn reserve(&mut self, items: u64) {
+        self.reserved = items;
+    }
+
+    /// Grows the LMDB map of the `env` so that it fits `items` more items linked to `m`
+    /// neighbours on top of the pages already used, and returns the new map size. The map is
+    /// never shrunk.
+    ///
+    /// A full map aborts the transaction of an import, reserving the space upfront avoids
+    /// growing it in the middle, see [`write_with_map_growth`](crate::write_with_map_growth).
+    ///
+    /// # Safety
+    ///
+    /// The map of an environment can only be resized while no transaction is alive. The caller
+    /// must ensure that no thread of the process holds a read or a write transaction of the
+    /// `env`.
+    pub unsafe fn reserve_map_size<Tls>(
+        &self,
+        env: &Env<Tls>,
+        items: u64,
+        m: usize,
+    ) -> Result<usize> {
+        let size = env.info().map_size;
+        let used = env.non_free_pages_size()?;
+        let needed = used + crate::memory::serving_bytes::<D>(items, self.dimensions, m);
+        let needed = (needed as usize).next_multiple_of(page_size::get());
+        if needed <= size {
+            return Ok(size);
+        }
+        debug!("growing the map of {size} bytes to {needed} bytes for {items} items");
+        env.resize(needed)?;
+        Ok(needed)
+    }
+
+    /// Invokes the `hooks` on the items this writer adds and deletes and on its builds, see
+    /// [`WriterHooks`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, WriterHooks, distances::Euclidean};
+    /// # let (mut writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use std::sync::Arc;
+    ///
+    /// struct Invalidate;
+    ///
+    /// impl WriterHooks for Invalidate {
+    ///     fn on_delete(&self, index: u16, item: u32) {
+    ///         println!("evict item {item} of index {index} from the cache");
+    ///     }
+    /// }
+    ///
+    /// writer.set_hooks(Arc::new(Invalidate));
+    /// writer.del_item(&mut wtxn, 42)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn set_hooks(&mut self, hooks: Arc<dyn WriterHooks>) {
+        self.hooks = Hooks::new(hooks);
+    }
+
+    /// Locks the index for this writer: the other writers of the index fail with
+    /// [`Error::IndexLocked`] when they change it in any way until it is unlocked, as do the
+    /// [`swap_indexes`](crate::swap_indexes) of the index.
+    ///
+    /// LMDB serializes the write transactions but not the writers, two processes or threads
+    /// interleaving their changes and builds in the same index would mix two graphs. The lock
+    /// is stored in the index and committed with the transaction. It survives the writer and
+    /// even the process, [`Self::unlock`] must be called once done with the index.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (mut writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// let mut wtxn = env.write_txn()?;
+    /// writer.lock(&mut wtxn)?;
+    /// wtxn.commit()?;
+    ///
+    /// // ... add, delete and build in other transactions
+    ///
+    /// let mut wtxn = env.write_txn()?;
+    /// writer.unlock(&mut wtxn)?;
+    /// wtxn.commit()?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn lock(&mut self, wtxn: &mut RwTxn) -> Result<()> {
+        self.check_lock(wtxn)?;
+        let token = self.lock.unwrap_or_else(lock_token);
+        let key = Key::write_lock(self.index);
+        self.database
+            .remap_data_type::<U64<BigEndian>>()
+            .put(wtxn, &key, &token)
+            .map_err(Error::on_key("put", key))?;
+        self.lock = Some(token);
+        Ok(())
+    }
+
+    /// Removes the lock this writer holds on the index and returns whether there was one. Fails
+    /// with [`Error::IndexLocked`] if another writer holds it, see [`Self::break_lock`].
+    pub fn unlock(&mut self, wtxn: &mut RwTxn) -> Result<bool> {
+        self.check_lock(wtxn)?;
+        self.break_lock(wtxn)
+    }
+
+    /// Removes the lock of the index, whichever writer holds it, and returns whether there was
+    /// one. This is how the lock of a crashed process is released.
+    pub fn break_lock(&mut self, wtxn: &mut RwTxn) -> Result<bool> {
+        self.lock = None;
+        let key = Key::write_lock(self.index);
+        self.database.delete(wtxn, &key).map_err(Error::on_key("delete", key))
+    }
+
+    /// Fails if another writer locked the index.
+    fn check_lock(&self, rtxn: &RoTxn) -> Result<()> {
+        check_lock(rtxn, self.database, self.index, self.lock)
+    }
+
+    /// Returns a writer of the free `index` a graph is rebuilt in, see
+    /// [`HannoyBuilder::rebuild_in_chunks`].
+    fn scratch(&self, index: u16) -> Writer<D> {
+        Writer {
+            index,
+            tmpdir: self.tmpdir.clone(),
+            soft_deletes: false,
+            hooks: Hooks::default(),
+            lock: None,
+            ..*self
+        }
+    }
+
+    /// Returns the items that were deleted but not vacuumed yet, see [`Self::set_soft_deletes`].
+    pub fn tombstones(&self, rtxn: &RoTxn) -> Result<RoaringBitmap> {
+        tombstones(rtxn, self.database, self.index)
+    }
+
+    /// Keeps the `versions` previous vectors of every item when they are replaced or deleted,
+    /// so that [`Self::point_in_time`] can show the items as they were at an older sequence
+    /// number of the change log, e.g. to reproduce an offline experiment against production data.
+    ///
+    /// The versions are only kept while the change log is enabled, see
+    /// [`Self::set_change_log`], and a version older than the `versions` last ones of its item
+    /// is overwritten. Defaults to `0`, no versions are kept.
+    #[cfg(feature = "versioning")]
+    pub fn set_versions(&mut self, versions: u8) {
+        self.versions = versions;
+    }
+
+    /// Returns a view of the items of this index as they were before the change of the given
+    /// `sequence` number was made, according to the versions kept since [`Self::set_versions`].
+    ///
+    /// Returns [`Error::VersionPruned`] if an item changed since and its version at that point
+    /// was already overwritten.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, rtxn): (Writer<Euclidean>, heed::RoTxn) = todo!();
+    /// let sequence = 1200;
+    /// let view = writer.point_in_time(&rtxn, sequence)?;
+    /// let nns = view.nns(&rtxn, &[0.3, 0.1, 0.8], 10)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    #[cfg(feature = "versioning")]
+    pub fn point_in_time(&self, rtxn: &RoTxn, sequence: u32) -> Result<PointInTime<D>> {
+        PointInTime::new(rtxn, self.database, self.index, self.dimensions, sequence)
+    }
+
+    /// Returns the logged changes whose sequence number is at least `since`, in order.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{ChangeKind, Writer, distances::Euclidean};
+    /// # let (writer, rtxn): (Writer<Euclidean>, heed::RoTxn) = todo!();
+    /// let mut next = 0;
+    /// for change in writer.changes(&rtxn, next)? {
+    ///     if change.kind == ChangeKind::Deleted {
+    ///         println!("item {} was deleted", change.item);
+    ///     }
+    ///     next = change.sequence + 1;
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn changes(&self, rtxn: &RoTxn, since: u32) -> Result<Vec<Change>> {
+        change_log::changes(rtxn, self.database, self.index, since)
+    }
+
+    /// Deletes the logged changes whose sequence number is lower than `before` and returns how
+    /// many were deleted. The following changes keep increasing from the last sequence number.
+    pub fn truncate_changes(&self, wtxn: &mut RwTxn, before: u32) -> Result<u64> {
+        self.check_lock(wtxn)?;
+        change_log::truncate(wtxn, self.database, self.index, before)
+    }
+
+    /// Makes this index a replica of the `leader` index, whose change log must be enabled, by
+    /// applying the changes the leader logged since the last call, and returns how many changes
+    /// were applied.
+    ///
+    /// The vectors are read from the `leader_rtxn`, usually a read transaction of another
+    /// environment, and only the last state of every changed item is applied. They are copied as
+    /// the leader stored them, already transformed by its weights, projection or Matryoshka
+    /// prefix, which the replica must share. The replica must then be built to update its graph
+    /// incrementally. The position of the replica in the log is
+    /// stored with its items, so that it resumes from there after a restart.
+    ///
+    /// Returns [`Error::ChangesTruncated`] if the leader truncated changes the replica didn't
+    /// apply, it must then be cleared and fully rebuilt.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (leader, replica, leader_env, replica_env): (Writer<Euclidean>, Writer<Euclidean>, heed::Env, heed::Env) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let leader_rtxn = leader_env.read_txn()?;
+    /// let mut wtxn = replica_env.write_txn()?;
+    /// if replica.apply_changes(&mut wtxn, &leader, &leader_rtxn)? > 0 {
+    ///     replica.builder(&mut StdRng::seed_from_u64(7)).build::<16, 32>(&mut wtxn)?;
+    /// }
+    /// wtxn.commit()?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn apply_changes(
+        &self,
+        wtxn: &mut RwTxn,
+        leader: &Writer<D>,
+        leader_rtxn: &RoTxn,
+    ) -> Result<u64> {
+        let applied = Key::applied_sequence(self.index);
+        let expected = change_log::next_sequence(wtxn, self.database, applied)?;
+        let changes = leader.changes(leader_rtxn, expected)?;
+        let first = match changes.first() {
+            Some(change) => change.sequence,
+            None => change_log::next_sequence(
+                leader_rtxn,
+                leader.database,
+                Key::change_sequence(leader.index),
+            )?,
+        };
+        if first > expected {
+            return Err(Error::ChangesTruncated { expected, first });
+        }
+
+        if leader.dimensions != self.dimensions {
+            return Err(Error::InvalidDimensions {
+                expected: self.dimensions,
+                got: leader.dimensions,
+            });
+        }
+        let inserts = self.start_inserts(wtxn)?;
+        let changed: RoaringBitmap = changes.iter().map(|change| change.item).collect();
+        for item in changed {
+            let stored = match is_tombstoned(leader_rtxn, leader.database, leader.index, item)? {
+                true => None,
+                false => get_item(leader.database, leader.index, leader_rtxn, item)?,
+            };
+            let Some(stored) = stored else {
+                self.del_item(wtxn, item)?;
+                continue;
+            };
+            self.put_item(wtxn, &inserts, item, stored)?;
+
+            // the complete vector of a Matryoshka index is copied as it is stored too
+            let leader_key = Key::full_vector(leader.index, item);
+            let key = Key::full_vector(self.index, item);
+            let full_vector = leader
+                .database
+                .remap_data_type::<Bytes>()
+                .get(leader_rtxn, &leader_key)
+                .map_err(Error::on_key("get", leader_key))?;
+            match full_vector {
+                Some(bytes) => self
+                    .database
+                    .remap_data_type::<Bytes>()
+                    .put(wtxn, &key, bytes)
+                    .map_err(Error::on_key("put", key))?,
+                None => {
+                    self.database.delete(wtxn, &key).map_err(Error::on_key("delete", key))?;
+                }
+            }
+            self.database.remap_data_type::<Unit>().put(
+                wtxn,
+                &Key::updated(self.index, item),
+                &(),
+            )?;
+        }
+
+        if let Some(last) = changes.last() {
+            change_log::set_next_sequence(wtxn, self.database, applied, last.sequence + 1)?;
+        }
+        Ok(changes.len() as u64)
+    }
+
+    /// Returns the sequence number of the next change of its leader this replica will apply, see
+    /// [`Self::apply_changes`].
+    pub fn next_change_to_apply(&self, rtxn: &RoTxn) -> Result<u32> {
+        change_log::next_sequence(rtxn, self.database, Key::applied_sequence(self.index))
+    }
+
+    /// Returns `true` if the index is empty.
+    pub fn is_empty(&self, rtxn: &RoTxn) -> Result<bool> {
+        self.iter(rtxn).map(|mut iter| iter.next().is_none())
+    }
+
+    /// Returns the statistics of the items of the index the [`BuildPreset`]s are chosen from.
+    ///
+    /// The intrinsic dimension is the maximum likelihood estimate of Levina and Bickel over the
+    /// euclidean distances between the vectors of at most a thousand items spread over the index.
+    pub fn dataset_stats(&self, rtxn: &RoTxn) -> Result<DatasetStats> {
+        crate::preset::dataset_stats(rtxn, self.database, self.index, self.dimensions)
+    }
+
+    /// Returns an estimation of the bytes resident while building the items of the index,
+    /// pending or not, with `m` links per node.
+    ///
+    /// Besides the pages read by the searches, see
+    /// [`Reader::estimated_ram_for_serving`](crate::Reader::estimated_ram_for_serving), a build
+    /// keeps the adjacency lists of every layer in memory.
+    pub fn estimated_build_memory(&self, rtxn: &RoTxn, m: usize) -> Result<u64> {
+        let items = crate::preset::item_ids(rtxn, self.database, self.index)?.len();
+        Ok(crate::memory::build_bytes::<D>(items, self.dimensions, m))
+    }
+
+    /// Returns the bytes of the keys and values of the index.
+    fn written_bytes(&self, rtxn: &RoTxn) -> Result<u64> {
+        let prefix = Prefix::all(self.index);
+        let mut bytes = 0;
+        for result in self
+            .database
+            .remap_types::<PrefixCodec, Bytes>()
+            .prefix_iter(rtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
+            .remap_key_type::<Bytes>()
+        {
+            let (key, value) = result?;
+            bytes += (key.len() + value.len()) as u64;
+        }
+        Ok(bytes)
+    }
+
+    /// Returns the number of items added, updated or deleted since the last build.
+    pub fn pending_changes(&self, rtxn: &RoTxn) -> Result<u64> {
+        let prefix = Prefix::updated(self.index);
+        let mut pending = 0;
+        for result in self
+            .database
+            .remap_types::<PrefixCodec, DecodeIgnore>()
+            .prefix_iter(rtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
+            .remap_key_type::<DecodeIgnore>()
+        {
+            let ((), ()) = result?;
+            pending += 1;
+        }
+        Ok(pending)
+    }
+
+    /// Returns `true` if the index needs to be built before being able to read in it.
+    ///
+    /// See [`Reader::is_built`](crate::Reader::is_built) to check it without a writer.
+    pub fn need_build(&self, rtxn: &RoTxn) -> Result<bool> {
+        Reader::is_built(rtxn, self.index, self.database).map(|built| !built)
+    }
+
+    /// Returns the id of the last build of the index, the one its commit publishes to the
+    /// readers, `None` if it was never built.
+    ///
+    /// See [`Reader::published_build_id`](crate::Reader::published_build_id) to get it without
+    /// a writer.
+    pub fn build_id(&self, rtxn: &RoTxn) -> Result<Option<u64>> {
+        Reader::published_build_id(rtxn, self.index, self.database)
+    }
+
+    /// Returns the progress of an operation spanning several transactions that was interrupted
+    /// before its last commit, e.g. by a crash, `None` if every such operation finished.
+    ///
+    /// Nothing else checks it, call it when opening the environment: the index is consistent as
+    /// of the last committed transaction of the operation, which can be resumed by running it
+    /// again or rolled back with [`Self::roll_back_ingest`]. The readers and the other writes of
+    /// the index aren't blocked in the meantime, only [`HannoyBuilder::ingest_in_transactions`],
+    /// [`HannoyBuilder::build_in_transactions`] and [`HannoyBuilder::rebuild_in_chunks`] fail
+    /// with [`Error::UnfinishedIngest`] if one of the others was interrupted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// let mut wtxn = env.write_txn()?;
+    /// if let Some(manifest) = writer.unfinished_ingest(&wtxn)? {
+    ///     println!("rolling back the {} items of an interrupted {:?}", manifest.added.len(), manifest.kind);
+    ///     writer.roll_back_ingest(&mut wtxn)?;
+    /// }
+    /// wtxn.commit()?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn unfinished_ingest(&self, rtxn: &RoTxn) -> Result<Option<IngestManifest>> {
+        manifest::get_manifest(rtxn, self.database, self.index)
+    }
+
+    /// Deletes the items added by an interrupted operation, see [`Self::unfinished_ingest`],
+    /// and returns its manifest, `None` if there was none.
+    ///
+    /// The deletions are pending until the next build. The changes built by an interrupted
+    /// [`HannoyBuilder::build_in_transactions`] are kept, the others stay pending. The graph of
+    /// an interrupted [`HannoyBuilder::rebuild_in_chunks`] is dropped and the index keeps its
+    /// previous one.
+    pub fn roll_back_ingest(&self, wtxn: &mut RwTxn) -> Result<Option<IngestManifest>> {
+        self.check_lock(wtxn)?;
+        let Some(manifest) = manifest::get_manifest(wtxn, self.database, self.index)? else {
+            return Ok(None);
+        };
+        self.del_items(wtxn, &manifest.added)?;
+        if let Some(scratch) = manifest.scratch {
+            self.scratch(scratch).clear(wtxn)?;
+            mark_updated(wtxn, self.database, self.index, &manifest.pending)?;
+        }
+        manifest::delete_manifest(wtxn, self.database, self.index)?;
+        Ok(Some(manifest))
+    }
+
+    /// Returns an `Option`al vector previous stored in this database.
+    pub fn item_vector(&self, rtxn: &RoTxn, item: ItemId) -> Result<Option<Vec<f32>>> {
+        if is_tombstoned(rtxn, self.database, self.index, item)? {
+            return Ok(None);
+        }
+        Ok(get_item(self.database, self.index, rtxn, item)?.map(|item| {
+            let mut vec = item.vector.to_vec();
+            vec.truncate(self.dimensions);
+            vec
+        }))
+    }
+
+    /// Returns `true` if the database contains the given item, tombstoned items excluded.
+    pub fn contains_item(&self, rtxn: &RoTxn, item: ItemId) -> Result<bool> {
+        let stored = self
+            .database
+            .remap_data_type::<DecodeIgnore>()
+            .get(rtxn, &Key::item(self.index, item))?
+            .is_some();
+        Ok(stored && !is_tombstoned(rtxn, self.database, self.index, item)?)
+    }
+
+    /// Returns an iterator over the items vector.
+    pub fn iter<'t>(&self, rtxn: &'t RoTxn) -> Result<ItemIter<'t, D>> {
+        Ok(ItemIter::new(self.database, self.index, self.dimensions, rtxn)?)
+    }
+
+    /// Add an item associated to a vector in the database.
+    ///
+    /// If the item already exists its vector is replaced. On the next build the old links of
+    /// the item are dropped, it is re-inserted in the graph and its former neighbours are
+    /// repaired.
+    pub fn add_item(&self, wtxn: &mut RwTxn, item: ItemId, vector: &[f32]) -> Result<()> {
+        let inserts = self.start_inserts(wtxn)?;
+        let settings = &inserts.settings;
+        let full = settings.full_dimensions;
+        let full_vector = vector;
+        let prefix = matryoshka::prefix(full, self.dimensions, full_vector)?;
+        let projection = projection::get_projection_view(wtxn, self.database, self.index)?;
+        let vector = projection::apply_view(projection.as_ref(), prefix)?;
+        self.check_vector(item, &vector)?;
+        if full.is_some() {
+            self.check_vector_values(item, full_vector)?;
+        }
+
+        let vector = weights::apply(settings.weights.as_deref(), D::preprocess(&vector));
+        let vector = UnalignedVector::from_slice(&vector);
+        let db_item = Item { header: D::new_header(&vector), vector };
+        self.put_item(wtxn, &inserts, item, db_item)?;
+        if full.is_some() {
+            matryoshka::put_full_vector(wtxn, self.database, self.index, item, full_vector)?;
+        }
+        self.database.remap_data_type::<Unit>().put(wtxn, &Key::updated(self.index, item), &())?;
+
+        Ok(())
+    }
+
+    /// Add many items associated to their vectors in the database.
+    ///
+    /// The items are sorted by id before being written so that LMDB ends up appending to
+    /// neighbouring pages instead of jumping around the B-tree, which is much faster than
+    /// calling [`Self::add_item`] in a loop with unordered ids. If the same id appears several
+    /// times the last vector wins, exactly like successive calls to [`Self::add_item`].
+    ///
+    /// All the vectors are validated before anything is written.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// let vectors = vec![(2, vec![0.0, 1.0]), (0, vec![1.0, 0.0]), (1, vec![1.0, 1.0])];
+    /// writer.add_items(&mut wtxn, vectors.iter().map(|(id, v)| (*id, v.as_slice())))?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn add_items<'v>(
+        &self,
+        wtxn: &mut RwTxn,
+        items: impl IntoIterator<Item = (ItemId, &'v [f32])>,
+    ) -> Result<()> {
+        let inserts = self.start_inserts(wtxn)?;
+        let settings = &inserts.settings;
+        let full = settings.full_dimensions;
+        let projection = projection::get_projection_view(wtxn, self.database, self.index)?;
+        let mut items = items
+            .into_iter()
+            .map(|(item, full_vector)| {
+                let prefix = matryoshka::prefix(full, self.dimensions, full_vector)?;
+                Ok((item, projection::apply_view(projection.as_ref(), prefix)?, full_vector))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (item, vector, full_vector) in &items {
+            self.check_vector(*item, vector)?;
+            if full.is_some() {
+                self.check_vector_values(*item, full_vector)?;
+            }
+        }
+
+        // A stable sort keeps the insertion order of duplicates, we then keep the last one.
+        items.sort_by_key(|(item, _, _)| *item);
+        items.reverse();
+        items.dedup_by_key(|(item, _, _)| *item);
+        items.reverse();
+
+        // the previous versions record the sequence of the change that replaced them, the items
+        // are then logged one by one like in `Self::del_items`
+        #[cfg(feature = "versioning")]
+        let log_at_once = inserts.change_log && self.versions == 0;
+        #[cfg(not(feature = "versioning"))]
+        let log_at_once = inserts.change_log;
+        let mut changes = Vec::new();
+        for (item, vector, full_vector) in &items {
+            let item = *item;
+            let vector = weights::apply(settings.weights.as_deref(), D::preprocess(vector));
+            let vector = UnalignedVector::from_slice(&vector);
+            let db_item = Item { header: D::new_header(&vector), vector };
+            if log_at_once {
+                changes.push((item, self.change_kind(wtxn, item)?));
+                self.write_item(wtxn, &inserts, item, db_item)?;
+            } else {
+                self.put_item(wtxn, &inserts, item, db_item)?;
+            }
+            if full.is_some() {
+                matryoshka::put_full_vector(wtxn, self.database, self.index, item, full_vector)?;
+            }
+        }
+
+        if log_at_once {
+            change_log::record_all(wtxn, self.database, self.index, changes)?;
+        }
+        let updated = self.database.remap_data_type::<Unit>();
+        for &(item, _, _) in &items {
+            updated.put(wtxn, &Key::updated(self.index, item), &())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reduces the dimensions of the vectors added to the index and of its queries with a
+    /// `projection`, the vectors are then given with the [`Projection::input_dimensions`] and
+    /// stored with the [`Projection::output_dimensions`], which must be the dimensions of the
+    /// index.
+    ///
+    /// The projection applies to the vectors as they are added, it must be set while the index
+    /// is empty and can't be changed afterward. The [`PreparedQuery`](crate::PreparedQuery)s and
+    /// the vectors read back from the index are projected ones.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Projection, Writer, distances::Cosine};
+    /// # let (mut wtxn, database): (heed::RwTxn, hannoy::Database<Cosine>) = todo!();
+    /// let writer = Writer::new(database, 0, 256);
+    /// let projection = Projection::random_orthogonal(3072, 256, &mut rand::thread_rng())?;
+    /// writer.set_projection(&mut wtxn, &projection)?;
+    /// writer.add_item(&mut wtxn, 0, &[0.5; 3072])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn set_projection(&self, wtxn: &mut RwTxn, projection: &Projection) -> Result<()> {
+        self.check_lock(wtxn)?;
+        if projection.output_dimensions() != self.dimensions {
+            let reason = format!(
+                "the projection outputs {} dimensions but the index has {}",
+                projection.output_dimensions(),
+                self.dimensions
+            );
+            return Err(Error::InvalidProjection { reason });
+        }
+        if !self.is_empty(wtxn)? {
+            let reason = "the projection must be set before adding the items".to_string();
+            return Err(Error::InvalidProjection { reason });
+        }
+        if matryoshka::get_full_dimensions(wtxn, self.database, self.index)?.is_some() {
+            let reason = "the index already indexes the prefixes of the vectors".to_string();
+            return Err(Error::InvalidProjection { reason });
+        }
+        projection::put_projection(wtxn, self.database, self.index, projection)
+    }
+
+    /// Returns the projection of the vectors of the index, if it was set.
+    pub fn projection(&self, rtxn: &RoTxn) -> Result<Option<Projection>> {
+        projection::get_projection(rtxn, self.database, self.index)
+    }
+
+    /// Makes the index a Matryoshka one: the vectors are given with `full_dimensions` and only
+    /// their prefixes of the dimensions of the index are stored in the graph, which makes the
+    /// insertions and the traversals of the searches cheaper. The complete vectors are kept
+    /// aside and re-rank the candidates of the searches, see
+    /// [`QueryBuilder::rerank`](crate::QueryBuilder::rerank).
+    ///
+    /// It suits the embeddings trained with Matryoshka Representation Learning, whose first
+    /// dimensions carry most of their meaning. Like a [`Projection`], it must be set while the
+    /// index is empty, can't be changed afterward and the queries are given with the full
+    /// dimensions.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Cosine};
+    /// # let (mut wtxn, database): (heed::RwTxn, hannoy::Database<Cosine>) = todo!();
+    /// let writer = Writer::new(database, 0, 256);
+    /// writer.set_matryoshka(&mut wtxn, 1024)?;
+    /// writer.add_item(&mut wtxn, 0, &[0.5; 1024])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn set_matryoshka(&self, wtxn: &mut RwTxn, full_dimensions: usize) -> Result<()> {
+        self.check_lock(wtxn)?;
+        if full_dimensions < self.dimensions {
+            let reason = format!(
+                "the vectors have {full_dimensions} dimensions, less than the {} of the prefixes",
+                self.dimensions
+            );
+            return Err(Error::InvalidMatryoshka { reason });
+        }
+        if !self.is_empty(wtxn)? {
+            let reason = "the prefixes must be set before adding the items".to_string();
+            return Err(Error::InvalidMatryoshka { reason });
+        }
+        if projection::get_projection_view(wtxn, self.database, self.index)?.is_some() {
+            let reason = "the index already projects its vectors".to_string();
+            return Err(Error::InvalidMatryoshka { reason });
+        }
+        matryoshka::put_full_dimensions(wtxn, self.database, self.index, full_dimensions)
+    }
+
+    /// Returns the settings the vectors are transformed with, read from the index.
+    fn settings(&self, rtxn: &RoTxn) -> Result<IndexSettings> {
+        // only the weighted distance can have weights, see `Writer::set_weights`
+        let weights = match TypeId::of::<D>() == TypeId::of::<WeightedEuclidean>() {
+            true => weights::get_weights(rtxn, self.database, self.index)?,
+            false => None,
+        };
+        let full_dimensions = matryoshka::get_full_dimensions(rtxn, self.database, self.index)?;
+        let dimensions = self
+            .database
+            .remap_data_type::<MetadataDimensionsCodec>()
+            .get(rtxn, &Key::metadata(self.index))?
+            .map(|dimensions| dimensions as usize);
+        Ok(IndexSettings { dimensions, full_dimensions, weights })
+    }
+
+    /// Makes sure this writer has the dimensions of the index it writes to, a writer created
+    /// with the wrong dimensions would otherwise mix vectors of different lengths in the graph.
+    fn check_dimensions(&self, settings: &IndexSettings) -> Result<()> {
+        match settings.dimensions {
+            Some(expected) if expected != self.dimensions => {
+                Err(Error::InvalidDimensions { expected, got: self.dimensions })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the dimensions of the complete vectors of a Matryoshka index, see
+    /// [`Self::set_matryoshka`].
+    pub fn matryoshka(&self, rtxn: &RoTxn) -> Result<Option<usize>> {
+        matryoshka::get_full_dimensions(rtxn, self.database, self.index)
+    }
+
+    /// Records the distribution of the current vectors of the index as the baseline
+    /// [`Reader::quantizer_drift`] compares the vectors to afterward.
+    ///
+    /// The baseline is the mean and the standard deviation of every dimension as the vectors
+    /// are stored. It doesn't change how the vectors are quantized: the quantized distances
+    /// only store the codes, a drift the codes lose recall to is only recovered by inserting
+    /// the vectors again in a new index. It goes through all the items, run it in a write
+    /// transaction of its own, e.g. on a background thread or with an
+    /// [`AsyncWriter`](crate::AsyncWriter), to not hold up the other writes.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, Writer, distances::BinaryQuantizedCosine};
+    /// # let (writer, reader, mut wtxn): (Writer<BinaryQuantizedCosine>, Reader<BinaryQuantizedCosine>, heed::RwTxn) = todo!();
+    /// if reader.quantizer_drift(&wtxn)?.is_none() {
+    ///     writer.recalibrate_drift_baseline(&mut wtxn)?;
+    /// }
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn recalibrate_drift_baseline(&self, wtxn: &mut RwTxn) -> Result<()> {
+        self.check_lock(wtxn)?;
+        let stats = calibration::item_stats(wtxn, self.database, self.index, self.dimensions)?;
+        calibration::put_calibration(wtxn, self.database, self.index, &stats)
+    }
+
+    /// Makes sure this writer can insert items in the index and reads what their writes need to
+    /// know about it, once per call before writing its items with [`Self::put_item`].
+    fn start_inserts(&self, rtxn: &RoTxn) -> Result<Inserts> {
+        self.check_lock(rtxn)?;
+        let settings = self.settings(rtxn)?;
+        self.check_dimensions(&settings)?;
+        let change_log = change_log::is_enabled(rtxn, self.database, self.index)?;
+        let tombstones = self.soft_deletes || has_tombstones(rtxn, self.database, self.index)?;
+        Ok(Inserts { change_log, tombstones, settings })
+    }
+
+    /// Returns whether writing an item adds it or updates it, to log the change.
+    fn change_kind(&self, rtxn: &RoTxn, item: ItemId) -> Result<ChangeKind> {
+        Ok(if self.contains_item(rtxn, item)? { ChangeKind::Updated } else { ChangeKind::Added })
+    }
+
+    /// Writes the vector of an item and logs the change if the change log is enabled.
+    fn put_item(
+        &self,
+        wtxn: &mut RwTxn,
+        inserts: &Inserts,
+        item: ItemId,
+        db_item: Item<D>,
+    ) -> Result<()> {
+        if inserts.change_log {
+            let kind = self.change_kind(wtxn, item)?;
+            #[cfg(feature = "versioning")]
+            if self.versions > 0 {
+                versioning::record(wtxn, self.database, self.index, item, self.versions)?;
+            }
+            change_log::record(wtxn, self.database, self.index, item, kind)?;
+        }
+        self.write_item(wtxn, inserts, item, db_item)
+    }
+
+    /// Writes the vector of an item without logging the change, see [`Self::put_item`].
+    fn write_item(
+        &self,
+        wtxn: &mut RwTxn,
+        inserts: &Inserts,
+        item: ItemId,
+        db_item: Item<D>,
+    ) -> Result<()> {
+        // an item added again after being tombstoned is alive again
+        if inserts.tombstones {
+            self.database.delete(wtxn, &Key::tombstone(self.index, item))?;
+        }
+        let key = Key::item(self.index, item);
+        self.database.put(wtxn, &key, &Node::Item(db_item)).map_err(Error::on_key("put", key))?;
+        self.hooks.call(|hooks| hooks.on_insert(self.index, item));
+        Ok(())
+    }
 
-            for item in updated_items {
-                let key = Key::updated(self.index, item);
-                self.database.remap_types::<KeyCodec, Unit>().put(wtxn, &key, &())?;
+    /// Makes sure a vector can be stored in this index.
+    fn check_vector(&self, item: ItemId, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.dimensions {
+            return Err(Error::InvalidDimensions { expected: self.dimensions, got: vector.len() });
+        }
+        self.check_vector_values(item, vector)
+    }
+
+    /// Makes sure the values of a vector are finite unless [`Self::allow_non_finite`] is set.
+    fn check_vector_values(&self, item: ItemId, vector: &[f32]) -> Result<()> {
+        if !self.allow_non_finite {
+            if let Some(position) = vector.iter().position(|x| !x.is_finite()) {
+                return Err(Error::NonFiniteVector { item, position, value: vector[position] });
             }
         }
 
-        let Writer { database, index, dimensions, tmpdir } = self;
-        Ok(Writer { database: database.remap_data_type(), index, dimensions, tmpdir })
+        Ok(())
     }
 
-    /// Sets the path to the temporary directory where files are written.
-    pub fn set_tmpdir(&mut self, path: impl Into<PathBuf>) {
-        self.tmpdir = Some(path.into());
+    /// Makes an item expire at the instant `at`, replacing its previous expiration.
+    ///
+    /// Expired items are still returned by the searches until they are deleted by
+    /// [`HannoyBuilder::sweep_expired`].
+    pub fn set_expiration(&self, wtxn: &mut RwTxn, item: ItemId, at: SystemTime) -> Result<()> {
+        self.check_lock(wtxn)?;
+        let key = Key::expiration(self.index, item);
+        self.database
+            .remap_data_type::<U64<BigEndian>>()
+            .put(wtxn, &key, &unix_millis(at))
+            .map_err(Error::on_key("put", key))
     }
 
-    /// Returns `true` if the index is empty.
-    pub fn is_empty(&self, rtxn: &RoTxn) -> Result<bool> {
-        self.iter(rtxn).map(|mut iter| iter.next().is_none())
+    /// Removes the expiration of an item and returns `true` if it had one.
+    pub fn clear_expiration(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<bool> {
+        self.check_lock(wtxn)?;
+        let key = Key::expiration(self.index, item);
+        self.database.delete(wtxn, &key).map_err(Error::on_key("delete", key))
     }
 
-    /// Returns `true` if the index needs to be built before being able to read in it.
-    pub fn need_build(&self, rtxn: &RoTxn) -> Result<bool> {
-        Ok(self
+    /// Returns the instant an item expires at, if it was given one.
+    pub fn expiration(&self, rtxn: &RoTxn, item: ItemId) -> Result<Option<SystemTime>> {
+        let key = Key::expiration(self.index, item);
+        let millis = self
             .database
-            .remap_types::<PrefixCodec, DecodeIgnore>()
-            .prefix_iter(rtxn, &Prefix::updated(self.index))?
-            .remap_key_type::<KeyCodec>()
-            .next()
-            .is_some()
-            || self
-                .database
-                .remap_data_type::<DecodeIgnore>()
-                .get(rtxn, &Key::metadata(self.index))?
-                .is_none())
+            .remap_data_type::<U64<BigEndian>>()
+            .get(rtxn, &key)
+            .map_err(Error::on_key("get", key))?;
+        Ok(millis.map(|millis| UNIX_EPOCH + Duration::from_millis(millis)))
     }
 
-    /// Returns an `Option`al vector previous stored in this database.
-    pub fn item_vector(&self, rtxn: &RoTxn, item: ItemId) -> Result<Option<Vec<f32>>> {
-        Ok(get_item(self.database, self.index, rtxn, item)?.map(|item| {
-            let mut vec = item.vector.to_vec();
-            vec.truncate(self.dimensions);
-            vec
-        }))
+    /// Records the instant an item was created or last refreshed at, replacing the previous one,
+    /// for the searches to favor the fresher items, see [`RecencyQuery`](crate::RecencyQuery).
+    pub fn set_timestamp(&self, wtxn: &mut RwTxn, item: ItemId, at: SystemTime) -> Result<()> {
+        self.check_lock(wtxn)?;
+        let key = Key::timestamp(self.index, item);
+        self.database
+            .remap_data_type::<U64<BigEndian>>()
+            .put(wtxn, &key, &unix_millis(at))
+            .map_err(Error::on_key("put", key))
     }
 
-    /// Returns `true` if the database contains the given item.
-    pub fn contains_item(&self, rtxn: &RoTxn, item: ItemId) -> Result<bool> {
+    /// Removes the timestamp of an item and returns `true` if it had one.
+    pub fn clear_timestamp(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<bool> {
+        self.check_lock(wtxn)?;
+        let key = Key::timestamp(self.index, item);
+        self.database.delete(wtxn, &key).map_err(Error::on_key("delete", key))
+    }
+
+    /// Returns the instant an item was created or last refreshed at, if it was given one.
+    pub fn timestamp(&self, rtxn: &RoTxn, item: ItemId) -> Result<Option<SystemTime>> {
+        get_timestamp(rtxn, self.database, self.index, item)
+    }
+
+    /// Attaches opaque bytes to an item, replacing its previous payload, for the searches to
+    /// hand them to their rescoring, see [`QueryBuilder::rescore`](crate::QueryBuilder::rescore).
+    pub fn set_payload(&self, wtxn: &mut RwTxn, item: ItemId, payload: &[u8]) -> Result<()> {
+        self.check_lock(wtxn)?;
+        let key = Key::payload(self.index, item);
         self.database
-            .remap_data_type::<DecodeIgnore>()
-            .get(rtxn, &Key::item(self.index, item))
-            .map(|opt| opt.is_some())
-            .map_err(Into::into)
+            .remap_data_type::<Bytes>()
+            .put(wtxn, &key, payload)
+            .map_err(Error::on_key("put", key))
     }
 
-    /// Returns an iterator over the items vector.
-    pub fn iter<'t>(&self, rtxn: &'t RoTxn) -> Result<ItemIter<'t, D>> {
-        Ok(ItemIter::new(self.database, self.index, self.dimensions, rtxn)?)
+    /// Removes the payload of an item and returns `true` if it had one.
+    pub fn clear_payload(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<bool> {
+        self.check_lock(wtxn)?;
+        let key = Key::payload(self.index, item);
+        self.database.delete(wtxn, &key).map_err(Error::on_key("delete", key))
     }
 
-    /// Add an item associated to a vector in the database.
-    pub fn add_item(&self, wtxn: &mut RwTxn, item: ItemId, vector: &[f32]) -> Result<()> {
-        if vector.len() != self.dimensions {
-            return Err(Error::InvalidVecDimension {
-                expected: self.dimensions,
-                received: vector.len(),
-            });
+    /// Returns the payload attached to an item, if it was given one.
+    pub fn payload<'t>(&self, rtxn: &'t RoTxn, item: ItemId) -> Result<Option<&'t [u8]>> {
+        get_payload(rtxn, self.database, self.index, item)
+    }
+
+    /// Boosts the final score of an item by a factor, replacing its previous boost, see
+    /// [`QueryBuilder::boosted`](crate::QueryBuilder::boosted). The boost must be positive and
+    /// finite, the items that weren't given one have a boost of `1.0`.
+    ///
+    /// The boosts are static priors, like the popularity or the quality of the items, they
+    /// don't change the graph and don't need a build.
+    pub fn set_boost(&self, wtxn: &mut RwTxn, item: ItemId, boost: f32) -> Result<()> {
+        self.check_lock(wtxn)?;
+        if !(boost.is_finite() && boost > 0.0) {
+            return Err(Error::InvalidBoost { item, boost });
         }
+        let key = Key::boost(self.index, item);
+        self.database
+            .remap_data_type::<U32<BigEndian>>()
+            .put(wtxn, &key, &boost.to_bits())
+            .map_err(Error::on_key("put", key))
+    }
 
-        let vector = UnalignedVector::from_slice(vector);
-        let db_item = Item { header: D::new_header(&vector), vector };
-        self.database.put(wtxn, &Key::item(self.index, item), &Node::Item(db_item))?;
-        self.database.remap_data_type::<Unit>().put(wtxn, &Key::updated(self.index, item), &())?;
+    /// Removes the boost of an item and returns `true` if it had one.
+    pub fn clear_boost(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<bool> {
+        self.check_lock(wtxn)?;
+        let key = Key::boost(self.index, item);
+        self.database.delete(wtxn, &key).map_err(Error::on_key("delete", key))
+    }
 
-        Ok(())
+    /// Returns the boost of an item, if it was given one.
+    pub fn boost(&self, rtxn: &RoTxn, item: ItemId) -> Result<Option<f32>> {
+        get_boost(rtxn, self.database, self.index, item)
+    }
+
+    /// Returns the items that expired at `now`.
+    pub fn expired_items(&self, rtxn: &RoTxn, now: SystemTime) -> Result<RoaringBitmap> {
+        let now = unix_millis(now);
+        let prefix = Prefix::expiration(self.index);
+        let mut expired = RoaringBitmap::new();
+        for result in self
+            .database
+            .remap_types::<PrefixCodec, U64<BigEndian>>()
+            .prefix_iter(rtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
+            .remap_key_type::<KeyCodec>()
+        {
+            let (key, at) = result?;
+            if at <= now {
+                expired.insert(key.node.item);
+            }
+        }
+        Ok(expired)
     }
 
     /// Deletes an item stored in this database and returns `true` if it existed.
+    ///
+    /// The item is only tombstoned when [`Self::set_soft_deletes`] is enabled.
     pub fn del_item(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<bool> {
-        if self.database.delete(wtxn, &Key::item(self.index, item))? {
-            self.database.remap_data_type::<Unit>().put(
-                wtxn,
-                &Key::updated(self.index, item),
-                &(),
-            )?;
+        self.check_lock(wtxn)?;
+        if !self.contains_item(wtxn, item)? {
+            return Ok(false);
+        }
+        let change_log = change_log::is_enabled(wtxn, self.database, self.index)?;
+        #[cfg(feature = "versioning")]
+        if change_log && self.versions > 0 {
+            versioning::record(wtxn, self.database, self.index, item, self.versions)?;
+        }
+        self.database.delete(wtxn, &Key::expiration(self.index, item))?;
+        self.database.delete(wtxn, &Key::timestamp(self.index, item))?;
+        self.database.delete(wtxn, &Key::payload(self.index, item))?;
+        self.database.delete(wtxn, &Key::boost(self.index, item))?;
+        self.database.delete(wtxn, &Key::full_vector(self.index, item))?;
+        if change_log {
+            change_log::record(wtxn, self.database, self.index, item, ChangeKind::Deleted)?;
+        }
 
-            Ok(true)
+        if self.soft_deletes {
+            let key = Key::tombstone(self.index, item);
+            self.database.remap_data_type::<Unit>().put(wtxn, &key, &())?;
         } else {
-            Ok(false)
+            self.remove_item(wtxn, item)?;
+        }
+        self.hooks.call(|hooks| hooks.on_delete(self.index, item));
+        Ok(true)
+    }
+
+    /// Deletes the `items` stored in this database and returns the number of them that existed,
+    /// e.g. to purge millions of items at once.
+    ///
+    /// The items are found and deleted in a single pass over their keys, the change log and the
+    /// [`WriterHooks::on_delete_items`] are updated once for all of them, and the next build
+    /// repairs their neighbourhoods in a single pass over the graph. The items are deleted one
+    /// by one when their previous vectors are kept, see `Self::set_versions`.
+    pub fn del_items(&self, wtxn: &mut RwTxn, items: &RoaringBitmap) -> Result<u64> {
+        self.check_lock(wtxn)?;
+        let (Some(first), Some(last)) = (items.min(), items.max()) else { return Ok(0) };
+        let change_log = change_log::is_enabled(wtxn, self.database, self.index)?;
+        #[cfg(feature = "versioning")]
+        if change_log && self.versions > 0 {
+            let mut deleted = 0;
+            for item in items {
+                deleted += self.del_item(wtxn, item)? as u64;
+            }
+            return Ok(deleted);
+        }
+
+        let tombstoned = tombstones(wtxn, self.database, self.index)?;
+        let mut deleted = RoaringBitmap::new();
+        let range = Key::item(self.index, first)..=Key::item(self.index, last);
+        let mut cursor = self
+            .database
+            .remap_types::<KeyCodec, DecodeIgnore>()
+            .range_mut(wtxn, &range)
+            .map_err(Error::on_range("iterate", Prefix::item(self.index)))?;
+        while let Some((key, ())) = cursor.next().transpose()? {
+            let item = key.node.item;
+            if !items.contains(item) || tombstoned.contains(item) {
+                continue;
+            }
+            deleted.push(item);
+            if !self.soft_deletes {
+                // SAFETY: Safe because we don't keep any references to the entry
+                unsafe { cursor.del_current() }?;
+            }
+        }
+        drop(cursor);
+        if deleted.is_empty() {
+            return Ok(0);
+        }
+
+        let marks = self.database.remap_data_type::<Unit>();
+        for item in &deleted {
+            self.database.delete(wtxn, &Key::expiration(self.index, item))?;
+            self.database.delete(wtxn, &Key::timestamp(self.index, item))?;
+            self.database.delete(wtxn, &Key::payload(self.index, item))?;
+            self.database.delete(wtxn, &Key::boost(self.index, item))?;
+            self.database.delete(wtxn, &Key::full_vector(self.index, item))?;
+            match self.soft_deletes {
+                true => marks.put(wtxn, &Key::tombstone(self.index, item), &())?,
+                false => marks.put(wtxn, &Key::updated(self.index, item), &())?,
+            }
+        }
+        if change_log {
+            let changes = deleted.iter().map(|item| (item, ChangeKind::Deleted));
+            change_log::record_all(wtxn, self.database, self.index, changes)?;
+        }
+        self.hooks.call(|hooks| hooks.on_delete_items(self.index, &deleted));
+        debug!("deleted {} items", deleted.len());
+        Ok(deleted.len())
+    }
+
+    /// Removes an item from the database and marks it for the next build to repair the graph.
+    fn remove_item(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<()> {
+        self.database.delete(wtxn, &Key::item(self.index, item))?;
+        self.database.delete(wtxn, &Key::tombstone(self.index, item))?;
+        self.database.remap_data_type::<Unit>().put(wtxn, &Key::updated(self.index, item), &())?;
+        Ok(())
+    }
+
+    /// Deletes an item stored in this database and returns the number of graph edges
+    /// pointing to it from the other items, or `None` if the item did not exist.
+    ///
+    /// Those are the edges that the next build will have to repair. They are found from the
+    /// links of the item itself, on every layer it's on, as its neighbours that link back to it:
+    /// a neighbour that pruned the item out of its own links has no edge to repair. An item that
+    /// was added but never built has no edges and returns `Some(0)`, like every item deleted
+    /// while soft deletes are enabled, see [`Self::set_soft_deletes`], which leaves the edges in
+    /// the graph until the index is vacuumed.
+    pub fn del_item_counting_edges(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<Option<u64>> {
+        if self.soft_deletes {
+            return Ok(self.del_item(wtxn, item)?.then_some(0));
+        }
+
+        let start = Key::links(self.index, item, 0);
+        let end = Key::links(self.index, item, LayerId::MAX);
+        let mut neighbours = Vec::new();
+        for result in self
+            .database
+            .range(wtxn, &(start..=end))
+            .map_err(Error::on_range("iterate", Prefix::links(self.index)))?
+        {
+            match result? {
+                (key, Node::Links(Links { links })) => {
+                    neighbours.extend(links.iter().map(|n| (n, key.node.layer)))
+                }
+                (key, Node::Item(_)) => return Err(Error::unexpected_node(key, "Links")),
+            }
         }
+
+        let mut edges = 0;
+        for (n, layer) in neighbours {
+            let links = get_links(wtxn, self.database, self.index, n, layer as usize)?;
+            if n != item && links.is_some_and(|Links { links }| links.contains(item)) {
+                edges += 1;
+            }
+        }
+
+        Ok(self.del_item(wtxn, item)?.then_some(edges))
     }
 
     /// Removes everything in the database, user items and internal graph links.
     pub fn clear(&self, wtxn: &mut RwTxn) -> Result<()> {
+        self.check_lock(wtxn)?;
         let mut cursor = self
             .database
             .remap_key_type::<PrefixCodec>()
@@ -444,6 +2352,137 @@ impl<D: Distance> Writer<D> {
         Ok(())
     }
 
+    /// Copies this built index into the `target` index with its items relabeled so that the
+    /// neighbours of the graph get close ids, which keeps them close in the LMDB pages and makes
+    /// the searches read fewer pages from a cold cache.
+    ///
+    /// The ids are given in the breadth-first order of the graph from its entry points and the
+    /// mapping between them and the ids of the items is stored with the copy, the [`Reader`]s
+    /// of the `target` index keep taking and returning the ids of the items. Everything in the
+    /// `target` index is replaced.
+    ///
+    /// The copy is meant to be served, not modified: the changes go to this index, which is
+    /// built and reordered into the `target` again.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, Writer, distances::Euclidean};
+    /// # let (writer, database, mut wtxn): (Writer<Euclidean>, hannoy::Database<Euclidean>, heed::RwTxn) = todo!();
+    /// writer.reorder_into(&mut wtxn, 1)?;
+    /// let reader = Reader::open(&wtxn, 1, database)?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn reorder_into(&self, wtxn: &mut RwTxn, target: u16) -> Result<()> {
+        self.check_lock(wtxn)?;
+        check_lock(wtxn, self.database, target, None)?;
+        reorder::reorder_into(wtxn, self.database, self.index, target)
+    }
+
+    /// Copies this built index into a free index of the database as its snapshot `name` and
+    /// returns the index of the copy, for the experiments to pin a version of the dataset while
+    /// this index keeps being updated, see [`Reader::open_snapshot`].
+    ///
+    /// The snapshot is a compacting copy of the graph, the items and the settings of the index,
+    /// without its change log, written in the transaction: it takes as much space as the index
+    /// and is never modified by the changes of the index. Fails with [`Error::NeedBuild`] if the
+    /// index has changes that weren't built and with [`Error::InvalidSnapshot`] if the name is
+    /// already taken.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Reader, Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// let mut wtxn = env.write_txn()?;
+    /// writer.snapshot(&mut wtxn, "2024-06-eval")?;
+    /// wtxn.commit()?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn snapshot(&self, wtxn: &mut RwTxn, name: &str) -> Result<u16> {
+        self.check_lock(wtxn)?;
+        snapshot::snapshot(wtxn, self.database, self.index, name)
+    }
+
+    /// Returns the name and the index of the snapshots of this index, in the order they were
+    /// taken, see [`Self::snapshot`].
+    pub fn snapshots(&self, rtxn: &RoTxn) -> Result<Vec<(String, u16)>> {
+        snapshot::get_snapshots(rtxn, self.database, self.index)
+    }
+
+    /// Removes the snapshot `name` of this index and its copy, and returns `false` if there was
+    /// no snapshot of this name.
+    pub fn delete_snapshot(&self, wtxn: &mut RwTxn, name: &str) -> Result<bool> {
+        self.check_lock(wtxn)?;
+        snapshot::delete_snapshot(wtxn, self.database, self.index, name)
+    }
+
+    /// Relabels the items of this built index with the dense ids `0..n`, keeping their order,
+    /// and returns the `(previous, new)` id of every item. After a long period of churn the ids
+    /// are sparse, dense ids make the bitmaps of the links and of the items smaller.
+    ///
+    /// The items keep their vectors, links, payloads, boosts, timestamps and expirations. The
+    /// applications must relabel the ids they store with the returned mapping, e.g. the ones of
+    /// the [`Attributes`](crate::Attributes). The ids of a [`MultiVector`](crate::MultiVector)
+    /// layout keep the item in their high bits and its vectors in the low ones, which the dense
+    /// ids don't, the indexes of such a layout must not be compacted. The indexes that were
+    /// reordered, see [`Self::reorder_into`], or whose change log isn't empty can't be compacted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// let mut wtxn = env.write_txn()?;
+    /// for (previous, new) in writer.compact(&mut wtxn)? {
+    ///     println!("item {previous} is now {new}");
+    /// }
+    /// wtxn.commit()?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn compact(&self, wtxn: &mut RwTxn) -> Result<Vec<(ItemId, ItemId)>> {
+        self.check_lock(wtxn)?;
+        compaction::compact(wtxn, self.database, self.index)
+    }
+
+    /// Starts moving the items of this index to the `successor` index, whose vectors have other
+    /// `dimensions`, e.g. after changing of embedding model. Everything in the `successor` index
+    /// is replaced.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// # let embed = |item: u32| -> Vec<f32> { todo!() };
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut wtxn = env.write_txn()?;
+    /// let migration = writer.migrate(&mut wtxn, 1, 1024)?;
+    /// for item in migration.missing(&wtxn)? {
+    ///     migration.writer().add_item(&mut wtxn, item, &embed(item))?;
+    /// }
+    /// let mut rng = StdRng::seed_from_u64(4729);
+    /// migration.writer().builder(&mut rng).build::<16, 32>(&mut wtxn)?;
+    /// let writer = migration.finish(&mut wtxn)?;
+    /// wtxn.commit()?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn migrate(
+        &self,
+        wtxn: &mut RwTxn,
+        successor: u16,
+        dimensions: usize,
+    ) -> Result<Migration<D>> {
+        if successor == self.index {
+            return Err(Error::MigrateInPlace(successor));
+        }
+        self.check_lock(wtxn)?;
+        check_lock(wtxn, self.database, successor, None)?;
+        Migration::start(wtxn, self.database, self.index, successor, dimensions)
+    }
+
     /// Returns an [`HannoyBuilder`] to configure the available options to build the database.
     pub fn builder<'a, R>(&'a self, rng: &'a mut R) -> HannoyBuilder<'a, D, R, NoProgress>
     where
@@ -452,6 +2491,7 @@ impl<D: Distance> Writer<D> {
         HannoyBuilder { writer: self, rng, inner: BuildOption::default() }
     }
 
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(name = "build", skip_all, fields(index = self.index, dimensions = self.dimensions, m = M, m0 = M0)))]
     fn build<R, P, const M: usize, const M0: usize>(
         &self,
         wtxn: &mut RwTxn,
@@ -462,45 +2502,88 @@ impl<D: Distance> Writer<D> {
         R: Rng + SeedableRng,
         P: steppe::Progress,
     {
+        self.check_lock(wtxn)?;
+        self.check_dimensions(&self.settings(wtxn)?)?;
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+
         let item_indices = self.item_indices(wtxn, options)?;
         // updated items can be an update, an addition or a removed item
-        let updated_items = self.reset_and_retrieve_updated_items(wtxn, options)?;
+        let (updated_items, deferred) =
+            self.reset_and_retrieve_updated_items(wtxn, &item_indices, options)?;
 
         let to_delete = updated_items.clone() - &item_indices;
         let to_insert = &item_indices & &updated_items;
+        #[cfg(feature = "metrics")]
+        let (indexed, removed) = (to_insert.len(), to_delete.len());
 
         let metadata = self
             .database
             .remap_data_type::<MetadataCodec>()
             .get(wtxn, &Key::metadata(self.index))?;
 
+        self.hooks.call(|hooks| hooks.on_build_start(self.index, &to_insert, &to_delete));
+
         let (entry_points, max_level) = metadata.as_ref().map_or_else(
             || (Vec::new(), usize::MIN),
             |metadata| (metadata.entry_points.iter().collect(), metadata.max_level as usize),
         );
+        // items that were already part of the previous graph and got a new vector
+        let replaced = metadata.as_ref().map_or_else(RoaringBitmap::new, |m| &m.items & &to_insert);
+        // the deferred additions are left out of the graph until a later build
+        let unbuilt = metadata.as_ref().map_or_else(|| deferred.clone(), |m| &deferred - &m.items);
 
         // we should not keep a reference to the metadata since they're going to be moved by LMDB
         drop(metadata);
 
+        let old_entry_points = entry_points.clone();
+        // the items linked to the changed ones in the previous graph
+        let mut region = RoaringBitmap::new();
+        if options.relink_regions {
+            for item in &updated_items {
+                if let Some(Links { links }) = get_links(wtxn, self.database, self.index, item, 0)?
+                {
+                    region |= links.into_owned();
+                }
+            }
+            region -= &updated_items;
+            debug!("relinking {} neighbours of the changed items", region.len());
+        }
+
         let mut hnsw = HnswBuilder::<D, M, M0>::new(options)
+            .with_relinked_items(region)
             .with_entry_points(entry_points)
-            .with_max_level(max_level);
-
-        let stats =
-            hnsw.build(to_insert, &to_delete, self.database, self.index, wtxn, rng, options)?;
+            .with_max_level(max_level)
+            .with_replaced_items(replaced)
+            .with_reserved_items(self.reserved);
+
+        let stats = hnsw.build(
+            to_insert.clone(),
+            &to_delete,
+            self.database,
+            self.index,
+            wtxn,
+            rng,
+            options,
+        )?;
         info!("{stats:?}");
 
         // Remove deleted links from lmdb AFTER build; in DiskANN we use a deleted item's
         // neighbours when filling in the "gaps" left in the graph from deletions. See
         // [`HnswBuilder::maybe_patch_old_links`] for more details.
-        self.delete_links_from_db(to_delete, wtxn)?;
+        self.delete_links_from_db(&to_delete, wtxn)?;
+        // the ef_search tuned on the previous graph doesn't hold for the new one
+        if !updated_items.is_empty() {
+            let key = Key::tuning(self.index);
+            self.database.delete(wtxn, &key).map_err(Error::on_key("delete", key))?;
+        }
 
         debug!("write the metadata...");
         options.progress.update(HannoyBuild::WriteTheMetadata);
 
         let metadata = Metadata {
             dimensions: self.dimensions.try_into().unwrap(),
-            items: item_indices,
+            items: item_indices - unbuilt,
             entry_points: ItemIds::from_slice(&hnsw.entry_points),
             max_level: hnsw.max_level as u8,
             distance: D::name(),
@@ -515,15 +2598,40 @@ impl<D: Distance> Writer<D> {
             &Key::version(self.index),
             &Version::current(),
         )?;
-
+        version::put_format(wtxn, self.database, self.index)?;
+        // the ids of the write transactions increase with every commit of the environment, even
+        // once the index was cleared
+        let build = wtxn.id() as u64;
+        let info = BuildInfo {
+            m: M,
+            m0: M0,
+            ef_construction: options.ef_construction,
+            alpha: options.alpha,
+            built_at: SystemTime::now(),
+        };
+        info::put_build(wtxn, self.database, self.index, build, Some(&info))?;
+        codes::write(wtxn, self.database, self.index, Some(&updated_items))?;
+        density::write(wtxn, self.database, self.index, options.densities)?;
+        lsh::write(wtxn, self.database, self.index, options.lsh_bits, Some(&updated_items))?;
+
+        #[cfg(feature = "metrics")]
+        crate::monitoring::record_build::<D>(self.index, indexed, removed, started.elapsed());
+        if old_entry_points != hnsw.entry_points {
+            self.hooks.call(|hooks| {
+                hooks.on_entry_points_change(self.index, &old_entry_points, &hnsw.entry_points)
+            });
+        }
+        self.hooks.call(|hooks| hooks.on_build_end(self.index, &to_insert, &to_delete));
         Ok(())
     }
 
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all, fields(updated = tracing::field::Empty)))]
     fn reset_and_retrieve_updated_items<P>(
         &self,
         wtxn: &mut RwTxn,
+        item_indices: &RoaringBitmap,
         options: &BuildOption<P>,
-    ) -> Result<RoaringBitmap, Error>
+    ) -> Result<(RoaringBitmap, RoaringBitmap), Error>
     where
         P: steppe::Progress,
     {
@@ -531,10 +2639,15 @@ impl<D: Distance> Writer<D> {
         options.progress.update(HannoyBuild::RetrieveTheUpdatedItems);
 
         let mut updated_items = RoaringBitmap::new();
+        // the insertions left for the next builds, the deletions are never deferred
+        let mut deferred = RoaringBitmap::new();
+        let mut insertions = 0;
+        let prefix = Prefix::updated(self.index);
         let mut updated_iter = self
             .database
             .remap_types::<PrefixCodec, DecodeIgnore>()
-            .prefix_iter_mut(wtxn, &Prefix::updated(self.index))?
+            .prefix_iter_mut(wtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
             .remap_key_type::<KeyCodec>();
 
         let mut index = 0;
@@ -543,17 +2656,29 @@ impl<D: Distance> Writer<D> {
                 return Err(Error::BuildCancelled);
             }
 
-            let inserted = updated_items.insert(key.node.item);
+            index += 1;
+            let item = key.node.item;
+            if item_indices.contains(item) {
+                if options.chunk.is_some_and(|chunk| insertions == chunk) {
+                    deferred.insert(item);
+                    continue;
+                }
+                insertions += 1;
+            }
+
+            let inserted = updated_items.insert(item);
             debug_assert!(inserted, "The keys should be sorted by LMDB");
             // SAFETY: Safe because we don't hold any reference to the database currently
-            unsafe { updated_iter.del_current()? };
-
-            index += 1;
+            unsafe { updated_iter.del_current() }.map_err(Error::on_key("delete", key))?;
         }
-        Ok(updated_items)
+
+        #[cfg(feature = "tracing-spans")]
+        tracing::Span::current().record("updated", updated_items.len());
+        Ok((updated_items, deferred))
     }
 
     // Fetches the item's ids, not the links.
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all))]
     fn item_indices<P>(&self, wtxn: &mut RwTxn, options: &BuildOption<P>) -> Result<RoaringBitmap>
     where
         P: steppe::Progress,
@@ -561,11 +2686,13 @@ impl<D: Distance> Writer<D> {
         debug!("started retrieving all the items ids...");
         options.progress.update(HannoyBuild::RetrievingTheItemsIds);
 
+        let prefix = Prefix::item(options.vectors.unwrap_or(self.index));
         let mut indices = RoaringBitmap::new();
         for (index, result) in self
             .database
             .remap_types::<PrefixCodec, DecodeIgnore>()
-            .prefix_iter(wtxn, &Prefix::item(self.index))?
+            .prefix_iter(wtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
             .remap_key_type::<KeyCodec>()
             .enumerate()
         {
@@ -582,17 +2709,20 @@ impl<D: Distance> Writer<D> {
 
     // Iterates over links in lmdb and deletes those in `to_delete`. There can be several links
     // with the same NodeId.item, each differing by their layer
-    fn delete_links_from_db(&self, to_delete: RoaringBitmap, wtxn: &mut RwTxn) -> Result<()> {
+    #[cfg_attr(feature = "tracing-spans", tracing::instrument(level = "debug", skip_all, fields(deleted = to_delete.len())))]
+    fn delete_links_from_db(&self, to_delete: &RoaringBitmap, wtxn: &mut RwTxn) -> Result<()> {
+        let prefix = Prefix::links(self.index);
         let mut cursor = self
             .database
             .remap_key_type::<PrefixCodec>()
-            .prefix_iter_mut(wtxn, &Prefix::links(self.index))?
+            .prefix_iter_mut(wtxn, &prefix)
+            .map_err(Error::on_range("iterate", prefix))?
             .remap_types::<KeyCodec, DecodeIgnore>();
 
         while let Some((key, _)) = cursor.next().transpose()? {
             if to_delete.contains(key.node.item) {
                 // SAFETY: Safe because we don't keep any references to the entry
-                unsafe { cursor.del_current() }?;
+                unsafe { cursor.del_current() }.map_err(Error::on_key("delete", key))?;
             }
         }
 
@@ -600,6 +2730,158 @@ impl<D: Distance> Writer<D> {
     }
 }
 
+impl Writer<WeightedEuclidean> {
+    /// Gives every dimension of the index an importance in the distances, see
+    /// [`WeightedEuclidean`].
+    ///
+    /// The weights apply to the vectors as they are added, they must be set while the index
+    /// is empty and can't be changed afterward. They must be finite and non-negative, a zero
+    /// weight ignores its dimension.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::WeightedEuclidean};
+    /// # let (writer, mut wtxn): (Writer<WeightedEuclidean>, heed::RwTxn) = todo!();
+    /// writer.set_weights(&mut wtxn, &[1.0, 0.5, 2.0])?;
+    /// writer.add_item(&mut wtxn, 0, &[0.3, 1.2, 0.8])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn set_weights(&self, wtxn: &mut RwTxn, weights: &[f32]) -> Result<()> {
+        self.check_lock(wtxn)?;
+        if weights.len() != self.dimensions {
+            return Err(Error::InvalidDimensions { expected: self.dimensions, got: weights.len() });
+        }
+        if let Some(weight) = weights.iter().find(|weight| !weight.is_finite() || **weight < 0.0) {
+            let reason = format!("{weight} is not a finite and non-negative weight");
+            return Err(Error::InvalidWeights { reason });
+        }
+        if !self.is_empty(wtxn)? {
+            let reason = "the weights must be set before adding the items".to_string();
+            return Err(Error::InvalidWeights { reason });
+        }
+        weights::put_weights(wtxn, self.database, self.index, weights)
+    }
+
+    /// Returns the weights of the dimensions of the index, if they were set.
+    pub fn weights(&self, rtxn: &RoTxn) -> Result<Option<Vec<f32>>> {
+        weights::get_weights(rtxn, self.database, self.index)
+    }
+}
+
+impl<D: Distance<VectorCodec = u8>> Writer<D> {
+    /// Add an item associated to a vector of bytes in the database, stored as is at one byte
+    /// per dimension. See [`Self::add_item`] to convert a vector of f32.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::U8Manhattan};
+    /// # let (writer, mut wtxn): (Writer<U8Manhattan>, heed::RwTxn) = todo!();
+    /// writer.add_u8_item(&mut wtxn, 0, &[12, 255, 0, 87])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn add_u8_item(&self, wtxn: &mut RwTxn, item: ItemId, vector: &[u8]) -> Result<()> {
+        if vector.len() != self.dimensions {
+            return Err(Error::InvalidDimensions { expected: self.dimensions, got: vector.len() });
+        }
+
+        let vector = Cow::Borrowed(UnalignedVector::from_u8_slice(vector));
+        let db_item = Item { header: D::new_header(&vector), vector };
+        let inserts = self.start_inserts(wtxn)?;
+        self.put_item(wtxn, &inserts, item, db_item)?;
+        self.database.remap_data_type::<Unit>().put(wtxn, &Key::updated(self.index, item), &())?;
+
+        Ok(())
+    }
+}
+
+impl<D: Distance<VectorCodec = Sparse>> Writer<D> {
+    /// Add an item associated to a sparse vector in the database, given by the `indices` of its
+    /// non-zero values sorted in increasing order and the `values` themselves.
+    ///
+    /// The indices must be lower than the dimensions of the writer, which is the size of the
+    /// vocabulary for SPLADE-like embeddings. See [`Self::add_item`] for a dense vector.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use hannoy::{Writer, distances::SparseDotProduct};
+    /// # let (writer, mut wtxn): (Writer<SparseDotProduct>, heed::RwTxn) = todo!();
+    /// writer.add_sparse_item(&mut wtxn, 0, &[3, 1207, 29_871], &[0.8, 1.3, 0.2])?;
+    /// # Ok::<(), hannoy::Error>(())
+    /// ```
+    pub fn add_sparse_item(
+        &self,
+        wtxn: &mut RwTxn,
+        item: ItemId,
+        indices: &[u32],
+        values: &[f32],
+    ) -> Result<()> {
+        check_sparse_vector(item, self.dimensions, indices, values, self.allow_non_finite)?;
+
+        let vector = Sparse::from_entries(self.dimensions, indices, values);
+        let db_item = Item { header: D::new_header(&vector), vector };
+        let inserts = self.start_inserts(wtxn)?;
+        self.put_item(wtxn, &inserts, item, db_item)?;
+        self.database.remap_data_type::<Unit>().put(wtxn, &Key::updated(self.index, item), &())?;
+
+        Ok(())
+    }
+}
+
+/// Makes sure the indices of a sparse vector are sorted, unique and within the dimensions, and
+/// that there is a value for each of them.
+fn check_sparse_vector(
+    item: ItemId,
+    dimensions: usize,
+    indices: &[u32],
+    values: &[f32],
+    allow_non_finite: bool,
+) -> Result<()> {
+    Sparse::check_entries(dimensions, indices, values)
+        .map_err(|reason| Error::InvalidSparseVector { item, reason })?;
+    if !allow_non_finite {
+        if let Some(position) = values.iter().position(|x| !x.is_finite()) {
+            let index = indices[position] as usize;
+            return Err(Error::NonFiniteVector { item, position: index, value: values[position] });
+        }
+    }
+    Ok(())
+}
+
+/// Returns the milliseconds elapsed between the Unix epoch and `at`, `0` before the epoch.
+fn unix_millis(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Fails if a writer other than the one holding the `held` token locked the `index`, see
+/// [`Writer::lock`].
+pub(crate) fn check_lock<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    held: Option<u64>,
+) -> Result<()> {
+    let key = Key::write_lock(index);
+    let token = database
+        .remap_data_type::<U64<BigEndian>>()
+        .get(rtxn, &key)
+        .map_err(Error::on_key("get", key))?;
+    match token {
+        Some(token) if Some(token) != held => Err(Error::IndexLocked(index)),
+        _ => Ok(()),
+    }
+}
+
+/// Returns a token that differs between the writers of a process and between the processes.
+fn lock_token() -> u64 {
+    static WRITERS: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+    let writer = WRITERS.fetch_add(1, Ordering::Relaxed);
+    nanos ^ ((std::process::id() as u64) << 40) ^ writer.rotate_left(20)
+}
+
 #[derive(Clone)]
 pub(crate) struct FrozenReader<'a, D: Distance> {
     pub index: u16,
@@ -635,3 +2917,40 @@ fn clear_links<D: Distance>(wtxn: &mut RwTxn, database: Database<D>, index: u16)
 
     Ok(())
 }
+
+/// Removes the pending changes of an index and returns their items.
+fn take_pending<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+) -> Result<RoaringBitmap> {
+    let prefix = Prefix::updated(index);
+    let mut cursor = database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter_mut(wtxn, &prefix)
+        .map_err(Error::on_range("iterate", prefix))?
+        .remap_key_type::<KeyCodec>();
+
+    let mut pending = RoaringBitmap::new();
+    while let Some((key, ())) = cursor.next().transpose()? {
+        pending.insert(key.node.item);
+        // SAFETY: Safe because we don't keep any references to the entry
+        unsafe { cursor.del_current() }.map_err(Error::on_key("delete", key))?;
+    }
+    Ok(pending)
+}
+
+/// Marks the `items` of an index as changed, for its next build to insert or remove them.
+fn mark_updated<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    items: &RoaringBitmap,
+) -> Result<()> {
+    let updated = database.remap_data_type::<Unit>();
+    for item in items {
+        let key = Key::updated(index, item);
+        updated.put(wtxn, &key, &()).map_err(Error::on_key("put", key))?;
+    }
+    Ok(())
+}
@@ -52,3 +52,35 @@ def test_multithreaded_reads(db) -> None:
 
     for t in threads:
         t.join()
+
+
+def test_numpy_bulk_writes_and_searches(tmp_path: Path) -> None:
+    import numpy as np
+
+    db = hannoy.Database(tmp_path, Metric.EUCLIDEAN)
+    vectors = np.eye(4, dtype=np.float32)
+
+    # the LMDB environment is shared by the whole process, use a dedicated index
+    writer = db.writer(4, index=7, m=4, ef=10)
+    writer.add_items([0, 1, 2, 3], vectors)
+    # the rows of a transposed array are read in their logical order
+    writer.add_items([4, 5], 5 * np.eye(4, 2, dtype=np.float32).T)
+    assert writer.del_item(3)
+    assert not writer.del_item(3)
+    writer.build()
+    hannoy.Database.commit_rw_txn()
+
+    reader = db.reader(7)
+    res = reader.by_vecs(vectors[:3], n=1)
+    assert [nns[0][0] for nns in res] == [0, 1, 2]
+    res = reader.by_vecs(5 * vectors[:2], n=1)
+    assert [nns[0][0] for nns in res] == [4, 5]
+
+
+def test_unsupported_links_raise_value_error(tmp_path: Path) -> None:
+    db = hannoy.Database(tmp_path, Metric.EUCLIDEAN)
+
+    writer = db.writer(2, index=8, m=5, ef=10)
+    writer.add_item(0, [1.0, 0.0])
+    with pytest.raises(ValueError, match="Unsupported number of links per node 5"):
+        writer.build()